@@ -0,0 +1,229 @@
+use crate::eval::{MemberGetter, ScriptResult, TypeNameResolver};
+use crate::variant::{
+    Array, FromVariant, FunctionType, FunctionVariant, Map, SharedArray, SharedMap, Variant,
+};
+use immutable_string::ImmutableString;
+use parking_lot::Mutex;
+use std::any::TypeId;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+struct CallTrace {
+    depth: usize,
+    calls: Vec<String>,
+}
+impl CallTrace {
+    //a runaway loop shouldn't fill the memory
+    const MAX_CALLS: usize = 10000;
+}
+
+thread_local! {
+    static TRACE: RefCell<Option<CallTrace>> = RefCell::new(None);
+}
+
+//runs the function and returns every call scripts made on this thread meanwhile, indented by depth
+pub fn trace_calls<R, F: FnOnce() -> R>(function: F) -> (R, Vec<String>) {
+    let previous = TRACE.with(|trace| {
+        trace.replace(Some(CallTrace {
+            depth: 0,
+            calls: Vec::new(),
+        }))
+    });
+    let result = function();
+    let trace = TRACE.with(|trace| trace.replace(previous)).unwrap();
+    (result, trace.calls)
+}
+pub fn is_tracing() -> bool {
+    TRACE.with(|trace| trace.borrow().is_some())
+}
+//the call is only described when tracing, returns whether exit_call has to follow
+pub(crate) fn enter_call<F: FnOnce() -> String>(describe: F) -> bool {
+    if !is_tracing() {
+        return false;
+    }
+    //describing can run member getters, so the trace isn't borrowed meanwhile
+    let call = describe();
+    TRACE.with(|trace| {
+        if let Some(trace) = trace.borrow_mut().as_mut() {
+            if trace.calls.len() < CallTrace::MAX_CALLS {
+                trace
+                    .calls
+                    .push(format!("{}{}", "  ".repeat(trace.depth), call));
+            }
+            trace.depth += 1;
+        }
+    });
+    true
+}
+pub(crate) fn exit_call(result: &ScriptResult) {
+    TRACE.with(|trace| {
+        if let Some(trace) = trace.borrow_mut().as_mut() {
+            trace.depth = trace.depth.saturating_sub(1);
+            if let Err(error) = result {
+                if trace.calls.len() < CallTrace::MAX_CALLS {
+                    trace.calls.push(format!(
+                        "{}failed: {}",
+                        "  ".repeat(trace.depth + 1),
+                        error.root()
+                    ));
+                }
+            }
+        }
+    });
+}
+
+//turns variants into readable text, custom objects are shown with their registered members
+pub struct VariantFormatter {
+    names: TypeNameResolver,
+    members: Arc<Mutex<HashMap<TypeId, Vec<(ImmutableString, MemberGetter)>>>>,
+}
+impl VariantFormatter {
+    //members of members quickly reach the whole world, and arrays can contain themselves
+    const MAX_DEPTH: usize = 4;
+    pub(crate) fn new(
+        names: TypeNameResolver,
+        members: Arc<Mutex<HashMap<TypeId, Vec<(ImmutableString, MemberGetter)>>>>,
+    ) -> Self {
+        VariantFormatter { names, members }
+    }
+    //on one line, custom objects only by their type name
+    pub fn format(&self, value: &Variant) -> String {
+        self.format_value(value, None, 0)
+    }
+    //over multiple lines with the members of custom objects
+    pub fn format_pretty(&self, value: &Variant) -> String {
+        self.format_value(value, Some(0), 0)
+    }
+    fn format_value(&self, value: &Variant, indent: Option<usize>, depth: usize) -> String {
+        if let Some(string) = ImmutableString::from_variant(value) {
+            return format!("{:?}", string.as_ref());
+        }
+        if let Some(value) = i64::from_variant(value) {
+            return value.to_string();
+        }
+        if let Some(value) = f64::from_variant(value) {
+            return value.to_string();
+        }
+        if let Some(value) = bool::from_variant(value) {
+            return value.to_string();
+        }
+        if (*value.0).as_any().type_id() == TypeId::of::<()>() {
+            return "null".to_string();
+        }
+        if let Some(range) = Range::<i64>::from_variant(value) {
+            return format!("{}..{}", range.start, range.end);
+        }
+        if let Some(function) = FunctionVariant::from_variant(value) {
+            return match &function.function {
+                FunctionType::ScriptFunction(function) => {
+                    format!(
+                        "fn {}({})",
+                        function.name,
+                        function.parameter_names.join(", ")
+                    )
+                }
+                FunctionType::RustFunction(_) => "fn(native)".to_string(),
+            };
+        }
+        let type_name = (*value.0).type_name().resolve_name(&self.names);
+        if depth >= Self::MAX_DEPTH {
+            return format!("{} {{...}}", type_name);
+        }
+        if let Some(array) = SharedArray::from_variant(value) {
+            let entries: Vec<_> = array
+                .lock()
+                .iter()
+                .map(|value| (None, value.clone()))
+                .collect();
+            return self.format_entries(&entries, "[", "]", indent, depth);
+        }
+        if let Some(array) = Array::from_variant(value) {
+            let entries: Vec<_> = array.iter().map(|value| (None, value.clone())).collect();
+            return self.format_entries(&entries, "[", "]", indent, depth);
+        }
+        if let Some(map) = SharedMap::from_variant(value) {
+            let entries = Self::sorted(map.lock().iter());
+            return self.format_entries(&entries, "{", "}", indent, depth);
+        }
+        if let Some(map) = Map::from_variant(value) {
+            let entries = Self::sorted(map.iter());
+            return self.format_entries(&entries, "{", "}", indent, depth);
+        }
+        if indent.is_none() {
+            return type_name.to_string();
+        }
+        //the getters are cloned out, so a getter that uses dbg itself doesn't deadlock
+        let getters = self
+            .members
+            .lock()
+            .get(&(*value.0).as_any().type_id())
+            .cloned()
+            .unwrap_or_default();
+        let mut members: Vec<_> = getters
+            .iter()
+            .filter_map(|(name, getter)| {
+                getter(value).map(|member| (Some(name.to_string()), member))
+            })
+            .collect();
+        members.sort_by(|(first, _), (second, _)| first.cmp(second));
+        if members.is_empty() {
+            return type_name.to_string();
+        }
+        format!(
+            "{} {}",
+            type_name,
+            self.format_entries(&members, "{", "}", indent, depth)
+        )
+    }
+    fn sorted<'a, I: Iterator<Item = (&'a ImmutableString, &'a Variant)>>(
+        entries: I,
+    ) -> Vec<(Option<String>, Variant)> {
+        let mut entries: Vec<_> = entries
+            .map(|(key, value)| (Some(key.to_string()), value.clone()))
+            .collect();
+        entries.sort_by(|(first, _), (second, _)| first.cmp(second));
+        entries
+    }
+    fn format_entries(
+        &self,
+        entries: &[(Option<String>, Variant)],
+        open: &str,
+        close: &str,
+        indent: Option<usize>,
+        depth: usize,
+    ) -> String {
+        if entries.is_empty() {
+            return format!("{}{}", open, close);
+        }
+        let entries = entries.iter().map(|(key, value)| {
+            let value = self.format_value(value, indent.map(|indent| indent + 1), depth + 1);
+            match key {
+                Some(key) => format!("{}: {}", key, value),
+                None => value,
+            }
+        });
+        match indent {
+            Some(indent) => {
+                let inner = "    ".repeat(indent + 1);
+                format!(
+                    "{}\n{}\n{}{}",
+                    open,
+                    entries
+                        .map(|entry| format!("{}{},", inner, entry))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    "    ".repeat(indent),
+                    close
+                )
+            }
+            None => format!(
+                "{}{}{}",
+                open,
+                entries.collect::<Vec<_>>().join(", "),
+                close
+            ),
+        }
+    }
+}
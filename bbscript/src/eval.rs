@@ -1,4 +1,5 @@
 use crate::ast::{Expression, Statement, StatementBlock};
+use crate::debug::{self, VariantFormatter};
 use crate::eval::ScriptError::{BreakOutsideLoop, InvalidIterator, MemberNotFound};
 use crate::lex::FilePosition;
 use crate::variant::{
@@ -6,12 +7,47 @@ use crate::variant::{
     Variant,
 };
 use immutable_string::ImmutableString;
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
 use std::any::{Any, TypeId};
+use std::cell::Cell;
 use std::collections::HashMap;
-use std::fmt::{Debug, Formatter};
+use std::fmt::{Debug, Display, Formatter};
 use std::ops::{ControlFlow, FromResidual, Range, Try};
 use std::sync::Arc;
+use std::time::Instant;
+
+thread_local! {
+    static DEADLINE: Cell<Option<Instant>> = Cell::new(None);
+}
+
+//scripts called inside fail once the deadline has passed, it's checked on every function call and loop iteration
+pub fn run_with_deadline<R, F: FnOnce() -> R>(deadline: Instant, function: F) -> R {
+    let previous = DEADLINE.with(|current| {
+        current.replace(Some(
+            current
+                .get()
+                .map_or(deadline, |previous| previous.min(deadline)),
+        ))
+    });
+    let result = function();
+    DEADLINE.with(|current| current.set(previous));
+    result
+}
+fn check_deadline(position: &FilePosition) -> Result<(), ScriptError> {
+    match DEADLINE.with(|deadline| deadline.get()) {
+        Some(deadline) if Instant::now() >= deadline => Err(ScriptError::runtime(
+            "script ran out of time",
+            position.clone(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StackFrame {
+    pub function: ImmutableString,
+    pub position: FilePosition,
+}
 
 #[derive(Debug)]
 pub enum ScriptError {
@@ -45,6 +81,13 @@ pub enum ScriptError {
     InvalidIterator {
         position: FilePosition,
     },
+    //an error that left script functions, innermost function first
+    Traced {
+        error: Box<ScriptError>,
+        stack: Vec<StackFrame>,
+        //where the last function in the stack was called from, the next frame points there
+        call_position: FilePosition,
+    },
 }
 enum ScriptControlFlow {
     Value(Variant),
@@ -78,6 +121,135 @@ impl ScriptError {
             position,
         }
     }
+    //the error itself, without the functions it passed through
+    pub fn root(&self) -> &ScriptError {
+        match self {
+            ScriptError::Traced { error, .. } => error,
+            error => error,
+        }
+    }
+    pub fn stack(&self) -> &[StackFrame] {
+        match self {
+            ScriptError::Traced { stack, .. } => stack,
+            _ => &[],
+        }
+    }
+    pub fn position(&self) -> FilePosition {
+        match self.root() {
+            ScriptError::MismatchedParameterCount { position, .. }
+            | ScriptError::MismatchedType { position, .. }
+            | ScriptError::VariableNotDefined { position, .. }
+            | ScriptError::MemberNotFound { position, .. }
+            | ScriptError::NonFunctionCalled { position }
+            | ScriptError::RuntimeError { position, .. }
+            | ScriptError::InvalidIterator { position } => position.clone(),
+            ScriptError::BreakOutsideLoop | ScriptError::Traced { .. } => FilePosition::INVALID,
+        }
+    }
+    //rust functions mostly fail without a position, the call is the closest thing to blame
+    pub(crate) fn called_at(self, position: &FilePosition) -> Self {
+        match self {
+            ScriptError::Traced { error, stack, .. } => ScriptError::Traced {
+                error,
+                stack,
+                call_position: position.clone(),
+            },
+            mut error => {
+                match &mut error {
+                    ScriptError::MismatchedParameterCount {
+                        position: error_position,
+                        ..
+                    }
+                    | ScriptError::MismatchedType {
+                        position: error_position,
+                        ..
+                    }
+                    | ScriptError::RuntimeError {
+                        position: error_position,
+                        ..
+                    } if error_position.file.is_none() && error_position.line == 0 => {
+                        *error_position = position.clone();
+                    }
+                    _ => {}
+                }
+                error
+            }
+        }
+    }
+    fn in_function(self, function: &ImmutableString) -> Self {
+        let (error, mut stack, position) = match self {
+            ScriptError::Traced {
+                error,
+                stack,
+                call_position,
+            } => (error, stack, call_position),
+            error => {
+                let position = error.position();
+                (Box::new(error), Vec::new(), position)
+            }
+        };
+        stack.push(StackFrame {
+            function: function.clone(),
+            position,
+        });
+        ScriptError::Traced {
+            error,
+            stack,
+            call_position: FilePosition::INVALID,
+        }
+    }
+    fn describe(&self) -> String {
+        match self {
+            ScriptError::MismatchedParameterCount {
+                function_name,
+                expected,
+                got,
+                ..
+            } => format!(
+                "{} takes ({}) but got ({})",
+                function_name,
+                expected.join(", "),
+                got.iter()
+                    .map(|type_name| type_name.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ScriptError::MismatchedType { expected, got, .. } => {
+                format!("expected {} but got {}", expected, got)
+            }
+            ScriptError::VariableNotDefined { variable, .. } => {
+                format!("variable {} is not defined", variable)
+            }
+            ScriptError::BreakOutsideLoop => "break outside of a loop".to_string(),
+            ScriptError::MemberNotFound { member, .. } => format!("member {} not found", member),
+            ScriptError::NonFunctionCalled { .. } => {
+                "called something that isn't a function".to_string()
+            }
+            ScriptError::RuntimeError { error, .. } => error.clone(),
+            ScriptError::InvalidIterator { .. } => "value can't be iterated".to_string(),
+            ScriptError::Traced { error, .. } => error.describe(),
+        }
+    }
+}
+//the message with the position it happened at, followed by every function it passed through
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.describe())?;
+        match self {
+            ScriptError::Traced { stack, .. } => {
+                for frame in stack {
+                    write!(f, "\n    at {} {:?}", frame.function, frame.position)?;
+                }
+            }
+            error => {
+                let position = error.position();
+                if position.file.is_some() || position.line != 0 {
+                    write!(f, " {:?}", position)?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 pub type ScriptResult = Result<Variant, ScriptError>;
 
@@ -147,9 +319,10 @@ impl Function {
         parameters: Vec<Variant>,
         environment: &ExecutionEnvironment,
     ) -> ScriptResult {
+        check_deadline(&FilePosition::INVALID).map_err(|error| error.in_function(&self.name))?;
         let stack = ScopeStack::new();
         let mut stack = parent_stack.unwrap_or(&stack);
-        for (name, global) in &environment.globals {
+        for (name, global) in environment.globals.read().iter() {
             stack.set_variable_top(name.clone(), global.clone());
         }
         if parameters.len() != self.parameter_names.len() {
@@ -173,8 +346,8 @@ impl Function {
         match Function::execute_block(&mut stack, &self.body, environment) {
             ScriptControlFlow::Value(val) => Ok(val),
             ScriptControlFlow::Return(val) => Ok(val),
-            ScriptControlFlow::Break(_) => Err(BreakOutsideLoop),
-            ScriptControlFlow::Err(error) => Err(error),
+            ScriptControlFlow::Break(_) => Err(BreakOutsideLoop.in_function(&self.name)),
+            ScriptControlFlow::Err(error) => Err(error.in_function(&self.name)),
         }
     }
     fn execute_block(
@@ -340,6 +513,9 @@ impl Function {
                         },
                     };
                     for value in array {
+                        if let Err(error) = check_deadline(position) {
+                            return ScriptControlFlow::Err(error);
+                        }
                         stack.set_variable_top(name.clone(), value);
                         match Function::execute_block(&stack, body, environment) {
                             ScriptControlFlow::Break(_) => {
@@ -396,7 +572,7 @@ impl Function {
             Expression::ScopedVariable { name, position } => {
                 let variable = stack
                     .get_variable(name.as_ref())
-                    .or_else(|| environment.globals.get(name).cloned())
+                    .or_else(|| environment.get_global(name))
                     .ok_or(ScriptError::VariableNotDefined {
                         position: position.clone(),
                         variable: name.to_string(),
@@ -408,12 +584,34 @@ impl Function {
                 parameters,
                 position,
             } => {
+                let name = match expression.as_ref() {
+                    Expression::ScopedVariable { name, .. }
+                    | Expression::MemberAccess { name, .. } => name.as_ref(),
+                    _ => "anon",
+                };
                 let expression = Function::eval_expression(stack, expression, environment)?;
                 let parameters = parameters
                     .iter()
                     .map(|parameter| Function::eval_expression(stack, parameter, environment))
                     .collect::<Result<Vec<_>, ScriptError>>()?;
-                Ok(expression.call(parameters, environment, position)?)
+                let traced = debug::enter_call(|| {
+                    let formatter = environment.get_variant_formatter();
+                    format!(
+                        "{}({}) {:?}",
+                        name,
+                        parameters
+                            .iter()
+                            .map(|parameter| formatter.format(parameter))
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        position
+                    )
+                });
+                let result = expression.call(parameters, environment, position);
+                if traced {
+                    debug::exit_call(&result);
+                }
+                result
             }
             Expression::MemberAccess {
                 expression,
@@ -490,15 +688,19 @@ impl Debug for Function {
 }
 pub struct ExecutionEnvironment {
     types: HashMap<TypeId, TypeInfo>,
-    globals: HashMap<ImmutableString, Variant>,
+    //behind a lock so globals can be swapped while scripts are running
+    globals: RwLock<HashMap<ImmutableString, Variant>>,
     custom_names: Arc<Mutex<HashMap<TypeId, ImmutableString>>>,
+    //the members again, shared so dbg can list them while the environment is borrowed
+    inspected_members: Arc<Mutex<HashMap<TypeId, Vec<(ImmutableString, MemberGetter)>>>>,
 }
 impl ExecutionEnvironment {
     pub fn new() -> Self {
         ExecutionEnvironment {
             types: HashMap::new(),
-            globals: HashMap::new(),
+            globals: RwLock::new(HashMap::new()),
             custom_names: Arc::new(Mutex::new(HashMap::new())),
+            inspected_members: Arc::new(Mutex::new(HashMap::new())),
         }
     }
     fn access_member(&self, value: &Variant, name: &ImmutableString) -> Option<Variant> {
@@ -522,17 +724,19 @@ impl ExecutionEnvironment {
         name: N,
         function: F,
     ) {
-        let function = Box::new(function);
+        let name = name.into();
+        let getter: MemberGetter = Arc::new(move |this| {
+            function(T::from_variant(this).unwrap()).map(|r| r.into_variant())
+        });
+        let mut inspected_members = self.inspected_members.lock();
+        let inspected = inspected_members.entry(TypeId::of::<T>()).or_default();
+        inspected.retain(|(member, _)| *member != name);
+        inspected.push((name.clone(), getter.clone()));
         self.types
             .entry(TypeId::of::<T>())
             .or_insert(TypeInfo::new())
             .members
-            .insert(
-                name.into(),
-                Box::new(move |this| {
-                    function(T::from_variant(this).unwrap()).map(|r| r.into_variant())
-                }),
-            );
+            .insert(name, Box::new(move |this| getter(this)));
     }
     pub fn register_method<
         T: Primitive,
@@ -566,10 +770,22 @@ impl ExecutionEnvironment {
             );
     }
     pub fn register_global<N: Into<ImmutableString>>(&mut self, name: N, value: Variant) {
-        self.globals.insert(name.into(), value);
+        self.globals.get_mut().insert(name.into(), value);
     }
-    pub fn get_global(&self, name: &ImmutableString) -> Option<&Variant> {
-        self.globals.get(name)
+    pub fn get_global(&self, name: &ImmutableString) -> Option<Variant> {
+        self.globals.read().get(name).cloned()
+    }
+    //replaces all given globals at once, scripts never see a mix of old and new ones
+    pub fn replace_globals(
+        &self,
+        removed: &[ImmutableString],
+        added: Vec<(ImmutableString, Variant)>,
+    ) {
+        let mut globals = self.globals.write();
+        for name in removed {
+            globals.remove(name);
+        }
+        globals.extend(added);
     }
     pub fn register_function<F: IntoScriptFunction<A>, N: Into<ImmutableString>, A: 'static>(
         &mut self,
@@ -578,7 +794,7 @@ impl ExecutionEnvironment {
     ) {
         let function = function.into_function();
         let function = Arc::new(move |_: Variant, parameters| function(parameters));
-        self.globals.insert(
+        self.globals.get_mut().insert(
             name.into(),
             FunctionVariant {
                 this: Variant::NULL(),
@@ -622,7 +838,14 @@ impl ExecutionEnvironment {
     pub fn get_type_name_resolver(&self) -> TypeNameResolver {
         TypeNameResolver(self.custom_names.clone())
     }
+    pub fn get_variant_formatter(&self) -> VariantFormatter {
+        VariantFormatter::new(
+            self.get_type_name_resolver(),
+            self.inspected_members.clone(),
+        )
+    }
 }
+pub type MemberGetter = Arc<dyn Fn(&Variant) -> Option<Variant> + Send + Sync>;
 pub struct TypeNameResolver(pub Arc<Mutex<HashMap<TypeId, ImmutableString>>>);
 pub struct TypeInfo {
     members: HashMap<ImmutableString, Box<dyn Fn(&Variant) -> Option<Variant> + Send + Sync>>,
@@ -1,6 +1,7 @@
 #![feature(try_trait_v2)]
 
 pub mod ast;
+pub mod debug;
 pub mod environment;
 pub mod eval;
 pub mod lex;
@@ -7,7 +7,7 @@ use immutable_string::ImmutableString;
 use parking_lot::Mutex;
 use std::any::{type_name, Any, TypeId};
 use std::collections::HashMap;
-use std::fmt::{Debug, Formatter};
+use std::fmt::{Debug, Display, Formatter};
 use std::sync::Arc;
 
 #[derive(Debug)]
@@ -25,6 +25,11 @@ impl TypeName {
             .unwrap_or(self.1.into())
     }
 }
+impl Display for TypeName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.1)
+    }
+}
 
 pub trait Primitive: Any + DynClone + Send + Sync {
     #[must_use]
@@ -84,7 +89,8 @@ impl Variant {
                 FunctionType::RustFunction(function) => {
                     function(function_variant.this.clone(), args)
                 }
-            },
+            }
+            .map_err(|error| error.called_at(position)),
             None => Err(ScriptError::NonFunctionCalled {
                 position: position.clone(),
             }),
@@ -0,0 +1,185 @@
+use block_byte_common::gui::{GUIComponent, GUIElement, PositionAnchor};
+use block_byte_common::messages::SoundCategory;
+use block_byte_common::{Color, Position};
+
+use crate::game::ClientPlayer;
+use crate::gui::GUIRenderer;
+use crate::render::GUIVertex;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ColorPalette {
+    Default,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+impl ColorPalette {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(ColorPalette::Default),
+            "protanopia" => Some(ColorPalette::Protanopia),
+            "deuteranopia" => Some(ColorPalette::Deuteranopia),
+            "tritanopia" => Some(ColorPalette::Tritanopia),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorPalette::Default => "default",
+            ColorPalette::Protanopia => "protanopia",
+            ColorPalette::Deuteranopia => "deuteranopia",
+            ColorPalette::Tritanopia => "tritanopia",
+        }
+    }
+    fn simulation_matrix(&self) -> Option<[[f32; 3]; 3]> {
+        match self {
+            ColorPalette::Default => None,
+            ColorPalette::Protanopia => {
+                Some([[0.567, 0.433, 0.], [0.558, 0.442, 0.], [0., 0.242, 0.758]])
+            }
+            ColorPalette::Deuteranopia => {
+                Some([[0.625, 0.375, 0.], [0.7, 0.3, 0.], [0., 0.3, 0.7]])
+            }
+            ColorPalette::Tritanopia => {
+                Some([[0.95, 0.05, 0.], [0., 0.433, 0.567], [0., 0.475, 0.525]])
+            }
+        }
+    }
+    pub fn remap(&self, color: Color) -> Color {
+        let matrix = match self.simulation_matrix() {
+            Some(matrix) => matrix,
+            None => return color,
+        };
+        let original = [color.r as f32, color.g as f32, color.b as f32];
+        let simulated: Vec<f32> = matrix
+            .iter()
+            .map(|row| row[0] * original[0] + row[1] * original[1] + row[2] * original[2])
+            .collect();
+        let error: Vec<f32> = original
+            .iter()
+            .zip(&simulated)
+            .map(|(original, simulated)| original - simulated)
+            .collect();
+        Color {
+            r: color.r,
+            g: (original[1] + 0.7 * error[0] + error[1]).clamp(0., 255.) as u8,
+            b: (original[2] + 0.7 * error[0] + error[2]).clamp(0., 255.) as u8,
+            a: color.a,
+        }
+    }
+    //every color drawn by the gui goes through here, including text, bars and overlays
+    pub fn remap_vertices(&self, vertices: &mut [GUIVertex]) {
+        if *self == ColorPalette::Default {
+            return;
+        }
+        for vertex in vertices {
+            let color = self.remap(Color {
+                r: vertex.color as u8,
+                g: (vertex.color >> 8) as u8,
+                b: (vertex.color >> 16) as u8,
+                a: (vertex.color >> 24) as u8,
+            });
+            vertex.color = (color.r as u32)
+                + ((color.g as u32) << 8)
+                + ((color.b as u32) << 16)
+                + ((color.a as u32) << 24);
+        }
+    }
+    pub fn next(&self) -> Self {
+        match self {
+            ColorPalette::Default => ColorPalette::Protanopia,
+            ColorPalette::Protanopia => ColorPalette::Deuteranopia,
+            ColorPalette::Deuteranopia => ColorPalette::Tritanopia,
+            ColorPalette::Tritanopia => ColorPalette::Default,
+        }
+    }
+    pub fn previous(&self) -> Self {
+        self.next().next().next()
+    }
+}
+
+pub struct SoundCaptions {
+    captions: Vec<(String, f32)>,
+}
+impl SoundCaptions {
+    const DURATION: f32 = 3.;
+    const MAX_CAPTIONS: usize = 5;
+    const ELEMENT_PREFIX: &'static str = "accessibility_caption";
+    pub fn new() -> Self {
+        SoundCaptions {
+            captions: Vec::new(),
+        }
+    }
+    pub fn on_sound(
+        &mut self,
+        category: SoundCategory,
+        position: Position,
+        relative: bool,
+        camera: &ClientPlayer,
+    ) {
+        let name = match category {
+            SoundCategory::Ambient => "ambient noise",
+            SoundCategory::Block => "block noise",
+            SoundCategory::Footsteps => "footsteps",
+            SoundCategory::Creature => "creature noise",
+            SoundCategory::Player => "player noise",
+            SoundCategory::Interface => return,
+        };
+        let caption = if relative {
+            format!("{} nearby", name)
+        } else {
+            let eye = camera.get_eye();
+            let front = camera.make_front();
+            let (x, z) = (position.x - eye.x, position.z - eye.z);
+            let forward = x * front.x as f64 + z * front.z as f64;
+            let side = x * front.z as f64 - z * front.x as f64;
+            let direction = if forward.abs() >= side.abs() {
+                if forward >= 0. {
+                    "in front of you"
+                } else {
+                    "behind you"
+                }
+            } else if side >= 0. {
+                "to your left"
+            } else {
+                "to your right"
+            };
+            format!("{} {}", name, direction)
+        };
+        self.captions.retain(|(text, _)| *text != caption);
+        self.captions.push((caption, Self::DURATION));
+        if self.captions.len() > Self::MAX_CAPTIONS {
+            self.captions.remove(0);
+        }
+    }
+    pub fn clear(&mut self, gui: &mut GUIRenderer) {
+        self.captions.clear();
+        gui.remove_elements(Self::ELEMENT_PREFIX);
+    }
+    pub fn tick(&mut self, delta_time: f32, gui: &mut GUIRenderer) {
+        for caption in &mut self.captions {
+            caption.1 -= delta_time;
+        }
+        self.captions.retain(|(_, remaining)| *remaining > 0.);
+        gui.remove_elements(Self::ELEMENT_PREFIX);
+        for (i, (text, _)) in self.captions.iter().rev().enumerate() {
+            gui.set_element(
+                format!("{}{}", Self::ELEMENT_PREFIX, i),
+                GUIElement {
+                    component_type: GUIComponent::TextComponent {
+                        font_size: 20.,
+                        text: text.clone(),
+                    },
+                    position: Position {
+                        x: -250.,
+                        y: 40. + i as f64 * 25.,
+                        z: 0.,
+                    },
+                    anchor: PositionAnchor::BottomRight,
+                    base_color: Color::WHITE,
+                    size_percent: None,
+                },
+            );
+        }
+    }
+}
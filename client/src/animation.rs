@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use block_byte_common::content::AnimationStateData;
+
+use crate::model::{Model, ModelInstanceData};
+
+pub struct AnimationStates {
+    states: HashMap<String, AnimationStateData>,
+    animations: HashMap<String, u32>,
+}
+impl AnimationStates {
+    //animations used by states are appended to the entity's list so the model resolves them like any other
+    pub fn add_animations(
+        animations: &mut Vec<String>,
+        states: &HashMap<String, AnimationStateData>,
+    ) {
+        for state in states.values() {
+            if !animations.contains(&state.animation) {
+                animations.push(state.animation.clone());
+            }
+        }
+    }
+    pub fn new(states: HashMap<String, AnimationStateData>, animations: &[String]) -> Self {
+        AnimationStates {
+            states,
+            animations: animations
+                .iter()
+                .enumerate()
+                .map(|(index, animation)| (animation.clone(), index as u32))
+                .collect(),
+        }
+    }
+    //states missing from content fall back to the animation with the same name
+    fn get_animation(&self, state: &str) -> Option<u32> {
+        let animation = self
+            .states
+            .get(state)
+            .map(|state| state.animation.as_str())
+            .unwrap_or(state);
+        self.animations.get(animation).cloned()
+    }
+    fn get_blend_time(&self, state: &str) -> f32 {
+        self.states
+            .get(state)
+            .map(|state| state.blend_time)
+            .unwrap_or(0.)
+    }
+}
+
+pub struct AnimationStateMachine {
+    state: String,
+    time: f32,
+    blend: Option<(u32, f32, f32, f32)>,
+}
+impl AnimationStateMachine {
+    pub fn new(state: String) -> Self {
+        AnimationStateMachine {
+            state,
+            time: 0.,
+            blend: None,
+        }
+    }
+    pub fn set_state(&mut self, state: String, states: &AnimationStates) {
+        if self.state != state {
+            let blend_time = states.get_blend_time(state.as_str());
+            self.change_state(state, blend_time, states);
+        }
+    }
+    fn change_state(&mut self, state: String, blend_time: f32, states: &AnimationStates) {
+        self.blend = match states.get_animation(self.state.as_str()) {
+            Some(animation) if blend_time > 0. => Some((animation, self.time, 0., blend_time)),
+            _ => None,
+        };
+        self.state = state;
+        self.time = 0.;
+    }
+    pub fn tick(
+        &mut self,
+        delta_time: f32,
+        movement_speed: f32,
+        states: &AnimationStates,
+        model: &Model,
+        instance: &mut ModelInstanceData,
+    ) {
+        let length = |state: &str| {
+            states
+                .get_animation(state)
+                .and_then(|animation| model.get_animation_length(animation))
+                .unwrap_or(0.)
+        };
+        if let Some(data) = states.states.get(&self.state) {
+            let finished = data.once && self.time >= length(self.state.as_str());
+            if let Some(transition) = data
+                .transitions
+                .iter()
+                .find(|transition| transition.matches(movement_speed, finished))
+            {
+                let blend_time = transition
+                    .blend_time
+                    .unwrap_or(states.get_blend_time(transition.to.as_str()));
+                self.change_state(transition.to.clone(), blend_time, states);
+            }
+        }
+        let (speed, once) = match states.states.get(&self.state) {
+            Some(data) => (
+                data.speed + data.speed_per_velocity * movement_speed,
+                data.once,
+            ),
+            None => (1., false),
+        };
+        let length = length(self.state.as_str());
+        self.time += delta_time * speed;
+        if length > 0. {
+            self.time = if once {
+                self.time.min(length)
+            } else {
+                self.time % length
+            };
+        }
+        self.blend = self.blend.and_then(|(animation, time, elapsed, duration)| {
+            let elapsed = elapsed + delta_time;
+            if elapsed < duration {
+                Some((animation, time, elapsed, duration))
+            } else {
+                None
+            }
+        });
+        instance.animation = states
+            .get_animation(self.state.as_str())
+            .map(|animation| (animation, self.time));
+        instance.blend = self
+            .blend
+            .map(|(animation, time, elapsed, duration)| (animation, time, 1. - elapsed / duration));
+    }
+}
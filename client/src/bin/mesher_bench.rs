@@ -0,0 +1,4 @@
+fn main() {
+    env_logger::init();
+    block_byte_client::mesher_bench::run();
+}
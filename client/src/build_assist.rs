@@ -0,0 +1,180 @@
+use block_byte_common::messages::MAX_PLACE_BATCH;
+use block_byte_common::{BlockPosition, Face, AABB};
+
+use crate::game::RaycastResult;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BuildShape {
+    Single,
+    Line,
+    Plane,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BuildMirror {
+    None,
+    X,
+    Z,
+}
+
+pub struct BuildAssist {
+    reach: Option<f32>,
+    shape: BuildShape,
+    mirror: BuildMirror,
+    anchor: Option<(BlockPosition, Face)>,
+    target: Option<BlockPosition>,
+}
+impl BuildAssist {
+    pub fn new() -> Self {
+        BuildAssist {
+            reach: None,
+            shape: BuildShape::Single,
+            mirror: BuildMirror::None,
+            anchor: None,
+            target: None,
+        }
+    }
+    pub fn set_reach(&mut self, reach: Option<f32>) {
+        self.reach = reach;
+        if reach.is_none() {
+            self.shape = BuildShape::Single;
+            self.mirror = BuildMirror::None;
+            self.anchor = None;
+            self.target = None;
+        }
+    }
+    pub fn get_reach(&self, default: f64) -> f64 {
+        self.reach
+            .map(|reach| (reach as f64).max(default))
+            .unwrap_or(default)
+    }
+    pub fn is_granted(&self) -> bool {
+        self.reach.is_some()
+    }
+    pub fn is_placing(&self) -> bool {
+        self.anchor.is_some()
+    }
+    pub fn cycle_shape(&mut self) {
+        self.shape = match self.shape {
+            BuildShape::Single => BuildShape::Line,
+            BuildShape::Line => BuildShape::Plane,
+            BuildShape::Plane => BuildShape::Single,
+        };
+    }
+    pub fn cycle_mirror(&mut self) {
+        self.mirror = match self.mirror {
+            BuildMirror::None => BuildMirror::X,
+            BuildMirror::X => BuildMirror::Z,
+            BuildMirror::Z => BuildMirror::None,
+        };
+    }
+    pub fn begin(&mut self, position: BlockPosition, face: Face) -> bool {
+        if !self.is_granted()
+            || (self.shape == BuildShape::Single && self.mirror == BuildMirror::None)
+        {
+            return false;
+        }
+        let anchor = position.offset_by_face(face);
+        self.anchor = Some((anchor, face));
+        self.target = Some(anchor);
+        true
+    }
+    pub fn update(&mut self, raycast: &RaycastResult) {
+        if self.anchor.is_none() {
+            return;
+        }
+        if let RaycastResult::Block(position, face) = raycast {
+            self.target = Some(position.offset_by_face(*face));
+        }
+    }
+    pub fn finish(&mut self) -> Option<(Vec<BlockPosition>, Face)> {
+        let positions = self.get_positions();
+        let face = self.anchor.take()?.1;
+        self.target = None;
+        Some((positions, face))
+    }
+    pub fn get_preview(&self) -> Option<AABB> {
+        let positions = self.get_positions();
+        let first = positions.first()?;
+        let (mut min, mut max) = (*first, *first);
+        for position in &positions {
+            min = BlockPosition {
+                x: min.x.min(position.x),
+                y: min.y.min(position.y),
+                z: min.z.min(position.z),
+            };
+            max = BlockPosition {
+                x: max.x.max(position.x),
+                y: max.y.max(position.y),
+                z: max.z.max(position.z),
+            };
+        }
+        Some(AABB {
+            x: min.x as f64,
+            y: min.y as f64,
+            z: min.z as f64,
+            w: (max.x - min.x + 1) as f64,
+            h: (max.y - min.y + 1) as f64,
+            d: (max.z - min.z + 1) as f64,
+        })
+    }
+    fn get_positions(&self) -> Vec<BlockPosition> {
+        let (anchor, face) = match self.anchor {
+            Some(anchor) => anchor,
+            None => return Vec::new(),
+        };
+        let target = self.target.unwrap_or(anchor);
+        let delta = [
+            target.x - anchor.x,
+            target.y - anchor.y,
+            target.z - anchor.z,
+        ];
+        let (min, max) = match self.shape {
+            BuildShape::Single => ([0; 3], [0; 3]),
+            BuildShape::Line => {
+                let axis = (0..3).max_by_key(|axis| delta[*axis].abs()).unwrap();
+                let mut min = [0; 3];
+                let mut max = [0; 3];
+                min[axis] = delta[axis].min(0);
+                max[axis] = delta[axis].max(0);
+                (min, max)
+            }
+            BuildShape::Plane => {
+                let normal = face.get_offset();
+                let normal = [normal.x, normal.y, normal.z];
+                let mut min = [0; 3];
+                let mut max = [0; 3];
+                for axis in 0..3 {
+                    if normal[axis] == 0 {
+                        min[axis] = delta[axis].min(0);
+                        max[axis] = delta[axis].max(0);
+                    }
+                }
+                (min, max)
+            }
+        };
+        let mut positions = Vec::new();
+        'outer: for x in min[0]..=max[0] {
+            for y in min[1]..=max[1] {
+                for z in min[2]..=max[2] {
+                    let position = anchor.add(x, y, z);
+                    let mirrored = match self.mirror {
+                        BuildMirror::None => None,
+                        BuildMirror::X => Some(anchor.add(-x, y, z)),
+                        BuildMirror::Z => Some(anchor.add(x, y, -z)),
+                    };
+                    for position in std::iter::once(position).chain(mirrored) {
+                        if !positions.contains(&position) {
+                            positions.push(position);
+                        }
+                    }
+                    if positions.len() >= MAX_PLACE_BATCH {
+                        break 'outer;
+                    }
+                }
+            }
+        }
+        positions.truncate(MAX_PLACE_BATCH);
+        positions
+    }
+}
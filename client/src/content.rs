@@ -1,13 +1,17 @@
+use crate::animation::AnimationStates;
 use crate::gui::TextRenderer;
 use crate::model::Model;
 use crate::texture::{pack_textures, TextureAtlas};
 use block_byte_common::content::{
-    ClientAnimatedTexture, ClientBlockData, ClientBlockRenderDataType, ClientContent,
-    ClientEntityData, ClientItemData, ClientItemModel, ClientTexture, ModelData, Transformation,
+    BlockTint, ClientAnimatedTexture, ClientBiomeData, ClientBlockData, ClientBlockRenderDataType,
+    ClientContent, ClientEntityData, ClientItemData, ClientItemModel, ClientItemModelValue,
+    ClientParticleData, ClientTexture, EntityHeadData, ModelData, StepParticles, Transformation,
 };
-use block_byte_common::{Face, Position, TexCoords, Vec2};
+use block_byte_common::{Face, Position, TexCoords, Vec2, Vec3};
 use image::RgbaImage;
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Source};
 use std::collections::HashMap;
+use std::f32::consts::TAU;
 use std::io::Cursor;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -21,6 +25,8 @@ pub fn load_assets(
     BlockRegistry,
     ItemRegistry,
     EntityRegistry,
+    BiomeRegistry,
+    ParticleRegistry,
     TextRenderer<'static>,
     SoundManager,
 ) {
@@ -80,7 +86,10 @@ pub fn load_assets(
     let font = font.unwrap();
     let content = content.unwrap();
     let (texture_atlas, texture_image) = pack_textures(textures_to_pack, &font.font, dump_atlas);
-    let mut block_registry = BlockRegistry { blocks: Vec::new() };
+    let mut block_registry = BlockRegistry {
+        blocks: Vec::new(),
+        names: content.block_names,
+    };
     for block in content.blocks {
         block_registry.add_block(block, &texture_atlas, &models);
     }
@@ -94,23 +103,80 @@ pub fn load_assets(
     for entity in content.entities {
         entity_registry.add_entity(entity, &texture_atlas, &models);
     }
+    let biome_registry = BiomeRegistry {
+        biomes: content.biomes,
+    };
+    let mut particle_registry = ParticleRegistry {
+        particles: HashMap::new(),
+    };
+    for (id, particle) in content.particles {
+        particle_registry.add_particle(id, particle, &texture_atlas);
+    }
     (
         texture_image,
         texture_atlas,
         block_registry,
         item_registry,
         entity_registry,
+        biome_registry,
+        particle_registry,
         font,
         sound_manager,
     )
 }
+pub struct ParticleRegistry {
+    particles: HashMap<String, ParticleData>,
+}
+impl ParticleRegistry {
+    pub fn get_particle(&self, id: &str) -> Option<&ParticleData> {
+        self.particles.get(id)
+    }
+    pub fn add_particle(
+        &mut self,
+        id: String,
+        particle_data: ClientParticleData,
+        texture_atlas: &TextureAtlas,
+    ) {
+        self.particles.insert(
+            id,
+            ParticleData {
+                texture: Texture::from_common(particle_data.texture, texture_atlas),
+                lifetime: particle_data.lifetime,
+                gravity: particle_data.gravity,
+                size: particle_data.size,
+            },
+        );
+    }
+}
+pub struct ParticleData {
+    pub texture: Texture,
+    pub lifetime: f32,
+    pub gravity: f32,
+    pub size: f32,
+}
+pub struct BiomeRegistry {
+    biomes: Vec<ClientBiomeData>,
+}
+impl BiomeRegistry {
+    pub fn get_biome(&self, biome: u16) -> Option<&ClientBiomeData> {
+        self.biomes.get(biome as usize)
+    }
+}
 pub struct BlockRegistry {
     blocks: Vec<BlockData>,
+    names: Vec<String>,
 }
 impl BlockRegistry {
     pub fn get_block(&self, block: u32) -> &BlockData {
         self.blocks.get(block as usize).unwrap()
     }
+    pub fn get_count(&self) -> u32 {
+        self.blocks.len() as u32
+    }
+    //servers from before block names don't send them
+    pub fn get_name(&self, block: u32) -> Option<&str> {
+        self.names.get(block as usize).map(|name| name.as_str())
+    }
     fn add_block(
         &mut self,
         block_data: ClientBlockData,
@@ -183,6 +249,10 @@ impl BlockRegistry {
             selectable: block_data.selectable,
             transparent: block_data.transparent,
             no_collide: block_data.no_collide,
+            tint: block_data.tint,
+            interaction_prompt: block_data.interaction_prompt,
+            step_particles: block_data.step_particles,
+            surface_velocity: block_data.surface_velocity,
         });
     }
 }
@@ -194,8 +264,15 @@ pub struct BlockData {
     pub transparent: bool,
     pub selectable: bool,
     pub no_collide: bool,
+    pub tint: BlockTint,
+    pub interaction_prompt: Option<String>,
+    pub step_particles: StepParticles,
+    pub surface_velocity: Option<Vec3>,
 }
 impl BlockData {
+    pub fn get_render_data(&self) -> u32 {
+        self.render_data as u32
+    }
     pub fn is_face_full(&self, _face: Face) -> bool {
         if self.transparent {
             return false;
@@ -207,6 +284,20 @@ impl BlockData {
             BlockRenderDataType::Foliage(_) => false,
         }
     }
+    pub fn get_particle_texture(&self) -> Option<Texture> {
+        match &self.block_type {
+            BlockRenderDataType::Air => self.dynamic.as_ref().map(|model| model.texture),
+            BlockRenderDataType::Cube(cube) => Some(cube.front),
+            BlockRenderDataType::Static(static_data) => static_data
+                .models
+                .first()
+                .map(|model| model.0.texture)
+                .or(self.dynamic.as_ref().map(|model| model.texture)),
+            BlockRenderDataType::Foliage(foliage) => {
+                foliage.cross.or(foliage.sides).or(foliage.bottom)
+            }
+        }
+    }
 }
 
 pub enum BlockRenderDataType {
@@ -248,7 +339,61 @@ pub struct BlockFoliageRenderData {
 
 pub struct ItemData {
     pub name: String,
+    pub block: Option<u32>,
     pub model: ItemModel,
+    pub dynamic: Option<DynamicItemModel>,
+}
+impl ItemData {
+    //the model to draw this frame and how far it is turned clockwise, in turns
+    pub fn get_model(&self) -> (&ItemModel, f32) {
+        match &self.dynamic {
+            Some(dynamic) => dynamic.get_model(&self.model),
+            None => (&self.model, 0.),
+        }
+    }
+}
+pub struct DynamicItemModel {
+    value_type: ClientItemModelValue,
+    frames: Vec<ItemModel>,
+    rotate: bool,
+    value: f32,
+}
+impl DynamicItemModel {
+    //how often compasses without a waypoint spin around per second
+    const SPIN_SPEED: f32 = 0.5;
+    fn update(&mut self, state: &ItemModelState) {
+        self.value = match &self.value_type {
+            ClientItemModelValue::Waypoint(name) => match state.waypoints.get(name) {
+                Some(target) => {
+                    let angle = (target.x - state.position.x).atan2(target.z - state.position.z);
+                    //yaw grows when turning left
+                    (state.yaw - angle as f32) / TAU
+                }
+                None => state.time * Self::SPIN_SPEED,
+            },
+            ClientItemModelValue::Time => state.time_of_day,
+        }
+        .rem_euclid(1.);
+    }
+    fn get_model<'a>(&'a self, model: &'a ItemModel) -> (&'a ItemModel, f32) {
+        let model = if self.frames.is_empty() {
+            model
+        } else {
+            let frame = (self.value * self.frames.len() as f32).round() as usize;
+            &self.frames[frame % self.frames.len()]
+        };
+        (model, if self.rotate { self.value } else { 0. })
+    }
+}
+//what dynamic item models depend on, gathered once per frame
+pub struct ItemModelState<'a> {
+    pub position: Position,
+    //in radians
+    pub yaw: f32,
+    pub time_of_day: f32,
+    pub waypoints: &'a HashMap<String, Position>,
+    //seconds since the client started
+    pub time: f32,
 }
 pub enum ItemModel {
     Texture {
@@ -269,6 +414,13 @@ impl ItemRegistry {
     pub fn get_item(&self, item: u32) -> &ItemData {
         self.items.get(item as usize).unwrap()
     }
+    pub fn update_dynamic_models(&mut self, state: &ItemModelState) {
+        for item in &mut self.items {
+            if let Some(dynamic) = &mut item.dynamic {
+                dynamic.update(state);
+            }
+        }
+    }
     fn is_pixel_full(image: &RgbaImage, texture: TexCoords, coords: (i32, i32)) -> bool {
         let width = ((texture.u2 - texture.u1) * image.width() as f32).round() as u32;
         let height = ((texture.v2 - texture.v1) * image.height() as f32).round() as u32;
@@ -279,6 +431,44 @@ impl ItemRegistry {
         }
         image.get_pixel(x + coords.0 as u32, y + coords.1 as u32).0[3] > 0
     }
+    fn create_texture_model(
+        texture: &str,
+        texture_atlas: &TextureAtlas,
+        image: &RgbaImage,
+    ) -> ItemModel {
+        let texture = texture_atlas.get(texture);
+        let mut sides = Vec::new();
+        let width = ((texture.u2 - texture.u1) * image.width() as f32).round();
+        let height = ((texture.v2 - texture.v1) * image.height() as f32).round();
+        for x in 0..width as u32 {
+            for y in 0..height as u32 {
+                let this_full = Self::is_pixel_full(image, texture, (x as i32, y as i32));
+                if this_full {
+                    for face in &[Face::Front, Face::Back, Face::Left, Face::Right] {
+                        let face_offset = face.get_offset();
+                        let side_full = Self::is_pixel_full(
+                            image,
+                            texture,
+                            (x as i32 + face_offset.x, y as i32 + face_offset.z),
+                        );
+                        if !side_full {
+                            sides.push(((x, y), *face));
+                        }
+                    }
+                }
+            }
+        }
+        ItemModel::Texture {
+            texture,
+            sides: (
+                sides,
+                Vec2 {
+                    x: width,
+                    y: height,
+                },
+            ),
+        }
+    }
     fn add_item(
         &mut self,
         item_data: ClientItemData,
@@ -288,41 +478,23 @@ impl ItemRegistry {
     ) {
         self.items.push(ItemData {
             name: item_data.name,
+            block: match item_data.model {
+                ClientItemModel::Block(block) => Some(block),
+                ClientItemModel::Texture(_) => None,
+            },
+            dynamic: item_data.dynamic.map(|dynamic| DynamicItemModel {
+                value_type: dynamic.value,
+                frames: dynamic
+                    .textures
+                    .iter()
+                    .map(|texture| Self::create_texture_model(texture, texture_atlas, image))
+                    .collect(),
+                rotate: dynamic.rotate,
+                value: 0.,
+            }),
             model: match item_data.model {
                 ClientItemModel::Texture(texture) => {
-                    let texture = texture_atlas.get(texture.as_str());
-                    let mut sides = Vec::new();
-                    let width = ((texture.u2 - texture.u1) * image.width() as f32).round();
-                    let height = ((texture.v2 - texture.v1) * image.height() as f32).round();
-                    for x in 0..width as u32 {
-                        for y in 0..height as u32 {
-                            let this_full =
-                                Self::is_pixel_full(image, texture, (x as i32, y as i32));
-                            if this_full {
-                                for face in &[Face::Front, Face::Back, Face::Left, Face::Right] {
-                                    let face_offset = face.get_offset();
-                                    let side_full = Self::is_pixel_full(
-                                        image,
-                                        texture,
-                                        (x as i32 + face_offset.x, y as i32 + face_offset.z),
-                                    );
-                                    if !side_full {
-                                        sides.push(((x, y), *face));
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    ItemModel::Texture {
-                        texture,
-                        sides: (
-                            sides,
-                            Vec2 {
-                                x: width,
-                                y: height,
-                            },
-                        ),
-                    }
+                    Self::create_texture_model(&texture, texture_atlas, image)
                 }
                 ClientItemModel::Block(block) => {
                     let block = block_registry.get_block(block);
@@ -356,6 +528,9 @@ impl EntityRegistry {
         texture_atlas: &TextureAtlas,
         models: &HashMap<String, ModelData>,
     ) {
+        let mut animations = entity_data.animations;
+        AnimationStates::add_animations(&mut animations, &entity_data.animation_states);
+        let animation_states = AnimationStates::new(entity_data.animation_states, &animations);
         self.entities.push(EntityData {
             model: Model::new(
                 models
@@ -363,13 +538,18 @@ impl EntityRegistry {
                     .unwrap_or(models.get("missing").unwrap())
                     .clone(),
                 Texture::from_common(entity_data.texture, texture_atlas),
-                entity_data.animations,
+                animations,
                 entity_data.items,
-            ),
+            )
+            .with_head_bone(entity_data.head.as_ref().map(|head| head.bone.clone())),
+            animation_states,
             hitbox_w: entity_data.hitbox_w,
             hitbox_h: entity_data.hitbox_h,
             hitbox_d: entity_data.hitbox_d,
             hitbox_h_shifting: entity_data.hitbox_h_shifting,
+            interaction_prompt: entity_data.interaction_prompt,
+            item_entity: entity_data.item_entity,
+            head: entity_data.head,
             viewmodel: entity_data.viewmodel.map(|viewmodel| {
                 Model::new(
                     models
@@ -386,11 +566,15 @@ impl EntityRegistry {
 }
 pub struct EntityData {
     pub model: Model,
+    pub animation_states: AnimationStates,
     pub hitbox_w: f64,
     pub hitbox_h: f64,
     pub hitbox_d: f64,
     pub hitbox_h_shifting: f64,
     pub viewmodel: Option<Model>,
+    pub interaction_prompt: Option<String>,
+    pub item_entity: bool,
+    pub head: Option<EntityHeadData>,
 }
 #[derive(Copy, Clone)]
 pub enum Texture {
@@ -437,13 +621,33 @@ impl Texture {
     }
 }
 
-//todo: better audio
-pub struct SoundManager {}
+//todo: better audio, sounds aren't positioned yet
+pub struct SoundManager {
+    volume: f32,
+    sounds: HashMap<String, Arc<[u8]>>,
+    output: Option<(OutputStream, OutputStreamHandle)>,
+}
 impl SoundManager {
     pub fn new() -> Self {
-        SoundManager {}
+        let output = match OutputStream::try_default() {
+            Ok(output) => Some(output),
+            Err(error) => {
+                log::warn!("no audio output: {}", error);
+                None
+            }
+        };
+        SoundManager {
+            volume: 1.,
+            sounds: HashMap::new(),
+            output,
+        }
+    }
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0., 1.);
+    }
+    pub fn load_sound(&mut self, id: String, data: Vec<u8>) {
+        self.sounds.insert(id, data.into());
     }
-    pub fn load_sound(&mut self, id: String, data: Vec<u8>) {}
     pub fn play_sound(
         &mut self,
         id: &str,
@@ -452,5 +656,21 @@ impl SoundManager {
         pitch: f32,
         relative: bool,
     ) {
+        let (data, handle) = match (self.sounds.get(id), &self.output) {
+            (Some(data), Some((_, handle))) => (data, handle),
+            _ => return,
+        };
+        let source = match Decoder::new_wav(Cursor::new(data.clone())) {
+            Ok(source) => source,
+            Err(error) => {
+                log::warn!("sound {} couldn't be decoded: {}", id, error);
+                return;
+            }
+        };
+        let gain = gain * self.volume;
+        if gain <= 0. {
+            return;
+        }
+        let _ = handle.play_raw(source.convert_samples().amplify(gain).speed(pitch));
     }
 }
@@ -0,0 +1,152 @@
+use std::collections::{HashSet, VecDeque};
+
+use block_byte_common::{ChunkPosition, Face, Position};
+use cgmath::{Matrix4, Vector4};
+
+use crate::content::BlockRegistry;
+use crate::game::World;
+
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+impl Frustum {
+    pub fn from_matrix(view_proj: Matrix4<f32>) -> Self {
+        let row = |i: usize| {
+            Vector4::new(
+                view_proj.x[i],
+                view_proj.y[i],
+                view_proj.z[i],
+                view_proj.w[i],
+            )
+        };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Frustum {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z],
+        }
+    }
+    pub fn contains_chunk(&self, position: ChunkPosition) -> bool {
+        let min = [
+            position.x as f32 * 16.,
+            position.y as f32 * 16.,
+            position.z as f32 * 16.,
+        ];
+        self.planes.iter().all(|plane| {
+            let x = min[0] + if plane.x >= 0. { 16. } else { 0. };
+            let y = min[1] + if plane.y >= 0. { 16. } else { 0. };
+            let z = min[2] + if plane.z >= 0. { 16. } else { 0. };
+            plane.x * x + plane.y * y + plane.z * z + plane.w >= 0.
+        })
+    }
+}
+
+pub fn compute_visibility(blocks: &[[[u32; 16]; 16]; 16], block_registry: &BlockRegistry) -> u64 {
+    let opaque = |x: usize, y: usize, z: usize| {
+        block_registry
+            .get_block(blocks[x][y][z])
+            .is_face_full(Face::Up)
+    };
+    let mut visited = [[[false; 16]; 16]; 16];
+    let mut visibility = 0u64;
+    let mut stack = Vec::new();
+    for x in 0..16 {
+        for y in 0..16 {
+            for z in 0..16 {
+                if visited[x][y][z] || opaque(x, y, z) {
+                    continue;
+                }
+                visited[x][y][z] = true;
+                stack.push((x as i32, y as i32, z as i32));
+                let mut touched = 0u8;
+                while let Some((x, y, z)) = stack.pop() {
+                    for face in Face::all() {
+                        let offset = face.get_offset();
+                        let (nx, ny, nz) = (x + offset.x, y + offset.y, z + offset.z);
+                        if !(0..16).contains(&nx)
+                            || !(0..16).contains(&ny)
+                            || !(0..16).contains(&nz)
+                        {
+                            touched |= 1 << (*face as u8);
+                            continue;
+                        }
+                        let (nx, ny, nz) = (nx as usize, ny as usize, nz as usize);
+                        if visited[nx][ny][nz] || opaque(nx, ny, nz) {
+                            continue;
+                        }
+                        visited[nx][ny][nz] = true;
+                        stack.push((nx as i32, ny as i32, nz as i32));
+                    }
+                }
+                for from in Face::all() {
+                    for to in Face::all() {
+                        if touched & (1 << (*from as u8)) != 0 && touched & (1 << (*to as u8)) != 0
+                        {
+                            visibility |= 1 << (*from as u64 * 6 + *to as u64);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    visibility
+}
+
+pub fn collect_visible_chunks(
+    world: &World,
+    eye: Position,
+    frustum: &Frustum,
+    max_distance: u32,
+) -> Vec<ChunkPosition> {
+    let camera_chunk = eye.to_block_pos().to_chunk_pos();
+    let in_range = |position: ChunkPosition| {
+        (position.x - camera_chunk.x)
+            .unsigned_abs()
+            .max((position.y - camera_chunk.y).unsigned_abs())
+            .max((position.z - camera_chunk.z).unsigned_abs())
+            <= max_distance
+    };
+    if !world.chunks.contains_key(&camera_chunk) {
+        return world
+            .chunks
+            .keys()
+            .filter(|position| in_range(**position) && frustum.contains_chunk(**position))
+            .cloned()
+            .collect();
+    }
+    let mut visible = Vec::new();
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    visited.insert(camera_chunk);
+    queue.push_back((camera_chunk, None, 0u8));
+    while let Some((position, entered, directions)) = queue.pop_front() {
+        visible.push(position);
+        let chunk = match world.chunks.get(&position) {
+            Some(chunk) => chunk,
+            None => continue,
+        };
+        for face in Face::all() {
+            if directions & (1 << (face.opposite() as u8)) != 0 {
+                continue;
+            }
+            if let Some(entered) = entered {
+                if !chunk.can_see_through(entered, *face) {
+                    continue;
+                }
+            }
+            let neighbor = position.with_offset(face);
+            if visited.contains(&neighbor)
+                || !world.chunks.contains_key(&neighbor)
+                || !in_range(neighbor)
+                || !frustum.contains_chunk(neighbor)
+            {
+                continue;
+            }
+            visited.insert(neighbor);
+            queue.push_back((
+                neighbor,
+                Some(face.opposite()),
+                directions | (1 << (*face as u8)),
+            ));
+        }
+    }
+    visible
+}
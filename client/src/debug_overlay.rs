@@ -0,0 +1,202 @@
+use std::collections::VecDeque;
+
+use block_byte_common::gui::{GUIComponent, GUIElement, PositionAnchor};
+use block_byte_common::{Color, Position, Vec2};
+
+use crate::game::{ClientPlayer, RaycastResult, World};
+use crate::gpu_timing::{DrawStats, GpuTimer};
+use crate::gui::GUIRenderer;
+
+pub struct DebugOverlay {
+    enabled: bool,
+    frame_times: VecDeque<f32>,
+    line_count: usize,
+}
+impl DebugOverlay {
+    const ELEMENT_PREFIX: &'static str = "debug_overlay";
+    const GRAPH_FRAMES: usize = 120;
+    //graph height per millisecond of frame time, bars are capped at 50ms
+    const GRAPH_SCALE: f32 = 2.;
+    pub fn new() -> Self {
+        DebugOverlay {
+            enabled: false,
+            frame_times: VecDeque::new(),
+            line_count: 0,
+        }
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+    pub fn toggle(&mut self, gui: &mut GUIRenderer, gpu_timer: &mut GpuTimer) {
+        self.enabled = !self.enabled;
+        gpu_timer.set_enabled(self.enabled);
+        if !self.enabled {
+            gui.remove_elements(Self::ELEMENT_PREFIX);
+            self.line_count = 0;
+        }
+    }
+    pub fn record_frame(&mut self, delta_time: f32) {
+        if self.frame_times.len() >= Self::GRAPH_FRAMES {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(delta_time);
+    }
+    pub fn show(
+        &mut self,
+        gui: &mut GUIRenderer,
+        player: &ClientPlayer,
+        world: &World,
+        target: &RaycastResult,
+        gpu_timer: &GpuTimer,
+        stats: &DrawStats,
+        ping: Option<u32>,
+        breaking: Option<f32>,
+    ) {
+        let mut lines = Vec::new();
+        let average = self.frame_times.iter().sum::<f32>() / self.frame_times.len().max(1) as f32;
+        let slowest = self.frame_times.iter().cloned().fold(0., f32::max);
+        lines.push(format!(
+            "fps: {:.0} (slowest frame {:.1}ms)",
+            1. / average.max(0.0001),
+            slowest * 1000.
+        ));
+        let position = player.position;
+        lines.push(format!(
+            "xyz: {:.2} {:.2} {:.2}",
+            position.x, position.y, position.z
+        ));
+        let block_position = player.get_eye().to_block_pos();
+        let chunk = block_position.to_chunk_pos();
+        let offset = block_position.chunk_offset();
+        lines.push(format!(
+            "chunk: {} {} {} in {} {} {}",
+            offset.0, offset.1, offset.2, chunk.x, chunk.y, chunk.z
+        ));
+        let front = player.make_front();
+        let facing = if front.x.abs() > front.z.abs() {
+            if front.x > 0. {
+                "+x"
+            } else {
+                "-x"
+            }
+        } else if front.z > 0. {
+            "+z"
+        } else {
+            "-z"
+        };
+        lines.push(format!(
+            "facing: {} (yaw {:.1} pitch {:.1})",
+            facing, player.yaw_deg, player.pitch_deg
+        ));
+        lines.push(match target {
+            RaycastResult::Block(position, face) => {
+                let block = world.get_block(*position).unwrap_or(0);
+                format!(
+                    "target: {} {} {} {:?}, block {} {}",
+                    position.x,
+                    position.y,
+                    position.z,
+                    face,
+                    block,
+                    world.block_registry.get_name(block).unwrap_or("")
+                )
+            }
+            RaycastResult::Entity(id) => format!("target: entity {}", id),
+            RaycastResult::Miss => "target: none".to_string(),
+        });
+        if let Some(breaking) = breaking {
+            lines.push(format!("breaking: {:.0}%", breaking * 100.));
+        }
+        lines.push(format!(
+            "chunks: {} entities: {}",
+            world.chunks.len(),
+            world.entities.len()
+        ));
+        lines.push(format!(
+            "draw calls: {} triangles: {}",
+            stats.draw_calls, stats.triangles
+        ));
+        lines.extend(gpu_timer.get_lines());
+        lines.push(match ping {
+            Some(ping) => format!("ping: {}ms", ping),
+            None => "ping: -".to_string(),
+        });
+        //ids of later lines start with the id of an earlier one, but those are stale as well
+        for i in lines.len()..self.line_count {
+            gui.remove_elements(format!("{}{}", Self::ELEMENT_PREFIX, i).as_str());
+        }
+        self.line_count = lines.len();
+        for (i, line) in lines.into_iter().enumerate() {
+            gui.set_element(
+                format!("{}{}", Self::ELEMENT_PREFIX, i),
+                GUIElement {
+                    component_type: GUIComponent::TextComponent {
+                        font_size: 18.,
+                        text: line,
+                    },
+                    position: Position {
+                        x: 10.,
+                        y: -30. - i as f64 * 22.,
+                        z: 0.,
+                    },
+                    anchor: PositionAnchor::TopLeft,
+                    base_color: Color::WHITE,
+                    size_percent: None,
+                },
+            );
+        }
+        self.show_graph(gui);
+    }
+    //one bar per frame, newest on the right, with a line at 60 fps
+    fn show_graph(&self, gui: &mut GUIRenderer) {
+        let mut add_rect = |id: String, x: f32, y: f32, size: Vec2, color: Color| {
+            gui.set_element(
+                format!("{}_graph{}", Self::ELEMENT_PREFIX, id),
+                GUIElement {
+                    component_type: GUIComponent::ImageComponent {
+                        texture: String::new(),
+                        size,
+                        slice: None,
+                    },
+                    position: Position {
+                        x: (x + size.x / 2.) as f64,
+                        y: (y + size.y / 2.) as f64,
+                        z: 0.,
+                    },
+                    anchor: PositionAnchor::BottomLeft,
+                    base_color: color,
+                    size_percent: None,
+                },
+            );
+        };
+        for (i, frame_time) in self.frame_times.iter().enumerate() {
+            let milliseconds = (frame_time * 1000.).min(50.);
+            add_rect(
+                i.to_string(),
+                10. + i as f32 * 2.,
+                10.,
+                Vec2 {
+                    x: 2.,
+                    y: milliseconds * Self::GRAPH_SCALE,
+                },
+                if milliseconds <= 1000. / 60. {
+                    Color::from_array([80, 220, 80, 200])
+                } else if milliseconds <= 1000. / 30. {
+                    Color::from_array([230, 200, 60, 200])
+                } else {
+                    Color::from_array([230, 70, 60, 200])
+                },
+            );
+        }
+        add_rect(
+            "_target".to_string(),
+            10.,
+            10. + 1000. / 60. * Self::GRAPH_SCALE,
+            Vec2 {
+                x: Self::GRAPH_FRAMES as f32 * 2.,
+                y: 1.,
+            },
+            Color::from_array([255, 255, 255, 120]),
+        );
+    }
+}
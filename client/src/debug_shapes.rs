@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+
+use block_byte_common::{Color, DebugShape, Position};
+
+use crate::render::OutlineVertex;
+
+//named groups of shapes from the server, drawn by the outline renderer
+pub struct DebugShapes {
+    groups: HashMap<String, (Vec<(DebugShape, Color)>, Option<f32>)>,
+    changed: bool,
+}
+impl DebugShapes {
+    const SPHERE_SEGMENTS: usize = 32;
+    pub fn new() -> Self {
+        DebugShapes {
+            groups: HashMap::new(),
+            changed: false,
+        }
+    }
+    pub fn set(&mut self, name: String, shapes: Vec<(DebugShape, Color)>, lifetime: f32) {
+        if shapes.is_empty() {
+            self.groups.remove(&name);
+        } else {
+            self.groups.insert(
+                name,
+                (shapes, Some(lifetime).filter(|lifetime| *lifetime > 0.)),
+            );
+        }
+        self.changed = true;
+    }
+    pub fn tick(&mut self, dt: f32) {
+        let count = self.groups.len();
+        self.groups.retain(|_, (_, lifetime)| match lifetime {
+            Some(lifetime) => {
+                *lifetime -= dt;
+                *lifetime > 0.
+            }
+            None => true,
+        });
+        if self.groups.len() != count {
+            self.changed = true;
+        }
+    }
+    //the vertices of every line, only when something changed since the last call
+    pub fn take_vertices(&mut self) -> Option<Vec<OutlineVertex>> {
+        if !self.changed {
+            return None;
+        }
+        self.changed = false;
+        let mut vertices = Vec::new();
+        for (shapes, _) in self.groups.values() {
+            for (shape, color) in shapes {
+                let color = [
+                    color.r as f32 / 255.,
+                    color.g as f32 / 255.,
+                    color.b as f32 / 255.,
+                ];
+                let mut line = |from: Position, to: Position| {
+                    vertices.push(OutlineVertex {
+                        position: [from.x as f32, from.y as f32, from.z as f32],
+                        color,
+                    });
+                    vertices.push(OutlineVertex {
+                        position: [to.x as f32, to.y as f32, to.z as f32],
+                        color,
+                    });
+                };
+                match *shape {
+                    DebugShape::Line(from, to) => line(from, to),
+                    DebugShape::Box(first, second) => {
+                        let min = Position {
+                            x: first.x.min(second.x),
+                            y: first.y.min(second.y),
+                            z: first.z.min(second.z),
+                        };
+                        let max = Position {
+                            x: first.x.max(second.x),
+                            y: first.y.max(second.y),
+                            z: first.z.max(second.z),
+                        };
+                        let corner = |x: bool, y: bool, z: bool| Position {
+                            x: if x { max.x } else { min.x },
+                            y: if y { max.y } else { min.y },
+                            z: if z { max.z } else { min.z },
+                        };
+                        for a in [false, true] {
+                            for b in [false, true] {
+                                line(corner(false, a, b), corner(true, a, b));
+                                line(corner(a, false, b), corner(a, true, b));
+                                line(corner(a, b, false), corner(a, b, true));
+                            }
+                        }
+                    }
+                    //a circle around each axis
+                    DebugShape::Sphere(center, radius) => {
+                        let point = |axis: usize, angle: f64| {
+                            let (sin, cos) = (angle.sin() * radius, angle.cos() * radius);
+                            match axis {
+                                0 => center.add(0., sin, cos),
+                                1 => center.add(sin, 0., cos),
+                                _ => center.add(sin, cos, 0.),
+                            }
+                        };
+                        for axis in 0..3 {
+                            for segment in 0..Self::SPHERE_SEGMENTS {
+                                let angle = TAU / Self::SPHERE_SEGMENTS as f64;
+                                line(
+                                    point(axis, angle * segment as f64),
+                                    point(axis, angle * (segment + 1) as f64),
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Some(vertices)
+    }
+}
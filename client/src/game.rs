@@ -1,10 +1,14 @@
-use crate::content::{BlockRegistry, BlockRenderDataType, EntityRegistry};
+use crate::animation::AnimationStateMachine;
+use crate::content::{BiomeRegistry, BlockRegistry, BlockRenderDataType, EntityRegistry};
+use crate::culling;
 use crate::game::RaycastResult::{Block, Entity};
-use crate::model::{ModelInstanceData, TransformationExt};
+use crate::model::{Model, ModelInstanceData, TransformationExt};
 use crate::render::{ChunkVertex, FaceVerticesExtension};
-use block_byte_common::messages::MovementType;
+use block_byte_common::content::{BlockTint, ClientBiomeData, EntityHeadData};
+use block_byte_common::messages::{ClientModelTarget, MovementType};
 use block_byte_common::{
-    BlockPosition, ChunkPosition, Direction, Face, FaceStorage, Position, Vec3, AABB,
+    BlockPosition, ChunkPosition, Color, Direction, Face, FaceStorage, Position, Vec3, WorldBorder,
+    AABB,
 };
 use cgmath::{point3, ElementWise, InnerSpace, Matrix4, Point3, Vector3};
 use log::warn;
@@ -23,6 +27,7 @@ pub struct ClientPlayer {
     shifting_animation: f32,
     pub last_moved: bool,
     speed: f32,
+    fly_speed: f32,
     movement_type: MovementType,
     block_registry: Rc<BlockRegistry>,
     pub hitbox: Option<(f64, f64, f64, f64)>,
@@ -33,6 +38,7 @@ impl ClientPlayer {
         y: 1.0,
         z: 0.0,
     };
+    const FLY_SMOOTHING: f32 = 8.;
     pub fn is_shifting(&self) -> bool {
         self.shifting
     }
@@ -55,14 +61,24 @@ impl ClientPlayer {
         }
         self.velocity += Vector3::new(x, y, z);
     }
+    pub fn get_aabb(&self) -> Option<AABB> {
+        let position = Position::from(self.position);
+        self.hitbox.map(|hitbox| AABB {
+            x: position.x,
+            y: position.y,
+            z: position.z,
+            w: hitbox.0,
+            h: if self.shifting { hitbox.3 } else { hitbox.1 },
+            d: hitbox.2,
+        })
+    }
     pub fn get_eye(&self) -> Position {
         let hitbox = self.hitbox.unwrap_or((0., 0., 0., 0.));
-        Position {
-            x: self.position.x as f64,
-            y: self.position.y as f64,
-            z: self.position.z as f64,
-        }
-        .add(hitbox.0 / 2., self.eye_height_diff() as f64, hitbox.2 / 2.)
+        Position::from(self.position).add(
+            hitbox.0 / 2.,
+            self.eye_height_diff() as f64,
+            hitbox.2 / 2.,
+        )
     }
     pub fn update_position(
         &mut self,
@@ -70,6 +86,10 @@ impl ClientPlayer {
         delta_time: f32,
         world: &World,
     ) {
+        if self.movement_type == MovementType::Spectator {
+            self.update_fly_position(keys, delta_time);
+            return;
+        }
         let mut forward = Vector3::new(
             f32::to_radians(self.yaw_deg).sin(),
             0.,
@@ -91,11 +111,7 @@ impl ClientPlayer {
                 _ => vec,
             },
         );
-        let position = Position {
-            x: self.position.x as f64,
-            y: self.position.y as f64,
-            z: self.position.z as f64,
-        };
+        let position = Position::from(self.position);
         self.shifting = keys.contains(&VirtualKeyCode::LShift);
         if !self.shifting {
             let collides = self.collides_at(position, world);
@@ -140,6 +156,15 @@ impl ClientPlayer {
 
         self.last_moved = move_vector.magnitude() > 0.;
 
+        //the server moves other entities the same way, so players ride conveyors without rubber-banding
+        if self.movement_type == MovementType::Normal
+            && self.collides_at(position.add(0., -0.1, 0.), world)
+        {
+            if let Some(surface) = self.get_surface_velocity(position, world) {
+                total_move += Vector3::from(surface) * delta_time;
+            }
+        }
+
         if (total_move.x != 0.
             && self.shifting
             && self.collides_at(position.add(0., -0.1, 0.), world))
@@ -208,8 +233,63 @@ impl ClientPlayer {
                 .clamp(0., (hitbox.1 - hitbox.3) as f32);
         }
     }
+    fn update_fly_position(
+        &mut self,
+        keys: &std::collections::HashSet<VirtualKeyCode>,
+        delta_time: f32,
+    ) {
+        self.shifting = false;
+        self.shifting_animation = 0.;
+        let forward = self.make_front();
+        let right = Vector3::new(
+            f32::to_radians(self.yaw_deg).sin(),
+            0.,
+            f32::to_radians(self.yaw_deg).cos(),
+        )
+        .cross(Self::UP)
+        .normalize();
+        let mut move_vector = keys.iter().copied().fold(
+            Vector3 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            |vec, key| match key {
+                VirtualKeyCode::W => vec + forward,
+                VirtualKeyCode::S => vec - forward,
+                VirtualKeyCode::A => vec - right,
+                VirtualKeyCode::D => vec + right,
+                VirtualKeyCode::Space => vec + Self::UP,
+                VirtualKeyCode::LShift => vec - Self::UP,
+                _ => vec,
+            },
+        );
+        if !(move_vector.x == 0.0 && move_vector.y == 0.0 && move_vector.z == 0.0) {
+            move_vector = move_vector.normalize();
+        }
+        let target_velocity = move_vector * self.speed * self.fly_speed * 5.;
+        self.velocity +=
+            (target_velocity - self.velocity) * (delta_time * Self::FLY_SMOOTHING).min(1.);
+        self.position += self.velocity * delta_time;
+        self.last_moved = self.velocity.magnitude() > 0.01;
+    }
+    pub fn adjust_fly_speed(&mut self, scroll: i32) -> bool {
+        if self.movement_type != MovementType::Spectator {
+            return false;
+        }
+        self.fly_speed = (self.fly_speed * 1.25f32.powi(scroll)).clamp(0.1, 20.);
+        true
+    }
+    fn get_surface_velocity(&self, position: Position, world: &World) -> Option<Vec3> {
+        let hitbox = self.hitbox?;
+        let below = position.add(hitbox.0 / 2., -0.1, hitbox.2 / 2.);
+        let block = world.get_block(below.to_block_pos())?;
+        self.block_registry.get_block(block).surface_velocity
+    }
     fn collides_at(&self, position: Position, world: &World) -> bool {
-        if self.movement_type == MovementType::NoClip {
+        if self.movement_type == MovementType::NoClip
+            || self.movement_type == MovementType::Spectator
+        {
             return false;
         }
         return if let Some(hitbox) = &self.hitbox {
@@ -221,6 +301,9 @@ impl ClientPlayer {
                 h: if self.shifting { hitbox.3 } else { hitbox.1 },
                 d: hitbox.2,
             };
+            if !world.border.contains_aabb(&bounding_box) {
+                return true;
+            }
             for block_pos in bounding_box.iter_blocks() {
                 if world.get_block(block_pos).map_or(true, |block| {
                     let block = self.block_registry.get_block(block);
@@ -248,13 +331,20 @@ impl ClientPlayer {
             shifting_animation: 0f32,
             last_moved: false,
             speed: 1.,
+            fly_speed: 1.,
             movement_type: MovementType::NoClip,
             block_registry,
             hitbox: None,
         }
     }
+    pub fn set_block_registry(&mut self, block_registry: Rc<BlockRegistry>) {
+        self.block_registry = block_registry;
+    }
     pub fn set_abilities(&mut self, speed: f32, movement_type: MovementType) {
         self.speed = speed;
+        if movement_type != self.movement_type {
+            self.velocity = Vector3::new(0., 0., 0.);
+        }
         self.movement_type = movement_type;
     }
     fn eye_height_diff(&self) -> f32 {
@@ -263,56 +353,130 @@ impl ClientPlayer {
             - self.shifting_animation
     }
     pub fn create_view_matrix(&self) -> Matrix4<f32> {
-        let eye = self.get_eye();
-        let eye = Point3 {
-            x: eye.x as f32,
-            y: eye.y as f32,
-            z: eye.z as f32,
-        };
+        let eye = Point3::from(self.get_eye());
         Matrix4::look_at_rh(eye, eye + self.make_front(), Self::UP)
     }
     pub fn create_default_view_matrix() -> Matrix4<f32> {
         Matrix4::look_at_rh(point3(0., 0., 0.), point3(0., 0., -1.), ClientPlayer::UP)
     }
-    pub fn create_projection_matrix(aspect: f32) -> Matrix4<f32> {
-        cgmath::perspective(cgmath::Deg(90.), aspect, 0.05, 500.)
+    pub fn create_projection_matrix(aspect: f32, fov: f32) -> Matrix4<f32> {
+        cgmath::perspective(cgmath::Deg(fov), aspect, 0.05, 500.)
     }
 }
 pub struct DynamicBlockData {
     pub id: u32,
     pub model_instance: ModelInstanceData,
 }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeshingMode {
+    Naive,
+    //merges faces of neighboring opaque cubes, textures get stretched over the merged faces so it is only benchmarked for now
+    Greedy,
+}
+#[derive(Default)]
+pub struct ChunkMesh {
+    pub vertices: Vec<ChunkVertex>,
+    pub transparent_vertices: Vec<ChunkVertex>,
+    pub foliage_vertices: Vec<ChunkVertex>,
+}
+impl ChunkMesh {
+    pub fn get_vertex_count(&self) -> usize {
+        self.vertices.len() + self.transparent_vertices.len() + self.foliage_vertices.len()
+    }
+}
+//the axis a face points along and the two axes spanning it, as indices into [x, y, z]
+fn get_face_axes(face: Face) -> (usize, usize, usize) {
+    match face {
+        Face::Left | Face::Right => (0, 1, 2),
+        Face::Up | Face::Down => (1, 0, 2),
+        Face::Front | Face::Back => (2, 0, 1),
+    }
+}
+fn get_face_index(face: Face, depth: usize, first: usize, second: usize) -> usize {
+    face as usize * 4096 + depth * 256 + first * 16 + second
+}
 pub struct Chunk {
     position: ChunkPosition,
     blocks: [[[u32; 16]; 16]; 16],
+    biomes: Vec<u16>,
+    climate: Vec<[u8; 2]>,
     buffer: Option<(Buffer, u32)>,
     transparent_buffer: Option<(Buffer, u32)>,
     foliage_buffer: Option<(Buffer, u32)>,
+    visibility: u64,
 }
 impl Chunk {
-    pub fn new(position: ChunkPosition, blocks: [[[u32; 16]; 16]; 16]) -> Self {
+    pub fn new(
+        position: ChunkPosition,
+        blocks: [[[u32; 16]; 16]; 16],
+        biomes: Vec<u16>,
+        climate: Vec<[u8; 2]>,
+    ) -> Self {
         Chunk {
             position,
             blocks,
+            biomes,
+            climate,
             buffer: None,
             transparent_buffer: None,
             foliage_buffer: None,
+            visibility: u64::MAX,
         }
     }
-    pub fn rebuild_chunk_mesh(
-        &mut self,
+    //foliage and water tint of every column, from its biome and climate
+    fn get_column_tints(&self, biome_registry: &BiomeRegistry) -> Vec<([u8; 4], [u8; 4])> {
+        let default_biome = ClientBiomeData::default();
+        let to_vertex_tint = |color: [f32; 3]| {
+            [
+                (color[0] * 255.).clamp(0., 255.) as u8,
+                (color[1] * 255.).clamp(0., 255.) as u8,
+                (color[2] * 255.).clamp(0., 255.) as u8,
+                255,
+            ]
+        };
+        (0..256)
+            .map(|column| {
+                let biome = self
+                    .biomes
+                    .get(column)
+                    .and_then(|biome| biome_registry.get_biome(*biome))
+                    .unwrap_or(&default_biome);
+                let [temperature, moisture] = self.climate.get(column).cloned().unwrap_or([128; 2]);
+                (
+                    to_vertex_tint(
+                        biome.get_foliage_tint(temperature as f32 / 255., moisture as f32 / 255.),
+                    ),
+                    to_vertex_tint(biome.water_tint),
+                )
+            })
+            .collect()
+    }
+    pub fn build_mesh(
+        &self,
         block_registry: &BlockRegistry,
-        device: &Device,
-        neighbor_chunks: FaceStorage<&Chunk>,
-    ) {
-        let mut vertices: Vec<ChunkVertex> = Vec::new();
-        let mut transparent_vertices: Vec<ChunkVertex> = Vec::new();
-        let mut foliage_vertices: Vec<ChunkVertex> = Vec::new();
+        biome_registry: &BiomeRegistry,
+        neighbor_chunks: &FaceStorage<&Chunk>,
+        mode: MeshingMode,
+    ) -> ChunkMesh {
+        let tints = self.get_column_tints(biome_registry);
+        let mut mesh = ChunkMesh::default();
+        //faces left for the greedy pass, by face, depth and the two coordinates spanning the face
+        let mut merged_faces = if mode == MeshingMode::Greedy {
+            vec![None; 6 * 16 * 16 * 16]
+        } else {
+            Vec::new()
+        };
         for x in 0..16 {
             for y in 0..16 {
                 for z in 0..16 {
                     let block = self.blocks[x][y][z];
                     let block = block_registry.get_block(block);
+                    let (foliage_tint, water_tint) = tints[x * 16 + z];
+                    let tint = match block.tint {
+                        BlockTint::None => [255; 4],
+                        BlockTint::Foliage => foliage_tint,
+                        BlockTint::Water => water_tint,
+                    };
                     let base_position = Position {
                         x: ((self.position.x * 16) + x as i32) as f64,
                         y: ((self.position.y * 16) + y as i32) as f64,
@@ -345,6 +509,18 @@ impl Chunk {
                                 {
                                     continue;
                                 }
+                                if mode == MeshingMode::Greedy && !block.transparent && !block.fluid
+                                {
+                                    let (normal, first, second) = get_face_axes(*face);
+                                    let coords = [x, y, z];
+                                    merged_faces[get_face_index(
+                                        *face,
+                                        coords[normal],
+                                        coords[first],
+                                        coords[second],
+                                    )] = Some((self.blocks[x][y][z], tint));
+                                    continue;
+                                }
 
                                 let texture = cube_data.by_face(*face);
                                 face.add_vertices(
@@ -354,16 +530,17 @@ impl Chunk {
                                             | (((position.y > 0.5) as u32) << 1)
                                             | (((position.z > 0.5) as u32) << 2);
                                         (if block.transparent {
-                                            &mut transparent_vertices
+                                            &mut mesh.transparent_vertices
                                         } else {
-                                            &mut vertices
+                                            &mut mesh.vertices
                                         })
                                         .push(
                                             ChunkVertex::new(
                                                 base_position + position,
                                                 [coords.0, coords.1],
-                                                block.render_data as u32 | (position_flags << 8),
+                                                block.get_render_data() | (position_flags << 8),
                                                 texture,
+                                                tint,
                                             ),
                                         );
                                     },
@@ -380,7 +557,7 @@ impl Chunk {
                                         let position_flags = ((position.x > 0.5) as u32)
                                             | (((position.y > 0.5) as u32) << 1)
                                             | (((position.z > 0.5) as u32) << 2);
-                                        vertices.push(ChunkVertex::new(
+                                        mesh.vertices.push(ChunkVertex::new(
                                             base_position
                                                 + position
                                                 + Position {
@@ -389,8 +566,9 @@ impl Chunk {
                                                     z: 0.5,
                                                 },
                                             [coords.0, coords.1],
-                                            block.render_data as u32 | (position_flags << 8),
+                                            block.get_render_data() | (position_flags << 8),
                                             model.0.texture,
+                                            tint,
                                         ))
                                     },
                                 );
@@ -406,7 +584,7 @@ impl Chunk {
                                                 | (((position.y > 0.5) as u32) << 1)
                                                 | (((position.z > 0.5) as u32) << 2);
                                             let shift = face.opposite().get_offset();
-                                            foliage_vertices.push(ChunkVertex::new(
+                                            mesh.foliage_vertices.push(ChunkVertex::new(
                                                 base_position
                                                     + position
                                                     + Position {
@@ -415,8 +593,9 @@ impl Chunk {
                                                         z: shift.z as f64 * 0.3,
                                                     },
                                                 [coords.0, coords.1],
-                                                block.render_data as u32 | (position_flags << 8),
+                                                block.get_render_data() | (position_flags << 8),
                                                 texture,
+                                                tint,
                                             ));
                                         },
                                     );
@@ -430,7 +609,7 @@ impl Chunk {
                                             let position_flags = ((position.x > 0.5) as u32)
                                                 | (((position.y > 0.5) as u32) << 1)
                                                 | (((position.z > 0.5) as u32) << 2);
-                                            foliage_vertices.push(ChunkVertex::new(
+                                            mesh.foliage_vertices.push(ChunkVertex::new(
                                                 base_position
                                                     + Position {
                                                         x: (shift - position.x).abs(),
@@ -438,8 +617,9 @@ impl Chunk {
                                                         z: (1. - position.x).abs(),
                                                     },
                                                 [coords.0, coords.1],
-                                                block.render_data as u32 | (position_flags << 8),
+                                                block.get_render_data() | (position_flags << 8),
                                                 texture,
+                                                tint,
                                             ));
                                         },
                                     );
@@ -450,6 +630,110 @@ impl Chunk {
                 }
             }
         }
+        if mode == MeshingMode::Greedy {
+            self.add_merged_faces(&mut merged_faces, block_registry, &mut mesh.vertices);
+        }
+        mesh
+    }
+    //grows every face along the second axis first, then along the first while the whole row matches
+    fn add_merged_faces(
+        &self,
+        faces: &mut [Option<(u32, [u8; 4])>],
+        block_registry: &BlockRegistry,
+        vertices: &mut Vec<ChunkVertex>,
+    ) {
+        for face in Face::all() {
+            let (normal, first_axis, second_axis) = get_face_axes(*face);
+            for depth in 0..16 {
+                let index = |first, second| get_face_index(*face, depth, first, second);
+                for first in 0..16 {
+                    let mut second = 0;
+                    while second < 16 {
+                        let key = match faces[index(first, second)] {
+                            Some(key) => key,
+                            None => {
+                                second += 1;
+                                continue;
+                            }
+                        };
+                        let mut height = 1;
+                        while second + height < 16
+                            && faces[index(first, second + height)] == Some(key)
+                        {
+                            height += 1;
+                        }
+                        let mut width = 1;
+                        while first + width < 16
+                            && (0..height).all(|offset| {
+                                faces[index(first + width, second + offset)] == Some(key)
+                            })
+                        {
+                            width += 1;
+                        }
+                        for covered_first in first..first + width {
+                            for covered_second in second..second + height {
+                                faces[index(covered_first, covered_second)] = None;
+                            }
+                        }
+                        let block = block_registry.get_block(key.0);
+                        if let BlockRenderDataType::Cube(cube_data) = &block.block_type {
+                            let mut start = [0; 3];
+                            start[normal] = depth;
+                            start[first_axis] = first;
+                            start[second_axis] = second;
+                            let mut size = [1.; 3];
+                            size[first_axis] = width as f64;
+                            size[second_axis] = height as f64;
+                            let base_position = Position {
+                                x: ((self.position.x * 16) + start[0] as i32) as f64,
+                                y: ((self.position.y * 16) + start[1] as i32) as f64,
+                                z: ((self.position.z * 16) + start[2] as i32) as f64,
+                            };
+                            let texture = cube_data.by_face(*face);
+                            face.add_vertices(
+                                texture.get_first_coords(),
+                                &mut |position, coords| {
+                                    let position_flags = ((position.x > 0.5) as u32)
+                                        | (((position.y > 0.5) as u32) << 1)
+                                        | (((position.z > 0.5) as u32) << 2);
+                                    vertices.push(ChunkVertex::new(
+                                        base_position
+                                            + Position {
+                                                x: position.x * size[0],
+                                                y: position.y * size[1],
+                                                z: position.z * size[2],
+                                            },
+                                        [coords.0, coords.1],
+                                        block.get_render_data() | (position_flags << 8),
+                                        texture,
+                                        key.1,
+                                    ));
+                                },
+                            );
+                        }
+                        second += height;
+                    }
+                }
+            }
+        }
+    }
+    pub fn rebuild_chunk_mesh(
+        &mut self,
+        block_registry: &BlockRegistry,
+        biome_registry: &BiomeRegistry,
+        device: &Device,
+        neighbor_chunks: FaceStorage<&Chunk>,
+    ) {
+        let ChunkMesh {
+            vertices,
+            transparent_vertices,
+            foliage_vertices,
+        } = self.build_mesh(
+            block_registry,
+            biome_registry,
+            &neighbor_chunks,
+            MeshingMode::Naive,
+        );
         if vertices.len() == 0 {
             self.buffer = None;
         } else {
@@ -486,9 +770,13 @@ impl Chunk {
                 foliage_vertices.len() as u32,
             ));
         }
+        self.visibility = culling::compute_visibility(&self.blocks, block_registry);
+    }
+    pub fn can_see_through(&self, from: Face, to: Face) -> bool {
+        self.visibility & (1 << (from as u64 * 6 + to as u64)) != 0
     }
     pub fn get_vertices(
-        &mut self,
+        &self,
     ) -> (
         Option<(BufferSlice, u32)>,
         Option<(BufferSlice, u32)>,
@@ -511,21 +799,42 @@ pub struct World {
     pub chunks: HashMap<ChunkPosition, Chunk>,
     pub block_registry: Rc<BlockRegistry>,
     pub entity_registry: Rc<EntityRegistry>,
+    pub biome_registry: Rc<BiomeRegistry>,
     pub modified_chunks: HashSet<ChunkPosition>,
     pub dynamic_blocks: HashMap<BlockPosition, DynamicBlockData>,
     pub entities: HashMap<u32, EntityData>,
+    pub text_displays: HashMap<u32, TextDisplay>,
+    pub border: WorldBorder,
 }
 impl World {
-    pub fn new(block_registry: Rc<BlockRegistry>, entity_registry: Rc<EntityRegistry>) -> Self {
+    pub fn new(
+        block_registry: Rc<BlockRegistry>,
+        entity_registry: Rc<EntityRegistry>,
+        biome_registry: Rc<BiomeRegistry>,
+    ) -> Self {
         World {
             chunks: HashMap::new(),
             block_registry,
             entity_registry,
+            biome_registry,
             modified_chunks: HashSet::new(),
             dynamic_blocks: HashMap::new(),
             entities: HashMap::new(),
+            text_displays: HashMap::new(),
+            border: WorldBorder::default(),
         }
     }
+    pub fn set_registries(
+        &mut self,
+        block_registry: Rc<BlockRegistry>,
+        entity_registry: Rc<EntityRegistry>,
+        biome_registry: Rc<BiomeRegistry>,
+    ) {
+        self.block_registry = block_registry;
+        self.entity_registry = entity_registry;
+        self.biome_registry = biome_registry;
+        self.modified_chunks.extend(self.chunks.keys().cloned());
+    }
     pub fn tick(&mut self, device: &Device) {
         let max_chunk_meshes_per_frame = 200;
         for chunk_position in self
@@ -544,6 +853,7 @@ impl World {
             ]) {
                 chunk.rebuild_chunk_mesh(
                     &self.block_registry,
+                    &self.biome_registry,
                     device,
                     FaceStorage {
                         front,
@@ -557,13 +867,29 @@ impl World {
             }
         }
     }
-    pub fn load_chunk(&mut self, position: ChunkPosition, blocks: [[[u32; 16]; 16]; 16]) {
-        self.chunks.insert(position, Chunk::new(position, blocks));
+    pub fn load_chunk(
+        &mut self,
+        position: ChunkPosition,
+        blocks: [[[u32; 16]; 16]; 16],
+        biomes: Vec<u16>,
+        climate: Vec<[u8; 2]>,
+    ) {
+        self.chunks
+            .insert(position, Chunk::new(position, blocks, biomes, climate));
         self.modified_chunks.insert(position);
         for face in Face::all() {
             self.modified_chunks.insert(position.with_offset(face));
         }
     }
+    //forgets everything the server sent, registries stay
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.modified_chunks.clear();
+        self.dynamic_blocks.clear();
+        self.entities.clear();
+        self.text_displays.clear();
+        self.border = WorldBorder::default();
+    }
     pub fn unload_chunk(&mut self, position: ChunkPosition) {
         self.chunks.remove(&position);
         self.dynamic_blocks
@@ -592,6 +918,34 @@ impl World {
                 }),
         )
     }
+    //the viewmodel is not part of the world, so it has no instance here
+    pub fn get_model_instance(
+        &mut self,
+        target: &ClientModelTarget,
+    ) -> Option<(&mut ModelInstanceData, &Model)> {
+        match target {
+            ClientModelTarget::Block(position) => {
+                let block_id = self.get_block(*position)?;
+                let model = self.block_registry.get_block(block_id).dynamic.as_ref()?;
+                let block =
+                    self.dynamic_blocks
+                        .entry(*position)
+                        .or_insert_with(|| DynamicBlockData {
+                            id: block_id,
+                            model_instance: ModelInstanceData::new(),
+                        });
+                Some((&mut block.model_instance, model))
+            }
+            ClientModelTarget::Entity(id) => {
+                let entity = self.entities.get_mut(id)?;
+                Some((
+                    &mut entity.model_instance,
+                    &self.entity_registry.get_entity(entity.type_id).model,
+                ))
+            }
+            ClientModelTarget::ViewModel => None,
+        }
+    }
     pub fn set_block(&mut self, position: BlockPosition, id: u32) {
         let chunk_position = position.to_chunk_pos();
         let offset = position.chunk_offset();
@@ -609,6 +963,43 @@ impl World {
         }
         self.dynamic_blocks.remove(&position);
     }
+    pub fn set_blocks(&mut self, chunk_position: ChunkPosition, blocks: Vec<((u8, u8, u8), u32)>) {
+        let chunk = match self.chunks.get_mut(&chunk_position) {
+            Some(chunk) => chunk,
+            None => {
+                warn!("setting blocks in unloaded chunk");
+                return;
+            }
+        };
+        for ((x, y, z), id) in blocks {
+            chunk.blocks[x as usize][y as usize][z as usize] = id;
+            let position = BlockPosition {
+                x: chunk_position.x * 16 + x as i32,
+                y: chunk_position.y * 16 + y as i32,
+                z: chunk_position.z * 16 + z as i32,
+            };
+            for face in Face::all() {
+                if position.offset_by_face(*face).to_chunk_pos() != chunk_position {
+                    self.modified_chunks
+                        .insert(chunk_position.with_offset(face));
+                }
+            }
+            self.dynamic_blocks.remove(&position);
+        }
+        self.modified_chunks.insert(chunk_position);
+    }
+    pub fn get_biome(&self, position: BlockPosition) -> Option<&ClientBiomeData> {
+        let offset = position.chunk_offset();
+        self.chunks
+            .get(&position.to_chunk_pos())
+            .and_then(|chunk| {
+                chunk
+                    .biomes
+                    .get(offset.0 as usize * 16 + offset.2 as usize)
+                    .cloned()
+            })
+            .and_then(|biome| self.biome_registry.get_biome(biome))
+    }
     pub fn get_block(&self, position: BlockPosition) -> Option<u32> {
         let chunk = position.to_chunk_pos();
         let offset = position.chunk_offset();
@@ -616,6 +1007,41 @@ impl World {
             .get(&chunk)
             .map(|chunk| chunk.blocks[offset.0 as usize][offset.1 as usize][offset.2 as usize])
     }
+    pub fn tick_entities(&mut self, delta_time: f32) {
+        for entity in self.entities.values_mut() {
+            entity.age += delta_time;
+            let entity_data = self.entity_registry.get_entity(entity.type_id);
+            entity.update_body_yaw(delta_time, entity_data.head.as_ref());
+            entity.update_movement_speed(delta_time);
+            entity.model_instance.tick_poses(delta_time);
+            entity.animation.tick(
+                delta_time,
+                entity.movement_speed,
+                &entity_data.animation_states,
+                &entity_data.model,
+                &mut entity.model_instance,
+            );
+            if let Some((_, progress)) = &mut entity.pickup {
+                *progress += delta_time / EntityData::PICKUP_TIME;
+            }
+        }
+        self.entities
+            .retain(|_, entity| entity.pickup.map_or(true, |(_, progress)| progress < 1.));
+    }
+    pub fn collides_entity(&self, aabb: &AABB) -> bool {
+        self.entities.values().any(|entity| {
+            let entity_data = self.entity_registry.get_entity(entity.type_id);
+            AABB {
+                x: entity.position.x,
+                y: entity.position.y,
+                z: entity.position.z,
+                w: entity_data.hitbox_w,
+                h: entity_data.hitbox_h,
+                d: entity_data.hitbox_d,
+            }
+            .collides(aabb)
+        })
+    }
     pub fn raycast(
         &self,
         max_distance: f64,
@@ -625,6 +1051,9 @@ impl World {
     ) -> RaycastResult {
         let mut closest_entity: Option<(f64, u32)> = None;
         for (id, entity) in &self.entities {
+            if entity.pickup.is_some() {
+                continue;
+            }
             let entity_data = self.entity_registry.get_entity(entity.type_id);
             let aabb = AABB {
                 x: entity.position.x,
@@ -634,14 +1063,7 @@ impl World {
                 h: entity_data.hitbox_h,
                 d: entity_data.hitbox_d,
             };
-            if let Some(distance) = aabb.raycast(
-                start_position,
-                Vec3 {
-                    x: direction.x,
-                    y: direction.y,
-                    z: direction.z,
-                },
-            ) {
+            if let Some(distance) = aabb.raycast(start_position, Vec3::from(direction)) {
                 if distance <= max_distance
                     && distance < closest_entity.map(|e| e.0).unwrap_or(f64::INFINITY)
                 {
@@ -650,13 +1072,9 @@ impl World {
             }
         }
         let entity_hit_position = match &closest_entity {
-            Some((distance, _)) => {
-                let mut entity_hit_position = start_position;
-                entity_hit_position.x += direction.x as f64 * distance;
-                entity_hit_position.y += direction.y as f64 * distance;
-                entity_hit_position.z += direction.z as f64 * distance;
-                Some(entity_hit_position.to_block_pos())
-            }
+            Some((distance, _)) => Some(
+                (start_position + Position::from(direction).multiply(*distance)).to_block_pos(),
+            ),
             None => None,
         };
         let mut output = None;
@@ -708,14 +1126,128 @@ impl World {
         result
     }
 }
+//where a ray that hit the block enters it through the face
+pub fn get_hit_position(
+    start_position: Position,
+    direction: Vector3<f32>,
+    position: BlockPosition,
+    face: Face,
+) -> Position {
+    let direction = Position::from(direction);
+    let offset = face.get_offset();
+    let (start, speed, plane) = if offset.x != 0 {
+        (start_position.x, direction.x, position.x + offset.x.max(0))
+    } else if offset.y != 0 {
+        (start_position.y, direction.y, position.y + offset.y.max(0))
+    } else {
+        (start_position.z, direction.z, position.z + offset.z.max(0))
+    };
+    if speed == 0. {
+        return start_position;
+    }
+    start_position + direction.multiply((plane as f64 - start) / speed)
+}
 pub enum RaycastResult {
     Entity(u32),
     Block(BlockPosition, Face),
     Miss,
 }
+pub struct TextDisplay {
+    pub position: Position,
+    pub text: String,
+    pub background: Color,
+    pub scale: f32,
+}
 pub struct EntityData {
     pub type_id: u32,
     pub position: Position,
     pub rotation: Direction,
+    pub body_yaw: f64,
     pub model_instance: ModelInstanceData,
+    pub animation: AnimationStateMachine,
+    pub movement_speed: f32,
+    pub previous_position: Position,
+    pub age: f32,
+    pub pickup: Option<(u32, f32)>,
+}
+impl EntityData {
+    const PICKUP_TIME: f32 = 0.2;
+    const MOVEMENT_SPEED_SMOOTHING: f32 = 5.;
+    const BOB_SPEED: f32 = 2.;
+    const BOB_HEIGHT: f32 = 0.1;
+    const SPIN_SPEED: f32 = 1.5;
+    const BODY_TURN_SPEED: f64 = 8.;
+    fn wrap_angle(angle: f64) -> f64 {
+        (angle + std::f64::consts::PI).rem_euclid(std::f64::consts::TAU) - std::f64::consts::PI
+    }
+    //the body turns towards where the entity walks and is dragged along when the head turns too far,
+    //entities without a head bone just face their rotation
+    pub fn update_body_yaw(&mut self, delta_time: f32, head: Option<&EntityHeadData>) {
+        let head = match head {
+            Some(head) => head,
+            None => {
+                self.body_yaw = self.rotation.yaw;
+                return;
+            }
+        };
+        let moved_x = self.position.x - self.previous_position.x;
+        let moved_z = self.position.z - self.previous_position.z;
+        if moved_x * moved_x + moved_z * moved_z > 0.0001 {
+            let difference = Self::wrap_angle(moved_x.atan2(moved_z) - self.body_yaw);
+            self.body_yaw += difference * (delta_time as f64 * Self::BODY_TURN_SPEED).min(1.);
+        }
+        let max_yaw = (head.max_yaw as f64).to_radians();
+        let head_yaw = Self::wrap_angle(self.rotation.yaw - self.body_yaw);
+        if head_yaw.abs() > max_yaw {
+            self.body_yaw = self.rotation.yaw - max_yaw.copysign(head_yaw);
+        }
+        self.body_yaw = Self::wrap_angle(self.body_yaw);
+        self.model_instance.head = Some((
+            Self::wrap_angle(self.rotation.yaw - self.body_yaw) as f32,
+            self.rotation.pitch as f32,
+        ));
+    }
+    //positions arrive in bursts from the server, so the horizontal speed is smoothed over a few frames
+    pub fn update_movement_speed(&mut self, delta_time: f32) {
+        if delta_time <= 0. {
+            return;
+        }
+        let distance = ((self.position.x - self.previous_position.x).powi(2)
+            + (self.position.z - self.previous_position.z).powi(2))
+        .sqrt() as f32;
+        self.previous_position = self.position;
+        let factor = (delta_time * Self::MOVEMENT_SPEED_SMOOTHING).min(1.);
+        self.movement_speed += (distance / delta_time - self.movement_speed) * factor;
+    }
+    pub fn get_render_transform(
+        &self,
+        world: &World,
+        camera: &ClientPlayer,
+    ) -> (Position, f64, f32) {
+        let mut position = self.position;
+        let mut yaw = self.body_yaw;
+        let mut scale = 1.;
+        if world.entity_registry.get_entity(self.type_id).item_entity {
+            position.y +=
+                ((self.age * Self::BOB_SPEED).sin() * Self::BOB_HEIGHT + Self::BOB_HEIGHT) as f64;
+            yaw = (self.age * Self::SPIN_SPEED) as f64;
+        }
+        if let Some((collector, progress)) = self.pickup {
+            let target = match world.entities.get(&collector) {
+                Some(collector) => {
+                    let entity_data = world.entity_registry.get_entity(collector.type_id);
+                    collector.position.add(0., entity_data.hitbox_h / 2., 0.)
+                }
+                None => camera.get_eye().add(0., -0.5, 0.),
+            };
+            let progress = (progress * progress) as f64;
+            position = Position {
+                x: position.x + (target.x - position.x) * progress,
+                y: position.y + (target.y - position.y) * progress,
+                z: position.z + (target.z - position.z) * progress,
+            };
+            scale = 1. - progress as f32;
+        }
+        (position, yaw, scale)
+    }
 }
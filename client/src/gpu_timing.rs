@@ -0,0 +1,199 @@
+use std::mem::size_of;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+use wgpu::{
+    Buffer, BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoder, Device, Features,
+    Maintain, MapMode, QuerySet, QuerySetDescriptor, QueryType, Queue,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GpuPass {
+    Chunks,
+    Entities,
+    Outline,
+    Gui,
+}
+impl GpuPass {
+    pub const ALL: [GpuPass; 4] = [
+        GpuPass::Chunks,
+        GpuPass::Entities,
+        GpuPass::Outline,
+        GpuPass::Gui,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            GpuPass::Chunks => "chunks",
+            GpuPass::Entities => "entities",
+            GpuPass::Outline => "outline",
+            GpuPass::Gui => "gui",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct DrawStats {
+    pub draw_calls: u32,
+    pub triangles: u32,
+}
+impl DrawStats {
+    pub fn draw_triangles(&mut self, vertices: u32) {
+        self.draw_calls += 1;
+        self.triangles += vertices / 3;
+    }
+    pub fn draw_lines(&mut self) {
+        self.draw_calls += 1;
+    }
+}
+
+//results arrive a few frames late, frames are not timed while the previous one is still being read back
+pub struct GpuTimer {
+    query_set: Option<QuerySet>,
+    resolve_buffer: Buffer,
+    readback_buffer: Buffer,
+    period: f32,
+    enabled: bool,
+    recording: bool,
+    open: bool,
+    segments: Vec<GpuPass>,
+    pending: Option<(Vec<GpuPass>, Receiver<Result<(), BufferAsyncError>>)>,
+    timings: [f32; 4],
+}
+impl GpuTimer {
+    const MAX_SEGMENTS: usize = 16;
+    pub fn new(device: &Device, queue: &Queue) -> Self {
+        let size = (Self::MAX_SEGMENTS * 2 * size_of::<u64>()) as u64;
+        GpuTimer {
+            query_set: device
+                .features()
+                .contains(Features::TIMESTAMP_QUERY)
+                .then(|| {
+                    device.create_query_set(&QuerySetDescriptor {
+                        label: Some("GPU Timer Queries"),
+                        ty: QueryType::Timestamp,
+                        count: Self::MAX_SEGMENTS as u32 * 2,
+                    })
+                }),
+            resolve_buffer: device.create_buffer(&BufferDescriptor {
+                label: Some("GPU Timer Resolve Buffer"),
+                size,
+                usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback_buffer: device.create_buffer(&BufferDescriptor {
+                label: Some("GPU Timer Readback Buffer"),
+                size,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            period: queue.get_timestamp_period(),
+            enabled: false,
+            recording: false,
+            open: false,
+            segments: Vec::new(),
+            pending: None,
+            timings: [0.; 4],
+        }
+    }
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.timings = [0.; 4];
+    }
+    //collects the timestamps of an earlier frame once they can be read
+    pub fn begin_frame(&mut self, device: &Device) {
+        self.recording = false;
+        if let Some((segments, receiver)) = self.pending.take() {
+            device.poll(Maintain::Poll);
+            match receiver.try_recv() {
+                Ok(Ok(())) => {
+                    self.read_timings(&segments);
+                    self.readback_buffer.unmap();
+                }
+                Ok(Err(_)) | Err(TryRecvError::Disconnected) => {}
+                Err(TryRecvError::Empty) => {
+                    self.pending = Some((segments, receiver));
+                    return;
+                }
+            }
+        }
+        self.recording = self.enabled && self.query_set.is_some();
+        self.segments.clear();
+    }
+    fn read_timings(&mut self, segments: &[GpuPass]) {
+        let mut timings = [0.; 4];
+        {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&*data);
+            for (i, pass) in segments.iter().enumerate() {
+                let ticks = timestamps[i * 2 + 1].saturating_sub(timestamps[i * 2]);
+                timings[*pass as usize] += ticks as f32 * self.period / 1_000_000.;
+            }
+        }
+        //smoothed so the numbers stay readable
+        for (timing, new_timing) in self.timings.iter_mut().zip(timings) {
+            *timing = *timing * 0.9 + new_timing * 0.1;
+        }
+    }
+    pub fn begin(&mut self, encoder: &mut CommandEncoder, pass: GpuPass) {
+        if !self.recording || self.segments.len() >= Self::MAX_SEGMENTS {
+            return;
+        }
+        if let Some(query_set) = &self.query_set {
+            encoder.write_timestamp(query_set, self.segments.len() as u32 * 2);
+            self.segments.push(pass);
+            self.open = true;
+        }
+    }
+    pub fn end(&mut self, encoder: &mut CommandEncoder) {
+        if !self.open {
+            return;
+        }
+        if let Some(query_set) = &self.query_set {
+            encoder.write_timestamp(query_set, self.segments.len() as u32 * 2 - 1);
+        }
+        self.open = false;
+    }
+    //must be recorded into the last encoder of the frame
+    pub fn resolve(&mut self, encoder: &mut CommandEncoder) {
+        if !self.recording || self.segments.is_empty() {
+            return;
+        }
+        if let Some(query_set) = &self.query_set {
+            let count = self.segments.len() as u32 * 2;
+            encoder.resolve_query_set(query_set, 0..count, &self.resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(
+                &self.resolve_buffer,
+                0,
+                &self.readback_buffer,
+                0,
+                (count as usize * size_of::<u64>()) as u64,
+            );
+        }
+    }
+    pub fn finish_frame(&mut self) {
+        if !self.recording || self.segments.is_empty() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        self.readback_buffer
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.pending = Some((std::mem::take(&mut self.segments), receiver));
+        self.recording = false;
+    }
+    pub fn get_lines(&self) -> Vec<String> {
+        if self.query_set.is_none() {
+            return vec!["gpu timing not supported".to_string()];
+        }
+        let mut lines: Vec<_> = GpuPass::ALL
+            .iter()
+            .map(|pass| format!("gpu {}: {:.2}ms", pass.name(), self.timings[*pass as usize]))
+            .collect();
+        lines.push(format!(
+            "gpu total: {:.2}ms",
+            self.timings.iter().sum::<f32>()
+        ));
+        lines
+    }
+}
@@ -1,24 +1,56 @@
+use crate::accessibility::ColorPalette;
 use crate::content::{ItemModel, ItemRegistry};
+use crate::hotbar::Hotbar;
 use crate::render::GUIVertex;
 use crate::texture::TextureAtlas;
-use block_byte_common::gui::{GUIComponent, GUIElement, PositionAnchor};
+use block_byte_common::gui::{ContainerLayout, GUIComponent, GUIElement, PositionAnchor};
 use block_byte_common::{Color, TexCoords, Vec2};
+use bytemuck::Zeroable;
 use rusttype::Scale;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::TAU;
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
-use wgpu::{Buffer, BufferSlice, BufferUsages, Device};
+use wgpu::{Buffer, BufferSlice, BufferUsages, Device, Queue};
 use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::VirtualKeyCode;
 
+#[derive(Clone, Copy)]
+struct ElementGeometry {
+    offset: u32,
+    capacity: u32,
+}
 pub struct GUIRenderer<'a> {
     elements: HashMap<String, GUIElement>,
     buffer: Buffer,
+    buffer_used: u32,
+    buffer_wasted: u32,
+    geometry: HashMap<String, ElementGeometry>,
+    dirty: HashSet<String>,
+    cleared: Vec<ElementGeometry>,
+    visible_ranges: HashMap<String, (u32, u32)>,
+    rebuild_all: bool,
+    last_size: PhysicalSize<u32>,
+    last_mouse: PhysicalPosition<f64>,
+    overlay_buffer: Buffer,
     gui_scale: f32,
+    server_scale: f32,
+    safe_area: f32,
     texture_atlas: TextureAtlas,
     cursor_locked: bool,
     text_renderer: TextRenderer<'a>,
-    pub selected: Option<String>,
+    selected: Option<String>,
+    caret: usize,
+    caret_time: f32,
+    preedit: String,
+    screen_fade: (f32, f32, f32),
+    color_palette: ColorPalette,
+    hotbar: Hotbar,
 }
 impl<'a> GUIRenderer<'a> {
+    const MIN_ASPECT_RATIO: f32 = 4. / 3.;
+    const VERTEX_SIZE: u64 = std::mem::size_of::<GUIVertex>() as u64;
+    const MIN_BUFFER_VERTICES: u32 = 6000;
+    const REBUILD_BUDGET: usize = 200;
     pub fn new(
         texture_atlas: TextureAtlas,
         device: &Device,
@@ -32,13 +64,75 @@ impl<'a> GUIRenderer<'a> {
                 contents: &[],
                 usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
             }),
+            buffer_used: 0,
+            buffer_wasted: 0,
+            geometry: HashMap::new(),
+            dirty: HashSet::new(),
+            cleared: Vec::new(),
+            visible_ranges: HashMap::new(),
+            rebuild_all: true,
+            last_size: PhysicalSize::new(0, 0),
+            last_mouse: PhysicalPosition::new(0., 0.),
+            overlay_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("gui overlay buffer"),
+                contents: &[],
+                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            }),
             gui_scale: 1. / 700.,
+            server_scale: 1.,
+            safe_area: 0.,
             cursor_locked: true,
             text_renderer,
             selected: None,
+            caret: 0,
+            caret_time: 0.,
+            preedit: String::new(),
+            screen_fade: (0., 0., 0.),
+            color_palette: ColorPalette::Default,
+            hotbar: Hotbar::new(),
+        }
+    }
+    pub fn set_content(&mut self, texture_atlas: TextureAtlas, text_renderer: TextRenderer<'a>) {
+        self.texture_atlas = texture_atlas;
+        self.text_renderer = text_renderer;
+        self.rebuild_all = true;
+    }
+    pub fn set_color_palette(&mut self, color_palette: ColorPalette) {
+        self.color_palette = color_palette;
+        self.rebuild_all = true;
+    }
+    pub fn get_selected_id(&self) -> Option<&String> {
+        self.selected.as_ref()
+    }
+    pub fn get_texture_atlas(&self) -> &TextureAtlas {
+        &self.texture_atlas
+    }
+    pub fn get_text_renderer(&self) -> &TextRenderer<'a> {
+        &self.text_renderer
+    }
+    pub fn set_screen_fade(&mut self, target: f32, duration: f32) {
+        let (current, _, _) = self.screen_fade;
+        let speed = if duration > 0. {
+            (target - current).abs() / duration
+        } else {
+            f32::INFINITY
+        };
+        self.screen_fade = (current, target.clamp(0., 1.), speed);
+    }
+    pub fn update_screen_fade(&mut self, delta_time: f32) {
+        let (current, target, speed) = &mut self.screen_fade;
+        let step = *speed * delta_time;
+        if (*target - *current).abs() <= step {
+            *current = *target;
+        } else {
+            *current += step.copysign(*target - *current);
         }
     }
+    pub fn get_hotbar(&mut self) -> &mut Hotbar {
+        &mut self.hotbar
+    }
     pub fn edit_element_text(&mut self, id: &str) -> Option<&mut String> {
+        self.dirty.insert(id.to_string());
         self.elements
             .get_mut(id)
             .and_then(|element| match &mut element.component_type {
@@ -46,16 +140,293 @@ impl<'a> GUIRenderer<'a> {
                 _ => None,
             })
     }
+    pub fn select(&mut self, selected: Option<String>) {
+        for id in self.selected.iter().chain(selected.iter()) {
+            self.dirty.insert(id.clone());
+        }
+        self.selected = selected;
+        self.caret = match self
+            .selected
+            .as_ref()
+            .and_then(|id| self.elements.get(id))
+            .map(|element| &element.component_type)
+        {
+            Some(GUIComponent::TextInput { text, .. }) => text.chars().count(),
+            _ => 0,
+        };
+        self.caret_time = 0.;
+        self.preedit.clear();
+    }
+    fn get_text_input(&mut self, id: &str) -> Option<&mut String> {
+        self.elements
+            .get_mut(id)
+            .and_then(|element| match &mut element.component_type {
+                GUIComponent::TextInput { text, .. } => Some(text),
+                _ => None,
+            })
+    }
+    pub fn is_text_input_focused(&self) -> bool {
+        self.selected
+            .as_ref()
+            .and_then(|id| self.elements.get(id))
+            .map(|element| matches!(element.component_type, GUIComponent::TextInput { .. }))
+            .unwrap_or(false)
+    }
+    pub fn is_composing(&self) -> bool {
+        !self.preedit.is_empty()
+    }
+    pub fn set_preedit(&mut self, preedit: String) {
+        self.preedit = preedit;
+        self.caret_time = 0.;
+    }
+    fn byte_index(text: &str, caret: usize) -> usize {
+        text.char_indices()
+            .nth(caret)
+            .map(|(index, _)| index)
+            .unwrap_or(text.len())
+    }
+    //the caret is counted in characters and clamped because the server can replace the text at any time
+    pub fn insert_text(&mut self, input: &str) -> Option<(String, String)> {
+        let id = self.selected.clone()?;
+        let caret = self.caret;
+        let input: String = input
+            .chars()
+            .filter(|character| !character.is_control())
+            .collect();
+        let text = self.get_text_input(id.as_str())?;
+        if input.is_empty() {
+            return None;
+        }
+        let caret = caret.min(text.chars().count());
+        text.insert_str(Self::byte_index(text, caret), input.as_str());
+        let text = text.clone();
+        self.caret = caret + input.chars().count();
+        self.caret_time = 0.;
+        Some((id, text))
+    }
+    //returns the new text when the key changed it, caret movement stays on the client
+    pub fn edit_text_input(&mut self, key: VirtualKeyCode) -> Option<(String, String)> {
+        let id = self.selected.clone()?;
+        let caret = self.caret;
+        let text = self.get_text_input(id.as_str())?;
+        let length = text.chars().count();
+        let caret = caret.min(length);
+        let (caret, changed) = match key {
+            VirtualKeyCode::Back if caret > 0 => {
+                text.remove(Self::byte_index(text, caret - 1));
+                (caret - 1, true)
+            }
+            VirtualKeyCode::Delete if caret < length => {
+                text.remove(Self::byte_index(text, caret));
+                (caret, true)
+            }
+            VirtualKeyCode::Left => (caret.saturating_sub(1), false),
+            VirtualKeyCode::Right => ((caret + 1).min(length), false),
+            VirtualKeyCode::Home => (0, false),
+            VirtualKeyCode::End => (length, false),
+            _ => (caret, false),
+        };
+        let text = text.clone();
+        self.caret = caret;
+        self.caret_time = 0.;
+        if changed {
+            Some((id, text))
+        } else {
+            None
+        }
+    }
+    pub fn update_caret(&mut self, delta_time: f32) {
+        self.caret_time = (self.caret_time + delta_time) % 1.;
+    }
     pub fn set_element(&mut self, id: String, element: GUIElement) {
-        self.elements.insert(id, element);
+        self.elements.insert(id.clone(), element);
+        self.mark_dirty(id.as_str());
     }
+    //elements handed out mutably are assumed to be edited
     pub fn get_element(&mut self, id: String) -> Option<&mut GUIElement> {
+        if self.elements.contains_key(id.as_str()) {
+            self.mark_dirty(id.as_str());
+        }
         self.elements.get_mut(id.as_str())
     }
     pub fn remove_elements(&mut self, id: &str) {
-        self.elements
+        for (element_id, _) in self
+            .elements
             .extract_if(|element_id, _| element_id.starts_with(id))
-            .count();
+        {
+            self.dirty.remove(&element_id);
+            self.visible_ranges.remove(&element_id);
+            if let Some(geometry) = self.geometry.remove(&element_id) {
+                self.buffer_wasted += geometry.capacity;
+                self.cleared.push(geometry);
+            }
+        }
+        self.hotbar.close(id);
+    }
+    pub fn set_gui_scale(&mut self, scale: f32) {
+        self.gui_scale = scale / 700.;
+        self.rebuild_all = true;
+    }
+    pub fn set_server_layout(&mut self, scale: f32, safe_area: f32) {
+        self.server_scale = scale;
+        self.safe_area = safe_area;
+        self.rebuild_all = true;
+    }
+    //layouts are designed for at least 4:3, narrower windows shrink the gui so wide HUDs still fit
+    fn get_layout_scale(&self, aspect_ratio: f32) -> f32 {
+        self.gui_scale * self.server_scale * (aspect_ratio / Self::MIN_ASPECT_RATIO).min(1.)
+    }
+    fn get_layout_position(&self, element: &GUIElement) -> Vec2 {
+        let inset = match element.anchor {
+            PositionAnchor::Cursor => Vec2::ZERO,
+            anchor => anchor.get_center(Vec2::ZERO),
+        };
+        Vec2 {
+            x: element.position.x as f32 - inset.x * self.safe_area,
+            y: element.position.y as f32 - inset.y * self.safe_area,
+        }
+    }
+    //returns the container an element is laid out in and the index of its cell
+    fn get_container<'b>(
+        &'b self,
+        id: &'b str,
+    ) -> Option<(&'b str, &'b GUIElement, ContainerLayout, u32)> {
+        for (separator, _) in id.rmatch_indices('.') {
+            let container_id = &id[..separator];
+            let index = id[separator + 1..]
+                .split('.')
+                .next()
+                .and_then(|index| index.parse::<u32>().ok());
+            if let (Some(index), Some(container)) = (index, self.elements.get(container_id)) {
+                if let Some(layout) = container.component_type.get_container_layout() {
+                    return Some((container_id, container, layout, index));
+                }
+            }
+        }
+        None
+    }
+    fn get_container_cells(&self, id: &str) -> u32 {
+        self.elements
+            .keys()
+            .filter_map(|element_id| {
+                self.get_container(element_id)
+                    .filter(|(container_id, ..)| *container_id == id)
+                    .map(|(.., index)| index + 1)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+    //children share the anchor of their container and are clipped to its area, given in gui units
+    fn get_placement(
+        &self,
+        id: &str,
+        element: &GUIElement,
+    ) -> (PositionAnchor, Vec2, Option<(Vec2, Vec2)>) {
+        match self.get_container(id) {
+            Some((container_id, container, layout, index)) => {
+                let (anchor, center, clip) = self.get_placement(container_id, container);
+                let cell = layout.get_cell_center(index);
+                let area = (
+                    Vec2 {
+                        x: center.x - layout.size.x / 2.,
+                        y: center.y - layout.size.y / 2.,
+                    },
+                    Vec2 {
+                        x: center.x + layout.size.x / 2.,
+                        y: center.y + layout.size.y / 2.,
+                    },
+                );
+                let area = match clip {
+                    Some((min, max)) => (
+                        Vec2 {
+                            x: area.0.x.max(min.x),
+                            y: area.0.y.max(min.y),
+                        },
+                        Vec2 {
+                            x: area.1.x.min(max.x),
+                            y: area.1.y.min(max.y),
+                        },
+                    ),
+                    None => area,
+                };
+                (
+                    anchor,
+                    Vec2 {
+                        x: center.x + cell.x + element.position.x as f32,
+                        y: center.y + cell.y + element.position.y as f32,
+                    },
+                    Some(area),
+                )
+            }
+            None => (element.anchor, self.get_layout_position(element), None),
+        }
+    }
+    fn mark_dirty(&mut self, id: &str) {
+        self.dirty.insert(id.to_string());
+        if self.elements.get(id).map_or(false, |element| {
+            element.component_type.get_container_layout().is_some()
+        }) {
+            let prefix = format!("{}.", id);
+            for element_id in self.elements.keys() {
+                if element_id.starts_with(prefix.as_str()) {
+                    self.dirty.insert(element_id.clone());
+                }
+            }
+        }
+    }
+    pub fn get_scroll_container(
+        &self,
+        mouse: PhysicalPosition<f64>,
+        size: PhysicalSize<u32>,
+    ) -> Option<String> {
+        let (id, element) = self.get_selected(mouse, size)?;
+        if element.component_type.get_container_layout().is_some() {
+            return Some(id.to_string());
+        }
+        self.get_container(id)
+            .map(|(container_id, ..)| container_id.to_string())
+    }
+    //scrolls by whole cells, returns the visible cells when they changed so the server can fill them
+    pub fn scroll_container(&mut self, id: &str, lines: f32) -> Option<(u32, u32)> {
+        let cells = self.get_container_cells(id);
+        let layout = self
+            .elements
+            .get(id)?
+            .component_type
+            .get_container_layout()?;
+        let previous = *self
+            .visible_ranges
+            .entry(id.to_string())
+            .or_insert(layout.get_visible_range());
+        let scroll =
+            (layout.scroll - lines * layout.cell_size.y).clamp(0., layout.get_max_scroll(cells));
+        self.elements
+            .get_mut(id)
+            .unwrap()
+            .component_type
+            .set_scroll(scroll);
+        self.mark_dirty(id);
+        let range = ContainerLayout { scroll, ..layout }.get_visible_range();
+        if range == previous {
+            return None;
+        }
+        self.visible_ranges.insert(id.to_string(), range);
+        Some(range)
+    }
+    fn get_layout_size(
+        &self,
+        element: &GUIElement,
+        size: Vec2,
+        aspect_ratio: f32,
+        gui_scale: f32,
+    ) -> Vec2 {
+        match element.size_percent {
+            Some(percent) => Vec2 {
+                x: percent.x / 100. * 2. * aspect_ratio / gui_scale,
+                y: percent.y / 100. * 2. / gui_scale,
+            },
+            None => size,
+        }
     }
     pub fn set_cursor_locked(&mut self, locked: bool) {
         self.cursor_locked = locked;
@@ -75,6 +446,31 @@ impl<'a> GUIRenderer<'a> {
             y: ((2. - (y * 2.)) - 1.) as f32,
         }
     }
+    pub fn get_hotbar_slot(
+        &self,
+        mouse: PhysicalPosition<f64>,
+        size: PhysicalSize<u32>,
+    ) -> Option<u32> {
+        let mouse = self.get_mouse_position(mouse, size);
+        let aspect_ratio = size.width as f32 / size.height as f32;
+        let gui_scale = self.get_layout_scale(aspect_ratio);
+        (0..self.hotbar.get_size()).find(|slot| {
+            Self::mouse_hovers(
+                mouse,
+                PositionAnchor::Bottom,
+                Vec2 {
+                    x: self.hotbar.get_slot_x(*slot as f32),
+                    y: Hotbar::SLOT_Y,
+                },
+                Vec2 {
+                    x: Hotbar::SLOT_SIZE,
+                    y: Hotbar::SLOT_SIZE,
+                },
+                gui_scale,
+                aspect_ratio,
+            )
+        })
+    }
     pub fn get_selected(
         &self,
         mouse: PhysicalPosition<f64>,
@@ -82,269 +478,1015 @@ impl<'a> GUIRenderer<'a> {
     ) -> Option<(&str, &GUIElement)> {
         let mouse = self.get_mouse_position(mouse, size);
         let aspect_ratio = size.width as f32 / size.height as f32;
+        let gui_scale = self.get_layout_scale(aspect_ratio);
+        let mut container = None;
         for (id, element) in &self.elements {
             let size = match &element.component_type {
                 GUIComponent::ImageComponent { size, .. } => Some(size),
                 GUIComponent::TextComponent { .. } => None,
                 GUIComponent::SlotComponent { size, .. } => Some(size),
                 GUIComponent::LineEdit { size, .. } => Some(size),
+                GUIComponent::TextInput { size, .. } => Some(size),
+                GUIComponent::ListContainer { size, .. } => Some(size),
+                GUIComponent::GridContainer { size, .. } => Some(size),
+                GUIComponent::GaugeComponent { size, .. } => Some(size),
             };
             if let Some(size) = size {
-                if Self::mouse_hovers(
-                    mouse,
-                    element.anchor,
-                    Vec2 {
-                        x: element.position.x as f32,
-                        y: element.position.y as f32,
-                    },
-                    *size,
-                    self.gui_scale,
-                    aspect_ratio,
-                ) {
-                    return Some((id.as_str(), element));
+                let (anchor, position, clip) = self.get_placement(id, element);
+                let visible = clip.map_or(true, |(min, max)| {
+                    let min = Self::to_screen(anchor, min, gui_scale, aspect_ratio, mouse);
+                    let max = Self::to_screen(anchor, max, gui_scale, aspect_ratio, mouse);
+                    mouse.x >= min.x && mouse.x <= max.x && mouse.y >= min.y && mouse.y <= max.y
+                });
+                if visible
+                    && Self::mouse_hovers(
+                        mouse,
+                        anchor,
+                        position,
+                        self.get_layout_size(element, *size, aspect_ratio, gui_scale),
+                        gui_scale,
+                        aspect_ratio,
+                    )
+                {
+                    //containers only get picked when none of their children is under the mouse
+                    if element.component_type.get_container_layout().is_some() {
+                        container = Some((id.as_str(), element));
+                    } else {
+                        return Some((id.as_str(), element));
+                    }
                 }
             }
         }
-        None
+        container
     }
+    //elements keep their own region of the vertex buffer and only changed ones are rebuilt, a few per frame
     pub fn draw(
         &mut self,
         device: &Device,
+        queue: &Queue,
         item_registry: &ItemRegistry,
         mouse_physical: PhysicalPosition<f64>,
         size: PhysicalSize<u32>,
-    ) -> (BufferSlice, u32) {
+    ) -> [(BufferSlice, u32); 2] {
         let aspect_ratio = size.width as f32 / size.height as f32;
         let mouse = self.get_mouse_position(mouse_physical, size);
+        let gui_scale = self.get_layout_scale(aspect_ratio);
+        if size != self.last_size {
+            self.last_size = size;
+            self.rebuild_all = true;
+        }
+        if mouse_physical != self.last_mouse {
+            self.last_mouse = mouse_physical;
+            let following: Vec<String> = self
+                .elements
+                .iter()
+                .filter(|(id, element)| self.get_placement(id, element).0 == PositionAnchor::Cursor)
+                .map(|(id, _)| id.clone())
+                .collect();
+            self.dirty.extend(following);
+        }
+        if self.is_text_input_focused() {
+            self.dirty.insert(self.selected.clone().unwrap());
+        }
+        //dynamic item models can change every frame
+        let animated: Vec<String> = self
+            .elements
+            .iter()
+            .filter(|(_, element)| match &element.component_type {
+                GUIComponent::SlotComponent {
+                    item_id: Some(item_id),
+                    ..
+                } => item_registry.get_item(item_id.0).dynamic.is_some(),
+                _ => false,
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        self.dirty.extend(animated);
+        if self.rebuild_all
+            || self.buffer_wasted > self.buffer_used / 2
+            || !self.update_buffer(queue, item_registry, aspect_ratio, gui_scale, mouse)
+        {
+            self.rebuild_buffer(device, item_registry, aspect_ratio, gui_scale, mouse);
+        }
         let mut vertices: Vec<GUIVertex> = Vec::new();
-        for element in self.elements.values() {
+        if self.screen_fade.0 > 0. {
+            Self::add_rect_vertices(
+                &mut vertices,
+                PositionAnchor::Center,
+                Vec2 { x: 0., y: 0. },
+                Vec2 {
+                    x: 2. * aspect_ratio / gui_scale,
+                    y: 2. / gui_scale,
+                },
+                TexCoords::ZERO,
+                Color {
+                    r: 0,
+                    g: 0,
+                    b: 0,
+                    a: (self.screen_fade.0 * 255.) as u8,
+                },
+                aspect_ratio,
+                gui_scale,
+                mouse,
+                50.,
+                None,
+            );
+        }
+        self.add_hotbar_vertices(&mut vertices, item_registry, aspect_ratio, gui_scale, mouse);
+        if let Some((_, element)) = self.get_selected(mouse_physical, size) {
             match &element.component_type {
-                GUIComponent::ImageComponent {
-                    texture: uv,
-                    size,
-                    slice,
+                GUIComponent::SlotComponent {
+                    item_id, tooltip, ..
                 } => {
+                    if let Some((item_id, _)) = item_id.as_ref() {
+                        let lines = if tooltip.is_empty() {
+                            vec![item_registry.get_item(*item_id).name.clone()]
+                        } else {
+                            tooltip.clone()
+                        };
+                        self.add_tooltip_vertices(&mut vertices, &lines, aspect_ratio, mouse);
+                    }
+                }
+                GUIComponent::GaugeComponent { tooltip, .. } => {
+                    if !tooltip.is_empty() {
+                        self.add_tooltip_vertices(&mut vertices, tooltip, aspect_ratio, mouse);
+                    }
+                }
+                _ => {}
+            }
+        } else if let Some(item) = self
+            .get_hotbar_slot(mouse_physical, size)
+            .filter(|_| !self.cursor_locked)
+            .and_then(|slot| self.hotbar.get_slot(slot))
+        {
+            let lines = vec![item_registry.get_item(item.item).name.clone()];
+            self.add_tooltip_vertices(&mut vertices, &lines, aspect_ratio, mouse);
+        }
+        self.color_palette.remap_vertices(&mut vertices);
+        if vertices.len() as u64 * Self::VERTEX_SIZE > self.overlay_buffer.size() {
+            self.overlay_buffer = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("gui overlay buffer"),
+                contents: bytemuck::cast_slice(vertices.as_slice()),
+                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            });
+        } else if !vertices.is_empty() {
+            queue.write_buffer(
+                &self.overlay_buffer,
+                0,
+                bytemuck::cast_slice(vertices.as_slice()),
+            );
+        }
+        [
+            (self.buffer.slice(..), self.buffer_used),
+            (self.overlay_buffer.slice(..), vertices.len() as u32),
+        ]
+    }
+    fn region_capacity(vertex_count: usize) -> u32 {
+        //spare room lets text and slices change without moving the element
+        (((vertex_count + vertex_count / 4) / 6 + 1) * 6) as u32
+    }
+    fn rebuild_buffer(
+        &mut self,
+        device: &Device,
+        item_registry: &ItemRegistry,
+        aspect_ratio: f32,
+        gui_scale: f32,
+        mouse: Vec2,
+    ) {
+        let mut vertices: Vec<GUIVertex> = Vec::new();
+        let mut geometry = HashMap::new();
+        for (id, element) in &self.elements {
+            let offset = vertices.len() as u32;
+            let element_vertices =
+                self.build_element(id, element, item_registry, aspect_ratio, gui_scale, mouse);
+            let capacity = Self::region_capacity(element_vertices.len());
+            vertices.extend(element_vertices);
+            vertices.resize((offset + capacity) as usize, GUIVertex::zeroed());
+            geometry.insert(id.clone(), ElementGeometry { offset, capacity });
+        }
+        self.buffer_used = vertices.len() as u32;
+        vertices.resize(
+            (self.buffer_used * 3 / 2).max(Self::MIN_BUFFER_VERTICES) as usize,
+            GUIVertex::zeroed(),
+        );
+        self.buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("gui buffer"),
+            contents: bytemuck::cast_slice(vertices.as_slice()),
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+        self.geometry = geometry;
+        self.dirty.clear();
+        self.cleared.clear();
+        self.buffer_wasted = 0;
+        self.rebuild_all = false;
+    }
+    //returns false when an element doesn't fit into the buffer anymore and everything has to be rebuilt
+    fn update_buffer(
+        &mut self,
+        queue: &Queue,
+        item_registry: &ItemRegistry,
+        aspect_ratio: f32,
+        gui_scale: f32,
+        mouse: Vec2,
+    ) -> bool {
+        for geometry in self.cleared.drain(..) {
+            queue.write_buffer(
+                &self.buffer,
+                geometry.offset as u64 * Self::VERTEX_SIZE,
+                bytemuck::cast_slice(
+                    vec![GUIVertex::zeroed(); geometry.capacity as usize].as_slice(),
+                ),
+            );
+        }
+        let ids: Vec<String> = self
+            .dirty
+            .iter()
+            .take(Self::REBUILD_BUDGET)
+            .cloned()
+            .collect();
+        for id in ids {
+            self.dirty.remove(&id);
+            let mut vertices = match self.elements.get(&id) {
+                Some(element) => {
+                    self.build_element(&id, element, item_registry, aspect_ratio, gui_scale, mouse)
+                }
+                None => continue,
+            };
+            let geometry = match self.geometry.get(&id).copied() {
+                Some(geometry) if vertices.len() as u32 <= geometry.capacity => geometry,
+                previous => {
+                    let capacity = Self::region_capacity(vertices.len());
+                    if (self.buffer_used + capacity) as u64 * Self::VERTEX_SIZE > self.buffer.size()
+                    {
+                        return false;
+                    }
+                    if let Some(previous) = previous {
+                        self.buffer_wasted += previous.capacity;
+                        queue.write_buffer(
+                            &self.buffer,
+                            previous.offset as u64 * Self::VERTEX_SIZE,
+                            bytemuck::cast_slice(
+                                vec![GUIVertex::zeroed(); previous.capacity as usize].as_slice(),
+                            ),
+                        );
+                    }
+                    let geometry = ElementGeometry {
+                        offset: self.buffer_used,
+                        capacity,
+                    };
+                    self.buffer_used += capacity;
+                    self.geometry.insert(id.clone(), geometry);
+                    geometry
+                }
+            };
+            vertices.resize(geometry.capacity as usize, GUIVertex::zeroed());
+            queue.write_buffer(
+                &self.buffer,
+                geometry.offset as u64 * Self::VERTEX_SIZE,
+                bytemuck::cast_slice(vertices.as_slice()),
+            );
+        }
+        true
+    }
+    fn build_element(
+        &self,
+        id: &str,
+        element: &GUIElement,
+        item_registry: &ItemRegistry,
+        aspect_ratio: f32,
+        gui_scale: f32,
+        mouse: Vec2,
+    ) -> Vec<GUIVertex> {
+        let mut vertices: Vec<GUIVertex> = Vec::new();
+        let (anchor, position, clip) = self.get_placement(id, element);
+        match &element.component_type {
+            GUIComponent::ImageComponent {
+                texture: uv,
+                size,
+                slice,
+            } => {
+                let size = self.get_layout_size(element, *size, aspect_ratio, gui_scale);
+                Self::add_rect_vertices(
+                    &mut vertices,
+                    anchor,
+                    position,
+                    size,
+                    if uv.is_empty() {
+                        TexCoords::ZERO
+                    } else {
+                        self.texture_atlas.get(uv.as_str())
+                    },
+                    element.base_color,
+                    aspect_ratio,
+                    gui_scale,
+                    mouse,
+                    element.position.z as f32,
+                    slice.as_ref(),
+                );
+            }
+            GUIComponent::SlotComponent {
+                background,
+                size,
+                item_id,
+                ..
+            } => {
+                let size = self.get_layout_size(element, *size, aspect_ratio, gui_scale);
+                if !background.is_empty() {
                     Self::add_rect_vertices(
                         &mut vertices,
-                        element.anchor,
-                        Vec2 {
-                            x: element.position.x as f32,
-                            y: element.position.y as f32,
-                        },
-                        *size,
-                        self.texture_atlas.get(uv.as_str()),
+                        anchor,
+                        position,
+                        size,
+                        self.texture_atlas.get(background.as_str()),
                         element.base_color,
                         aspect_ratio,
-                        self.gui_scale,
+                        gui_scale,
                         mouse,
                         element.position.z as f32,
-                        slice.as_ref(),
+                        None,
                     );
                 }
-                GUIComponent::SlotComponent {
-                    background,
-                    size,
-                    item_id,
-                } => {
-                    if !background.is_empty() {
-                        Self::add_rect_vertices(
-                            &mut vertices,
-                            element.anchor,
-                            Vec2 {
-                                x: element.position.x as f32,
-                                y: element.position.y as f32,
-                            },
-                            *size,
-                            self.texture_atlas.get(background.as_str()),
-                            element.base_color,
-                            aspect_ratio,
-                            self.gui_scale,
-                            mouse,
-                            element.position.z as f32,
-                            None,
-                        );
-                    }
-                    if let Some(item_id) = item_id.as_ref() {
-                        let item = item_registry.get_item(item_id.0);
-                        let size = Vec2 {
-                            x: size.x * (7. / 8.),
-                            y: size.y * (7. / 8.),
-                        };
-                        match &item.model {
-                            ItemModel::Texture { texture, .. } => {
-                                Self::add_rect_vertices(
-                                    &mut vertices,
-                                    element.anchor,
-                                    Vec2 {
-                                        x: element.position.x as f32,
-                                        y: element.position.y as f32,
-                                    },
-                                    size,
-                                    *texture,
-                                    Color::WHITE,
-                                    aspect_ratio,
-                                    self.gui_scale,
-                                    mouse,
-                                    element.position.z as f32 + 0.1,
-                                    None,
-                                );
-                            }
-                            ItemModel::Block { front, .. } => {
-                                Self::add_rect_vertices(
-                                    &mut vertices,
-                                    element.anchor,
-                                    Vec2 {
-                                        x: element.position.x as f32,
-                                        y: element.position.y as f32,
-                                    },
-                                    size,
-                                    *front,
-                                    Color::WHITE,
-                                    aspect_ratio,
-                                    self.gui_scale,
-                                    mouse,
-                                    element.position.z as f32 + 0.1,
-                                    None,
-                                );
-                            }
-                        }
-                        if item_id.1 != 1 {
-                            let text_size =
-                                self.text_renderer.get_size(20., &item_id.1.to_string());
-                            self.text_renderer.render(
-                                &mut vertices,
-                                element.anchor,
-                                Vec2 {
-                                    x: element.position.x as f32 + (size.x / 2.)
-                                        - (text_size.x / 2.),
-                                    y: element.position.y as f32 - (size.y / 2.)
-                                        + (text_size.y / 2.),
-                                },
-                                20.,
-                                &item_id.1.to_string(),
-                                Color {
-                                    r: 0,
-                                    g: 0,
-                                    b: 0,
-                                    a: 255,
-                                },
-                                &self.texture_atlas,
-                                aspect_ratio,
-                                self.gui_scale,
-                                mouse,
-                                element.position.z as f32 + 0.2,
-                                true,
-                            );
-                        }
-                    }
-                }
-                GUIComponent::TextComponent { text, font_size } => {
-                    self.text_renderer.render(
+                if let Some(item_id) = item_id.as_ref() {
+                    self.add_item_vertices(
                         &mut vertices,
-                        element.anchor,
-                        Vec2 {
-                            x: element.position.x as f32,
-                            y: element.position.y as f32,
-                        },
-                        *font_size,
-                        text,
-                        Color {
-                            r: 0,
-                            g: 0,
-                            b: 0,
-                            a: 255,
-                        },
-                        &self.texture_atlas,
+                        item_registry,
+                        *item_id,
+                        anchor,
+                        position,
+                        size,
                         aspect_ratio,
-                        self.gui_scale,
+                        gui_scale,
                         mouse,
                         element.position.z as f32,
-                        true,
                     );
                 }
+            }
+            GUIComponent::TextComponent { text, font_size } => {
+                self.text_renderer.render(
+                    &mut vertices,
+                    anchor,
+                    position,
+                    *font_size,
+                    text,
+                    Color {
+                        r: 0,
+                        g: 0,
+                        b: 0,
+                        a: 255,
+                    },
+                    &self.texture_atlas,
+                    aspect_ratio,
+                    gui_scale,
+                    mouse,
+                    element.position.z as f32,
+                    true,
+                );
+            }
 
-                GUIComponent::LineEdit { text, size } => {
-                    Self::add_rect_vertices(
-                        &mut vertices,
-                        element.anchor,
-                        Vec2 {
-                            x: element.position.x as f32,
-                            y: element.position.y as f32,
-                        },
-                        *size,
-                        TexCoords::ZERO,
+            GUIComponent::LineEdit { text, size } => {
+                let size = self.get_layout_size(element, *size, aspect_ratio, gui_scale);
+                Self::add_rect_vertices(
+                    &mut vertices,
+                    anchor,
+                    position,
+                    size,
+                    TexCoords::ZERO,
+                    Color {
+                        r: 0,
+                        g: 0,
+                        b: 0,
+                        a: 255,
+                    },
+                    aspect_ratio,
+                    gui_scale,
+                    mouse,
+                    element.position.z as f32 - 0.5,
+                    None,
+                );
+                self.text_renderer.render(
+                    &mut vertices,
+                    anchor,
+                    position,
+                    size.y,
+                    text,
+                    Color {
+                        r: 0,
+                        g: 0,
+                        b: 0,
+                        a: 255,
+                    },
+                    &self.texture_atlas,
+                    aspect_ratio,
+                    gui_scale,
+                    mouse,
+                    element.position.z as f32,
+                    true,
+                );
+            }
+            GUIComponent::TextInput {
+                text,
+                size,
+                placeholder,
+            } => {
+                let size = self.get_layout_size(element, *size, aspect_ratio, gui_scale);
+                let focused = self.selected.as_deref() == Some(id);
+                Self::add_rect_vertices(
+                    &mut vertices,
+                    anchor,
+                    position,
+                    size,
+                    TexCoords::ZERO,
+                    element.base_color,
+                    aspect_ratio,
+                    gui_scale,
+                    mouse,
+                    element.position.z as f32,
+                    None,
+                );
+                let font_size = size.y * 0.7;
+                let padding = size.y * 0.15;
+                let caret = Self::byte_index(text, self.caret.min(text.chars().count()));
+                let (shown, color) = if focused {
+                    (
+                        format!("{}{}{}", &text[..caret], self.preedit, &text[caret..]),
                         Color {
                             r: 0,
                             g: 0,
                             b: 0,
                             a: 255,
                         },
-                        aspect_ratio,
-                        self.gui_scale,
-                        mouse,
-                        element.position.z as f32 - 0.5,
-                        None,
-                    );
-                    self.text_renderer.render(
-                        &mut vertices,
-                        element.anchor,
-                        Vec2 {
-                            x: element.position.x as f32,
-                            y: element.position.y as f32,
+                    )
+                } else if text.is_empty() {
+                    (
+                        placeholder.clone(),
+                        Color {
+                            r: 100,
+                            g: 100,
+                            b: 100,
+                            a: 255,
                         },
-                        size.y,
-                        text,
+                    )
+                } else {
+                    (
+                        text.clone(),
                         Color {
                             r: 0,
                             g: 0,
                             b: 0,
                             a: 255,
                         },
-                        &self.texture_atlas,
+                    )
+                };
+                let left = position.x - size.x / 2. + padding;
+                let width = self.text_renderer.get_size(font_size, &shown).x;
+                self.text_renderer.render(
+                    &mut vertices,
+                    anchor,
+                    Vec2 {
+                        x: left + width / 2.,
+                        y: position.y,
+                    },
+                    font_size,
+                    &shown,
+                    color,
+                    &self.texture_atlas,
+                    aspect_ratio,
+                    gui_scale,
+                    mouse,
+                    element.position.z as f32,
+                    false,
+                );
+                if focused && self.caret_time < 0.5 {
+                    let caret_x = self
+                        .text_renderer
+                        .get_size(font_size, &shown[..caret + self.preedit.len()])
+                        .x;
+                    Self::add_rect_vertices(
+                        &mut vertices,
+                        anchor,
+                        Vec2 {
+                            x: left + caret_x,
+                            y: position.y,
+                        },
+                        Vec2 {
+                            x: 3.,
+                            y: font_size,
+                        },
+                        TexCoords::ZERO,
+                        color,
+                        aspect_ratio,
+                        gui_scale,
+                        mouse,
+                        element.position.z as f32 + 0.2,
+                        None,
+                    );
+                }
+            }
+            GUIComponent::GaugeComponent {
+                background,
+                texture,
+                size,
+                fill,
+                horizontal,
+                ..
+            } => {
+                let size = self.get_layout_size(element, *size, aspect_ratio, gui_scale);
+                if !background.is_empty() {
+                    Self::add_rect_vertices(
+                        &mut vertices,
+                        anchor,
+                        position,
+                        size,
+                        self.texture_atlas.get(background.as_str()),
+                        element.base_color,
                         aspect_ratio,
-                        self.gui_scale,
+                        gui_scale,
                         mouse,
                         element.position.z as f32,
-                        true,
+                        None,
+                    );
+                }
+                if !texture.is_empty() && *fill > 0. {
+                    Self::add_rect_vertices(
+                        &mut vertices,
+                        anchor,
+                        position,
+                        size,
+                        self.texture_atlas.get(texture.as_str()),
+                        Color::WHITE,
+                        aspect_ratio,
+                        gui_scale,
+                        mouse,
+                        element.position.z as f32 + 0.1,
+                        Some(&(
+                            Vec2::ZERO,
+                            if *horizontal {
+                                Vec2 { x: *fill, y: 1. }
+                            } else {
+                                Vec2 { x: 1., y: *fill }
+                            },
+                        )),
+                    );
+                }
+            }
+            GUIComponent::ListContainer { size, .. } | GUIComponent::GridContainer { size, .. } => {
+                if element.base_color.a > 0 {
+                    Self::add_rect_vertices(
+                        &mut vertices,
+                        anchor,
+                        position,
+                        *size,
+                        TexCoords::ZERO,
+                        element.base_color,
+                        aspect_ratio,
+                        gui_scale,
+                        mouse,
+                        element.position.z as f32,
+                        None,
                     );
                 }
             }
         }
-        if let Some((_, element)) = self.get_selected(mouse_physical, size) {
-            match &element.component_type {
-                GUIComponent::SlotComponent { item_id, .. } => {
-                    if let Some((item_id, _)) = item_id.as_ref() {
-                        let item = item_registry.get_item(*item_id);
-                        self.text_renderer.render(
-                            &mut vertices,
-                            PositionAnchor::Cursor,
-                            Vec2 { x: 0., y: 0. },
-                            50.,
-                            &item.name,
-                            Color {
-                                r: 0,
-                                g: 0,
-                                b: 0,
-                                a: 255,
-                            },
-                            &self.texture_atlas,
-                            aspect_ratio,
-                            self.gui_scale,
-                            mouse,
-                            100.,
-                            true,
-                        );
+        if let Some((min, max)) = clip {
+            Self::clip_triangles(
+                &mut vertices,
+                Self::to_screen(anchor, min, gui_scale, aspect_ratio, mouse),
+                Self::to_screen(anchor, max, gui_scale, aspect_ratio, mouse),
+            );
+        }
+        self.color_palette.remap_vertices(&mut vertices);
+        vertices
+    }
+    //rebuilt every frame so the animations don't dirty any elements, later parts are drawn over earlier ones
+    fn add_hotbar_vertices(
+        &self,
+        vertices: &mut Vec<GUIVertex>,
+        item_registry: &ItemRegistry,
+        aspect_ratio: f32,
+        gui_scale: f32,
+        mouse: Vec2,
+    ) {
+        let hotbar = &self.hotbar;
+        let slot_size = Vec2 {
+            x: Hotbar::SLOT_SIZE,
+            y: Hotbar::SLOT_SIZE,
+        };
+        let get_position = |slot: f32| Vec2 {
+            x: hotbar.get_slot_x(slot),
+            y: Hotbar::SLOT_Y,
+        };
+        let add_rect = |vertices: &mut Vec<GUIVertex>,
+                        position: Vec2,
+                        size: Vec2,
+                        uv: TexCoords,
+                        color: Color,
+                        depth: f32| {
+            Self::add_rect_vertices(
+                vertices,
+                PositionAnchor::Bottom,
+                position,
+                size,
+                uv,
+                color,
+                aspect_ratio,
+                gui_scale,
+                mouse,
+                depth,
+                None,
+            );
+        };
+        for slot in 0..hotbar.get_size() {
+            add_rect(
+                vertices,
+                get_position(slot as f32),
+                slot_size,
+                self.texture_atlas.get("bb:slot"),
+                Color::WHITE,
+                0.,
+            );
+        }
+        if let Some(indicator) = hotbar.get_indicator() {
+            let position = get_position(indicator);
+            let outer = Hotbar::SLOT_SIZE + 2. * Hotbar::INDICATOR_WIDTH;
+            let offset = (outer - Hotbar::INDICATOR_WIDTH) / 2.;
+            let horizontal = Vec2 {
+                x: outer,
+                y: Hotbar::INDICATOR_WIDTH,
+            };
+            let vertical = Vec2 {
+                x: Hotbar::INDICATOR_WIDTH,
+                y: outer,
+            };
+            for (x, y, size) in [
+                (0., offset, horizontal),
+                (0., -offset, horizontal),
+                (offset, 0., vertical),
+                (-offset, 0., vertical),
+            ] {
+                add_rect(
+                    vertices,
+                    Vec2 {
+                        x: position.x + x,
+                        y: position.y + y,
+                    },
+                    size,
+                    TexCoords::ZERO,
+                    Color::WHITE,
+                    0.05,
+                );
+            }
+        }
+        for slot in 0..hotbar.get_size() {
+            let item = match hotbar.get_slot(slot) {
+                Some(item) => item,
+                None => continue,
+            };
+            let position = get_position(slot as f32);
+            self.add_item_vertices(
+                vertices,
+                item_registry,
+                (item.item, item.count),
+                PositionAnchor::Bottom,
+                position,
+                slot_size,
+                aspect_ratio,
+                gui_scale,
+                mouse,
+                0.,
+            );
+            if let Some(durability) = item.durability.filter(|durability| *durability < 1.) {
+                let width = Hotbar::SLOT_SIZE * 0.75;
+                let y = position.y - Hotbar::SLOT_SIZE * 0.38;
+                add_rect(
+                    vertices,
+                    Vec2 { x: position.x, y },
+                    Vec2 { x: width, y: 8. },
+                    TexCoords::ZERO,
+                    Color::from_array([0, 0, 0, 255]),
+                    0.12,
+                );
+                add_rect(
+                    vertices,
+                    Vec2 {
+                        x: position.x - width * (1. - durability) / 2.,
+                        y,
+                    },
+                    Vec2 {
+                        x: width * durability,
+                        y: 8.,
+                    },
+                    TexCoords::ZERO,
+                    Color::from_array([
+                        (255. * (1. - durability)) as u8,
+                        (255. * durability) as u8,
+                        0,
+                        255,
+                    ]),
+                    0.13,
+                );
+            }
+            //the cover shrinks towards the top of the slot as the cooldown runs out
+            if let Some(cooldown) = hotbar.get_cooldown(slot) {
+                let height = Hotbar::SLOT_SIZE * cooldown;
+                add_rect(
+                    vertices,
+                    Vec2 {
+                        x: position.x,
+                        y: position.y + (Hotbar::SLOT_SIZE - height) / 2.,
+                    },
+                    Vec2 {
+                        x: Hotbar::SLOT_SIZE,
+                        y: height,
+                    },
+                    TexCoords::ZERO,
+                    Color::from_array([255, 255, 255, 130]),
+                    0.15,
+                );
+            }
+        }
+    }
+    //icon and count of an item filling a slot of the given size
+    fn add_item_vertices(
+        &self,
+        vertices: &mut Vec<GUIVertex>,
+        item_registry: &ItemRegistry,
+        (item_id, count): (u32, u32),
+        anchor: PositionAnchor,
+        position: Vec2,
+        size: Vec2,
+        aspect_ratio: f32,
+        gui_scale: f32,
+        mouse: Vec2,
+        depth: f32,
+    ) {
+        let item = item_registry.get_item(item_id);
+        let size = Vec2 {
+            x: size.x * (7. / 8.),
+            y: size.y * (7. / 8.),
+        };
+        match item.get_model() {
+            (ItemModel::Texture { texture, .. }, turns) if turns != 0. => {
+                Self::add_rotated_rect_vertices(
+                    vertices,
+                    anchor,
+                    position,
+                    size,
+                    *texture,
+                    turns,
+                    aspect_ratio,
+                    gui_scale,
+                    mouse,
+                    depth + 0.1,
+                );
+            }
+            (ItemModel::Texture { texture, .. }, _) => {
+                Self::add_rect_vertices(
+                    vertices,
+                    anchor,
+                    position,
+                    size,
+                    *texture,
+                    Color::WHITE,
+                    aspect_ratio,
+                    gui_scale,
+                    mouse,
+                    depth + 0.1,
+                    None,
+                );
+            }
+            (ItemModel::Block { up, front, right }, _) => {
+                Self::add_block_vertices(
+                    vertices,
+                    anchor,
+                    position,
+                    size,
+                    (*up, *front, *right),
+                    aspect_ratio,
+                    gui_scale,
+                    mouse,
+                    depth + 0.1,
+                );
+            }
+        }
+        if count != 1 {
+            let text_size = self.text_renderer.get_size(20., &count.to_string());
+            self.text_renderer.render(
+                vertices,
+                anchor,
+                Vec2 {
+                    x: position.x + (size.x / 2.) - (text_size.x / 2.),
+                    y: position.y - (size.y / 2.) + (text_size.y / 2.),
+                },
+                20.,
+                &count.to_string(),
+                Color {
+                    r: 0,
+                    g: 0,
+                    b: 0,
+                    a: 255,
+                },
+                &self.texture_atlas,
+                aspect_ratio,
+                gui_scale,
+                mouse,
+                depth + 0.2,
+                true,
+            );
+        }
+    }
+    fn add_tooltip_vertices(
+        &self,
+        vertices: &mut Vec<GUIVertex>,
+        lines: &[String],
+        aspect_ratio: f32,
+        mouse: Vec2,
+    ) {
+        let font_size = 40.;
+        let line_height = font_size * 1.2;
+        let padding = 10.;
+        let widths: Vec<f32> = lines
+            .iter()
+            .map(|line| self.text_renderer.get_size(font_size, line).x)
+            .collect();
+        let gui_scale = self.get_layout_scale(aspect_ratio);
+        let width = widths.iter().cloned().fold(0., f32::max) + padding * 2.;
+        let height = line_height * lines.len() as f32 + padding * 2.;
+        let left = 20.;
+        Self::add_rect_vertices(
+            vertices,
+            PositionAnchor::Cursor,
+            Vec2 {
+                x: left + width / 2.,
+                y: -height / 2.,
+            },
+            Vec2 {
+                x: width,
+                y: height,
+            },
+            TexCoords::ZERO,
+            Color::WHITE,
+            aspect_ratio,
+            gui_scale,
+            mouse,
+            100.,
+            None,
+        );
+        for (i, (line, line_width)) in lines.iter().zip(widths).enumerate() {
+            self.text_renderer.render(
+                vertices,
+                PositionAnchor::Cursor,
+                Vec2 {
+                    x: left + padding + line_width / 2.,
+                    y: -padding - line_height * (i as f32 + 0.5),
+                },
+                font_size,
+                line,
+                Color {
+                    r: 0,
+                    g: 0,
+                    b: 0,
+                    a: 255,
+                },
+                &self.texture_atlas,
+                aspect_ratio,
+                gui_scale,
+                mouse,
+                100.,
+                false,
+            );
+        }
+    }
+    fn to_screen(
+        anchor: PositionAnchor,
+        point: Vec2,
+        gui_scale: f32,
+        aspect_ratio: f32,
+        mouse: Vec2,
+    ) -> Vec2 {
+        let anchor = anchor.get_center(mouse);
+        Vec2 {
+            x: anchor.x + point.x * gui_scale / aspect_ratio,
+            y: anchor.y + point.y * gui_scale,
+        }
+    }
+    //gui geometry is cut to the clip area triangle by triangle, texture coordinates follow the cut edges
+    fn clip_triangles(vertices: &mut Vec<GUIVertex>, min: Vec2, max: Vec2) {
+        let mut clipped = Vec::with_capacity(vertices.len());
+        for triangle in vertices.chunks_exact(3) {
+            let mut polygon = triangle.to_vec();
+            for (axis, limit, below) in [
+                (0, min.x, false),
+                (0, max.x, true),
+                (1, min.y, false),
+                (1, max.y, true),
+            ] {
+                if polygon.is_empty() {
+                    break;
+                }
+                let inside = |vertex: &GUIVertex| {
+                    if below {
+                        vertex.position[axis] <= limit
+                    } else {
+                        vertex.position[axis] >= limit
+                    }
+                };
+                let mut output = Vec::new();
+                for (index, current) in polygon.iter().enumerate() {
+                    let previous = &polygon[(index + polygon.len() - 1) % polygon.len()];
+                    if inside(current) != inside(previous) {
+                        let t = (limit - previous.position[axis])
+                            / (current.position[axis] - previous.position[axis]);
+                        output.push(Self::lerp_vertex(previous, current, t));
+                    }
+                    if inside(current) {
+                        output.push(*current);
                     }
                 }
-                _ => {}
+                polygon = output;
+            }
+            for index in 1..polygon.len().saturating_sub(1) {
+                clipped.push(polygon[0]);
+                clipped.push(polygon[index]);
+                clipped.push(polygon[index + 1]);
             }
         }
-        self.buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("gui buffer"),
-            contents: bytemuck::cast_slice(vertices.as_slice()),
-            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-        });
-        (self.buffer.slice(..), vertices.len() as u32)
+        *vertices = clipped;
+    }
+    fn lerp_vertex(first: &GUIVertex, second: &GUIVertex, t: f32) -> GUIVertex {
+        let lerp = |first: f32, second: f32| first + (second - first) * t;
+        GUIVertex {
+            position: [
+                lerp(first.position[0], second.position[0]),
+                lerp(first.position[1], second.position[1]),
+                first.position[2],
+            ],
+            tex_coords: [
+                lerp(first.tex_coords[0], second.tex_coords[0]),
+                lerp(first.tex_coords[1], second.tex_coords[1]),
+            ],
+            color: first.color,
+        }
+    }
+    //draws the top, front and right faces of a cube as seen from above, darker towards the right like in the world
+    fn add_block_vertices(
+        vertices: &mut Vec<GUIVertex>,
+        anchor: PositionAnchor,
+        center: Vec2,
+        size: Vec2,
+        faces: (TexCoords, TexCoords, TexCoords),
+        aspect_ratio: f32,
+        gui_scale: f32,
+        mouse: Vec2,
+        depth: f32,
+    ) {
+        let (up, front, right) = faces;
+        let half_width = size.x * 0.45;
+        let half_height = size.y * 0.45;
+        let point = |x: f32, y: f32| {
+            Self::to_screen(
+                anchor,
+                Vec2 {
+                    x: center.x + x * half_width,
+                    y: center.y + y * half_height,
+                },
+                gui_scale,
+                aspect_ratio,
+                mouse,
+            )
+        };
+        let top = point(0., 1.);
+        let upper_left = point(-1., 0.5);
+        let upper_right = point(1., 0.5);
+        let middle = point(0., 0.);
+        let lower_left = point(-1., -0.5);
+        let lower_right = point(1., -0.5);
+        let bottom = point(0., -1.);
+        let depth = depth / 1000.;
+        for (corners, uv, shade) in [
+            ([top, upper_right, middle, upper_left], up, 255),
+            ([upper_left, middle, bottom, lower_left], front, 204),
+            ([middle, upper_right, lower_right, bottom], right, 153),
+        ] {
+            let color = shade + (shade << 8) + (shade << 16) + (255 << 24);
+            let uvs = [
+                [uv.u1, uv.v1],
+                [uv.u2, uv.v1],
+                [uv.u2, uv.v2],
+                [uv.u1, uv.v2],
+            ];
+            let vertex = |index: usize| GUIVertex {
+                position: [corners[index].x, corners[index].y, depth],
+                tex_coords: uvs[index],
+                color,
+            };
+            vertices.push(vertex(0));
+            vertices.push(vertex(3));
+            vertices.push(vertex(2));
+
+            vertices.push(vertex(2));
+            vertices.push(vertex(1));
+            vertices.push(vertex(0));
+        }
     }
     fn mouse_hovers(
         mouse: Vec2,
@@ -449,6 +1591,47 @@ impl<'a> GUIRenderer<'a> {
         vertices.push(vertex_2);
         vertices.push(vertex_1);
     }
+    //a white rect turned clockwise around its center
+    fn add_rotated_rect_vertices(
+        vertices: &mut Vec<GUIVertex>,
+        anchor: PositionAnchor,
+        center: Vec2,
+        size: Vec2,
+        uv: TexCoords,
+        turns: f32,
+        aspect_ratio: f32,
+        gui_scale: f32,
+        mouse: Vec2,
+        depth: f32,
+    ) {
+        let depth = depth / 1000.;
+        let anchor = anchor.get_center(mouse);
+        let (sin, cos) = (turns * TAU).sin_cos();
+        let corner = |x: f32, y: f32, u: f32, v: f32| {
+            let x = x * size.x / 2.;
+            let y = y * size.y / 2.;
+            GUIVertex {
+                position: [
+                    anchor.x + ((center.x + x * cos + y * sin) * gui_scale) / aspect_ratio,
+                    anchor.y + ((center.y - x * sin + y * cos) * gui_scale),
+                    depth,
+                ],
+                tex_coords: [u, v],
+                color: u32::MAX,
+            }
+        };
+        let top_left = corner(-1., 1., uv.u1, uv.v1);
+        let bottom_left = corner(-1., -1., uv.u1, uv.v2);
+        let bottom_right = corner(1., -1., uv.u2, uv.v2);
+        let top_right = corner(1., 1., uv.u2, uv.v1);
+        vertices.push(top_left);
+        vertices.push(bottom_left);
+        vertices.push(bottom_right);
+
+        vertices.push(bottom_right);
+        vertices.push(top_right);
+        vertices.push(top_left);
+    }
 }
 pub struct TextRenderer<'a> {
     pub font: rusttype::Font<'a>,
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use block_byte_common::messages::HotbarItem;
+
+//a gui the server lets the client draw itself, the server only sends the slots that changed
+pub struct Hotbar {
+    id: Option<String>,
+    slots: Vec<Option<HotbarItem>>,
+    selected: u32,
+    indicator: f32,
+    //remaining and total seconds per item client id
+    cooldowns: HashMap<u32, (f32, f32)>,
+}
+impl Hotbar {
+    //how fast the selection indicator catches up with the selected slot
+    const INDICATOR_SPEED: f32 = 20.;
+    pub const SLOT_SIZE: f32 = 100.;
+    pub const SLOT_SPACING: f32 = 130.;
+    pub const SLOT_Y: f32 = 100.;
+    pub const INDICATOR_WIDTH: f32 = 8.;
+    pub fn new() -> Self {
+        Hotbar {
+            id: None,
+            slots: Vec::new(),
+            selected: 0,
+            indicator: 0.,
+            cooldowns: HashMap::new(),
+        }
+    }
+    pub fn open(&mut self, id: String, size: u32) {
+        self.id = Some(id);
+        self.slots = vec![None; size as usize];
+        self.indicator = self.selected as f32;
+    }
+    //called with the prefix of removed gui elements
+    pub fn close(&mut self, prefix: &str) {
+        if self.id.as_ref().map_or(false, |id| id.starts_with(prefix)) {
+            self.id = None;
+            self.slots.clear();
+        }
+    }
+    pub fn set_slot(&mut self, slot: u32, item: Option<HotbarItem>) {
+        if let Some(slot) = self.slots.get_mut(slot as usize) {
+            *slot = item;
+        }
+    }
+    pub fn select(&mut self, slot: u32) {
+        //the indicator jumps instead of sliding when it wasn't shown before
+        if self.get_indicator().is_none() {
+            self.indicator = slot as f32;
+        }
+        self.selected = slot;
+    }
+    pub fn set_cooldown(&mut self, item: u32, seconds: f32) {
+        if seconds > 0. {
+            self.cooldowns.insert(item, (seconds, seconds));
+        } else {
+            self.cooldowns.remove(&item);
+        }
+    }
+    pub fn update(&mut self, delta_time: f32) {
+        let target = self.selected as f32;
+        self.indicator +=
+            (target - self.indicator) * (1. - (-Self::INDICATOR_SPEED * delta_time).exp());
+        if (target - self.indicator).abs() < 0.001 {
+            self.indicator = target;
+        }
+        self.cooldowns.retain(|_, (remaining, _)| {
+            *remaining -= delta_time;
+            *remaining > 0.
+        });
+    }
+    pub fn get_size(&self) -> u32 {
+        self.slots.len() as u32
+    }
+    pub fn get_slot(&self, slot: u32) -> Option<&HotbarItem> {
+        self.slots.get(slot as usize).and_then(|item| item.as_ref())
+    }
+    //the id the slot would have as a gui element, so clicks go to the server like any other gui click
+    pub fn get_element_id(&self, slot: u32) -> Option<String> {
+        self.id.as_ref().map(|id| format!("{}_{}", id, slot))
+    }
+    //in slots, between two slots while the indicator slides
+    pub fn get_indicator(&self) -> Option<f32> {
+        ((self.selected as usize) < self.slots.len()).then_some(self.indicator)
+    }
+    //part of the cooldown that is still left, from 1 down to 0
+    pub fn get_cooldown(&self, slot: u32) -> Option<f32> {
+        let item = self.get_slot(slot)?;
+        self.cooldowns
+            .get(&item.item)
+            .map(|(remaining, total)| remaining / total)
+    }
+    pub fn get_slot_x(&self, slot: f32) -> f32 {
+        (slot - (self.slots.len() as f32 - 1.) / 2.) * Self::SLOT_SPACING
+    }
+}
@@ -0,0 +1,35 @@
+use block_byte_common::KeyboardKey;
+
+use crate::settings::ClientSettings;
+
+pub struct KeybindRegistry {
+    actions: Vec<(String, KeyboardKey)>,
+}
+impl KeybindRegistry {
+    pub fn new() -> Self {
+        KeybindRegistry {
+            actions: Vec::new(),
+        }
+    }
+    //keys chosen in client settings take precedence over the defaults sent by the server
+    pub fn register(&mut self, settings: &mut ClientSettings, actions: Vec<(String, KeyboardKey)>) {
+        for (action, default_key) in actions {
+            let setting = format!("keybind.{}", action);
+            let key =
+                KeyboardKey::from_name(settings.get(&setting, &default_key.to_string()).as_str())
+                    .unwrap_or_else(|| {
+                        log::warn!("unknown key for keybind {}", action);
+                        default_key
+                    });
+            settings.set(&setting, key.to_string());
+            self.actions.retain(|(id, _)| id != &action);
+            self.actions.push((action, key));
+        }
+    }
+    pub fn get_actions(&self, key: KeyboardKey) -> impl Iterator<Item = &String> {
+        self.actions
+            .iter()
+            .filter(move |(_, action_key)| *action_key == key)
+            .map(|(action, _)| action)
+    }
+}
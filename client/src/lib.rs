@@ -1,18 +1,38 @@
 #![feature(fn_traits)]
 #![feature(map_many_mut)]
 #![feature(hash_extract_if)]
+mod accessibility;
+mod animation;
+mod build_assist;
 mod content;
+mod culling;
+mod debug_overlay;
+mod debug_shapes;
 mod game;
+mod gpu_timing;
 mod gui;
+mod hotbar;
+mod keybinds;
+pub mod mesher_bench;
 mod model;
 mod net;
+mod particles;
 mod render;
+mod replay;
+mod settings;
+mod settings_screen;
+mod sky;
+mod snapshot;
 mod texture;
+mod viewmodel;
 
 use array_init::array_init;
-use block_byte_common::messages::{ClientModelTarget, NetworkMessageC2S, NetworkMessageS2C};
+use block_byte_common::gui::{GUIComponent, GUIElement, PositionAnchor};
+use block_byte_common::messages::{
+    Capability, ClientModelTarget, MovementType, NetworkMessageC2S, NetworkMessageS2C,
+};
 use block_byte_common::{
-    BlockPosition, Direction, Face, KeyboardKey, KeyboardModifier, Position, AABB,
+    BlockPosition, Color, Direction, Face, KeyboardKey, KeyboardModifier, Position, AABB,
 };
 use cgmath::Point3;
 use std::collections::{HashMap, HashSet};
@@ -29,11 +49,25 @@ use winit::{
     window::WindowBuilder,
 };
 
-use crate::game::{ClientPlayer, EntityData, RaycastResult, World};
+use crate::accessibility::SoundCaptions;
+use crate::animation::AnimationStateMachine;
+use crate::build_assist::BuildAssist;
+use crate::content::{BlockRenderDataType, ItemModelState, SoundManager};
+use crate::debug_overlay::DebugOverlay;
+use crate::debug_shapes::DebugShapes;
+use crate::game::{get_hit_position, ClientPlayer, EntityData, RaycastResult, TextDisplay, World};
 use crate::gui::GUIRenderer;
+use crate::keybinds::KeybindRegistry;
 use crate::model::ModelInstanceData;
 use crate::net::SocketConnection;
+use crate::particles::ParticleManager;
 use crate::render::RenderState;
+use crate::settings::{
+    AccessibilitySettings, AudioSettings, ClientSettings, GraphicsSettings, Identity,
+    MouseSettings, PrivacySettings, ScrollSettings, ViewModelSettings,
+};
+use crate::settings_screen::SettingsScreen;
+use crate::viewmodel::ViewModelMotion;
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
@@ -48,20 +82,51 @@ pub async fn run() {
         }
     }
     let args: Vec<String> = args().collect();
+    let content_path = PathBuf::from(args.get(1).unwrap());
+    let mut settings = ClientSettings::load(if cfg!(target_arch = "wasm32") {
+        None
+    } else {
+        Some(PathBuf::from("client_settings.txt"))
+    });
+    let mut mouse_settings = MouseSettings::load(&mut settings);
+    mouse_settings.save(&mut settings);
+    let mut accessibility_settings = AccessibilitySettings::load(&mut settings);
+    accessibility_settings.save(&mut settings);
+    let mut scroll_settings = ScrollSettings::load(&mut settings);
+    scroll_settings.save(&mut settings);
+    let mut graphics_settings = GraphicsSettings::load(&mut settings);
+    graphics_settings.save(&mut settings);
+    let mut audio_settings = AudioSettings::load(&mut settings);
+    audio_settings.save(&mut settings);
+    let viewmodel_settings = ViewModelSettings::load(&mut settings);
+    viewmodel_settings.save(&mut settings);
+    let mut privacy_settings = PrivacySettings::load(&mut settings);
+    privacy_settings.save(&mut settings);
+    let identity = Identity::load(&mut settings);
+    let quic = settings.get("network.transport", "websocket") == "quic";
+    let mut chunk_recorder = Some(settings.get("debug.record_chunks", ""))
+        .filter(|path| !path.is_empty())
+        .and_then(|path| mesher_bench::ChunkRecorder::create(PathBuf::from(path)));
+    settings.save();
     let (
         texture_image,
         texture_atlas,
         block_registry,
-        item_registry,
+        mut item_registry,
         entity_registry,
+        biome_registry,
+        mut particle_registry,
         text_renderer,
         mut sound_manager,
-    ) = content::load_assets(PathBuf::from(args.get(1).unwrap()), false);
-    let block_registry = Rc::new(block_registry);
-    let entity_registry = Rc::new(entity_registry);
+    ) = content::load_assets(content_path.clone(), false);
+    let mut block_registry = Rc::new(block_registry);
+    let mut entity_registry = Rc::new(entity_registry);
 
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
+    let window = WindowBuilder::new()
+        .with_title("BlockByte")
+        .build(&event_loop)
+        .unwrap();
     window.set_cursor_grab(CursorGrabMode::Confined).ok();
     window.set_cursor_visible(false);
     #[cfg(target_arch = "wasm32")]
@@ -80,6 +145,7 @@ pub async fn run() {
             .expect("Couldn't append canvas to document body.");
     }
     let mut render_state = RenderState::new(window, texture_image).await;
+    render_state.set_reduced_motion(accessibility_settings.reduced_motion);
     let mut camera = ClientPlayer::at_position(
         Position {
             x: 0.,
@@ -89,10 +155,41 @@ pub async fn run() {
         block_registry.clone(),
     );
     let mut keys = HashSet::new();
-    let mut world = World::new(block_registry.clone(), entity_registry.clone());
+    let mut keybinds = KeybindRegistry::new();
+    let mut world = World::new(
+        block_registry.clone(),
+        entity_registry.clone(),
+        Rc::new(biome_registry),
+    );
+    render_state.crack_renderer.set_stages(&texture_atlas);
     let mut gui = GUIRenderer::new(texture_atlas, render_state.device(), text_renderer);
-    let mut connection = SocketConnection::new(args.get(2).unwrap());
+    gui.set_color_palette(accessibility_settings.color_palette);
+    apply_settings(
+        &mut render_state,
+        &mut gui,
+        &mut sound_manager,
+        &graphics_settings,
+        &audio_settings,
+    );
+    let mut settings_screen = SettingsScreen::new();
+    let mut sound_captions = SoundCaptions::new();
+    let mut particle_manager = ParticleManager::new();
+    let mut debug_shapes = DebugShapes::new();
+    let mut connection = if args.get(2).map(|arg| arg.as_str()) == Some("--replay") {
+        SocketConnection::replay(args.get(3).unwrap())
+            .unwrap_or_else(|error| panic!("couldn't load replay: {}", error))
+    } else {
+        SocketConnection::new(args.get(2).unwrap(), &identity, quic)
+    };
+    let replaying = connection.get_replay().is_some();
+    if replaying {
+        camera.set_abilities(1., MovementType::Spectator);
+    }
     let mut first_teleport = false;
+    let mut ping = None;
+    let mut server_capabilities = HashSet::new();
+    let mut waypoints = HashMap::new();
+    let mut debug_overlay = DebugOverlay::new();
     let mut last_render_time = Instant::now();
     let start_time = Instant::now();
     let mut fluid_selectable = false;
@@ -100,12 +197,18 @@ pub async fn run() {
     let mut last_position_sent = Instant::now();
 
     let mut block_breaking_manager = BlockBreakingManager::new();
+    let mut build_assist = BuildAssist::new();
+    let mut interaction_prompt: Option<String> = None;
+    let mut hand_item: Option<u32> = None;
+    let mut hotbar_scroll = 0f32;
 
     let mut player_entity_type = None;
 
     let text_input_channel = spawn_stdin_channel();
 
     let mut viewmodel_instance = ModelInstanceData::new();
+    let mut viewmodel_motion =
+        ViewModelMotion::new(viewmodel_settings, accessibility_settings.reduced_motion);
     #[allow(deprecated)]
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
@@ -124,14 +227,44 @@ pub async fn run() {
                 ..
             } => {
                 if let Some(virtual_keycode) = virtual_keycode.as_ref() {
-                    match state {
-                        ElementState::Pressed => {
-                            keys.insert(*virtual_keycode);
+                    //keys typed into a text input are not sent to the server as key presses
+                    if gui.is_text_input_focused() {
+                        match state {
+                            ElementState::Pressed => match virtual_keycode {
+                                VirtualKeyCode::Return
+                                | VirtualKeyCode::NumpadEnter
+                                | VirtualKeyCode::Escape => {
+                                    gui.select(None);
+                                    render_state.window().set_ime_allowed(false);
+                                }
+                                key => {
+                                    if let Some((id, text)) = gui.edit_text_input(*key) {
+                                        connection.send_message(
+                                            &NetworkMessageC2S::GuiTextChanged(id, text),
+                                        );
+                                    }
+                                }
+                            },
+                            ElementState::Released => {
+                                keys.remove(virtual_keycode);
+                            }
                         }
+                        return;
+                    }
+                    if *state == ElementState::Pressed && gui.is_cursor_locked() {
+                        if let Some(replay) = connection.get_replay() {
+                            if replay.on_key(*virtual_keycode) {
+                                return;
+                            }
+                        }
+                    }
+                    let repeat = match state {
+                        ElementState::Pressed => !keys.insert(*virtual_keycode),
                         ElementState::Released => {
                             keys.remove(virtual_keycode);
+                            false
                         }
-                    }
+                    };
                     let mut modifiers = 0;
                     if mods.contains(ModifiersState::SHIFT) {
                         modifiers |= KeyboardModifier::SHIFT;
@@ -142,13 +275,103 @@ pub async fn run() {
                     if mods.contains(ModifiersState::ALT) {
                         modifiers |= KeyboardModifier::ALT;
                     }
+                    let keyboard_key = keyboard_key_from_virtual_keycode(*virtual_keycode);
                     connection.send_message(&NetworkMessageC2S::Keyboard(
-                        keyboard_key_from_virtual_keycode(*virtual_keycode),
+                        keyboard_key,
                         modifiers,
                         *state == ElementState::Pressed,
-                        false,
+                        repeat,
                     ));
-                    if let Some(selected) = gui.selected.clone() {
+                    if !repeat {
+                        for action in keybinds.get_actions(keyboard_key) {
+                            connection.send_message(&NetworkMessageC2S::KeybindAction(
+                                action.clone(),
+                                *state == ElementState::Pressed,
+                            ));
+                        }
+                    }
+                    if *state == ElementState::Pressed
+                        && gui.is_cursor_locked()
+                        && build_assist.is_granted()
+                    {
+                        match virtual_keycode {
+                            VirtualKeyCode::B => build_assist.cycle_shape(),
+                            VirtualKeyCode::N => build_assist.cycle_mirror(),
+                            _ => {}
+                        }
+                    }
+                    if *state == ElementState::Pressed
+                        && !repeat
+                        && *virtual_keycode == VirtualKeyCode::Escape
+                    {
+                        if settings_screen.is_open() {
+                            settings_screen.close(&mut gui);
+                            set_cursor_lock(&render_state, &mut gui, true);
+                        } else if gui.is_cursor_locked() {
+                            settings_screen.open(
+                                &mut gui,
+                                &graphics_settings,
+                                &mouse_settings,
+                                &audio_settings,
+                                &privacy_settings,
+                                &accessibility_settings,
+                            );
+                            set_cursor_lock(&render_state, &mut gui, false);
+                        }
+                    }
+                    if *state == ElementState::Pressed
+                        && gui.is_cursor_locked()
+                        && *virtual_keycode == VirtualKeyCode::L
+                    {
+                        scroll_settings.hotbar_locked = !scroll_settings.hotbar_locked;
+                        hotbar_scroll = 0.;
+                        scroll_settings.save(&mut settings);
+                        settings.save();
+                    }
+                    if *state == ElementState::Pressed
+                        && !repeat
+                        && *virtual_keycode == VirtualKeyCode::F3
+                    {
+                        debug_overlay.toggle(&mut gui, &mut render_state.gpu_timer);
+                    }
+                    if *state == ElementState::Pressed
+                        && gui.is_cursor_locked()
+                        && *virtual_keycode == VirtualKeyCode::F
+                    {
+                        connection.send_message(&NetworkMessageC2S::SwapHands);
+                    }
+                    if *state == ElementState::Pressed
+                        && *virtual_keycode == VirtualKeyCode::F5
+                        && mods.contains(ModifiersState::CTRL)
+                    {
+                        let (
+                            texture_image,
+                            texture_atlas,
+                            new_block_registry,
+                            new_item_registry,
+                            new_entity_registry,
+                            new_biome_registry,
+                            new_particle_registry,
+                            text_renderer,
+                            new_sound_manager,
+                        ) = content::load_assets(content_path.clone(), false);
+                        render_state.set_texture(texture_image);
+                        render_state.crack_renderer.set_stages(&texture_atlas);
+                        gui.set_content(texture_atlas, text_renderer);
+                        block_registry = Rc::new(new_block_registry);
+                        entity_registry = Rc::new(new_entity_registry);
+                        item_registry = new_item_registry;
+                        sound_manager = new_sound_manager;
+                        sound_manager.set_volume(audio_settings.volume);
+                        particle_registry = new_particle_registry;
+                        camera.set_block_registry(block_registry.clone());
+                        world.set_registries(
+                            block_registry.clone(),
+                            entity_registry.clone(),
+                            Rc::new(new_biome_registry),
+                        );
+                    }
+                    if let Some(selected) = gui.get_selected_id().cloned() {
                         if let Some(text_edit) = gui.edit_element_text(selected.as_str()) {
                             let text = match virtual_keycode {
                                 VirtualKeyCode::Key1 | VirtualKeyCode::Numpad1 => "1",
@@ -218,9 +441,57 @@ pub async fn run() {
                     if *state == ElementState::Pressed {
                         let selected = gui
                             .get_selected(render_state.mouse, render_state.size())
-                            .map(|element| element.0.to_string());
-                        gui.selected = selected.clone();
-                        if let Some(id) = selected {
+                            .map(|element| element.0.to_string())
+                            .or_else(|| {
+                                gui.get_hotbar_slot(render_state.mouse, render_state.size())
+                                    .and_then(|slot| gui.get_hotbar().get_element_id(slot))
+                            });
+                        gui.select(selected.clone());
+                        render_state
+                            .window()
+                            .set_ime_allowed(gui.is_text_input_focused());
+                        if gui.is_text_input_focused() {
+                            render_state.window().set_ime_position(render_state.mouse);
+                        }
+                        if let Some(id) = selected
+                            .as_ref()
+                            .filter(|id| SettingsScreen::is_element(id))
+                        {
+                            if settings_screen.click(
+                                id,
+                                &mut gui,
+                                &mut graphics_settings,
+                                &mut mouse_settings,
+                                &mut audio_settings,
+                                &mut privacy_settings,
+                                &mut accessibility_settings,
+                            ) {
+                                apply_settings(
+                                    &mut render_state,
+                                    &mut gui,
+                                    &mut sound_manager,
+                                    &graphics_settings,
+                                    &audio_settings,
+                                );
+                                gui.set_color_palette(accessibility_settings.color_palette);
+                                render_state
+                                    .set_reduced_motion(accessibility_settings.reduced_motion);
+                                viewmodel_motion
+                                    .set_reduced_motion(accessibility_settings.reduced_motion);
+                                if !accessibility_settings.sound_captions {
+                                    sound_captions.clear(&mut gui);
+                                }
+                                graphics_settings.save(&mut settings);
+                                mouse_settings.save(&mut settings);
+                                audio_settings.save(&mut settings);
+                                privacy_settings.save(&mut settings);
+                                accessibility_settings.save(&mut settings);
+                                settings.save();
+                            }
+                            if !settings_screen.is_open() {
+                                set_cursor_lock(&render_state, &mut gui, true);
+                            }
+                        } else if let Some(id) = selected {
                             connection.send_message(&NetworkMessageC2S::GuiClick(
                                 id,
                                 match button {
@@ -246,6 +517,15 @@ pub async fn run() {
                 } else {
                     if *button == MouseButton::Left {
                         block_breaking_manager.set_left_click_held(*state == Pressed);
+                        if *state == Pressed {
+                            viewmodel_motion.swing();
+                        }
+                    }
+                    if *button == MouseButton::Right && *state == ElementState::Released {
+                        if let Some((positions, face)) = build_assist.finish() {
+                            connection
+                                .send_message(&NetworkMessageC2S::PlaceBlocks(positions, face));
+                        }
                     }
                     match world.raycast(5., camera.get_eye(), camera.make_front(), fluid_selectable)
                     {
@@ -262,12 +542,32 @@ pub async fn run() {
                         }
                         RaycastResult::Block(position, face) => match button {
                             MouseButton::Right => {
-                                if *state == ElementState::Pressed {
-                                    connection.send_message(&NetworkMessageC2S::RightClickBlock(
-                                        position,
-                                        face,
-                                        camera.is_shifting(),
-                                    ))
+                                if *state == ElementState::Pressed
+                                    && !build_assist.begin(position, face)
+                                {
+                                    if server_capabilities.contains(&Capability::PlacementHit) {
+                                        connection.send_message(
+                                            &NetworkMessageC2S::RightClickBlockAt(
+                                                position,
+                                                face,
+                                                camera.is_shifting(),
+                                                get_hit_position(
+                                                    camera.get_eye(),
+                                                    camera.make_front(),
+                                                    position,
+                                                    face,
+                                                ),
+                                            ),
+                                        )
+                                    } else {
+                                        connection.send_message(
+                                            &NetworkMessageC2S::RightClickBlock(
+                                                position,
+                                                face,
+                                                camera.is_shifting(),
+                                            ),
+                                        )
+                                    }
                                 }
                             }
                             _ => {}
@@ -276,14 +576,50 @@ pub async fn run() {
                     }
                 }
             }
+            WindowEvent::ReceivedCharacter(character) => {
+                if !gui.is_composing() {
+                    if let Some((id, text)) = gui.insert_text(character.to_string().as_str()) {
+                        connection.send_message(&NetworkMessageC2S::GuiTextChanged(id, text));
+                    }
+                }
+            }
+            WindowEvent::Ime(Ime::Preedit(preedit, _)) => {
+                gui.set_preedit(preedit.clone());
+            }
+            WindowEvent::Ime(Ime::Commit(committed)) => {
+                gui.set_preedit(String::new());
+                if let Some((id, text)) = gui.insert_text(committed.as_str()) {
+                    connection.send_message(&NetworkMessageC2S::GuiTextChanged(id, text));
+                }
+            }
             WindowEvent::MouseWheel { delta, .. } => match delta {
                 MouseScrollDelta::LineDelta(x, y) => {
-                    let x = *x as i32;
-                    let y = *y as i32;
                     if gui.is_cursor_locked() {
-                        connection.send_message(&NetworkMessageC2S::MouseScroll(x, y));
+                        if !camera.adjust_fly_speed(*y as i32) && !scroll_settings.hotbar_locked {
+                            hotbar_scroll += scroll_settings.apply(*y);
+                            let steps = hotbar_scroll.trunc();
+                            if steps != 0. {
+                                hotbar_scroll -= steps;
+                                connection.send_message(&NetworkMessageC2S::ChangeHandSlot(
+                                    -steps as i32,
+                                ));
+                            }
+                        }
                     } else {
-                        if let Some(element) =
+                        let x = *x as i32;
+                        let y = *y as i32;
+                        //containers scroll on the client, the server only hears which cells became visible
+                        if let Some(container) =
+                            gui.get_scroll_container(render_state.mouse, render_state.size())
+                        {
+                            if let Some((first, end)) =
+                                gui.scroll_container(container.as_str(), y as f32)
+                            {
+                                connection.send_message(&NetworkMessageC2S::GuiVisibleRange(
+                                    container, first, end,
+                                ));
+                            }
+                        } else if let Some(element) =
                             gui.get_selected(render_state.mouse, render_state.size())
                         {
                             connection.send_message(&NetworkMessageC2S::GuiScroll(
@@ -314,8 +650,8 @@ pub async fn run() {
         } => match event {
             DeviceEvent::MouseMotion { delta: (x, y) } => {
                 if gui.is_cursor_locked() {
-                    let sensitivity = 0.3;
-                    camera.update_orientation(-*y as f32 * sensitivity, -*x as f32 * sensitivity);
+                    let (x, y) = mouse_settings.apply(*x, *y);
+                    camera.update_orientation(-y, -x);
                 }
             }
             _ => {}
@@ -326,36 +662,122 @@ pub async fn run() {
             last_render_time = now;
             let dt = dt.as_secs_f32();
             camera.update_position(&keys, dt, &world);
-            render_state.window().set_title(&format!(
-                "BlockByte x: {} y: {} z: {} fps: {} {}",
-                (camera.position.x * 10.).floor() / 10.,
-                (camera.position.y * 10.).floor() / 10.,
-                (camera.position.z * 10.).floor() / 10.,
-                1. / dt,
-                block_breaking_manager
-                    .breaking_animation
-                    .as_ref()
-                    .map(|animation| format!(
-                        "breaking: {}%",
-                        (animation.0 / animation.1 * 100.) as u8
-                    ))
-                    .unwrap_or(String::new())
-            ));
+            debug_overlay.record_frame(dt);
             if let Some(animation) = viewmodel_instance.animation.as_mut() {
                 animation.1 += dt;
             }
+            viewmodel_instance.tick_poses(dt);
+            viewmodel_motion.tick(dt, camera.position, &mut viewmodel_instance);
             block_breaking_manager.tick(dt, &mut connection, keys.contains(&VirtualKeyCode::R));
+            gui.update_screen_fade(dt);
+            gui.update_caret(dt);
+            gui.get_hotbar().update(dt);
+            item_registry.update_dynamic_models(&ItemModelState {
+                position: Position::from(camera.position),
+                yaw: camera.yaw_deg.to_radians(),
+                time_of_day: render_state.sky_renderer.get_time_of_day(),
+                waypoints: &waypoints,
+                time: now.duration_since(start_time).as_secs_f32(),
+            });
+            particle_manager.tick(dt, &world);
+            particle_manager.tick_footsteps(dt, &world, &camera);
+            debug_shapes.tick(dt);
+            world.tick_entities(dt);
+            if accessibility_settings.sound_captions {
+                sound_captions.tick(dt, &mut gui);
+            }
             while let Ok(message) = text_input_channel.try_recv() {
                 connection.send_message(&NetworkMessageC2S::SendMessage(message));
             }
             let raycast =
                 world.raycast(5., camera.get_eye(), camera.make_front(), fluid_selectable);
+            if build_assist.is_placing() {
+                build_assist.update(&world.raycast(
+                    build_assist.get_reach(5.),
+                    camera.get_eye(),
+                    camera.make_front(),
+                    fluid_selectable,
+                ));
+            }
             block_breaking_manager.set_target_block(match raycast {
                 RaycastResult::Block(block, face) => Some((block, face)),
                 _ => None,
             });
+            let prompt = match raycast {
+                RaycastResult::Entity(id) => world.entities.get(&id).and_then(|entity| {
+                    entity_registry
+                        .get_entity(entity.type_id)
+                        .interaction_prompt
+                        .clone()
+                }),
+                RaycastResult::Block(position, _) => world
+                    .get_block(position)
+                    .and_then(|block| block_registry.get_block(block).interaction_prompt.clone()),
+                RaycastResult::Miss => None,
+            };
+            if prompt != interaction_prompt {
+                match &prompt {
+                    Some(text) => gui.set_element(
+                        "interaction_prompt".to_string(),
+                        GUIElement {
+                            component_type: GUIComponent::TextComponent {
+                                font_size: 25.,
+                                text: text.clone(),
+                            },
+                            position: Position {
+                                x: 0.,
+                                y: -60.,
+                                z: 0.,
+                            },
+                            anchor: PositionAnchor::Center,
+                            base_color: Color::WHITE,
+                            size_percent: None,
+                        },
+                    ),
+                    None => gui.remove_elements("interaction_prompt"),
+                }
+                interaction_prompt = prompt;
+            }
+            let ghost_block = match raycast {
+                RaycastResult::Block(position, face)
+                    if !build_assist.is_placing() && interaction_prompt.is_none() =>
+                {
+                    hand_item
+                        .and_then(|item| item_registry.get_item(item).block)
+                        .map(|block| (position.offset_by_face(face), block))
+                }
+                _ => None,
+            }
+            .filter(|(position, _)| {
+                world.get_block(*position).map_or(false, |block| {
+                    let block = block_registry.get_block(block);
+                    matches!(block.block_type, BlockRenderDataType::Air) || block.fluid
+                })
+            });
+            let ghost_blocked = ghost_block.map_or(false, |(position, block)| {
+                let block_aabb = AABB {
+                    x: position.x as f64,
+                    y: position.y as f64,
+                    z: position.z as f64,
+                    w: 1.,
+                    h: 1.,
+                    d: 1.,
+                };
+                !block_registry.get_block(block).no_collide
+                    && (camera
+                        .get_aabb()
+                        .map_or(false, |aabb| aabb.collides(&block_aabb))
+                        || world.collides_entity(&block_aabb))
+            });
+            render_state.ghost_renderer.set_block(
+                ghost_block.map(|(position, block)| (position, block_registry.get_block(block))),
+                ghost_blocked,
+            );
+            render_state
+                .crack_renderer
+                .set_block(block_breaking_manager.get_progress());
             render_state.outline_renderer.set_aabb(
-                match raycast {
+                build_assist.get_preview().or_else(|| match raycast {
                     RaycastResult::Entity(id) => {
                         let entity = world.entities.get(&id).unwrap();
                         let position = entity.position;
@@ -378,10 +800,12 @@ pub async fn run() {
                         d: 1.,
                     }),
                     RaycastResult::Miss => None,
-                },
+                }),
+                interaction_prompt.is_some() && !build_assist.is_placing(),
                 &render_state.queue,
             );
             for (_, dynamic_block_data) in &mut world.dynamic_blocks {
+                dynamic_block_data.model_instance.tick_poses(dt);
                 if let Some(animation) = dynamic_block_data.model_instance.animation.as_mut() {
                     animation.1 += dt;
                     animation.1 %= block_registry
@@ -396,11 +820,7 @@ pub async fn run() {
             if first_teleport && last_position_sent.elapsed().as_millis() > 100 {
                 last_position_sent = Instant::now();
                 connection.send_message(&NetworkMessageC2S::PlayerPosition(
-                    Position {
-                        x: camera.position.x as f64,
-                        y: camera.position.y as f64,
-                        z: camera.position.z as f64,
-                    },
+                    Position::from(camera.position),
                     camera.is_shifting(),
                     Direction {
                         pitch: camera.pitch_deg.to_radians() as f64,
@@ -409,15 +829,44 @@ pub async fn run() {
                     camera.last_moved,
                 ));
             }
-            for message in connection.read_messages() {
+            let messages = connection.read_messages();
+            //sounds and chat are left out while a replay catches up after seeking
+            let rebuilding = connection
+                .get_replay()
+                .map_or(false, |replay| replay.take_rebuilding());
+            if rebuilding {
+                world.clear();
+            }
+            for message in messages {
                 match message {
                     NetworkMessageS2C::SetBlock(block_position, id) => {
+                        spawn_block_break_particles(
+                            &world,
+                            &block_registry,
+                            &mut particle_manager,
+                            block_position,
+                            id,
+                        );
                         world.set_block(block_position, id);
                     }
-                    NetworkMessageS2C::LoadChunk(position, palette, blocks) => {
-                        let mut decoder = flate2::read::GzDecoder::new(blocks.as_slice());
-                        let mut blocks_data = Vec::new();
-                        std::io::copy(&mut decoder, &mut blocks_data).unwrap();
+                    NetworkMessageS2C::SetBlocks(chunk_position, blocks) => {
+                        for ((x, y, z), id) in &blocks {
+                            spawn_block_break_particles(
+                                &world,
+                                &block_registry,
+                                &mut particle_manager,
+                                BlockPosition {
+                                    x: chunk_position.x * 16 + *x as i32,
+                                    y: chunk_position.y * 16 + *y as i32,
+                                    z: chunk_position.z * 16 + *z as i32,
+                                },
+                                *id,
+                            );
+                        }
+                        world.set_blocks(chunk_position, blocks);
+                    }
+                    NetworkMessageS2C::LoadChunk(position, palette, blocks, biomes, climate) => {
+                        let blocks_data = connection.decompress_chunk(blocks);
                         let blocks: [[[u16; 16]; 16]; 16] =
                             bitcode::deserialize(blocks_data.as_slice()).unwrap();
                         let blocks = array_init(|x| {
@@ -425,7 +874,13 @@ pub async fn run() {
                                 array_init(|z| *palette.get(blocks[x][y][z] as usize).unwrap())
                             })
                         });
-                        world.load_chunk(position, blocks)
+                        if let Some(chunk_recorder) = &mut chunk_recorder {
+                            chunk_recorder.record(position, &blocks, &biomes, &climate);
+                        }
+                        world.load_chunk(position, blocks, biomes, climate)
+                    }
+                    NetworkMessageS2C::ChunkDictionary(dictionary) => {
+                        connection.set_chunk_dictionary(dictionary);
                     }
                     NetworkMessageS2C::UnloadChunk(position) => {
                         world.unload_chunk(position);
@@ -436,41 +891,34 @@ pub async fn run() {
                     NetworkMessageS2C::GuiRemoveElements(id) => {
                         gui.remove_elements(id.as_str());
                     }
+                    NetworkMessageS2C::GuiLayout(scale, safe_area) => {
+                        gui.set_server_layout(scale, safe_area);
+                    }
                     NetworkMessageS2C::GuiEditElement(id, edit) => {
                         if let Some(element) = gui.get_element(id) {
                             element.edit(edit);
                         }
                     }
                     NetworkMessageS2C::SetCursorLock(locked) => {
-                        gui.set_cursor_locked(locked);
-                        render_state
-                            .window()
-                            .set_cursor_grab(if locked {
-                                CursorGrabMode::Confined
-                            } else {
-                                CursorGrabMode::None
-                            })
-                            .ok();
-                        render_state.window().set_cursor_visible(!locked);
-                        render_state
-                            .window()
-                            .set_cursor_position(PhysicalPosition {
-                                x: render_state.size().width as f32 / 2.,
-                                y: render_state.size().height as f32 / 2.,
-                            })
-                            .ok();
+                        if settings_screen.is_open() {
+                            settings_screen.close(&mut gui);
+                        }
+                        set_cursor_lock(&render_state, &mut gui, locked);
                     }
-                    NetworkMessageS2C::AddEntity(type_id, id, position, rotation, animation, _) => {
+                    NetworkMessageS2C::AddEntity(type_id, id, position, rotation, state, _) => {
                         world.entities.insert(
                             id,
                             EntityData {
                                 type_id,
                                 position,
                                 rotation,
-                                model_instance: ModelInstanceData {
-                                    items: HashMap::new(),
-                                    animation: Some((animation, 0.)),
-                                },
+                                body_yaw: rotation.yaw,
+                                model_instance: ModelInstanceData::new(),
+                                animation: AnimationStateMachine::new(state),
+                                movement_speed: 0.,
+                                previous_position: position,
+                                age: 0.,
+                                pickup: None,
                             },
                         );
                     }
@@ -481,7 +929,32 @@ pub async fn run() {
                         }
                     }
                     NetworkMessageS2C::DeleteEntity(id) => {
-                        world.entities.remove(&id);
+                        if world
+                            .entities
+                            .get(&id)
+                            .map_or(true, |entity| entity.pickup.is_none())
+                        {
+                            world.entities.remove(&id);
+                        }
+                    }
+                    NetworkMessageS2C::PickupEntity(id, collector) => {
+                        if let Some(entity) = world.entities.get_mut(&id) {
+                            entity.pickup = Some((collector, 0.));
+                        }
+                    }
+                    NetworkMessageS2C::AddTextDisplay(id, position, text, background, scale) => {
+                        world.text_displays.insert(
+                            id,
+                            TextDisplay {
+                                position,
+                                text,
+                                background,
+                                scale,
+                            },
+                        );
+                    }
+                    NetworkMessageS2C::RemoveTextDisplay(id) => {
+                        world.text_displays.remove(&id);
                     }
                     NetworkMessageS2C::BlockBreakTimeResponse(id, time) => {
                         block_breaking_manager.on_block_break_time_response(id, time);
@@ -492,18 +965,36 @@ pub async fn run() {
                     NetworkMessageS2C::FluidSelectable(selectable) => {
                         fluid_selectable = selectable;
                     }
-                    NetworkMessageS2C::PlaySound(id, position, gain, pitch, relative) => {
+                    NetworkMessageS2C::PlaySound(..) if rebuilding => {}
+                    NetworkMessageS2C::PlaySound(id, position, gain, pitch, relative, category) => {
                         sound_manager.play_sound(id.as_str(), position, gain, pitch, relative);
+                        if accessibility_settings.sound_captions {
+                            sound_captions.on_sound(category, position, relative, &camera);
+                        }
+                    }
+                    NetworkMessageS2C::SpawnParticles(id, position, count, spread, velocity) => {
+                        if let Some(particle_data) = particle_registry.get_particle(id.as_str()) {
+                            particle_manager.spawn(
+                                particle_data,
+                                position,
+                                count,
+                                spread,
+                                velocity,
+                            );
+                        }
                     }
+                    NetworkMessageS2C::BlockBreakProgress(..) => {}
+                    NetworkMessageS2C::ChatMessage(..) if rebuilding => {}
                     NetworkMessageS2C::ChatMessage(message) => {
                         println!("[CHAT]{}", message);
                     }
                     NetworkMessageS2C::PlayerAbilities(speed, movement_type) => {
                         camera.set_abilities(speed, movement_type);
                     }
+                    //the camera of a replay is only placed once and moves freely after that
+                    NetworkMessageS2C::TeleportPlayer(..) if replaying && first_teleport => {}
                     NetworkMessageS2C::TeleportPlayer(position, rotation) => {
-                        camera.position =
-                            Point3::new(position.x as f32, position.y as f32, position.z as f32);
+                        camera.position = Point3::from(position);
                         camera.pitch_deg = rotation.pitch as f32;
                         camera.yaw_deg = rotation.yaw as f32;
                         first_teleport = true;
@@ -523,52 +1014,78 @@ pub async fn run() {
                             model_instance.animation = Some((animation, 0.));
                         }
                     }
-                    NetworkMessageS2C::ModelItem(target, slot, item) => {
-                        let model_data = match target {
-                            ClientModelTarget::Block(position) => {
-                                let block_id = world.get_block(position);
-                                world.get_dynamic_block_data(position).map(|block| {
-                                    (
-                                        &mut block.model_instance,
-                                        block_registry
-                                            .get_block(block_id.unwrap())
-                                            .dynamic
-                                            .as_ref()
-                                            .and_then(|model| model.get_item_slot(slot))
-                                            .unwrap(),
-                                    )
-                                })
-                            }
-                            ClientModelTarget::Entity(id) => {
-                                world.entities.get_mut(&id).map(|entity| {
-                                    (
-                                        &mut entity.model_instance,
-                                        entity_registry
-                                            .get_entity(entity.type_id)
-                                            .model
-                                            .get_item_slot(slot)
-                                            .unwrap(),
-                                    )
-                                })
-                            }
-                            ClientModelTarget::ViewModel => player_entity_type
+                    NetworkMessageS2C::ModelBonePose(target, path, pose) => {
+                        let model_instance = match target {
+                            ClientModelTarget::Block(position) => world
+                                .get_dynamic_block_data(position)
+                                .map(|block| &mut block.model_instance),
+                            ClientModelTarget::Entity(id) => world
+                                .entities
+                                .get_mut(&id)
+                                .map(|entity| &mut entity.model_instance),
+                            ClientModelTarget::ViewModel => Some(&mut viewmodel_instance),
+                        };
+                        if let Some(model_instance) = model_instance {
+                            model_instance.set_pose(
+                                path.into_iter().map(|index| index as usize).collect(),
+                                pose,
+                            );
+                        }
+                    }
+                    NetworkMessageS2C::EntityAnimationState(id, state) => {
+                        if let Some(entity) = world.entities.get_mut(&id) {
+                            entity.animation.set_state(
+                                state,
+                                &entity_registry.get_entity(entity.type_id).animation_states,
+                            );
+                        }
+                    }
+                    NetworkMessageS2C::ModelItem(target, items) => {
+                        //the viewmodel swaps items once it is lowered
+                        if let ClientModelTarget::ViewModel = target {
+                            let viewmodel = player_entity_type
                                 .as_ref()
                                 .map(|id| entity_registry.get_entity(*id))
-                                .and_then(|entity| entity.viewmodel.as_ref())
-                                .map(|viewmodel| {
-                                    (
-                                        &mut viewmodel_instance,
-                                        viewmodel.get_item_slot(slot).unwrap(),
-                                    )
-                                }),
-                        };
-                        if let Some((model_instance, slot)) = model_data {
-                            match item {
-                                Some(item) => {
-                                    model_instance.items.insert(slot.clone(), item);
+                                .and_then(|entity| entity.viewmodel.as_ref());
+                            for (slot, item) in items {
+                                if slot == 0 {
+                                    hand_item = item;
                                 }
-                                None => {
-                                    model_instance.items.remove(slot);
+                                if let Some(slot) =
+                                    viewmodel.and_then(|viewmodel| viewmodel.get_item_slot(slot))
+                                {
+                                    viewmodel_motion.change_item(slot.clone(), item);
+                                }
+                            }
+                        } else if let Some((model_instance, model)) =
+                            world.get_model_instance(&target)
+                        {
+                            for (slot, item) in items {
+                                let slot = model.get_item_slot(slot).unwrap();
+                                match item {
+                                    Some(item) => {
+                                        model_instance.items.insert(slot.clone(), item);
+                                    }
+                                    None => {
+                                        model_instance.items.remove(slot);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    NetworkMessageS2C::ModelItemTransform(target, transforms) => {
+                        if let Some((model_instance, model)) = world.get_model_instance(&target) {
+                            for (slot, transform) in transforms {
+                                let slot = model.get_item_slot(slot).unwrap();
+                                match transform {
+                                    Some(transform) => {
+                                        model_instance
+                                            .item_transforms
+                                            .insert(slot.clone(), transform);
+                                    }
+                                    None => {
+                                        model_instance.item_transforms.remove(slot);
+                                    }
                                 }
                             }
                         }
@@ -585,9 +1102,99 @@ pub async fn run() {
                             )
                         });
                         viewmodel_instance = ModelInstanceData::new();
+                        viewmodel_motion.reset();
+                        hand_item = None;
+                    }
+                    NetworkMessageS2C::ScreenFade(target, duration) => {
+                        gui.set_screen_fade(target, duration);
+                    }
+                    NetworkMessageS2C::SetWorldBorder(border) => {
+                        world.border = border;
+                    }
+                    NetworkMessageS2C::SetSky(sky) => {
+                        render_state.sky_renderer.set_settings(sky);
+                    }
+                    NetworkMessageS2C::SetBuildAssist(reach) => {
+                        build_assist.set_reach(reach);
+                    }
+                    NetworkMessageS2C::RegisterKeybinds(actions) => {
+                        keybinds.register(&mut settings, actions);
+                        settings.save();
+                    }
+                    NetworkMessageS2C::Disconnect(reason) => {
+                        show_disconnect_screen(
+                            &render_state,
+                            &mut gui,
+                            format!("Disconnected: {}", reason),
+                        );
+                    }
+                    NetworkMessageS2C::JoinFailed(failure) => {
+                        show_disconnect_screen(
+                            &render_state,
+                            &mut gui,
+                            format!("Couldn't join: {}", failure.describe()),
+                        );
+                    }
+                    NetworkMessageS2C::Welcome(hello) => {
+                        log::info!(
+                            "server uses protocol {} with {}",
+                            hello.version,
+                            hello.capabilities.join(", ")
+                        );
+                        server_capabilities = hello.get_capabilities();
+                    }
+                    NetworkMessageS2C::Ping(id, round_trip) => {
+                        connection.send_message(&NetworkMessageC2S::Pong(id));
+                        ping = round_trip;
+                    }
+                    NetworkMessageS2C::HotbarOpen(id, size) => {
+                        gui.get_hotbar().open(id, size);
+                    }
+                    NetworkMessageS2C::HotbarSlot(slot, item) => {
+                        gui.get_hotbar().set_slot(slot, item);
+                    }
+                    NetworkMessageS2C::HotbarSelect(slot) => {
+                        gui.get_hotbar().select(slot);
+                    }
+                    NetworkMessageS2C::ItemCooldown(item, seconds) => {
+                        gui.get_hotbar().set_cooldown(item, seconds);
+                    }
+                    NetworkMessageS2C::SetWaypoint(name, position) => match position {
+                        Some(position) => {
+                            waypoints.insert(name, position);
+                        }
+                        None => {
+                            waypoints.remove(&name);
+                        }
+                    },
+                    NetworkMessageS2C::RequestSnapshot(id, max_size) => {
+                        if privacy_settings.allow_snapshots {
+                            render_state.snapshots.request(id, max_size);
+                        } else {
+                            connection.send_message(&NetworkMessageC2S::Snapshot(id, None));
+                        }
+                    }
+                    NetworkMessageS2C::SetDebugShapes(name, shapes, lifetime) => {
+                        debug_shapes.set(name, shapes, lifetime);
                     }
                 }
             }
+            if let Some(vertices) = debug_shapes.take_vertices() {
+                render_state.set_debug_shapes(&vertices);
+            }
+            if rebuilding {
+                particle_manager = ParticleManager::new();
+            }
+            if let Some(replay) = connection.get_replay() {
+                replay.show_status(&mut gui);
+            }
+            while let Some((id, snapshot)) = render_state.poll_snapshot() {
+                connection.send_message(&NetworkMessageC2S::Snapshot(id, snapshot));
+            }
+            render_state.snapshots.tick_indicator(dt, &mut gui);
+            if let Some(error) = connection.take_error() {
+                show_disconnect_screen(&render_state, &mut gui, format!("Disconnected: {}", error));
+            }
 
             match render_state.render(
                 &camera,
@@ -599,10 +1206,26 @@ pub async fn run() {
                     .as_ref()
                     .map(|id| entity_registry.get_entity(*id))
                     .and_then(|entity| entity.viewmodel.as_ref())
-                    .map(|model| (model, &viewmodel_instance)),
+                    .map(|model| (model, &viewmodel_instance, viewmodel_motion.get_transform())),
+                &particle_manager,
                 now.duration_since(start_time).as_millis() as f32 / 1000.,
             ) {
-                Ok(_) => {}
+                Ok(_) => {
+                    if debug_overlay.is_enabled() {
+                        debug_overlay.show(
+                            &mut gui,
+                            &camera,
+                            &world,
+                            &raycast,
+                            &render_state.gpu_timer,
+                            &render_state.draw_stats,
+                            ping,
+                            block_breaking_manager
+                                .breaking_animation
+                                .map(|animation| animation.0 / animation.1),
+                        );
+                    }
+                }
                 Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
                     render_state.resize(render_state.size())
                 }
@@ -624,6 +1247,7 @@ struct BlockBreakingManager {
     key_down: bool,
     breaking_animation: Option<(f32, f32)>,
     just_pressed: bool,
+    aborted: bool,
 }
 impl BlockBreakingManager {
     pub fn new() -> Self {
@@ -634,6 +1258,7 @@ impl BlockBreakingManager {
             key_down: false,
             time_requested: false,
             just_pressed: false,
+            aborted: false,
         }
     }
     pub fn tick(
@@ -665,8 +1290,25 @@ impl BlockBreakingManager {
                 }
             }
         }
+        if self.aborted {
+            self.aborted = false;
+            connection.send_message(&NetworkMessageC2S::AbortBlockBreak);
+        }
         self.just_pressed = false;
     }
+    pub fn get_progress(&self) -> Option<(BlockPosition, f32)> {
+        match (self.target_block, self.breaking_animation) {
+            (Some(target_block), Some((time, total))) if total > 0. => {
+                Some((target_block.0, time / total))
+            }
+            _ => None,
+        }
+    }
+    fn abort(&mut self) {
+        if self.breaking_animation.take().is_some() {
+            self.aborted = true;
+        }
+    }
     pub fn on_block_break_time_response(&mut self, id: u32, time: f32) {
         if self.id == id {
             self.breaking_animation = Some((0., time));
@@ -680,7 +1322,7 @@ impl BlockBreakingManager {
         self.time_requested = false;
         self.key_down = held;
         if !held {
-            self.breaking_animation = None;
+            self.abort();
         }
     }
     pub fn set_target_block(&mut self, block: Option<(BlockPosition, Face)>) {
@@ -688,13 +1330,74 @@ impl BlockBreakingManager {
             (Some(previous), Some(current)) => previous.0 != current.0,
             _ => true,
         } {
-            self.breaking_animation = None;
+            self.abort();
             self.time_requested = false;
         }
         self.target_block = block;
     }
 }
 
+fn set_cursor_lock(render_state: &RenderState, gui: &mut GUIRenderer, locked: bool) {
+    gui.set_cursor_locked(locked);
+    if locked {
+        gui.select(None);
+        render_state.window().set_ime_allowed(false);
+    }
+    render_state
+        .window()
+        .set_cursor_grab(if locked {
+            CursorGrabMode::Confined
+        } else {
+            CursorGrabMode::None
+        })
+        .ok();
+    render_state.window().set_cursor_visible(!locked);
+    render_state
+        .window()
+        .set_cursor_position(PhysicalPosition {
+            x: render_state.size().width as f32 / 2.,
+            y: render_state.size().height as f32 / 2.,
+        })
+        .ok();
+}
+fn show_disconnect_screen(render_state: &RenderState, gui: &mut GUIRenderer, text: String) {
+    gui.set_element(
+        "disconnect".to_string(),
+        GUIElement {
+            component_type: GUIComponent::TextComponent {
+                font_size: 30.,
+                text,
+            },
+            position: Position {
+                x: 0.,
+                y: 0.,
+                z: 10.,
+            },
+            anchor: PositionAnchor::Center,
+            base_color: Color::WHITE,
+            size_percent: None,
+        },
+    );
+    gui.set_cursor_locked(false);
+    render_state
+        .window()
+        .set_cursor_grab(CursorGrabMode::None)
+        .ok();
+    render_state.window().set_cursor_visible(true);
+}
+fn apply_settings(
+    render_state: &mut RenderState,
+    gui: &mut GUIRenderer,
+    sound_manager: &mut SoundManager,
+    graphics: &GraphicsSettings,
+    audio: &AudioSettings,
+) {
+    render_state.set_fov(graphics.fov);
+    render_state.set_render_distance(graphics.render_distance);
+    render_state.set_vsync(graphics.vsync);
+    gui.set_gui_scale(graphics.gui_scale);
+    sound_manager.set_volume(audio.volume);
+}
 fn spawn_stdin_channel() -> std::sync::mpsc::Receiver<String> {
     let (tx, rx) = std::sync::mpsc::channel::<String>();
     std::thread::spawn(move || loop {
@@ -704,6 +1407,26 @@ fn spawn_stdin_channel() -> std::sync::mpsc::Receiver<String> {
     });
     rx
 }
+fn spawn_block_break_particles(
+    world: &World,
+    block_registry: &content::BlockRegistry,
+    particle_manager: &mut ParticleManager,
+    block_position: BlockPosition,
+    id: u32,
+) {
+    if let Some(previous) = world.get_block(block_position) {
+        if previous != id
+            && matches!(
+                block_registry.get_block(id).block_type,
+                BlockRenderDataType::Air
+            )
+        {
+            if let Some(texture) = block_registry.get_block(previous).get_particle_texture() {
+                particle_manager.spawn_block_break(block_position, texture);
+            }
+        }
+    }
+}
 pub fn keyboard_key_from_virtual_keycode(keycode: VirtualKeyCode) -> KeyboardKey {
     match keycode {
         VirtualKeyCode::Key1 => KeyboardKey::Key1,
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::env::args;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use array_init::array_init;
+use block_byte_common::{ChunkPosition, Face, FaceStorage};
+
+use crate::content::{self, BiomeRegistry, BlockData, BlockRegistry, BlockRenderDataType};
+use crate::game::{Chunk, MeshingMode};
+
+type RecordedChunk = (ChunkPosition, Vec<u32>, Vec<u16>, Vec<[u8; 2]>);
+
+//appends every chunk received from the server, block ids are only valid with the same content
+pub struct ChunkRecorder {
+    file: File,
+}
+impl ChunkRecorder {
+    pub fn create(path: PathBuf) -> Option<Self> {
+        match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => Some(ChunkRecorder { file }),
+            Err(error) => {
+                log::warn!(
+                    "couldn't open chunk recording {}: {}",
+                    path.display(),
+                    error
+                );
+                None
+            }
+        }
+    }
+    pub fn record(
+        &mut self,
+        position: ChunkPosition,
+        blocks: &[[[u32; 16]; 16]; 16],
+        biomes: &Vec<u16>,
+        climate: &Vec<[u8; 2]>,
+    ) {
+        let chunk: RecordedChunk = (
+            position,
+            blocks.iter().flatten().flatten().cloned().collect(),
+            biomes.clone(),
+            climate.clone(),
+        );
+        let data = bitcode::serialize(&chunk).unwrap();
+        let mut record = (data.len() as u32).to_le_bytes().to_vec();
+        record.extend(data);
+        if let Err(error) = self.file.write_all(record.as_slice()) {
+            log::warn!("couldn't record chunk: {}", error);
+        }
+    }
+}
+fn load_recording(path: PathBuf) -> HashMap<ChunkPosition, Chunk> {
+    let data = std::fs::read(&path).expect("chunk recording not found");
+    let mut chunks = HashMap::new();
+    let mut rest = data.as_slice();
+    while rest.len() >= 4 {
+        let length = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+        let (position, blocks, biomes, climate): RecordedChunk =
+            bitcode::deserialize(&rest[4..4 + length]).expect("chunk recording invalid");
+        let blocks = array_init(|x| array_init(|y| array_init(|z| blocks[x * 256 + y * 16 + z])));
+        chunks.insert(position, Chunk::new(position, blocks, biomes, climate));
+        rest = &rest[4 + length..];
+    }
+    chunks
+}
+
+struct BlockMix {
+    opaque: u32,
+    transparent: u32,
+    fluid: u32,
+    foliage: u32,
+}
+impl BlockMix {
+    fn from_registry(block_registry: &BlockRegistry) -> Self {
+        let find = |predicate: &dyn Fn(&BlockData) -> bool| {
+            (0..block_registry.get_count())
+                .find(|id| predicate(block_registry.get_block(*id)))
+                .unwrap_or(0)
+        };
+        let is_cube = |block: &BlockData| matches!(block.block_type, BlockRenderDataType::Cube(_));
+        BlockMix {
+            opaque: find(&|block| is_cube(block) && !block.transparent && !block.fluid),
+            transparent: find(&|block| is_cube(block) && block.transparent && !block.fluid),
+            fluid: find(&|block| block.fluid),
+            foliage: find(&|block| matches!(block.block_type, BlockRenderDataType::Foliage(_))),
+        }
+    }
+    //synthetic chunks covering the usual terrain and the worst cases for both meshers
+    fn create_chunks(&self) -> Vec<(&'static str, [[[u32; 16]; 16]; 16])> {
+        let mut seed: u32 = 0x9E3779B9;
+        let mut random = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed
+        };
+        vec![
+            (
+                "solid",
+                array_init(|_| array_init(|_| array_init(|_| self.opaque))),
+            ),
+            (
+                "terrain",
+                array_init(|_| {
+                    array_init(|y| {
+                        array_init(|_| match y {
+                            0..=7 => self.opaque,
+                            8 => self.foliage,
+                            _ => 0,
+                        })
+                    })
+                }),
+            ),
+            (
+                "water",
+                array_init(|_| array_init(|y| array_init(|_| if y < 8 { self.fluid } else { 0 }))),
+            ),
+            (
+                "checkerboard",
+                array_init(|x| {
+                    array_init(|y| {
+                        array_init(|z| if (x + y + z) % 2 == 0 { self.opaque } else { 0 })
+                    })
+                }),
+            ),
+            (
+                "random",
+                array_init(|_| {
+                    array_init(|_| {
+                        array_init(|_| match random() % 5 {
+                            0 => self.opaque,
+                            1 => self.transparent,
+                            2 => self.fluid,
+                            3 => self.foliage,
+                            _ => 0,
+                        })
+                    })
+                }),
+            ),
+        ]
+    }
+}
+
+//average time and vertex count per chunk
+fn measure(
+    chunks: &[(&Chunk, FaceStorage<&Chunk>)],
+    block_registry: &BlockRegistry,
+    biome_registry: &BiomeRegistry,
+    mode: MeshingMode,
+    iterations: u32,
+) -> (Duration, usize) {
+    let mut vertices = 0;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        vertices = 0;
+        for (chunk, neighbors) in chunks {
+            vertices += chunk
+                .build_mesh(block_registry, biome_registry, neighbors, mode)
+                .get_vertex_count();
+        }
+    }
+    let meshed = (chunks.len() as u32 * iterations).max(1);
+    (start.elapsed() / meshed, vertices / chunks.len().max(1))
+}
+fn report(
+    name: &str,
+    chunks: &[(&Chunk, FaceStorage<&Chunk>)],
+    block_registry: &BlockRegistry,
+    biome_registry: &BiomeRegistry,
+    iterations: u32,
+) {
+    for mode in [MeshingMode::Naive, MeshingMode::Greedy] {
+        let (time, vertices) = measure(chunks, block_registry, biome_registry, mode, iterations);
+        println!(
+            "{:<14} {:<8} {:>10.1}us {:>8} vertices",
+            name,
+            format!("{:?}", mode).to_lowercase(),
+            time.as_nanos() as f64 / 1000.,
+            vertices
+        );
+    }
+}
+
+//mesher_bench <content.zip> [chunk recording] [iterations]
+pub fn run() {
+    let args: Vec<String> = args().collect();
+    let content_path = PathBuf::from(args.get(1).expect("missing content path"));
+    let recording = args.get(2).map(PathBuf::from);
+    let iterations = args
+        .get(3)
+        .and_then(|iterations| iterations.parse().ok())
+        .unwrap_or(100);
+    let (_, _, block_registry, _, _, biome_registry, _, _, _) =
+        content::load_assets(content_path, false);
+    let empty = |position| Chunk::new(position, [[[0; 16]; 16]; 16], Vec::new(), Vec::new());
+    let origin = ChunkPosition { x: 0, y: 0, z: 0 };
+    let air = empty(origin);
+    let air_neighbors = || FaceStorage {
+        front: &air,
+        back: &air,
+        left: &air,
+        right: &air,
+        up: &air,
+        down: &air,
+    };
+    let mix = BlockMix::from_registry(&block_registry);
+    for (name, blocks) in mix.create_chunks() {
+        let chunk = Chunk::new(origin, blocks, Vec::new(), Vec::new());
+        report(
+            name,
+            &[(&chunk, air_neighbors())],
+            &block_registry,
+            &biome_registry,
+            iterations,
+        );
+    }
+    if let Some(recording) = recording {
+        let recorded = load_recording(recording);
+        let missing: HashMap<_, _> = recorded
+            .keys()
+            .flat_map(|position| {
+                Face::all()
+                    .iter()
+                    .map(move |face| position.with_offset(face))
+            })
+            .filter(|position| !recorded.contains_key(position))
+            .map(|position| (position, empty(position)))
+            .collect();
+        let get = |position: ChunkPosition| {
+            recorded
+                .get(&position)
+                .or_else(|| missing.get(&position))
+                .unwrap()
+        };
+        let chunks: Vec<_> = recorded
+            .iter()
+            .map(|(position, chunk)| {
+                (
+                    chunk,
+                    FaceStorage {
+                        front: get(position.with_offset(&Face::Front)),
+                        back: get(position.with_offset(&Face::Back)),
+                        left: get(position.with_offset(&Face::Left)),
+                        right: get(position.with_offset(&Face::Right)),
+                        up: get(position.with_offset(&Face::Up)),
+                        down: get(position.with_offset(&Face::Down)),
+                    },
+                )
+            })
+            .collect();
+        println!("{} recorded chunks", chunks.len());
+        report(
+            "recorded",
+            chunks.as_slice(),
+            &block_registry,
+            &biome_registry,
+            (iterations / 10).max(1),
+        );
+    }
+}
@@ -1,15 +1,18 @@
 use crate::content::{ItemModel, ItemRegistry, Texture};
 use crate::render::FaceVerticesExtension;
 use block_byte_common::content::{ModelAnimationData, ModelBone, ModelCubeElement, ModelData, ModelItemElement, ModelMeshElement, Transformation};
+use block_byte_common::messages::BonePose;
 use block_byte_common::{Face, Position, TexCoords, Vec3};
 use cgmath::{Matrix4, Point3, Rad, SquareMatrix, Transform, Vector3};
 use std::collections::HashMap;
+use std::f32::consts::TAU;
 
 pub struct Model {
     data: ModelData,
     pub texture: Texture,
     animations: Vec<u32>,
     items: Vec<String>,
+    head_bone: Option<Vec<usize>>,
 }
 impl Model {
     pub fn new(
@@ -34,8 +37,13 @@ impl Model {
             },
             data,
             items,
+            head_bone: None,
         }
     }
+    pub fn with_head_bone(mut self, head_bone: Option<Vec<usize>>) -> Self {
+        self.head_bone = head_bone;
+        self
+    }
     pub fn get_item_slot(&self, slot: u32) -> Option<&String> {
         self.items.get(slot as usize)
     }
@@ -52,39 +60,71 @@ impl Model {
             &self.data.root_bone,
             base_matrix,
             instance,
+            &mut Vec::new(),
             item_registry,
             vertex_consumer,
         );
     }
+    //path holds the child indices leading from the root bone to this one
     fn add_bone<F>(
         &self,
         bone: &ModelBone,
         parent_transform: Matrix4<f32>,
         instance: &ModelInstanceData,
+        path: &mut Vec<usize>,
         item_registry: Option<&ItemRegistry>,
         vertex_consumer: &mut F,
     ) where
         F: FnMut(Position, (f32, f32)),
     {
-        let (translate, rotate, scale) = instance
-            .animation
-            .and_then(|(animation, time)| {
-                self.animations
-                    .get(animation as usize)
-                    .and_then(|animation| bone.animations.get(animation))
-                    .map(|animation| animation.get_for_time(time))
-            })
-            .unwrap_or(ModelAnimationData::get_default());
-        let transform =
+        let (translate, rotate, scale) = match instance.blend {
+            Some((animation, time, weight)) => {
+                let current = self.get_bone_pose(bone, instance.animation);
+                let previous = self.get_bone_pose(bone, Some((animation, time)));
+                (
+                    Self::lerp(&current.0, &previous.0, weight),
+                    Self::lerp(&current.1, &previous.1, weight),
+                    Self::lerp(&current.2, &previous.2, weight),
+                )
+            }
+            None => self.get_bone_pose(bone, instance.animation),
+        };
+        let (translate, rotate) = match instance.poses.get(path.as_slice()) {
+            Some((pose, _)) => (
+                Self::lerp(&translate, &pose.position, pose.weight),
+                Self::lerp(&rotate, &pose.rotation, pose.weight),
+            ),
+            None => (translate, rotate),
+        };
+        let mut transform =
             parent_transform * Self::create_matrix_trs(&translate, &rotate, &bone.origin, &scale);
-        for child_bone in &bone.child_bones {
+        if let Some((yaw, pitch)) = instance
+            .head
+            .filter(|_| self.head_bone.as_deref() == Some(path.as_slice()))
+        {
+            transform = transform
+                * Self::create_matrix_trs(
+                    &Vec3::ZERO,
+                    &Vec3 {
+                        x: pitch,
+                        y: yaw,
+                        z: 0.,
+                    },
+                    &bone.origin,
+                    &Vec3::ONE,
+                );
+        }
+        for (index, child_bone) in bone.child_bones.iter().enumerate() {
+            path.push(index);
             self.add_bone(
                 child_bone,
                 transform,
                 instance,
+                path,
                 item_registry,
                 vertex_consumer,
             );
+            path.pop();
         }
         for child_cube_element in &bone.cube_elements {
             self.add_cube_element(child_cube_element, transform, vertex_consumer);
@@ -94,6 +134,19 @@ impl Model {
         }
         if let Some(item_registry) = item_registry {
             for child_item_element in &bone.item_elements {
+                //the origin of a slot transform is relative to the pivot of the item element
+                let transform = match instance.item_transforms.get(&child_item_element.name) {
+                    Some(item_transform) => {
+                        transform
+                            * Self::create_matrix_trs(
+                                &item_transform.position,
+                                &item_transform.rotation,
+                                &(item_transform.origin + child_item_element.origin),
+                                &item_transform.scale,
+                            )
+                    }
+                    None => transform,
+                };
                 self.add_item_element(
                     child_item_element,
                     transform,
@@ -103,6 +156,27 @@ impl Model {
             }
         }
     }
+    fn get_bone_pose(
+        &self,
+        bone: &ModelBone,
+        animation: Option<(u32, f32)>,
+    ) -> (Vec3, Vec3, Vec3) {
+        animation
+            .and_then(|(animation, time)| {
+                self.animations
+                    .get(animation as usize)
+                    .and_then(|animation| bone.animations.get(animation))
+                    .map(|animation| animation.get_for_time(time))
+            })
+            .unwrap_or(ModelAnimationData::get_default())
+    }
+    fn lerp(first: &Vec3, second: &Vec3, amount: f32) -> Vec3 {
+        Vec3 {
+            x: first.x * (1. - amount) + second.x * amount,
+            y: first.y * (1. - amount) + second.y * amount,
+            z: first.z * (1. - amount) + second.z * amount,
+        }
+    }
     fn add_cube_element<F>(
         &self,
         cube_element: &ModelCubeElement,
@@ -198,10 +272,27 @@ impl Model {
         F: FnMut(Position, (f32, f32)),
     {
         if let Some(item) = items.0.get(&item_element.name) {
-            let (main_texture, sides) = match &items.1.get_item(*item).model {
+            let (model, turns) = items.1.get_item(*item).get_model();
+            let (main_texture, sides) = match model {
                 ItemModel::Texture { texture, sides } => (*texture, Some(sides)),
                 ItemModel::Block { front, .. } => (*front, None),
             };
+            //dynamic models turn around the middle of the item
+            let center = Vector3::new(
+                item_element.position.x + item_element.size.x / 2.,
+                item_element.position.y + item_element.size.y / 2.,
+                0.,
+            );
+            let transform = parent_transform
+                * Self::create_matrix_trs(
+                    &Vec3::ZERO,
+                    &item_element.rotation,
+                    &item_element.origin,
+                    &Vec3::ONE,
+                )
+                * Matrix4::from_translation(center)
+                * Matrix4::from_angle_z(Rad(-turns * TAU))
+                * Matrix4::from_translation(-center);
             if let Some(sides) = sides {
                 for side in &sides.0 {
                     side.1.add_vertices(
@@ -212,14 +303,7 @@ impl Model {
                             v2: 0.,
                         },
                         &mut |position, _coords| {
-                            let position = (parent_transform
-                                * Self::create_matrix_trs(
-                                    &Vec3::ZERO,
-                                    &item_element.rotation,
-                                    &item_element.origin,
-                                    &Vec3::ONE,
-                                ))
-                            .transform_point(Point3 {
+                            let position = transform.transform_point(Point3 {
                                 x: item_element.position.x
                                     + (((position.x as f32 + side.0 .0 as f32) / sides.1.x)
                                         * item_element.size.x),
@@ -248,14 +332,7 @@ impl Model {
                 }
             }
             Face::Down.add_vertices(main_texture.flip_horizontally(), &mut |position, coords| {
-                let position = (parent_transform
-                    * Self::create_matrix_trs(
-                        &Vec3::ZERO,
-                        &item_element.rotation,
-                        &item_element.origin,
-                        &Vec3::ONE,
-                    ))
-                .transform_point(Point3 {
+                let position = transform.transform_point(Point3 {
                     x: item_element.position.x + (position.x as f32 * item_element.size.x),
                     y: item_element.position.y + (position.z as f32 * item_element.size.y),
                     z: item_element.position.z + (1. / 32.),
@@ -270,14 +347,7 @@ impl Model {
                 ));
             });
             Face::Up.add_vertices(main_texture, &mut |position, coords| {
-                let position = (parent_transform
-                    * Self::create_matrix_trs(
-                        &Vec3::ZERO,
-                        &item_element.rotation,
-                        &item_element.origin,
-                        &Vec3::ONE,
-                    ))
-                .transform_point(Point3 {
+                let position = transform.transform_point(Point3 {
                     x: item_element.position.x + (position.x as f32 * item_element.size.x),
                     y: item_element.position.y + (position.z as f32 * item_element.size.y),
                     z: item_element.position.z,
@@ -318,14 +388,48 @@ impl Model {
 }
 pub struct ModelInstanceData {
     pub animation: Option<(u32, f32)>,
+    //animation being faded out, its time and how much of it is still shown
+    pub blend: Option<(u32, f32, f32)>,
     pub items: HashMap<String, u32>,
+    //yaw of the head relative to the body and its pitch, in radians
+    pub head: Option<(f32, f32)>,
+    //server set bone overrides by bone path, the shown pose eases towards the target one
+    pub poses: HashMap<Vec<usize>, (BonePose, BonePose)>,
+    pub item_transforms: HashMap<String, Transformation>,
 }
 impl ModelInstanceData {
+    const POSE_SMOOTHING: f32 = 15.;
     pub fn new() -> Self {
         ModelInstanceData {
             animation: None,
+            blend: None,
             items: HashMap::new(),
+            head: None,
+            poses: HashMap::new(),
+            item_transforms: HashMap::new(),
+        }
+    }
+    //new poses fade in from the animation and cleared ones fade back out before being dropped
+    pub fn set_pose(&mut self, path: Vec<usize>, pose: Option<BonePose>) {
+        match (self.poses.get_mut(&path), pose) {
+            (Some((_, target)), Some(pose)) => *target = pose,
+            (Some((_, target)), None) => target.weight = 0.,
+            (None, Some(pose)) => {
+                self.poses
+                    .insert(path, (BonePose { weight: 0., ..pose }, pose));
+            }
+            (None, None) => {}
+        }
+    }
+    pub fn tick_poses(&mut self, delta_time: f32) {
+        let amount = (delta_time * Self::POSE_SMOOTHING).min(1.);
+        for (current, target) in self.poses.values_mut() {
+            current.position = current.position.lerp(&target.position, amount);
+            current.rotation = current.rotation.lerp(&target.rotation, amount);
+            current.weight += (target.weight - current.weight) * amount;
         }
+        self.poses
+            .retain(|_, (current, target)| target.weight > 0. || current.weight > 0.001);
     }
 }
 pub trait TransformationExt {
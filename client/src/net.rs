@@ -1,13 +1,152 @@
-use block_byte_common::messages::{NetworkMessageC2S, NetworkMessageS2C};
+use block_byte_common::messages::{
+    ChunkCompression, ChunkPayload, NetworkMessageC2S, NetworkMessageS2C, ProtocolHello,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use block_byte_common::{
+    messages::MessageChannel,
+    quic::{QuicConnection, Received},
+};
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read};
 use std::net::TcpStream;
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::path::Path;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::SystemTime;
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::runtime::Runtime;
 use tungstenite::{Message, WebSocket};
 use url::Url;
 
+use crate::replay::Replay;
+use crate::settings::Identity;
+
+enum Transport {
+    WebSocket(WebSocket<TcpStream>),
+    //the runtime drives the connection and has to live as long as it
+    #[cfg(not(target_arch = "wasm32"))]
+    Quic(QuicConnection, Runtime),
+    //messages come from a recording and nothing is sent
+    Replay(Replay),
+}
+
+//servers generate a new certificate on every start so there is nothing to verify it against,
+//websocket connections aren't encrypted at all
+#[cfg(not(target_arch = "wasm32"))]
+struct AcceptAnyCertificate;
+#[cfg(not(target_arch = "wasm32"))]
+impl rustls::client::ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _server_name: &rustls::ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::ServerCertVerified::assertion())
+    }
+}
+
 pub struct SocketConnection {
-    socket: WebSocket<TcpStream>,
+    transport: Transport,
+    closed: bool,
+    chunk_dictionary: Vec<u8>,
+    received_any: bool,
+    error: Option<String>,
+    movement_sequences: HashMap<u32, u32>,
 }
 impl SocketConnection {
-    pub fn new(address: &str) -> Self {
+    //same limit tungstenite puts on websocket messages
+    #[cfg(not(target_arch = "wasm32"))]
+    const MAX_FRAME_SIZE: usize = 64 << 20;
+    pub fn new(address: &str, identity: &Identity, quic: bool) -> Self {
+        let transport = if quic {
+            Self::connect_quic(address)
+        } else {
+            Self::connect_websocket(address)
+        };
+        let mut connection = SocketConnection {
+            transport,
+            closed: false,
+            chunk_dictionary: Vec::new(),
+            received_any: false,
+            error: None,
+            movement_sequences: HashMap::new(),
+        };
+        connection.send_message(&NetworkMessageC2S::Hello(ProtocolHello::current()));
+        connection.send_message(&NetworkMessageC2S::ConnectionMode(0));
+        connection.send_message(&NetworkMessageC2S::Identify(
+            identity.username.clone(),
+            identity.uuid.clone(),
+            identity.token.clone(),
+        ));
+        connection.send_message(&NetworkMessageC2S::SupportedCompression(vec![
+            ChunkCompression::Zstd,
+            ChunkCompression::Gzip,
+        ]));
+        if let Transport::WebSocket(socket) = &mut connection.transport {
+            socket.get_mut().set_nonblocking(true).unwrap();
+        }
+        connection
+    }
+    pub fn replay(path: &str) -> Result<Self, String> {
+        Ok(SocketConnection {
+            transport: Transport::Replay(Replay::load(Path::new(path))?),
+            closed: false,
+            chunk_dictionary: Vec::new(),
+            received_any: false,
+            error: None,
+            movement_sequences: HashMap::new(),
+        })
+    }
+    pub fn get_replay(&mut self) -> Option<&mut Replay> {
+        match &mut self.transport {
+            Transport::Replay(replay) => Some(replay),
+            _ => None,
+        }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    fn connect_quic(address: &str) -> Transport {
+        let address = address.to_socket_addrs().unwrap().next().unwrap();
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let connection = runtime.block_on(async {
+            let local_address = if address.is_ipv6() {
+                SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))
+            } else {
+                SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))
+            };
+            let mut endpoint = quinn::Endpoint::client(local_address).unwrap();
+            endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(
+                rustls::ClientConfig::builder()
+                    .with_safe_defaults()
+                    .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate))
+                    .with_no_client_auth(),
+            )));
+            let connection = endpoint
+                .connect(address, "blockbyte")
+                .unwrap()
+                .await
+                .unwrap();
+            let control = connection.open_bi().await.unwrap();
+            QuicConnection::start(connection, control, None, Self::MAX_FRAME_SIZE)
+        });
+        Transport::Quic(connection, runtime)
+    }
+    //browsers can't open raw quic connections and webtransport isn't supported yet
+    #[cfg(target_arch = "wasm32")]
+    fn connect_quic(address: &str) -> Transport {
+        log::warn!("quic is only supported by native builds, connecting over websockets");
+        Self::connect_websocket(address)
+    }
+    fn connect_websocket(address: &str) -> Transport {
         let tcp_stream = std::net::TcpStream::connect(address).unwrap();
         let (socket, _response) = tungstenite::client::client_with_config(
             Url::parse("ws://aaa123").unwrap(),
@@ -15,26 +154,139 @@ impl SocketConnection {
             None,
         )
         .unwrap();
-        let mut connection = SocketConnection { socket };
-        connection.send_message(&NetworkMessageC2S::ConnectionMode(0));
-        connection.socket.get_mut().set_nonblocking(true).unwrap();
-        connection
+        Transport::WebSocket(socket)
     }
     pub fn send_message(&mut self, message: &NetworkMessageC2S) {
-        self.socket
-            .send(Message::Binary(bitcode::serialize(message).unwrap()))
-            .unwrap();
+        if self.closed {
+            return;
+        }
+        let data = bitcode::serialize(message).unwrap();
+        match &mut self.transport {
+            Transport::WebSocket(socket) => socket.send(Message::Binary(data)).unwrap(),
+            #[cfg(not(target_arch = "wasm32"))]
+            Transport::Quic(connection, _) => connection.send(MessageChannel::Control, data),
+            Transport::Replay(_) => {}
+        }
     }
     pub fn read_messages(&mut self) -> Vec<NetworkMessageS2C> {
         let mut messages = Vec::new();
-        while let Ok(message) = self.socket.read() {
-            match message {
-                Message::Binary(data) => messages
-                    .push(bitcode::deserialize::<NetworkMessageS2C>(data.as_slice()).unwrap()),
-                Message::Close(_) => panic!("close"),
-                _ => {}
+        if self.closed {
+            return messages;
+        }
+        let mut received = Vec::new();
+        let mut closed = false;
+        match &mut self.transport {
+            Transport::WebSocket(socket) => loop {
+                let message = match socket.read() {
+                    Ok(message) => message,
+                    Err(tungstenite::Error::Io(error)) if error.kind() == ErrorKind::WouldBlock => {
+                        break;
+                    }
+                    Err(_) => {
+                        closed = true;
+                        break;
+                    }
+                };
+                match message {
+                    Message::Binary(data) => received.push((None, data)),
+                    Message::Close(_) => {
+                        closed = true;
+                        break;
+                    }
+                    _ => {}
+                }
+            },
+            #[cfg(not(target_arch = "wasm32"))]
+            Transport::Quic(connection, _) => {
+                while let Some(message) = connection.receive() {
+                    received.push(match message {
+                        Received::Reliable(data) => (None, data),
+                        Received::Unreliable(sequence, data) => (Some(sequence), data),
+                    });
+                }
+                closed = connection.is_closed();
+            }
+            Transport::Replay(replay) => {
+                received.extend(replay.take_messages().into_iter().map(|data| (None, data)));
             }
         }
+        for (sequence, data) in received {
+            match bitcode::deserialize::<NetworkMessageS2C>(data.as_slice()) {
+                Ok(message) => {
+                    self.received_any = true;
+                    if let (Some(sequence), NetworkMessageS2C::MoveEntity(id, ..)) =
+                        (sequence, &message)
+                    {
+                        if !self.is_newest_movement(*id, sequence) {
+                            continue;
+                        }
+                    }
+                    messages.push(message);
+                }
+                Err(_) => {
+                    self.error = Some(
+                        "the server sent a message this client can't read, it may be running a different version"
+                            .to_string(),
+                    );
+                    self.closed = true;
+                    return messages;
+                }
+            }
+        }
+        if closed {
+            self.close();
+        }
         messages
     }
+    //datagrams can arrive out of order, older positions than the last applied one are dropped
+    fn is_newest_movement(&mut self, entity: u32, sequence: u32) -> bool {
+        let last = self.movement_sequences.entry(entity).or_insert(0);
+        if sequence <= *last {
+            return false;
+        }
+        *last = sequence;
+        true
+    }
+    fn close(&mut self) {
+        //servers from before protocol versioning can't read the hello and hang up right away
+        if !self.received_any {
+            self.error = Some(
+                "the server closed the connection during the handshake, it may be running an older version"
+                    .to_string(),
+            );
+        }
+        self.closed = true;
+    }
+    pub fn take_error(&mut self) -> Option<String> {
+        self.error.take()
+    }
+    pub fn set_chunk_dictionary(&mut self, dictionary: Vec<u8>) {
+        self.chunk_dictionary = dictionary;
+    }
+    pub fn decompress_chunk(&self, payload: ChunkPayload) -> Vec<u8> {
+        let mut data = Vec::new();
+        match payload {
+            ChunkPayload::Gzip(compressed) => {
+                flate2::read::GzDecoder::new(compressed.as_slice())
+                    .read_to_end(&mut data)
+                    .unwrap();
+            }
+            ChunkPayload::Zstd {
+                dictionary,
+                data: compressed,
+            } => {
+                zstd::stream::read::Decoder::with_dictionary(
+                    compressed.as_slice(),
+                    if dictionary {
+                        self.chunk_dictionary.as_slice()
+                    } else {
+                        &[]
+                    },
+                )
+                .and_then(|mut decoder| decoder.read_to_end(&mut data))
+                .unwrap();
+            }
+        }
+        data
+    }
 }
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+
+use block_byte_common::{BlockPosition, Face, Position, TexCoords, Vec3};
+use cgmath::{InnerSpace, Vector3};
+
+use crate::content::{ParticleData, Texture};
+use crate::game::{ClientPlayer, World};
+use crate::render::Vertex;
+
+struct Particle {
+    position: Vector3<f32>,
+    velocity: Vector3<f32>,
+    age: f32,
+    lifetime: f32,
+    gravity: f32,
+    size: f32,
+    coords: TexCoords,
+    shift: f32,
+    stages: u8,
+}
+
+struct Footsteps {
+    position: Position,
+    distance: f32,
+}
+
+pub struct ParticleManager {
+    particles: Vec<Particle>,
+    seed: u32,
+    footsteps: HashMap<u32, Footsteps>,
+    player_footsteps: Option<Footsteps>,
+}
+impl ParticleManager {
+    const MAX_PARTICLES: usize = 4096;
+    const BLOCK_BREAK_COUNT: u32 = 24;
+    const BLOCK_BREAK_LIFETIME: f32 = 0.8;
+    const BLOCK_BREAK_GRAVITY: f32 = 16.;
+    const BLOCK_BREAK_SIZE: f32 = 0.15;
+    const STEP_LIFETIME: f32 = 0.5;
+    const STEP_GRAVITY: f32 = 8.;
+    const RUN_SPEED: f32 = 5.;
+    const MAX_STEP: f32 = 2.;
+    pub fn new() -> Self {
+        ParticleManager {
+            particles: Vec::new(),
+            seed: 0x9E3779B9,
+            footsteps: HashMap::new(),
+            player_footsteps: None,
+        }
+    }
+    fn random(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed as f32 / u32::MAX as f32
+    }
+    fn add(&mut self, particle: Particle) {
+        if self.particles.len() >= Self::MAX_PARTICLES {
+            self.particles.swap_remove(0);
+        }
+        self.particles.push(particle);
+    }
+    pub fn spawn(
+        &mut self,
+        particle_data: &ParticleData,
+        position: Position,
+        count: u32,
+        spread: f32,
+        velocity: Vec3,
+    ) {
+        let stages = match particle_data.texture {
+            Texture::Static { .. } => 1,
+            Texture::Animated { stages, .. } => stages.max(1),
+        };
+        for _ in 0..count {
+            let offset = Vector3::new(
+                self.random() - 0.5,
+                self.random() - 0.5,
+                self.random() - 0.5,
+            ) * 2.
+                * spread;
+            let lifetime = particle_data.lifetime * (0.75 + self.random() * 0.5);
+            self.add(Particle {
+                position: Vector3::new(position.x as f32, position.y as f32, position.z as f32)
+                    + offset,
+                velocity: Vector3::new(velocity.x, velocity.y, velocity.z),
+                age: 0.,
+                lifetime,
+                gravity: particle_data.gravity,
+                size: particle_data.size,
+                coords: particle_data.texture.get_first_coords(),
+                shift: particle_data.texture.get_shift(),
+                stages,
+            });
+        }
+    }
+    //a random quarter of the texture, so fragments of one block don't all look the same
+    fn random_fragment(&mut self, texture: Texture) -> TexCoords {
+        let coords = texture.get_first_coords();
+        let width = (coords.u2 - coords.u1) / 4.;
+        let height = (coords.v2 - coords.v1) / 4.;
+        let u1 = coords.u1 + (self.random() * 3.).floor() * width;
+        let v1 = coords.v1 + (self.random() * 3.).floor() * height;
+        TexCoords {
+            u1,
+            v1,
+            u2: u1 + width,
+            v2: v1 + height,
+        }
+    }
+    pub fn spawn_block_break(&mut self, position: BlockPosition, texture: Texture) {
+        for _ in 0..Self::BLOCK_BREAK_COUNT {
+            let offset = Vector3::new(self.random(), self.random(), self.random());
+            let coords = self.random_fragment(texture);
+            let lifetime = Self::BLOCK_BREAK_LIFETIME * (0.5 + self.random());
+            self.add(Particle {
+                position: Vector3::new(position.x as f32, position.y as f32, position.z as f32)
+                    + offset,
+                velocity: (offset - Vector3::new(0.5, 0.5, 0.5)) * 4. + Vector3::new(0., 2., 0.),
+                age: 0.,
+                lifetime,
+                gravity: Self::BLOCK_BREAK_GRAVITY,
+                size: Self::BLOCK_BREAK_SIZE,
+                coords,
+                shift: 0.,
+                stages: 1,
+            });
+        }
+    }
+    //entities and the player kick up dust from the block they walk on, the server isn't involved
+    pub fn tick_footsteps(&mut self, delta_time: f32, world: &World, player: &ClientPlayer) {
+        let mut footsteps = std::mem::take(&mut self.footsteps);
+        footsteps.retain(|id, _| world.entities.contains_key(id));
+        for (id, entity) in &world.entities {
+            let entity_data = world.entity_registry.get_entity(entity.type_id);
+            let feet =
+                entity
+                    .position
+                    .add(entity_data.hitbox_w / 2., 0., entity_data.hitbox_d / 2.);
+            let entry = footsteps.entry(*id).or_insert(Footsteps {
+                position: feet,
+                distance: 0.,
+            });
+            self.step(
+                entry,
+                feet,
+                entity_data.hitbox_w.max(entity_data.hitbox_d) as f32,
+                delta_time,
+                world,
+            );
+        }
+        self.footsteps = footsteps;
+        if let Some(hitbox) = player.hitbox {
+            let feet = Position::from(player.position).add(hitbox.0 / 2., 0., hitbox.2 / 2.);
+            let mut footsteps = self.player_footsteps.take().unwrap_or(Footsteps {
+                position: feet,
+                distance: 0.,
+            });
+            self.step(
+                &mut footsteps,
+                feet,
+                hitbox.0.max(hitbox.2) as f32,
+                delta_time,
+                world,
+            );
+            self.player_footsteps = Some(footsteps);
+        }
+    }
+    fn step(
+        &mut self,
+        footsteps: &mut Footsteps,
+        feet: Position,
+        width: f32,
+        delta_time: f32,
+        world: &World,
+    ) {
+        let moved = Vector3::new(
+            (feet.x - footsteps.position.x) as f32,
+            0.,
+            (feet.z - footsteps.position.z) as f32,
+        );
+        footsteps.position = feet;
+        let distance = moved.magnitude();
+        //teleports and standing still don't make dust
+        if distance > Self::MAX_STEP || distance == 0. {
+            return;
+        }
+        let below = BlockPosition {
+            x: feet.x.floor() as i32,
+            y: (feet.y - 0.05).floor() as i32,
+            z: feet.z.floor() as i32,
+        };
+        let block = match world.get_block(below) {
+            Some(block) => world.block_registry.get_block(block),
+            None => return,
+        };
+        if (feet.y - (below.y + 1) as f64).abs() > 0.05 || !block.is_face_full(Face::Up) {
+            footsteps.distance = 0.;
+            return;
+        }
+        let texture = match block.get_particle_texture() {
+            Some(texture) => texture,
+            None => return,
+        };
+        let settings = block.step_particles;
+        let running = delta_time > 0. && distance / delta_time > Self::RUN_SPEED;
+        footsteps.distance += distance
+            * if running {
+                settings.run_rate
+            } else {
+                settings.rate
+            };
+        let direction = moved / distance;
+        while footsteps.distance >= 1. {
+            footsteps.distance -= 1.;
+            let offset = Vector3::new(self.random() - 0.5, 0., self.random() - 0.5) * width;
+            let spread = Vector3::new(self.random() - 0.5, 0., self.random() - 0.5) * 0.6;
+            let lifetime = Self::STEP_LIFETIME * (0.5 + self.random());
+            let coords = self.random_fragment(texture);
+            let lift = 1. + self.random();
+            self.add(Particle {
+                position: Vector3::new(feet.x as f32, feet.y as f32 + 0.05, feet.z as f32) + offset,
+                velocity: spread + Vector3::new(0., lift, 0.)
+                    - direction * if running { 1.5 } else { 0.5 },
+                age: 0.,
+                lifetime,
+                gravity: Self::STEP_GRAVITY,
+                size: settings.size,
+                coords,
+                shift: 0.,
+                stages: 1,
+            });
+        }
+    }
+    pub fn tick(&mut self, delta_time: f32, world: &World) {
+        self.particles.retain_mut(|particle| {
+            particle.age += delta_time;
+            if particle.age >= particle.lifetime {
+                return false;
+            }
+            particle.velocity.y -= particle.gravity * delta_time;
+            let next = particle.position + particle.velocity * delta_time;
+            let block = world.get_block(BlockPosition {
+                x: next.x.floor() as i32,
+                y: next.y.floor() as i32,
+                z: next.z.floor() as i32,
+            });
+            if block.map_or(false, |block| {
+                world.block_registry.get_block(block).is_face_full(Face::Up)
+            }) {
+                particle.velocity = Vector3::new(0., 0., 0.);
+            } else {
+                particle.position = next;
+            }
+            true
+        });
+    }
+    pub fn add_vertices(&self, camera: &ClientPlayer, vertices: &mut Vec<Vertex>) {
+        let front = camera.make_front();
+        let right = front.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(front).normalize();
+        for particle in &self.particles {
+            let frame = ((particle.age / particle.lifetime * particle.stages as f32) as u8)
+                .min(particle.stages - 1);
+            let u_offset = particle.shift * frame as f32;
+            let right = right * (particle.size / 2.);
+            let up = up * (particle.size / 2.);
+            let coords = &particle.coords;
+            let corners = [
+                (-1., -1., coords.u1, coords.v2),
+                (1., -1., coords.u2, coords.v2),
+                (1., 1., coords.u2, coords.v1),
+                (-1., 1., coords.u1, coords.v1),
+            ];
+            for index in [0, 1, 2, 2, 3, 0] {
+                let (x, y, u, v) = corners[index];
+                let position = particle.position + right * x + up * y;
+                vertices.push(Vertex {
+                    position: [position.x, position.y, position.z],
+                    tex_coords: [u + u_offset, v],
+                });
+            }
+        }
+    }
+}
@@ -1,12 +1,20 @@
-use crate::content::{EntityRegistry, ItemRegistry, Texture};
-use crate::game::{ClientPlayer, World};
-use crate::gui::GUIRenderer;
-use crate::model::{Model, ModelInstanceData};
+use crate::content::{BlockData, BlockRenderDataType, EntityRegistry, ItemRegistry, Texture};
+use crate::culling::{self, Frustum};
+use crate::game::{ClientPlayer, TextDisplay, World};
+use crate::gpu_timing::{DrawStats, GpuPass, GpuTimer};
+use crate::gui::{GUIRenderer, TextRenderer};
+use crate::model::{Model, ModelInstanceData, TransformationExt};
+use crate::particles::ParticleManager;
+use crate::sky::SkyRenderer;
+use crate::snapshot::SnapshotCapture;
 use crate::texture;
-use crate::texture::GPUTexture;
-use block_byte_common::{Face, Position, TexCoords, Vec3, AABB};
-use cgmath::{Matrix4, SquareMatrix};
+use crate::texture::{GPUTexture, TextureAtlas};
+use block_byte_common::content::ClientBiomeData;
+use block_byte_common::{BlockPosition, Color, Face, Position, TexCoords, Vec3, WorldBorder, AABB};
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector3};
 use image::RgbaImage;
+use rusttype::Scale;
+use std::collections::HashMap;
 use std::f64::consts::PI;
 use std::iter;
 use std::mem::size_of;
@@ -33,14 +41,25 @@ pub struct RenderState {
     gui_render_pipeline: wgpu::RenderPipeline,
     model_render_pipeline: wgpu::RenderPipeline,
     pub outline_renderer: OutlineRenderer,
+    border_renderer: BorderRenderer,
+    pub sky_renderer: SkyRenderer,
+    pub ghost_renderer: GhostRenderer,
+    pub crack_renderer: CrackRenderer,
+    text_display_renderer: TextDisplayRenderer,
     texture: GPUTexture,
     camera_uniform: CameraUniform,
     camera_buffer: Buffer,
     camera_bind_group: wgpu::BindGroup,
-    time_buffer: Buffer,
-    time_bind_group: wgpu::BindGroup,
+    environment: Environment,
+    environment_buffer: Buffer,
+    environment_bind_group: wgpu::BindGroup,
     depth_texture: (wgpu::Texture, Sampler, TextureView),
     pub mouse: PhysicalPosition<f64>,
+    fov: f32,
+    render_distance: u32,
+    pub gpu_timer: GpuTimer,
+    pub draw_stats: DrawStats,
+    pub snapshots: SnapshotCapture,
 }
 
 impl RenderState {
@@ -63,7 +82,9 @@ impl RenderState {
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::DEPTH_CLIP_CONTROL,
+                    //timestamps are optional, the overlay only shows draw calls without them
+                    features: wgpu::Features::DEPTH_CLIP_CONTROL
+                        | (adapter.features() & wgpu::Features::TIMESTAMP_QUERY),
                     limits: if cfg!(target_arch = "wasm32") {
                         wgpu::Limits::downlevel_webgl2_defaults()
                     } else {
@@ -82,8 +103,14 @@ impl RenderState {
             .copied()
             .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
+        //copying out of the surface is only needed for snapshots, not every backend allows it
+        let copyable = surface_caps.usages.contains(wgpu::TextureUsages::COPY_SRC);
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage: if copyable {
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
+            } else {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            },
             format: surface_format,
             width: size.width,
             height: size.height,
@@ -109,6 +136,18 @@ impl RenderState {
             label: Some("GUI Shader"),
             source: wgpu::ShaderSource::Wgsl(include_str!("assets/outline_shader.wgsl").into()),
         });
+        let border_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Border Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("assets/border_shader.wgsl").into()),
+        });
+        let ghost_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Ghost Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("assets/ghost_shader.wgsl").into()),
+        });
+        let text_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Text Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("assets/text_shader.wgsl").into()),
+        });
         let camera_uniform = CameraUniform::new();
         let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Camera Buffer"),
@@ -137,16 +176,17 @@ impl RenderState {
             }],
             label: Some("camera_bind_group"),
         });
-        let time_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Time Buffer"),
-            contents: bytemuck::cast_slice(&[0f32]),
+        let environment = Environment::new();
+        let environment_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Environment Buffer"),
+            contents: bytemuck::cast_slice(&[environment.uniform]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
-        let time_bind_group_layout =
+        let environment_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -154,15 +194,15 @@ impl RenderState {
                     },
                     count: None,
                 }],
-                label: Some("time_bind_group_layout"),
+                label: Some("environment_bind_group_layout"),
             });
-        let time_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &time_bind_group_layout,
+        let environment_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &environment_bind_group_layout,
             entries: &[wgpu::BindGroupEntry {
                 binding: 0,
-                resource: time_buffer.as_entire_binding(),
+                resource: environment_buffer.as_entire_binding(),
             }],
-            label: Some("time_bind_group"),
+            label: Some("environment_bind_group"),
         });
         let depth_texture = texture::create_depth_texture(&device, &config, "depth_texture");
         let chunk_render_pipeline_layout =
@@ -171,7 +211,7 @@ impl RenderState {
                 bind_group_layouts: &[
                     &texture.texture_bind_group_layout,
                     &camera_bind_group_layout,
-                    &time_bind_group_layout,
+                    &environment_bind_group_layout,
                 ],
                 push_constant_ranges: &[],
             });
@@ -435,6 +475,247 @@ impl RenderState {
                 },
                 multiview: None,
             });
+        let border_render_pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Border Render Pipeline"),
+                layout: Some(&outline_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &border_shader,
+                    entry_point: "vs_main",
+                    buffers: &[BorderVertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &border_shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+            });
+        let sky_render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sky Render Pipeline"),
+            layout: Some(&outline_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &border_shader,
+                entry_point: "vs_main",
+                buffers: &[BorderVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &border_shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+        let cloud_render_pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Cloud Render Pipeline"),
+                layout: Some(&outline_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &border_shader,
+                    entry_point: "vs_main",
+                    buffers: &[BorderVertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &border_shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+            });
+        let ghost_render_pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Ghost Render Pipeline"),
+                layout: Some(&model_render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &ghost_shader,
+                    entry_point: "vs_main",
+                    buffers: &[GhostVertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &ghost_shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+            });
+        let text_display_render_pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Text Display Render Pipeline"),
+                layout: Some(&model_render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &text_shader,
+                    entry_point: "vs_main",
+                    buffers: &[GhostVertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &text_shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+            });
+        let crack_render_pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Crack Render Pipeline"),
+                layout: Some(&model_render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &ghost_shader,
+                    entry_point: "vs_main",
+                    buffers: &[GhostVertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &ghost_shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+            });
+        let gpu_timer = GpuTimer::new(&device, &queue);
+        let snapshots = SnapshotCapture::new(config.format, copyable);
         Self {
             window,
             surface,
@@ -447,15 +728,26 @@ impl RenderState {
             gui_render_pipeline,
             model_render_pipeline,
             outline_renderer: OutlineRenderer::new(outline_render_pipeline, &device),
+            border_renderer: BorderRenderer::new(border_render_pipeline),
+            sky_renderer: SkyRenderer::new(sky_render_pipeline, cloud_render_pipeline),
+            ghost_renderer: GhostRenderer::new(ghost_render_pipeline),
+            crack_renderer: CrackRenderer::new(crack_render_pipeline),
+            text_display_renderer: TextDisplayRenderer::new(text_display_render_pipeline),
             texture,
             camera_uniform,
             camera_buffer,
             camera_bind_group,
-            time_bind_group,
-            time_buffer,
+            environment,
+            environment_bind_group,
+            environment_buffer,
             depth_texture,
             mouse: PhysicalPosition::new(0., 0.),
+            gpu_timer,
+            draw_stats: DrawStats::default(),
+            snapshots,
             device,
+            fov: 90.,
+            render_distance: 16,
         }
     }
 
@@ -465,9 +757,43 @@ impl RenderState {
     pub fn device(&self) -> &Device {
         &self.device
     }
+    pub fn set_debug_shapes(&mut self, vertices: &[OutlineVertex]) {
+        self.outline_renderer.set_shapes(vertices, &self.device);
+    }
+    pub fn poll_snapshot(&mut self) -> Option<(u32, Option<Vec<u8>>)> {
+        self.snapshots.poll(&self.device)
+    }
     pub fn size(&self) -> PhysicalSize<u32> {
         self.size
     }
+    pub fn set_reduced_motion(&mut self, reduced_motion: bool) {
+        self.environment.reduced_motion = reduced_motion;
+    }
+    pub fn set_fov(&mut self, fov: f32) {
+        self.fov = fov;
+    }
+    pub fn set_render_distance(&mut self, render_distance: u32) {
+        self.render_distance = render_distance;
+    }
+    pub fn set_vsync(&mut self, vsync: bool) {
+        let present_mode = if vsync {
+            wgpu::PresentMode::AutoVsync
+        } else {
+            wgpu::PresentMode::AutoNoVsync
+        };
+        if self.config.present_mode != present_mode {
+            self.config.present_mode = present_mode;
+            self.surface.configure(&self.device, &self.config);
+        }
+    }
+    pub fn set_texture(&mut self, texture_image: RgbaImage) {
+        self.texture = GPUTexture::from_image(
+            &self.device,
+            &self.queue,
+            &texture_image,
+            Some("main texture"),
+        );
+    }
 
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
@@ -487,23 +813,54 @@ impl RenderState {
         gui: &mut GUIRenderer,
         item_registry: &ItemRegistry,
         entity_registry: &EntityRegistry,
-        viewmodel: Option<(&Model, &ModelInstanceData)>,
+        viewmodel: Option<(&Model, &ModelInstanceData, Matrix4<f32>)>,
+        particles: &ParticleManager,
         time: f32,
     ) -> Result<(), wgpu::SurfaceError> {
-        self.camera_uniform
-            .load_view_proj_matrix(camera, self.size.width as f32 / self.size.height as f32);
+        self.camera_uniform.load_view_proj_matrix(
+            camera,
+            self.size.width as f32 / self.size.height as f32,
+            self.fov,
+        );
         self.queue.write_buffer(
             &self.camera_buffer,
             0,
             bytemuck::cast_slice(&[self.camera_uniform]),
         );
-        self.queue
-            .write_buffer(&self.time_buffer, 0, bytemuck::cast_slice(&[time]));
+        self.environment.update(
+            world
+                .get_biome(camera.get_eye().to_block_pos())
+                .cloned()
+                .unwrap_or_default(),
+            time,
+        );
+        let mut environment = self.environment.uniform;
+        environment.fog_color = self.sky_renderer.get_horizon_color(environment.fog_color);
+        self.queue.write_buffer(
+            &self.environment_buffer,
+            0,
+            bytemuck::cast_slice(&[environment]),
+        );
+
+        world.tick(&self.device);
+        let visible_chunks = culling::collect_visible_chunks(
+            world,
+            camera.get_eye(),
+            &Frustum::from_matrix(
+                ClientPlayer::create_projection_matrix(
+                    self.size.width as f32 / self.size.height as f32,
+                    self.fov,
+                ) * camera.create_view_matrix(),
+            ),
+            self.render_distance,
+        );
 
         let output = self.surface.get_current_texture()?;
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut stats = DrawStats::default();
+        self.gpu_timer.begin_frame(&self.device);
 
         let mut encoder = self
             .device
@@ -511,6 +868,17 @@ impl RenderState {
                 label: Some("Render Encoder"),
             });
 
+        self.sky_renderer.render_sky(
+            &self.device,
+            &mut encoder,
+            &view,
+            &self.camera_bind_group,
+            camera.get_eye(),
+            self.environment.sky_color,
+            self.environment.uniform.fog_color,
+            &mut stats,
+        );
+        self.gpu_timer.begin(&mut encoder, GpuPass::Chunks);
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Chunk Render Pass"),
@@ -518,12 +886,7 @@ impl RenderState {
                     view: &view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Load,
                         store: true,
                     },
                 })],
@@ -539,16 +902,17 @@ impl RenderState {
             render_pass.set_pipeline(&self.chunk_render_pipeline);
             render_pass.set_bind_group(0, &self.texture.diffuse_bind_group, &[]);
             render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-            render_pass.set_bind_group(2, &self.time_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.environment_bind_group, &[]);
 
-            world.tick(&self.device);
-            for chunk in &mut world.chunks {
-                if let Some(vertex_buffer) = chunk.1.get_vertices().0 {
+            for position in &visible_chunks {
+                if let Some(vertex_buffer) = world.chunks.get(position).unwrap().get_vertices().0 {
                     render_pass.set_vertex_buffer(0, vertex_buffer.0);
                     render_pass.draw(0..vertex_buffer.1, 0..1);
+                    stats.draw_triangles(vertex_buffer.1);
                 }
             }
         }
+        self.gpu_timer.end(&mut encoder);
         let (model_buffer, model_vertex_count) = {
             let mut vertices = Vec::new();
             for (block_position, dynamic_block_data) in &world.dynamic_blocks {
@@ -576,20 +940,25 @@ impl RenderState {
             }
             for (_, entity) in &world.entities {
                 let entity_data = entity_registry.get_entity(entity.type_id);
+                let (position, yaw, scale) = entity.get_render_transform(world, camera);
                 entity_data.model.add_vertices(
                     Model::create_matrix_trs(
                         &Vec3 {
-                            x: (entity.position.x + (entity_data.hitbox_w / 2.)) as f32,
-                            y: entity.position.y as f32,
-                            z: (entity.position.z + (entity_data.hitbox_d / 2.)) as f32,
+                            x: (position.x + (entity_data.hitbox_w / 2.)) as f32,
+                            y: position.y as f32,
+                            z: (position.z + (entity_data.hitbox_d / 2.)) as f32,
                         },
                         &Vec3 {
                             x: 0.,
-                            y: (entity.rotation.yaw + PI) as f32,
+                            y: (yaw + PI) as f32,
                             z: 0.,
                         },
                         &Vec3::ZERO,
-                        &Vec3::ONE,
+                        &Vec3 {
+                            x: scale,
+                            y: scale,
+                            z: scale,
+                        },
                     ),
                     &entity.model_instance,
                     Some(item_registry),
@@ -601,6 +970,7 @@ impl RenderState {
                     },
                 );
             }
+            particles.add_vertices(camera, &mut vertices);
             let buffer = self.device.create_buffer_init(&BufferInitDescriptor {
                 label: Some("Model Buffer"),
                 usage: BufferUsages::VERTEX,
@@ -608,6 +978,7 @@ impl RenderState {
             });
             (buffer, vertices.len() as u32)
         };
+        self.gpu_timer.begin(&mut encoder, GpuPass::Entities);
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Model Render Pass"),
@@ -634,7 +1005,10 @@ impl RenderState {
 
             render_pass.set_vertex_buffer(0, model_buffer.slice(..));
             render_pass.draw(0..model_vertex_count, 0..1);
+            stats.draw_triangles(model_vertex_count);
         }
+        self.gpu_timer.end(&mut encoder);
+        self.gpu_timer.begin(&mut encoder, GpuPass::Chunks);
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Foliage Chunk Render Pass"),
@@ -658,11 +1032,12 @@ impl RenderState {
             render_pass.set_pipeline(&self.chunk_foliage_render_pipeline);
             render_pass.set_bind_group(0, &self.texture.diffuse_bind_group, &[]);
             render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-            render_pass.set_bind_group(2, &self.time_bind_group, &[]);
-            for chunk in &mut world.chunks {
-                if let Some(vertex_buffer) = chunk.1.get_vertices().2 {
+            render_pass.set_bind_group(2, &self.environment_bind_group, &[]);
+            for position in &visible_chunks {
+                if let Some(vertex_buffer) = world.chunks.get(position).unwrap().get_vertices().2 {
                     render_pass.set_vertex_buffer(0, vertex_buffer.0);
                     render_pass.draw(0..vertex_buffer.1, 0..1);
+                    stats.draw_triangles(vertex_buffer.1);
                 }
             }
         }
@@ -689,17 +1064,74 @@ impl RenderState {
             render_pass.set_pipeline(&self.chunk_transparent_render_pipeline);
             render_pass.set_bind_group(0, &self.texture.diffuse_bind_group, &[]);
             render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-            render_pass.set_bind_group(2, &self.time_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.environment_bind_group, &[]);
 
-            for chunk in &mut world.chunks {
-                if let Some(vertex_buffer) = chunk.1.get_vertices().1 {
+            for position in visible_chunks.iter().rev() {
+                if let Some(vertex_buffer) = world.chunks.get(position).unwrap().get_vertices().1 {
                     render_pass.set_vertex_buffer(0, vertex_buffer.0);
                     render_pass.draw(0..vertex_buffer.1, 0..1);
+                    stats.draw_triangles(vertex_buffer.1);
                 }
             }
         }
+        self.gpu_timer.end(&mut encoder);
+        self.sky_renderer.render_clouds(
+            &self.device,
+            &mut encoder,
+            &view,
+            &self.depth_texture.2,
+            &self.camera_bind_group,
+            camera.get_eye(),
+            time,
+            &mut stats,
+        );
+        self.ghost_renderer.render(
+            &self.device,
+            &mut encoder,
+            &view,
+            &self.depth_texture.2,
+            &self.texture.diffuse_bind_group,
+            &self.camera_bind_group,
+            &mut stats,
+        );
+        self.crack_renderer.render(
+            &self.device,
+            &mut encoder,
+            &view,
+            &self.depth_texture.2,
+            &self.texture.diffuse_bind_group,
+            &self.camera_bind_group,
+            &mut stats,
+        );
+        self.text_display_renderer.set_text_displays(
+            camera,
+            &world.text_displays,
+            gui.get_text_renderer(),
+            gui.get_texture_atlas(),
+        );
+        self.text_display_renderer.render(
+            &self.device,
+            &mut encoder,
+            &view,
+            &self.depth_texture.2,
+            &self.texture.diffuse_bind_group,
+            &self.camera_bind_group,
+            &mut stats,
+        );
+        self.border_renderer.render(
+            &self.device,
+            &mut encoder,
+            &view,
+            &self.depth_texture.2,
+            &self.camera_bind_group,
+            &world.border,
+            camera.get_eye(),
+            &mut stats,
+        );
+        self.gpu_timer.begin(&mut encoder, GpuPass::Outline);
         self.outline_renderer
-            .render(&mut encoder, &view, &self.camera_bind_group);
+            .render(&mut encoder, &view, &self.camera_bind_group, &mut stats);
+        self.gpu_timer.end(&mut encoder);
 
         self.queue.submit(iter::once(encoder.finish()));
         let mut encoder = self
@@ -710,10 +1142,10 @@ impl RenderState {
 
         let viewmodel = {
             match viewmodel {
-                Some((viewmodel, viewmodel_instance)) => {
+                Some((viewmodel, viewmodel_instance, transform)) => {
                     let mut vertices = Vec::new();
                     viewmodel.add_vertices(
-                        Model::create_matrix_trs(&Vec3::ZERO, &Vec3::ZERO, &Vec3::ZERO, &Vec3::ONE),
+                        transform,
                         viewmodel_instance,
                         Some(item_registry),
                         &mut |position, coords| {
@@ -766,8 +1198,12 @@ impl RenderState {
 
             render_pass.set_vertex_buffer(0, viewmodel.0.slice(..));
             render_pass.draw(0..viewmodel.1, 0..1);
+            stats.draw_triangles(viewmodel.1);
         }
 
+        self.snapshots
+            .capture(&self.device, &mut encoder, &output.texture);
+        self.gpu_timer.begin(&mut encoder, GpuPass::Gui);
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("GUI Render Pass"),
@@ -790,13 +1226,25 @@ impl RenderState {
             });
             render_pass.set_pipeline(&self.gui_render_pipeline);
             render_pass.set_bind_group(0, &self.texture.diffuse_bind_group, &[]);
-            let (buffer, vertex_count) =
-                gui.draw(&self.device, item_registry, self.mouse, self.size);
-            render_pass.set_vertex_buffer(0, buffer);
-            render_pass.draw(0..vertex_count, 0..1);
+            for (buffer, vertex_count) in gui.draw(
+                &self.device,
+                &self.queue,
+                item_registry,
+                self.mouse,
+                self.size,
+            ) {
+                render_pass.set_vertex_buffer(0, buffer);
+                render_pass.draw(0..vertex_count, 0..1);
+                stats.draw_triangles(vertex_count);
+            }
         }
+        self.gpu_timer.end(&mut encoder);
+        self.gpu_timer.resolve(&mut encoder);
 
         self.queue.submit(iter::once(encoder.finish()));
+        self.gpu_timer.finish_frame();
+        self.snapshots.finish_frame();
+        self.draw_stats = stats;
         output.present();
 
         Ok(())
@@ -805,6 +1253,8 @@ impl RenderState {
 pub struct OutlineRenderer {
     buffer: Buffer,
     render: AtomicBool,
+    //debug shapes from the server and their vertex count
+    shapes: Option<(Buffer, u32)>,
     pipeline: wgpu::RenderPipeline,
 }
 impl OutlineRenderer {
@@ -817,51 +1267,43 @@ impl OutlineRenderer {
                 usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
             }),
             render: AtomicBool::new(false),
+            shapes: None,
         }
     }
-    pub fn set_aabb(&self, aabb: Option<AABB>, queue: &Queue) {
+    pub fn set_shapes(&mut self, vertices: &[OutlineVertex], device: &Device) {
+        self.shapes = if vertices.is_empty() {
+            None
+        } else {
+            Some((
+                device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("Debug Shape Buffer"),
+                    contents: bytemuck::cast_slice(vertices),
+                    usage: BufferUsages::VERTEX,
+                }),
+                vertices.len() as u32,
+            ))
+        };
+    }
+    pub fn set_aabb(&self, aabb: Option<AABB>, highlighted: bool, queue: &Queue) {
         self.render.store(aabb.is_some(), Relaxed);
         if let Some(aabb) = aabb {
-            let p000 = OutlineVertex {
-                position: [aabb.x as f32, aabb.y as f32, aabb.z as f32],
-            };
-            let p001 = OutlineVertex {
-                position: [aabb.x as f32, aabb.y as f32, (aabb.z + aabb.d) as f32],
+            let color = if highlighted {
+                [1., 0.85, 0.3]
+            } else {
+                [0., 0., 0.]
             };
-            let p010 = OutlineVertex {
-                position: [aabb.x as f32, (aabb.y + aabb.h) as f32, aabb.z as f32],
-            };
-            let p011 = OutlineVertex {
-                position: [
-                    aabb.x as f32,
-                    (aabb.y + aabb.h) as f32,
-                    (aabb.z + aabb.d) as f32,
-                ],
-            };
-            let p100 = OutlineVertex {
-                position: [(aabb.x + aabb.w) as f32, aabb.y as f32, aabb.z as f32],
-            };
-            let p101 = OutlineVertex {
-                position: [
-                    (aabb.x + aabb.w) as f32,
-                    aabb.y as f32,
-                    (aabb.z + aabb.d) as f32,
-                ],
-            };
-            let p110 = OutlineVertex {
-                position: [
-                    (aabb.x + aabb.w) as f32,
-                    (aabb.y + aabb.h) as f32,
-                    aabb.z as f32,
-                ],
-            };
-            let p111 = OutlineVertex {
-                position: [
-                    (aabb.x + aabb.w) as f32,
-                    (aabb.y + aabb.h) as f32,
-                    (aabb.z + aabb.d) as f32,
-                ],
+            let vertex = |x: f64, y: f64, z: f64| OutlineVertex {
+                position: [x as f32, y as f32, z as f32],
+                color,
             };
+            let p000 = vertex(aabb.x, aabb.y, aabb.z);
+            let p001 = vertex(aabb.x, aabb.y, aabb.z + aabb.d);
+            let p010 = vertex(aabb.x, aabb.y + aabb.h, aabb.z);
+            let p011 = vertex(aabb.x, aabb.y + aabb.h, aabb.z + aabb.d);
+            let p100 = vertex(aabb.x + aabb.w, aabb.y, aabb.z);
+            let p101 = vertex(aabb.x + aabb.w, aabb.y, aabb.z + aabb.d);
+            let p110 = vertex(aabb.x + aabb.w, aabb.y + aabb.h, aabb.z);
+            let p111 = vertex(aabb.x + aabb.w, aabb.y + aabb.h, aabb.z + aabb.d);
             let vertices = vec![
                 p000, p001, p001, p101, p101, p100, p100, p000, p010, p011, p011, p111, p111, p110,
                 p110, p010, p000, p010, p100, p110, p101, p111, p001, p011,
@@ -875,8 +1317,10 @@ impl OutlineRenderer {
         encoder: &mut CommandEncoder,
         view: &TextureView,
         camera_bind_group: &BindGroup,
+        stats: &mut DrawStats,
     ) {
-        if !self.render.load(Relaxed) {
+        let outline = self.render.load(Relaxed);
+        if !outline && self.shapes.is_none() {
             return;
         }
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -893,8 +1337,605 @@ impl OutlineRenderer {
         });
         render_pass.set_bind_group(0, camera_bind_group, &[]);
         render_pass.set_pipeline(&self.pipeline);
-        render_pass.set_vertex_buffer(0, self.buffer.slice(..));
-        render_pass.draw(0..24, 0..1);
+        if outline {
+            render_pass.set_vertex_buffer(0, self.buffer.slice(..));
+            render_pass.draw(0..24, 0..1);
+            stats.draw_lines();
+        }
+        if let Some((buffer, count)) = &self.shapes {
+            render_pass.set_vertex_buffer(0, buffer.slice(..));
+            render_pass.draw(0..*count, 0..1);
+            stats.draw_lines();
+        }
+    }
+}
+pub struct BorderRenderer {
+    pipeline: wgpu::RenderPipeline,
+}
+impl BorderRenderer {
+    const VIEW_DISTANCE: f64 = 64.;
+    const WARNING_DISTANCE: f64 = 8.;
+    pub fn new(pipeline: wgpu::RenderPipeline) -> Self {
+        Self { pipeline }
+    }
+    fn create_vertices(border: &WorldBorder, position: Position) -> Vec<BorderVertex> {
+        let mut vertices = Vec::new();
+        let distance = border.distance_to_edge(&position);
+        if distance > Self::VIEW_DISTANCE {
+            return vertices;
+        }
+        let warning = (1. - (distance / Self::WARNING_DISTANCE)).clamp(0., 1.);
+        let alpha = (1. - (distance / Self::VIEW_DISTANCE)).clamp(0., 1.) * 0.5;
+        let color = ((0.2 + warning * 0.8) * 255.) as u32
+            | ((((0.4 - warning * 0.3) * 255.) as u32) << 8)
+            | ((((1. - warning * 0.9) * 255.) as u32) << 16)
+            | (((alpha * 255.) as u32) << 24);
+        let min_x = border.center_x - border.radius;
+        let max_x = border.center_x + border.radius;
+        let min_z = border.center_z - border.radius;
+        let max_z = border.center_z + border.radius;
+        let y0 = (position.y - Self::VIEW_DISTANCE) as f32;
+        let y1 = (position.y + Self::VIEW_DISTANCE) as f32;
+        let from_x = (position.x - Self::VIEW_DISTANCE).max(min_x) as f32;
+        let to_x = (position.x + Self::VIEW_DISTANCE).min(max_x) as f32;
+        let from_z = (position.z - Self::VIEW_DISTANCE).max(min_z) as f32;
+        let to_z = (position.z + Self::VIEW_DISTANCE).min(max_z) as f32;
+        let mut add_quad = |p0: [f32; 3], p1: [f32; 3], p2: [f32; 3], p3: [f32; 3]| {
+            for position in [p0, p1, p2, p2, p3, p0] {
+                vertices.push(BorderVertex { position, color });
+            }
+        };
+        if position.x - min_x <= Self::VIEW_DISTANCE {
+            let x = min_x as f32;
+            add_quad(
+                [x, y0, from_z],
+                [x, y0, to_z],
+                [x, y1, to_z],
+                [x, y1, from_z],
+            );
+        }
+        if max_x - position.x <= Self::VIEW_DISTANCE {
+            let x = max_x as f32;
+            add_quad(
+                [x, y0, from_z],
+                [x, y0, to_z],
+                [x, y1, to_z],
+                [x, y1, from_z],
+            );
+        }
+        if position.z - min_z <= Self::VIEW_DISTANCE {
+            let z = min_z as f32;
+            add_quad(
+                [from_x, y0, z],
+                [to_x, y0, z],
+                [to_x, y1, z],
+                [from_x, y1, z],
+            );
+        }
+        if max_z - position.z <= Self::VIEW_DISTANCE {
+            let z = max_z as f32;
+            add_quad(
+                [from_x, y0, z],
+                [to_x, y0, z],
+                [to_x, y1, z],
+                [from_x, y1, z],
+            );
+        }
+        vertices
+    }
+    pub fn render(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        depth_view: &TextureView,
+        camera_bind_group: &BindGroup,
+        border: &WorldBorder,
+        position: Position,
+        stats: &mut DrawStats,
+    ) {
+        let vertices = Self::create_vertices(border, position);
+        if vertices.is_empty() {
+            return;
+        }
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Border Buffer"),
+            usage: BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(vertices.as_slice()),
+        });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Border Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        render_pass.draw(0..vertices.len() as u32, 0..1);
+        stats.draw_triangles(vertices.len() as u32);
+    }
+}
+
+pub struct GhostRenderer {
+    pipeline: wgpu::RenderPipeline,
+    vertices: Vec<GhostVertex>,
+}
+impl GhostRenderer {
+    const COLOR: [f32; 4] = [1., 1., 1., 0.5];
+    const BLOCKED_COLOR: [f32; 4] = [1., 0.2, 0.2, 0.5];
+    pub fn new(pipeline: wgpu::RenderPipeline) -> Self {
+        Self {
+            pipeline,
+            vertices: Vec::new(),
+        }
+    }
+    pub fn set_block(&mut self, block: Option<(BlockPosition, &BlockData)>, blocked: bool) {
+        self.vertices.clear();
+        let (position, block) = match block {
+            Some(block) => block,
+            None => return,
+        };
+        let color = if blocked {
+            Self::BLOCKED_COLOR
+        } else {
+            Self::COLOR
+        };
+        let base_position = position.to_position();
+        let mut add_vertex = |position: Position, coords: (f32, f32)| {
+            self.vertices.push(GhostVertex {
+                position: [position.x as f32, position.y as f32, position.z as f32],
+                tex_coords: [coords.0, coords.1],
+                color,
+            })
+        };
+        match &block.block_type {
+            BlockRenderDataType::Air => {}
+            BlockRenderDataType::Cube(cube_data) => {
+                for face in Face::all() {
+                    face.add_vertices(
+                        cube_data.by_face(*face).get_first_coords(),
+                        &mut |position, coords| add_vertex(base_position + position, coords),
+                    );
+                }
+            }
+            BlockRenderDataType::Static(model) => {
+                for model in &model.models {
+                    model.0.add_vertices(
+                        model.1.to_matrix(),
+                        &ModelInstanceData::new(),
+                        None,
+                        &mut |position, coords| {
+                            add_vertex(
+                                base_position
+                                    + position
+                                    + Position {
+                                        x: 0.5,
+                                        y: 0.,
+                                        z: 0.5,
+                                    },
+                                coords,
+                            )
+                        },
+                    );
+                }
+            }
+            BlockRenderDataType::Foliage(foliage) => {
+                if let Some(texture) = foliage.cross {
+                    for shift in &[0., 1.] {
+                        Face::Front.add_vertices(
+                            texture.get_first_coords(),
+                            &mut |position, coords| {
+                                add_vertex(
+                                    base_position
+                                        + Position {
+                                            x: (shift - position.x).abs(),
+                                            y: position.y,
+                                            z: (1. - position.x).abs(),
+                                        },
+                                    coords,
+                                )
+                            },
+                        );
+                    }
+                }
+            }
+        }
+    }
+    pub fn render(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        depth_view: &TextureView,
+        texture_bind_group: &BindGroup,
+        camera_bind_group: &BindGroup,
+        stats: &mut DrawStats,
+    ) {
+        if self.vertices.is_empty() {
+            return;
+        }
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Ghost Buffer"),
+            usage: BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(self.vertices.as_slice()),
+        });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Ghost Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, texture_bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        render_pass.draw(0..self.vertices.len() as u32, 0..1);
+        stats.draw_triangles(self.vertices.len() as u32);
+    }
+}
+
+//stage textures are bb:breaking_0, bb:breaking_1 and so on, as many as the content provides
+pub struct CrackRenderer {
+    pipeline: wgpu::RenderPipeline,
+    stages: Vec<TexCoords>,
+    vertices: Vec<GhostVertex>,
+}
+impl CrackRenderer {
+    const OFFSET: f64 = 0.002;
+    pub fn new(pipeline: wgpu::RenderPipeline) -> Self {
+        Self {
+            pipeline,
+            stages: Vec::new(),
+            vertices: Vec::new(),
+        }
+    }
+    pub fn set_stages(&mut self, texture_atlas: &TextureAtlas) {
+        self.stages = (0..)
+            .map(|stage| format!("bb:breaking_{}", stage))
+            .take_while(|texture| texture_atlas.contains(texture))
+            .map(|texture| texture_atlas.get(texture.as_str()))
+            .collect();
+    }
+    pub fn set_block(&mut self, block: Option<(BlockPosition, f32)>) {
+        self.vertices.clear();
+        let (position, progress) = match block {
+            Some(block) if !self.stages.is_empty() => block,
+            _ => return,
+        };
+        let stage =
+            ((progress.max(0.) * self.stages.len() as f32) as usize).min(self.stages.len() - 1);
+        let texture = self.stages[stage];
+        let base_position = position.to_position();
+        let vertices = &mut self.vertices;
+        //the overlay is slightly bigger than the block so it doesn't fight with its faces
+        for face in Face::all() {
+            face.add_vertices(texture, &mut |position, coords| {
+                let scale = |value: f64| (value - 0.5) * (1. + Self::OFFSET * 2.) + 0.5;
+                vertices.push(GhostVertex {
+                    position: [
+                        (base_position.x + scale(position.x)) as f32,
+                        (base_position.y + scale(position.y)) as f32,
+                        (base_position.z + scale(position.z)) as f32,
+                    ],
+                    tex_coords: [coords.0, coords.1],
+                    color: [1., 1., 1., 1.],
+                })
+            });
+        }
+    }
+    pub fn render(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        depth_view: &TextureView,
+        texture_bind_group: &BindGroup,
+        camera_bind_group: &BindGroup,
+        stats: &mut DrawStats,
+    ) {
+        if self.vertices.is_empty() {
+            return;
+        }
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Crack Buffer"),
+            usage: BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(self.vertices.as_slice()),
+        });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Crack Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, texture_bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        render_pass.draw(0..self.vertices.len() as u32, 0..1);
+        stats.draw_triangles(self.vertices.len() as u32);
+    }
+}
+
+pub struct TextDisplayRenderer {
+    pipeline: wgpu::RenderPipeline,
+    vertices: Vec<GhostVertex>,
+}
+impl TextDisplayRenderer {
+    const FONT_SIZE: f32 = 60.;
+    const PIXEL_SIZE: f32 = 0.25 / Self::FONT_SIZE;
+    const PADDING: f32 = 8.;
+    const MAX_DISTANCE: f64 = 64.;
+    pub fn new(pipeline: wgpu::RenderPipeline) -> Self {
+        Self {
+            pipeline,
+            vertices: Vec::new(),
+        }
+    }
+    fn color_to_array(color: Color) -> [f32; 4] {
+        [
+            color.r as f32 / 255.,
+            color.g as f32 / 255.,
+            color.b as f32 / 255.,
+            color.a as f32 / 255.,
+        ]
+    }
+    fn parse_line(line: &str) -> (String, Vec<[f32; 4]>) {
+        let mut text = String::new();
+        let mut colors = Vec::new();
+        let mut color = [1., 1., 1., 1.];
+        let mut chars = line.chars();
+        while let Some(ch) = chars.next() {
+            if ch == '§' {
+                let code: String = chars.clone().take(6).collect();
+                if code.len() == 6 && code.chars().all(|ch| ch.is_ascii_hexdigit()) {
+                    let rgb = u32::from_str_radix(code.as_str(), 16).unwrap();
+                    color = [
+                        ((rgb >> 16) & 255) as f32 / 255.,
+                        ((rgb >> 8) & 255) as f32 / 255.,
+                        (rgb & 255) as f32 / 255.,
+                        1.,
+                    ];
+                    for _ in 0..6 {
+                        chars.next();
+                    }
+                    continue;
+                }
+            }
+            text.push(ch);
+            colors.push(color);
+        }
+        (text, colors)
+    }
+    pub fn set_text_displays(
+        &mut self,
+        camera: &ClientPlayer,
+        text_displays: &HashMap<u32, TextDisplay>,
+        text_renderer: &TextRenderer,
+        texture_atlas: &TextureAtlas,
+    ) {
+        self.vertices.clear();
+        let eye = camera.get_eye();
+        let front = camera.make_front();
+        let right = front.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(front).normalize();
+        let mut text_displays: Vec<_> = text_displays
+            .values()
+            .filter(|text_display| text_display.position.distance(&eye) < Self::MAX_DISTANCE)
+            .collect();
+        text_displays.sort_by(|a, b| {
+            b.position
+                .distance(&eye)
+                .total_cmp(&a.position.distance(&eye))
+        });
+        let scale = Scale::uniform(Self::FONT_SIZE);
+        let v_metrics = text_renderer.font.v_metrics(scale);
+        let line_height = v_metrics.ascent - v_metrics.descent + v_metrics.line_gap;
+        for text_display in text_displays {
+            let pixel_size = Self::PIXEL_SIZE * text_display.scale;
+            let center = Vector3::new(
+                text_display.position.x as f32,
+                text_display.position.y as f32,
+                text_display.position.z as f32,
+            );
+            let lines: Vec<_> = text_display
+                .text
+                .split('\n')
+                .map(|line| {
+                    let (text, colors) = Self::parse_line(line);
+                    let glyphs: Vec<_> = text_renderer
+                        .font
+                        .layout(
+                            text.as_str(),
+                            scale,
+                            rusttype::Point {
+                                x: 0.,
+                                y: v_metrics.ascent,
+                            },
+                        )
+                        .collect();
+                    let width = glyphs
+                        .last()
+                        .map(|glyph| {
+                            glyph.position().x + glyph.unpositioned().h_metrics().advance_width
+                        })
+                        .unwrap_or(0.);
+                    (glyphs, colors, width)
+                })
+                .collect();
+            let width = lines
+                .iter()
+                .map(|line| line.2)
+                .fold(0., |a: f32, b| a.max(b));
+            let height = line_height * lines.len() as f32;
+            let mut add_quad =
+                |x1: f32, y1: f32, x2: f32, y2: f32, uv: TexCoords, color: [f32; 4]| {
+                    let corner = |x: f32, y: f32| {
+                        let position = center + right * ((x - width / 2.) * pixel_size)
+                            - up * ((y - height / 2.) * pixel_size);
+                        [position.x, position.y, position.z]
+                    };
+                    let vertex_1 = GhostVertex {
+                        position: corner(x1, y1),
+                        tex_coords: [uv.u1, uv.v1],
+                        color,
+                    };
+                    let vertex_2 = GhostVertex {
+                        position: corner(x2, y1),
+                        tex_coords: [uv.u2, uv.v1],
+                        color,
+                    };
+                    let vertex_3 = GhostVertex {
+                        position: corner(x2, y2),
+                        tex_coords: [uv.u2, uv.v2],
+                        color,
+                    };
+                    let vertex_4 = GhostVertex {
+                        position: corner(x1, y2),
+                        tex_coords: [uv.u1, uv.v2],
+                        color,
+                    };
+                    self.vertices.push(vertex_1);
+                    self.vertices.push(vertex_4);
+                    self.vertices.push(vertex_3);
+                    self.vertices.push(vertex_3);
+                    self.vertices.push(vertex_2);
+                    self.vertices.push(vertex_1);
+                };
+            if text_display.background.a > 0 {
+                add_quad(
+                    -Self::PADDING,
+                    -Self::PADDING,
+                    width + Self::PADDING,
+                    height + Self::PADDING,
+                    TexCoords::ZERO,
+                    Self::color_to_array(text_display.background),
+                );
+            }
+            for (i, (glyphs, colors, line_width)) in lines.iter().enumerate() {
+                let shift_x = (width - line_width) / 2.;
+                let shift_y = line_height * i as f32;
+                for (glyph, color) in glyphs.iter().zip(colors.iter()) {
+                    if let Some(bb) = glyph.pixel_bounding_box() {
+                        add_quad(
+                            bb.min.x as f32 + shift_x,
+                            bb.min.y as f32 + shift_y,
+                            bb.max.x as f32 + shift_x,
+                            bb.max.y as f32 + shift_y,
+                            texture_atlas.get(
+                                ("font_".to_string() + glyph.id().0.to_string().as_str()).as_str(),
+                            ),
+                            *color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+    pub fn render(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        depth_view: &TextureView,
+        texture_bind_group: &BindGroup,
+        camera_bind_group: &BindGroup,
+        stats: &mut DrawStats,
+    ) {
+        if self.vertices.is_empty() {
+            return;
+        }
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Text Display Buffer"),
+            usage: BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(self.vertices.as_slice()),
+        });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Text Display Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, texture_bind_group, &[]);
+        render_pass.set_bind_group(1, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        render_pass.draw(0..self.vertices.len() as u32, 0..1);
+        stats.draw_triangles(self.vertices.len() as u32);
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GhostVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub color: [f32; 4],
+}
+impl GhostVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x4];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
     }
 }
 
@@ -905,28 +1946,42 @@ pub struct ChunkVertex {
     pub tex_coords: [f32; 2],
     pub render_data: u32,
     pub animation_shift: f32,
+    pub tint: [u8; 4],
 }
 impl ChunkVertex {
-    pub fn new(position: Position, coords: [f32; 2], render_data: u32, texture: Texture) -> Self {
+    pub fn new(
+        position: Position,
+        coords: [f32; 2],
+        render_data: u32,
+        texture: Texture,
+        tint: [u8; 4],
+    ) -> Self {
         match texture {
             Texture::Static { .. } => ChunkVertex {
                 position: [position.x as f32, position.y as f32, position.z as f32],
                 tex_coords: coords,
                 animation_shift: 0.,
                 render_data,
+                tint,
             },
             Texture::Animated { stages, time, .. } => ChunkVertex {
                 position: [position.x as f32, position.y as f32, position.z as f32],
                 tex_coords: coords,
                 animation_shift: texture.get_shift(),
                 render_data: render_data | ((stages as u32) << 24) | ((time as u32) << 16),
+                tint,
             },
         }
     }
 }
 impl ChunkVertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 4] =
-        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Uint32, 3 => Float32];
+    const ATTRIBS: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x2,
+        2 => Uint32,
+        3 => Float32,
+        4 => Unorm8x4
+    ];
 
     fn desc() -> wgpu::VertexBufferLayout<'static> {
         use std::mem;
@@ -986,9 +2041,32 @@ impl GUIVertex {
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct OutlineVertex {
     pub position: [f32; 3],
+    pub color: [f32; 3],
 }
 impl OutlineVertex {
-    const ATTRIBS: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![0 => Float32x3];
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BorderVertex {
+    pub position: [f32; 3],
+    pub color: u32,
+}
+impl BorderVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Uint32];
 
     fn desc() -> wgpu::VertexBufferLayout<'static> {
         use std::mem;
@@ -1001,6 +2079,54 @@ impl OutlineVertex {
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct EnvironmentUniform {
+    fog_color: [f32; 3],
+    time: f32,
+    fog_density: f32,
+    motion: f32,
+    _padding: [f32; 2],
+}
+struct Environment {
+    uniform: EnvironmentUniform,
+    sky_color: [f32; 3],
+    last_time: f32,
+    reduced_motion: bool,
+}
+impl Environment {
+    const BLEND_SPEED: f32 = 1.;
+    fn new() -> Self {
+        let biome = ClientBiomeData::default();
+        Environment {
+            uniform: EnvironmentUniform {
+                fog_color: biome.fog_color,
+                time: 0.,
+                fog_density: biome.fog_density,
+                motion: 1.,
+                _padding: [0.; 2],
+            },
+            sky_color: biome.sky_color,
+            last_time: 0.,
+            reduced_motion: false,
+        }
+    }
+    fn update(&mut self, biome: ClientBiomeData, time: f32) {
+        let factor = ((time - self.last_time) * Self::BLEND_SPEED).clamp(0., 1.);
+        self.last_time = time;
+        let blend = |current: &mut [f32; 3], target: [f32; 3]| {
+            for (current, target) in current.iter_mut().zip(target) {
+                *current += (target - *current) * factor;
+            }
+        };
+        blend(&mut self.sky_color, biome.sky_color);
+        blend(&mut self.uniform.fog_color, biome.fog_color);
+        self.uniform.fog_density += (biome.fog_density - self.uniform.fog_density) * factor;
+        self.uniform.time = time;
+        self.uniform.motion = if self.reduced_motion { 0. } else { 1. };
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 struct CameraUniform {
@@ -1012,15 +2138,15 @@ impl CameraUniform {
             view_proj: cgmath::Matrix4::identity().into(),
         }
     }
-    fn load_view_proj_matrix(&mut self, camera: &ClientPlayer, aspect_ratio: f32) {
+    fn load_view_proj_matrix(&mut self, camera: &ClientPlayer, aspect_ratio: f32, fov: f32) {
         self.view_proj = (Self::OPENGL_TO_WGPU_MATRIX
-            * ClientPlayer::create_projection_matrix(aspect_ratio)
+            * ClientPlayer::create_projection_matrix(aspect_ratio, fov)
             * camera.create_view_matrix())
         .into();
     }
     fn load_viewmodel_matrix(&mut self, aspect_ratio: f32) {
         self.view_proj = (Self::OPENGL_TO_WGPU_MATRIX
-            * ClientPlayer::create_projection_matrix(aspect_ratio)
+            * ClientPlayer::create_projection_matrix(aspect_ratio, 90.)
             * ClientPlayer::create_default_view_matrix())
         .into();
     }
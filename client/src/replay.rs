@@ -0,0 +1,189 @@
+use std::path::Path;
+use std::time::Instant;
+
+use block_byte_common::gui::{GUIComponent, GUIElement, PositionAnchor};
+use block_byte_common::messages::PROTOCOL_VERSION;
+use block_byte_common::recording::{
+    self, RecordingFrame, RecordingHeader, RECORDING_TICKS_PER_SECOND, RECORDING_VERSION,
+};
+use block_byte_common::{Color, Position};
+use winit::event::VirtualKeyCode;
+
+use crate::gui::GUIRenderer;
+
+//plays back a recording made by the server, seeking rebuilds the world from the last keyframe before the target
+pub struct Replay {
+    header: RecordingHeader,
+    frames: Vec<RecordingFrame>,
+    //index of the next frame to play
+    next: usize,
+    //in ticks
+    time: f32,
+    length: u32,
+    speed: f32,
+    paused: bool,
+    last_update: Instant,
+    seek_target: Option<f32>,
+    rebuilding: bool,
+    status: String,
+}
+impl Replay {
+    const STATUS_ELEMENT: &'static str = "replay_status";
+    const SEEK_SECONDS: f32 = 10.;
+    const MIN_SPEED: f32 = 0.25;
+    const MAX_SPEED: f32 = 8.;
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|error| error.to_string())?;
+        let records =
+            recording::split_records(&data).ok_or_else(|| "not a recording".to_string())?;
+        let (header, frames) = records
+            .split_first()
+            .ok_or_else(|| "the recording is empty".to_string())?;
+        let header: RecordingHeader =
+            bitcode::deserialize(header).map_err(|_| "invalid recording header".to_string())?;
+        if header.version != RECORDING_VERSION {
+            return Err(format!("unsupported recording version {}", header.version));
+        }
+        if header.protocol_version != PROTOCOL_VERSION {
+            return Err(format!(
+                "recorded with protocol {}, this client uses {}",
+                header.protocol_version, PROTOCOL_VERSION
+            ));
+        }
+        let frames = frames
+            .iter()
+            .map(|frame| bitcode::deserialize::<RecordingFrame>(frame))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| "invalid recording frame".to_string())?;
+        if !frames.iter().any(|frame| frame.keyframe) {
+            return Err("the recording has no keyframe".to_string());
+        }
+        log::info!(
+            "replaying {} in {}, recorded at {}",
+            header.player,
+            header.world,
+            header.started
+        );
+        Ok(Replay {
+            header,
+            length: frames.last().map_or(0, |frame| frame.tick),
+            frames,
+            next: 0,
+            time: 0.,
+            speed: 1.,
+            paused: false,
+            last_update: Instant::now(),
+            seek_target: Some(0.),
+            rebuilding: false,
+            status: String::new(),
+        })
+    }
+    //the messages that are due, as they were sent by the server
+    pub fn take_messages(&mut self) -> Vec<Vec<u8>> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_update).as_secs_f32();
+        self.last_update = now;
+        let mut messages = match self.seek_target.take() {
+            Some(target) => self.rebuild(target),
+            None => {
+                if !self.paused {
+                    self.time += elapsed * RECORDING_TICKS_PER_SECOND as f32 * self.speed;
+                }
+                Vec::new()
+            }
+        };
+        while let Some(frame) = self
+            .frames
+            .get(self.next)
+            .filter(|frame| frame.tick as f32 <= self.time)
+        {
+            //keyframes repeat what the frames before them already did
+            if !frame.keyframe {
+                messages.extend(frame.messages.iter().cloned());
+            }
+            self.next += 1;
+        }
+        if self.time >= self.length as f32 {
+            self.time = self.length as f32;
+            self.paused = true;
+        }
+        messages
+    }
+    fn rebuild(&mut self, target: f32) -> Vec<Vec<u8>> {
+        let keyframe = self
+            .frames
+            .iter()
+            .rposition(|frame| frame.keyframe && frame.tick as f32 <= target)
+            .or_else(|| self.frames.iter().position(|frame| frame.keyframe))
+            .unwrap();
+        self.time = target.max(self.frames[keyframe].tick as f32);
+        self.next = keyframe + 1;
+        self.rebuilding = true;
+        self.frames[keyframe].messages.clone()
+    }
+    //true once after a seek, the world has to be cleared before the messages read with it are applied
+    pub fn take_rebuilding(&mut self) -> bool {
+        std::mem::take(&mut self.rebuilding)
+    }
+    //returns false for keys the replay doesn't use
+    pub fn on_key(&mut self, key: VirtualKeyCode) -> bool {
+        match key {
+            VirtualKeyCode::P => {
+                self.paused = !self.paused;
+                if !self.paused && self.time >= self.length as f32 {
+                    self.seek_target = Some(0.);
+                }
+            }
+            VirtualKeyCode::Left => self.seek_by(-Self::SEEK_SECONDS),
+            VirtualKeyCode::Right => self.seek_by(Self::SEEK_SECONDS),
+            VirtualKeyCode::Up => {
+                self.speed = (self.speed * 2.).clamp(Self::MIN_SPEED, Self::MAX_SPEED)
+            }
+            VirtualKeyCode::Down => {
+                self.speed = (self.speed / 2.).clamp(Self::MIN_SPEED, Self::MAX_SPEED)
+            }
+            _ => return false,
+        }
+        true
+    }
+    fn seek_by(&mut self, seconds: f32) {
+        let target =
+            self.seek_target.unwrap_or(self.time) + seconds * RECORDING_TICKS_PER_SECOND as f32;
+        self.seek_target = Some(target.clamp(0., self.length as f32));
+    }
+    fn format_time(ticks: f32) -> String {
+        let seconds = (ticks / RECORDING_TICKS_PER_SECOND as f32) as u32;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+    pub fn show_status(&mut self, gui: &mut GUIRenderer) {
+        let status = format!(
+            "{} {} / {} x{}{}",
+            self.header.player,
+            Self::format_time(self.time),
+            Self::format_time(self.length as f32),
+            self.speed,
+            if self.paused { " paused" } else { "" }
+        );
+        if status == self.status {
+            return;
+        }
+        gui.set_element(
+            Self::STATUS_ELEMENT.to_string(),
+            GUIElement {
+                component_type: GUIComponent::TextComponent {
+                    font_size: 25.,
+                    text: status.clone(),
+                },
+                position: Position {
+                    x: 0.,
+                    y: -30.,
+                    z: 0.,
+                },
+                anchor: PositionAnchor::Top,
+                base_color: Color::WHITE,
+                size_percent: None,
+            },
+        );
+        self.status = status;
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::path::PathBuf;
+
+use crate::accessibility::ColorPalette;
+
+pub struct ClientSettings {
+    path: Option<PathBuf>,
+    settings: HashMap<String, String>,
+}
+impl ClientSettings {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let mut settings = HashMap::new();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(input) = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+        {
+            for line in input.lines() {
+                if let Some((key, value)) = line.split_once('=') {
+                    settings.insert(key.trim().to_string(), value.trim().to_string());
+                }
+            }
+        }
+        ClientSettings { path, settings }
+    }
+    pub fn save(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = &self.path {
+            let mut keys: Vec<_> = self.settings.keys().collect();
+            keys.sort();
+            let output: String = keys
+                .iter()
+                .map(|key| format!("{}={}\n", key, self.settings.get(*key).unwrap()))
+                .collect();
+            if let Err(error) = std::fs::write(path, output) {
+                log::warn!("couldn't save client settings: {}", error);
+            }
+        }
+    }
+    pub fn get_f32(&mut self, key: &str, default: f32) -> f32 {
+        self.settings
+            .entry(key.to_string())
+            .or_insert_with(|| default.to_string())
+            .parse()
+            .unwrap_or(default)
+    }
+    pub fn get(&mut self, key: &str, default: &str) -> String {
+        self.settings
+            .entry(key.to_string())
+            .or_insert_with(|| default.to_string())
+            .clone()
+    }
+    pub fn get_bool(&mut self, key: &str, default: bool) -> bool {
+        self.settings
+            .entry(key.to_string())
+            .or_insert_with(|| default.to_string())
+            .parse()
+            .unwrap_or(default)
+    }
+    pub fn set(&mut self, key: &str, value: String) {
+        self.settings.insert(key.to_string(), value);
+    }
+}
+
+//the deltas come from device events, so they are never accelerated by the os
+pub struct MouseSettings {
+    pub accelerate: bool,
+    pub sensitivity: f32,
+    pub sensitivity_x: f32,
+    pub sensitivity_y: f32,
+    pub acceleration: f32,
+    pub invert_y: bool,
+}
+impl MouseSettings {
+    pub fn load(settings: &mut ClientSettings) -> Self {
+        MouseSettings {
+            accelerate: settings.get_bool("mouse.accelerate", false),
+            sensitivity: settings.get_f32("mouse.sensitivity", 0.3),
+            sensitivity_x: settings.get_f32("mouse.sensitivity_x", 1.),
+            sensitivity_y: settings.get_f32("mouse.sensitivity_y", 1.),
+            acceleration: settings.get_f32("mouse.acceleration", 0.05),
+            invert_y: settings.get_bool("mouse.invert_y", false),
+        }
+    }
+    pub fn save(&self, settings: &mut ClientSettings) {
+        settings.set("mouse.accelerate", self.accelerate.to_string());
+        settings.set("mouse.sensitivity", self.sensitivity.to_string());
+        settings.set("mouse.sensitivity_x", self.sensitivity_x.to_string());
+        settings.set("mouse.sensitivity_y", self.sensitivity_y.to_string());
+        settings.set("mouse.acceleration", self.acceleration.to_string());
+        settings.set("mouse.invert_y", self.invert_y.to_string());
+    }
+    pub fn apply(&self, x: f64, y: f64) -> (f32, f32) {
+        let (mut x, mut y) = (x as f32, y as f32);
+        if self.accelerate {
+            let factor = 1. + (x * x + y * y).sqrt() * self.acceleration;
+            x *= factor;
+            y *= factor;
+        }
+        x *= self.sensitivity * self.sensitivity_x;
+        y *= self.sensitivity * self.sensitivity_y;
+        if self.invert_y {
+            y = -y;
+        }
+        (x, y)
+    }
+}
+
+pub struct ScrollSettings {
+    pub sensitivity: f32,
+    pub invert: bool,
+    pub hotbar_locked: bool,
+}
+impl ScrollSettings {
+    pub fn load(settings: &mut ClientSettings) -> Self {
+        ScrollSettings {
+            sensitivity: settings.get_f32("scroll.sensitivity", 1.),
+            invert: settings.get_bool("scroll.invert", false),
+            hotbar_locked: settings.get_bool("scroll.hotbar_locked", false),
+        }
+    }
+    pub fn save(&self, settings: &mut ClientSettings) {
+        settings.set("scroll.sensitivity", self.sensitivity.to_string());
+        settings.set("scroll.invert", self.invert.to_string());
+        settings.set("scroll.hotbar_locked", self.hotbar_locked.to_string());
+    }
+    pub fn apply(&self, y: f32) -> f32 {
+        let y = y * self.sensitivity;
+        if self.invert {
+            -y
+        } else {
+            y
+        }
+    }
+}
+
+pub struct AccessibilitySettings {
+    pub color_palette: ColorPalette,
+    pub reduced_motion: bool,
+    pub sound_captions: bool,
+}
+impl AccessibilitySettings {
+    pub fn load(settings: &mut ClientSettings) -> Self {
+        AccessibilitySettings {
+            color_palette: ColorPalette::parse(
+                settings
+                    .get("accessibility.color_palette", "default")
+                    .as_str(),
+            )
+            .unwrap_or(ColorPalette::Default),
+            reduced_motion: settings.get_bool("accessibility.reduced_motion", false),
+            sound_captions: settings.get_bool("accessibility.sound_captions", false),
+        }
+    }
+    pub fn save(&self, settings: &mut ClientSettings) {
+        settings.set(
+            "accessibility.color_palette",
+            self.color_palette.name().to_string(),
+        );
+        settings.set(
+            "accessibility.reduced_motion",
+            self.reduced_motion.to_string(),
+        );
+        settings.set(
+            "accessibility.sound_captions",
+            self.sound_captions.to_string(),
+        );
+    }
+}
+
+pub struct GraphicsSettings {
+    pub render_distance: u32,
+    pub fov: f32,
+    pub vsync: bool,
+    pub gui_scale: f32,
+}
+impl GraphicsSettings {
+    pub fn load(settings: &mut ClientSettings) -> Self {
+        GraphicsSettings {
+            render_distance: settings.get_f32("graphics.render_distance", 16.).max(1.) as u32,
+            fov: settings.get_f32("graphics.fov", 90.).clamp(30., 120.),
+            vsync: settings.get_bool("graphics.vsync", true),
+            gui_scale: settings.get_f32("graphics.gui_scale", 1.).clamp(0.5, 2.),
+        }
+    }
+    pub fn save(&self, settings: &mut ClientSettings) {
+        settings.set("graphics.render_distance", self.render_distance.to_string());
+        settings.set("graphics.fov", self.fov.to_string());
+        settings.set("graphics.vsync", self.vsync.to_string());
+        settings.set("graphics.gui_scale", self.gui_scale.to_string());
+    }
+}
+
+pub struct AudioSettings {
+    pub volume: f32,
+}
+impl AudioSettings {
+    pub fn load(settings: &mut ClientSettings) -> Self {
+        AudioSettings {
+            volume: settings.get_f32("audio.volume", 1.).clamp(0., 1.),
+        }
+    }
+    pub fn save(&self, settings: &mut ClientSettings) {
+        settings.set("audio.volume", self.volume.to_string());
+    }
+}
+
+pub struct PrivacySettings {
+    //the server can ask for a snapshot of the view, without this it is always declined
+    pub allow_snapshots: bool,
+}
+impl PrivacySettings {
+    pub fn load(settings: &mut ClientSettings) -> Self {
+        PrivacySettings {
+            allow_snapshots: settings.get_bool("privacy.allow_snapshots", false),
+        }
+    }
+    pub fn save(&self, settings: &mut ClientSettings) {
+        settings.set("privacy.allow_snapshots", self.allow_snapshots.to_string());
+    }
+}
+
+pub struct ViewModelSettings {
+    pub bobbing: f32,
+    pub swing_time: f32,
+    pub swing_angle: f32,
+    pub equip_time: f32,
+}
+impl ViewModelSettings {
+    pub fn load(settings: &mut ClientSettings) -> Self {
+        ViewModelSettings {
+            bobbing: settings.get_f32("viewmodel.bobbing", 1.).max(0.),
+            swing_time: settings.get_f32("viewmodel.swing_time", 0.25).max(0.05),
+            swing_angle: settings.get_f32("viewmodel.swing_angle", 40.),
+            equip_time: settings.get_f32("viewmodel.equip_time", 0.15).max(0.),
+        }
+    }
+    pub fn save(&self, settings: &mut ClientSettings) {
+        settings.set("viewmodel.bobbing", self.bobbing.to_string());
+        settings.set("viewmodel.swing_time", self.swing_time.to_string());
+        settings.set("viewmodel.swing_angle", self.swing_angle.to_string());
+        settings.set("viewmodel.equip_time", self.equip_time.to_string());
+    }
+}
+
+pub struct Identity {
+    pub username: String,
+    pub uuid: String,
+    pub token: Option<String>,
+}
+impl Identity {
+    pub fn load(settings: &mut ClientSettings) -> Self {
+        let username = settings.get("identity.username", "player");
+        let uuid = settings.get("identity.uuid", "");
+        let uuid = if uuid.is_empty() {
+            let uuid = Self::generate_uuid();
+            settings.set("identity.uuid", uuid.clone());
+            uuid
+        } else {
+            uuid
+        };
+        let token = settings.get("identity.token", "");
+        Identity {
+            username,
+            uuid,
+            token: if token.is_empty() { None } else { Some(token) },
+        }
+    }
+    fn generate_uuid() -> String {
+        let random = || {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos(),
+            );
+            hasher.finish()
+        };
+        let high = (random() & 0xFFFF_FFFF_FFFF_0FFF) | 0x4000;
+        let low = (random() & 0x3FFF_FFFF_FFFF_FFFF) | 0x8000_0000_0000_0000;
+        format!(
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            high >> 32,
+            (high >> 16) & 0xFFFF,
+            high & 0xFFFF,
+            low >> 48,
+            low & 0xFFFF_FFFF_FFFF
+        )
+    }
+}
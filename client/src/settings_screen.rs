@@ -0,0 +1,331 @@
+use block_byte_common::gui::{GUIComponent, GUIElement, PositionAnchor};
+use block_byte_common::{Color, Position, Vec2};
+
+use crate::gui::GUIRenderer;
+use crate::settings::{
+    AccessibilitySettings, AudioSettings, GraphicsSettings, MouseSettings, PrivacySettings,
+};
+
+#[derive(Clone, Copy)]
+enum SettingsOption {
+    RenderDistance,
+    Fov,
+    MouseSensitivity,
+    Vsync,
+    Volume,
+    GuiScale,
+    Snapshots,
+    ColorPalette,
+    ReducedMotion,
+    SoundCaptions,
+}
+impl SettingsOption {
+    const ALL: [SettingsOption; 10] = [
+        SettingsOption::RenderDistance,
+        SettingsOption::Fov,
+        SettingsOption::MouseSensitivity,
+        SettingsOption::Vsync,
+        SettingsOption::Volume,
+        SettingsOption::GuiScale,
+        SettingsOption::Snapshots,
+        SettingsOption::ColorPalette,
+        SettingsOption::ReducedMotion,
+        SettingsOption::SoundCaptions,
+    ];
+    fn id(&self) -> &'static str {
+        match self {
+            SettingsOption::RenderDistance => "render_distance",
+            SettingsOption::Fov => "fov",
+            SettingsOption::MouseSensitivity => "mouse_sensitivity",
+            SettingsOption::Vsync => "vsync",
+            SettingsOption::Volume => "volume",
+            SettingsOption::GuiScale => "gui_scale",
+            SettingsOption::Snapshots => "snapshots",
+            SettingsOption::ColorPalette => "color_palette",
+            SettingsOption::ReducedMotion => "reduced_motion",
+            SettingsOption::SoundCaptions => "sound_captions",
+        }
+    }
+    fn is_accessibility(&self) -> bool {
+        matches!(
+            self,
+            SettingsOption::ColorPalette
+                | SettingsOption::ReducedMotion
+                | SettingsOption::SoundCaptions
+        )
+    }
+    fn label(
+        &self,
+        graphics: &GraphicsSettings,
+        mouse: &MouseSettings,
+        audio: &AudioSettings,
+        privacy: &PrivacySettings,
+        accessibility: &AccessibilitySettings,
+    ) -> String {
+        let on_off = |value: bool| if value { "on" } else { "off" };
+        match self {
+            SettingsOption::RenderDistance => {
+                format!("Render Distance: {} chunks", graphics.render_distance)
+            }
+            SettingsOption::Fov => format!("FOV: {:.0}", graphics.fov),
+            SettingsOption::MouseSensitivity => {
+                format!("Mouse Sensitivity: {:.2}", mouse.sensitivity)
+            }
+            SettingsOption::Vsync => format!("VSync: {}", on_off(graphics.vsync)),
+            SettingsOption::Volume => format!("Volume: {:.0}%", audio.volume * 100.),
+            SettingsOption::GuiScale => format!("GUI Scale: {:.2}", graphics.gui_scale),
+            SettingsOption::Snapshots => format!(
+                "Server Snapshots: {}",
+                if privacy.allow_snapshots {
+                    "allowed"
+                } else {
+                    "denied"
+                }
+            ),
+            SettingsOption::ColorPalette => {
+                format!("Color Palette: {}", accessibility.color_palette.name())
+            }
+            SettingsOption::ReducedMotion => {
+                format!("Reduced Motion: {}", on_off(accessibility.reduced_motion))
+            }
+            SettingsOption::SoundCaptions => {
+                format!("Sound Captions: {}", on_off(accessibility.sound_captions))
+            }
+        }
+    }
+    fn adjust(
+        &self,
+        step: f32,
+        graphics: &mut GraphicsSettings,
+        mouse: &mut MouseSettings,
+        audio: &mut AudioSettings,
+        privacy: &mut PrivacySettings,
+        accessibility: &mut AccessibilitySettings,
+    ) {
+        match self {
+            SettingsOption::RenderDistance => {
+                graphics.render_distance =
+                    (graphics.render_distance as i32 + step as i32 * 2).clamp(2, 32) as u32
+            }
+            SettingsOption::Fov => graphics.fov = (graphics.fov + step * 5.).clamp(30., 120.),
+            SettingsOption::MouseSensitivity => {
+                mouse.sensitivity = (mouse.sensitivity + step * 0.05).clamp(0.05, 2.)
+            }
+            SettingsOption::Vsync => graphics.vsync = !graphics.vsync,
+            SettingsOption::Volume => audio.volume = (audio.volume + step * 0.1).clamp(0., 1.),
+            SettingsOption::GuiScale => {
+                graphics.gui_scale = (graphics.gui_scale + step * 0.25).clamp(0.5, 2.)
+            }
+            SettingsOption::Snapshots => privacy.allow_snapshots = !privacy.allow_snapshots,
+            SettingsOption::ColorPalette => {
+                accessibility.color_palette = if step > 0. {
+                    accessibility.color_palette.next()
+                } else {
+                    accessibility.color_palette.previous()
+                }
+            }
+            SettingsOption::ReducedMotion => {
+                accessibility.reduced_motion = !accessibility.reduced_motion
+            }
+            SettingsOption::SoundCaptions => {
+                accessibility.sound_captions = !accessibility.sound_captions
+            }
+        }
+    }
+}
+
+//the screen only lives on the client, its elements share a prefix so clicks on them are never sent to the server
+pub struct SettingsScreen {
+    open: bool,
+}
+impl SettingsScreen {
+    const PREFIX: &'static str = "client_settings";
+    const ROW_HEIGHT: f32 = 65.;
+    pub fn new() -> Self {
+        SettingsScreen { open: false }
+    }
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+    pub fn is_element(id: &str) -> bool {
+        id.starts_with(Self::PREFIX)
+    }
+    pub fn open(
+        &mut self,
+        gui: &mut GUIRenderer,
+        graphics: &GraphicsSettings,
+        mouse: &MouseSettings,
+        audio: &AudioSettings,
+        privacy: &PrivacySettings,
+        accessibility: &AccessibilitySettings,
+    ) {
+        self.open = true;
+        gui.set_element(
+            format!("{}.title", Self::PREFIX),
+            Self::text(
+                "Settings".to_string(),
+                50.,
+                Vec2 {
+                    x: 0.,
+                    y: Self::ROW_HEIGHT * 6.,
+                },
+            ),
+        );
+        gui.set_element(
+            format!("{}.done", Self::PREFIX),
+            Self::button(
+                Vec2 {
+                    x: 0.,
+                    y: -Self::ROW_HEIGHT * 6.,
+                },
+                200.,
+            ),
+        );
+        gui.set_element(
+            format!("{}.done_text", Self::PREFIX),
+            Self::text(
+                "Done".to_string(),
+                40.,
+                Vec2 {
+                    x: 0.,
+                    y: -Self::ROW_HEIGHT * 6.,
+                },
+            ),
+        );
+        self.update(gui, graphics, mouse, audio, privacy, accessibility);
+    }
+    pub fn close(&mut self, gui: &mut GUIRenderer) {
+        self.open = false;
+        gui.remove_elements(Self::PREFIX);
+    }
+    fn update(
+        &self,
+        gui: &mut GUIRenderer,
+        graphics: &GraphicsSettings,
+        mouse: &MouseSettings,
+        audio: &AudioSettings,
+        privacy: &PrivacySettings,
+        accessibility: &AccessibilitySettings,
+    ) {
+        let mut heading_placed = false;
+        for (row, option) in SettingsOption::ALL.iter().enumerate() {
+            //the accessibility options are listed in their own section below a heading
+            let row = if option.is_accessibility() {
+                row + 1
+            } else {
+                row
+            };
+            if option.is_accessibility() && !heading_placed {
+                heading_placed = true;
+                gui.set_element(
+                    format!("{}.accessibility", Self::PREFIX),
+                    Self::text(
+                        "Accessibility".to_string(),
+                        40.,
+                        Vec2 {
+                            x: 0.,
+                            y: Self::ROW_HEIGHT * (6. - row as f32),
+                        },
+                    ),
+                );
+            }
+            let y = Self::ROW_HEIGHT * (5. - row as f32);
+            let id = format!("{}.{}", Self::PREFIX, option.id());
+            gui.set_element(
+                format!("{}.label", id),
+                Self::text(
+                    option.label(graphics, mouse, audio, privacy, accessibility),
+                    35.,
+                    Vec2 { x: -150., y },
+                ),
+            );
+            for (action, text, x) in [("decrease", "-", 200.), ("increase", "+", 300.)] {
+                gui.set_element(
+                    format!("{}.{}", id, action),
+                    Self::button(Vec2 { x, y }, 80.),
+                );
+                gui.set_element(
+                    format!("{}.{}_text", id, action),
+                    Self::text(text.to_string(), 40., Vec2 { x, y }),
+                );
+            }
+        }
+    }
+    //returns true when a setting was changed and has to be applied
+    pub fn click(
+        &mut self,
+        id: &str,
+        gui: &mut GUIRenderer,
+        graphics: &mut GraphicsSettings,
+        mouse: &mut MouseSettings,
+        audio: &mut AudioSettings,
+        privacy: &mut PrivacySettings,
+        accessibility: &mut AccessibilitySettings,
+    ) -> bool {
+        let id = match id
+            .strip_prefix(Self::PREFIX)
+            .and_then(|id| id.strip_prefix('.'))
+        {
+            Some(id) => id,
+            None => return false,
+        };
+        if id == "done" {
+            self.close(gui);
+            return false;
+        }
+        let (option, action) = match id.split_once('.') {
+            Some(split) => split,
+            None => return false,
+        };
+        let step = match action {
+            "decrease" => -1.,
+            "increase" => 1.,
+            _ => return false,
+        };
+        let option = match SettingsOption::ALL
+            .iter()
+            .find(|candidate| candidate.id() == option)
+        {
+            Some(option) => option,
+            None => return false,
+        };
+        option.adjust(step, graphics, mouse, audio, privacy, accessibility);
+        self.update(gui, graphics, mouse, audio, privacy, accessibility);
+        true
+    }
+    fn button(position: Vec2, width: f32) -> GUIElement {
+        GUIElement {
+            component_type: GUIComponent::ImageComponent {
+                texture: String::new(),
+                size: Vec2 { x: width, y: 60. },
+                slice: None,
+            },
+            position: Position {
+                x: position.x as f64,
+                y: position.y as f64,
+                z: 20.,
+            },
+            anchor: PositionAnchor::Center,
+            base_color: Color {
+                r: 160,
+                g: 160,
+                b: 160,
+                a: 255,
+            },
+            size_percent: None,
+        }
+    }
+    fn text(text: String, font_size: f32, position: Vec2) -> GUIElement {
+        GUIElement {
+            component_type: GUIComponent::TextComponent { font_size, text },
+            position: Position {
+                x: position.x as f64,
+                y: position.y as f64,
+                z: 21.,
+            },
+            anchor: PositionAnchor::Center,
+            base_color: Color::WHITE,
+            size_percent: None,
+        }
+    }
+}
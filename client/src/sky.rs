@@ -0,0 +1,317 @@
+use std::f32::consts::TAU;
+use std::time::Instant;
+
+use block_byte_common::{Position, SkySettings};
+use cgmath::{InnerSpace, Vector3};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{BindGroup, BufferUsages, CommandEncoder, Device, LoadOp, TextureView};
+
+use crate::gpu_timing::DrawStats;
+use crate::render::BorderVertex;
+
+//world time is 0 at sunrise, the sun rises in +x and sets in -x
+pub struct SkyRenderer {
+    sky_pipeline: wgpu::RenderPipeline,
+    cloud_pipeline: wgpu::RenderPipeline,
+    settings: SkySettings,
+    synced: Instant,
+    cloud_map: Vec<f32>,
+}
+impl SkyRenderer {
+    const TICKS_PER_SECOND: f64 = 20.;
+    const DOME_RADIUS: f32 = 100.;
+    const DOME_SEGMENTS: usize = 24;
+    const DOME_RINGS: [f32; 8] = [-90., -15., 0., 8., 20., 40., 65., 90.];
+    const SUN_SIZE: f32 = 8.;
+    const MOON_SIZE: f32 = 6.;
+    const SUN_COLOR: [f32; 3] = [1., 0.95, 0.75];
+    const MOON_COLOR: [f32; 3] = [0.85, 0.87, 0.95];
+    const NIGHT_ZENITH: [f32; 3] = [0.01, 0.01, 0.04];
+    const NIGHT_HORIZON: [f32; 3] = [0.03, 0.04, 0.09];
+    const SUNSET_COLOR: [f32; 3] = [1., 0.45, 0.15];
+    const CLOUD_NIGHT_COLOR: [f32; 3] = [0.1, 0.1, 0.15];
+    const CLOUD_MAP_SIZE: usize = 64;
+    const CLOUD_CELL_SIZE: f32 = 12.;
+    const CLOUD_RADIUS: i32 = 24;
+    const CLOUD_ALPHA: f32 = 0.8;
+    pub fn new(sky_pipeline: wgpu::RenderPipeline, cloud_pipeline: wgpu::RenderPipeline) -> Self {
+        SkyRenderer {
+            sky_pipeline,
+            cloud_pipeline,
+            settings: SkySettings::default(),
+            synced: Instant::now(),
+            cloud_map: Self::create_cloud_map(),
+        }
+    }
+    pub fn set_settings(&mut self, settings: SkySettings) {
+        self.settings = settings;
+        self.synced = Instant::now();
+    }
+    //tileable value noise, cells under the cloud cover get a cloud
+    fn create_cloud_map() -> Vec<f32> {
+        let size = Self::CLOUD_MAP_SIZE;
+        let lattice = |x: usize, z: usize| {
+            let mut hash = (x as u32)
+                .wrapping_mul(374761393)
+                .wrapping_add((z as u32).wrapping_mul(668265263));
+            hash = (hash ^ (hash >> 13)).wrapping_mul(1274126177);
+            (hash ^ (hash >> 16)) as f32 / u32::MAX as f32
+        };
+        let octave = |x: usize, z: usize, period: usize| {
+            let cell = (size / period) as f32;
+            let (fx, fz) = (x as f32 / cell, z as f32 / cell);
+            let (ix, iz) = (fx.floor() as usize, fz.floor() as usize);
+            let smooth = |t: f32| t * t * (3. - 2. * t);
+            let (tx, tz) = (smooth(fx.fract()), smooth(fz.fract()));
+            let corner = |dx: usize, dz: usize| lattice((ix + dx) % period, (iz + dz) % period);
+            let near = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * tx;
+            let far = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * tx;
+            near + (far - near) * tz
+        };
+        let mut map = Vec::with_capacity(size * size);
+        for x in 0..size {
+            for z in 0..size {
+                map.push(octave(x, z, 8) * 0.65 + octave(x, z, 16) * 0.35);
+            }
+        }
+        map
+    }
+    //from 0 at sunrise to 1 at the next sunrise
+    pub fn get_time_of_day(&self) -> f32 {
+        let day_length = self.settings.day_length.max(1) as f64;
+        let mut time = self.settings.time as f64;
+        if self.settings.daylight_cycle {
+            time += self.synced.elapsed().as_secs_f64() * Self::TICKS_PER_SECOND;
+        }
+        ((time % day_length) / day_length) as f32
+    }
+    fn get_sun_direction(&self) -> Vector3<f32> {
+        let angle = self.get_time_of_day() * TAU;
+        Vector3::new(angle.cos(), angle.sin(), 0.)
+    }
+    //1 at day, 0 at night
+    fn get_daylight(sun: Vector3<f32>) -> f32 {
+        (sun.y * 4. + 0.5).clamp(0., 1.)
+    }
+    //terrain fog uses the horizon color, so distant terrain fades into the sky at night too
+    pub fn get_horizon_color(&self, fog_color: [f32; 3]) -> [f32; 3] {
+        mix(
+            Self::NIGHT_HORIZON,
+            fog_color,
+            Self::get_daylight(self.get_sun_direction()),
+        )
+    }
+    fn get_dome_color(
+        direction: Vector3<f32>,
+        sun: Vector3<f32>,
+        zenith: [f32; 3],
+        horizon: [f32; 3],
+    ) -> [f32; 3] {
+        let color = mix(horizon, zenith, direction.y.max(0.).sqrt());
+        let sunset = (1. - sun.y.abs() * 4.).clamp(0., 1.)
+            * (direction.x * sun.x.signum()).max(0.).powi(2)
+            * (1. - direction.y.abs() * 3.).clamp(0., 1.);
+        mix(color, Self::SUNSET_COLOR, sunset * 0.8)
+    }
+    pub fn render_sky(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        camera_bind_group: &BindGroup,
+        eye: Position,
+        sky_color: [f32; 3],
+        fog_color: [f32; 3],
+        stats: &mut DrawStats,
+    ) {
+        let sun = self.get_sun_direction();
+        let daylight = Self::get_daylight(sun);
+        let zenith = mix(Self::NIGHT_ZENITH, sky_color, daylight);
+        let horizon = mix(Self::NIGHT_HORIZON, fog_color, daylight);
+        let eye = Vector3::new(eye.x as f32, eye.y as f32, eye.z as f32);
+        let mut vertices = Vec::new();
+        let mut add_quad = |corners: [Vector3<f32>; 4], colors: [u32; 4]| {
+            for index in [0, 1, 2, 2, 3, 0] {
+                vertices.push(BorderVertex {
+                    position: (eye + corners[index]).into(),
+                    color: colors[index],
+                });
+            }
+        };
+        let dome_vertex = |elevation: f32, segment: usize| {
+            let elevation = elevation.to_radians();
+            let azimuth = segment as f32 / Self::DOME_SEGMENTS as f32 * TAU;
+            let direction = Vector3::new(
+                elevation.cos() * azimuth.cos(),
+                elevation.sin(),
+                elevation.cos() * azimuth.sin(),
+            );
+            (
+                direction * Self::DOME_RADIUS,
+                pack_color(Self::get_dome_color(direction, sun, zenith, horizon), 1.),
+            )
+        };
+        for ring in Self::DOME_RINGS.windows(2) {
+            for segment in 0..Self::DOME_SEGMENTS {
+                let corners = [
+                    dome_vertex(ring[0], segment),
+                    dome_vertex(ring[0], segment + 1),
+                    dome_vertex(ring[1], segment + 1),
+                    dome_vertex(ring[1], segment),
+                ];
+                add_quad(
+                    corners.map(|corner| corner.0),
+                    corners.map(|corner| corner.1),
+                );
+            }
+        }
+        if self.settings.sun_and_moon {
+            for (direction, size, color) in [
+                (sun, Self::SUN_SIZE, Self::SUN_COLOR),
+                (-sun, Self::MOON_SIZE, Self::MOON_COLOR),
+            ] {
+                //fades out while sinking below the horizon, there is no depth to hide it
+                let color = pack_color(color, ((direction.y + 0.1) * 10.).clamp(0., 1.));
+                let center = direction * Self::DOME_RADIUS * 0.95;
+                let side = Vector3::new(0., 0., size);
+                let up = direction.cross(Vector3::unit_z()).normalize() * size;
+                add_quad(
+                    [
+                        center - side - up,
+                        center + side - up,
+                        center + side + up,
+                        center - side + up,
+                    ],
+                    [color; 4],
+                );
+            }
+        }
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Sky Buffer"),
+            usage: BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(vertices.as_slice()),
+        });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Sky Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: LoadOp::Clear(wgpu::Color {
+                        r: horizon[0] as f64,
+                        g: horizon[1] as f64,
+                        b: horizon[2] as f64,
+                        a: 1.0,
+                    }),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_pipeline(&self.sky_pipeline);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        render_pass.draw(0..vertices.len() as u32, 0..1);
+        stats.draw_triangles(vertices.len() as u32);
+    }
+    pub fn render_clouds(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        depth_view: &TextureView,
+        camera_bind_group: &BindGroup,
+        eye: Position,
+        time: f32,
+        stats: &mut DrawStats,
+    ) {
+        if !self.settings.clouds {
+            return;
+        }
+        let cell_size = Self::CLOUD_CELL_SIZE;
+        let map_size = Self::CLOUD_MAP_SIZE as i32;
+        //the map repeats, so the offset can wrap around before it loses precision
+        let offset = (time * self.settings.cloud_speed).rem_euclid(cell_size * map_size as f32);
+        let height = self.settings.cloud_height;
+        let color = mix(
+            Self::CLOUD_NIGHT_COLOR,
+            [1., 1., 1.],
+            Self::get_daylight(self.get_sun_direction()),
+        );
+        let center_x = ((eye.x as f32 - offset) / cell_size).floor() as i32;
+        let center_z = (eye.z as f32 / cell_size).floor() as i32;
+        let max_distance = Self::CLOUD_RADIUS as f32 * cell_size;
+        let mut vertices = Vec::new();
+        for cell_x in (center_x - Self::CLOUD_RADIUS)..=(center_x + Self::CLOUD_RADIUS) {
+            for cell_z in (center_z - Self::CLOUD_RADIUS)..=(center_z + Self::CLOUD_RADIUS) {
+                let noise = self.cloud_map[(cell_x.rem_euclid(map_size) * map_size
+                    + cell_z.rem_euclid(map_size))
+                    as usize];
+                if noise >= self.settings.cloud_cover {
+                    continue;
+                }
+                let x0 = cell_x as f32 * cell_size + offset;
+                let z0 = cell_z as f32 * cell_size;
+                let distance =
+                    (x0 + cell_size / 2. - eye.x as f32).hypot(z0 + cell_size / 2. - eye.z as f32);
+                let alpha = (1. - distance / max_distance).clamp(0., 1.) * Self::CLOUD_ALPHA;
+                if alpha <= 0. {
+                    continue;
+                }
+                let color = pack_color(color, alpha);
+                let (x1, z1) = (x0 + cell_size, z0 + cell_size);
+                for position in [
+                    [x0, height, z0],
+                    [x1, height, z0],
+                    [x1, height, z1],
+                    [x1, height, z1],
+                    [x0, height, z1],
+                    [x0, height, z0],
+                ] {
+                    vertices.push(BorderVertex { position, color });
+                }
+            }
+        }
+        if vertices.is_empty() {
+            return;
+        }
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Cloud Buffer"),
+            usage: BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(vertices.as_slice()),
+        });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Cloud Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: LoadOp::Load,
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_pipeline(&self.cloud_pipeline);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        render_pass.draw(0..vertices.len() as u32, 0..1);
+        stats.draw_triangles(vertices.len() as u32);
+    }
+}
+fn mix(from: [f32; 3], to: [f32; 3], factor: f32) -> [f32; 3] {
+    std::array::from_fn(|i| from[i] + (to[i] - from[i]) * factor)
+}
+fn pack_color(color: [f32; 3], alpha: f32) -> u32 {
+    (color[0] * 255.) as u32
+        | (((color[1] * 255.) as u32) << 8)
+        | (((color[2] * 255.) as u32) << 16)
+        | (((alpha * 255.) as u32) << 24)
+}
@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+use std::io::Cursor;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+use block_byte_common::gui::{GUIComponent, GUIElement, PositionAnchor};
+use block_byte_common::{Color, Position};
+use image::imageops::FilterType;
+use image::{ImageOutputFormat, RgbaImage};
+use wgpu::{
+    Buffer, BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoder, Device, Extent3d,
+    ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, Maintain, MapMode, Origin3d, Texture,
+    TextureAspect, TextureFormat,
+};
+
+use crate::gui::GUIRenderer;
+
+struct PendingSnapshot {
+    id: u32,
+    max_size: u32,
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    bytes_per_row: u32,
+    receiver: Option<Receiver<Result<(), BufferAsyncError>>>,
+}
+
+//copies one frame at a time back from the gpu, requests wait until the previous snapshot is read
+pub struct SnapshotCapture {
+    //surfaces that can't be copied from decline every request
+    supported: bool,
+    bgra: bool,
+    requests: VecDeque<(u32, u32)>,
+    pending: Option<PendingSnapshot>,
+    indicator: f32,
+}
+impl SnapshotCapture {
+    const INDICATOR_ELEMENT: &'static str = "snapshot_indicator";
+    const INDICATOR_TIME: f32 = 5.;
+    const MAX_SIZE: u32 = 512;
+    pub fn new(format: TextureFormat, copyable: bool) -> Self {
+        SnapshotCapture {
+            supported: copyable
+                && matches!(
+                    format,
+                    TextureFormat::Bgra8Unorm
+                        | TextureFormat::Bgra8UnormSrgb
+                        | TextureFormat::Rgba8Unorm
+                        | TextureFormat::Rgba8UnormSrgb
+                ),
+            bgra: matches!(
+                format,
+                TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+            ),
+            requests: VecDeque::new(),
+            pending: None,
+            indicator: 0.,
+        }
+    }
+    pub fn request(&mut self, id: u32, max_size: u32) {
+        self.requests
+            .push_back((id, max_size.clamp(1, Self::MAX_SIZE)));
+    }
+    //records the copy, called before the gui is drawn so chat and inventories never end up in a snapshot
+    pub fn capture(&mut self, device: &Device, encoder: &mut CommandEncoder, texture: &Texture) {
+        if !self.supported || self.pending.is_some() {
+            return;
+        }
+        let (id, max_size) = match self.requests.pop_front() {
+            Some(request) => request,
+            None => return,
+        };
+        let width = texture.width();
+        let height = texture.height();
+        //rows of a buffer copy have to be aligned
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let bytes_per_row = (width * 4 + alignment - 1) / alignment * alignment;
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Snapshot Readback Buffer"),
+            size: (bytes_per_row * height) as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.pending = Some(PendingSnapshot {
+            id,
+            max_size,
+            buffer,
+            width,
+            height,
+            bytes_per_row,
+            receiver: None,
+        });
+    }
+    //must be called after the frame with the copy was submitted
+    pub fn finish_frame(&mut self) {
+        if let Some(pending) = self
+            .pending
+            .as_mut()
+            .filter(|pending| pending.receiver.is_none())
+        {
+            let (sender, receiver) = mpsc::channel();
+            pending
+                .buffer
+                .slice(..)
+                .map_async(MapMode::Read, move |result| {
+                    let _ = sender.send(result);
+                });
+            pending.receiver = Some(receiver);
+        }
+    }
+    //finished snapshots as png, None for requests that couldn't be captured
+    pub fn poll(&mut self, device: &Device) -> Option<(u32, Option<Vec<u8>>)> {
+        if !self.supported {
+            return self.requests.pop_front().map(|(id, _)| (id, None));
+        }
+        let pending = self.pending.as_ref()?;
+        device.poll(Maintain::Poll);
+        let mapped = match pending.receiver.as_ref()?.try_recv() {
+            Ok(Ok(())) => true,
+            Ok(Err(_)) | Err(TryRecvError::Disconnected) => false,
+            Err(TryRecvError::Empty) => return None,
+        };
+        let pending = self.pending.take().unwrap();
+        if !mapped {
+            return Some((pending.id, None));
+        }
+        let image = self.encode(&pending);
+        pending.buffer.unmap();
+        self.indicator = Self::INDICATOR_TIME;
+        Some((pending.id, Some(image)))
+    }
+    fn encode(&self, pending: &PendingSnapshot) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity((pending.width * pending.height * 4) as usize);
+        {
+            let data = pending.buffer.slice(..).get_mapped_range();
+            for row in data.chunks(pending.bytes_per_row as usize) {
+                for pixel in row[..(pending.width * 4) as usize].chunks(4) {
+                    if self.bgra {
+                        pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], 255]);
+                    } else {
+                        pixels.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 255]);
+                    }
+                }
+            }
+        }
+        let image = RgbaImage::from_raw(pending.width, pending.height, pixels).unwrap();
+        let scale = (pending.max_size as f32 / pending.width.max(pending.height) as f32).min(1.);
+        let image = image::imageops::resize(
+            &image,
+            ((pending.width as f32 * scale) as u32).max(1),
+            ((pending.height as f32 * scale) as u32).max(1),
+            FilterType::Triangle,
+        );
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+            .unwrap();
+        png
+    }
+    //tells the player for a few seconds after every snapshot that was sent
+    pub fn tick_indicator(&mut self, dt: f32, gui: &mut GUIRenderer) {
+        if self.indicator <= 0. {
+            return;
+        }
+        if self.indicator == Self::INDICATOR_TIME {
+            gui.set_element(
+                Self::INDICATOR_ELEMENT.to_string(),
+                GUIElement {
+                    component_type: GUIComponent::TextComponent {
+                        font_size: 30.,
+                        text: "The server took a snapshot of your view".to_string(),
+                    },
+                    position: Position {
+                        x: 0.,
+                        y: -60.,
+                        z: 0.,
+                    },
+                    anchor: PositionAnchor::Top,
+                    base_color: Color {
+                        r: 255,
+                        g: 80,
+                        b: 80,
+                        a: 255,
+                    },
+                    size_percent: None,
+                },
+            );
+        }
+        self.indicator -= dt;
+        if self.indicator <= 0. {
+            gui.remove_elements(Self::INDICATOR_ELEMENT);
+        }
+    }
+}
@@ -244,4 +244,7 @@ impl TextureAtlas {
     pub fn get(&self, texture: &str) -> TexCoords {
         *self.textures.get(texture).unwrap_or(&self.missing_texture)
     }
+    pub fn contains(&self, texture: &str) -> bool {
+        self.textures.contains_key(texture)
+    }
 }
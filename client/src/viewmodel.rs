@@ -0,0 +1,116 @@
+use block_byte_common::Vec3;
+use cgmath::{Matrix4, Point3};
+
+use crate::model::{Model, ModelInstanceData};
+use crate::settings::ViewModelSettings;
+
+//procedural motion layered on top of the viewmodel's own animations, none of it is known to the server
+pub struct ViewModelMotion {
+    settings: ViewModelSettings,
+    previous_position: Option<Point3<f32>>,
+    bob_time: f32,
+    bob_amount: f32,
+    swing: Option<f32>,
+    equip: f32,
+    pending_items: Vec<(String, Option<u32>)>,
+    reduced_motion: bool,
+}
+impl ViewModelMotion {
+    const WALK_SPEED: f32 = 4.3;
+    pub fn new(settings: ViewModelSettings, reduced_motion: bool) -> Self {
+        ViewModelMotion {
+            settings,
+            previous_position: None,
+            bob_time: 0.,
+            bob_amount: 0.,
+            swing: None,
+            equip: 0.,
+            pending_items: Vec::new(),
+            reduced_motion,
+        }
+    }
+    pub fn set_reduced_motion(&mut self, reduced_motion: bool) {
+        self.reduced_motion = reduced_motion;
+    }
+    //started on click, before the server confirms anything
+    pub fn swing(&mut self) {
+        self.swing = Some(0.);
+    }
+    //item changes wait until the viewmodel is lowered, then it comes back up holding the new item
+    pub fn change_item(&mut self, slot: String, item: Option<u32>) {
+        self.pending_items.push((slot, item));
+    }
+    pub fn reset(&mut self) {
+        self.pending_items.clear();
+        self.swing = None;
+        self.equip = 0.;
+    }
+    pub fn tick(
+        &mut self,
+        delta_time: f32,
+        position: Point3<f32>,
+        instance: &mut ModelInstanceData,
+    ) {
+        let speed = match self.previous_position.replace(position) {
+            Some(previous) if delta_time > 0. => {
+                let x = position.x - previous.x;
+                let z = position.z - previous.z;
+                (x * x + z * z).sqrt() / delta_time
+            }
+            _ => 0.,
+        };
+        let bobbing = if self.reduced_motion {
+            0.
+        } else {
+            self.settings.bobbing
+        };
+        let target = (speed / Self::WALK_SPEED).min(1.) * bobbing;
+        self.bob_amount += (target - self.bob_amount) * (delta_time * 10.).min(1.);
+        self.bob_time += delta_time * speed.min(Self::WALK_SPEED * 2.) * 2.;
+        let swing_time = self.settings.swing_time;
+        self.swing = self
+            .swing
+            .map(|time| time + delta_time)
+            .filter(|time| *time < swing_time);
+        let equip_step = delta_time / self.settings.equip_time.max(0.01);
+        if self.pending_items.is_empty() {
+            self.equip = (self.equip + equip_step).min(1.);
+        } else {
+            self.equip -= equip_step;
+            if self.equip <= 0. {
+                self.equip = 0.;
+                for (slot, item) in self.pending_items.drain(..) {
+                    match item {
+                        Some(item) => {
+                            instance.items.insert(slot, item);
+                        }
+                        None => {
+                            instance.items.remove(&slot);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    pub fn get_transform(&self) -> Matrix4<f32> {
+        let swing = self
+            .swing
+            .map(|time| (time / self.settings.swing_time * std::f32::consts::PI).sin())
+            .unwrap_or(0.);
+        let lowered = 1. - self.equip * self.equip * (3. - 2. * self.equip);
+        Model::create_matrix_trs(
+            &Vec3 {
+                x: self.bob_time.sin() * 0.04 * self.bob_amount,
+                y: -self.bob_time.cos().abs() * 0.03 * self.bob_amount - lowered * 0.6,
+                z: -swing * 0.1,
+            },
+            &Vec3 {
+                x: -swing * self.settings.swing_angle.to_radians(),
+                y: 0.,
+                z: 0.,
+            },
+            &Vec3::ZERO,
+            &Vec3::ONE,
+        )
+    }
+}
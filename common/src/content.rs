@@ -8,6 +8,73 @@ pub struct ClientContent {
     pub blocks: Vec<ClientBlockData>,
     pub items: Vec<ClientItemData>,
     pub entities: Vec<ClientEntityData>,
+    #[serde(default)]
+    pub biomes: Vec<ClientBiomeData>,
+    #[serde(default)]
+    pub particles: HashMap<String, ClientParticleData>,
+    //full state names like bb:door{open=true} in the same order as blocks, only used for debugging
+    #[serde(default)]
+    pub block_names: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClientParticleData {
+    pub texture: ClientTexture,
+    pub lifetime: f32,
+    #[serde(default)]
+    pub gravity: f32,
+    pub size: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClientBiomeData {
+    pub sky_color: [f32; 3],
+    pub fog_color: [f32; 3],
+    pub fog_density: f32,
+    pub foliage_tint: [f32; 3],
+    pub water_tint: [f32; 3],
+    pub climate_tint: Option<ClimateTint>,
+}
+impl Default for ClientBiomeData {
+    fn default() -> Self {
+        ClientBiomeData {
+            sky_color: [0.1, 0.2, 0.3],
+            fog_color: [0.1, 0.2, 0.3],
+            fog_density: 0.,
+            foliage_tint: [1., 1., 1.],
+            water_tint: [1., 1., 1.],
+            climate_tint: None,
+        }
+    }
+}
+impl ClientBiomeData {
+    //temperature and moisture go from 0 to 1
+    pub fn get_foliage_tint(&self, temperature: f32, moisture: f32) -> [f32; 3] {
+        match &self.climate_tint {
+            Some(climate_tint) => {
+                let climate = climate_tint.sample(temperature, moisture);
+                std::array::from_fn(|i| self.foliage_tint[i] * climate[i])
+            }
+            None => self.foliage_tint,
+        }
+    }
+}
+
+//colors at the corners of a triangle, moisture matters less the colder it gets
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ClimateTint {
+    pub cold: [f32; 3],
+    pub dry: [f32; 3],
+    pub wet: [f32; 3],
+}
+impl ClimateTint {
+    pub fn sample(&self, temperature: f32, moisture: f32) -> [f32; 3] {
+        let temperature = temperature.clamp(0., 1.);
+        let wet = temperature * moisture.clamp(0., 1.);
+        let dry = temperature - wet;
+        let cold = 1. - temperature;
+        std::array::from_fn(|i| self.cold[i] * cold + self.dry[i] * dry + self.wet[i] * wet)
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -19,6 +86,41 @@ pub struct ClientBlockData {
     pub transparent: bool,
     pub selectable: bool,
     pub no_collide: bool,
+    #[serde(default)]
+    pub tint: BlockTint,
+    #[serde(default)]
+    pub interaction_prompt: Option<String>,
+    #[serde(default)]
+    pub step_particles: StepParticles,
+    //blocks per second added to the movement of entities standing on the block
+    #[serde(default)]
+    pub surface_velocity: Option<Vec3>,
+}
+
+//dust kicked up by entities moving over the block, rate is particles per block walked
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StepParticles {
+    pub rate: f32,
+    pub run_rate: f32,
+    pub size: f32,
+}
+impl Default for StepParticles {
+    fn default() -> Self {
+        StepParticles {
+            rate: 1.,
+            run_rate: 4.,
+            size: 0.1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockTint {
+    #[default]
+    None,
+    Foliage,
+    Water,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -62,6 +164,9 @@ pub struct ClientModel {
 pub struct ClientItemData {
     pub name: String,
     pub model: ClientItemModel,
+    //clients that don't know it show the plain model
+    #[serde(default)]
+    pub dynamic: Option<ClientDynamicItemModel>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -69,6 +174,24 @@ pub enum ClientItemModel {
     Texture(String), //todo: support animated textures
     Block(u32),
 }
+//evaluated by the client every frame, the value goes from 0 to 1
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClientDynamicItemModel {
+    pub value: ClientItemModelValue,
+    //picked by the value, spread evenly so the first one is used around 0
+    #[serde(default)]
+    pub textures: Vec<String>,
+    //turns the model clockwise, a full turn from 0 to 1
+    #[serde(default)]
+    pub rotate: bool,
+}
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ClientItemModelValue {
+    //direction to the waypoint, 0 straight ahead and growing clockwise
+    Waypoint(String),
+    //time of day, 0 at sunrise
+    Time,
+}
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Transformation {
     pub position: Vec3,
@@ -103,6 +226,65 @@ pub struct ClientEntityData {
     pub animations: Vec<String>,
     pub items: Vec<String>,
     pub viewmodel: Option<(String, ClientTexture, Vec<String>, Vec<String>)>,
+    #[serde(default)]
+    pub interaction_prompt: Option<String>,
+    #[serde(default)]
+    pub item_entity: bool,
+    #[serde(default)]
+    pub animation_states: HashMap<String, AnimationStateData>,
+    #[serde(default)]
+    pub head: Option<EntityHeadData>,
+}
+//bone that turns towards where the entity looks, found by child indices starting at the root bone
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EntityHeadData {
+    pub bone: Vec<usize>,
+    #[serde(default = "EntityHeadData::default_max_yaw")]
+    pub max_yaw: f32,
+}
+impl EntityHeadData {
+    fn default_max_yaw() -> f32 {
+        75.
+    }
+}
+//logical states are set by the server, the client picks the animation and follows transitions on its own
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnimationStateData {
+    pub animation: String,
+    #[serde(default)]
+    pub blend_time: f32,
+    #[serde(default = "AnimationStateData::default_speed")]
+    pub speed: f32,
+    #[serde(default)]
+    pub speed_per_velocity: f32,
+    #[serde(default)]
+    pub once: bool,
+    #[serde(default)]
+    pub transitions: Vec<AnimationTransitionData>,
+}
+impl AnimationStateData {
+    fn default_speed() -> f32 {
+        1.
+    }
+}
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnimationTransitionData {
+    pub to: String,
+    #[serde(default)]
+    pub min_speed: Option<f32>,
+    #[serde(default)]
+    pub max_speed: Option<f32>,
+    #[serde(default)]
+    pub on_end: bool,
+    #[serde(default)]
+    pub blend_time: Option<f32>,
+}
+impl AnimationTransitionData {
+    pub fn matches(&self, movement_speed: f32, finished: bool) -> bool {
+        self.min_speed.map_or(true, |min| movement_speed >= min)
+            && self.max_speed.map_or(true, |max| movement_speed < max)
+            && (!self.on_end || finished)
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
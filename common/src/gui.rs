@@ -37,6 +37,9 @@ pub struct GUIElement {
     pub position: Position,
     pub anchor: PositionAnchor,
     pub base_color: Color,
+    //percentage of the screen width and height, replaces the component size
+    #[serde(default)]
+    pub size_percent: Option<Vec2>,
 }
 impl GUIElement {
     pub fn edit(&mut self, edit: GUIElementEdit) {
@@ -71,9 +74,80 @@ pub enum GUIComponent {
         item_id: Option<(u32, u32)>,
         background: String,
         size: Vec2,
+        #[serde(default)]
+        tooltip: Vec<String>,
+    },
+    TextInput {
+        text: String,
+        size: Vec2,
+        #[serde(default)]
+        placeholder: String,
+    },
+    ListContainer {
+        size: Vec2,
+        item_height: f32,
+        #[serde(default)]
+        scroll: f32,
+    },
+    GridContainer {
+        size: Vec2,
+        columns: u32,
+        cell_size: Vec2,
+        #[serde(default)]
+        scroll: f32,
+    },
+    //fills from the bottom or from the left for horizontal bars, fill goes from 0 to 1
+    GaugeComponent {
+        background: String,
+        #[serde(default)]
+        texture: String,
+        size: Vec2,
+        #[serde(default)]
+        fill: f32,
+        #[serde(default)]
+        tooltip: Vec<String>,
+        #[serde(default)]
+        horizontal: bool,
     },
 }
 impl GUIComponent {
+    //lists are laid out as grids with a single column
+    pub fn get_container_layout(&self) -> Option<ContainerLayout> {
+        match self {
+            GUIComponent::ListContainer {
+                size,
+                item_height,
+                scroll,
+            } => Some(ContainerLayout {
+                size: *size,
+                columns: 1,
+                cell_size: Vec2 {
+                    x: size.x,
+                    y: *item_height,
+                },
+                scroll: *scroll,
+            }),
+            GUIComponent::GridContainer {
+                size,
+                columns,
+                cell_size,
+                scroll,
+            } => Some(ContainerLayout {
+                size: *size,
+                columns: (*columns).max(1),
+                cell_size: *cell_size,
+                scroll: *scroll,
+            }),
+            _ => None,
+        }
+    }
+    pub fn set_scroll(&mut self, new_scroll: f32) {
+        match self {
+            GUIComponent::ListContainer { scroll, .. }
+            | GUIComponent::GridContainer { scroll, .. } => *scroll = new_scroll,
+            _ => {}
+        }
+    }
     pub fn edit(&mut self, edit: GUIComponentEdit) {
         match (self, edit) {
             (
@@ -103,11 +177,13 @@ impl GUIComponent {
                     background,
                     size,
                     item_id,
+                    tooltip,
                 },
                 GUIComponentEdit::SlotComponent {
                     background: background_edit,
                     size: size_edit,
                     item_id: item_id_edit,
+                    tooltip: tooltip_edit,
                 },
             ) => {
                 if let Some(background_edit) = background_edit {
@@ -119,6 +195,9 @@ impl GUIComponent {
                 if let Some(item_id_edit) = item_id_edit {
                     *item_id = item_id_edit;
                 }
+                if let Some(tooltip_edit) = tooltip_edit {
+                    *tooltip = tooltip_edit;
+                }
             }
             (
                 GUIComponent::TextComponent { text, font_size },
@@ -134,6 +213,110 @@ impl GUIComponent {
                     *font_size = font_size_edit;
                 }
             }
+            (
+                GUIComponent::TextInput {
+                    text,
+                    size,
+                    placeholder,
+                },
+                GUIComponentEdit::TextInput {
+                    text: text_edit,
+                    size: size_edit,
+                    placeholder: placeholder_edit,
+                },
+            ) => {
+                if let Some(text_edit) = text_edit {
+                    *text = text_edit;
+                }
+                if let Some(size_edit) = size_edit {
+                    *size = size_edit;
+                }
+                if let Some(placeholder_edit) = placeholder_edit {
+                    *placeholder = placeholder_edit;
+                }
+            }
+            (
+                GUIComponent::ListContainer {
+                    size,
+                    item_height,
+                    scroll,
+                },
+                GUIComponentEdit::ListContainer {
+                    size: size_edit,
+                    item_height: item_height_edit,
+                    scroll: scroll_edit,
+                },
+            ) => {
+                if let Some(size_edit) = size_edit {
+                    *size = size_edit;
+                }
+                if let Some(item_height_edit) = item_height_edit {
+                    *item_height = item_height_edit;
+                }
+                if let Some(scroll_edit) = scroll_edit {
+                    *scroll = scroll_edit;
+                }
+            }
+            (
+                GUIComponent::GridContainer {
+                    size,
+                    columns,
+                    cell_size,
+                    scroll,
+                },
+                GUIComponentEdit::GridContainer {
+                    size: size_edit,
+                    columns: columns_edit,
+                    cell_size: cell_size_edit,
+                    scroll: scroll_edit,
+                },
+            ) => {
+                if let Some(size_edit) = size_edit {
+                    *size = size_edit;
+                }
+                if let Some(columns_edit) = columns_edit {
+                    *columns = columns_edit;
+                }
+                if let Some(cell_size_edit) = cell_size_edit {
+                    *cell_size = cell_size_edit;
+                }
+                if let Some(scroll_edit) = scroll_edit {
+                    *scroll = scroll_edit;
+                }
+            }
+            (
+                GUIComponent::GaugeComponent {
+                    background,
+                    texture,
+                    size,
+                    fill,
+                    tooltip,
+                    ..
+                },
+                GUIComponentEdit::GaugeComponent {
+                    background: background_edit,
+                    texture: texture_edit,
+                    size: size_edit,
+                    fill: fill_edit,
+                    tooltip: tooltip_edit,
+                },
+            ) => {
+                if let Some(background_edit) = background_edit {
+                    *background = background_edit;
+                }
+                if let Some(texture_edit) = texture_edit {
+                    *texture = texture_edit;
+                }
+                if let Some(size_edit) = size_edit {
+                    *size = size_edit;
+                }
+                if let Some(fill_edit) = fill_edit {
+                    *fill = fill_edit.clamp(0., 1.);
+                }
+                if let Some(tooltip_edit) = tooltip_edit {
+                    *tooltip = tooltip_edit;
+                }
+            }
             _ => {}
         }
     }
@@ -163,5 +346,62 @@ pub enum GUIComponentEdit {
         item_id: Option<Option<(u32, u32)>>,
         background: Option<String>,
         size: Option<Vec2>,
+        tooltip: Option<Vec<String>>,
     },
+    TextInput {
+        text: Option<String>,
+        size: Option<Vec2>,
+        placeholder: Option<String>,
+    },
+    ListContainer {
+        size: Option<Vec2>,
+        item_height: Option<f32>,
+        scroll: Option<f32>,
+    },
+    GridContainer {
+        size: Option<Vec2>,
+        columns: Option<u32>,
+        cell_size: Option<Vec2>,
+        scroll: Option<f32>,
+    },
+    GaugeComponent {
+        background: Option<String>,
+        texture: Option<String>,
+        size: Option<Vec2>,
+        fill: Option<f32>,
+        tooltip: Option<Vec<String>>,
+    },
+}
+//children of a container have ids like "container.3" or "container.3.label", the number picks their cell
+#[derive(Clone, Copy)]
+pub struct ContainerLayout {
+    pub size: Vec2,
+    pub columns: u32,
+    pub cell_size: Vec2,
+    pub scroll: f32,
+}
+impl ContainerLayout {
+    pub fn get_cell_center(&self, index: u32) -> Vec2 {
+        let column = index % self.columns;
+        let row = index / self.columns;
+        Vec2 {
+            x: -self.size.x / 2. + self.cell_size.x * (column as f32 + 0.5),
+            y: self.size.y / 2. - self.cell_size.y * (row as f32 + 0.5) + self.scroll,
+        }
+    }
+    pub fn get_max_scroll(&self, cells: u32) -> f32 {
+        let rows = (cells + self.columns - 1) / self.columns;
+        (rows as f32 * self.cell_size.y - self.size.y).max(0.)
+    }
+    //first cell and the cell after the last one that are at least partly visible
+    pub fn get_visible_range(&self) -> (u32, u32) {
+        if self.cell_size.y <= 0. {
+            return (0, 0);
+        }
+        let first_row = (self.scroll / self.cell_size.y).floor().max(0.) as u32;
+        let end_row = ((self.scroll + self.size.y) / self.cell_size.y)
+            .ceil()
+            .max(0.) as u32;
+        (first_row * self.columns, end_row * self.columns)
+    }
 }
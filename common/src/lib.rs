@@ -3,12 +3,17 @@
 pub mod block_palette;
 pub mod content;
 pub mod gui;
+pub mod math;
 pub mod messages;
+#[cfg(feature = "quic")]
+pub mod quic;
+pub mod recording;
 
 use serde::{Deserialize, Serialize};
 use std::fmt::{Debug, Display, Formatter};
 use std::ops;
 use std::ops::Neg;
+use strum::IntoEnumIterator;
 use strum_macros::{Display, EnumIter};
 
 #[derive(Copy, Clone, Serialize, Deserialize, Default)]
@@ -187,6 +192,84 @@ impl std::ops::Add for Position {
         }
     }
 }
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WorldBorder {
+    pub center_x: f64,
+    pub center_z: f64,
+    pub radius: f64,
+}
+impl WorldBorder {
+    pub const DEFAULT_RADIUS: f64 = 30_000_000.;
+    pub fn contains(&self, position: &Position) -> bool {
+        (position.x - self.center_x).abs() <= self.radius
+            && (position.z - self.center_z).abs() <= self.radius
+    }
+    pub fn contains_aabb(&self, aabb: &AABB) -> bool {
+        aabb.x >= self.center_x - self.radius
+            && aabb.x + aabb.w <= self.center_x + self.radius
+            && aabb.z >= self.center_z - self.radius
+            && aabb.z + aabb.d <= self.center_z + self.radius
+    }
+    pub fn clamp(&self, position: Position) -> Position {
+        Position {
+            x: position
+                .x
+                .clamp(self.center_x - self.radius, self.center_x + self.radius),
+            y: position.y,
+            z: position
+                .z
+                .clamp(self.center_z - self.radius, self.center_z + self.radius),
+        }
+    }
+    pub fn distance_to_edge(&self, position: &Position) -> f64 {
+        (self.radius - (position.x - self.center_x).abs())
+            .min(self.radius - (position.z - self.center_z).abs())
+    }
+}
+//drawn as lines through walls, for admins to see what the server sees
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum DebugShape {
+    Line(Position, Position),
+    //opposite corners
+    Box(Position, Position),
+    //center and radius
+    Sphere(Position, f64),
+}
+impl Default for WorldBorder {
+    fn default() -> Self {
+        WorldBorder {
+            center_x: 0.,
+            center_z: 0.,
+            radius: WorldBorder::DEFAULT_RADIUS,
+        }
+    }
+}
+//time is the world time when the settings were sent, clients keep counting from it while the daylight cycle runs
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SkySettings {
+    pub time: u64,
+    pub day_length: u64,
+    pub daylight_cycle: bool,
+    pub sun_and_moon: bool,
+    pub clouds: bool,
+    pub cloud_height: f32,
+    pub cloud_speed: f32,
+    pub cloud_cover: f32,
+}
+impl Default for SkySettings {
+    fn default() -> Self {
+        SkySettings {
+            time: 6000,
+            day_length: 24000,
+            daylight_cycle: false,
+            sun_and_moon: true,
+            clouds: true,
+            cloud_height: 128.,
+            cloud_speed: 1.,
+            cloud_cover: 0.4,
+        }
+    }
+}
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct BlockPosition {
     pub x: i32,
@@ -212,6 +295,13 @@ impl BlockPosition {
     pub fn offset_by_face(&self, face: Face) -> BlockPosition {
         *self + face.get_offset()
     }
+    pub fn add(&self, x: i32, y: i32, z: i32) -> Self {
+        BlockPosition {
+            x: self.x + x,
+            y: self.y + y,
+            z: self.z + z,
+        }
+    }
     pub fn distance(&self, other: &BlockPosition) -> f64 {
         (((self.x - other.x).pow(2) + (self.y - other.y).pow(2) + (self.z - other.z).pow(2)) as f64)
             .sqrt()
@@ -723,6 +813,9 @@ pub enum KeyboardKey {
     Cut,
 }
 impl KeyboardKey {
+    pub fn from_name(name: &str) -> Option<KeyboardKey> {
+        KeyboardKey::iter().find(|key| key.to_string() == name)
+    }
     pub fn get_slot(&self) -> Option<u8> {
         match self {
             KeyboardKey::Key1 => Some(0),
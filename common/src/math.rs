@@ -0,0 +1,162 @@
+use std::ops;
+
+use crate::{Position, Vec3};
+
+impl Vec3 {
+    pub fn dot(&self, other: &Vec3) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+    pub fn cross(&self, other: &Vec3) -> Vec3 {
+        Vec3 {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
+    }
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+    //zero vectors stay zero instead of turning into NaN
+    pub fn normalize(&self) -> Vec3 {
+        let length = self.length();
+        if length == 0. {
+            *self
+        } else {
+            *self * (1. / length)
+        }
+    }
+    pub fn lerp(&self, other: &Vec3, t: f32) -> Vec3 {
+        *self + (*other - *self) * t
+    }
+}
+impl ops::Add for Vec3 {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Vec3 {
+            x: self.x + other.x,
+            y: self.y + other.y,
+            z: self.z + other.z,
+        }
+    }
+}
+impl ops::Sub for Vec3 {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Vec3 {
+            x: self.x - other.x,
+            y: self.y - other.y,
+            z: self.z - other.z,
+        }
+    }
+}
+impl ops::Mul<f32> for Vec3 {
+    type Output = Self;
+    fn mul(self, scalar: f32) -> Self {
+        Vec3 {
+            x: self.x * scalar,
+            y: self.y * scalar,
+            z: self.z * scalar,
+        }
+    }
+}
+
+impl Position {
+    pub fn dot(&self, other: &Position) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+    pub fn cross(&self, other: &Position) -> Position {
+        Position {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
+    }
+    pub fn length(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+    pub fn normalize(&self) -> Position {
+        let length = self.length();
+        if length == 0. {
+            *self
+        } else {
+            self.multiply(1. / length)
+        }
+    }
+    pub fn lerp(&self, other: &Position, t: f64) -> Position {
+        *self + (*other - *self).multiply(t)
+    }
+}
+impl ops::Sub for Position {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Position {
+            x: self.x - other.x,
+            y: self.y - other.y,
+            z: self.z - other.z,
+        }
+    }
+}
+impl From<Vec3> for Position {
+    fn from(value: Vec3) -> Self {
+        Position {
+            x: value.x as f64,
+            y: value.y as f64,
+            z: value.z as f64,
+        }
+    }
+}
+impl From<Position> for Vec3 {
+    fn from(value: Position) -> Self {
+        Vec3 {
+            x: value.x as f32,
+            y: value.y as f32,
+            z: value.z as f32,
+        }
+    }
+}
+
+//the client does its rendering math in cgmath, these keep the casts in one place
+#[cfg(feature = "cgmath")]
+mod cgmath_conversions {
+    use cgmath::{Point3, Vector3};
+
+    use crate::{Position, Vec3};
+
+    impl From<Vector3<f32>> for Vec3 {
+        fn from(value: Vector3<f32>) -> Self {
+            Vec3 {
+                x: value.x,
+                y: value.y,
+                z: value.z,
+            }
+        }
+    }
+    impl From<Vec3> for Vector3<f32> {
+        fn from(value: Vec3) -> Self {
+            Vector3::new(value.x, value.y, value.z)
+        }
+    }
+    impl From<Point3<f32>> for Position {
+        fn from(value: Point3<f32>) -> Self {
+            Position {
+                x: value.x as f64,
+                y: value.y as f64,
+                z: value.z as f64,
+            }
+        }
+    }
+    impl From<Position> for Point3<f32> {
+        fn from(value: Position) -> Self {
+            Point3::new(value.x as f32, value.y as f32, value.z as f32)
+        }
+    }
+    impl From<Vector3<f32>> for Position {
+        fn from(value: Vector3<f32>) -> Self {
+            Position {
+                x: value.x as f64,
+                y: value.y as f64,
+                z: value.z as f64,
+            }
+        }
+    }
+}
@@ -1,17 +1,101 @@
+use crate::content::Transformation;
 use crate::gui::{GUIElement, GUIElementEdit};
-use crate::{BlockPosition, ChunkPosition, Direction, Face, KeyboardKey, Position};
+use crate::{
+    BlockPosition, ChunkPosition, Color, DebugShape, Direction, Face, KeyboardKey, Position,
+    SkySettings, Vec3, WorldBorder,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use strum::IntoEnumIterator;
 use strum_macros::{Display, EnumIter};
 
+pub const MAX_PLACE_BATCH: usize = 256;
+//raised whenever messages change in a way older peers can't read, new variants only go at the end of the enums
+pub const PROTOCOL_VERSION: u32 = 1;
+//oldest client the server still accepts
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+//sent by both sides before anything else, unknown capabilities are ignored
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProtocolHello {
+    pub version: u32,
+    pub capabilities: Vec<String>,
+}
+impl ProtocolHello {
+    pub fn current() -> Self {
+        ProtocolHello {
+            version: PROTOCOL_VERSION,
+            capabilities: Capability::iter()
+                .map(|capability| capability.name().to_string())
+                .collect(),
+        }
+    }
+    pub fn get_capabilities(&self) -> HashSet<Capability> {
+        self.capabilities
+            .iter()
+            .filter_map(|name| Capability::from_name(name))
+            .collect()
+    }
+}
+//optional features, messages that need one are not sent to clients without it
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, EnumIter)]
+pub enum Capability {
+    Particles,
+    TextDisplays,
+    Sky,
+    BuildAssist,
+    Keybinds,
+    BonePoses,
+    BreakProgress,
+    GuiLayout,
+    Ping,
+    PlacementHit,
+    Hotbar,
+    Waypoints,
+    Snapshots,
+    DebugShapes,
+}
+impl Capability {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Capability::Particles => "particles",
+            Capability::TextDisplays => "text_displays",
+            Capability::Sky => "sky",
+            Capability::BuildAssist => "build_assist",
+            Capability::Keybinds => "keybinds",
+            Capability::BonePoses => "bone_poses",
+            Capability::BreakProgress => "break_progress",
+            Capability::GuiLayout => "gui_layout",
+            Capability::Ping => "ping",
+            Capability::PlacementHit => "placement_hit",
+            Capability::Hotbar => "hotbar",
+            Capability::Waypoints => "waypoints",
+            Capability::Snapshots => "snapshots",
+            Capability::DebugShapes => "debug_shapes",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        Capability::iter().find(|capability| capability.name() == name)
+    }
+}
+
 #[repr(u8)]
 #[derive(Serialize, Deserialize)]
 pub enum NetworkMessageS2C {
     SetBlock(BlockPosition, u32),
-    LoadChunk(ChunkPosition, Vec<u32>, Vec<u8>),
+    SetBlocks(ChunkPosition, Vec<((u8, u8, u8), u32)>),
+    LoadChunk(
+        ChunkPosition,
+        Vec<u32>,
+        ChunkPayload,
+        Vec<u16>,
+        Vec<[u8; 2]>,
+    ),
     UnloadChunk(ChunkPosition),
-    AddEntity(u32, u32, Position, Direction, u32, f32),
+    AddEntity(u32, u32, Position, Direction, String, f32),
     MoveEntity(u32, Position, Direction),
     DeleteEntity(u32),
+    PickupEntity(u32, u32),
     GuiSetElement(String, GUIElement),
     GuiRemoveElements(String),
     GuiEditElement(String, GUIElementEdit),
@@ -19,13 +103,135 @@ pub enum NetworkMessageS2C {
     BlockBreakTimeResponse(u32, f32),
     Knockback(f32, f32, f32, bool),
     FluidSelectable(bool),
-    PlaySound(String, Position, f32, f32, bool),
+    PlaySound(String, Position, f32, f32, bool, SoundCategory),
     ChatMessage(String),
     PlayerAbilities(f32, MovementType),
     TeleportPlayer(Position, Direction),
-    ModelItem(ClientModelTarget, u32, Option<u32>),
+    ModelItem(ClientModelTarget, Vec<(u32, Option<u32>)>),
+    ModelItemTransform(ClientModelTarget, Vec<(u32, Option<Transformation>)>),
     ModelAnimation(ClientModelTarget, u32),
+    ModelBonePose(ClientModelTarget, Vec<u32>, Option<BonePose>),
+    EntityAnimationState(u32, String),
     ControllingEntity(u32),
+    ScreenFade(f32, f32),
+    SetWorldBorder(WorldBorder),
+    SetSky(SkySettings),
+    Disconnect(String),
+    SetBuildAssist(Option<f32>),
+    SpawnParticles(String, Position, u32, f32, Vec3),
+    BlockBreakProgress(u32, BlockPosition, Option<u8>),
+    AddTextDisplay(u32, Position, String, Color, f32),
+    RemoveTextDisplay(u32),
+    RegisterKeybinds(Vec<(String, KeyboardKey)>),
+    JoinFailed(JoinFailure),
+    ChunkDictionary(Vec<u8>),
+    GuiLayout(f32, f32),
+    Welcome(ProtocolHello),
+    //id and the last measured round trip time in milliseconds
+    Ping(u32, Option<u32>),
+    //gui id and slot count of a layout drawn as the native hotbar, closed like other guis with GuiRemoveElements
+    HotbarOpen(String, u32),
+    HotbarSlot(u32, Option<HotbarItem>),
+    //the hand slot, which is also the index in the hotbar
+    HotbarSelect(u32),
+    //item client id and cooldown length in seconds, 0 ends the cooldown
+    ItemCooldown(u32, f32),
+    //named positions dynamic item models can point at, None removes the waypoint
+    SetWaypoint(String, Option<Position>),
+    //id and the longest side in pixels, always answered with Snapshot
+    RequestSnapshot(u32, u32),
+    //replaces a named group of shapes, removed after the lifetime in seconds or never if it is 0, an empty list removes the group
+    SetDebugShapes(String, Vec<(DebugShape, Color)>, f32),
+}
+impl NetworkMessageS2C {
+    pub fn get_required_capability(&self) -> Option<Capability> {
+        match self {
+            NetworkMessageS2C::SpawnParticles(..) => Some(Capability::Particles),
+            NetworkMessageS2C::AddTextDisplay(..) | NetworkMessageS2C::RemoveTextDisplay(..) => {
+                Some(Capability::TextDisplays)
+            }
+            NetworkMessageS2C::SetSky(..) => Some(Capability::Sky),
+            NetworkMessageS2C::SetBuildAssist(..) => Some(Capability::BuildAssist),
+            NetworkMessageS2C::RegisterKeybinds(..) => Some(Capability::Keybinds),
+            NetworkMessageS2C::ModelBonePose(..) => Some(Capability::BonePoses),
+            NetworkMessageS2C::BlockBreakProgress(..) => Some(Capability::BreakProgress),
+            NetworkMessageS2C::GuiLayout(..) => Some(Capability::GuiLayout),
+            NetworkMessageS2C::Ping(..) => Some(Capability::Ping),
+            NetworkMessageS2C::HotbarOpen(..)
+            | NetworkMessageS2C::HotbarSlot(..)
+            | NetworkMessageS2C::HotbarSelect(..)
+            | NetworkMessageS2C::ItemCooldown(..) => Some(Capability::Hotbar),
+            NetworkMessageS2C::SetWaypoint(..) => Some(Capability::Waypoints),
+            NetworkMessageS2C::RequestSnapshot(..) => Some(Capability::Snapshots),
+            NetworkMessageS2C::SetDebugShapes(..) => Some(Capability::DebugShapes),
+            _ => None,
+        }
+    }
+    //only used by quic, websockets send everything in order
+    pub fn get_channel(&self) -> MessageChannel {
+        match self {
+            NetworkMessageS2C::LoadChunk(..)
+            | NetworkMessageS2C::UnloadChunk(..)
+            | NetworkMessageS2C::SetBlock(..)
+            | NetworkMessageS2C::SetBlocks(..)
+            | NetworkMessageS2C::ChunkDictionary(..) => MessageChannel::Chunks,
+            NetworkMessageS2C::MoveEntity(..) => MessageChannel::Unreliable,
+            _ => MessageChannel::Control,
+        }
+    }
+}
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MessageChannel {
+    Control,
+    //block changes go with the chunks so they can't overtake the chunk they belong to
+    Chunks,
+    //may be lost or arrive out of order
+    Unreliable,
+}
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum ChunkCompression {
+    Gzip,
+    Zstd,
+}
+#[derive(Serialize, Deserialize)]
+pub enum ChunkPayload {
+    Gzip(Vec<u8>),
+    //dictionary is the last one sent with ChunkDictionary
+    Zstd { dictionary: bool, data: Vec<u8> },
+}
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum JoinFailure {
+    MissingEntityType(String),
+    InvalidSpawnInfo(String),
+    ScriptError { mod_name: String, error: String },
+    AlreadyConnected,
+}
+impl JoinFailure {
+    pub fn describe(&self) -> String {
+        match self {
+            JoinFailure::MissingEntityType(entity_type) => {
+                format!("player entity type {} doesn't exist", entity_type)
+            }
+            JoinFailure::InvalidSpawnInfo(field) => {
+                format!("spawn info is missing valid {}", field)
+            }
+            JoinFailure::ScriptError { mod_name, error } => {
+                format!("script error in mod {}: {}", mod_name, error)
+            }
+            JoinFailure::AlreadyConnected => {
+                "a player with the same uuid is already connected".to_string()
+            }
+        }
+    }
+}
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SoundCategory {
+    Ambient,
+    Block,
+    Footsteps,
+    Creature,
+    Player,
+    Interface,
 }
 #[derive(Serialize, Deserialize)]
 pub enum ClientModelTarget {
@@ -33,18 +239,33 @@ pub enum ClientModelTarget {
     Entity(u32),
     ViewModel,
 }
+//durability goes from 0 to 1 and is only set for damageable items
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct HotbarItem {
+    pub item: u32,
+    pub count: u32,
+    pub durability: Option<f32>,
+}
+//rotation is in radians, weight is how much of the animated pose gets replaced
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct BonePose {
+    pub position: Vec3,
+    pub rotation: Vec3,
+    pub weight: f32,
+}
 #[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display, EnumIter)]
 pub enum MovementType {
     Normal = 0,
     Fly = 1,
     NoClip = 2,
+    Spectator = 3,
 }
 #[derive(Serialize, Deserialize)]
 pub enum NetworkMessageC2S {
     BreakBlock(BlockPosition),
     RightClickBlock(BlockPosition, Face, bool),
     PlayerPosition(Position, bool, Direction, bool),
-    MouseScroll(i32, i32),
+    ChangeHandSlot(i32),
     Keyboard(KeyboardKey, u8, bool, bool),
     GuiClick(String, MouseButton, bool),
     RequestBlockBreakTime(u32, BlockPosition),
@@ -54,6 +275,20 @@ pub enum NetworkMessageC2S {
     RightClick(bool),
     SendMessage(String),
     ConnectionMode(u8),
+    Identify(String, String, Option<String>),
+    PlaceBlocks(Vec<BlockPosition>, Face),
+    SwapHands,
+    AbortBlockBreak,
+    KeybindAction(String, bool),
+    SupportedCompression(Vec<ChunkCompression>),
+    GuiTextChanged(String, String),
+    GuiVisibleRange(String, u32, u32),
+    Hello(ProtocolHello),
+    Pong(u32),
+    //like RightClickBlock with where the block was hit, only sent to servers with PlacementHit
+    RightClickBlockAt(BlockPosition, Face, bool, Position),
+    //png of the view without the gui, None when the player doesn't allow snapshots
+    Snapshot(u32, Option<Vec<u8>>),
 }
 #[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display, EnumIter, Debug)]
 pub enum MouseButton {
@@ -0,0 +1,222 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::Arc;
+use std::time::Duration;
+
+use quinn::{Connection, RecvStream, SendStream, VarInt};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::messages::MessageChannel;
+
+pub enum Received {
+    Reliable(Vec<u8>),
+    //sequence number, later datagrams have higher numbers
+    Unreliable(u32, Vec<u8>),
+}
+
+//messages are length prefixed frames, control goes over one bidirectional stream opened by the client and
+//chunks over a unidirectional stream from the server so they can't hold up everything else
+pub struct QuicConnection {
+    connection: Connection,
+    control: Option<UnboundedSender<Vec<u8>>>,
+    chunks: Option<UnboundedSender<Vec<u8>>>,
+    received: Receiver<Received>,
+    queued: Arc<AtomicUsize>,
+    closed: Arc<AtomicBool>,
+    datagram_sequence: u32,
+}
+impl QuicConnection {
+    const BACKLOG_LIMIT: usize = 1024 * 1024;
+    //must be called from within the tokio runtime
+    pub fn start(
+        connection: Connection,
+        control: (SendStream, RecvStream),
+        chunks: Option<SendStream>,
+        max_frame_size: usize,
+    ) -> Self {
+        let (received_sender, received) = mpsc::channel();
+        let queued = Arc::new(AtomicUsize::new(0));
+        let closed = Arc::new(AtomicBool::new(false));
+        {
+            let received_sender = received_sender.clone();
+            let closed = closed.clone();
+            let recv = control.1;
+            tokio::spawn(async move {
+                read_frames(recv, received_sender, max_frame_size).await;
+                closed.store(true, Ordering::Relaxed);
+            });
+        }
+        {
+            let received_sender = received_sender.clone();
+            let connection = connection.clone();
+            tokio::spawn(async move {
+                while let Ok(recv) = connection.accept_uni().await {
+                    tokio::spawn(read_frames(recv, received_sender.clone(), max_frame_size));
+                }
+            });
+        }
+        {
+            let connection = connection.clone();
+            tokio::spawn(async move {
+                while let Ok(datagram) = connection.read_datagram().await {
+                    if datagram.len() < 4 {
+                        continue;
+                    }
+                    let sequence = u32::from_le_bytes(datagram[..4].try_into().unwrap());
+                    if received_sender
+                        .send(Received::Unreliable(sequence, datagram[4..].to_vec()))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+        }
+        let (control_sender, control_receiver) = unbounded_channel();
+        let control_writer = tokio::spawn(write_frames(
+            control.0,
+            control_receiver,
+            queued.clone(),
+            closed.clone(),
+        ));
+        let (chunks_sender, chunk_writer) = match chunks {
+            Some(stream) => {
+                let (sender, receiver) = unbounded_channel();
+                let writer = tokio::spawn(write_frames(
+                    stream,
+                    receiver,
+                    queued.clone(),
+                    closed.clone(),
+                ));
+                (Some(sender), Some(writer))
+            }
+            None => (None, None),
+        };
+        {
+            //closes once both streams are finished, so everything sent before close still arrives
+            let connection = connection.clone();
+            tokio::spawn(async move {
+                control_writer.await.ok();
+                if let Some(chunk_writer) = chunk_writer {
+                    chunk_writer.await.ok();
+                }
+                connection.close(VarInt::from_u32(0), b"");
+            });
+        }
+        QuicConnection {
+            connection,
+            control: Some(control_sender),
+            chunks: chunks_sender,
+            received,
+            queued,
+            closed,
+            datagram_sequence: 0,
+        }
+    }
+    pub fn get_address(&self) -> SocketAddr {
+        self.connection.remote_address()
+    }
+    pub fn send(&mut self, channel: MessageChannel, data: Vec<u8>) {
+        if channel == MessageChannel::Unreliable {
+            //too large datagrams go over the control stream instead
+            if self
+                .connection
+                .max_datagram_size()
+                .map_or(false, |max_size| data.len() + 4 <= max_size)
+            {
+                self.datagram_sequence = self.datagram_sequence.wrapping_add(1);
+                let mut datagram = self.datagram_sequence.to_le_bytes().to_vec();
+                datagram.extend(data);
+                if self.connection.send_datagram(datagram.into()).is_err() {
+                    self.closed.store(true, Ordering::Relaxed);
+                }
+                return;
+            }
+        }
+        let sender = match channel {
+            MessageChannel::Chunks => self.chunks.as_ref().or(self.control.as_ref()),
+            _ => self.control.as_ref(),
+        };
+        if let Some(sender) = sender {
+            self.queued.fetch_add(data.len(), Ordering::Relaxed);
+            if sender.send(data).is_err() {
+                self.closed.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+    pub fn receive(&mut self) -> Option<Received> {
+        match self.received.try_recv() {
+            Ok(received) => Some(received),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.closed.store(true, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+    pub fn receive_blocking(&mut self, timeout: Duration) -> Option<Received> {
+        match self.received.recv_timeout(timeout) {
+            Ok(received) => Some(received),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => {
+                self.closed.store(true, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+    //the streams are finished after everything queued was written, then the connection is closed
+    pub fn close(&mut self) {
+        self.control = None;
+        self.chunks = None;
+        self.closed.store(true, Ordering::Relaxed);
+    }
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+    pub fn is_backlogged(&self) -> bool {
+        self.queued.load(Ordering::Relaxed) > Self::BACKLOG_LIMIT
+    }
+}
+
+async fn read_frames(mut stream: RecvStream, received: Sender<Received>, max_frame_size: usize) {
+    loop {
+        let mut length = [0; 4];
+        if stream.read_exact(&mut length).await.is_err() {
+            break;
+        }
+        let length = u32::from_le_bytes(length) as usize;
+        if length > max_frame_size {
+            break;
+        }
+        let mut frame = vec![0; length];
+        if stream.read_exact(&mut frame).await.is_err() {
+            break;
+        }
+        if received.send(Received::Reliable(frame)).is_err() {
+            break;
+        }
+    }
+}
+async fn write_frames(
+    mut stream: SendStream,
+    mut frames: UnboundedReceiver<Vec<u8>>,
+    queued: Arc<AtomicUsize>,
+    closed: Arc<AtomicBool>,
+) {
+    while let Some(frame) = frames.recv().await {
+        let result = async {
+            stream
+                .write_all(&(frame.len() as u32).to_le_bytes())
+                .await?;
+            stream.write_all(frame.as_slice()).await
+        }
+        .await;
+        queued.fetch_sub(frame.len(), Ordering::Relaxed);
+        if result.is_err() {
+            closed.store(true, Ordering::Relaxed);
+            return;
+        }
+    }
+    stream.finish().await.ok();
+}
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+//a recording starts with the magic, followed by the header and the frames,
+//each one serialized with bitcode and prefixed with its length as a little endian u32
+pub const RECORDING_MAGIC: &[u8; 4] = b"BBRC";
+pub const RECORDING_VERSION: u32 = 1;
+pub const RECORDING_TICKS_PER_SECOND: u32 = 20;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub version: u32,
+    pub protocol_version: u32,
+    pub world: String,
+    pub player: String,
+    //unix time in seconds
+    pub started: u64,
+}
+#[derive(Serialize, Deserialize)]
+pub struct RecordingFrame {
+    //ticks since the recording started
+    pub tick: u32,
+    //everything needed to show the world as it was at the end of the tick, skipped during playback and used for seeking
+    pub keyframe: bool,
+    //serialized NetworkMessageS2C, exactly as they were sent
+    pub messages: Vec<Vec<u8>>,
+}
+
+pub fn write_record<W: Write>(output: &mut W, data: &[u8]) -> std::io::Result<()> {
+    output.write_all(&(data.len() as u32).to_le_bytes())?;
+    output.write_all(data)
+}
+//None if the data doesn't start with the magic, a record cut off at the end is left out
+pub fn split_records(data: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut data = data.strip_prefix(RECORDING_MAGIC)?;
+    let mut records = Vec::new();
+    while data.len() >= 4 {
+        let length = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        if data.len() - 4 < length {
+            break;
+        }
+        records.push(&data[4..4 + length]);
+        data = &data[4 + length..];
+    }
+    Some(records)
+}
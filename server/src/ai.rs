@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bbscript::variant::{FromVariant, IntoVariant};
+use block_byte_common::{BlockPosition, Face};
+use json::JsonValue;
+use parking_lot::Mutex;
+use rand::Rng;
+use uuid::Uuid;
+
+use crate::registry::{Block, BlockRegistry};
+use crate::util::{BlockLocation, Identifier};
+use crate::world::World;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    Sleep,
+    Work,
+    Wander,
+}
+impl Activity {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "sleep" => Some(Activity::Sleep),
+            "work" => Some(Activity::Work),
+            "wander" => Some(Activity::Wander),
+            _ => None,
+        }
+    }
+}
+
+pub struct ScheduleEntry {
+    pub start: u64,
+    pub activity: Activity,
+    pub claim: Option<Arc<Block>>,
+}
+
+pub struct Schedule {
+    entries: Vec<ScheduleEntry>,
+}
+impl Schedule {
+    pub fn from_json(
+        json: &JsonValue,
+        block_registry: &BlockRegistry,
+    ) -> Result<Option<Self>, String> {
+        if json.is_null() {
+            return Ok(None);
+        }
+        let mut entries = json
+            .members()
+            .map(|entry| {
+                let activity = entry["activity"]
+                    .as_str()
+                    .ok_or_else(|| "schedule entry needs an activity".to_string())?;
+                Ok(ScheduleEntry {
+                    start: entry["time"]
+                        .as_u64()
+                        .ok_or_else(|| "schedule entry needs a time".to_string())?,
+                    activity: Activity::parse(activity)
+                        .ok_or_else(|| format!("unknown activity {}", activity))?,
+                    claim: match entry["block"].as_str() {
+                        Some(block) => Some(
+                            Identifier::parse(block)
+                                .ok()
+                                .and_then(|id| block_registry.block_by_identifier(&id))
+                                .ok_or_else(|| format!("unknown block {}", block))?
+                                .clone(),
+                        ),
+                        None => None,
+                    },
+                })
+            })
+            .collect::<Result<Vec<ScheduleEntry>, String>>()?;
+        entries.sort_by_key(|entry| entry.start);
+        Ok(Some(Schedule { entries }))
+    }
+    pub fn get_entry(&self, day_time: u64) -> Option<(usize, &ScheduleEntry)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, entry)| entry.start <= day_time)
+            .or(self.entries.iter().enumerate().last())
+    }
+}
+
+#[derive(Clone)]
+pub struct BlockClaims(Arc<Mutex<HashMap<BlockPosition, Uuid>>>);
+impl BlockClaims {
+    pub fn id() -> Identifier {
+        Identifier::new("bb", "block_claims")
+    }
+    pub fn of(world: &World) -> BlockClaims {
+        let mut user_data = world.user_data.lock();
+        if let Some(claims) = user_data
+            .0
+            .get(&Self::id())
+            .and_then(|claims| BlockClaims::from_variant(claims))
+        {
+            return claims.clone();
+        }
+        let claims = BlockClaims(Arc::new(Mutex::new(HashMap::new())));
+        user_data
+            .0
+            .insert(Self::id(), claims.clone().into_variant());
+        claims
+    }
+    pub fn claim(&self, position: BlockPosition, entity: Uuid) -> bool {
+        let mut claims = self.0.lock();
+        match claims.get(&position) {
+            Some(owner) => *owner == entity,
+            None => {
+                claims.insert(position, entity);
+                true
+            }
+        }
+    }
+    pub fn is_claimed_by(&self, position: BlockPosition, entity: Uuid) -> bool {
+        self.0.lock().get(&position) == Some(&entity)
+    }
+    pub fn release(&self, position: BlockPosition) {
+        self.0.lock().remove(&position);
+    }
+    pub fn release_all(&self, entity: Uuid) {
+        self.0.lock().retain(|_, owner| *owner != entity);
+    }
+    pub fn get_in_radius(&self, center: BlockPosition, radius: f64) -> Vec<(BlockPosition, Uuid)> {
+        self.0
+            .lock()
+            .iter()
+            .filter(|(position, _)| position.distance(&center) <= radius)
+            .map(|(position, owner)| (*position, *owner))
+            .collect()
+    }
+}
+
+pub struct ScheduleController {
+    entry: Option<usize>,
+    claim: Option<BlockPosition>,
+    target: Option<BlockPosition>,
+    cooldown: u32,
+}
+impl ScheduleController {
+    const SEARCH_RADIUS: i32 = 16;
+    const SEARCH_HEIGHT: i32 = 4;
+    const WANDER_RADIUS: i32 = 8;
+    const RETRY_DELAY: u32 = 100;
+    pub fn new() -> Self {
+        ScheduleController {
+            entry: None,
+            claim: None,
+            target: None,
+            cooldown: 0,
+        }
+    }
+    pub fn tick(
+        &mut self,
+        schedule: &Schedule,
+        location: &BlockLocation,
+        entity: Uuid,
+    ) -> Option<BlockLocation> {
+        let world = &location.world;
+        let (index, entry) = schedule.get_entry(world.get_day_time())?;
+        let claims = BlockClaims::of(world);
+        if self.entry != Some(index) {
+            self.entry = Some(index);
+            if let Some(claim) = self.claim.take() {
+                claims.release(claim);
+            }
+            self.target = None;
+            self.cooldown = 0;
+        }
+        if let Some(claim) = self.claim {
+            //claims are only checked by their owner, so a claimed block that got replaced is given up here
+            let replaced = entry
+                .claim
+                .as_ref()
+                .and_then(|block| Self::is_block(world, claim, block))
+                == Some(false);
+            if replaced {
+                claims.release(claim);
+            }
+            if replaced || !claims.is_claimed_by(claim, entity) {
+                self.claim = None;
+                self.target = None;
+                self.cooldown = 0;
+            }
+        }
+        if self.cooldown > 0 {
+            self.cooldown -= 1;
+        } else {
+            match (entry.activity, &entry.claim) {
+                (Activity::Wander, _) => {
+                    let mut rng = rand::thread_rng();
+                    self.target = Some(location.position.add(
+                        rng.gen_range(-Self::WANDER_RADIUS..=Self::WANDER_RADIUS),
+                        0,
+                        rng.gen_range(-Self::WANDER_RADIUS..=Self::WANDER_RADIUS),
+                    ));
+                    self.cooldown = Self::RETRY_DELAY;
+                }
+                (_, Some(block)) => {
+                    if self.claim.is_none() {
+                        self.claim =
+                            Self::find_claim(world, location.position, block, &claims, entity);
+                        self.target = self.claim.map(|claim| claim.offset_by_face(Face::Up));
+                        if self.claim.is_none() {
+                            self.cooldown = Self::RETRY_DELAY;
+                        }
+                    }
+                }
+                (_, None) => {
+                    self.target = None;
+                }
+            }
+        }
+        self.target.map(|position| BlockLocation {
+            world: world.clone(),
+            position,
+        })
+    }
+    fn find_claim(
+        world: &World,
+        center: BlockPosition,
+        block: &Arc<Block>,
+        claims: &BlockClaims,
+        entity: Uuid,
+    ) -> Option<BlockPosition> {
+        for x in -Self::SEARCH_RADIUS..=Self::SEARCH_RADIUS {
+            for y in -Self::SEARCH_HEIGHT..=Self::SEARCH_HEIGHT {
+                for z in -Self::SEARCH_RADIUS..=Self::SEARCH_RADIUS {
+                    let position = center.add(x, y, z);
+                    let matches = Self::is_block(world, position, block).unwrap_or(false);
+                    if matches && claims.claim(position, entity) {
+                        return Some(position);
+                    }
+                }
+            }
+        }
+        None
+    }
+    fn is_block(world: &World, position: BlockPosition, block: &Arc<Block>) -> Option<bool> {
+        world.get_block(&position).map(|data| {
+            Arc::ptr_eq(
+                &world
+                    .server
+                    .block_registry
+                    .state_by_ref(data.get_block_state())
+                    .parent,
+                block,
+            )
+        })
+    }
+}
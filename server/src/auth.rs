@@ -0,0 +1,144 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use hmac::{Hmac, Mac};
+use parking_lot::Mutex;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::logging::{self, LogTarget};
+
+#[derive(Clone)]
+pub struct PlayerIdentity {
+    pub username: String,
+    pub uuid: Uuid,
+}
+impl PlayerIdentity {
+    pub fn create_token(secret: &str, username: &str, uuid: &Uuid) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}:{}", uuid, username).as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+}
+
+struct IdentityList {
+    path: PathBuf,
+    entries: HashSet<String>,
+}
+impl IdentityList {
+    fn load(path: PathBuf) -> Self {
+        let entries = fs::read_to_string(&path)
+            .map(|content| {
+                content
+                    .lines()
+                    .map(|line| line.trim())
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(|line| line.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        IdentityList { path, entries }
+    }
+    fn save(&self) {
+        let mut entries: Vec<_> = self.entries.iter().cloned().collect();
+        entries.sort();
+        let mut content = entries.join("\n");
+        content.push('\n');
+        if let Err(error) = fs::write(&self.path, content) {
+            logging::error(
+                LogTarget::Server,
+                format!("could not save {}: {}", self.path.display(), error),
+            );
+        }
+    }
+    fn contains(&self, identity: &PlayerIdentity) -> bool {
+        self.entries.contains(&identity.uuid.to_string())
+            || self.entries.contains(&identity.username)
+    }
+}
+
+pub struct AccessList {
+    whitelist: Mutex<IdentityList>,
+    banlist: Mutex<IdentityList>,
+    operators: Mutex<IdentityList>,
+}
+impl AccessList {
+    pub fn load(save_directory: &Path) -> Self {
+        AccessList {
+            whitelist: Mutex::new(IdentityList::load(save_directory.join("whitelist.txt"))),
+            banlist: Mutex::new(IdentityList::load(save_directory.join("banlist.txt"))),
+            operators: Mutex::new(IdentityList::load(save_directory.join("operators.txt"))),
+        }
+    }
+    pub fn check(
+        &self,
+        identity: &PlayerIdentity,
+        token: Option<&str>,
+        whitelist_enabled: bool,
+        require_auth: bool,
+        secret: &str,
+    ) -> Result<(), String> {
+        if identity.username.is_empty() || identity.username.len() > 32 {
+            return Err("invalid username".to_string());
+        }
+        if require_auth {
+            let expected = PlayerIdentity::create_token(secret, &identity.username, &identity.uuid);
+            if secret.is_empty()
+                || !token
+                    .is_some_and(|token| constant_time_eq(token.as_bytes(), expected.as_bytes()))
+            {
+                return Err("authentication failed".to_string());
+            }
+        }
+        if self.banlist.lock().contains(identity) {
+            return Err("you are banned from this server".to_string());
+        }
+        if whitelist_enabled && !self.whitelist.lock().contains(identity) {
+            return Err("you are not whitelisted on this server".to_string());
+        }
+        Ok(())
+    }
+    pub fn ban(&self, uuid: Uuid) {
+        let mut banlist = self.banlist.lock();
+        if banlist.entries.insert(uuid.to_string()) {
+            banlist.save();
+        }
+    }
+    pub fn unban(&self, uuid: Uuid) {
+        let mut banlist = self.banlist.lock();
+        if banlist.entries.remove(&uuid.to_string()) {
+            banlist.save();
+        }
+    }
+    pub fn whitelist(&self, entry: String, add: bool) {
+        Self::update_list(&self.whitelist, entry, add);
+    }
+    pub fn set_operator(&self, entry: String, add: bool) {
+        Self::update_list(&self.operators, entry, add);
+    }
+    pub fn is_operator(&self, identity: &PlayerIdentity) -> bool {
+        self.operators.lock().contains(identity)
+    }
+    fn update_list(list: &Mutex<IdentityList>, entry: String, add: bool) {
+        let mut list = list.lock();
+        let changed = if add {
+            list.entries.insert(entry)
+        } else {
+            list.entries.remove(&entry)
+        };
+        if changed {
+            list.save();
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |difference, (a, b)| difference | (a ^ b))
+        == 0
+}
@@ -0,0 +1,127 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+use block_byte_common::{BlockPosition, Face};
+use parking_lot::Mutex;
+
+use crate::util::{BlockLocation, Identifier};
+use crate::world::{BlockNetwork, World};
+
+//what the neighbor on a face of a cable is to its network
+pub enum Connection<E> {
+    Cable,
+    Endpoint(E),
+    None,
+}
+
+struct CableState<E> {
+    networks: HashMap<BlockPosition, Arc<BlockNetwork>>,
+    endpoints: HashMap<BlockPosition, Arc<Vec<E>>>,
+    rebuilt: HashSet<BlockPosition>,
+    rebuilt_time: u64,
+    //cable blocks of freshly loaded chunks, networks are only kept in memory
+    loaded: Vec<BlockPosition>,
+}
+
+//connected cables form one network, which is rebuilt from scratch whenever one of its blocks changes
+pub struct CableNetworks<E> {
+    network_id: Identifier,
+    state: Mutex<CableState<E>>,
+}
+impl<E: Clone + PartialEq> CableNetworks<E> {
+    const MAX_NETWORK_SIZE: usize = 4096;
+    pub fn new(network_id: Identifier) -> Self {
+        CableNetworks {
+            network_id,
+            state: Mutex::new(CableState {
+                networks: HashMap::new(),
+                endpoints: HashMap::new(),
+                rebuilt: HashSet::new(),
+                rebuilt_time: 0,
+                loaded: Vec::new(),
+            }),
+        }
+    }
+    pub fn on_chunk_load(&self, positions: Vec<BlockPosition>) {
+        self.state.lock().loaded.extend(positions);
+    }
+    pub fn take_loaded(&self) -> Vec<BlockPosition> {
+        std::mem::take(&mut self.state.lock().loaded)
+    }
+    pub fn get_network(&self, position: BlockPosition) -> Option<Arc<BlockNetwork>> {
+        self.state.lock().networks.get(&position).cloned()
+    }
+    pub fn get_endpoints(&self, position: BlockPosition) -> Arc<Vec<E>> {
+        self.state
+            .lock()
+            .endpoints
+            .get(&position)
+            .cloned()
+            .unwrap_or_default()
+    }
+    pub fn remove(&self, position: BlockPosition) {
+        let mut state = self.state.lock();
+        state.networks.remove(&position);
+        state.endpoints.remove(&position);
+    }
+    pub fn was_rebuilt(&self, world: &World, position: BlockPosition) -> bool {
+        let state = self.state.lock();
+        state.rebuilt_time == world.get_time() && state.rebuilt.contains(&position)
+    }
+    //setup runs before the network is visible to anyone, returns the members and endpoints
+    pub fn rebuild<C, S>(
+        &self,
+        world: &World,
+        start: BlockPosition,
+        connection: C,
+        setup: S,
+    ) -> (Vec<BlockPosition>, Arc<Vec<E>>)
+    where
+        C: Fn(BlockPosition, Face) -> Connection<E>,
+        S: FnOnce(&BlockNetwork, &[E]),
+    {
+        let mut members = HashSet::new();
+        let mut queue = VecDeque::new();
+        let mut endpoints = Vec::new();
+        members.insert(start);
+        queue.push_back(start);
+        while let Some(position) = queue.pop_front() {
+            for face in Face::all() {
+                let neighbor = position.offset_by_face(*face);
+                match connection(neighbor, *face) {
+                    Connection::Cable => {
+                        if members.len() < Self::MAX_NETWORK_SIZE && members.insert(neighbor) {
+                            queue.push_back(neighbor);
+                        }
+                    }
+                    Connection::Endpoint(endpoint) => {
+                        if !endpoints.contains(&endpoint) {
+                            endpoints.push(endpoint);
+                        }
+                    }
+                    Connection::None => {}
+                }
+            }
+        }
+        let network = BlockNetwork::new(self.network_id.clone());
+        setup(&network, endpoints.as_slice());
+        let endpoints = Arc::new(endpoints);
+        let members: Vec<_> = members.into_iter().collect();
+        let mut state = self.state.lock();
+        let time = world.get_time();
+        if state.rebuilt_time != time {
+            state.rebuilt_time = time;
+            state.rebuilt.clear();
+        }
+        for member in &members {
+            network.add_member(BlockLocation {
+                position: *member,
+                world: world.ptr(),
+            });
+            state.networks.insert(*member, network.clone());
+            state.endpoints.insert(*member, endpoints.clone());
+            state.rebuilt.insert(*member);
+        }
+        (members, endpoints)
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use block_byte_common::ChunkPosition;
+use parking_lot::Mutex;
+
+use crate::world::{Chunk, World};
+
+pub struct GenerationQueue {
+    pending: Mutex<Vec<Arc<Chunk>>>,
+    in_flight: AtomicU32,
+    generated: AtomicU64,
+    generation_time: AtomicU64,
+}
+impl GenerationQueue {
+    pub fn new() -> Self {
+        GenerationQueue {
+            pending: Mutex::new(Vec::new()),
+            in_flight: AtomicU32::new(0),
+            generated: AtomicU64::new(0),
+            generation_time: AtomicU64::new(0),
+        }
+    }
+    pub fn push(&self, chunk: Arc<Chunk>) {
+        self.pending.lock().push(chunk);
+    }
+    pub fn get_queued(&self) -> usize {
+        self.pending.lock().len() + self.in_flight.load(Ordering::Relaxed) as usize
+    }
+    pub fn get_generated(&self) -> u64 {
+        self.generated.load(Ordering::Relaxed)
+    }
+    pub fn get_average_generation_time(&self) -> Duration {
+        let generated = self.get_generated();
+        if generated == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_micros(self.generation_time.load(Ordering::Relaxed) / generated)
+    }
+    pub fn record_generation(&self, duration: Duration) {
+        self.generation_time
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+        self.generated.fetch_add(1, Ordering::Relaxed);
+    }
+    fn get_priority(position: ChunkPosition, player_chunks: &[ChunkPosition]) -> u32 {
+        player_chunks
+            .iter()
+            .map(|player_chunk| {
+                (player_chunk.x - position.x)
+                    .unsigned_abs()
+                    .max((player_chunk.y - position.y).unsigned_abs())
+                    .max((player_chunk.z - position.z).unsigned_abs())
+            })
+            .min()
+            .unwrap_or(u32::MAX)
+    }
+    //chunks closest to players are sent to the worldgen threads first, the rest waits until there is room
+    pub fn dispatch(&self, world: &World, player_chunks: &[ChunkPosition]) {
+        let capacity = world
+            .server
+            .settings
+            .get_i64("server.worldgen_queue_size", 32)
+            .max(1) as usize;
+        let free = capacity.saturating_sub(self.in_flight.load(Ordering::Relaxed) as usize);
+        if free == 0 {
+            return;
+        }
+        let mut pending = self.pending.lock();
+        pending.retain(|chunk| {
+            world
+                .get_chunk(chunk.position)
+                .map_or(false, |loaded| Arc::ptr_eq(&loaded, chunk))
+        });
+        pending.sort_by_key(|chunk| Self::get_priority(chunk.position, player_chunks));
+        let count = free.min(pending.len());
+        for chunk in pending.drain(..count) {
+            self.in_flight.fetch_add(1, Ordering::Relaxed);
+            world.server.worldgen_pool.execute(Box::new(move || {
+                chunk.load();
+                chunk
+                    .world
+                    .generation_queue
+                    .in_flight
+                    .fetch_sub(1, Ordering::Relaxed);
+            }));
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use block_byte_common::BlockPosition;
+
+use crate::registry::BlockStateRef;
+use crate::util::Identifier;
+use crate::world::{Structure, StructureTransform, World};
+use crate::Server;
+
+pub struct Clipboard {
+    first: Option<BlockPosition>,
+    second: Option<BlockPosition>,
+    structure: Option<Structure>,
+    history: Vec<(Identifier, Vec<(BlockPosition, BlockStateRef)>)>,
+}
+impl Clipboard {
+    const MAX_HISTORY: usize = 16;
+    const MAX_VOLUME: i64 = 64 * 64 * 64;
+    pub fn new() -> Self {
+        Clipboard {
+            first: None,
+            second: None,
+            structure: None,
+            history: Vec::new(),
+        }
+    }
+    pub fn set_first(&mut self, position: BlockPosition) {
+        self.first = Some(position);
+    }
+    pub fn set_second(&mut self, position: BlockPosition) {
+        self.second = Some(position);
+    }
+    pub fn copy(&mut self, world: &World, origin: BlockPosition) -> Result<usize, String> {
+        let (first, second) = match (self.first, self.second) {
+            (Some(first), Some(second)) => (first, second),
+            _ => return Err("select both corners first".to_string()),
+        };
+        let volume = ((first.x - second.x).abs() as i64 + 1)
+            * ((first.y - second.y).abs() as i64 + 1)
+            * ((first.z - second.z).abs() as i64 + 1);
+        if volume > Self::MAX_VOLUME {
+            return Err(format!("selection too large ({} blocks)", volume));
+        }
+        let structure = Structure::from_world(world, first, second, origin);
+        let count = structure.get_block_count();
+        self.structure = Some(structure);
+        Ok(count)
+    }
+    pub fn paste(&mut self, world: &Arc<World>, position: BlockPosition) -> Result<usize, String> {
+        let structure = self
+            .structure
+            .as_ref()
+            .ok_or_else(|| "clipboard is empty".to_string())?;
+        let mut previous = Vec::new();
+        let mut blocks = Vec::new();
+        structure.place(
+            |block_position, block| {
+                if !world.is_inside_border(block_position) {
+                    return;
+                }
+                previous.push((
+                    block_position,
+                    world.get_block_load(block_position).get_block_state(),
+                ));
+                blocks.push((block_position, block));
+            },
+            position,
+            StructureTransform::IDENTITY,
+        );
+        world.set_blocks(blocks, true);
+        structure.place_data(world, position, StructureTransform::IDENTITY, None);
+        let count = previous.len();
+        self.history.push((world.id.clone(), previous));
+        if self.history.len() > Self::MAX_HISTORY {
+            self.history.remove(0);
+        }
+        Ok(count)
+    }
+    pub fn rotate(&mut self, turns: i64) -> Result<(), String> {
+        let structure = self
+            .structure
+            .as_mut()
+            .ok_or_else(|| "clipboard is empty".to_string())?;
+        *structure = structure.with_transform(StructureTransform::new(turns, false));
+        Ok(())
+    }
+    pub fn flip(&mut self, axis: &str) -> Result<(), String> {
+        let structure = self
+            .structure
+            .as_mut()
+            .ok_or_else(|| "clipboard is empty".to_string())?;
+        *structure = match axis {
+            "x" => structure.with_transform(StructureTransform::new(0, true)),
+            "y" => structure.flipped_vertically(),
+            "z" => structure.with_transform(StructureTransform::new(2, true)),
+            _ => return Err(format!("unknown axis {}", axis)),
+        };
+        Ok(())
+    }
+    pub fn undo(&mut self, server: &Server) -> Result<usize, String> {
+        let (world, previous) = self
+            .history
+            .pop()
+            .ok_or_else(|| "nothing to undo".to_string())?;
+        let world = server
+            .worlds
+            .lock()
+            .get(&world)
+            .cloned()
+            .ok_or_else(|| format!("world {} isn't loaded", world))?;
+        let count = previous.len();
+        world.set_blocks(previous, true);
+        Ok(count)
+    }
+    pub fn run_command(
+        &mut self,
+        world: &Arc<World>,
+        position: BlockPosition,
+        command: &[&str],
+    ) -> Option<Result<String, String>> {
+        Some(match command {
+            ["pos1"] => {
+                self.set_first(position);
+                Ok(format!("first corner set to {}", position))
+            }
+            ["pos2"] => {
+                self.set_second(position);
+                Ok(format!("second corner set to {}", position))
+            }
+            ["copy"] => self
+                .copy(world, position)
+                .map(|count| format!("copied {} blocks", count)),
+            ["paste"] => self
+                .paste(world, position)
+                .map(|count| format!("pasted {} blocks", count)),
+            ["rotate", turns] => match turns.parse::<i64>() {
+                Ok(turns) => self
+                    .rotate(turns)
+                    .map(|_| format!("rotated clipboard {} times", turns)),
+                Err(_) => Err(format!("invalid rotation {}", turns)),
+            },
+            ["flip", axis] => self
+                .flip(axis)
+                .map(|_| format!("flipped clipboard along {}", axis)),
+            ["undo"] => self
+                .undo(&world.server)
+                .map(|count| format!("restored {} blocks", count)),
+            _ => return None,
+        })
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+use bbscript::eval::ExecutionEnvironment;
+use bbscript::variant::{FromVariant, FunctionVariant, IntoVariant, SharedArray, Variant};
+use immutable_string::ImmutableString;
+use json::JsonValue;
+use parking_lot::Mutex;
+use uuid::Uuid;
+
+use crate::auth::PlayerIdentity;
+use crate::logging::{self, LogTarget};
+use crate::mods::{self, ScriptCallback};
+use crate::recording;
+use crate::util::Identifier;
+use crate::world::PlayerData;
+use crate::Server;
+
+//a command from the commands folder of a mod, named after its file
+pub struct ModCommand {
+    pub permission: Option<String>,
+    pub usage: Option<String>,
+    execute: ScriptCallback,
+}
+impl ModCommand {
+    pub fn from_json(id: &Identifier, json: JsonValue, environment: &ExecutionEnvironment) -> Self {
+        ModCommand {
+            permission: json["permission"].as_str().map(|node| node.to_string()),
+            usage: json["usage"].as_str().map(|usage| usage.to_string()),
+            execute: FunctionVariant::from_variant(&mods::json_to_variant(
+                json["execute"].clone(),
+                environment,
+            ))
+            .map(|function| ScriptCallback::from_function_variant(function))
+            .unwrap_or_else(|| panic!("command {} has no execute function", id)),
+        }
+    }
+}
+
+pub struct CommandRegistry {
+    commands: HashMap<String, ModCommand>,
+}
+impl CommandRegistry {
+    pub fn new(commands: HashMap<String, ModCommand>) -> Self {
+        CommandRegistry { commands }
+    }
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+    //None if no mod has the command, a string returned by the script is sent back to the player
+    pub fn run(
+        &self,
+        server: &Server,
+        player: &Arc<PlayerData>,
+        command: &[&str],
+    ) -> Option<Result<String, String>> {
+        let (name, arguments) = command.split_first()?;
+        let command = self.commands.get(*name)?;
+        if let Some(permission) = &command.permission {
+            if !player.has_permission(permission) {
+                return Some(Err(format!("you need the permission {}", permission)));
+            }
+        }
+        let arguments: SharedArray = Arc::new(Mutex::new(
+            arguments
+                .iter()
+                .map(|argument| Variant::from_str(argument))
+                .collect(),
+        ));
+        Some(
+            match command.execute.call_function(
+                &server.script_environment,
+                None,
+                vec![player.clone().into_variant(), arguments.into_variant()],
+            ) {
+                Ok(result) => Ok(ImmutableString::from_variant(&result)
+                    .map(|message| message.to_string())
+                    .unwrap_or_default()),
+                Err(error) => {
+                    logging::error(
+                        LogTarget::Scripts,
+                        format!("command {} failed: {}", name, error),
+                    );
+                    Err(match &command.usage {
+                        Some(usage) => format!("usage: {}", usage),
+                        None => "command failed".to_string(),
+                    })
+                }
+            },
+        )
+    }
+}
+
+//lines typed into the server terminal, run with the rights of an operator
+pub struct Console {
+    lines: Mutex<Receiver<String>>,
+}
+impl Console {
+    pub fn start() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+        Console {
+            lines: Mutex::new(receiver),
+        }
+    }
+    pub fn tick(&self, server: &Server) {
+        let lines: Vec<String> = self.lines.lock().try_iter().collect();
+        for line in lines {
+            let command: Vec<&str> = line.trim_start_matches('/').split_whitespace().collect();
+            if command.is_empty() {
+                continue;
+            }
+            match Self::run(server, &command) {
+                Ok(message) => logging::info(LogTarget::Server, message),
+                Err(error) => logging::warn(LogTarget::Server, format!("error: {}", error)),
+            }
+        }
+    }
+    fn create_token(server: &Server, username: &str, uuid: &Uuid) -> String {
+        format!(
+            "identity.token of {} ({}): {}",
+            username,
+            uuid,
+            PlayerIdentity::create_token(
+                server.settings.get("server.auth_secret", "").as_str(),
+                username,
+                uuid
+            )
+        )
+    }
+    fn run(server: &Server, command: &[&str]) -> Result<String, String> {
+        match command {
+            ["op", entry] => {
+                server.access_list.set_operator(entry.to_string(), true);
+                Ok(format!("{} is now an operator", entry))
+            }
+            ["deop", entry] => {
+                server.access_list.set_operator(entry.to_string(), false);
+                Ok(format!("{} is no longer an operator", entry))
+            }
+            ["token", username, uuid] => {
+                let uuid = Uuid::parse_str(uuid).map_err(|_| "malformed uuid".to_string())?;
+                Ok(Self::create_token(server, username, &uuid))
+            }
+            ["token", username] => {
+                let uuid = server
+                    .players
+                    .lock()
+                    .iter()
+                    .find(|player| player.identity.username == *username)
+                    .map(|player| player.identity.uuid)
+                    .ok_or_else(|| {
+                        format!("{} is not online, use token <player> <uuid>", username)
+                    })?;
+                Ok(Self::create_token(server, username, &uuid))
+            }
+            ["perm", arguments @ ..] => server.permissions.run_command(server, arguments),
+            ["record", arguments @ ..] => recording::run_command(server, arguments),
+            ["scripttrace", arguments @ ..] => server.event_tracer.run_command(arguments),
+            ["reload"] => server.reload(),
+            ["stop", ..] | ["restart", ..] => server.shutdown.run_command(server, command),
+            ["list"] => {
+                let players: Vec<_> = server
+                    .players
+                    .lock()
+                    .iter()
+                    .map(|player| player.identity.username.clone())
+                    .collect();
+                Ok(format!(
+                    "{} players online: {}",
+                    players.len(),
+                    players.join(", ")
+                ))
+            }
+            _ => Err(
+                "console commands: op <player>, deop <player>, token <player> [uuid], perm, record, scripttrace, reload, stop, restart, list"
+                    .to_string(),
+            ),
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use block_byte_common::messages::{ChunkCompression, ChunkPayload};
+use flate2::Compression;
+use parking_lot::Mutex;
+
+use crate::logging::{self, LogTarget};
+
+#[derive(Clone)]
+pub struct ChunkEncoder {
+    pub compression: ChunkCompression,
+    pub level: i32,
+    pub dictionary: Option<Arc<Vec<u8>>>,
+}
+impl ChunkEncoder {
+    pub fn encode(&self, data: &[u8]) -> ChunkPayload {
+        match self.compression {
+            ChunkCompression::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(
+                    Vec::new(),
+                    Compression::new(self.level.clamp(0, 9) as u32),
+                );
+                encoder.write_all(data).unwrap();
+                ChunkPayload::Gzip(encoder.finish().unwrap())
+            }
+            ChunkCompression::Zstd => {
+                let level = self.level.clamp(1, 22);
+                match &self.dictionary {
+                    Some(dictionary) => ChunkPayload::Zstd {
+                        dictionary: true,
+                        data: zstd::bulk::Compressor::with_dictionary(level, dictionary)
+                            .and_then(|mut compressor| compressor.compress(data))
+                            .unwrap(),
+                    },
+                    None => ChunkPayload::Zstd {
+                        dictionary: false,
+                        data: zstd::bulk::compress(data, level).unwrap(),
+                    },
+                }
+            }
+        }
+    }
+}
+
+//samples the first chunks sent to players and trains a zstd dictionary on them, which is kept in the save directory
+pub struct ChunkDictionaryTrainer {
+    path: PathBuf,
+    samples: Mutex<Vec<Vec<u8>>>,
+    sampling_finished: AtomicBool,
+    dictionary: Arc<Mutex<Option<Arc<Vec<u8>>>>>,
+}
+impl ChunkDictionaryTrainer {
+    const SAMPLE_COUNT: usize = 1000;
+    const MAX_SIZE: usize = 16 * 1024;
+    pub fn load(path: PathBuf, enabled: bool) -> Self {
+        let dictionary = if enabled {
+            std::fs::read(&path).ok().map(Arc::new)
+        } else {
+            None
+        };
+        ChunkDictionaryTrainer {
+            path,
+            samples: Mutex::new(Vec::new()),
+            sampling_finished: AtomicBool::new(!enabled || dictionary.is_some()),
+            dictionary: Arc::new(Mutex::new(dictionary)),
+        }
+    }
+    pub fn get_dictionary(&self) -> Option<Arc<Vec<u8>>> {
+        self.dictionary.lock().clone()
+    }
+    pub fn add_sample(&self, data: &[u8]) {
+        if self.sampling_finished.load(Ordering::Relaxed) {
+            return;
+        }
+        let samples = {
+            let mut samples = self.samples.lock();
+            samples.push(data.to_vec());
+            if samples.len() < Self::SAMPLE_COUNT
+                || self.sampling_finished.swap(true, Ordering::Relaxed)
+            {
+                return;
+            }
+            std::mem::take(&mut *samples)
+        };
+        //training takes a while, samples come from chunk sending jobs that shouldn't wait for it
+        let path = self.path.clone();
+        let target = self.dictionary.clone();
+        thread::spawn(move || Self::train(path, target, samples));
+    }
+    fn train(path: PathBuf, target: Arc<Mutex<Option<Arc<Vec<u8>>>>>, samples: Vec<Vec<u8>>) {
+        match zstd::dict::from_samples(&samples, Self::MAX_SIZE) {
+            Ok(dictionary) => {
+                if let Err(error) = std::fs::write(&path, &dictionary) {
+                    logging::warn(
+                        LogTarget::Net,
+                        format!("couldn't save chunk dictionary: {}", error),
+                    );
+                }
+                logging::info(
+                    LogTarget::Net,
+                    format!(
+                        "trained chunk dictionary from {} samples ({} bytes)",
+                        samples.len(),
+                        dictionary.len()
+                    ),
+                );
+                *target.lock() = Some(Arc::new(dictionary));
+            }
+            Err(error) => logging::warn(
+                LogTarget::Net,
+                format!("couldn't train chunk dictionary: {}", error),
+            ),
+        }
+    }
+}
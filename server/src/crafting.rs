@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use json::JsonValue;
+
+use crate::inventory::{Inventory, InventoryView, ItemStack, Recipe};
+use crate::logging::{self, LogTarget};
+use crate::mods::IdentifierTag;
+use crate::registry::{Item, ItemRegistry, RecipeManager};
+use crate::util::Identifier;
+use crate::world::PlayerData;
+
+#[derive(Clone)]
+pub enum Ingredient {
+    Item(Arc<Item>),
+    Tag(Arc<IdentifierTag>),
+}
+impl Ingredient {
+    //"#core:planks" is any item in the tag, anything else is an item id
+    pub fn parse(
+        name: &str,
+        item_registry: &ItemRegistry,
+        tags: &HashMap<Identifier, Arc<IdentifierTag>>,
+    ) -> Result<Self, String> {
+        match name.strip_prefix('#') {
+            Some(tag) => Identifier::parse(tag)
+                .ok()
+                .and_then(|tag| tags.get(&tag))
+                .map(|tag| Ingredient::Tag(tag.clone()))
+                .ok_or_else(|| format!("unknown tag {}", tag)),
+            None => Identifier::parse(name)
+                .ok()
+                .and_then(|id| item_registry.item_by_identifier(&id))
+                .map(|item| Ingredient::Item(item.clone()))
+                .ok_or_else(|| format!("unknown item {}", name)),
+        }
+    }
+    pub fn matches(&self, item: &Arc<Item>) -> bool {
+        match self {
+            Ingredient::Item(expected) => Arc::ptr_eq(expected, item),
+            Ingredient::Tag(tag) => tag.contains(&item.id),
+        }
+    }
+    fn is_same(&self, other: &Ingredient) -> bool {
+        match (self, other) {
+            (Ingredient::Item(first), Ingredient::Item(second)) => Arc::ptr_eq(first, second),
+            (Ingredient::Tag(first), Ingredient::Tag(second)) => Arc::ptr_eq(first, second),
+            _ => false,
+        }
+    }
+}
+
+pub struct RecipeInput {
+    pub ingredient: Ingredient,
+    pub count: u32,
+}
+impl RecipeInput {
+    pub fn from_json(
+        json: &JsonValue,
+        item_registry: &ItemRegistry,
+        tags: &HashMap<Identifier, Arc<IdentifierTag>>,
+    ) -> Result<Self, String> {
+        Ok(RecipeInput {
+            ingredient: Ingredient::parse(
+                json["id"].as_str().ok_or("recipe input without id")?,
+                item_registry,
+                tags,
+            )?,
+            count: json["count"].as_u32().unwrap_or(1),
+        })
+    }
+    //the rest that is missing from the inventory
+    pub fn remove_from(&self, inventory: &InventoryView) -> u32 {
+        inventory.remove_matching(self.count, |item| self.ingredient.matches(item.get_type()))
+    }
+}
+
+pub enum RecipeShape {
+    Shaped {
+        width: u32,
+        height: u32,
+        pattern: Vec<Option<Ingredient>>,
+    },
+    Shapeless,
+}
+impl RecipeShape {
+    pub fn from_json(
+        json: &JsonValue,
+        item_registry: &ItemRegistry,
+        tags: &HashMap<Identifier, Arc<IdentifierTag>>,
+    ) -> Result<Self, String> {
+        if json["pattern"].is_null() {
+            return Ok(RecipeShape::Shapeless);
+        }
+        let mut rows = Vec::new();
+        for row in json["pattern"].members() {
+            rows.push(row.as_str().ok_or("pattern rows must be strings")?);
+        }
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(0) as u32;
+        let height = rows.len() as u32;
+        let mut pattern = Vec::with_capacity((width * height) as usize);
+        for row in &rows {
+            for x in 0..width as usize {
+                pattern.push(match row.chars().nth(x) {
+                    Some(' ') | None => None,
+                    Some(key) => Some(Ingredient::parse(
+                        json["key"][key.to_string().as_str()]
+                            .as_str()
+                            .ok_or_else(|| format!("pattern key {} has no item", key))?,
+                        item_registry,
+                        tags,
+                    )?),
+                });
+            }
+        }
+        Ok(RecipeShape::Shaped {
+            width,
+            height,
+            pattern,
+        })
+    }
+    pub fn to_inputs(&self) -> Vec<RecipeInput> {
+        let mut inputs: Vec<RecipeInput> = Vec::new();
+        if let RecipeShape::Shaped { pattern, .. } = self {
+            for ingredient in pattern.iter().flatten() {
+                match inputs
+                    .iter_mut()
+                    .find(|input| input.ingredient.is_same(ingredient))
+                {
+                    Some(input) => input.count += 1,
+                    None => inputs.push(RecipeInput {
+                        ingredient: ingredient.clone(),
+                        count: 1,
+                    }),
+                }
+            }
+        }
+        inputs
+    }
+}
+
+pub struct CraftingGrid {
+    grid_size: u32,
+    recipes: Vec<Arc<Recipe>>,
+}
+impl CraftingGrid {
+    pub fn shaped_type() -> Identifier {
+        Identifier::new("bb", "crafting_shaped")
+    }
+    pub fn shapeless_type() -> Identifier {
+        Identifier::new("bb", "crafting_shapeless")
+    }
+    pub fn new(grid_size: u32, recipe_manager: &RecipeManager) -> Self {
+        let mut recipes = Vec::new();
+        recipes.extend(recipe_manager.by_type(&Self::shaped_type()).iter().cloned());
+        recipes.extend(
+            recipe_manager
+                .by_type(&Self::shapeless_type())
+                .iter()
+                .cloned(),
+        );
+        CraftingGrid { grid_size, recipes }
+    }
+    pub fn get_output_slot(&self) -> u32 {
+        self.grid_size * self.grid_size
+    }
+    pub fn is_grid_slot(&self, slot: u32) -> bool {
+        slot < self.get_output_slot()
+    }
+    fn grid_view<'a>(&self, inventory: &'a Inventory) -> InventoryView<'a> {
+        inventory.get_view(0..self.get_output_slot())
+    }
+    pub fn find_recipe(&self, inventory: &Inventory) -> Option<Arc<Recipe>> {
+        let grid = self.grid_view(inventory).export_content();
+        self.recipes
+            .iter()
+            .find(|recipe| self.matches(recipe, &grid))
+            .cloned()
+    }
+    fn matches(&self, recipe: &Recipe, grid: &[Option<ItemStack>]) -> bool {
+        match recipe.get_shape() {
+            RecipeShape::Shaped {
+                width,
+                height,
+                pattern,
+            } => {
+                let (min_x, min_y) = match self.get_pattern_offset(*width, *height, grid) {
+                    Some(offset) => offset,
+                    None => return false,
+                };
+                for y in 0..self.grid_size {
+                    for x in 0..self.grid_size {
+                        let expected = if x >= min_x
+                            && y >= min_y
+                            && x - min_x < *width
+                            && y - min_y < *height
+                        {
+                            pattern[((x - min_x) + (y - min_y) * width) as usize].as_ref()
+                        } else {
+                            None
+                        };
+                        let actual = grid[(x + y * self.grid_size) as usize].as_ref();
+                        let matching = match (expected, actual) {
+                            (Some(expected), Some(actual)) => expected.matches(actual.get_type()),
+                            (None, None) => true,
+                            _ => false,
+                        };
+                        if !matching {
+                            return false;
+                        }
+                    }
+                }
+                true
+            }
+            RecipeShape::Shapeless => {
+                let inputs = recipe.get_inputs();
+                if inputs.is_empty() {
+                    return false;
+                }
+                for item in grid.iter().flatten() {
+                    if !inputs
+                        .iter()
+                        .any(|input| input.ingredient.matches(item.get_type()))
+                    {
+                        return false;
+                    }
+                }
+                let grid_inventory = Inventory::new_owned(grid.len() as u32, None);
+                grid_inventory.load_content(grid.to_vec().into_boxed_slice());
+                recipe.has_ingredients(&grid_inventory.get_full_view())
+            }
+        }
+    }
+    //where the top left corner of a pattern has to be, given by the top left item in the grid
+    fn get_pattern_offset(
+        &self,
+        width: u32,
+        height: u32,
+        grid: &[Option<ItemStack>],
+    ) -> Option<(u32, u32)> {
+        let occupied: Vec<(u32, u32)> = (0..self.grid_size)
+            .flat_map(|y| (0..self.grid_size).map(move |x| (x, y)))
+            .filter(|(x, y)| grid[(x + y * self.grid_size) as usize].is_some())
+            .collect();
+        let min_x = occupied.iter().map(|(x, _)| *x).min()?;
+        let min_y = occupied.iter().map(|(_, y)| *y).min()?;
+        if min_x + width > self.grid_size || min_y + height > self.grid_size {
+            return None;
+        }
+        Some((min_x, min_y))
+    }
+    pub fn update_output(&self, inventory: &Inventory) {
+        let output = self.find_recipe(inventory).map(|recipe| recipe.get_icon());
+        inventory.set_item_no_update(self.get_output_slot(), output);
+    }
+    pub fn take_output(&self, inventory: &Inventory, player: &PlayerData) {
+        let recipe = match self.find_recipe(inventory) {
+            Some(recipe) => recipe,
+            None => return,
+        };
+        let output = recipe.get_icon();
+        let fits = match player.hand_item.lock().as_ref() {
+            Some(hand) => {
+                hand.is_stackable_with(&output)
+                    && hand.get_count() + output.get_count() <= hand.get_type().stack_size
+            }
+            None => true,
+        };
+        if !fits {
+            return;
+        }
+        let grid = self.grid_view(inventory);
+        match recipe.get_shape() {
+            RecipeShape::Shaped {
+                width,
+                height,
+                pattern,
+            } => {
+                let (min_x, min_y) =
+                    match self.get_pattern_offset(*width, *height, &grid.export_content()) {
+                        Some(offset) => offset,
+                        None => return,
+                    };
+                for y in 0..*height {
+                    for x in 0..*width {
+                        if pattern[(x + y * width) as usize].is_none() {
+                            continue;
+                        }
+                        grid.modify_item(min_x + x + (min_y + y) * self.grid_size, |item| {
+                            if let Some(item) = item {
+                                item.add_count(-1);
+                            }
+                        })
+                        .unwrap();
+                    }
+                }
+            }
+            RecipeShape::Shapeless => {
+                if recipe.consume_inputs(&grid).is_err() {
+                    return;
+                }
+            }
+        }
+        player.modify_inventory_hand(|hand| {
+            *hand = Some(match hand.take() {
+                Some(mut hand) => {
+                    hand.add_count(output.get_count() as i32);
+                    hand
+                }
+                None => output,
+            });
+        });
+        self.update_output(inventory);
+    }
+    pub fn return_items(&self, inventory: &Inventory, player: &PlayerData) {
+        let grid = self.grid_view(inventory);
+        let entity = player.get_entity();
+        let entity_inventory = entity.inventory.get_full_view();
+        for slot in 0..grid.get_size() {
+            if let Some(item) = grid.get_item(slot).unwrap() {
+                grid.set_item(slot, None).unwrap();
+                //the grid is dropped with the gui, so what doesn't fit is dropped at the player
+                if let Some(rest) = entity_inventory.add_item(&item) {
+                    if let Err(error) = entity
+                        .server
+                        .drop_item(&(&entity.get_location()).into(), rest)
+                    {
+                        logging::error(LogTarget::Server, error);
+                    }
+                }
+            }
+        }
+    }
+}
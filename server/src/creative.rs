@@ -0,0 +1,178 @@
+use block_byte_common::gui::{GUIComponentEdit, GUIElementEdit};
+use block_byte_common::messages::{MouseButton, NetworkMessageS2C};
+use parking_lot::Mutex;
+
+use crate::inventory::{GuiKey, Inventory, ItemStack};
+
+struct CatalogFilter {
+    //index into the categories, None shows every item
+    category: Option<usize>,
+    query: String,
+    page: usize,
+}
+
+//a paged grid of every registered item, creative players take stacks out of it and anything put back is deleted
+pub struct CreativeCatalog {
+    page_size: u32,
+    categories: Vec<String>,
+    filter: Mutex<CatalogFilter>,
+}
+impl CreativeCatalog {
+    pub fn new(page_size: u32, categories: Vec<String>) -> Self {
+        CreativeCatalog {
+            page_size,
+            categories,
+            filter: Mutex::new(CatalogFilter {
+                category: None,
+                query: String::new(),
+                page: 0,
+            }),
+        }
+    }
+    pub fn get_size(&self) -> u32 {
+        self.page_size
+    }
+    //fills the slots with the current page and updates the page and category labels
+    pub fn refresh(&self, inventory: &Inventory, key: &GuiKey) {
+        let server = &key.player.server;
+        let mut filter = self.filter.lock();
+        let mut query = "@item".to_string();
+        if let Some(category) = filter.category {
+            query.push_str(format!(" ${}", self.categories[category]).as_str());
+        }
+        query.push(' ');
+        query.push_str(filter.query.as_str());
+        let mut results =
+            server
+                .search_index
+                .search(query.as_str(), filter.page, self.page_size as usize);
+        //a narrower search can have fewer pages than the one before
+        if filter.page > 0 && filter.page >= results.pages {
+            filter.page = results.pages.saturating_sub(1);
+            results =
+                server
+                    .search_index
+                    .search(query.as_str(), filter.page, self.page_size as usize);
+        }
+        let view = inventory.get_full_view();
+        for slot in 0..self.page_size {
+            let item = results
+                .ids
+                .get(slot as usize)
+                .and_then(|id| server.item_registry.item_by_identifier(id))
+                .map(|item| ItemStack::new(item, item.stack_size));
+            view.set_item(slot, item).unwrap();
+        }
+        Self::set_text(
+            key,
+            "page",
+            format!("page {}/{}", filter.page + 1, results.pages.max(1)),
+        );
+        Self::set_text(
+            key,
+            "category",
+            match filter.category {
+                Some(category) => self.categories[category].clone(),
+                None => "all".to_string(),
+            },
+        );
+    }
+    fn set_text(key: &GuiKey, element: &str, text: String) {
+        key.player.send_message(&NetworkMessageS2C::GuiEditElement(
+            format!("{}_{}", key.id, element),
+            GUIElementEdit {
+                component_type: GUIComponentEdit::TextComponent {
+                    text: Some(text),
+                    font_size: None,
+                },
+                ..Default::default()
+            },
+        ));
+    }
+    pub fn set_query(&self, inventory: &Inventory, key: &GuiKey, query: &str) {
+        {
+            let mut filter = self.filter.lock();
+            filter.query = query.to_string();
+            filter.page = 0;
+        }
+        self.refresh(inventory, key);
+    }
+    pub fn on_click(
+        &self,
+        inventory: &Inventory,
+        key: &GuiKey,
+        id: &str,
+        button: MouseButton,
+        shifting: bool,
+    ) {
+        if let Ok(slot) = id.parse::<u32>() {
+            self.take(inventory, key, slot, button, shifting);
+            return;
+        }
+        {
+            let mut filter = self.filter.lock();
+            let count = self.categories.len();
+            match id {
+                //refresh stops at the last page
+                "next" => filter.page += 1,
+                "previous" => filter.page = filter.page.saturating_sub(1),
+                "category_next" => {
+                    filter.category = match filter.category {
+                        None if count > 0 => Some(0),
+                        Some(category) if category + 1 < count => Some(category + 1),
+                        _ => None,
+                    };
+                    filter.page = 0;
+                }
+                "category_previous" => {
+                    filter.category = match filter.category {
+                        None if count > 0 => Some(count - 1),
+                        Some(category) if category > 0 => Some(category - 1),
+                        _ => None,
+                    };
+                    filter.page = 0;
+                }
+                _ => return,
+            }
+        }
+        self.refresh(inventory, key);
+    }
+    //only creative players get items, the slots themselves never change
+    fn take(
+        &self,
+        inventory: &Inventory,
+        key: &GuiKey,
+        slot: u32,
+        button: MouseButton,
+        shifting: bool,
+    ) {
+        let player = &key.player;
+        if !player.is_creative() {
+            return;
+        }
+        let item = inventory.get_full_view().get_item(slot).ok().flatten();
+        let hand = player.hand_item.lock().clone();
+        match (hand, item) {
+            (None, Some(item)) if shifting => {
+                player
+                    .get_entity()
+                    .inventory
+                    .get_full_view()
+                    .add_item(&item);
+            }
+            (None, Some(item)) => player.set_inventory_hand(Some(match button {
+                MouseButton::Right => item.copy(1),
+                _ => item,
+            })),
+            (Some(hand), Some(item)) if hand.is_stackable_with(&item) => {
+                let stack_size = item.get_type().stack_size;
+                player.set_inventory_hand(Some(hand.copy(match button {
+                    MouseButton::Right => (hand.get_count() + 1).min(stack_size),
+                    _ => stack_size,
+                })));
+            }
+            (Some(_), _) => player.set_inventory_hand(None),
+            (None, None) => {}
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::sync::Arc;
+
+use block_byte_common::messages::{Capability, NetworkMessageS2C};
+use block_byte_common::{BlockPosition, ChunkPosition, Color, DebugShape, Position};
+use uuid::Uuid;
+
+use crate::ai::BlockClaims;
+use crate::util::Identifier;
+use crate::world::{PlayerData, StructureTransform, World};
+
+const CHUNK_BORDERS: &str = "chunk_borders";
+const GROUPS: [&str; 4] = [CHUNK_BORDERS, "claims", "paths", "structure"];
+//shapes other than chunk borders show what was there when asked and don't follow changes
+const LIFETIME: f32 = 30.;
+const RADIUS: f64 = 32.;
+//how many chunks around the player get their corners marked
+const CHUNK_RADIUS: i32 = 2;
+
+const CURRENT_CHUNK_COLOR: Color = Color {
+    r: 255,
+    g: 220,
+    b: 0,
+    a: 255,
+};
+const CHUNK_CORNER_COLOR: Color = Color {
+    r: 0,
+    g: 120,
+    b: 255,
+    a: 255,
+};
+const PATH_COLOR: Color = Color {
+    r: 0,
+    g: 255,
+    b: 80,
+    a: 255,
+};
+const STRUCTURE_COLOR: Color = Color {
+    r: 255,
+    g: 0,
+    b: 255,
+    a: 255,
+};
+
+//chunk borders are resent whenever the player walks into another chunk
+pub struct DebugShapeView {
+    chunk_borders: bool,
+    shown_chunk: Option<ChunkPosition>,
+}
+impl DebugShapeView {
+    pub fn new() -> Self {
+        DebugShapeView {
+            chunk_borders: false,
+            shown_chunk: None,
+        }
+    }
+    pub fn tick(&mut self, player: &PlayerData) {
+        if !self.chunk_borders {
+            return;
+        }
+        let chunk = player.get_entity().get_location().chunk.position;
+        if self.shown_chunk == Some(chunk) {
+            return;
+        }
+        self.shown_chunk = Some(chunk);
+        player.send_message(&NetworkMessageS2C::SetDebugShapes(
+            CHUNK_BORDERS.to_string(),
+            chunk_borders(chunk),
+            0.,
+        ));
+    }
+    fn toggle_chunk_borders(&mut self, player: &PlayerData) -> bool {
+        self.chunk_borders = !self.chunk_borders;
+        self.shown_chunk = None;
+        if !self.chunk_borders {
+            player.send_message(&NetworkMessageS2C::SetDebugShapes(
+                CHUNK_BORDERS.to_string(),
+                Vec::new(),
+                0.,
+            ));
+        }
+        self.chunk_borders
+    }
+}
+
+pub fn run_command(player: &Arc<PlayerData>, command: &[&str]) -> Result<String, String> {
+    if !player.has_capability(Capability::DebugShapes) {
+        return Err("your client can't show debug shapes".to_string());
+    }
+    let location = player.get_entity().get_location();
+    let position = location.position.to_block_pos();
+    let (group, shapes) = match command {
+        ["chunks"] => {
+            return Ok(if player.debug_shapes.lock().toggle_chunk_borders(player) {
+                "chunk borders shown"
+            } else {
+                "chunk borders hidden"
+            }
+            .to_string());
+        }
+        ["clear"] => {
+            let mut view = player.debug_shapes.lock();
+            if view.chunk_borders {
+                view.toggle_chunk_borders(player);
+            }
+            for group in GROUPS {
+                player.send_message(&NetworkMessageS2C::SetDebugShapes(
+                    group.to_string(),
+                    Vec::new(),
+                    0.,
+                ));
+            }
+            return Ok("debug shapes cleared".to_string());
+        }
+        ["claims"] => ("claims", claims(&location.chunk.world, position)),
+        ["paths"] => ("paths", paths(&location.chunk.world, location.position)),
+        ["structure", id, transform @ ..] => {
+            let id = Identifier::parse(*id).map_err(|_| format!("invalid structure id {}", id))?;
+            let structure = player
+                .server
+                .structures
+                .get(&id)
+                .ok_or_else(|| format!("unknown structure {}", id))?;
+            let rotation = |rotation: &str| {
+                rotation
+                    .parse::<i64>()
+                    .map_err(|_| format!("invalid rotation {}", rotation))
+            };
+            let transform = match transform {
+                [] => StructureTransform::IDENTITY,
+                [turns] => StructureTransform::new(rotation(turns)?, false),
+                [turns, "mirror"] => StructureTransform::new(rotation(turns)?, true),
+                _ => return Err(usage()),
+            };
+            let (min, max) = structure
+                .get_bounds(position, transform)
+                .ok_or_else(|| format!("structure {} is empty", id))?;
+            (
+                "structure",
+                vec![
+                    (block_box(min, max), STRUCTURE_COLOR),
+                    //the origin, which would be at the player's feet
+                    (
+                        DebugShape::Sphere(position.to_position().add(0.5, 0.5, 0.5), 0.5),
+                        STRUCTURE_COLOR,
+                    ),
+                ],
+            )
+        }
+        _ => return Err(usage()),
+    };
+    let count = shapes.len();
+    player.send_message(&NetworkMessageS2C::SetDebugShapes(
+        group.to_string(),
+        shapes,
+        LIFETIME,
+    ));
+    Ok(format!(
+        "showing {} debug shapes for {} seconds",
+        count, LIFETIME
+    ))
+}
+fn usage() -> String {
+    "usage: /debugshapes [chunks|claims|paths|structure <id> [rotation] [mirror]|clear]".to_string()
+}
+
+fn block_box(min: BlockPosition, max: BlockPosition) -> DebugShape {
+    DebugShape::Box(min.to_position(), max.to_position().add(1., 1., 1.))
+}
+fn chunk_corner(chunk: ChunkPosition) -> Position {
+    Position {
+        x: chunk.x as f64 * 16.,
+        y: chunk.y as f64 * 16.,
+        z: chunk.z as f64 * 16.,
+    }
+}
+//the current chunk as a box and vertical lines at the corners of the chunks around it
+fn chunk_borders(chunk: ChunkPosition) -> Vec<(DebugShape, Color)> {
+    let mut shapes = vec![(
+        DebugShape::Box(chunk_corner(chunk), chunk_corner(chunk.add(1, 1, 1))),
+        CURRENT_CHUNK_COLOR,
+    )];
+    for x in -CHUNK_RADIUS..=CHUNK_RADIUS + 1 {
+        for z in -CHUNK_RADIUS..=CHUNK_RADIUS + 1 {
+            shapes.push((
+                DebugShape::Line(
+                    chunk_corner(chunk.add(x, -CHUNK_RADIUS, z)),
+                    chunk_corner(chunk.add(x, CHUNK_RADIUS + 1, z)),
+                ),
+                CHUNK_CORNER_COLOR,
+            ));
+        }
+    }
+    shapes
+}
+//every owner gets its own color so neighbouring claims can be told apart
+fn owner_color(owner: Uuid) -> Color {
+    let bytes = owner.as_bytes();
+    Color {
+        r: 64 + bytes[0] % 192,
+        g: 64 + bytes[1] % 192,
+        b: 64 + bytes[2] % 192,
+        a: 255,
+    }
+}
+fn claims(world: &World, center: BlockPosition) -> Vec<(DebugShape, Color)> {
+    BlockClaims::of(world)
+        .get_in_radius(center, RADIUS)
+        .into_iter()
+        .map(|(position, owner)| (block_box(position, position), owner_color(owner)))
+        .collect()
+}
+//lines from each entity through the centers of the blocks it is going to walk through
+fn paths(world: &World, center: Position) -> Vec<(DebugShape, Color)> {
+    let mut shapes = Vec::new();
+    for entity in world.get_entities_in_radius(center, RADIUS) {
+        let path = entity.get_path();
+        let mut previous = entity.get_location().position;
+        for position in &path {
+            let next = position.to_position().add(0.5, 0.5, 0.5);
+            shapes.push((DebugShape::Line(previous, next), PATH_COLOR));
+            previous = next;
+        }
+        if let Some(target) = path.last() {
+            shapes.push((block_box(*target, *target), PATH_COLOR));
+        }
+    }
+    shapes
+}
@@ -0,0 +1,221 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use bbscript::variant::{FromVariant, IntoVariant, Variant};
+use block_byte_common::{BlockPosition, AABB};
+use json::JsonValue;
+use parking_lot::Mutex;
+
+use crate::logging::{self, LogTarget};
+use crate::registry::BlockState;
+use crate::util::{BlockLocation, Identifier};
+use crate::world::{Entity, World};
+
+pub struct Detector {
+    //relative to the block and kept inside it, entities are only matched against the blocks they overlap
+    pub aabb: AABB,
+    pub property: Option<String>,
+    pub players_only: bool,
+}
+impl Detector {
+    pub fn from_json(json: &JsonValue) -> Option<Self> {
+        if json.is_null() {
+            return None;
+        }
+        let aabb = &json["aabb"];
+        let get = |key: &str, default: f64| aabb[key].as_f64().unwrap_or(default).clamp(0., 1.);
+        let (x, y, z) = (get("x", 0.), get("y", 0.), get("z", 0.));
+        Some(Detector {
+            aabb: AABB {
+                x,
+                y,
+                z,
+                w: get("w", 1.).min(1. - x),
+                h: get("h", 1.).min(1. - y),
+                d: get("d", 1.).min(1. - z),
+            },
+            property: json["property"]
+                .as_str()
+                .map(|property| property.to_string()),
+            players_only: json["players_only"].as_bool().unwrap_or(false),
+        })
+    }
+    fn overlaps(&self, position: BlockPosition, collider: &AABB) -> bool {
+        self.aabb
+            .move_by(position.x as f64, position.y as f64, position.z as f64)
+            .collides(collider)
+    }
+}
+
+fn get_state(world: &World, position: BlockPosition) -> Option<&BlockState> {
+    let block = world.get_block(&position)?;
+    Some(
+        world
+            .server
+            .block_registry
+            .state_by_ref(block.get_block_state()),
+    )
+}
+
+//detector blocks overlapped by the collider, only the blocks the collider touches are looked at
+pub fn detect(world: &World, collider: &AABB, player: bool) -> HashSet<BlockPosition> {
+    collider
+        .iter_blocks()
+        .filter(|position| {
+            get_state(world, *position)
+                .and_then(|state| state.parent.detector.as_ref())
+                .map_or(false, |detector| {
+                    (player || !detector.players_only) && detector.overlaps(*position, collider)
+                })
+        })
+        .collect()
+}
+
+struct OccupantState {
+    counts: HashMap<BlockPosition, u32>,
+    //blocks left by entities of unloaded chunks, released on the next tick
+    released: Vec<BlockPosition>,
+}
+
+#[derive(Clone)]
+pub struct DetectorOccupants(Arc<Mutex<OccupantState>>);
+impl DetectorOccupants {
+    pub fn id() -> Identifier {
+        Identifier::new("bb", "detector_occupants")
+    }
+    pub fn of(world: &World) -> DetectorOccupants {
+        let mut user_data = world.user_data.lock();
+        if let Some(occupants) = user_data
+            .0
+            .get(&Self::id())
+            .and_then(|occupants| DetectorOccupants::from_variant(occupants))
+        {
+            return occupants.clone();
+        }
+        let occupants = DetectorOccupants(Arc::new(Mutex::new(OccupantState {
+            counts: HashMap::new(),
+            released: Vec::new(),
+        })));
+        user_data
+            .0
+            .insert(Self::id(), occupants.clone().into_variant());
+        occupants
+    }
+    //returns the new number of entities on the block
+    fn change(&self, position: BlockPosition, entered: bool) -> u32 {
+        let occupants = &mut self.0.lock().counts;
+        let count = occupants.entry(position).or_insert(0);
+        if entered {
+            *count += 1;
+        } else {
+            *count = count.saturating_sub(1);
+        }
+        let count = *count;
+        if count == 0 {
+            occupants.remove(&position);
+        }
+        count
+    }
+}
+
+fn set_property(world: &Arc<World>, position: BlockPosition, state: &BlockState, value: bool) {
+    let property = match &state.parent.detector {
+        Some(Detector {
+            property: Some(property),
+            ..
+        }) => property,
+        _ => return,
+    };
+    if let Ok(new_state) = state.with_property(property.as_str(), value.into_variant()) {
+        world.set_block(position, new_state, true, Variant::NULL());
+    }
+}
+
+pub fn on_enter(world: &Arc<World>, position: BlockPosition, entity: &Arc<Entity>) {
+    let count = DetectorOccupants::of(world).change(position, true);
+    let state = match get_state(world, position) {
+        Some(state) if state.parent.detector.is_some() => state,
+        _ => return,
+    };
+    if count == 1 {
+        set_property(world, position, state, true);
+    }
+    if let Err(error) = state
+        .parent
+        .static_data
+        .get_function("on_entity_enter")
+        .call_function(
+            &world.server.script_environment,
+            Some(
+                BlockLocation {
+                    position,
+                    world: world.clone(),
+                }
+                .into_variant(),
+            ),
+            vec![entity.clone().into_variant()],
+        )
+    {
+        logging::error(
+            LogTarget::Scripts,
+            format!("on_entity_enter of {} failed: {:?}", state.parent.id, error),
+        );
+    }
+}
+//the block may have been replaced since the entity entered, then only the count is updated
+pub fn on_leave(world: &Arc<World>, position: BlockPosition, entity: &Arc<Entity>) {
+    let count = DetectorOccupants::of(world).change(position, false);
+    let state = match get_state(world, position) {
+        Some(state) if state.parent.detector.is_some() => state,
+        _ => return,
+    };
+    if count == 0 {
+        set_property(world, position, state, false);
+    }
+    if let Err(error) = state
+        .parent
+        .static_data
+        .get_function("on_entity_leave")
+        .call_function(
+            &world.server.script_environment,
+            Some(
+                BlockLocation {
+                    position,
+                    world: world.clone(),
+                }
+                .into_variant(),
+            ),
+            vec![entity.clone().into_variant()],
+        )
+    {
+        logging::error(
+            LogTarget::Scripts,
+            format!("on_entity_leave of {} failed: {:?}", state.parent.id, error),
+        );
+    }
+}
+//entities of unloading chunks leave without callbacks, the chunks are locked so blocks are only changed on the next tick
+pub fn on_unload(world: &World, positions: HashSet<BlockPosition>) {
+    let occupants = DetectorOccupants::of(world);
+    for position in positions {
+        if occupants.change(position, false) == 0 {
+            occupants.0.lock().released.push(position);
+        }
+    }
+}
+pub fn tick(world: &Arc<World>) {
+    let occupants = DetectorOccupants::of(world);
+    let released: Vec<_> = {
+        let mut state = occupants.0.lock();
+        let released = std::mem::take(&mut state.released);
+        released
+            .into_iter()
+            .filter(|position| !state.counts.contains_key(position))
+            .collect()
+    };
+    for position in released {
+        if let Some(state) = get_state(world, position) {
+            set_property(world, position, state, false);
+        }
+    }
+}
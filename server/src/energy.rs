@@ -0,0 +1,230 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use block_byte_common::{BlockPosition, Face};
+use json::JsonValue;
+
+use crate::cable::{CableNetworks, Connection};
+use crate::world::{BlockData, BlockNetwork, World, WorldBlock};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnergySide {
+    None,
+    Input,
+    Output,
+    Both,
+}
+impl EnergySide {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "none" => EnergySide::None,
+            "input" => EnergySide::Input,
+            "output" => EnergySide::Output,
+            "both" => EnergySide::Both,
+            _ => return None,
+        })
+    }
+    pub fn can_input(&self) -> bool {
+        matches!(self, EnergySide::Input | EnergySide::Both)
+    }
+    pub fn can_output(&self) -> bool {
+        matches!(self, EnergySide::Output | EnergySide::Both)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EnergyStorage {
+    pub capacity: u32,
+    //limits of a single transfer, blocks with max_output push energy every tick while ticking
+    pub max_input: u32,
+    pub max_output: u32,
+    sides: HashMap<Face, EnergySide>,
+}
+impl EnergyStorage {
+    //faces missing from the configuration accept and provide energy
+    pub fn get_side(&self, face: Face) -> EnergySide {
+        self.sides.get(&face).cloned().unwrap_or(EnergySide::Both)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum EnergyType {
+    Cable,
+    Storage(EnergyStorage),
+}
+impl EnergyType {
+    pub fn from_json(json: &JsonValue) -> Result<Option<Self>, String> {
+        if json.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(
+            match json["type"].as_str().ok_or("missing energy type")? {
+                "cable" => EnergyType::Cable,
+                "storage" => {
+                    let capacity = json["capacity"]
+                        .as_u32()
+                        .ok_or("missing energy storage capacity")?;
+                    let mut sides = HashMap::new();
+                    for (face, side) in json["sides"].entries() {
+                        let side = side
+                            .as_str()
+                            .and_then(EnergySide::from_name)
+                            .ok_or_else(|| format!("unknown energy side {}", side))?;
+                        if face == "all" {
+                            for face in Face::all() {
+                                sides.entry(*face).or_insert(side);
+                            }
+                        } else {
+                            sides.insert(
+                                Face::all()
+                                    .iter()
+                                    .find(|candidate| candidate.name() == face)
+                                    .cloned()
+                                    .ok_or_else(|| format!("unknown face {}", face))?,
+                                side,
+                            );
+                        }
+                    }
+                    EnergyType::Storage(EnergyStorage {
+                        capacity,
+                        max_input: json["max_input"].as_u32().unwrap_or(capacity),
+                        max_output: json["max_output"].as_u32().unwrap_or(0),
+                        sides,
+                    })
+                }
+                energy_type => return Err(format!("unknown energy type {}", energy_type)),
+            },
+        ))
+    }
+    pub fn get_storage(&self) -> Option<&EnergyStorage> {
+        match self {
+            EnergyType::Storage(storage) => Some(storage),
+            EnergyType::Cable => None,
+        }
+    }
+}
+
+fn get_energy(world: &World, position: BlockPosition) -> Option<&EnergyType> {
+    let block = world.get_block(&position)?;
+    world
+        .server
+        .block_registry
+        .state_by_ref(block.get_block_state())
+        .parent
+        .energy
+        .as_ref()
+}
+fn get_storage_block(world: &World, position: BlockPosition) -> Option<Arc<WorldBlock>> {
+    match world.get_block(&position) {
+        Some(BlockData::Data(block)) if block.get_energy_storage().is_some() => Some(block),
+        _ => None,
+    }
+}
+
+//storages touching each network and the face of the storage the cable is on
+pub type EnergyNetworks = CableNetworks<(BlockPosition, Face)>;
+
+fn rebuild(world: &World, start: BlockPosition) {
+    world.energy_networks.rebuild(
+        world,
+        start,
+        |neighbor, face| match get_energy(world, neighbor) {
+            Some(EnergyType::Cable) => Connection::Cable,
+            Some(EnergyType::Storage(storage))
+                if storage.get_side(face.opposite()) != EnergySide::None =>
+            {
+                Connection::Endpoint((neighbor, face.opposite()))
+            }
+            _ => Connection::None,
+        },
+        |_, _| {},
+    );
+}
+
+pub fn tick(world: &World) {
+    for position in world.energy_networks.take_loaded() {
+        on_block_update(world, position);
+    }
+}
+
+pub fn on_block_update(world: &World, position: BlockPosition) {
+    let networks = &world.energy_networks;
+    if let Some(EnergyType::Cable) = get_energy(world, position) {
+        if !networks.was_rebuilt(world, position) {
+            rebuild(world, position);
+        }
+        return;
+    }
+    networks.remove(position);
+    //placed or removed storages change the endpoints of the cables around them
+    for face in Face::all() {
+        let neighbor = position.offset_by_face(*face);
+        if let Some(EnergyType::Cable) = get_energy(world, neighbor) {
+            if !networks.was_rebuilt(world, neighbor) {
+                rebuild(world, neighbor);
+            }
+        }
+    }
+}
+
+//offers up to max_output to adjacent storages and to every storage on adjacent cable networks, split evenly
+pub fn push_energy(world: &World, source: &WorldBlock) {
+    let storage = match source.get_energy_storage() {
+        Some(storage) if storage.max_output > 0 => storage,
+        _ => return,
+    };
+    let networks = &world.energy_networks;
+    let mut targets = Vec::new();
+    let mut visited: Vec<Arc<BlockNetwork>> = Vec::new();
+    let mut seen = HashSet::new();
+    seen.insert(source.position);
+    for face in Face::all() {
+        if !storage.get_side(*face).can_output() {
+            continue;
+        }
+        let neighbor = source.position.offset_by_face(*face);
+        match get_energy(world, neighbor) {
+            Some(EnergyType::Cable) => {
+                let network = match networks.get_network(neighbor) {
+                    Some(network) => network,
+                    None => continue,
+                };
+                if visited.iter().any(|other| Arc::ptr_eq(other, &network)) {
+                    continue;
+                }
+                visited.push(network);
+                for (position, side) in networks.get_endpoints(neighbor).iter() {
+                    if seen.insert(*position) {
+                        if let Some(target) = get_storage_block(world, *position) {
+                            targets.push((target, *side));
+                        }
+                    }
+                }
+            }
+            Some(EnergyType::Storage(_)) => {
+                if seen.insert(neighbor) {
+                    if let Some(target) = get_storage_block(world, neighbor) {
+                        targets.push((target, face.opposite()));
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+    if targets.is_empty() {
+        return;
+    }
+    //taken out before offering it, so concurrent changes to the source can't duplicate energy
+    let available = source.extract_energy(storage.max_output);
+    if available == 0 {
+        return;
+    }
+    let mut sent = 0;
+    let count = targets.len() as u32;
+    for (i, (target, side)) in targets.iter().enumerate() {
+        let remaining_targets = count - i as u32;
+        let share = (available - sent + remaining_targets - 1) / remaining_targets;
+        sent += target.insert_energy(Some(*side), share);
+    }
+    source.restore_energy(available - sent);
+}
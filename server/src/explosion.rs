@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+
+use bbscript::eval::ExecutionEnvironment;
+use bbscript::variant::{IntoVariant, Variant};
+use block_byte_common::messages::{NetworkMessageS2C, SoundCategory};
+use block_byte_common::{BlockPosition, Face, Position, Vec3};
+use fxhash::FxHashSet;
+use immutable_string::ImmutableString;
+use parking_lot::Mutex;
+use rand::{thread_rng, Rng};
+
+use crate::fire;
+use crate::gamerules::GameRules;
+use crate::mods::ScriptingObject;
+use crate::registry::BlockStateRef;
+use crate::util::Identifier;
+use crate::world::World;
+use crate::Server;
+
+const RAY_GRID: i32 = 16;
+const RAY_STEP: f64 = 0.3;
+const KNOCKBACK_STRENGTH: f64 = 1.5;
+
+//passed to on_destroy of the blocks an explosion removes, so they drop their items like mined blocks
+#[derive(Clone)]
+pub struct Explosion {
+    pub position: Position,
+    pub power: f64,
+    pub drops: bool,
+}
+impl ScriptingObject for Explosion {
+    fn engine_register_server(env: &mut ExecutionEnvironment, _server: &Weak<Server>) {
+        env.register_custom_name::<Explosion, _>("Explosion");
+        env.register_member("position", |explosion: &Explosion| {
+            Some(explosion.position.into_variant())
+        });
+        env.register_member("power", |explosion: &Explosion| Some(explosion.power));
+        env.register_member("drops", |explosion: &Explosion| Some(explosion.drops));
+    }
+}
+
+fn get_affected_blocks(world: &World, center: Position, power: f32) -> FxHashSet<BlockPosition> {
+    let mut rng = thread_rng();
+    let mut affected = FxHashSet::default();
+    for x in 0..RAY_GRID {
+        for y in 0..RAY_GRID {
+            for z in 0..RAY_GRID {
+                if x != 0
+                    && x != RAY_GRID - 1
+                    && y != 0
+                    && y != RAY_GRID - 1
+                    && z != 0
+                    && z != RAY_GRID - 1
+                {
+                    continue;
+                }
+                let direction = Position {
+                    x: x as f64 / (RAY_GRID - 1) as f64 * 2. - 1.,
+                    y: y as f64 / (RAY_GRID - 1) as f64 * 2. - 1.,
+                    z: z as f64 / (RAY_GRID - 1) as f64 * 2. - 1.,
+                };
+                let length = direction.distance(&Position {
+                    x: 0.,
+                    y: 0.,
+                    z: 0.,
+                });
+                let step = direction.multiply(RAY_STEP / length);
+                let mut intensity = power as f64 * rng.gen_range(0.7..1.3);
+                let mut position = center;
+                while intensity > 0. {
+                    let block_position = position.to_block_pos();
+                    if !world.is_inside_border(block_position) {
+                        break;
+                    }
+                    let resistance = match world.get_block(&block_position) {
+                        Some(block) if block.is_air() => 0.,
+                        Some(block) => {
+                            world
+                                .server
+                                .block_registry
+                                .state_by_ref(block.get_block_state())
+                                .parent
+                                .blast_resistance as f64
+                                + RAY_STEP
+                        }
+                        None => break,
+                    };
+                    intensity -= resistance * RAY_STEP;
+                    if intensity > 0. && resistance > 0. {
+                        affected.insert(block_position);
+                    }
+                    position = position.add(step.x, step.y, step.z);
+                    intensity -= RAY_STEP * 0.75;
+                }
+            }
+        }
+    }
+    affected
+}
+
+pub fn explode(world: &Arc<World>, center: Position, power: f32, fire: bool) {
+    let power = power.clamp(0., 16.);
+    let affected = get_affected_blocks(world, center, power);
+    let drop_chance = world
+        .get_gamerule_number(GameRules::EXPLOSION_DROP_CHANCE)
+        .clamp(0., 1.);
+    let mut rng = thread_rng();
+    for position in &affected {
+        if world.get_block(position).is_none() {
+            continue;
+        }
+        let explosion = Explosion {
+            position: center,
+            power: power as f64,
+            drops: rng.gen_bool(drop_chance),
+        };
+        world.set_block(
+            *position,
+            BlockStateRef::AIR,
+            true,
+            explosion.into_variant(),
+        );
+    }
+    if fire {
+        for position in &affected {
+            let below = position.offset_by_face(Face::Down);
+            if rng.gen_bool(1. / 3.)
+                && world
+                    .get_block(&below)
+                    .map_or(false, |block| !block.is_air())
+            {
+                fire::ignite(world, *position);
+            }
+        }
+    }
+    let radius = power as f64 * 2.;
+    if radius <= 0. {
+        return;
+    }
+    for entity in world.get_entities_in_radius(center, radius) {
+        if entity.is_spectator() {
+            continue;
+        }
+        let position = entity.get_location().position;
+        let distance = position.distance(&center);
+        let impact = 1. - distance / radius;
+        let direction = if distance > 0. {
+            Position {
+                x: position.x - center.x,
+                y: position.y - center.y,
+                z: position.z - center.z,
+            }
+            .multiply(impact * KNOCKBACK_STRENGTH / distance)
+        } else {
+            Position {
+                x: 0.,
+                y: impact * KNOCKBACK_STRENGTH,
+                z: 0.,
+            }
+        };
+        match entity.get_player() {
+            //client velocity is in blocks per second
+            Some(player) => player.send_message(&NetworkMessageS2C::Knockback(
+                direction.x as f32 * 20.,
+                direction.y as f32 * 20.,
+                direction.z as f32 * 20.,
+                false,
+            )),
+            None => entity.apply_knockback(direction.x, direction.y, direction.z),
+        }
+        entity.play_sound_event("hurt");
+        let mut event_data: HashMap<ImmutableString, Variant> = HashMap::new();
+        event_data.insert("entity".into(), entity.clone().into_variant());
+        event_data.insert(
+            "damage".into(),
+            ((impact * impact + impact) / 2. * 7. * radius + 1.).into_variant(),
+        );
+        event_data.insert("position".into(), center.into_variant());
+        world.server.call_event(
+            Identifier::new("bb", "entity_explosion"),
+            Arc::new(Mutex::new(event_data)).into_variant(),
+        );
+    }
+    if let Some(chunk) = world.get_chunk(center.to_chunk_pos()) {
+        chunk.announce_to_viewers(&NetworkMessageS2C::PlaySound(
+            "bb:explosion".to_string(),
+            center,
+            1.,
+            1.,
+            false,
+            SoundCategory::Block,
+        ));
+        chunk.announce_to_viewers(&NetworkMessageS2C::SpawnParticles(
+            "bb:explosion".to_string(),
+            center,
+            (power * 8.) as u32,
+            power / 2.,
+            Vec3::ZERO,
+        ));
+    }
+}
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use bbscript::variant::Variant;
+use block_byte_common::{BlockPosition, Face};
+use json::JsonValue;
+use rand::{thread_rng, Rng};
+
+use crate::gamerules::GameRules;
+use crate::registry::BlockStateRef;
+use crate::world::World;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Flammability {
+    pub catch_chance: f32,
+    pub burn_time: u32,
+}
+impl Flammability {
+    pub fn from_json(json: &JsonValue) -> Option<Self> {
+        if json.is_null() {
+            return None;
+        }
+        Some(Flammability {
+            catch_chance: json["catch_chance"].as_f32().unwrap_or(0.2).clamp(0., 1.),
+            burn_time: json["burn_time"].as_u32().unwrap_or(10).max(1),
+        })
+    }
+}
+
+fn get_flammability(world: &World, position: BlockPosition) -> Option<Flammability> {
+    let block = world.get_block(&position)?;
+    world
+        .server
+        .block_registry
+        .state_by_ref(block.get_block_state())
+        .parent
+        .flammability
+}
+pub fn is_fire(world: &World, position: BlockPosition) -> bool {
+    world.get_block(&position).map_or(false, |block| {
+        world
+            .server
+            .block_registry
+            .state_by_ref(block.get_block_state())
+            .parent
+            .fire
+    })
+}
+fn is_fluid(world: &World, position: BlockPosition) -> bool {
+    world.get_block(&position).map_or(false, |block| {
+        world
+            .server
+            .block_registry
+            .state_by_ref(block.get_block_state())
+            .client_data
+            .fluid
+    })
+}
+
+pub fn ignite(world: &Arc<World>, position: BlockPosition) -> bool {
+    let fire = match world.server.block_registry.get_fire_block() {
+        Some(fire) => fire,
+        None => return false,
+    };
+    if !world.is_inside_border(position)
+        || !world
+            .get_block(&position)
+            .map_or(false, |block| block.is_air())
+        || Face::all()
+            .iter()
+            .any(|face| is_fluid(world, position.offset_by_face(*face)))
+    {
+        return false;
+    }
+    world.set_block(position, fire, true, Variant::NULL());
+    true
+}
+pub fn extinguish(world: &Arc<World>, position: BlockPosition) -> bool {
+    if !is_fire(world, position) {
+        return false;
+    }
+    world.set_block(position, BlockStateRef::AIR, true, Variant::NULL());
+    true
+}
+pub fn random_tick(world: &Arc<World>, position: BlockPosition) {
+    let mut rng = thread_rng();
+    let neighbors: Vec<_> = Face::all()
+        .iter()
+        .map(|face| position.offset_by_face(*face))
+        .collect();
+    if neighbors.iter().any(|neighbor| is_fluid(world, *neighbor)) {
+        extinguish(world, position);
+        return;
+    }
+    let spread = world.get_gamerule_bool(GameRules::FIRE_SPREAD);
+    let mut has_fuel = false;
+    for neighbor in &neighbors {
+        if let Some(flammability) = get_flammability(world, *neighbor) {
+            has_fuel = true;
+            if spread && rng.gen_bool(1. / flammability.burn_time as f64) {
+                world.set_block(*neighbor, BlockStateRef::AIR, true, Variant::NULL());
+                ignite(world, *neighbor);
+            }
+        }
+    }
+    if !has_fuel {
+        if rng.gen_bool(0.5) {
+            extinguish(world, position);
+        }
+        return;
+    }
+    if !spread {
+        return;
+    }
+    for x in -1..=1 {
+        for y in -1..=1 {
+            for z in -1..=1 {
+                let target = position.add(x, y, z);
+                if target == position {
+                    continue;
+                }
+                let catch_chance = Face::all()
+                    .iter()
+                    .filter_map(|face| get_flammability(world, target.offset_by_face(*face)))
+                    .map(|flammability| flammability.catch_chance)
+                    .fold(0f32, f32::max);
+                if catch_chance > 0. && rng.gen_bool(catch_chance as f64) {
+                    ignite(world, target);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+
+use bbscript::eval::{ExecutionEnvironment, ScriptError};
+use bbscript::lex::FilePosition;
+use bbscript::variant::Variant;
+use immutable_string::ImmutableString;
+use json::JsonValue;
+use parking_lot::Mutex;
+
+use crate::mods::ScriptingObject;
+use crate::util::Identifier;
+use crate::Server;
+
+pub struct Fluid {
+    pub id: Identifier,
+    pub name: String,
+    pub texture: String,
+}
+impl Fluid {
+    pub fn from_json(id: Identifier, json: &JsonValue) -> Self {
+        Fluid {
+            name: json["name"]
+                .as_str()
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| id.to_string()),
+            texture: json["texture"].as_str().unwrap_or("").to_string(),
+            id,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct FluidStack {
+    pub fluid: Arc<Fluid>,
+    pub amount: u32,
+}
+impl FluidStack {
+    pub fn new(fluid: &Arc<Fluid>, amount: u32) -> Self {
+        FluidStack {
+            fluid: fluid.clone(),
+            amount,
+        }
+    }
+    pub fn is_same_fluid(&self, other: &FluidStack) -> bool {
+        Arc::ptr_eq(&self.fluid, &other.fluid)
+    }
+    pub fn copy(&self, amount: u32) -> Self {
+        FluidStack::new(&self.fluid, amount)
+    }
+}
+impl ScriptingObject for FluidStack {
+    fn engine_register_server(env: &mut ExecutionEnvironment, server: &Weak<Server>) {
+        env.register_custom_name::<FluidStack, _>("FluidStack");
+        {
+            let server = server.clone();
+            env.register_function(
+                "FluidStack",
+                move |fluid: &ImmutableString, amount: &i64| {
+                    let server = server.upgrade().unwrap();
+                    let id = Identifier::parse(fluid.as_ref()).map_err(|_| {
+                        ScriptError::runtime(
+                            format!("invalid fluid id {}", fluid).as_str(),
+                            FilePosition::INVALID,
+                        )
+                    })?;
+                    let fluid = server.fluids.get(&id).ok_or_else(|| {
+                        ScriptError::runtime(
+                            format!("unknown fluid {}", id).as_str(),
+                            FilePosition::INVALID,
+                        )
+                    })?;
+                    Ok(FluidStack::new(fluid, (*amount).max(0) as u32))
+                },
+            );
+        }
+        env.register_member("id", |stack: &FluidStack| {
+            Some(Variant::from_str(stack.fluid.id.to_string().as_str()))
+        });
+        env.register_member("name", |stack: &FluidStack| {
+            Some(Variant::from_str(stack.fluid.name.as_str()))
+        });
+        env.register_member("amount", |stack: &FluidStack| Some(stack.amount as i64));
+        env.register_method("with_amount", |stack: &FluidStack, amount: &i64| {
+            Ok(stack.copy((*amount).max(0) as u32))
+        });
+    }
+}
+
+//every tank holds a single fluid at a time and is empty again once it is drained
+pub struct FluidTanks {
+    capacities: Box<[u32]>,
+    tanks: Mutex<Box<[Option<FluidStack>]>>,
+}
+impl FluidTanks {
+    pub fn new(capacities: &[u32]) -> Self {
+        FluidTanks {
+            capacities: capacities.into(),
+            tanks: Mutex::new(vec![None; capacities.len()].into_boxed_slice()),
+        }
+    }
+    pub fn get_count(&self) -> u32 {
+        self.capacities.len() as u32
+    }
+    pub fn get_capacity(&self, tank: u32) -> Result<u32, ()> {
+        self.capacities.get(tank as usize).cloned().ok_or(())
+    }
+    //all tanks together, from 0 for empty to 1 for full
+    pub fn get_fill(&self) -> f64 {
+        let capacity: u32 = self.capacities.iter().sum();
+        let stored: u32 = self
+            .tanks
+            .lock()
+            .iter()
+            .map(|tank| tank.as_ref().map_or(0, |stack| stack.amount))
+            .sum();
+        stored as f64 / capacity.max(1) as f64
+    }
+    pub fn get_fluid(&self, tank: u32) -> Result<Option<FluidStack>, ()> {
+        self.tanks.lock().get(tank as usize).cloned().ok_or(())
+    }
+    //tanks already holding the fluid are filled before empty ones, returns the inserted amount
+    pub fn insert(&self, fluid: &FluidStack) -> u32 {
+        let mut tanks = self.tanks.lock();
+        let mut remaining = fluid.amount;
+        for same_fluid in [true, false] {
+            for (tank, capacity) in tanks.iter_mut().zip(self.capacities.iter()) {
+                if remaining == 0 {
+                    break;
+                }
+                let stored = match tank {
+                    Some(stored) if same_fluid && stored.is_same_fluid(fluid) => stored.amount,
+                    None if !same_fluid => 0,
+                    _ => continue,
+                };
+                let added = remaining.min(capacity.saturating_sub(stored));
+                if added > 0 {
+                    *tank = Some(fluid.copy(stored + added));
+                    remaining -= added;
+                }
+            }
+        }
+        fluid.amount - remaining
+    }
+    pub fn extract(&self, tank: u32, amount: u32) -> Result<Option<FluidStack>, ()> {
+        let mut tanks = self.tanks.lock();
+        let tank = tanks.get_mut(tank as usize).ok_or(())?;
+        let stored = match tank {
+            Some(stored) => stored,
+            None => return Ok(None),
+        };
+        let extracted = stored.copy(amount.min(stored.amount));
+        stored.amount -= extracted.amount;
+        if stored.amount == 0 {
+            *tank = None;
+        }
+        Ok(Some(extracted).filter(|extracted| extracted.amount > 0))
+    }
+    //moves from the first tanks onwards until amount is reached or nothing fits anymore
+    pub fn move_to(&self, target: &FluidTanks, amount: u32) -> u32 {
+        let mut moved = 0;
+        for tank in 0..self.get_count() {
+            if moved >= amount {
+                break;
+            }
+            let offered = match self.get_fluid(tank).unwrap() {
+                Some(fluid) => fluid.copy(fluid.amount.min(amount - moved)),
+                None => continue,
+            };
+            let inserted = target.insert(&offered);
+            if inserted > 0 {
+                self.extract(tank, inserted).unwrap();
+                moved += inserted;
+            }
+        }
+        moved
+    }
+    pub fn export_content(&self) -> Vec<Option<(Identifier, u32)>> {
+        self.tanks
+            .lock()
+            .iter()
+            .map(|tank| {
+                tank.as_ref()
+                    .map(|stack| (stack.fluid.id.clone(), stack.amount))
+            })
+            .collect()
+    }
+    //fluids that no longer exist are dropped
+    pub fn load_content(
+        &self,
+        content: Vec<Option<(Identifier, u32)>>,
+        fluids: &HashMap<Identifier, Arc<Fluid>>,
+    ) {
+        let mut tanks = self.tanks.lock();
+        for ((tank, saved), capacity) in tanks.iter_mut().zip(content).zip(self.capacities.iter()) {
+            *tank = saved.and_then(|(id, amount)| {
+                fluids
+                    .get(&id)
+                    .map(|fluid| FluidStack::new(fluid, amount.min(*capacity)))
+            });
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use bbscript::variant::{FromVariant, IntoVariant, Variant};
+use parking_lot::Mutex;
+
+use crate::ServerSettings;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameRuleValue {
+    Bool(bool),
+    Number(f64),
+}
+impl GameRuleValue {
+    pub fn parse_like(&self, text: &str) -> Option<Self> {
+        match self {
+            GameRuleValue::Bool(_) => text.parse().ok().map(GameRuleValue::Bool),
+            GameRuleValue::Number(_) => text.parse().ok().map(GameRuleValue::Number),
+        }
+    }
+    pub fn from_variant(variant: &Variant) -> Option<Self> {
+        if let Some(value) = bool::from_variant(variant) {
+            Some(GameRuleValue::Bool(*value))
+        } else if let Some(value) = f64::from_variant(variant) {
+            Some(GameRuleValue::Number(*value))
+        } else {
+            i64::from_variant(variant).map(|value| GameRuleValue::Number(*value as f64))
+        }
+    }
+    pub fn to_variant(&self) -> Variant {
+        match self {
+            GameRuleValue::Bool(value) => (*value).into_variant(),
+            GameRuleValue::Number(value) => (*value).into_variant(),
+        }
+    }
+}
+impl Display for GameRuleValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameRuleValue::Bool(value) => write!(f, "{}", value),
+            GameRuleValue::Number(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+pub struct GameRules {
+    defaults: Mutex<BTreeMap<String, GameRuleValue>>,
+}
+impl GameRules {
+    pub const FIRE_SPREAD: &'static str = "fire_spread";
+    pub const DAYLIGHT_CYCLE: &'static str = "daylight_cycle";
+    pub const RANDOM_TICK_SPEED: &'static str = "random_tick_speed";
+    pub const EXPLOSION_DROP_CHANCE: &'static str = "explosion_drop_chance";
+    pub fn new() -> Self {
+        let mut defaults = BTreeMap::new();
+        defaults.insert(Self::FIRE_SPREAD.to_string(), GameRuleValue::Bool(true));
+        defaults.insert(Self::DAYLIGHT_CYCLE.to_string(), GameRuleValue::Bool(true));
+        defaults.insert(
+            Self::RANDOM_TICK_SPEED.to_string(),
+            GameRuleValue::Number(3.),
+        );
+        defaults.insert(
+            Self::EXPLOSION_DROP_CHANCE.to_string(),
+            GameRuleValue::Number(0.3),
+        );
+        GameRules {
+            defaults: Mutex::new(defaults),
+        }
+    }
+    pub fn register(&self, name: String, default: GameRuleValue) {
+        self.defaults.lock().entry(name).or_insert(default);
+    }
+    pub fn list(&self) -> Vec<String> {
+        self.defaults.lock().keys().cloned().collect()
+    }
+    pub fn get(&self, settings: &ServerSettings, name: &str) -> Option<GameRuleValue> {
+        let default = *self.defaults.lock().get(name)?;
+        Some(
+            default
+                .parse_like(
+                    settings
+                        .get(
+                            format!("gamerule.{}", name).as_str(),
+                            default.to_string().as_str(),
+                        )
+                        .as_str(),
+                )
+                .unwrap_or(default),
+        )
+    }
+    pub fn get_bool(&self, settings: &ServerSettings, name: &str) -> bool {
+        match self.get(settings, name) {
+            Some(GameRuleValue::Bool(value)) => value,
+            Some(GameRuleValue::Number(value)) => value != 0.,
+            None => false,
+        }
+    }
+    pub fn get_number(&self, settings: &ServerSettings, name: &str) -> f64 {
+        match self.get(settings, name) {
+            Some(GameRuleValue::Number(value)) => value,
+            Some(GameRuleValue::Bool(value)) => value as u8 as f64,
+            None => 0.,
+        }
+    }
+    pub fn set(
+        &self,
+        settings: &ServerSettings,
+        name: &str,
+        value: GameRuleValue,
+    ) -> Result<(), String> {
+        let default = *self
+            .defaults
+            .lock()
+            .get(name)
+            .ok_or_else(|| format!("unknown gamerule {}", name))?;
+        if std::mem::discriminant(&default) != std::mem::discriminant(&value) {
+            return Err(format!("wrong value type for gamerule {}", name));
+        }
+        settings.set(format!("gamerule.{}", name).as_str(), value.to_string());
+        Ok(())
+    }
+    pub fn run_command(&self, settings: &ServerSettings, args: &[&str]) -> Result<String, String> {
+        match args {
+            [] => Ok(self.list().join(", ")),
+            [name] => self
+                .get(settings, name)
+                .map(|value| format!("{} = {}", name, value))
+                .ok_or_else(|| format!("unknown gamerule {}", name)),
+            [name, value] => {
+                let parsed = self
+                    .get(settings, name)
+                    .ok_or_else(|| format!("unknown gamerule {}", name))?
+                    .parse_like(value)
+                    .ok_or_else(|| format!("invalid value {}", value))?;
+                self.set(settings, name, parsed)?;
+                Ok(format!("{} set to {}", name, value))
+            }
+            _ => Err("usage: /gamerule [name] [value]".to_string()),
+        }
+    }
+}
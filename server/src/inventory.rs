@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
 use std::{
     ops::Range,
@@ -7,11 +8,13 @@ use std::{
 
 use bbscript::eval::{ExecutionEnvironment, ScriptError};
 use bbscript::lex::FilePosition;
-use bbscript::variant::{FromVariant, FunctionVariant, IntoVariant, Variant};
+use bbscript::variant::{Array, FromVariant, FunctionVariant, IntoVariant, Variant};
 use block_byte_common::gui::{
     GUIComponent, GUIComponentEdit, GUIElement, GUIElementEdit, PositionAnchor,
 };
-use block_byte_common::messages::{ClientModelTarget, MouseButton, NetworkMessageS2C};
+use block_byte_common::messages::{
+    Capability, ClientModelTarget, HotbarItem, MouseButton, NetworkMessageS2C,
+};
 use block_byte_common::{Color, Position, Vec2};
 use fxhash::FxHashMap;
 use immutable_string::ImmutableString;
@@ -21,7 +24,13 @@ use rand::{thread_rng, Rng};
 use serde::{Deserialize, Deserializer, Serialize};
 use uuid::Uuid;
 
-use crate::mods::{ScriptCallback, ScriptingObject, UserDataWrapper};
+use crate::crafting::{CraftingGrid, Ingredient, RecipeInput, RecipeShape};
+use crate::creative::CreativeCatalog;
+use crate::fluid::{Fluid, FluidStack, FluidTanks};
+use crate::logging::{self, LogTarget};
+use crate::mods::{IdentifierTag, ScriptCallback, ScriptingObject, UserDataWrapper};
+use crate::overlay::ElementBinding;
+use crate::sensor;
 use crate::world::{PlayerData, UserData};
 use crate::worldgen::Spline;
 use crate::{
@@ -32,28 +41,154 @@ use crate::{
     Server,
 };
 
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum ItemDataValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+impl ItemDataValue {
+    pub fn from_variant(variant: &Variant) -> Option<Self> {
+        if let Some(value) = bool::from_variant(variant) {
+            Some(ItemDataValue::Bool(*value))
+        } else if let Some(value) = i64::from_variant(variant) {
+            Some(ItemDataValue::Int(*value))
+        } else if let Some(value) = f64::from_variant(variant) {
+            Some(ItemDataValue::Float(*value))
+        } else {
+            ImmutableString::from_variant(variant)
+                .map(|value| ItemDataValue::Text(value.to_string()))
+        }
+    }
+    pub fn to_variant(&self) -> Variant {
+        match self {
+            ItemDataValue::Bool(value) => (*value).into_variant(),
+            ItemDataValue::Int(value) => (*value).into_variant(),
+            ItemDataValue::Float(value) => (*value).into_variant(),
+            ItemDataValue::Text(value) => Variant::from_str(value.as_str()),
+        }
+    }
+}
+impl Display for ItemDataValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ItemDataValue::Bool(value) => write!(f, "{}", value),
+            ItemDataValue::Int(value) => write!(f, "{}", value),
+            ItemDataValue::Float(value) => write!(f, "{}", value),
+            ItemDataValue::Text(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ItemData {
+    entries: BTreeMap<String, (ItemDataValue, bool)>,
+}
+impl ItemData {
+    pub fn get(&self, key: &Identifier) -> Option<&ItemDataValue> {
+        self.entries.get(&key.to_string()).map(|entry| &entry.0)
+    }
+    pub fn set(&mut self, key: &Identifier, value: ItemDataValue, client_visible: bool) {
+        self.entries
+            .insert(key.to_string(), (value, client_visible));
+    }
+    pub fn remove(&mut self, key: &Identifier) {
+        self.entries.remove(&key.to_string());
+    }
+    pub fn get_tooltip(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|(_, (_, client_visible))| *client_visible)
+            .map(|(key, (value, _))| format!("{}: {}", key, value))
+            .collect()
+    }
+    pub fn from_json(json: &JsonValue) -> Self {
+        let mut data = ItemData::default();
+        for (key, entry) in json.entries() {
+            let value = &entry["value"];
+            let value = if let Some(value) = value.as_bool() {
+                ItemDataValue::Bool(value)
+            } else if let Some(value) = value.as_i64() {
+                ItemDataValue::Int(value)
+            } else if let Some(value) = value.as_f64() {
+                ItemDataValue::Float(value)
+            } else if let Some(value) = value.as_str() {
+                ItemDataValue::Text(value.to_string())
+            } else {
+                continue;
+            };
+            data.entries.insert(
+                key.to_string(),
+                (value, entry["visible"].as_bool().unwrap_or(false)),
+            );
+        }
+        data
+    }
+    pub fn to_json(&self) -> JsonValue {
+        let mut json = JsonValue::new_object();
+        for (key, (value, client_visible)) in &self.entries {
+            let value = match value {
+                ItemDataValue::Bool(value) => JsonValue::from(*value),
+                ItemDataValue::Int(value) => JsonValue::from(*value),
+                ItemDataValue::Float(value) => JsonValue::from(*value),
+                ItemDataValue::Text(value) => JsonValue::from(value.as_str()),
+            };
+            json.insert(
+                key.as_str(),
+                object! {
+                    value: value,
+                    visible: *client_visible,
+                },
+            )
+            .unwrap();
+        }
+        json
+    }
+}
+
 #[derive(Clone)]
 pub struct ItemStack {
     pub item_type: Arc<Item>,
     item_count: u32,
+    pub data: ItemData,
 }
 impl ItemStack {
     pub fn new(item_type: &Arc<Item>, item_count: u32) -> Self {
         ItemStack {
             item_type: item_type.clone(),
             item_count: item_count.min(item_type.stack_size),
+            data: ItemData::default(),
         }
     }
+    pub fn is_stackable_with(&self, other: &ItemStack) -> bool {
+        Arc::ptr_eq(&self.item_type, &other.item_type) && self.data == other.data
+    }
     pub fn from_json(json: &JsonValue, item_registry: &ItemRegistry) -> Result<Self, ()> {
         item_registry
             .item_by_identifier(&Identifier::parse(json["id"].as_str().unwrap()).unwrap())
-            .map(|item| Self::new(item, json["count"].as_u32().unwrap_or(1)))
+            .map(|item| {
+                let mut stack = Self::new(item, json["count"].as_u32().unwrap_or(1));
+                stack.data = ItemData::from_json(&json["data"]);
+                stack
+            })
             .ok_or(())
     }
+    pub fn to_json(&self) -> JsonValue {
+        let mut json = object! {
+            id: self.item_type.id.to_string(),
+            count: self.item_count,
+        };
+        if !self.data.entries.is_empty() {
+            json.insert("data", self.data.to_json()).unwrap();
+        }
+        json
+    }
     pub fn copy(&self, new_count: u32) -> Self {
         ItemStack {
             item_type: self.item_type.clone(),
             item_count: new_count,
+            data: self.data.clone(),
         }
     }
     pub fn get_type(&self) -> &Arc<Item> {
@@ -70,6 +205,61 @@ impl ItemStack {
     pub fn get_count(&self) -> u32 {
         self.item_count
     }
+    pub fn get_tooltip(&self) -> Vec<String> {
+        let mut tooltip = vec![self.item_type.client_data.name.clone()];
+        if let Some(lore) = self
+            .item_type
+            .static_data
+            .get("lore")
+            .and_then(|lore| Array::from_variant(lore))
+        {
+            tooltip.extend(
+                lore.iter()
+                    .filter_map(|line| ImmutableString::from_variant(line))
+                    .map(|line| line.to_string()),
+            );
+        }
+        tooltip.extend(self.data.get_tooltip());
+        tooltip
+    }
+    //damage is kept in the bb:damage item data, so scripts wear items down like any other data
+    pub fn get_durability(&self) -> Option<f32> {
+        let max_durability = self.item_type.durability? as f32;
+        let damage = match self.data.get(&Identifier::new("bb", "damage")) {
+            Some(ItemDataValue::Int(damage)) => *damage as f32,
+            Some(ItemDataValue::Float(damage)) => *damage as f32,
+            _ => 0.,
+        };
+        Some(((max_durability - damage) / max_durability).clamp(0., 1.))
+    }
+    pub fn to_hotbar_item(&self) -> HotbarItem {
+        HotbarItem {
+            item: self.item_type.client_id,
+            count: self.item_count,
+            durability: self.get_durability(),
+        }
+    }
+    fn with_data(
+        item: &ItemStack,
+        key: &ImmutableString,
+        value: &Variant,
+        client_visible: bool,
+    ) -> Result<ItemStack, ScriptError> {
+        let value = ItemDataValue::from_variant(value).ok_or_else(|| {
+            ScriptError::runtime(
+                "item data must be a bool, number or string",
+                FilePosition::INVALID,
+            )
+        })?;
+        let mut item = item.clone();
+        item.data
+            .set(&Self::parse_data_key(key)?, value, client_visible);
+        Ok(item)
+    }
+    fn parse_data_key(key: &ImmutableString) -> Result<Identifier, ScriptError> {
+        Identifier::parse(key.as_ref())
+            .map_err(|_| ScriptError::runtime("invalid data key", FilePosition::INVALID))
+    }
 }
 impl ScriptingObject for ItemStack {
     fn engine_register_server(env: &mut ExecutionEnvironment, server: &Weak<Server>) {
@@ -94,13 +284,84 @@ impl ScriptingObject for ItemStack {
         env.register_member("stack_size", |item: &ItemStack| {
             Some(item.item_type.stack_size as i64)
         });
+        env.register_member("durability", |item: &ItemStack| {
+            Some(Variant::from_option(
+                item.get_durability().map(|durability| durability as f64),
+            ))
+        });
         env.register_method("with_count", |item: &ItemStack, new_count: &i64| {
-            Ok(ItemStack::new(item.get_type(), *new_count as u32))
+            Ok(item.copy((*new_count as u32).min(item.item_type.stack_size)))
+        });
+        env.register_method("get_data", |item: &ItemStack, key: &ImmutableString| {
+            Ok(Variant::from_option(
+                item.data
+                    .get(&ItemStack::parse_data_key(key)?)
+                    .map(|value| value.to_variant()),
+            ))
+        });
+        env.register_method(
+            "with_data",
+            |item: &ItemStack, key: &ImmutableString, value: &Variant| {
+                ItemStack::with_data(item, key, value, false)
+            },
+        );
+        env.register_method(
+            "with_client_data",
+            |item: &ItemStack, key: &ImmutableString, value: &Variant| {
+                ItemStack::with_data(item, key, value, true)
+            },
+        );
+        env.register_method("without_data", |item: &ItemStack, key: &ImmutableString| {
+            let mut item = item.clone();
+            item.data.remove(&ItemStack::parse_data_key(key)?);
+            Ok(item)
         });
     }
 }
 pub type InventorySetItemHandler = Box<dyn Fn(&Inventory, u32) + Send + Sync>;
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum SlotKind {
+    Storage,
+    Input,
+    Output,
+    Fuel,
+}
+impl SlotKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "storage" => Some(SlotKind::Storage),
+            "input" => Some(SlotKind::Input),
+            "output" => Some(SlotKind::Output),
+            "fuel" => Some(SlotKind::Fuel),
+            _ => None,
+        }
+    }
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            SlotKind::Storage => "storage",
+            SlotKind::Input => "input",
+            SlotKind::Output => "output",
+            SlotKind::Fuel => "fuel",
+        }
+    }
+}
+//rules only limit players and the transfer helpers, set_item and add_item ignore them so machines can fill their outputs
+#[derive(Clone)]
+pub struct SlotRule {
+    pub kind: SlotKind,
+    pub filter: Option<Arc<IdentifierTag>>,
+}
+impl SlotRule {
+    pub fn accepts(&self, item: &ItemStack) -> bool {
+        self.kind != SlotKind::Output
+            && self
+                .filter
+                .as_ref()
+                .map_or(true, |filter| filter.contains(&item.get_type().id))
+    }
+}
+
 pub struct Inventory {
     owner: WeakInventoryWrapper,
     items: Mutex<Box<[Option<ItemStack>]>>,
@@ -108,8 +369,29 @@ pub struct Inventory {
     pub user_data: Mutex<UserData>,
     set_item_handler: Option<InventorySetItemHandler>,
     client_properties: Mutex<HashMap<Identifier, Variant>>,
+    slot_rules: Mutex<FxHashMap<u32, SlotRule>>,
+    crafting: Option<CraftingGrid>,
+    catalog: Option<CreativeCatalog>,
+    fluid_tanks: FluidTanks,
+    transfer_lock: Mutex<()>,
 }
 impl Inventory {
+    //held while items move between two inventories, taken in address order so transfers can't deadlock
+    fn lock_transfers<'a>(
+        first: &'a Inventory,
+        second: &'a Inventory,
+    ) -> (MutexGuard<'a, ()>, Option<MutexGuard<'a, ()>>) {
+        if std::ptr::eq(first, second) {
+            return (first.transfer_lock.lock(), None);
+        }
+        let (first, second) = if (first as *const Inventory) < (second as *const Inventory) {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        let first = first.transfer_lock.lock();
+        (first, Some(second.transfer_lock.lock()))
+    }
     pub fn new_owned(size: u32, set_item_handler: Option<InventorySetItemHandler>) -> Arc<Self> {
         let inventory = Arc::new_cyclic(|this| Inventory {
             items: Mutex::new(vec![None; size as usize].into_boxed_slice()),
@@ -118,9 +400,59 @@ impl Inventory {
             set_item_handler,
             owner: WeakInventoryWrapper::Own(this.clone()),
             client_properties: Mutex::new(HashMap::new()),
+            slot_rules: Mutex::new(FxHashMap::default()),
+            crafting: None,
+            catalog: None,
+            fluid_tanks: FluidTanks::new(&[]),
+            transfer_lock: Mutex::new(()),
         });
         inventory
     }
+    pub fn new_crafting(crafting: CraftingGrid) -> Arc<Self> {
+        let size = crafting.get_output_slot() + 1;
+        Arc::new_cyclic(|this| Inventory {
+            items: Mutex::new(vec![None; size as usize].into_boxed_slice()),
+            viewers: Mutex::new(FxHashMap::default()),
+            user_data: Mutex::new(UserData::new()),
+            set_item_handler: None,
+            owner: WeakInventoryWrapper::Own(this.clone()),
+            client_properties: Mutex::new(HashMap::new()),
+            slot_rules: Mutex::new(FxHashMap::default()),
+            crafting: Some(crafting),
+            catalog: None,
+            fluid_tanks: FluidTanks::new(&[]),
+            transfer_lock: Mutex::new(()),
+        })
+    }
+    //all slots are output slots, so nothing can be moved into the catalog
+    pub fn new_catalog(catalog: CreativeCatalog) -> Arc<Self> {
+        let size = catalog.get_size();
+        Arc::new_cyclic(|this| Inventory {
+            items: Mutex::new(vec![None; size as usize].into_boxed_slice()),
+            viewers: Mutex::new(FxHashMap::default()),
+            user_data: Mutex::new(UserData::new()),
+            set_item_handler: None,
+            owner: WeakInventoryWrapper::Own(this.clone()),
+            client_properties: Mutex::new(HashMap::new()),
+            slot_rules: Mutex::new(
+                (0..size)
+                    .map(|slot| {
+                        (
+                            slot,
+                            SlotRule {
+                                kind: SlotKind::Output,
+                                filter: None,
+                            },
+                        )
+                    })
+                    .collect(),
+            ),
+            crafting: None,
+            catalog: Some(catalog),
+            fluid_tanks: FluidTanks::new(&[]),
+            transfer_lock: Mutex::new(()),
+        })
+    }
     pub fn new<T>(owner: T, size: u32, set_item_handler: Option<InventorySetItemHandler>) -> Self
     where
         T: Into<WeakInventoryWrapper>,
@@ -132,6 +464,171 @@ impl Inventory {
             set_item_handler,
             owner: owner.into(),
             client_properties: Mutex::new(HashMap::new()),
+            slot_rules: Mutex::new(FxHashMap::default()),
+            crafting: None,
+            catalog: None,
+            fluid_tanks: FluidTanks::new(&[]),
+            transfer_lock: Mutex::new(()),
+        }
+    }
+    pub fn with_fluid_tanks(mut self, capacities: &[u32]) -> Self {
+        self.fluid_tanks = FluidTanks::new(capacities);
+        self
+    }
+    pub fn get_crafting(&self) -> Option<&CraftingGrid> {
+        self.crafting.as_ref()
+    }
+    pub fn get_catalog(&self) -> Option<&CreativeCatalog> {
+        self.catalog.as_ref()
+    }
+    pub fn get_fluid_tanks(&self) -> &FluidTanks {
+        &self.fluid_tanks
+    }
+    pub fn insert_fluid(&self, fluid: &FluidStack) -> u32 {
+        let inserted = self.fluid_tanks.insert(fluid);
+        if inserted > 0 {
+            self.sync_tanks();
+        }
+        inserted
+    }
+    pub fn extract_fluid(&self, tank: u32, amount: u32) -> Result<Option<FluidStack>, ()> {
+        let extracted = self.fluid_tanks.extract(tank, amount)?;
+        if extracted.is_some() {
+            self.sync_tanks();
+        }
+        Ok(extracted)
+    }
+    pub fn move_fluid(&self, target: &Inventory, amount: u32) -> u32 {
+        let moved = self.fluid_tanks.move_to(&target.fluid_tanks, amount);
+        if moved > 0 {
+            self.sync_tanks();
+            target.sync_tanks();
+        }
+        moved
+    }
+    pub fn load_fluids(
+        &self,
+        content: Vec<Option<(Identifier, u32)>>,
+        fluids: &HashMap<Identifier, Arc<Fluid>>,
+    ) {
+        self.fluid_tanks.load_content(content, fluids);
+        self.sync_tanks();
+    }
+    //tank n is shown by the gauge element tank_n of the layout, if it has one
+    fn get_tank_edit(&self, tank: u32) -> GUIElementEdit {
+        let capacity = self.fluid_tanks.get_capacity(tank).unwrap();
+        let fluid = self.fluid_tanks.get_fluid(tank).unwrap();
+        GUIElementEdit {
+            component_type: GUIComponentEdit::GaugeComponent {
+                background: None,
+                texture: Some(
+                    fluid
+                        .as_ref()
+                        .map(|fluid| fluid.fluid.texture.clone())
+                        .unwrap_or_default(),
+                ),
+                size: None,
+                fill: Some(
+                    fluid
+                        .as_ref()
+                        .map(|fluid| fluid.amount as f32 / capacity.max(1) as f32)
+                        .unwrap_or(0.),
+                ),
+                tooltip: Some(match &fluid {
+                    Some(fluid) => vec![
+                        fluid.fluid.name.clone(),
+                        format!("{} / {}", fluid.amount, capacity),
+                    ],
+                    None => vec![format!("0 / {}", capacity)],
+                }),
+            },
+            ..Default::default()
+        }
+    }
+    fn send_tanks(&self, viewer: &GuiInventoryViewer) {
+        for tank in 0..self.fluid_tanks.get_count() {
+            let element = format!("tank_{}", tank);
+            if viewer.layout.elements.contains_key(&element) {
+                viewer
+                    .viewer
+                    .send_message(&NetworkMessageS2C::GuiEditElement(
+                        format!("{}_{}", viewer.id.to_string().as_str(), element),
+                        self.get_tank_edit(tank),
+                    ));
+            }
+        }
+    }
+    fn sync_tanks(&self) {
+        for viewer in self.viewers.lock().values() {
+            self.send_tanks(viewer);
+        }
+        self.notify_sensors();
+    }
+    //the gauge element energy shows the energy stored in the block owning the inventory
+    fn send_energy(&self, viewer: &GuiInventoryViewer) {
+        if !viewer.layout.elements.contains_key("energy") {
+            return;
+        }
+        let block = match self.owner.upgrade() {
+            Some(InventoryWrapper::Block(block)) => block,
+            _ => return,
+        };
+        let capacity = match block.get_energy_storage() {
+            Some(storage) => storage.capacity,
+            None => return,
+        };
+        let energy = block.get_energy();
+        viewer
+            .viewer
+            .send_message(&NetworkMessageS2C::GuiEditElement(
+                format!("{}_energy", viewer.id.to_string().as_str()),
+                GUIElementEdit {
+                    component_type: GUIComponentEdit::GaugeComponent {
+                        background: None,
+                        texture: None,
+                        size: None,
+                        fill: Some(energy as f32 / capacity.max(1) as f32),
+                        tooltip: Some(vec![format!("{} / {}", energy, capacity)]),
+                    },
+                    ..Default::default()
+                },
+            ));
+    }
+    pub fn sync_energy(&self) {
+        for viewer in self.viewers.lock().values() {
+            self.send_energy(viewer);
+        }
+        self.notify_sensors();
+    }
+    //sensors next to a block inventory re-check it on the next world tick
+    fn notify_sensors(&self) {
+        if let WeakInventoryWrapper::Block(block) = &self.owner {
+            if let Some(block) = block.upgrade() {
+                if let Some(chunk) = block.chunk.upgrade() {
+                    sensor::on_chunk_change(&chunk, block.position);
+                }
+            }
+        }
+    }
+    //average fill of the slots, counted against the stack size of the items in them
+    pub fn get_fill(&self) -> f64 {
+        let items = self.items.lock();
+        let filled: f64 = items
+            .iter()
+            .flatten()
+            .map(|item| item.get_count() as f64 / item.item_type.stack_size.max(1) as f64)
+            .sum();
+        filled / items.len().max(1) as f64
+    }
+    pub fn set_item_no_update(&self, index: u32, item: Option<ItemStack>) {
+        self.items.lock()[index as usize] = item;
+        self.sync_slot(index, false);
+    }
+    fn on_slot_changed(&self, index: u32) {
+        if let Some(crafting) = &self.crafting {
+            if crafting.is_grid_slot(index) {
+                crafting.update_output(self);
+            }
         }
     }
     pub fn set_client_property(&self, id: Identifier, value: Variant, server: &Server) {
@@ -191,24 +688,7 @@ impl Inventory {
             if viewer.slot_range.contains(&index) {
                 viewer
                     .viewer
-                    .send_message(&NetworkMessageS2C::GuiEditElement(
-                        format!(
-                            "{}_{}",
-                            viewer.id.to_string().as_str(),
-                            (index - viewer.slot_range.start).to_string()
-                        ),
-                        GUIElementEdit {
-                            component_type: GUIComponentEdit::SlotComponent {
-                                item_id: Some(
-                                    item.as_ref()
-                                        .map(|item| (item.item_type.client_id, item.item_count)),
-                                ),
-                                size: None,
-                                background: None,
-                            },
-                            ..Default::default()
-                        },
-                    ));
+                    .send_message(&viewer.create_slot_message(index, item.as_ref()));
             }
         }
         if !only_count {
@@ -219,14 +699,19 @@ impl Inventory {
                         entity.get_location().chunk.announce_to_viewers(
                             &NetworkMessageS2C::ModelItem(
                                 ClientModelTarget::Entity(entity.client_id),
-                                *mapping,
-                                item.as_ref().map(|item| item.item_type.client_id),
+                                vec![(
+                                    *mapping,
+                                    item.as_ref().map(|item| item.item_type.client_id),
+                                )],
                             ),
                         );
                     }
                     if index == *entity.slot.lock() {
                         entity.sync_main_hand_viewmodel(item.as_ref());
                     }
+                    if Some(index) == entity.entity_type.offhand_slot {
+                        entity.sync_offhand_viewmodel(item.as_ref());
+                    }
                 }
                 InventoryWrapper::Block(block) => {
                     let chunk = block.chunk.upgrade().unwrap();
@@ -235,14 +720,14 @@ impl Inventory {
                     {
                         chunk.announce_to_viewers(&NetworkMessageS2C::ModelItem(
                             ClientModelTarget::Block(block.position),
-                            *mapping,
-                            item.as_ref().map(|item| item.item_type.client_id),
+                            vec![(*mapping, item.as_ref().map(|item| item.item_type.client_id))],
                         ));
                     }
                 }
                 _ => {}
             }
         }
+        self.notify_sensors();
     }
     pub fn add_viewer(&self, viewer: GuiInventoryViewer) {
         let key = GuiKey {
@@ -252,35 +737,24 @@ impl Inventory {
         if self.viewers.lock().contains_key(&key) {
             return;
         }
-        viewer
-            .layout
-            .send_to_player(&viewer.viewer, viewer.id.to_string().as_str());
+        if viewer.is_native_hotbar() {
+            viewer.viewer.send_message(&NetworkMessageS2C::HotbarOpen(
+                viewer.id.to_string(),
+                viewer.slot_range.len() as u32,
+            ));
+        } else {
+            viewer
+                .layout
+                .send_to_player(&viewer.viewer, viewer.id.to_string().as_str());
+        }
         for slot in viewer.slot_range.clone() {
-            let item = self
-                .items
-                .lock()
-                .get(slot as usize)
-                .unwrap()
-                .as_ref()
-                .map(|item| (item.item_type.client_id, item.item_count));
+            let item = self.items.lock().get(slot as usize).unwrap().clone();
             viewer
                 .viewer
-                .send_message(&NetworkMessageS2C::GuiEditElement(
-                    format!(
-                        "{}_{}",
-                        viewer.id.to_string().as_str(),
-                        (slot - viewer.slot_range.start).to_string()
-                    ),
-                    GUIElementEdit {
-                        component_type: GUIComponentEdit::SlotComponent {
-                            item_id: Some(item),
-                            size: None,
-                            background: None,
-                        },
-                        ..Default::default()
-                    },
-                ));
+                .send_message(&viewer.create_slot_message(slot, item.as_ref()));
         }
+        self.send_tanks(&viewer);
+        self.send_energy(&viewer);
         for property in self.client_properties.lock().iter() {
             let _ = viewer.layout.on_client_property.call_function(
                 &viewer.viewer.server.script_environment,
@@ -334,6 +808,7 @@ impl Inventory {
                         },
                         size: Vec2 { x: 100., y: 100. },
                         background: "".to_string(),
+                        tooltip: Vec::new(),
                     },
                     anchor: PositionAnchor::Cursor,
                     position: Position {
@@ -342,6 +817,7 @@ impl Inventory {
                         z: 10.,
                     },
                     base_color: Color::WHITE,
+                    size_percent: None,
                 },
             ));
         } else {
@@ -390,13 +866,37 @@ impl Inventory {
             .unwrap_or(InteractionResult::Ignored)
         };
         if let InteractionResult::Ignored = result {
+            if let Some(catalog) = &self.catalog {
+                catalog.on_click(self, &key, id, button, shifting);
+                return;
+            }
+            if let (Some(crafting), Some(slot_id)) = (&self.crafting, slot) {
+                if !crafting.is_grid_slot(slot_id) {
+                    if button == MouseButton::Left {
+                        crafting.take_output(self, &key.player);
+                    }
+                    return;
+                }
+            }
+            if button == MouseButton::Left && shifting {
+                if let Some(slot_id) = slot {
+                    if self.shift_click(&key, slot_id) {
+                        return;
+                    }
+                }
+            }
             if button == MouseButton::Left {
                 if let Some(slot_id) = slot {
                     let mut hand = key.player.hand_item.lock().clone();
+                    if let Some(hand) = &hand {
+                        if !self.get_full_view().accepts(slot_id, hand) {
+                            return;
+                        }
+                    }
                     let mut slot = self.get_full_view().get_item(slot_id).unwrap().clone();
                     match (hand.as_mut(), slot.as_mut()) {
                         (Some(hand), Some(slot)) => {
-                            if Arc::ptr_eq(hand.get_type(), slot.get_type()) {
+                            if hand.is_stackable_with(slot) {
                                 if hand.get_count() < hand.item_type.stack_size
                                     && slot.get_count() < slot.item_type.stack_size
                                 {
@@ -416,6 +916,37 @@ impl Inventory {
             }
         }
     }
+    //the stack goes to the guis listed in the layout's shift_click, in order, as far as they have room,
+    //returns false for layouts without shift_click so the click is handled like a normal one
+    fn shift_click(&self, key: &GuiKey, slot: u32) -> bool {
+        let targets = match self.viewers.lock().get(key) {
+            Some(viewer) if !viewer.layout.shift_click.is_empty() => {
+                viewer.layout.shift_click.clone()
+            }
+            _ => return false,
+        };
+        let source = self.get_full_view();
+        for target_id in targets {
+            let target = match key.player.open_guis.lock().get(&target_id) {
+                Some(target) => target.clone(),
+                None => continue,
+            };
+            let target_key = GuiKey {
+                player: key.player.clone(),
+                id: target_id,
+            };
+            let slot_range = match target.get_inventory().viewers.lock().get(&target_key) {
+                Some(viewer) => viewer.slot_range.clone(),
+                None => continue,
+            };
+            let count = match source.get_item(slot) {
+                Ok(Some(item)) => item.get_count(),
+                _ => break,
+            };
+            source.move_slot(slot, &target.get_inventory().get_view(slot_range), count);
+        }
+        true
+    }
     pub fn on_scroll(&self, key: GuiKey, id: &str, x: i32, y: i32, shifting: bool) {
         let slot = id.parse::<u32>().ok();
         let result = {
@@ -458,6 +989,14 @@ impl Inventory {
             .unwrap_or(InteractionResult::Ignored)
         };
         if let InteractionResult::Ignored = result {
+            if self.catalog.is_some() {
+                return;
+            }
+            if let (Some(crafting), Some(slot)) = (&self.crafting, slot) {
+                if !crafting.is_grid_slot(slot) {
+                    return;
+                }
+            }
             if let Some(slot) = slot {
                 key.player.modify_inventory_hand(|first| {
                     self.get_full_view()
@@ -471,7 +1010,7 @@ impl Inventory {
                             if let Some(first) = first {
                                 match second {
                                     Some(second) => {
-                                        if Arc::ptr_eq(first.get_type(), second.get_type())
+                                        if first.is_stackable_with(second)
                                             && second.get_count() < second.get_type().stack_size
                                         {
                                             second.add_count(1);
@@ -479,7 +1018,7 @@ impl Inventory {
                                         }
                                     }
                                     None => {
-                                        *second = Some(ItemStack::new(first.get_type(), 1));
+                                        *second = Some(first.copy(1));
                                         first.add_count(-1);
                                     }
                                 }
@@ -490,6 +1029,58 @@ impl Inventory {
             }
         }
     }
+    pub fn on_text_changed(&self, key: GuiKey, id: &str, text: String) {
+        let viewer = match self.viewers.lock().get(&key) {
+            Some(viewer) => viewer.clone(),
+            None => return,
+        };
+        if let Err(error) = viewer.layout.on_text_changed.call_function(
+            &key.player.server.script_environment,
+            None,
+            vec![
+                ModGuiViewer {
+                    viewer: viewer.viewer.clone(),
+                    id: viewer.id.clone(),
+                }
+                .into_variant(),
+                Variant::from_str(id),
+                Variant::from_str(text.as_str()),
+            ],
+        ) {
+            logging::error(
+                LogTarget::Scripts,
+                format!("on_text_changed of {} failed: {:?}", id, error),
+            );
+        }
+        if let (Some(catalog), "search") = (&self.catalog, id) {
+            catalog.set_query(self, &key, text.as_str());
+        }
+    }
+    pub fn on_visible_range(&self, key: GuiKey, id: &str, first: u32, end: u32) {
+        let viewer = match self.viewers.lock().get(&key) {
+            Some(viewer) => viewer.clone(),
+            None => return,
+        };
+        if let Err(error) = viewer.layout.on_visible_range.call_function(
+            &key.player.server.script_environment,
+            None,
+            vec![
+                ModGuiViewer {
+                    viewer: viewer.viewer.clone(),
+                    id: viewer.id.clone(),
+                }
+                .into_variant(),
+                Variant::from_str(id),
+                (first as i64).into_variant(),
+                (end as i64).into_variant(),
+            ],
+        ) {
+            logging::error(
+                LogTarget::Scripts,
+                format!("on_visible_range of {} failed: {:?}", id, error),
+            );
+        }
+    }
     pub fn serialize(&self) -> InventorySaveData {
         InventorySaveData {
             items: self
@@ -497,8 +1088,13 @@ impl Inventory {
                 .lock()
                 .iter()
                 .map(|item| {
-                    item.as_ref()
-                        .map(|item| (item.item_type.id.to_string(), item.item_count))
+                    item.as_ref().map(|item| {
+                        (
+                            item.item_type.id.to_string(),
+                            item.item_count,
+                            item.data.clone(),
+                        )
+                    })
                 })
                 .collect(),
         }
@@ -508,20 +1104,26 @@ impl Inventory {
         inventory_save_data: InventorySaveData,
         item_registry: &ItemRegistry,
     ) {
-        let items: Vec<_> = inventory_save_data
+        let mut items: Vec<_> = inventory_save_data
             .items
             .iter()
             .map(|item| {
                 item.as_ref().map(|item| {
-                    ItemStack::new(
+                    let mut stack = ItemStack::new(
                         item_registry
                             .item_by_identifier(&Identifier::parse(item.0.as_str()).unwrap())
                             .unwrap(),
                         item.1,
-                    )
+                    );
+                    stack.data = item.2.clone();
+                    stack
                 })
             })
             .collect();
+        //inventories saved before their type grew get the new slots empty
+        if items.len() < self.get_size() as usize {
+            items.resize_with(self.get_size() as usize, || None);
+        }
         self.load_content(items.into_boxed_slice());
     }
     pub fn get_view(&self, slot_range: Range<u32>) -> InventoryView {
@@ -547,10 +1149,26 @@ impl Eq for GuiKey {}
 impl Hash for GuiKey {
     fn hash<H: Hasher>(&self, state: &mut H) {}
 }
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct InventorySaveData {
+    items: Vec<Option<(String, u32, ItemData)>>,
+}
+//layout used before items had data
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LegacyInventorySaveData {
     items: Vec<Option<(String, u32)>>,
 }
+impl From<LegacyInventorySaveData> for InventorySaveData {
+    fn from(legacy: LegacyInventorySaveData) -> Self {
+        InventorySaveData {
+            items: legacy
+                .items
+                .into_iter()
+                .map(|item| item.map(|(id, count)| (id, count, ItemData::default())))
+                .collect(),
+        }
+    }
+}
 #[derive(Clone)]
 pub struct OwnedInventoryView {
     slot_range: Range<u32>,
@@ -600,6 +1218,67 @@ impl ScriptingObject for OwnedInventoryView {
         env.register_method("get_inventory", |view: &OwnedInventoryView| {
             Ok(view.inventory.clone())
         });
+        env.register_method(
+            "set_slot_rule",
+            |view: &OwnedInventoryView, index: &i64, kind: &ImmutableString, filter: &Variant| {
+                let kind = SlotKind::from_name(kind.as_ref()).ok_or_else(|| {
+                    ScriptError::runtime(
+                        format!("unknown slot kind {}", kind).as_str(),
+                        FilePosition::INVALID,
+                    )
+                })?;
+                view.view()
+                    .set_slot_rule(
+                        *index as u32,
+                        Some(SlotRule {
+                            kind,
+                            filter: Arc::<IdentifierTag>::from_variant(filter).cloned(),
+                        }),
+                    )
+                    .map_err(|_| {
+                        ScriptError::runtime(
+                            "inventory view access out of bounds",
+                            FilePosition::INVALID,
+                        )
+                    })?;
+                Ok(())
+            },
+        );
+        env.register_method("get_slot_kind", |view: &OwnedInventoryView, index: &i64| {
+            Ok(ImmutableString::from(
+                view.view()
+                    .get_slot_rule(*index as u32)
+                    .map_or(SlotKind::Storage, |rule| rule.kind)
+                    .get_name(),
+            ))
+        });
+        env.register_method(
+            "accepts",
+            |view: &OwnedInventoryView, index: &i64, item: &ItemStack| {
+                Ok(view.view().accepts(*index as u32, item))
+            },
+        );
+        env.register_method(
+            "insert_item",
+            |view: &OwnedInventoryView, item: &ItemStack| {
+                Ok(Variant::from_option(view.view().insert_item(item)))
+            },
+        );
+        env.register_method(
+            "move_slot",
+            |view: &OwnedInventoryView, index: &i64, target: &OwnedInventoryView, count: &i64| {
+                Ok(view
+                    .view()
+                    .move_slot(*index as u32, &target.view(), (*count).max(0) as u32)
+                    as i64)
+            },
+        );
+        env.register_function(
+            "move_item",
+            |from: &OwnedInventoryView, to: &OwnedInventoryView, count: &i64| {
+                Ok(from.view().move_items(&to.view(), (*count).max(0) as u32) as i64)
+            },
+        );
     }
 }
 pub struct GuiInventoryData {
@@ -679,6 +1358,25 @@ impl ScriptingObject for ModGuiViewer {
                 Ok(())
             },
         );
+        env.register_method(
+            "set_input_text",
+            |viewer: &ModGuiViewer, element_id: &ImmutableString, text: &ImmutableString| {
+                viewer
+                    .viewer
+                    .send_message(&NetworkMessageS2C::GuiEditElement(
+                        format!("{}_{}", viewer.id.to_string(), element_id),
+                        GUIElementEdit {
+                            component_type: GUIComponentEdit::TextInput {
+                                text: Some(text.to_string()),
+                                size: None,
+                                placeholder: None,
+                            },
+                            ..Default::default()
+                        },
+                    ));
+                Ok(())
+            },
+        );
         env.register_method(
             "clear_slice",
             |viewer: &ModGuiViewer, element_id: &ImmutableString| {
@@ -712,6 +1410,27 @@ impl GuiInventoryViewer {
     pub fn view<'a>(&self, inventory: &'a Inventory) -> InventoryView<'a> {
         inventory.get_view(self.slot_range.clone())
     }
+    pub fn is_native_hotbar(&self) -> bool {
+        self.layout.hotbar && self.viewer.has_capability(Capability::Hotbar)
+    }
+    pub fn create_slot_message(&self, index: u32, item: Option<&ItemStack>) -> NetworkMessageS2C {
+        let slot = index - self.slot_range.start;
+        if self.is_native_hotbar() {
+            return NetworkMessageS2C::HotbarSlot(slot, item.map(|item| item.to_hotbar_item()));
+        }
+        NetworkMessageS2C::GuiEditElement(
+            format!("{}_{}", self.id.to_string().as_str(), slot.to_string()),
+            GUIElementEdit {
+                component_type: GUIComponentEdit::SlotComponent {
+                    item_id: Some(item.map(|item| (item.item_type.client_id, item.item_count))),
+                    size: None,
+                    background: None,
+                    tooltip: Some(item.map(|item| item.get_tooltip()).unwrap_or_default()),
+                },
+                ..Default::default()
+            },
+        )
+    }
 }
 pub struct InventoryView<'a> {
     slot_range: Range<u32>,
@@ -772,6 +1491,7 @@ impl<'a> InventoryView<'a> {
         if let Some(handler) = self.inventory.set_item_handler.as_ref() {
             handler.call((self.inventory, index));
         }
+        self.inventory.on_slot_changed(index);
         Ok(())
     }
     pub fn modify_item<F>(&self, index: u32, function: F) -> Result<(), ()>
@@ -797,6 +1517,7 @@ impl<'a> InventoryView<'a> {
             }
         };
         self.inventory.sync_slot(index, only_count);
+        self.inventory.on_slot_changed(index);
         Ok(())
     }
     pub fn add_item(&self, item: &ItemStack) -> Option<ItemStack> {
@@ -805,7 +1526,7 @@ impl<'a> InventoryView<'a> {
             self.modify_item(slot as u32, |slot_item| {
                 let set_rest = match slot_item {
                     Some(slot_item) => {
-                        if Arc::ptr_eq(item.get_type(), slot_item.get_type()) {
+                        if item.is_stackable_with(slot_item) {
                             let transfer =
                                 (slot_item.item_type.stack_size - slot_item.get_count()).min(rest);
                             slot_item.add_count(transfer as i32);
@@ -827,14 +1548,48 @@ impl<'a> InventoryView<'a> {
         }
         Some(item.copy(rest))
     }
-    pub fn remove_item(&self, item: &ItemStack) -> Option<ItemStack> {
+    pub fn get_slot_rule(&self, index: u32) -> Option<SlotRule> {
+        let index = self.map_slot(index).ok()?;
+        self.inventory.slot_rules.lock().get(&index).cloned()
+    }
+    pub fn set_slot_rule(&self, index: u32, rule: Option<SlotRule>) -> Result<(), ()> {
+        let index = self.map_slot(index)?;
+        let mut slot_rules = self.inventory.slot_rules.lock();
+        match rule {
+            Some(rule) => slot_rules.insert(index, rule),
+            None => slot_rules.remove(&index),
+        };
+        Ok(())
+    }
+    pub fn accepts(&self, index: u32, item: &ItemStack) -> bool {
+        self.get_slot_rule(index)
+            .map_or(true, |rule| rule.accepts(item))
+    }
+    //like add_item, but only into slots whose rules accept the item, filtered slots are filled before the rest
+    pub fn insert_item(&self, item: &ItemStack) -> Option<ItemStack> {
+        self.insert_item_except(item, None)
+    }
+    fn insert_item_except(&self, item: &ItemStack, except: Option<u32>) -> Option<ItemStack> {
+        let slots: Vec<(u32, bool)> = (0..self.get_size())
+            .filter(|slot| Some(*slot) != except && self.accepts(*slot, item))
+            .map(|slot| {
+                let filtered = self
+                    .get_slot_rule(slot)
+                    .map_or(false, |rule| rule.filter.is_some());
+                (slot, filtered)
+            })
+            .collect();
         let mut rest = item.get_count();
-        for slot in 0..self.get_size() {
-            self.modify_item(slot as u32, |slot_item| {
+        for (slot, _) in &slots {
+            self.modify_item(*slot, |slot_item| {
                 if let Some(slot_item) = slot_item {
-                    if Arc::ptr_eq(item.get_type(), slot_item.get_type()) {
-                        let transfer = slot_item.get_count().min(rest);
-                        slot_item.add_count(-(transfer as i32));
+                    if item.is_stackable_with(slot_item) {
+                        let transfer = slot_item
+                            .item_type
+                            .stack_size
+                            .saturating_sub(slot_item.get_count())
+                            .min(rest);
+                        slot_item.add_count(transfer as i32);
                         rest -= transfer;
                     }
                 }
@@ -844,8 +1599,102 @@ impl<'a> InventoryView<'a> {
                 return None;
             }
         }
+        for filtered in [true, false] {
+            for (slot, _) in slots.iter().filter(|slot| slot.1 == filtered) {
+                self.modify_item(*slot, |slot_item| {
+                    if slot_item.is_none() {
+                        let transfer = item.item_type.stack_size.min(rest);
+                        *slot_item = Some(item.copy(transfer));
+                        rest -= transfer;
+                    }
+                })
+                .unwrap();
+                if rest == 0 {
+                    return None;
+                }
+            }
+        }
         Some(item.copy(rest))
     }
+    //returns how many items were moved
+    pub fn move_slot(&self, index: u32, target: &InventoryView, count: u32) -> u32 {
+        let _transfer = Inventory::lock_transfers(self.inventory, target.inventory);
+        let item = match self.get_item(index) {
+            Ok(Some(item)) => item,
+            _ => return 0,
+        };
+        let count = count.min(item.get_count());
+        if count == 0 {
+            return 0;
+        }
+        //an item can't be moved into the slot it comes from
+        let except = if std::ptr::eq(self.inventory, target.inventory) {
+            self.map_slot(index)
+                .ok()
+                .and_then(|index| index.checked_sub(target.slot_range.start))
+        } else {
+            None
+        };
+        let rest = target
+            .insert_item_except(&item.copy(count), except)
+            .map_or(0, |rest| rest.get_count());
+        let moved = count - rest;
+        if moved > 0 {
+            self.modify_item(index, |item| {
+                if let Some(item) = item {
+                    item.add_count(-(moved.min(item.get_count()) as i32));
+                }
+            })
+            .unwrap();
+        }
+        moved
+    }
+    //takes from storage and output slots only, input and fuel slots hold what a machine is about to use
+    pub fn move_items(&self, target: &InventoryView, count: u32) -> u32 {
+        let mut moved = 0;
+        for slot in 0..self.get_size() {
+            if moved >= count {
+                break;
+            }
+            let kind = self
+                .get_slot_rule(slot)
+                .map_or(SlotKind::Storage, |rule| rule.kind);
+            if kind == SlotKind::Input || kind == SlotKind::Fuel {
+                continue;
+            }
+            moved += self.move_slot(slot, target, count - moved);
+        }
+        moved
+    }
+    pub fn remove_item(&self, item: &ItemStack) -> Option<ItemStack> {
+        let rest = self.remove_matching(item.get_count(), |slot_item| {
+            item.is_stackable_with(slot_item)
+        });
+        (rest > 0).then(|| item.copy(rest))
+    }
+    //returns how many of count couldn't be removed
+    pub fn remove_matching<F>(&self, count: u32, matches: F) -> u32
+    where
+        F: Fn(&ItemStack) -> bool,
+    {
+        let mut rest = count;
+        for slot in 0..self.get_size() {
+            if rest == 0 {
+                break;
+            }
+            self.modify_item(slot as u32, |slot_item| {
+                if let Some(slot_item) = slot_item {
+                    if matches(slot_item) {
+                        let transfer = slot_item.get_count().min(rest);
+                        slot_item.add_count(-(transfer as i32));
+                        rest -= transfer;
+                    }
+                }
+            })
+            .unwrap();
+        }
+        rest
+    }
 }
 
 #[derive(Clone)]
@@ -901,6 +1750,53 @@ impl ScriptingObject for InventoryWrapper {
         env.register_member("user_data", |inventory: &InventoryWrapper| {
             Some(UserDataWrapper::Inventory(inventory.clone()))
         });
+        env.register_member("tank_count", |inventory: &InventoryWrapper| {
+            Some(inventory.get_inventory().get_fluid_tanks().get_count() as i64)
+        });
+        env.register_method("get_fluid", |inventory: &InventoryWrapper, tank: &i64| {
+            inventory
+                .get_inventory()
+                .get_fluid_tanks()
+                .get_fluid(*tank as u32)
+                .map(|fluid| Variant::from_option(fluid))
+                .map_err(|_| ScriptError::runtime("tank out of range", FilePosition::INVALID))
+        });
+        env.register_method(
+            "get_tank_capacity",
+            |inventory: &InventoryWrapper, tank: &i64| {
+                inventory
+                    .get_inventory()
+                    .get_fluid_tanks()
+                    .get_capacity(*tank as u32)
+                    .map(|capacity| capacity as i64)
+                    .map_err(|_| ScriptError::runtime("tank out of range", FilePosition::INVALID))
+            },
+        );
+        env.register_method(
+            "insert_fluid",
+            |inventory: &InventoryWrapper, fluid: &FluidStack| {
+                Ok(inventory.get_inventory().insert_fluid(fluid) as i64)
+            },
+        );
+        env.register_method(
+            "extract_fluid",
+            |inventory: &InventoryWrapper, tank: &i64, amount: &i64| {
+                inventory
+                    .get_inventory()
+                    .extract_fluid(*tank as u32, (*amount).max(0) as u32)
+                    .map(|fluid| Variant::from_option(fluid))
+                    .map_err(|_| ScriptError::runtime("tank out of range", FilePosition::INVALID))
+            },
+        );
+        env.register_method(
+            "move_fluid",
+            |inventory: &InventoryWrapper, target: &InventoryWrapper, amount: &i64| {
+                Ok(inventory
+                    .get_inventory()
+                    .move_fluid(target.get_inventory(), (*amount).max(0) as u32)
+                    as i64)
+            },
+        );
         {
             let server = server.clone();
             env.register_method(
@@ -939,25 +1835,47 @@ impl WeakInventoryWrapper {
 pub struct Recipe {
     pub id: Identifier,
     recipe_type: Identifier,
-    input_items: Vec<ItemStack>,
+    input_items: Vec<RecipeInput>,
     output_items: Vec<ItemStack>,
+    shape: RecipeShape,
 }
 impl Recipe {
-    pub fn from_json(id: Identifier, json: JsonValue, item_registry: &ItemRegistry) -> Self {
-        let mut input_items = Vec::new();
+    pub fn from_json(
+        id: Identifier,
+        json: JsonValue,
+        item_registry: &ItemRegistry,
+        tags: &HashMap<Identifier, Arc<IdentifierTag>>,
+    ) -> Result<Self, String> {
+        let shape = RecipeShape::from_json(&json, item_registry, tags)?;
+        let mut input_items = shape.to_inputs();
         let mut output_items = Vec::new();
         for item_input in json["item_inputs"].members() {
-            input_items.push(ItemStack::from_json(item_input, item_registry).unwrap());
+            input_items.push(RecipeInput::from_json(item_input, item_registry, tags)?);
         }
+        //exact items are taken first, so tags don't use up items another input needs
+        input_items.sort_by_key(|input| matches!(input.ingredient, Ingredient::Tag(_)));
         for item_output in json["item_outputs"].members() {
-            output_items.push(ItemStack::from_json(item_output, item_registry).unwrap());
+            output_items.push(
+                ItemStack::from_json(item_output, item_registry)
+                    .map_err(|_| format!("unknown output item {}", item_output["id"]))?,
+            );
         }
-        Recipe {
+        Ok(Recipe {
             id,
-            recipe_type: Identifier::parse(json["type"].as_str().unwrap()).unwrap(),
+            recipe_type: json["type"]
+                .as_str()
+                .and_then(|recipe_type| Identifier::parse(recipe_type).ok())
+                .ok_or("recipe without a valid type")?,
             input_items,
             output_items,
-        }
+            shape,
+        })
+    }
+    pub fn get_shape(&self) -> &RecipeShape {
+        &self.shape
+    }
+    pub fn get_inputs(&self) -> &Vec<RecipeInput> {
+        &self.input_items
     }
     pub fn get_icon(&self) -> ItemStack {
         self.output_items.get(0).unwrap().clone()
@@ -970,7 +1888,7 @@ impl Recipe {
         let inventory_copy_view = inventory_copy.get_full_view();
         inventory_copy.load_content(inventory.export_content());
         for input_item in &self.input_items {
-            if let Some(_) = inventory_copy_view.remove_item(input_item) {
+            if input_item.remove_from(&inventory_copy_view) > 0 {
                 return false;
             }
         }
@@ -992,7 +1910,7 @@ impl Recipe {
             return Err(());
         }
         for item in &self.input_items {
-            inventory.remove_item(item);
+            item.remove_from(inventory);
         }
         Ok(())
     }
@@ -1016,7 +1934,7 @@ impl ScriptingObject for Recipe {
                     server
                         .upgrade()
                         .unwrap()
-                        .recipes
+                        .get_recipes()
                         .by_id(&Identifier::parse(id.as_ref()).unwrap()),
                 ))
             });
@@ -1027,7 +1945,7 @@ impl ScriptingObject for Recipe {
                 let server = server.upgrade().unwrap();
                 Ok(Arc::new(Mutex::new(
                     server
-                        .recipes
+                        .get_recipes()
                         .by_type(&Identifier::parse(id.as_ref()).unwrap())
                         .iter()
                         .cloned()
@@ -1075,32 +1993,68 @@ impl ScriptingObject for Recipe {
 pub struct GUILayout {
     elements: HashMap<String, GUIElement>,
     on_client_property: ScriptCallback,
+    on_text_changed: ScriptCallback,
+    on_visible_range: ScriptCallback,
+    shift_click: Vec<Identifier>,
+    bindings: Vec<ElementBinding>,
+    //clients that support it draw the slots themselves instead of the elements
+    hotbar: bool,
 }
 impl GUILayout {
-    pub fn from_json(mut json: JsonValue, environment: &ExecutionEnvironment) -> GUILayout {
-        let on_client_property = json.remove("on_client_property");
-        let on_client_property = if on_client_property.is_null() {
-            ScriptCallback::empty()
-        } else {
-            ScriptCallback::from_function_variant(
-                FunctionVariant::from_variant(&mods::json_to_variant(
-                    on_client_property,
-                    environment,
-                ))
-                .unwrap(),
-            )
+    pub fn from_json(
+        mut json: JsonValue,
+        environment: &ExecutionEnvironment,
+    ) -> Result<GUILayout, String> {
+        let mut callback = |name: &str| {
+            let function = json.remove(name);
+            if function.is_null() {
+                return Ok(ScriptCallback::empty());
+            }
+            let variant = mods::json_to_variant(function.clone(), environment);
+            FunctionVariant::from_variant(&variant)
+                .map(ScriptCallback::from_function_variant)
+                .ok_or_else(|| format!("{} function {} not found", name, function))
         };
+        let on_client_property = callback("on_client_property")?;
+        let on_text_changed = callback("on_text_changed")?;
+        let on_visible_range = callback("on_visible_range")?;
+        let shift_click = json["shift_click"]
+            .members()
+            .filter_map(|id| id.as_str())
+            .map(|id| Identifier::parse(id).map_err(|_| format!("invalid shift_click gui {}", id)))
+            .collect::<Result<_, _>>()?;
         let mut elements = HashMap::new();
+        let mut bindings = Vec::new();
         for (key, element) in json["elements"].entries() {
+            bindings.extend(ElementBinding::from_json(key, &element["bind"])?);
             elements.insert(
                 key.to_string(),
-                serde_json::from_str(element.to_string().as_str()).unwrap(),
+                serde_json::from_str(element.to_string().as_str())
+                    .map_err(|error| format!("invalid element {}: {}", key, error))?,
             );
         }
-        GUILayout {
+        Ok(GUILayout {
             on_client_property,
+            on_text_changed,
+            on_visible_range,
+            shift_click,
             elements,
-        }
+            bindings,
+            hotbar: json["hotbar"].as_bool().unwrap_or(false),
+        })
+    }
+    pub fn get_bindings(&self) -> &Vec<ElementBinding> {
+        &self.bindings
+    }
+    //slot elements named by their slot number, like the ones a catalog fills
+    pub fn get_slot_count(&self) -> u32 {
+        self.elements
+            .iter()
+            .filter(|(id, element)| {
+                id.parse::<u32>().is_ok()
+                    && matches!(element.component_type, GUIComponent::SlotComponent { .. })
+            })
+            .count() as u32
     }
     pub fn send_to_player(&self, player: &PlayerData, container_id: &str) {
         for element in &self.elements {
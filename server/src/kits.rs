@@ -0,0 +1,315 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+
+use bbscript::eval::{ExecutionEnvironment, ScriptError};
+use bbscript::lex::FilePosition;
+use bbscript::variant::{IntoVariant, SharedArray, Variant};
+use immutable_string::ImmutableString;
+use json::{object, JsonValue};
+use parking_lot::Mutex;
+
+use crate::inventory::ItemStack;
+use crate::logging::{self, LogTarget};
+use crate::mods::ScriptingObject;
+use crate::registry::ItemRegistry;
+use crate::util::Identifier;
+use crate::world::{Entity, PlayerData};
+use crate::Server;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum KitPolicy {
+    //empties the inventory first, so it holds exactly the kit
+    Overwrite,
+    //keeps the inventory, kit items go into their slot if it's free and anywhere else otherwise
+    Merge,
+}
+impl KitPolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "overwrite" => Some(KitPolicy::Overwrite),
+            "merge" => Some(KitPolicy::Merge),
+            _ => None,
+        }
+    }
+}
+
+pub struct Kit {
+    items: Vec<(u32, ItemStack)>,
+}
+impl Kit {
+    pub fn from_json(json: &JsonValue, item_registry: &ItemRegistry) -> Result<Self, String> {
+        let mut items = Vec::new();
+        for item in json["items"].members() {
+            let id = item["id"].as_str().ok_or("kit item without id")?;
+            Identifier::parse(id).map_err(|_| format!("invalid item id {}", id))?;
+            let slot = item["slot"]
+                .as_u32()
+                .ok_or_else(|| format!("kit item {} without slot", id))?;
+            let stack = ItemStack::from_json(item, item_registry)
+                .map_err(|_| format!("unknown item {}", id))?;
+            items.push((slot, stack));
+        }
+        Ok(Kit { items })
+    }
+    pub fn to_json(&self) -> JsonValue {
+        let mut items = JsonValue::new_array();
+        for (slot, stack) in &self.items {
+            let mut item = stack.to_json();
+            item.insert("slot", *slot).unwrap();
+            items.push(item).unwrap();
+        }
+        object! {
+            items: items
+        }
+    }
+    pub fn capture(entity: &Entity) -> Self {
+        Kit {
+            items: entity
+                .inventory
+                .get_full_view()
+                .export_content()
+                .iter()
+                .enumerate()
+                .filter_map(|(slot, item)| item.clone().map(|item| (slot as u32, item)))
+                .collect(),
+        }
+    }
+    //items that don't fit are dropped at the entity
+    pub fn give(&self, entity: &Entity, policy: KitPolicy) {
+        let inventory = entity.inventory.get_full_view();
+        if policy == KitPolicy::Overwrite {
+            for slot in 0..inventory.get_size() {
+                inventory.set_item(slot, None).unwrap();
+            }
+        }
+        for (slot, stack) in &self.items {
+            let free = inventory
+                .get_item(*slot)
+                .map_or(false, |item| item.is_none());
+            let rest = if free && inventory.accepts(*slot, stack) {
+                inventory.set_item(*slot, Some(stack.clone())).unwrap();
+                None
+            } else {
+                inventory.add_item(stack)
+            };
+            if let Some(rest) = rest {
+                if let Err(error) = entity
+                    .server
+                    .drop_item(&(&entity.get_location()).into(), rest)
+                {
+                    logging::error(LogTarget::Server, error);
+                }
+            }
+        }
+    }
+}
+
+//kits from mods have ids like core:starter, kits saved in game have plain names and live in the kits folder
+pub struct KitManager {
+    mod_kits: HashMap<String, Arc<Kit>>,
+    saved_kits: Mutex<BTreeMap<String, Arc<Kit>>>,
+    directory: PathBuf,
+}
+impl KitManager {
+    pub fn load(
+        save_directory: &Path,
+        mod_kits: HashMap<String, Arc<Kit>>,
+        item_registry: &ItemRegistry,
+    ) -> Self {
+        let directory = save_directory.join("kits");
+        let mut saved_kits = BTreeMap::new();
+        for entry in std::fs::read_dir(&directory).into_iter().flatten() {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(_) => continue,
+            };
+            let name = match path.file_stem() {
+                Some(name)
+                    if path
+                        .extension()
+                        .map_or(false, |extension| extension == "json") =>
+                {
+                    name.to_string_lossy().to_string()
+                }
+                _ => continue,
+            };
+            let kit = std::fs::read_to_string(&path)
+                .map_err(|error| error.to_string())
+                .and_then(|data| json::parse(data.as_str()).map_err(|error| error.to_string()))
+                .and_then(|json| Kit::from_json(&json, item_registry));
+            match kit {
+                Ok(kit) => {
+                    saved_kits.insert(name, Arc::new(kit));
+                }
+                Err(error) => logging::warn(
+                    LogTarget::Server,
+                    format!("could not load kit {}: {}", path.display(), error),
+                ),
+            }
+        }
+        KitManager {
+            mod_kits,
+            saved_kits: Mutex::new(saved_kits),
+            directory,
+        }
+    }
+    pub fn get(&self, name: &str) -> Option<Arc<Kit>> {
+        self.mod_kits
+            .get(name)
+            .cloned()
+            .or_else(|| self.saved_kits.lock().get(name).cloned())
+    }
+    pub fn list(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.mod_kits.keys().cloned().collect();
+        names.sort();
+        names.extend(self.saved_kits.lock().keys().cloned());
+        names
+    }
+    pub fn save(&self, name: &str, kit: Kit) -> Result<(), String> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || char == '_' || char == '-')
+        {
+            return Err(format!(
+                "invalid kit name {}, only letters, numbers, _ and - are allowed",
+                name
+            ));
+        }
+        std::fs::create_dir_all(&self.directory)
+            .and_then(|_| {
+                std::fs::write(
+                    self.directory.join(format!("{}.json", name)),
+                    kit.to_json().pretty(4),
+                )
+            })
+            .map_err(|error| format!("could not save kit {}: {}", name, error))?;
+        self.saved_kits
+            .lock()
+            .insert(name.to_string(), Arc::new(kit));
+        Ok(())
+    }
+    pub fn delete(&self, name: &str) -> Result<(), String> {
+        if self.saved_kits.lock().remove(name).is_none() {
+            return Err(if self.mod_kits.contains_key(name) {
+                format!("kit {} comes from a mod and can't be deleted", name)
+            } else {
+                format!("unknown kit {}", name)
+            });
+        }
+        std::fs::remove_file(self.directory.join(format!("{}.json", name)))
+            .map_err(|error| format!("could not delete kit {}: {}", name, error))
+    }
+    pub fn run_command(
+        &self,
+        server: &Server,
+        player: &PlayerData,
+        command: &[&str],
+    ) -> Result<String, String> {
+        match command {
+            [] | ["list"] => Ok(format!("kits: {}", self.list().join(", "))),
+            ["give", name, rest @ ..] if rest.len() <= 2 => {
+                let kit = self
+                    .get(name)
+                    .ok_or_else(|| format!("unknown kit {}", name))?;
+                let (username, policy) = match rest {
+                    [] => (None, None),
+                    [policy] if KitPolicy::from_name(policy).is_some() => (None, Some(*policy)),
+                    [username] => (Some(*username), None),
+                    [username, policy] => (Some(*username), Some(*policy)),
+                    _ => unreachable!(),
+                };
+                let policy = match policy {
+                    Some(policy) => KitPolicy::from_name(policy)
+                        .ok_or_else(|| format!("unknown policy {}, use merge or overwrite", policy))?,
+                    None => KitPolicy::Merge,
+                };
+                let target = match username {
+                    Some(username) => server
+                        .players
+                        .lock()
+                        .iter()
+                        .find(|player| player.identity.username == username)
+                        .cloned()
+                        .ok_or_else(|| format!("player {} is not online", username))?
+                        .get_entity(),
+                    None => player.get_entity(),
+                };
+                kit.give(&target, policy);
+                Ok(format!("gave kit {} to {}", name, username.unwrap_or("you")))
+            }
+            ["save", name] => {
+                self.save(name, Kit::capture(&player.get_entity()))?;
+                Ok(format!("saved your inventory as kit {}", name))
+            }
+            ["delete", name] => {
+                self.delete(name)?;
+                Ok(format!("deleted kit {}", name))
+            }
+            _ => Err(
+                "usage: /kit [list|give <kit> [player] [merge|overwrite]|save <name>|delete <name>]"
+                    .to_string(),
+            ),
+        }
+    }
+}
+impl ScriptingObject for KitManager {
+    fn engine_register_server(env: &mut ExecutionEnvironment, server: &Weak<Server>) {
+        {
+            let server = server.clone();
+            env.register_function(
+                "give_kit",
+                move |player: &Arc<PlayerData>,
+                      name: &ImmutableString,
+                      policy: &ImmutableString| {
+                    let policy = KitPolicy::from_name(policy.as_ref()).ok_or_else(|| {
+                        ScriptError::runtime(
+                            "kit policy must be merge or overwrite",
+                            FilePosition::INVALID,
+                        )
+                    })?;
+                    Ok(match server.upgrade().unwrap().kits.get(name.as_ref()) {
+                        Some(kit) => {
+                            kit.give(&player.get_entity(), policy);
+                            true
+                        }
+                        None => false,
+                    })
+                },
+            );
+        }
+        {
+            let server = server.clone();
+            env.register_function(
+                "save_kit",
+                move |name: &ImmutableString, player: &Arc<PlayerData>| {
+                    server
+                        .upgrade()
+                        .unwrap()
+                        .kits
+                        .save(name.as_ref(), Kit::capture(&player.get_entity()))
+                        .map_err(|error| {
+                            ScriptError::runtime(error.as_str(), FilePosition::INVALID)
+                        })
+                },
+            );
+        }
+        {
+            let server = server.clone();
+            env.register_function("list_kits", move || {
+                let names: SharedArray = Arc::new(Mutex::new(
+                    server
+                        .upgrade()
+                        .unwrap()
+                        .kits
+                        .list()
+                        .iter()
+                        .map(|name| Variant::from_str(name.as_str()))
+                        .collect(),
+                ));
+                Ok(names.into_variant())
+            });
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::fmt::Display;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+impl LogLevel {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "debug" => Some(LogLevel::Debug),
+            "info" => Some(LogLevel::Info),
+            "warn" => Some(LogLevel::Warn),
+            "error" => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogTarget {
+    Server,
+    Net,
+    Worldgen,
+    Scripts,
+    ChunkIO,
+}
+impl LogTarget {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogTarget::Server => "server",
+            LogTarget::Net => "net",
+            LogTarget::Worldgen => "worldgen",
+            LogTarget::Scripts => "scripts",
+            LogTarget::ChunkIO => "chunkio",
+        }
+    }
+}
+
+struct LogFile {
+    file: File,
+    size: u64,
+}
+
+pub struct Logger {
+    directory: PathBuf,
+    level: Mutex<LogLevel>,
+    file: Mutex<Option<LogFile>>,
+}
+
+static LOGGER: OnceCell<Logger> = OnceCell::new();
+
+impl Logger {
+    const MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+    const MAX_ROTATED_FILES: usize = 10;
+    fn latest_path(&self) -> PathBuf {
+        let mut path = self.directory.clone();
+        path.push("latest.log");
+        path
+    }
+    fn rotate(&self) {
+        let latest = self.latest_path();
+        if latest.exists() {
+            let mut rotated = self.directory.clone();
+            rotated.push(format!(
+                "{}.log",
+                format_timestamp(SystemTime::now())
+                    .replace(' ', "_")
+                    .replace(':', "-")
+            ));
+            fs::rename(&latest, rotated).ok();
+        }
+        let mut rotated_files: Vec<_> = fs::read_dir(&self.directory)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| {
+                        path.extension()
+                            .map_or(false, |extension| extension == "log")
+                            && *path != latest
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        rotated_files.sort();
+        while rotated_files.len() > Self::MAX_ROTATED_FILES {
+            fs::remove_file(rotated_files.remove(0)).ok();
+        }
+    }
+    fn open(&self) -> Option<LogFile> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.latest_path())
+            .ok()
+            .map(|file| LogFile { file, size: 0 })
+    }
+    fn write(&self, line: &str) {
+        let mut file = self.file.lock();
+        if file
+            .as_ref()
+            .map_or(false, |file| file.size >= Self::MAX_FILE_SIZE)
+        {
+            *file = None;
+            self.rotate();
+            *file = self.open();
+        }
+        if let Some(file) = file.as_mut() {
+            if writeln!(file.file, "{}", line).is_ok() {
+                file.size += line.len() as u64 + 1;
+            }
+        }
+    }
+}
+
+pub fn init(directory: PathBuf) {
+    fs::create_dir_all(&directory).unwrap();
+    let logger = Logger {
+        directory,
+        level: Mutex::new(LogLevel::Info),
+        file: Mutex::new(None),
+    };
+    logger.rotate();
+    *logger.file.lock() = logger.open();
+    LOGGER.set(logger).ok();
+}
+pub fn set_level(level: LogLevel) {
+    if let Some(logger) = LOGGER.get() {
+        *logger.level.lock() = level;
+    }
+}
+
+pub fn log<T: Display>(target: LogTarget, level: LogLevel, message: T) {
+    if let Some(logger) = LOGGER.get() {
+        if level < *logger.level.lock() {
+            return;
+        }
+    }
+    let line = format!(
+        "[{}] [{}/{}] {}",
+        format_timestamp(SystemTime::now()),
+        target.name(),
+        level.name(),
+        message
+    );
+    println!("{}", line);
+    if let Some(logger) = LOGGER.get() {
+        logger.write(&line);
+    }
+}
+pub fn debug<T: Display>(target: LogTarget, message: T) {
+    log(target, LogLevel::Debug, message);
+}
+pub fn info<T: Display>(target: LogTarget, message: T) {
+    log(target, LogLevel::Info, message);
+}
+pub fn warn<T: Display>(target: LogTarget, message: T) {
+    log(target, LogLevel::Warn, message);
+}
+pub fn error<T: Display>(target: LogTarget, message: T) {
+    log(target, LogLevel::Error, message);
+}
+
+fn format_timestamp(time: SystemTime) -> String {
+    let duration = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = duration.as_secs();
+    let days = (seconds / 86400) as i64;
+    let seconds_of_day = seconds % 86400;
+    let (year, month, day) = {
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z.rem_euclid(146097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_part = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_part + 2) / 5 + 1;
+        let month = if month_part < 10 {
+            month_part + 3
+        } else {
+            month_part - 9
+        };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        (year, month, day)
+    };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        (seconds_of_day / 60) % 60,
+        seconds_of_day % 60,
+        duration.subsec_millis()
+    )
+}
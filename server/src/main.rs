@@ -11,11 +11,44 @@
 
 extern crate core;
 
+mod ai;
+mod auth;
+mod cable;
+mod chunkgen;
+mod clipboard;
+mod commands;
+mod compression;
+mod crafting;
+mod creative;
+mod debug_shapes;
+mod detector;
+mod energy;
+mod explosion;
+mod fire;
+mod fluid;
+mod gamerules;
 mod inventory;
+mod kits;
+mod logging;
+mod metrics;
+mod migration;
 mod mods;
 mod net;
+mod overlay;
+mod permissions;
+mod placement;
+mod portal;
+mod recording;
 mod registry;
+mod scheduler;
+mod search;
+mod sensor;
+mod shutdown;
+mod signal;
+mod snapshot;
+mod text_display;
 mod threadpool;
+mod transfer;
 mod util;
 mod world;
 mod worldgen;
@@ -24,41 +57,72 @@ use std::{
     collections::HashMap,
     fs,
     net::TcpListener,
+    panic::{self, AssertUnwindSafe},
     path::{Path, PathBuf},
     process,
-    sync::{atomic::AtomicBool, Arc, Weak},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Weak,
+    },
     thread::{self, spawn},
     time::{Duration, Instant, SystemTime},
 };
 
-use crate::inventory::{GUILayout, Recipe};
+use crate::ai::Schedule;
+use crate::auth::{AccessList, PlayerIdentity};
+use crate::commands::{CommandRegistry, Console, ModCommand};
+use crate::compression::ChunkDictionaryTrainer;
+use crate::detector::Detector;
+use crate::energy::EnergyType;
+use crate::fire::Flammability;
+use crate::fluid::Fluid;
+use crate::gamerules::{GameRuleValue, GameRules};
+use crate::inventory::{GUILayout, ItemStack, Recipe};
+use crate::kits::{Kit, KitManager};
+use crate::logging::{LogLevel, LogTarget};
+use crate::metrics::{Metrics, TickPhase};
 use crate::mods::{
-    json_to_variant, ClientContentData, ContentType, EventManager, IdentifierTag, ModImage,
-    ScriptCallback, ScriptingObject,
+    json_to_variant, ClientContentData, ContentType, EventManager, EventTracer, IdentifierTag,
+    ModImage, ScriptCallback, ScriptingObject,
 };
-use crate::registry::{BlockStateProperty, BlockStatePropertyStorage, RecipeManager, StaticData};
+use crate::permissions::PermissionManager;
+use crate::placement::Placement;
+use crate::portal::Portal;
+use crate::registry::{
+    BlockStateProperty, BlockStatePropertyStorage, BlockStateRef, RecipeManager, StaticData,
+};
+use crate::scheduler::Scheduler;
+use crate::search::SearchIndex;
+use crate::sensor::Sensor;
+use crate::shutdown::ShutdownManager;
+use crate::signal::SignalType;
+use crate::transfer::{ItemTransfer, SidedAccess, SurfaceMovement};
 use crate::world::PlayerData;
 use crate::worldgen::{WorldGenerator, WorldGeneratorType};
-use bbscript::eval::ExecutionEnvironment;
+use bbscript::eval::{ExecutionEnvironment, ScriptError};
 use bbscript::lex::FilePosition;
 use bbscript::variant::{FromVariant, FunctionVariant, IntoVariant, Map, SharedMap, Variant};
 use block_byte_common::content::{
     ClientBlockData, ClientEntityData, ClientItemData, ClientItemModel, ClientTexture,
 };
-use block_byte_common::Position;
-use crossbeam_channel::Receiver;
+use block_byte_common::messages::{ChunkCompression, JoinFailure, PROTOCOL_VERSION};
+use block_byte_common::{KeyboardKey, Position};
+use crossbeam_channel::{Receiver, Sender};
 use fxhash::FxHashMap;
 use immutable_string::ImmutableString;
 use json::{object, JsonValue};
+use migration::MigrationRegistry;
 use mods::ModManager;
-use net::PlayerConnection;
+use net::{PlayerConnection, Transport};
 use parking_lot::Mutex;
+use rand::{thread_rng, Rng};
 use registry::{
     Block, BlockRegistry, EntityRegistry, EntityType, Item, ItemModelMapping, ItemRegistry,
 };
 use threadpool::ThreadPool;
 use util::{Identifier, Location};
-use world::{Entity, Structure, World};
+use uuid::Uuid;
+use world::{Entity, Structure, World, WorldMetadata};
 use worldgen::Biome;
 
 fn main() {
@@ -74,42 +138,67 @@ fn main() {
         .unwrap();
     }
     {
-        let server = Server::new(4321, {
+        let save = {
             let mut save = std::env::current_dir().unwrap();
             save.push("save");
             std::fs::create_dir_all(&save).unwrap();
             save
+        };
+        logging::init({
+            let mut logs = save.clone();
+            logs.push("logs");
+            logs
         });
+        let server = Server::new(4321, save);
+        if let Some(level) = LogLevel::parse(&server.settings.get("server.log_level", "info")) {
+            logging::set_level(level);
+        }
+        let metrics_port = server.settings.get_i64("server.metrics_port", 0);
+        if metrics_port > 0 {
+            Metrics::start_http_endpoint(
+                Arc::downgrade(&server),
+                server.settings.get("server.metrics_address", "127.0.0.1"),
+                metrics_port as u16,
+            );
+        }
         let start_time = Instant::now();
         let mut tick_count: u32 = 0;
-        println!("server started");
+        logging::info(LogTarget::Server, "server started");
         let mut highest_sleep_time = 0;
-        while running.load(std::sync::atomic::Ordering::Relaxed) {
+        while running.load(std::sync::atomic::Ordering::Relaxed) && !server.shutdown.is_finished() {
             let mspt_timer = Instant::now();
             server.tick();
-            if false {
-                println!(
-                    "mspt: {}",
-                    Instant::now().duration_since(mspt_timer).as_micros() as f64 / 1000.
-                );
+            let mspt = Instant::now().duration_since(mspt_timer);
+            if let Some((file_name, trace)) = server.metrics.finish_tick(mspt) {
+                server.export_file(file_name.clone(), trace.into_bytes());
+                logging::info(LogTarget::Server, format!("trace saved to {}", file_name));
             }
+            logging::debug(
+                LogTarget::Server,
+                format!("mspt: {}", mspt.as_micros() as f64 / 1000.),
+            );
             let sleep_time = (tick_count as i64 * 50)
                 - Instant::now().duration_since(start_time).as_millis() as i64;
             if sleep_time > 0 {
                 thread::sleep(Duration::from_millis(sleep_time as u64));
             } else if sleep_time < 0 {
                 if (-sleep_time) > highest_sleep_time {
-                    println!("server is running {}ms behind", -sleep_time);
+                    logging::warn(
+                        LogTarget::Server,
+                        format!("server is running {}ms behind", -sleep_time),
+                    );
                 }
                 highest_sleep_time = -sleep_time;
             }
             server.wait_for_tasks();
             tick_count += 1;
         }
-        println!("saving");
+        logging::info(LogTarget::Server, "saving");
+        server.wait_for_generation();
         server.destroy();
         server.wait_for_tasks();
-        println!("server stopped");
+        logging::info(LogTarget::Server, "server stopped");
+        process::exit(server.shutdown.get_exit_code(&server));
     }
 }
 
@@ -123,16 +212,32 @@ pub struct Server {
     mods: Mutex<ModManager>,
     client_content: (Vec<u8>, String),
     pub thread_pool: ThreadPool,
+    pub worldgen_pool: ThreadPool,
     structures: HashMap<Identifier, Arc<Structure>>,
-    recipes: RecipeManager,
-    events: EventManager,
+    recipes: Mutex<Arc<RecipeManager>>,
+    events: Mutex<Arc<EventManager>>,
+    event_tracer: EventTracer,
     script_environment: ExecutionEnvironment,
     save_directory: PathBuf,
     settings: ServerSettings,
+    access_list: AccessList,
+    permissions: PermissionManager,
+    commands: Mutex<Arc<CommandRegistry>>,
+    console: Console,
+    pub game_rules: GameRules,
+    pub metrics: Metrics,
+    scheduler: Scheduler,
+    shutdown: ShutdownManager,
+    autosave_timer: AtomicU64,
     players: Mutex<Vec<Arc<PlayerData>>>,
-    gui_layouts: HashMap<Identifier, Arc<GUILayout>>,
+    gui_layouts: Mutex<HashMap<Identifier, Arc<GUILayout>>>,
+    pub fluids: HashMap<Identifier, Arc<Fluid>>,
     tags: HashMap<Identifier, Arc<IdentifierTag>>,
     world_generators: HashMap<Identifier, Arc<WorldGeneratorType>>,
+    keybinds: HashMap<Identifier, KeyboardKey>,
+    search_index: SearchIndex,
+    kits: KitManager,
+    chunk_dictionary: ChunkDictionaryTrainer,
 }
 
 impl Server {
@@ -140,10 +245,13 @@ impl Server {
         let (mod_manager, errors, mut engine) = ModManager::load_mods(Path::new("mods"));
         ModManager::init_engine_load(&mut engine);
         for error in &errors {
-            println!("script error at {}: {:?}", error.0, error.1);
+            logging::error(
+                LogTarget::Scripts,
+                format!("script error at {}: {:?}", error.0, error.1),
+            );
         }
         if errors.len() > 0 {
-            println!("server stopped because of mod errors");
+            logging::error(LogTarget::Server, "server stopped because of mod errors");
             process::exit(0);
         }
         let mut block_registry = BlockRegistry::new();
@@ -151,11 +259,10 @@ impl Server {
         let mut entity_registry = EntityRegistry::new();
         let mut biomes = Vec::new();
         let mut structures = HashMap::new();
-        let mut events = EventManager::new();
-        let mut recipes = HashMap::new();
-        let mut gui_layouts = HashMap::new();
-        let mut tags = HashMap::new();
+        let mut fluids = HashMap::new();
+        let mut tags_json = HashMap::new();
         let mut world_generators = HashMap::new();
+        let mut keybinds = HashMap::new();
 
         let static_data_from_json = |json: JsonValue| StaticData {
             data: {
@@ -225,6 +332,74 @@ impl Server {
                 );
 
                 let mut item = json.remove("item");
+                let flammability = Flammability::from_json(&json.remove("flammability"));
+                let fire = json.remove("fire").as_bool().unwrap_or(false);
+                let blast_resistance = json
+                    .remove("blast_resistance")
+                    .as_f32()
+                    .unwrap_or(1.)
+                    .max(0.);
+                let portal = Portal::from_json(&json.remove("portal")).unwrap_or_else(|error| {
+                    logging::error(LogTarget::Server, format!("portal of {}: {}", id, error));
+                    None
+                });
+                let signal =
+                    SignalType::from_json(&json.remove("signal")).unwrap_or_else(|error| {
+                        logging::error(LogTarget::Server, format!("signal of {}: {}", id, error));
+                        None
+                    });
+                let energy =
+                    EnergyType::from_json(&json.remove("energy")).unwrap_or_else(|error| {
+                        logging::error(LogTarget::Server, format!("energy of {}: {}", id, error));
+                        None
+                    });
+                let sensor = Sensor::from_json(&json.remove("sensor")).unwrap_or_else(|error| {
+                    logging::error(LogTarget::Server, format!("sensor of {}: {}", id, error));
+                    None
+                });
+                let inventory_size = json.remove("inventory_size").as_u32();
+                let fluid_tanks: Vec<u32> = json
+                    .remove("fluid_tanks")
+                    .members()
+                    .filter_map(|capacity| {
+                        let tank = capacity.as_u32();
+                        if tank.is_none() {
+                            logging::error(
+                                LogTarget::Server,
+                                format!("fluid_tanks of {}: invalid capacity {}", id, capacity),
+                            );
+                        }
+                        tank
+                    })
+                    .collect();
+                let item_model_mapping = ItemModelMapping::from_json(
+                    &json.remove("item_model_mapping"),
+                )
+                .unwrap_or_else(|error| {
+                    logging::error(
+                        LogTarget::Server,
+                        format!("item_model_mapping of {}: {}", id, error),
+                    );
+                    ItemModelMapping {
+                        mapping: HashMap::new(),
+                    }
+                });
+                let sides =
+                    SidedAccess::from_json(&json.remove("sides"), inventory_size.unwrap_or(0))
+                        .unwrap_or_else(|error| {
+                            logging::error(
+                                LogTarget::Server,
+                                format!("sides of {}: {}", id, error),
+                            );
+                            None
+                        });
+                let transfer = ItemTransfer::from_json(&json.remove("transfer"));
+                let surface =
+                    SurfaceMovement::from_json(&json.remove("surface")).unwrap_or_else(|error| {
+                        logging::error(LogTarget::Server, format!("surface of {}: {}", id, error));
+                        None
+                    });
+                let detector = Detector::from_json(&json.remove("detector"));
                 let client_state_creation_data = json_to_variant(json.clone(), &engine);
                 let static_data = static_data_from_json(json);
                 let state_id = block_registry
@@ -234,17 +409,34 @@ impl Server {
                             Arc::new(Block {
                                 id: id.clone(),
                                 default_state,
-                                data_container: None,
-                                item_model_mapping: ItemModelMapping {
-                                    mapping: HashMap::new(),
-                                },
+                                //tanks and stored energy need block data, which may have no item slots
+                                data_container: inventory_size
+                                    .or(Some(0).filter(|_| {
+                                        !fluid_tanks.is_empty()
+                                            || energy.as_ref().map_or(false, |energy| {
+                                                energy.get_storage().is_some()
+                                            })
+                                    }))
+                                    .map(|size| (size,)),
+                                fluid_tanks,
+                                item_model_mapping,
                                 properties,
                                 networks: HashMap::new(),
                                 static_data,
+                                flammability,
+                                fire,
+                                blast_resistance,
+                                portal,
+                                signal,
+                                energy,
+                                sensor,
+                                sides,
+                                transfer,
+                                detector,
                             })
                         },
                         |id, block| {
-                            ClientBlockData::from_variant(
+                            let mut client_data = ClientBlockData::from_variant(
                                 &client_data_creator
                                     .call_function(
                                         &engine,
@@ -254,12 +446,24 @@ impl Server {
                                     .unwrap(),
                             )
                             .unwrap()
-                            .clone()
+                            .clone();
+                            client_data.surface_velocity = surface
+                                .as_ref()
+                                .and_then(|surface| surface.get_velocity(&block.properties, id));
+                            client_data
                         },
                     )
                     .unwrap();
                 if !item.is_null() {
                     let stack_size = item.remove("stack_size").as_u32().unwrap_or(20);
+                    let category = item
+                        .remove("category")
+                        .as_str()
+                        .map(|category| category.to_string());
+                    let placement = Placement::from_json(
+                        &item.remove("placement"),
+                        BlockStateRef::from_state_id(state_id),
+                    );
                     let static_data = static_data_from_json(item);
                     item_registry
                         .register(id.clone(), move |client_id| {
@@ -268,10 +472,14 @@ impl Server {
                                 client_data: ClientItemData {
                                     name,
                                     model: ClientItemModel::Block(state_id),
+                                    dynamic: None,
                                 },
                                 client_id,
                                 stack_size,
                                 static_data,
+                                placement,
+                                durability: None,
+                                category,
                             })
                         })
                         .unwrap();
@@ -282,6 +490,14 @@ impl Server {
         mod_manager.load_resource_type("items", |id, content| match content {
             ContentType::Json(mut json) => {
                 let stack_size = json.remove("stack_size").as_u32().unwrap_or(1);
+                let durability = json
+                    .remove("durability")
+                    .as_u32()
+                    .filter(|durability| *durability > 0);
+                let category = json
+                    .remove("category")
+                    .as_str()
+                    .map(|category| category.to_string());
                 let client_data: ClientItemData =
                     serde_json::from_str(json.remove("client").to_string().as_str()).unwrap();
                 let static_data = static_data_from_json(json);
@@ -293,12 +509,21 @@ impl Server {
                             client_id,
                             stack_size,
                             static_data,
+                            placement: None,
+                            durability,
+                            category,
                         })
                     })
                     .unwrap();
             }
             ContentType::Binary(_) => unimplemented!(),
         });
+        mod_manager.load_resource_type("fluids", |id, content| match content {
+            ContentType::Json(json) => {
+                fluids.insert(id.clone(), Arc::new(Fluid::from_json(id, &json)));
+            }
+            ContentType::Binary(_) => {}
+        });
         mod_manager.load_resource_type("entities", |id, content| match content {
             ContentType::Json(mut json) => {
                 let client_data: ClientEntityData =
@@ -312,6 +537,28 @@ impl Server {
                     item_model_mapping
                 };
                 let inventory_size = json.remove("inventory_size").as_u32().unwrap_or(0);
+                let offhand_slot = json
+                    .remove("offhand_slot")
+                    .as_u32()
+                    .filter(|slot| *slot < inventory_size);
+                let schedule = Schedule::from_json(&json.remove("schedule"), &block_registry)
+                    .unwrap_or_else(|error| {
+                        logging::error(LogTarget::Server, format!("schedule of {}: {}", id, error));
+                        None
+                    });
+                let low_priority = json.remove("low_priority").as_bool().unwrap_or(false);
+                let despawn_distance = json.remove("despawn_distance").as_f64();
+                let projectile = json.remove("projectile").as_bool().unwrap_or(false);
+                let pushable = json.remove("pushable").as_bool().unwrap_or(false);
+                let sounds = json
+                    .remove("sounds")
+                    .entries()
+                    .filter_map(|(state, sound)| {
+                        sound
+                            .as_str()
+                            .map(|sound| (state.to_string(), sound.to_string()))
+                    })
+                    .collect();
                 let static_data = static_data_from_json(json);
                 entity_registry
                     .register(id.clone(), move |client_id| {
@@ -324,6 +571,13 @@ impl Server {
                             },
                             static_data,
                             inventory_size,
+                            offhand_slot,
+                            schedule,
+                            low_priority,
+                            despawn_distance,
+                            sounds,
+                            projectile,
+                            pushable,
                         })
                     })
                     .unwrap();
@@ -331,9 +585,14 @@ impl Server {
             ContentType::Binary(_) => unimplemented!(),
         });
         mod_manager.load_resource_type("structures", |id, content| match content {
-            ContentType::Json(json) => {
-                structures.insert(id, Arc::new(Structure::from_json(json, &block_registry)));
-            }
+            ContentType::Json(json) => match Structure::from_json(json, &block_registry) {
+                Ok(structure) => {
+                    structures.insert(id, Arc::new(structure));
+                }
+                Err(error) => {
+                    logging::error(LogTarget::Server, format!("structure {}: {}", id, error))
+                }
+            },
             ContentType::Binary(_) => {}
         });
         mod_manager.load_resource_type("biomes", |id, content| match content {
@@ -342,53 +601,65 @@ impl Server {
             }
             ContentType::Binary(_) => {}
         });
-        mod_manager.load_resource_type("recipes", |id, content| match content {
+        mod_manager.load_resource_type("tags", |id, content| match content {
             ContentType::Json(json) => {
-                recipes.insert(
-                    id.clone(),
-                    Arc::new(Recipe::from_json(id, json, &item_registry)),
-                );
+                tags_json.insert(id, json);
             }
             ContentType::Binary(_) => {}
         });
-        mod_manager.load_resource_type("gui", |id, content| match content {
+        let tags = IdentifierTag::load_all(tags_json);
+        let recipes = Self::load_recipes(&mod_manager, &item_registry, &tags);
+        let gui_layouts = Self::load_gui_layouts(&mod_manager, &engine);
+        let commands = Self::load_commands(&mod_manager, &engine);
+        mod_manager.load_resource_type("world_generators", |id, content| match content {
             ContentType::Json(json) => {
-                gui_layouts.insert(id, Arc::new(GUILayout::from_json(json, &engine)));
+                match WorldGeneratorType::from_json(&json, biomes.clone(), &engine) {
+                    Ok(world_generator) => {
+                        world_generators.insert(id, world_generator);
+                    }
+                    Err(error) => logging::error(
+                        LogTarget::Server,
+                        format!("world generator {}: {}", id, error),
+                    ),
+                }
             }
             ContentType::Binary(_) => {}
         });
-        mod_manager.load_resource_type("tags", |id, content| match content {
+        let events = Self::load_events(&mod_manager);
+        mod_manager.load_resource_type("keybinds", |id, content| match content {
             ContentType::Json(json) => {
-                tags.insert(id, IdentifierTag::load(json));
+                match json["key"].as_str().and_then(KeyboardKey::from_name) {
+                    Some(key) => {
+                        keybinds.insert(id, key);
+                    }
+                    None => {
+                        logging::error(
+                            LogTarget::Server,
+                            format!("keybind {}: unknown key {}", id, json["key"]),
+                        );
+                    }
+                }
             }
             ContentType::Binary(_) => {}
         });
-        mod_manager.load_resource_type("world_generators", |id, content| match content {
-            ContentType::Json(json) => {
-                //world_generators.insert(id, WorldGeneratorType::from_json(json));
-                world_generators.insert(id, WorldGeneratorType::new(biomes.clone()));
-            }
+        let mut mod_kits = HashMap::new();
+        mod_manager.load_resource_type("kits", |id, content| match content {
+            ContentType::Json(json) => match Kit::from_json(&json, &item_registry) {
+                Ok(kit) => {
+                    mod_kits.insert(id.to_string(), Arc::new(kit));
+                }
+                Err(error) => {
+                    logging::error(LogTarget::Server, format!("kit {}: {}", id, error));
+                }
+            },
             ContentType::Binary(_) => {}
         });
-        mod_manager.load_resource_type("events", |id, content| match content {
-            ContentType::Json(_) => {}
-            ContentType::Binary(text) => {
-                let text = String::from_utf8(text).unwrap();
-                let (id, event) = text.split_once("\n").unwrap();
-                events.register(
-                    Identifier::parse(&id[1..]).unwrap(),
-                    ScriptCallback::new(Arc::new(
-                        bbscript::parse_source_file(event, Some(id.to_string().into()), 1)
-                            .unwrap()
-                            .remove(0),
-                    )),
-                );
-            }
-        });
+        let kits = KitManager::load(&save_directory, mod_kits, &item_registry);
         let mut client_content_data = ClientContentData {
             images: HashMap::new(),
             sounds: HashMap::new(),
             models: HashMap::new(),
+            particles: HashMap::new(),
         };
         mod_manager.load_resource_type("images", |id, content| match content {
             ContentType::Json(json) => {
@@ -413,11 +684,25 @@ impl Server {
                 client_content_data.models.insert(id, data);
             }
         });
+        mod_manager.load_resource_type("particles", |id, content| match content {
+            ContentType::Json(json) => {
+                client_content_data
+                    .particles
+                    .insert(id, serde_json::from_str(json.to_string().as_str()).unwrap());
+            }
+            ContentType::Binary(_) => {}
+        });
+        block_registry.freeze();
+        let search_index = SearchIndex::new(&item_registry, &block_registry, &tags);
         let client_content = {
             let client_content = registry::ClientContentGenerator::generate_zip(
                 &block_registry,
                 &item_registry,
                 &entity_registry,
+                biomes
+                    .iter()
+                    .map(|biome| biome.get_client_data().clone())
+                    .collect(),
                 client_content_data,
             );
             let hash = sha256::digest(client_content.as_slice());
@@ -428,42 +713,228 @@ impl Server {
             content.push("content.zip");
             fs::write(content, &client_content.0).unwrap();
         }
+        if let Err(error) = MigrationRegistry::new().upgrade_saves(&save_directory) {
+            logging::error(LogTarget::Server, error);
+            logging::error(
+                LogTarget::Server,
+                "server stopped because the save can't be loaded",
+            );
+            process::exit(1);
+        }
+        let settings = {
+            let mut path = save_directory.clone();
+            path.push("settings.txt");
+            let settings = ServerSettings::load_from_file(&path);
+            //identities can't be trusted without a secret, so one is generated on first start
+            if settings.get("server.auth_secret", "").is_empty() {
+                settings.set(
+                    "server.auth_secret",
+                    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()),
+                );
+                fs::write(&path, settings.save_to_string()).unwrap();
+                logging::info(LogTarget::Server, "generated server.auth_secret");
+            }
+            settings
+        };
+        let chunk_dictionary = {
+            let mut path = save_directory.clone();
+            path.push("chunk_dictionary.zstd");
+            ChunkDictionaryTrainer::load(
+                path,
+                settings.get("server.chunk_dictionary", "true") == "true",
+            )
+        };
         Arc::new_cyclic(|this| Server {
             this: this.clone(),
-            new_players: Mutex::new(Server::create_listener_thread(this.clone(), port)),
+            new_players: Mutex::new(Server::create_listener_thread(
+                this.clone(),
+                port,
+                settings.get("server.quic", "false") == "true",
+            )),
             worlds: Mutex::new(FxHashMap::default()),
             item_registry,
             entity_registry,
             mods: Mutex::new(mod_manager),
             client_content,
             thread_pool: ThreadPool::new(4),
+            worldgen_pool: ThreadPool::new(
+                settings.get_i64("server.worldgen_threads", 2).max(1) as u32
+            ),
             block_registry,
             structures,
-            recipes: RecipeManager::new(recipes),
-            events,
+            recipes: Mutex::new(Arc::new(RecipeManager::new(recipes))),
+            events: Mutex::new(Arc::new(events)),
+            event_tracer: EventTracer::new(),
             script_environment: {
                 ModManager::runtime_engine_load(&mut engine, this.clone());
                 engine
             },
-            settings: {
-                let path = {
-                    let mut path = save_directory.clone();
-                    path.push("settings.txt");
-                    path
-                };
-                if path.exists() {
-                    ServerSettings::load_from_string(fs::read_to_string(path).unwrap())
-                } else {
-                    ServerSettings::new()
-                }
-            },
+            settings,
+            access_list: AccessList::load(&save_directory),
+            permissions: PermissionManager::load(&save_directory),
+            commands: Mutex::new(Arc::new(CommandRegistry::new(commands))),
+            console: Console::start(),
+            game_rules: GameRules::new(),
+            metrics: Metrics::new(),
+            scheduler: Scheduler::new(),
+            shutdown: ShutdownManager::new(),
+            autosave_timer: AtomicU64::new(0),
             save_directory,
             players: Mutex::new(Vec::new()),
-            gui_layouts,
+            gui_layouts: Mutex::new(gui_layouts),
+            fluids,
             tags,
             world_generators,
+            keybinds,
+            search_index,
+            kits,
+            chunk_dictionary,
         })
     }
+    fn load_events(mod_manager: &ModManager) -> EventManager {
+        let mut events = EventManager::new();
+        mod_manager.load_resource_type("events", |id, content| match content {
+            ContentType::Json(_) => {}
+            ContentType::Binary(text) => {
+                let text = String::from_utf8(text).unwrap();
+                let mod_name = id.get_namespace().to_string();
+                let (id, event) = text.split_once("\n").unwrap();
+                events.register(
+                    Identifier::parse(&id[1..]).unwrap(),
+                    mod_name,
+                    ScriptCallback::new(Arc::new(
+                        bbscript::parse_source_file(event, Some(id.to_string().into()), 1)
+                            .unwrap()
+                            .remove(0),
+                    )),
+                );
+            }
+        });
+        events
+    }
+    fn load_recipes(
+        mod_manager: &ModManager,
+        item_registry: &ItemRegistry,
+        tags: &HashMap<Identifier, Arc<IdentifierTag>>,
+    ) -> HashMap<Identifier, Arc<Recipe>> {
+        let mut recipes = HashMap::new();
+        mod_manager.load_resource_type("recipes", |id, content| match content {
+            ContentType::Json(json) => {
+                match Recipe::from_json(id.clone(), json, item_registry, tags) {
+                    Ok(recipe) => {
+                        recipes.insert(id, Arc::new(recipe));
+                    }
+                    Err(error) => logging::error(
+                        LogTarget::Server,
+                        format!("recipe {} not loaded: {}", id, error),
+                    ),
+                }
+            }
+            ContentType::Binary(_) => {}
+        });
+        recipes
+    }
+    fn load_gui_layouts(
+        mod_manager: &ModManager,
+        engine: &ExecutionEnvironment,
+    ) -> HashMap<Identifier, Arc<GUILayout>> {
+        let mut gui_layouts = HashMap::new();
+        mod_manager.load_resource_type("gui", |id, content| match content {
+            ContentType::Json(json) => match GUILayout::from_json(json, engine) {
+                Ok(layout) => {
+                    gui_layouts.insert(id, Arc::new(layout));
+                }
+                Err(error) => {
+                    logging::error(LogTarget::Server, format!("gui layout {}: {}", id, error))
+                }
+            },
+            ContentType::Binary(_) => {}
+        });
+        gui_layouts
+    }
+    //commands/give_all.json in any mod adds /give_all, a later mod replaces a command with the same name
+    fn load_commands(
+        mod_manager: &ModManager,
+        engine: &ExecutionEnvironment,
+    ) -> HashMap<String, ModCommand> {
+        let mut commands = HashMap::new();
+        mod_manager.load_resource_type("commands", |id, content| match content {
+            ContentType::Json(json) => {
+                let command = ModCommand::from_json(&id, json, engine);
+                if commands.insert(id.get_key().to_string(), command).is_some() {
+                    logging::warn(
+                        LogTarget::Server,
+                        format!("command /{} was replaced by {}", id.get_key(), id),
+                    );
+                }
+            }
+            ContentType::Binary(_) => {}
+        });
+        commands
+    }
+    //scripts, events, recipes, gui layouts and commands are swapped together, or nothing changes when any of them fails
+    pub fn reload(&self) -> Result<String, String> {
+        let mut mods = self.mods.lock();
+        let scripts = mods.compile_scripts().map_err(|errors| {
+            for error in &errors {
+                logging::error(
+                    LogTarget::Scripts,
+                    format!("script error at {}: {:?}", error.0, error.1),
+                );
+            }
+            format!(
+                "reload cancelled, {} scripts failed to compile",
+                errors.len()
+            )
+        })?;
+        let script_count = scripts.len();
+        //layouts look up script functions while loading, so the new ones have to be in place already
+        let previous_scripts = mods.replace_scripts(&self.script_environment, scripts);
+        //broken content panics while loading, which cancels the reload instead of stopping the server
+        let content = panic::catch_unwind(AssertUnwindSafe(|| {
+            (
+                Self::load_events(&mods),
+                Self::load_recipes(&mods, &self.item_registry, &self.tags),
+                Self::load_gui_layouts(&mods, &self.script_environment),
+                Self::load_commands(&mods, &self.script_environment),
+            )
+        }));
+        let (events, recipes, gui_layouts, commands) = match content {
+            Ok(content) => content,
+            Err(_) => {
+                mods.replace_scripts(&self.script_environment, previous_scripts);
+                logging::error(
+                    LogTarget::Server,
+                    "reload cancelled, mod content failed to load",
+                );
+                return Err("reload cancelled, mod content failed to load".to_string());
+            }
+        };
+        let recipe_count = recipes.len();
+        let gui_layout_count = gui_layouts.len();
+        let command_count = commands.len();
+        *self.events.lock() = Arc::new(events);
+        *self.recipes.lock() = Arc::new(RecipeManager::new(recipes));
+        *self.gui_layouts.lock() = gui_layouts;
+        *self.commands.lock() = Arc::new(CommandRegistry::new(commands));
+        logging::info(LogTarget::Server, "mods reloaded");
+        Ok(format!(
+            "reloaded {} script functions, {} recipes, {} gui layouts and {} commands",
+            script_count, recipe_count, gui_layout_count, command_count
+        ))
+    }
+    pub fn get_events(&self) -> Arc<EventManager> {
+        self.events.lock().clone()
+    }
+    pub fn get_recipes(&self) -> Arc<RecipeManager> {
+        self.recipes.lock().clone()
+    }
+    pub fn get_commands(&self) -> Arc<CommandRegistry> {
+        self.commands.lock().clone()
+    }
+    pub fn get_gui_layout(&self, id: &Identifier) -> Option<Arc<GUILayout>> {
+        self.gui_layouts.lock().get(id).cloned()
+    }
     pub fn export_file(&self, filename: String, data: Vec<u8>) {
         let path = {
             let mut path = self.save_directory.clone();
@@ -481,13 +952,40 @@ impl Server {
         if let Some(world) = worlds.get(&identifier) {
             return world.clone();
         }
+        logging::info(
+            LogTarget::Worldgen,
+            format!("creating world {} using {}", identifier, world_generator),
+        );
+        let path = World::get_path(&self.save_directory, &identifier);
+        let mut metadata = WorldMetadata::load(&path).unwrap_or_else(|| {
+            //worlds from before metadata files were all generated with seed 1
+            let seed = if path.exists() {
+                1
+            } else {
+                thread_rng().gen::<u32>() as u64
+            };
+            WorldMetadata::new(seed, world_generator.clone())
+        });
+        //a world keeps the generator it was created with
+        let generator_type = match self.world_generators.get(&metadata.generator) {
+            Some(generator_type) => generator_type.clone(),
+            None => {
+                logging::warn(
+                    LogTarget::Worldgen,
+                    format!(
+                        "world generator {} of world {} is missing, using {}",
+                        metadata.generator, identifier, world_generator
+                    ),
+                );
+                metadata.generator = world_generator.clone();
+                self.world_generators.get(&world_generator).unwrap().clone()
+            }
+        };
         let world = World::new(
             self.this.upgrade().unwrap(),
-            WorldGenerator::new(
-                1,
-                self.world_generators.get(&world_generator).unwrap().clone(),
-            ),
+            WorldGenerator::new(metadata.seed, generator_type),
             identifier.clone(),
+            metadata,
         );
         worlds.insert(identifier, world.clone());
         world
@@ -496,75 +994,269 @@ impl Server {
         let worlds = self.worlds.lock();
         worlds.get(&identifier).map(|world| world.clone())
     }
+    pub fn drop_experience(&self, location: &Location, amount: u32) {
+        let entity_type = self
+            .settings
+            .get("server.experience_orb_entity", "bb:experience_orb");
+        let entity_type = match Identifier::parse(entity_type.as_str()) {
+            Ok(entity_type) => entity_type,
+            Err(_) => {
+                logging::error(
+                    LogTarget::Server,
+                    format!(
+                        "server.experience_orb_entity {} is not a valid id",
+                        entity_type
+                    ),
+                );
+                return;
+            }
+        };
+        if let Some(entity_type) = self.entity_registry.entity_by_identifier(&entity_type) {
+            let entity = Entity::new(location, entity_type);
+            entity.experience.store(amount, Ordering::Relaxed);
+        }
+    }
+    pub fn drop_item(&self, location: &Location, item: ItemStack) -> Result<(), String> {
+        let entity_type = self.settings.get("server.item_entity", "core:item");
+        let entity_type = Identifier::parse(entity_type.as_str())
+            .map_err(|_| format!("server.item_entity {} is not a valid id", entity_type))?;
+        let entity_type = self
+            .entity_registry
+            .entity_by_identifier(&entity_type)
+            .ok_or_else(|| format!("item entity {} doesn't exist", entity_type))?;
+        let entity = Entity::new(location, entity_type);
+        entity
+            .inventory
+            .get_full_view()
+            .set_item(0, Some(item))
+            .map_err(|_| format!("item entity {} has no inventory", entity_type.id))
+    }
     pub fn call_event(&self, id: Identifier, event_data: Variant) {
-        self.events
-            .call_event(id, event_data, &self.script_environment)
+        self.metrics.time(TickPhase::Scripts, || {
+            self.event_tracer.trace(id.clone(), || {
+                self.get_events().call_event(
+                    id,
+                    event_data,
+                    &self.script_environment,
+                    &self.metrics,
+                )
+            })
+        })
     }
-    pub fn tick(&self) {
-        while let Ok(connection) = self.new_players.lock().try_recv() {
-            let player = {
-                let mut event_data: HashMap<ImmutableString, Variant> = HashMap::new();
-                let event_data = Arc::new(Mutex::new(event_data)).into_variant();
-                self.call_event(
-                    Identifier::new("bb", "player_spawn_info"),
-                    event_data.clone(),
-                );
-                let event_data = SharedMap::from_variant(&event_data).unwrap();
-                let entity_type = Identifier::parse(
-                    ImmutableString::from_variant(
-                        &event_data.lock().remove("entity_type").unwrap(),
-                    )
-                    .unwrap()
-                    .as_ref(),
+    pub fn try_call_event(
+        &self,
+        id: Identifier,
+        event_data: Variant,
+    ) -> Result<(), (String, ScriptError)> {
+        self.metrics.time(TickPhase::Scripts, || {
+            self.event_tracer.trace(id.clone(), || {
+                self.get_events().try_call_event(
+                    id,
+                    event_data,
+                    &self.script_environment,
+                    &self.metrics,
                 )
-                .unwrap();
-                let location =
-                    Location::from_variant(&event_data.lock().remove("location").unwrap())
-                        .unwrap()
-                        .clone();
-                let entity = Entity::new(
-                    &location,
-                    self.entity_registry
-                        .entity_by_identifier(&entity_type)
-                        .unwrap(),
-                );
-
-                let player = PlayerData::new(connection, self.ptr(), entity);
-                self.players.lock().push(player.clone());
-
-                player
-            };
-            {
-                let mut event_data = HashMap::new();
-                event_data.insert("player".into(), player.into_variant());
-                let event_data: SharedMap = Arc::new(Mutex::new(event_data));
-                self.call_event(
-                    Identifier::new("bb", "player_join"),
-                    event_data.into_variant(),
-                );
+            })
+        })
+    }
+    fn get_spawn_info(&self) -> Result<(Arc<EntityType>, Location), JoinFailure> {
+        let event_data: HashMap<ImmutableString, Variant> = HashMap::new();
+        let event_data = Arc::new(Mutex::new(event_data)).into_variant();
+        self.try_call_event(
+            Identifier::new("bb", "player_spawn_info"),
+            event_data.clone(),
+        )
+        .map_err(|(mod_name, error)| JoinFailure::ScriptError {
+            mod_name,
+            error: error.to_string(),
+        })?;
+        let event_data = SharedMap::from_variant(&event_data).unwrap();
+        let entity_type = event_data
+            .lock()
+            .remove("entity_type")
+            .and_then(|entity_type| {
+                ImmutableString::from_variant(&entity_type).map(|id| id.to_string())
+            })
+            .ok_or(JoinFailure::InvalidSpawnInfo("entity_type".to_string()))?;
+        let entity_type = Identifier::parse(entity_type.as_str())
+            .ok()
+            .and_then(|id| self.entity_registry.entity_by_identifier(&id).cloned())
+            .ok_or(JoinFailure::MissingEntityType(entity_type))?;
+        let location = event_data
+            .lock()
+            .remove("location")
+            .and_then(|location| Location::from_variant(&location).cloned())
+            .ok_or(JoinFailure::InvalidSpawnInfo("location".to_string()))?;
+        Ok((entity_type, location))
+    }
+    fn join_player(&self, mut connection: PlayerConnection) {
+        let identity = connection.identity.clone().unwrap();
+        let reject = |connection: &mut PlayerConnection, failure: JoinFailure| {
+            logging::error(
+                LogTarget::Net,
+                format!(
+                    "{} ({}) couldn't join: {}",
+                    identity.username,
+                    identity.uuid,
+                    failure.describe()
+                ),
+            );
+            connection.reject(failure);
+        };
+        if self
+            .players
+            .lock()
+            .iter()
+            .any(|player| player.identity.uuid == identity.uuid)
+        {
+            reject(&mut connection, JoinFailure::AlreadyConnected);
+            return;
+        }
+        let (entity_type, mut location) = match self.get_spawn_info() {
+            Ok(spawn_info) => spawn_info,
+            Err(failure) => {
+                reject(&mut connection, failure);
+                return;
+            }
+        };
+        let save_data = PlayerData::load_save_data(self, &identity.uuid);
+        if let Some(save_data) = &save_data {
+            if let Some(world) = self.worlds.lock().get(&save_data.world) {
+                location = Location {
+                    position: save_data.position,
+                    world: world.clone(),
+                };
             }
         }
-        for player in &*self.players.lock() {
-            player.tick();
+        let entity = Entity::new(&location, &entity_type);
+        let player = PlayerData::new(connection, self.ptr(), entity);
+        if let Some(save_data) = save_data {
+            player.apply_save_data(save_data);
         }
+        self.players.lock().push(player.clone());
+        let mut event_data = HashMap::new();
+        event_data.insert("player".into(), player.clone().into_variant());
+        let event_data: SharedMap = Arc::new(Mutex::new(event_data));
+        if let Err((mod_name, error)) = self.try_call_event(
+            Identifier::new("bb", "player_join"),
+            event_data.into_variant(),
+        ) {
+            //the player was never fully joined, so it is dropped without saving
+            self.players
+                .lock()
+                .retain(|other| !Arc::ptr_eq(other, &player));
+            player.destroy();
+            reject(
+                &mut player.connection.lock(),
+                JoinFailure::ScriptError {
+                    mod_name,
+                    error: error.to_string(),
+                },
+            );
+        }
+    }
+    pub fn tick(&self) {
+        while let Ok(connection) = self.new_players.lock().try_recv() {
+            self.join_player(connection);
+        }
+        self.console.tick(self);
+        let autosave_interval = self
+            .settings
+            .get_i64("server.player_autosave_interval", 1200)
+            .max(0) as u64;
+        let autosave = autosave_interval > 0
+            && (self.autosave_timer.fetch_add(1, Ordering::Relaxed) + 1) % autosave_interval == 0;
+        self.metrics.time(TickPhase::Players, || {
+            for player in &*self.players.lock() {
+                player.tick();
+                if autosave {
+                    player.save();
+                }
+            }
+        });
+        self.metrics.time(TickPhase::Worlds, || {
+            for world in self.worlds.lock().values() {
+                world.tick();
+                if autosave {
+                    world.save();
+                }
+            }
+        });
+        self.metrics.time(TickPhase::Scripts, || {
+            self.scheduler.tick(&self.script_environment)
+        });
+        self.shutdown.tick(self);
+        self.tick_sleeping();
         for world in self.worlds.lock().values() {
-            world.tick();
+            world.flush_block_updates();
         }
-        self.worlds
+        for (_, world) in self
+            .worlds
             .lock()
             .extract_if(|_, world| world.should_unload())
-            .count();
-        self.players
+        {
+            world.destroy();
+        }
+        for player in self
+            .players
             .lock()
             .extract_if(|player| player.connection.lock().is_closed())
-            .count();
+        {
+            player.save();
+        }
+    }
+    fn tick_sleeping(&self) {
+        let threshold = self.settings.get_f64("server.sleep_threshold", 1.);
+        let players = self.players.lock().clone();
+        let worlds: Vec<_> = self.worlds.lock().values().cloned().collect();
+        for world in worlds {
+            let world_players: Vec<_> = players
+                .iter()
+                .filter(|player| {
+                    Arc::ptr_eq(&player.get_entity().get_location().chunk.world, &world)
+                })
+                .collect();
+            let sleeping = world_players
+                .iter()
+                .filter(|player| player.is_sleeping())
+                .count();
+            if sleeping == 0 || (sleeping as f64) < world_players.len() as f64 * threshold {
+                world.sleep_ticks.store(0, Ordering::Relaxed);
+                continue;
+            }
+            if world.sleep_ticks.fetch_add(1, Ordering::Relaxed) + 1 < World::SLEEP_SKIP_DELAY {
+                continue;
+            }
+            world.sleep_ticks.store(0, Ordering::Relaxed);
+            world.skip_to_morning();
+            {
+                let mut event_data = HashMap::new();
+                event_data.insert("world".into(), world.clone().into_variant());
+                let event_data: SharedMap = Arc::new(Mutex::new(event_data));
+                self.call_event(
+                    Identifier::new("bb", "sleep_skip"),
+                    event_data.into_variant(),
+                );
+            }
+            for player in world_players {
+                player.wake_up();
+            }
+        }
     }
     pub fn wait_for_tasks(&self) {
         while !self.thread_pool.all_tasks_finished() {
             thread::yield_now();
         }
     }
+    pub fn wait_for_generation(&self) {
+        while !self.worldgen_pool.all_tasks_finished() {
+            thread::yield_now();
+        }
+    }
     pub fn destroy(&self) {
+        for player in self.players.lock().drain(..) {
+            player.save();
+        }
         for world in self.worlds.lock().drain() {
             world.1.destroy();
         }
@@ -578,8 +1270,25 @@ impl Server {
         )
         .unwrap();
     }
-    fn create_listener_thread(game_server: Weak<Server>, port: u16) -> Receiver<PlayerConnection> {
+    fn create_listener_thread(
+        game_server: Weak<Server>,
+        port: u16,
+        quic: bool,
+    ) -> Receiver<PlayerConnection> {
         let (tx, rx) = crossbeam_channel::unbounded();
+        if quic {
+            let (quic_tx, quic_rx) = crossbeam_channel::unbounded();
+            net::listen_quic(port, quic_tx);
+            let game_server = game_server.clone();
+            let tx = tx.clone();
+            spawn(move || {
+                for transport in quic_rx {
+                    let tx = tx.clone();
+                    let server = game_server.upgrade().unwrap();
+                    spawn(move || Server::accept_connection(server, transport, tx));
+                }
+            });
+        }
         spawn(move || {
             let server = TcpListener::bind(("0.0.0.0", port)).unwrap();
             for stream in server.incoming() {
@@ -588,28 +1297,104 @@ impl Server {
                     let server = game_server.upgrade().unwrap();
                     spawn(move || {
                         let websocket = tungstenite::accept(stream).unwrap();
-                        let player_connection = PlayerConnection::new(websocket);
-                        if let Ok(mut connection) = player_connection {
-                            match connection.1 {
-                                0 => tx.send(connection.0).unwrap(),
-                                1 => {
-                                    let json = object! {
-                                        motd: server.settings.get("server.motd", "test server").clone(),
-                                        time: SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis().to_string(),
-                                        client_content_hash: server.client_content.1.clone()
-                                    };
-                                    connection.0.send_json(json);
-                                }
-                                2 => connection.0.send_binary(&server.client_content.0),
-                                _ => {}
-                            }
-                        }
+                        Server::accept_connection(server, Transport::WebSocket(websocket), tx);
                     });
                 }
             }
         });
         rx
     }
+    fn accept_connection(server: Arc<Server>, transport: Transport, tx: Sender<PlayerConnection>) {
+        let address = transport.get_address();
+        let player_connection = PlayerConnection::new(transport);
+        if let Ok(mut connection) = player_connection {
+            match connection.1 {
+                0 => {
+                    if let Err(reason) = connection.0.check_protocol() {
+                        if let Some(address) = &address {
+                            logging::info(
+                                LogTarget::Net,
+                                format!("rejected {}: {}", address, reason),
+                            );
+                        }
+                        connection.0.disconnect(reason);
+                        return;
+                    }
+                    let identity = connection.0.identity.clone().unwrap();
+                    if let Err(reason) = server.access_list.check(
+                        &identity,
+                        connection.0.get_token(),
+                        server.settings.get("server.whitelist", "false") == "true",
+                        server.settings.get("server.require_auth", "false") == "true",
+                        server.settings.get("server.auth_secret", "").as_str(),
+                    ) {
+                        logging::info(
+                            LogTarget::Net,
+                            format!(
+                                "rejected {} ({}): {}",
+                                identity.username, identity.uuid, reason
+                            ),
+                        );
+                        connection.0.disconnect(reason);
+                        return;
+                    }
+                    if server.shutdown.is_joining_blocked() {
+                        connection
+                            .0
+                            .disconnect("server is shutting down".to_string());
+                        return;
+                    }
+                    connection.0.negotiate_compression(
+                        if server.settings.get("server.chunk_compression", "zstd") == "gzip" {
+                            ChunkCompression::Gzip
+                        } else {
+                            ChunkCompression::Zstd
+                        },
+                        server.settings.get_i64("server.chunk_compression_level", 3) as i32,
+                    );
+                    if let Some(address) = address {
+                        logging::info(
+                            LogTarget::Net,
+                            format!(
+                                "player {} ({}) connected from {}",
+                                identity.username, identity.uuid, address
+                            ),
+                        );
+                    }
+                    connection.0.welcome();
+                    tx.send(connection.0).unwrap()
+                }
+                1 => {
+                    let json = object! {
+                        motd: server.settings.get("server.motd", "test server").clone(),
+                        time: SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis().to_string(),
+                        protocol: PROTOCOL_VERSION,
+                        client_content_hash: server.client_content.1.clone()
+                    };
+                    connection.0.send_json(json);
+                }
+                2 => connection.0.send_binary(&server.client_content.0),
+                _ => {}
+            }
+        } else {
+            logging::warn(LogTarget::Net, "received invalid handshake");
+        }
+    }
+    pub fn kick(&self, player: &PlayerData, reason: String) {
+        logging::info(
+            LogTarget::Net,
+            format!("kicked {}: {}", player.identity.username, reason),
+        );
+        player.connection.lock().disconnect(reason);
+    }
+    pub fn ban(&self, uuid: Uuid) {
+        self.access_list.ban(uuid);
+        for player in &*self.players.lock() {
+            if player.identity.uuid == uuid {
+                self.kick(player, "you are banned from this server".to_string());
+            }
+        }
+    }
     pub fn ptr(&self) -> Arc<Server> {
         self.this.upgrade().unwrap()
     }
@@ -628,6 +1413,113 @@ impl ScriptingObject for Server {
                     .collect::<bbscript::variant::SharedArray>())
             });
         }
+        {
+            let server = server.clone();
+            env.register_function(
+                "kick",
+                move |player: &Arc<PlayerData>, reason: &ImmutableString| {
+                    server.upgrade().unwrap().kick(player, reason.to_string());
+                    Ok(())
+                },
+            );
+        }
+        {
+            let server = server.clone();
+            env.register_function("ban", move |uuid: &ImmutableString| {
+                let uuid = Uuid::parse_str(uuid.as_ref())
+                    .map_err(|_| ScriptError::runtime("malformed uuid", FilePosition::INVALID))?;
+                server.upgrade().unwrap().ban(uuid);
+                Ok(())
+            });
+        }
+        {
+            let server = server.clone();
+            env.register_function("unban", move |uuid: &ImmutableString| {
+                let uuid = Uuid::parse_str(uuid.as_ref())
+                    .map_err(|_| ScriptError::runtime("malformed uuid", FilePosition::INVALID))?;
+                server.upgrade().unwrap().access_list.unban(uuid);
+                Ok(())
+            });
+        }
+        {
+            let server = server.clone();
+            env.register_function(
+                "create_token",
+                move |username: &ImmutableString, uuid: &ImmutableString| {
+                    let uuid = Uuid::parse_str(uuid.as_ref()).map_err(|_| {
+                        ScriptError::runtime("malformed uuid", FilePosition::INVALID)
+                    })?;
+                    Ok(ImmutableString::from(
+                        PlayerIdentity::create_token(
+                            server
+                                .upgrade()
+                                .unwrap()
+                                .settings
+                                .get("server.auth_secret", "")
+                                .as_str(),
+                            username.as_ref(),
+                            &uuid,
+                        )
+                        .as_str(),
+                    ))
+                },
+            );
+        }
+        {
+            let server = server.clone();
+            env.register_function("whitelist", move |entry: &ImmutableString, add: &bool| {
+                server
+                    .upgrade()
+                    .unwrap()
+                    .access_list
+                    .whitelist(entry.to_string(), *add);
+                Ok(())
+            });
+        }
+        {
+            let server = server.clone();
+            env.register_function("op", move |entry: &ImmutableString, add: &bool| {
+                server
+                    .upgrade()
+                    .unwrap()
+                    .access_list
+                    .set_operator(entry.to_string(), *add);
+                Ok(())
+            });
+        }
+        {
+            let server = server.clone();
+            env.register_function(
+                "register_gamerule",
+                move |name: &ImmutableString, default: &Variant| {
+                    let default = GameRuleValue::from_variant(default).ok_or_else(|| {
+                        ScriptError::runtime(
+                            "gamerule default must be a bool or number",
+                            FilePosition::INVALID,
+                        )
+                    })?;
+                    server
+                        .upgrade()
+                        .unwrap()
+                        .game_rules
+                        .register(name.to_string(), default);
+                    Ok(())
+                },
+            );
+        }
+        {
+            let server = server.clone();
+            env.register_function(
+                "drop_experience",
+                move |location: &Location, amount: &i64| {
+                    server
+                        .upgrade()
+                        .unwrap()
+                        .drop_experience(location, (*amount).max(0) as u32);
+                    Ok(())
+                },
+            );
+        }
     }
 }
 pub struct ServerSettings {
@@ -672,6 +1564,36 @@ impl ServerSettings {
             .parse()
             .unwrap_or(default)
     }
+    pub fn set(&self, key: &str, value: String) {
+        self.settings.lock().insert(key.to_string(), value);
+    }
+    //keys are returned without the prefix, sorted
+    pub fn get_prefixed(&self, prefix: &str) -> Vec<(String, String)> {
+        let settings = self.settings.lock();
+        let mut entries: Vec<_> = settings
+            .iter()
+            .filter_map(|(key, value)| {
+                key.strip_prefix(prefix)
+                    .map(|key| (key.to_string(), value.clone()))
+            })
+            .collect();
+        entries.sort();
+        entries
+    }
+    pub fn remove_prefixed(&self, prefix: &str) -> Vec<(String, String)> {
+        let entries = self.get_prefixed(prefix);
+        self.settings
+            .lock()
+            .retain(|key, _| !key.starts_with(prefix));
+        entries
+    }
+    pub fn load_from_file(path: &Path) -> Self {
+        if path.exists() {
+            ServerSettings::load_from_string(fs::read_to_string(path).unwrap())
+        } else {
+            ServerSettings::new()
+        }
+    }
     pub fn save_to_string(&self) -> String {
         let mut output = String::new();
         let settings = self.settings.lock();
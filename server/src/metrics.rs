@@ -0,0 +1,438 @@
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Weak;
+use std::thread::{self, spawn};
+use std::time::{Duration, Instant, SystemTime};
+
+use json::{object, JsonValue};
+use parking_lot::Mutex;
+
+use crate::logging::{self, LogTarget};
+use crate::Server;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TickPhase {
+    Players,
+    Worlds,
+    ChunkIO,
+    Scripts,
+}
+impl TickPhase {
+    pub const ALL: [TickPhase; 4] = [
+        TickPhase::Players,
+        TickPhase::Worlds,
+        TickPhase::ChunkIO,
+        TickPhase::Scripts,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            TickPhase::Players => "players",
+            TickPhase::Worlds => "worlds",
+            TickPhase::ChunkIO => "chunk_io",
+            TickPhase::Scripts => "scripts",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct TickSample {
+    total: Duration,
+    phases: [Duration; 4],
+}
+
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+thread_local! {
+    static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+    //time spent in phases nested inside the phase currently timed on this thread
+    static NESTED_TIME: Cell<Duration> = Cell::new(Duration::ZERO);
+}
+
+struct TraceSpan {
+    name: String,
+    category: &'static str,
+    mod_name: Option<String>,
+    start: Duration,
+    duration: Duration,
+    thread: u64,
+}
+struct TraceCapture {
+    file_name: String,
+    start: Instant,
+    ticks_left: u32,
+    spans: Vec<TraceSpan>,
+    threads: HashMap<u64, String>,
+    dropped: u64,
+}
+impl TraceCapture {
+    const MAX_SPANS: usize = 1_000_000;
+    fn push(&mut self, span: TraceSpan) {
+        if self.spans.len() >= Self::MAX_SPANS {
+            self.dropped += 1;
+            return;
+        }
+        self.threads.entry(span.thread).or_insert_with(|| {
+            thread::current()
+                .name()
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| format!("thread {}", span.thread))
+        });
+        self.spans.push(span);
+    }
+    //chrome trace event format, speedscope and chrome://tracing both open it
+    fn export(&self) -> String {
+        let mut events = JsonValue::new_array();
+        for (thread, name) in &self.threads {
+            events
+                .push(object! {
+                    name: "thread_name",
+                    ph: "M",
+                    pid: 1,
+                    tid: *thread,
+                    args: object! {name: name.as_str()},
+                })
+                .unwrap();
+        }
+        for span in &self.spans {
+            let mut event = object! {
+                name: span.name.as_str(),
+                cat: span.category,
+                ph: "X",
+                ts: span.start.as_nanos() as f64 / 1000.,
+                dur: span.duration.as_nanos() as f64 / 1000.,
+                pid: 1,
+                tid: span.thread,
+            };
+            if let Some(mod_name) = &span.mod_name {
+                event["args"] = object! {"mod": mod_name.as_str()};
+            }
+            events.push(event).unwrap();
+        }
+        object! {
+            traceEvents: events,
+            displayTimeUnit: "ms",
+            otherData: object! {dropped_spans: self.dropped},
+        }
+        .dump()
+    }
+}
+
+pub struct Metrics {
+    samples: Mutex<VecDeque<TickSample>>,
+    current: Mutex<[Duration; 4]>,
+    total_ticks: AtomicU64,
+    tracing: AtomicBool,
+    trace: Mutex<Option<TraceCapture>>,
+}
+impl Metrics {
+    const MAX_SAMPLES: usize = 1200;
+    const SHORT_WINDOW: usize = 100;
+    pub const MAX_TRACE_TICKS: u32 = 1200;
+    pub fn new() -> Self {
+        Metrics {
+            samples: Mutex::new(VecDeque::new()),
+            current: Mutex::new([Duration::ZERO; 4]),
+            total_ticks: AtomicU64::new(0),
+            tracing: AtomicBool::new(false),
+            trace: Mutex::new(None),
+        }
+    }
+    pub fn record(&self, phase: TickPhase, duration: Duration) {
+        self.current.lock()[phase as usize] += duration;
+    }
+    pub fn time<F, R>(&self, phase: TickPhase, function: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let outer_nested = NESTED_TIME.with(|nested| nested.replace(Duration::ZERO));
+        let start = Instant::now();
+        let result = function();
+        let duration = start.elapsed();
+        //nested phases are only counted once, for the innermost one
+        let nested = NESTED_TIME.with(|nested| nested.replace(outer_nested + duration));
+        self.record(phase, duration.saturating_sub(nested));
+        if self.is_tracing() {
+            self.record_span(
+                phase.name().to_string(),
+                phase.name(),
+                None,
+                start,
+                duration,
+            );
+        }
+        result
+    }
+    pub fn is_tracing(&self) -> bool {
+        self.tracing.load(Ordering::Relaxed)
+    }
+    //the name is only built while a trace is captured
+    pub fn trace<N, F, R>(
+        &self,
+        category: &'static str,
+        name: N,
+        mod_name: Option<&str>,
+        function: F,
+    ) -> R
+    where
+        N: FnOnce() -> String,
+        F: FnOnce() -> R,
+    {
+        if !self.is_tracing() {
+            return function();
+        }
+        let start = Instant::now();
+        let result = function();
+        self.record_span(
+            name(),
+            category,
+            mod_name.map(|mod_name| mod_name.to_string()),
+            start,
+            start.elapsed(),
+        );
+        result
+    }
+    fn record_span(
+        &self,
+        name: String,
+        category: &'static str,
+        mod_name: Option<String>,
+        start: Instant,
+        duration: Duration,
+    ) {
+        if let Some(trace) = &mut *self.trace.lock() {
+            let span = TraceSpan {
+                name,
+                category,
+                mod_name,
+                start: start.saturating_duration_since(trace.start),
+                duration,
+                thread: THREAD_ID.with(|id| *id),
+            };
+            trace.push(span);
+        }
+    }
+    //returns the file name the trace will be saved as
+    pub fn start_trace(&self, ticks: u32) -> Result<String, ()> {
+        let mut trace = self.trace.lock();
+        if trace.is_some() {
+            return Err(());
+        }
+        let file_name = format!(
+            "trace_{}.json",
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        );
+        *trace = Some(TraceCapture {
+            file_name: file_name.clone(),
+            start: Instant::now(),
+            ticks_left: ticks.clamp(1, Self::MAX_TRACE_TICKS),
+            spans: Vec::new(),
+            threads: HashMap::new(),
+            dropped: 0,
+        });
+        self.tracing.store(true, Ordering::Relaxed);
+        Ok(file_name)
+    }
+    //returns the file name and content once the last tick of a trace finished
+    pub fn finish_tick(&self, total: Duration) -> Option<(String, String)> {
+        let phases = std::mem::replace(&mut *self.current.lock(), [Duration::ZERO; 4]);
+        {
+            let mut samples = self.samples.lock();
+            samples.push_back(TickSample { total, phases });
+            if samples.len() > Self::MAX_SAMPLES {
+                samples.pop_front();
+            }
+        }
+        self.total_ticks.fetch_add(1, Ordering::Relaxed);
+        if !self.is_tracing() {
+            return None;
+        }
+        let tick = self.total_ticks.load(Ordering::Relaxed);
+        self.record_span(
+            format!("tick {}", tick),
+            "tick",
+            None,
+            Instant::now() - total,
+            total,
+        );
+        let mut trace = self.trace.lock();
+        let capture = trace.as_mut().unwrap();
+        capture.ticks_left -= 1;
+        if capture.ticks_left > 0 {
+            return None;
+        }
+        self.tracing.store(false, Ordering::Relaxed);
+        trace
+            .take()
+            .map(|capture| (capture.file_name.clone(), capture.export()))
+    }
+    fn average(&self, window: usize) -> TickSample {
+        let samples = self.samples.lock();
+        let count = samples.len().min(window);
+        let mut average = TickSample::default();
+        if count == 0 {
+            return average;
+        }
+        for sample in samples.iter().rev().take(count) {
+            average.total += sample.total;
+            for (phase, duration) in average.phases.iter_mut().zip(sample.phases.iter()) {
+                *phase += *duration;
+            }
+        }
+        average.total /= count as u32;
+        for phase in &mut average.phases {
+            *phase /= count as u32;
+        }
+        average
+    }
+    fn tps_from_mspt(mspt: f64) -> f64 {
+        if mspt <= 50. {
+            20.
+        } else {
+            1000. / mspt
+        }
+    }
+    fn to_ms(duration: Duration) -> f64 {
+        duration.as_micros() as f64 / 1000.
+    }
+    pub fn get_summary(&self) -> String {
+        let short = self.average(Self::SHORT_WINDOW);
+        let long = self.average(Self::MAX_SAMPLES);
+        let mut summary = format!(
+            "tps (5s/1m): {:.1}/{:.1}, mspt: {:.2}",
+            Self::tps_from_mspt(Self::to_ms(short.total)),
+            Self::tps_from_mspt(Self::to_ms(long.total)),
+            Self::to_ms(short.total)
+        );
+        for phase in TickPhase::ALL {
+            write!(
+                summary,
+                ", {}: {:.2}",
+                phase.name(),
+                Self::to_ms(short.phases[phase as usize])
+            )
+            .unwrap();
+        }
+        summary
+    }
+    pub fn to_prometheus(&self, players: usize, worlds: usize) -> String {
+        let mut output = String::new();
+        output.push_str("# TYPE blockbyte_tps gauge\n");
+        output.push_str("# TYPE blockbyte_mspt gauge\n");
+        output.push_str("# TYPE blockbyte_phase_mspt gauge\n");
+        for (window, samples) in [("5s", Self::SHORT_WINDOW), ("1m", Self::MAX_SAMPLES)] {
+            let average = self.average(samples);
+            let mspt = Self::to_ms(average.total);
+            writeln!(
+                output,
+                "blockbyte_tps{{window=\"{}\"}} {}",
+                window,
+                Self::tps_from_mspt(mspt)
+            )
+            .unwrap();
+            writeln!(output, "blockbyte_mspt{{window=\"{}\"}} {}", window, mspt).unwrap();
+            for phase in TickPhase::ALL {
+                writeln!(
+                    output,
+                    "blockbyte_phase_mspt{{window=\"{}\",phase=\"{}\"}} {}",
+                    window,
+                    phase.name(),
+                    Self::to_ms(average.phases[phase as usize])
+                )
+                .unwrap();
+            }
+        }
+        output.push_str("# TYPE blockbyte_ticks_total counter\n");
+        writeln!(
+            output,
+            "blockbyte_ticks_total {}",
+            self.total_ticks.load(Ordering::Relaxed)
+        )
+        .unwrap();
+        output.push_str("# TYPE blockbyte_players gauge\n");
+        writeln!(output, "blockbyte_players {}", players).unwrap();
+        output.push_str("# TYPE blockbyte_worlds gauge\n");
+        writeln!(output, "blockbyte_worlds {}", worlds).unwrap();
+        output
+    }
+    pub fn world_generation_to_prometheus(server: &Server) -> String {
+        let mut output = String::new();
+        output.push_str("# TYPE blockbyte_worldgen_queued gauge\n");
+        output.push_str("# TYPE blockbyte_worldgen_generated_total counter\n");
+        output.push_str("# TYPE blockbyte_worldgen_average_ms gauge\n");
+        for (id, world) in server.worlds.lock().iter() {
+            let queue = &world.generation_queue;
+            writeln!(
+                output,
+                "blockbyte_worldgen_queued{{world=\"{}\"}} {}",
+                id,
+                queue.get_queued()
+            )
+            .unwrap();
+            writeln!(
+                output,
+                "blockbyte_worldgen_generated_total{{world=\"{}\"}} {}",
+                id,
+                queue.get_generated()
+            )
+            .unwrap();
+            writeln!(
+                output,
+                "blockbyte_worldgen_average_ms{{world=\"{}\"}} {}",
+                id,
+                Self::to_ms(queue.get_average_generation_time())
+            )
+            .unwrap();
+        }
+        output
+    }
+    pub fn start_http_endpoint(server: Weak<Server>, address: String, port: u16) {
+        let listener = match TcpListener::bind((address.as_str(), port)) {
+            Ok(listener) => listener,
+            Err(error) => {
+                logging::error(
+                    LogTarget::Server,
+                    format!(
+                        "could not start metrics endpoint on {}:{}: {}",
+                        address, port, error
+                    ),
+                );
+                return;
+            }
+        };
+        logging::info(
+            LogTarget::Server,
+            format!("metrics endpoint listening on {}:{}", address, port),
+        );
+        spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let server = match server.upgrade() {
+                    Some(server) => server,
+                    None => break,
+                };
+                //requests are served one at a time, so a silent client can't hold up the others for long
+                let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+                let _ = stream.set_write_timeout(Some(Duration::from_secs(5)));
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request);
+                let mut body = server
+                    .metrics
+                    .to_prometheus(server.players.lock().len(), server.worlds.lock().len());
+                body.push_str(&Metrics::world_generation_to_prometheus(&server));
+                let _ = stream.write_all(
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                    .as_bytes(),
+                );
+            }
+        });
+    }
+}
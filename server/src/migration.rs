@@ -0,0 +1,241 @@
+use std::path::{Path, PathBuf};
+
+use json::object;
+
+use crate::logging::{self, LogTarget};
+use crate::world::{Chunk, ChunkSaveData, PlayerSaveData};
+
+//bump when a save format changes and register a migration from the previous version for every affected kind
+pub const SAVE_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SaveKind {
+    Chunk,
+    Player,
+    WorldUserData,
+    WorldMetadata,
+    Settings,
+}
+impl SaveKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SaveKind::Chunk => "chunk",
+            SaveKind::Player => "player",
+            SaveKind::WorldUserData => "world user data",
+            SaveKind::WorldMetadata => "world metadata",
+            SaveKind::Settings => "settings",
+        }
+    }
+}
+
+//version of everything in a directory, saves from before versioning have no manifest and are version 0
+pub struct SaveManifest {
+    pub version: u32,
+}
+impl SaveManifest {
+    const FILE_NAME: &'static str = "save_version.json";
+    pub fn exists(directory: &Path) -> bool {
+        directory.join(Self::FILE_NAME).exists()
+    }
+    pub fn load(directory: &Path) -> Self {
+        let version = std::fs::read_to_string(directory.join(Self::FILE_NAME))
+            .ok()
+            .and_then(|manifest| json::parse(manifest.as_str()).ok())
+            .and_then(|manifest| manifest["version"].as_u32())
+            .unwrap_or(0);
+        SaveManifest { version }
+    }
+    pub fn save(&self, directory: &Path) {
+        let manifest = object! {
+            version: self.version
+        };
+        std::fs::write(directory.join(Self::FILE_NAME), manifest.pretty(4)).unwrap();
+    }
+    //new directories start at the current version, there is nothing to migrate in them
+    pub fn create_if_missing(directory: &Path) {
+        if !Self::exists(directory) {
+            SaveManifest {
+                version: SAVE_VERSION,
+            }
+            .save(directory);
+        }
+    }
+}
+
+struct Migration {
+    kind: SaveKind,
+    from: u32,
+    migrate: fn(Vec<u8>) -> Result<Vec<u8>, String>,
+}
+
+pub struct MigrationRegistry {
+    migrations: Vec<Migration>,
+}
+impl MigrationRegistry {
+    pub fn new() -> Self {
+        let mut registry = MigrationRegistry {
+            migrations: Vec::new(),
+        };
+        //version 1 added block and entity user data, fluids, energy, entity experience and item data to chunks and user data to players
+        registry.register(SaveKind::Chunk, 0, ChunkSaveData::upgrade_legacy);
+        registry.register(SaveKind::Player, 0, PlayerSaveData::upgrade_legacy);
+        registry
+    }
+    fn register(
+        &mut self,
+        kind: SaveKind,
+        from: u32,
+        migrate: fn(Vec<u8>) -> Result<Vec<u8>, String>,
+    ) {
+        self.migrations.push(Migration {
+            kind,
+            from,
+            migrate,
+        });
+    }
+    //runs every step from version up to SAVE_VERSION in order, kinds without a step for a version are unchanged by it
+    pub fn migrate(
+        &self,
+        kind: SaveKind,
+        version: u32,
+        mut data: Vec<u8>,
+    ) -> Result<Vec<u8>, String> {
+        for from in version..SAVE_VERSION {
+            if let Some(migration) = self
+                .migrations
+                .iter()
+                .find(|migration| migration.kind == kind && migration.from == from)
+            {
+                data = (migration.migrate)(data).map_err(|error| {
+                    format!(
+                        "{} migration from version {} failed: {}",
+                        kind.name(),
+                        from,
+                        error
+                    )
+                })?;
+            }
+        }
+        Ok(data)
+    }
+    //upgrades the whole save before anything is loaded, refuses saves written by a newer server
+    pub fn upgrade_saves(&self, save_directory: &Path) -> Result<(), String> {
+        self.upgrade_directory(save_directory, "server", |directory| {
+            let mut files = vec![(SaveKind::Settings, directory.join("settings.txt"))];
+            files.extend(
+                list_files(&directory.join("players"))
+                    .into_iter()
+                    .filter(|path| {
+                        path.extension()
+                            .map_or(false, |extension| extension == "bin")
+                    })
+                    .map(|path| (SaveKind::Player, path)),
+            );
+            files
+        })?;
+        for world in list_files(&save_directory.join("worlds")) {
+            if !world.is_dir() {
+                continue;
+            }
+            let name = format!("world {}", world.file_name().unwrap().to_string_lossy());
+            self.upgrade_directory(&world, name.as_str(), |directory| {
+                let mut files = vec![
+                    (SaveKind::Settings, directory.join("settings.txt")),
+                    (SaveKind::WorldUserData, directory.join("user_data.bin")),
+                    (SaveKind::WorldMetadata, directory.join("world.json")),
+                ];
+                files.extend(
+                    list_files(directory)
+                        .into_iter()
+                        .filter(|path| {
+                            path.file_name()
+                                .map_or(false, |name| name.to_string_lossy().starts_with("chunk"))
+                                && path.extension().map_or(false, |extension| {
+                                    extension == "bws" || extension == "bak"
+                                })
+                        })
+                        .map(|path| (SaveKind::Chunk, path)),
+                );
+                files
+            })?;
+        }
+        Ok(())
+    }
+    fn upgrade_directory<F>(&self, directory: &Path, name: &str, files: F) -> Result<(), String>
+    where
+        F: FnOnce(&Path) -> Vec<(SaveKind, PathBuf)>,
+    {
+        let files: Vec<_> = files(directory)
+            .into_iter()
+            .filter(|(_, path)| path.exists())
+            .collect();
+        if !SaveManifest::exists(directory) && files.is_empty() {
+            SaveManifest::create_if_missing(directory);
+            return Ok(());
+        }
+        let manifest = SaveManifest::load(directory);
+        if manifest.version > SAVE_VERSION {
+            return Err(format!(
+                "{} was saved by a newer server (save version {}), this server supports up to save version {}",
+                name, manifest.version, SAVE_VERSION
+            ));
+        }
+        if manifest.version == SAVE_VERSION {
+            return Ok(());
+        }
+        logging::info(
+            LogTarget::Server,
+            format!(
+                "upgrading {} from save version {} to {}",
+                name, manifest.version, SAVE_VERSION
+            ),
+        );
+        for (kind, path) in files {
+            //chunks that fail their checksum are left for the chunk loader to quarantine
+            let data = match kind {
+                SaveKind::Chunk => match Chunk::read_checked(&path) {
+                    Some(data) => data,
+                    None => continue,
+                },
+                _ => std::fs::read(&path)
+                    .map_err(|error| format!("{}: {}", path.display(), error))?,
+            };
+            let data = match self.migrate(kind, manifest.version, data) {
+                Ok(data) => data,
+                //unreadable chunks are quarantined when they load, like ones failing their checksum
+                Err(error) if kind == SaveKind::Chunk => {
+                    logging::warn(
+                        LogTarget::Server,
+                        format!("could not upgrade {}: {}", path.display(), error),
+                    );
+                    continue;
+                }
+                Err(error) => return Err(format!("{}: {}", path.display(), error)),
+            };
+            let data = match kind {
+                SaveKind::Chunk => Chunk::with_checksum(data),
+                _ => data,
+            };
+            //chunk backups are upgraded on their own, so files are replaced without rotating them
+            let temp_path = path.with_extension("migrating");
+            std::fs::write(&temp_path, data)
+                .and_then(|_| std::fs::rename(&temp_path, &path))
+                .map_err(|error| format!("{}: {}", path.display(), error))?;
+        }
+        SaveManifest {
+            version: SAVE_VERSION,
+        }
+        .save(directory);
+        Ok(())
+    }
+}
+
+fn list_files(directory: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(directory)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -1,4 +1,5 @@
 use anyhow::{anyhow, Context, Result};
+use bbscript::debug;
 use bbscript::eval::{ExecutionEnvironment, Function, ScopeStack, ScriptError, ScriptResult};
 use bbscript::lex::FilePosition;
 use bbscript::variant::{
@@ -6,8 +7,8 @@ use bbscript::variant::{
     SharedMap, TypeName, Variant,
 };
 use block_byte_common::content::{
-    ClientAnimatedTexture, ClientBlockData, ClientBlockRenderDataType, ClientModel, ClientTexture,
-    Transformation,
+    BlockTint, ClientAnimatedTexture, ClientBlockData, ClientBlockRenderDataType, ClientModel,
+    ClientParticleData, ClientTexture, StepParticles, Transformation,
 };
 use block_byte_common::gui::PositionAnchor;
 use block_byte_common::messages::MovementType;
@@ -39,10 +40,21 @@ use strum::IntoEnumIterator;
 use uuid::Uuid;
 use walkdir::WalkDir;
 
+use crate::explosion::Explosion;
+use crate::fluid::FluidStack;
 use crate::inventory::{InventoryWrapper, ItemStack, ModGuiViewer, OwnedInventoryView};
+use crate::kits::KitManager;
+use crate::logging::{self, LogTarget};
+use crate::metrics::Metrics;
+use crate::overlay::Overlay;
+use crate::permissions::PermissionManager;
 use crate::registry::{BlockState, BlockStateRef, InteractionResult};
+use crate::scheduler::TaskHandle;
+use crate::search::SearchIndex;
+use crate::text_display::TextDisplay;
 use crate::util::BlockLocation;
 use crate::world::{BlockNetwork, PlayerData, UserData, World, WorldBlock};
+use crate::worldgen::ProtoChunk;
 use crate::{
     inventory::Recipe,
     util::{Identifier, Location},
@@ -55,6 +67,7 @@ pub struct ClientContentData {
     pub images: HashMap<Identifier, Vec<u8>>,
     pub sounds: HashMap<Identifier, Vec<u8>>,
     pub models: HashMap<Identifier, Vec<u8>>,
+    pub particles: HashMap<Identifier, ClientParticleData>,
 }
 
 pub enum ContentType {
@@ -117,14 +130,20 @@ impl Mod {
             let module_name = module_path.replace("/", "::");
             let module_name = module_name.replace(".rhs", "");
             let module_name = format!("{}{}", id, module_name);
-            for function in bbscript::parse_source_file(
+            match bbscript::parse_source_file(
                 std::fs::read_to_string(path).unwrap().as_str(),
                 Some(module_name.clone().into()),
                 0,
-            )
-            .unwrap()
-            {
-                functions.push((format!("{}::{}", module_name, function.name), function));
+            ) {
+                Ok(parsed) => {
+                    for function in parsed {
+                        functions.push((format!("{}::{}", module_name, function.name), function));
+                    }
+                }
+                Err(parse_errors) => script_errors.push((
+                    module_name,
+                    ScriptError::runtime(parse_errors.join(", ").as_str(), FilePosition::INVALID),
+                )),
             }
         }
         functions
@@ -226,6 +245,8 @@ impl Mod {
 
 pub struct ModManager {
     mods: HashMap<String, Mod>,
+    //script functions registered as globals, replaced on reload
+    script_names: Vec<ImmutableString>,
 }
 
 impl ModManager {
@@ -242,26 +263,72 @@ impl ModManager {
                 }
                 mods.insert(loaded_mod.namespace.clone(), loaded_mod);
             } else {
-                println!("loading mod '{}' failed", name);
+                logging::error(LogTarget::Server, format!("loading mod '{}' failed", name));
             }
         }
 
         let mut script_environment = ExecutionEnvironment::new();
-        for (mod_id, loaded_mod) in &mods {
-            let script_modules = loaded_mod.load_scripts(mod_id.as_str(), &mut errors);
-            for (name, function) in script_modules {
-                script_environment.register_global(
-                    name,
+        let scripts = Self::load_script_functions(&mods, &mut errors);
+        let script_names = scripts.iter().map(|(name, _)| name.clone()).collect();
+        for (name, function) in scripts {
+            script_environment.register_global(name, function);
+        }
+
+        (
+            ModManager { mods, script_names },
+            errors,
+            script_environment,
+        )
+    }
+    fn load_script_functions(
+        mods: &HashMap<String, Mod>,
+        errors: &mut Vec<(String, ScriptError)>,
+    ) -> Vec<(ImmutableString, Variant)> {
+        let mut functions = Vec::new();
+        for (mod_id, loaded_mod) in mods {
+            for (name, function) in loaded_mod.load_scripts(mod_id.as_str(), errors) {
+                functions.push((
+                    name.into(),
                     FunctionVariant {
                         this: Variant::NULL(),
                         function: FunctionType::ScriptFunction(Arc::new(function)),
                     }
                     .into_variant(),
-                );
+                ));
             }
         }
-
-        (ModManager { mods }, errors, script_environment)
+        functions
+    }
+    //scripts are read again from disk, nothing is returned unless all of them compile
+    pub fn compile_scripts(
+        &self,
+    ) -> std::result::Result<Vec<(ImmutableString, Variant)>, Vec<(String, ScriptError)>> {
+        let mut errors = Vec::new();
+        let functions = Self::load_script_functions(&self.mods, &mut errors);
+        if errors.is_empty() {
+            Ok(functions)
+        } else {
+            Err(errors)
+        }
+    }
+    //returns the functions that were replaced, so they can be put back
+    pub fn replace_scripts(
+        &mut self,
+        env: &ExecutionEnvironment,
+        functions: Vec<(ImmutableString, Variant)>,
+    ) -> Vec<(ImmutableString, Variant)> {
+        let previous = self
+            .script_names
+            .iter()
+            .filter_map(|name| {
+                env.get_global(name)
+                    .map(|function| (name.clone(), function))
+            })
+            .collect();
+        let names = functions.iter().map(|(name, _)| name.clone()).collect();
+        env.replace_globals(&self.script_names, functions);
+        self.script_names = names;
+        previous
     }
     pub fn load_resource_type<F: FnMut(Identifier, ContentType)>(
         &self,
@@ -321,6 +388,24 @@ impl ModManager {
         Self::load_enum::<InteractionResult>(env, "InteractionResult");
         Self::load_enum::<KeyboardKey>(env, "KeyboardKey");
 
+        env.register_function("log_info", |text: &ImmutableString| {
+            logging::info(LogTarget::Scripts, text);
+            Ok(())
+        });
+        env.register_function("log_warn", |text: &ImmutableString| {
+            logging::warn(LogTarget::Scripts, text);
+            Ok(())
+        });
+        env.register_function("log_error", |text: &ImmutableString| {
+            logging::error(LogTarget::Scripts, text);
+            Ok(())
+        });
+        //replaces the default dbg, which only prints to stdout
+        let formatter = env.get_variant_formatter();
+        env.register_function("dbg", move |value: &Variant| {
+            logging::info(LogTarget::Scripts, formatter.format_pretty(value));
+            Ok(value.clone())
+        });
         env.register_function("random_uuid", || {
             Ok(Variant::from_str(Uuid::new_v4().to_string().as_str()))
         });
@@ -337,23 +422,32 @@ impl ModManager {
         Self::load_scripting_object_server::<BlockLocation>(env, &server);
         Self::load_scripting_object_server::<Position>(env, &server);
         Self::load_scripting_object_server::<Structure>(env, &server);
+        Self::load_scripting_object_server::<ProtoChunk>(env, &server);
         Self::load_scripting_object_server::<BlockPosition>(env, &server);
         Self::load_scripting_object_server::<BlockState>(env, &server);
         Self::load_scripting_object_server::<UserDataWrapper>(env, &server);
         Self::load_scripting_object_server::<InventoryWrapper>(env, &server);
         Self::load_scripting_object_server::<Recipe>(env, &server);
         Self::load_scripting_object_server::<ModGuiViewer>(env, &server);
+        Self::load_scripting_object_server::<Overlay>(env, &server);
         Self::load_scripting_object_server::<Transformation>(env, &server);
         Self::load_scripting_object_server::<Face>(env, &server);
         Self::load_scripting_object_server::<HorizontalFace>(env, &server);
         Self::load_scripting_object_server::<IdentifierTag>(env, &server);
         Self::load_scripting_object_server::<ItemStack>(env, &server);
+        Self::load_scripting_object_server::<FluidStack>(env, &server);
         Self::load_scripting_object_server::<KeyboardKey>(env, &server);
         Self::load_scripting_object_server::<Server>(env, &server);
         Self::load_scripting_object_server::<OwnedInventoryView>(env, &server);
         Self::load_scripting_object_server::<BlockNetwork>(env, &server);
         Self::load_scripting_object_server::<Direction>(env, &server);
         Self::load_scripting_object_server::<ClientBlockData>(env, &server);
+        Self::load_scripting_object_server::<TaskHandle>(env, &server);
+        Self::load_scripting_object_server::<TextDisplay>(env, &server);
+        Self::load_scripting_object_server::<SearchIndex>(env, &server);
+        Self::load_scripting_object_server::<KitManager>(env, &server);
+        Self::load_scripting_object_server::<PermissionManager>(env, &server);
+        Self::load_scripting_object_server::<Explosion>(env, &server);
         {
             let server = server.clone();
             env.register_function(
@@ -631,6 +725,40 @@ impl ScriptingObject for ClientBlockData {
                 no_collide: bool::from_option_variant(data.get("no_collide"))
                     .cloned()
                     .unwrap_or(false),
+                tint: match ImmutableString::from_option_variant(data.get("tint"))
+                    .map(|tint| tint.as_ref())
+                {
+                    Some("Foliage") => BlockTint::Foliage,
+                    Some("Water") => BlockTint::Water,
+                    //older mods mark foliage with "tinted": true
+                    None if bool::from_option_variant(data.get("tinted"))
+                        .cloned()
+                        .unwrap_or(false) =>
+                    {
+                        BlockTint::Foliage
+                    }
+                    _ => BlockTint::None,
+                },
+                interaction_prompt: ImmutableString::from_option_variant(
+                    data.get("interaction_prompt"),
+                )
+                .map(|prompt| prompt.to_string()),
+                step_particles: Map::from_option_variant(data.get("step_particles"))
+                    .map(|data| {
+                        let default = StepParticles::default();
+                        let get = |key: &str, default: f32| {
+                            f64::from_option_variant(data.get(key))
+                                .map(|value| *value as f32)
+                                .unwrap_or(default)
+                        };
+                        StepParticles {
+                            rate: get("rate", default.rate),
+                            run_rate: get("run_rate", default.run_rate),
+                            size: get("size", default.size),
+                        }
+                    })
+                    .unwrap_or_default(),
+                surface_velocity: None,
             })
         });
     }
@@ -655,14 +783,62 @@ pub struct IdentifierTag {
     ids: HashSet<Identifier>,
 }
 impl IdentifierTag {
-    pub fn load(json: JsonValue) -> Arc<Self> {
-        Arc::new(IdentifierTag {
-            ids: json
-                .members()
-                .into_iter()
-                .map(|id| Identifier::parse(id.as_str().unwrap()).unwrap())
-                .collect(),
-        })
+    //entries starting with # include every id of another tag, tags that are part of a cycle are not loaded
+    pub fn load_all(
+        tags_json: HashMap<Identifier, JsonValue>,
+    ) -> HashMap<Identifier, Arc<IdentifierTag>> {
+        let mut tags = HashMap::new();
+        let mut ids: Vec<_> = tags_json.keys().cloned().collect();
+        ids.sort_by_key(|id| id.to_string());
+        for id in ids {
+            if let Err(error) = Self::resolve(&id, &tags_json, &mut tags, &mut Vec::new()) {
+                logging::error(LogTarget::Server, format!("tag {}: {}", id, error));
+            }
+        }
+        tags
+    }
+    fn resolve(
+        id: &Identifier,
+        tags_json: &HashMap<Identifier, JsonValue>,
+        tags: &mut HashMap<Identifier, Arc<IdentifierTag>>,
+        stack: &mut Vec<Identifier>,
+    ) -> Result<Arc<IdentifierTag>, String> {
+        if let Some(tag) = tags.get(id) {
+            return Ok(tag.clone());
+        }
+        if let Some(start) = stack.iter().position(|included| included == id) {
+            let cycle: Vec<_> = stack[start..]
+                .iter()
+                .chain(std::iter::once(id))
+                .map(|id| id.to_string())
+                .collect();
+            return Err(format!("tags include each other: {}", cycle.join(" -> ")));
+        }
+        let json = tags_json
+            .get(id)
+            .ok_or_else(|| format!("unknown tag {}", id))?;
+        stack.push(id.clone());
+        let mut ids = HashSet::new();
+        for entry in json.members() {
+            let entry = entry
+                .as_str()
+                .ok_or_else(|| format!("tag {} has an entry that isn't a string", id))?;
+            let parse =
+                |id: &str| Identifier::parse(id).map_err(|_| format!("invalid id {} in tag", id));
+            match entry.strip_prefix('#') {
+                Some(included) => {
+                    let included = Self::resolve(&parse(included)?, tags_json, tags, stack)?;
+                    ids.extend(included.ids.iter().cloned());
+                }
+                None => {
+                    ids.insert(parse(entry)?);
+                }
+            }
+        }
+        stack.pop();
+        let tag = Arc::new(IdentifierTag { ids });
+        tags.insert(id.clone(), tag.clone());
+        Ok(tag)
     }
     pub fn contains(&self, id: &Identifier) -> bool {
         self.ids.contains(id)
@@ -676,20 +852,56 @@ impl ScriptingObject for IdentifierTag {
         {
             let server = server.clone();
             env.register_function("Tag", move |id: &ImmutableString| {
+                let server = server.upgrade().unwrap();
                 Ok(Variant::from_option(
-                    server
-                        .upgrade()
-                        .unwrap()
-                        .tags
-                        .get(&Identifier::parse(id.as_ref()).unwrap())
-                        .cloned(),
+                    Identifier::parse(id.as_ref())
+                        .ok()
+                        .and_then(|id| server.tags.get(&id).cloned()),
                 ))
             });
         }
+        {
+            let server = server.clone();
+            env.register_function(
+                "tag_contains",
+                move |tag: &ImmutableString, id: &ImmutableString| {
+                    //unparsable ids are in no tag
+                    let server = server.upgrade().unwrap();
+                    Ok(Identifier::parse(tag.as_ref())
+                        .ok()
+                        .and_then(|tag| server.tags.get(&tag))
+                        .zip(Identifier::parse(id.as_ref()).ok())
+                        .map_or(false, |(tag, id)| tag.contains(&id)))
+                },
+            );
+        }
+        {
+            let server = server.clone();
+            env.register_function("items_with_tag", move |tag: &ImmutableString| {
+                let server = server.upgrade().unwrap();
+                let mut items: Vec<_> = Identifier::parse(tag.as_ref())
+                    .ok()
+                    .and_then(|tag| server.tags.get(&tag))
+                    .map(|tag| tag.list())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|id| server.item_registry.item_by_identifier(id).is_some())
+                    .map(|id| id.to_string())
+                    .collect();
+                items.sort();
+                let items: SharedArray = Arc::new(Mutex::new(
+                    items
+                        .iter()
+                        .map(|id| Variant::from_str(id.as_str()))
+                        .collect(),
+                ));
+                Ok(items.into_variant())
+            });
+        }
         env.register_method(
             "contains",
             |tag: &Arc<IdentifierTag>, id: &ImmutableString| {
-                Ok(tag.contains(&Identifier::parse(id.as_ref()).unwrap()))
+                Ok(Identifier::parse(id.as_ref()).map_or(false, |id| tag.contains(&id)))
             },
         );
         {
@@ -880,7 +1092,7 @@ impl ScriptCallback {
 }
 #[derive(Clone)]
 pub struct EventManager {
-    events: HashMap<Identifier, Vec<ScriptCallback>>,
+    events: HashMap<Identifier, Vec<(String, ScriptCallback)>>,
 }
 impl EventManager {
     pub fn new() -> Self {
@@ -888,17 +1100,127 @@ impl EventManager {
             events: HashMap::new(),
         }
     }
-    pub fn call_event(&self, id: Identifier, event_data: Variant, env: &ExecutionEnvironment) {
+    pub fn has_handlers(&self, id: &Identifier) -> bool {
+        self.events
+            .get(id)
+            .map(|event_list| !event_list.is_empty())
+            .unwrap_or(false)
+    }
+    pub fn call_event(
+        &self,
+        id: Identifier,
+        event_data: Variant,
+        env: &ExecutionEnvironment,
+        metrics: &Metrics,
+    ) {
         if let Some(event_list) = self.events.get(&id) {
-            for event in event_list {
-                event
-                    .call_function(env, Some(event_data.clone()), vec![])
-                    .unwrap();
+            for (mod_name, event) in event_list {
+                if let Err(error) = metrics.trace(
+                    "script",
+                    || format!("event {}", id),
+                    Some(mod_name.as_str()),
+                    || event.call_function(env, Some(event_data.clone()), vec![]),
+                ) {
+                    logging::error(
+                        LogTarget::Scripts,
+                        format!("event {} from mod {} failed: {}", id, mod_name, error),
+                    );
+                }
             }
         }
     }
-    pub fn register(&mut self, id: Identifier, callback: ScriptCallback) {
-        self.events.entry(id).or_insert(Vec::new()).push(callback);
+    //stops at the first failing handler and returns the mod it was registered by
+    pub fn try_call_event(
+        &self,
+        id: Identifier,
+        event_data: Variant,
+        env: &ExecutionEnvironment,
+        metrics: &Metrics,
+    ) -> Result<(), (String, ScriptError)> {
+        if let Some(event_list) = self.events.get(&id) {
+            for (mod_name, event) in event_list {
+                metrics
+                    .trace(
+                        "script",
+                        || format!("event {}", id),
+                        Some(mod_name.as_str()),
+                        || event.call_function(env, Some(event_data.clone()), vec![]),
+                    )
+                    .map_err(|error| (mod_name.clone(), error))?;
+            }
+        }
+        Ok(())
+    }
+    pub fn register(&mut self, id: Identifier, mod_name: String, callback: ScriptCallback) {
+        self.events
+            .entry(id)
+            .or_insert(Vec::new())
+            .push((mod_name, callback));
+    }
+}
+//logs every function scripts call while the next few calls of an event run, kept across reloads
+pub struct EventTracer {
+    events: Mutex<HashMap<Identifier, u32>>,
+}
+impl EventTracer {
+    pub fn new() -> Self {
+        EventTracer {
+            events: Mutex::new(HashMap::new()),
+        }
+    }
+    pub fn trace<R, F: FnOnce() -> R>(&self, id: Identifier, function: F) -> R {
+        //events called from a traced event are already part of its trace
+        if debug::is_tracing() || !self.take(&id) {
+            return function();
+        }
+        let (result, calls) = debug::trace_calls(function);
+        logging::info(
+            LogTarget::Scripts,
+            if calls.is_empty() {
+                format!("trace of event {}: no calls", id)
+            } else {
+                format!("trace of event {}:\n{}", id, calls.join("\n"))
+            },
+        );
+        result
+    }
+    fn take(&self, id: &Identifier) -> bool {
+        let mut events = self.events.lock();
+        match events.get_mut(id) {
+            Some(count) => {
+                *count -= 1;
+                if *count == 0 {
+                    events.remove(id);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+    pub fn run_command(&self, command: &[&str]) -> Result<String, String> {
+        match command {
+            ["clear"] => {
+                self.events.lock().clear();
+                Ok("no events are traced anymore".to_string())
+            }
+            [event] | [event, _] => {
+                let id = Identifier::parse(*event).map_err(|error| error.to_string())?;
+                let count = match command.get(1) {
+                    Some(count) => count
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|count| *count > 0)
+                        .ok_or_else(|| format!("invalid count {}", count))?,
+                    None => 1,
+                };
+                self.events.lock().insert(id.clone(), count);
+                Ok(format!(
+                    "tracing the next {} calls of event {} to the server log",
+                    count, id
+                ))
+            }
+            _ => Err("usage: /scripttrace [<event> [count]|clear]".to_string()),
+        }
     }
 }
 
@@ -1151,6 +1473,27 @@ fn patch_up_json(base: JsonValue, patch: JsonValue) -> JsonValue {
         (_base, patch) => patch,
     }
 }
+//like json_to_variant, but strings stay strings, for data that isn't written by mods
+pub fn json_data_to_variant(json: &JsonValue) -> Variant {
+    match json {
+        JsonValue::Null => Variant::NULL(),
+        JsonValue::Short(_) | JsonValue::String(_) => Variant::from_str(json.as_str().unwrap()),
+        JsonValue::Number(number) => Into::<f64>::into(*number).into_variant(),
+        JsonValue::Boolean(bool) => (*bool).into_variant(),
+        JsonValue::Object(object) => Arc::new(
+            object
+                .iter()
+                .map(|(name, property)| (name.into(), json_data_to_variant(property)))
+                .collect::<HashMap<ImmutableString, _>>(),
+        )
+        .into_variant(),
+        JsonValue::Array(array) => array
+            .iter()
+            .map(json_data_to_variant)
+            .collect::<Array>()
+            .into_variant(),
+    }
+}
 pub fn json_to_variant(json: JsonValue, script_environment: &ExecutionEnvironment) -> Variant {
     if let Some(string) = json.as_str() {
         return if string.starts_with("!") {
@@ -1165,10 +1508,7 @@ pub fn json_to_variant(json: JsonValue, script_environment: &ExecutionEnvironmen
             }
             .into_variant()
         } else if string.starts_with("@") {
-            script_environment
-                .get_global(&string[1..].into())
-                .cloned()
-                .unwrap()
+            script_environment.get_global(&string[1..].into()).unwrap()
         } else {
             Variant::from_str(string)
         };
@@ -1,74 +1,375 @@
-use std::net::TcpStream;
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::thread::spawn;
+use std::time::Duration;
 
-use block_byte_common::messages::{NetworkMessageC2S, NetworkMessageS2C};
+use block_byte_common::messages::{
+    Capability, ChunkCompression, JoinFailure, MessageChannel, NetworkMessageC2S,
+    NetworkMessageS2C, ProtocolHello, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+use block_byte_common::quic::{QuicConnection, Received};
+use crossbeam_channel::Sender;
 use json::JsonValue;
 use tungstenite::WebSocket;
+use uuid::Uuid;
+
+use crate::auth::PlayerIdentity;
+use crate::compression::ChunkEncoder;
+
+pub enum Transport {
+    WebSocket(WebSocket<TcpStream>),
+    Quic(QuicConnection),
+}
+impl Transport {
+    pub fn get_address(&self) -> Option<SocketAddr> {
+        match self {
+            Transport::WebSocket(socket) => socket.get_ref().peer_addr().ok(),
+            Transport::Quic(connection) => Some(connection.get_address()),
+        }
+    }
+    fn set_nonblocking(&mut self) -> Result<(), ()> {
+        match self {
+            Transport::WebSocket(socket) => socket.get_ref().set_nonblocking(true).map_err(|_| ()),
+            Transport::Quic(_) => Ok(()),
+        }
+    }
+    fn close(&mut self) {
+        match self {
+            Transport::WebSocket(socket) => {
+                socket.close(None).ok();
+                socket.flush().ok();
+            }
+            Transport::Quic(connection) => connection.close(),
+        }
+    }
+}
+
+//quic runs on the same port as websockets, only over udp, with a certificate generated on every start
+pub fn listen_quic(port: u16, connections: Sender<Transport>) {
+    spawn(move || {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async move {
+            let certificate =
+                rcgen::generate_simple_self_signed(vec!["blockbyte".to_string()]).unwrap();
+            let config = quinn::ServerConfig::with_single_cert(
+                vec![rustls::Certificate(certificate.serialize_der().unwrap())],
+                rustls::PrivateKey(certificate.serialize_private_key_der()),
+            )
+            .unwrap();
+            let endpoint =
+                quinn::Endpoint::server(config, SocketAddr::from(([0, 0, 0, 0], port))).unwrap();
+            while let Some(connecting) = endpoint.accept().await {
+                let connections = connections.clone();
+                tokio::spawn(async move {
+                    let connection = match connecting.await {
+                        Ok(connection) => connection,
+                        Err(_) => return,
+                    };
+                    let control = match connection.accept_bi().await {
+                        Ok(control) => control,
+                        Err(_) => return,
+                    };
+                    let chunks = match connection.open_uni().await {
+                        Ok(chunks) => chunks,
+                        Err(_) => return,
+                    };
+                    connections
+                        .send(Transport::Quic(QuicConnection::start(
+                            connection,
+                            control,
+                            Some(chunks),
+                            PlayerConnection::MAX_MESSAGE_SIZE,
+                        )))
+                        .ok();
+                });
+            }
+        });
+    });
+}
 
 pub struct PlayerConnection {
-    socket: WebSocket<TcpStream>,
+    transport: Transport,
     closed: bool,
+    backlogged: bool,
+    pub identity: Option<PlayerIdentity>,
+    token: Option<String>,
+    supported_compression: Vec<ChunkCompression>,
+    chunk_encoder: ChunkEncoder,
+    protocol_version: u32,
+    capabilities: HashSet<Capability>,
+    //last movement datagram of every entity and the ticks since it was sent
+    unconfirmed_movement: HashMap<u32, (Vec<u8>, u32)>,
 }
 impl PlayerConnection {
-    pub fn new(mut socket: WebSocket<TcpStream>) -> Result<(Self, u8), ()> {
-        let mode_message = socket.read().map_err(|_| ())?;
-        match mode_message {
-            tungstenite::Message::Binary(message) => {
-                match bitcode::deserialize::<NetworkMessageC2S>(message.as_slice()) {
-                    Ok(NetworkMessageC2S::ConnectionMode(mode)) => {
-                        socket.get_ref().set_nonblocking(true).map_err(|_| ())?;
-                        Ok((
-                            PlayerConnection {
-                                socket,
-                                closed: false,
-                            },
-                            mode,
-                        ))
-                    }
-                    _ => Err(()),
+    const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+    const MOVEMENT_RESEND_TICKS: u32 = 10;
+    pub fn new(mut transport: Transport) -> Result<(Self, u8), ()> {
+        //clients from before protocol versioning start with the connection mode
+        let (hello, first_message) = match Self::read_handshake_message(&mut transport)? {
+            NetworkMessageC2S::Hello(hello) => {
+                (hello, Self::read_handshake_message(&mut transport)?)
+            }
+            message => (
+                ProtocolHello {
+                    version: 0,
+                    capabilities: Vec::new(),
+                },
+                message,
+            ),
+        };
+        let mode = match first_message {
+            NetworkMessageC2S::ConnectionMode(mode) => mode,
+            _ => return Err(()),
+        };
+        if mode == 0 && !Self::is_supported_version(hello.version) {
+            transport.set_nonblocking()?;
+            return Ok((
+                Self::from_transport(transport, None, None, Vec::new(), hello),
+                mode,
+            ));
+        }
+        let (identity, token, supported_compression) = if mode == 0 {
+            let (identity, token) = match Self::read_handshake_message(&mut transport)? {
+                NetworkMessageC2S::Identify(username, uuid, token) => (
+                    Some(PlayerIdentity {
+                        username,
+                        uuid: Uuid::parse_str(uuid.as_str()).map_err(|_| ())?,
+                    }),
+                    token,
+                ),
+                _ => return Err(()),
+            };
+            let supported_compression = match Self::read_handshake_message(&mut transport)? {
+                NetworkMessageC2S::SupportedCompression(supported) => supported,
+                _ => return Err(()),
+            };
+            (identity, token, supported_compression)
+        } else {
+            (None, None, Vec::new())
+        };
+        transport.set_nonblocking()?;
+        Ok((
+            Self::from_transport(transport, identity, token, supported_compression, hello),
+            mode,
+        ))
+    }
+    fn from_transport(
+        transport: Transport,
+        identity: Option<PlayerIdentity>,
+        token: Option<String>,
+        supported_compression: Vec<ChunkCompression>,
+        hello: ProtocolHello,
+    ) -> Self {
+        PlayerConnection {
+            transport,
+            closed: false,
+            backlogged: false,
+            identity,
+            token,
+            supported_compression,
+            chunk_encoder: ChunkEncoder {
+                compression: ChunkCompression::Gzip,
+                level: 6,
+                dictionary: None,
+            },
+            protocol_version: hello.version,
+            capabilities: hello.get_capabilities(),
+            unconfirmed_movement: HashMap::new(),
+        }
+    }
+    fn is_supported_version(version: u32) -> bool {
+        (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version)
+    }
+    //players whose client can't talk to this server are told why before being disconnected
+    pub fn check_protocol(&self) -> Result<(), String> {
+        if self.protocol_version < MIN_PROTOCOL_VERSION {
+            Err(format!(
+                "your client is outdated (protocol {}), this server needs protocol {} or newer",
+                self.protocol_version, MIN_PROTOCOL_VERSION
+            ))
+        } else if self.protocol_version > PROTOCOL_VERSION {
+            Err(format!(
+                "your client is newer than this server (protocol {}), it supports up to protocol {}",
+                self.protocol_version, PROTOCOL_VERSION
+            ))
+        } else {
+            Ok(())
+        }
+    }
+    pub fn has_capability(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+    pub fn welcome(&mut self) {
+        self.send(&NetworkMessageS2C::Welcome(ProtocolHello::current()));
+    }
+    fn read_handshake_message(transport: &mut Transport) -> Result<NetworkMessageC2S, ()> {
+        let message = match transport {
+            Transport::WebSocket(socket) => match socket.read().map_err(|_| ())? {
+                tungstenite::Message::Binary(message) => message,
+                _ => return Err(()),
+            },
+            Transport::Quic(connection) => {
+                match connection.receive_blocking(Duration::from_secs(10)) {
+                    Some(Received::Reliable(message)) => message,
+                    _ => return Err(()),
                 }
             }
-            _ => Err(()),
+        };
+        bitcode::deserialize::<NetworkMessageC2S>(message.as_slice()).map_err(|_| ())
+    }
+    pub fn get_address(&self) -> Option<SocketAddr> {
+        self.transport.get_address()
+    }
+    pub fn get_token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+    //gzip is always understood, zstd is only used if the client announced it
+    pub fn negotiate_compression(&mut self, preferred: ChunkCompression, level: i32) {
+        self.chunk_encoder.compression = if self.supported_compression.contains(&preferred) {
+            preferred
+        } else {
+            ChunkCompression::Gzip
+        };
+        self.chunk_encoder.level = level;
+    }
+    pub fn get_chunk_encoder(&self) -> ChunkEncoder {
+        self.chunk_encoder.clone()
+    }
+    pub fn set_chunk_dictionary(&mut self, dictionary: Arc<Vec<u8>>) {
+        if self.chunk_encoder.compression != ChunkCompression::Zstd
+            || self
+                .chunk_encoder
+                .dictionary
+                .as_ref()
+                .map_or(false, |current| Arc::ptr_eq(current, &dictionary))
+        {
+            return;
         }
+        self.send(&NetworkMessageS2C::ChunkDictionary(dictionary.to_vec()));
+        self.chunk_encoder.dictionary = Some(dictionary);
+    }
+    pub fn disconnect(&mut self, reason: String) {
+        self.send(&NetworkMessageS2C::Disconnect(reason));
+        self.transport.close();
+        self.closed = true;
+    }
+    pub fn reject(&mut self, failure: JoinFailure) {
+        self.send(&NetworkMessageS2C::JoinFailed(failure));
+        self.transport.close();
+        self.closed = true;
     }
     pub fn send_json(&mut self, json: JsonValue) {
-        self.socket
-            .send(tungstenite::Message::Text(json.dump()))
-            .ok();
+        match &mut self.transport {
+            Transport::WebSocket(socket) => {
+                socket.send(tungstenite::Message::Text(json.dump())).ok();
+            }
+            Transport::Quic(connection) => {
+                connection.send(MessageChannel::Control, json.dump().into_bytes())
+            }
+        }
     }
     pub fn send_binary(&mut self, data: &Vec<u8>) {
-        self.socket
-            .send(tungstenite::Message::Binary(data.clone()))
-            .ok();
+        match &mut self.transport {
+            Transport::WebSocket(socket) => {
+                socket.send(tungstenite::Message::Binary(data.clone())).ok();
+            }
+            Transport::Quic(connection) => connection.send(MessageChannel::Control, data.clone()),
+        }
     }
     pub fn send(&mut self, message: &NetworkMessageS2C) {
-        if let Err(_) = self.socket.send(tungstenite::Message::Binary(
-            bitcode::serialize(message).unwrap(),
-        )) {
-            //panic!("socket error: {}", error);
-            self.closed = true;
+        //older clients go without the features they don't know about
+        if let Some(capability) = message.get_required_capability() {
+            if !self.capabilities.contains(&capability) {
+                return;
+            }
+        }
+        let data = bitcode::serialize(message).unwrap();
+        let socket = match &mut self.transport {
+            Transport::WebSocket(socket) => socket,
+            Transport::Quic(connection) => {
+                if let NetworkMessageS2C::MoveEntity(id, ..) = message {
+                    self.unconfirmed_movement.insert(*id, (data.clone(), 0));
+                }
+                connection.send(message.get_channel(), data);
+                self.backlogged = connection.is_backlogged();
+                return;
+            }
+        };
+        match socket.send(tungstenite::Message::Binary(data)) {
+            Ok(()) => {
+                self.backlogged = false;
+            }
+            //the message stays in the write buffer and gets sent with the next flush
+            Err(tungstenite::Error::Io(error)) if error.kind() == ErrorKind::WouldBlock => {
+                self.backlogged = true;
+            }
+            Err(_) => {
+                //panic!("socket error: {}", error);
+                self.closed = true;
+            }
         }
     }
+    pub fn is_backlogged(&self) -> bool {
+        self.backlogged
+    }
+    //datagrams are never resent, so once an entity stops moving its last position is sent again reliably
+    pub fn resend_movement(&mut self) {
+        let connection = match &mut self.transport {
+            Transport::Quic(connection) => connection,
+            Transport::WebSocket(_) => return,
+        };
+        self.unconfirmed_movement.retain(|_, (data, ticks)| {
+            *ticks += 1;
+            if *ticks < Self::MOVEMENT_RESEND_TICKS {
+                return true;
+            }
+            connection.send(MessageChannel::Control, std::mem::take(data));
+            false
+        });
+    }
     pub fn receive_messages(&mut self) -> Vec<NetworkMessageC2S> {
-        let mut messages = Vec::new();
-        while let Ok(message) = self.socket.read() {
-            match message {
-                tungstenite::Message::Binary(message) => {
-                    match bitcode::deserialize::<NetworkMessageC2S>(message.as_slice()) {
-                        Ok(message) => messages.push(message),
-                        Err(_) => {
+        let mut received = Vec::new();
+        match &mut self.transport {
+            Transport::WebSocket(socket) => {
+                while let Ok(message) = socket.read() {
+                    match message {
+                        tungstenite::Message::Binary(message) => received.push(message),
+                        tungstenite::Message::Close(_) => {
                             self.closed = true;
                         }
+                        _ => {}
                     }
                 }
-                tungstenite::Message::Close(_) => {
+            }
+            Transport::Quic(connection) => {
+                while let Some(message) = connection.receive() {
+                    if let Received::Reliable(message) = message {
+                        received.push(message);
+                    }
+                }
+            }
+        }
+        let mut messages = Vec::new();
+        for message in received {
+            match bitcode::deserialize::<NetworkMessageC2S>(message.as_slice()) {
+                Ok(message) => messages.push(message),
+                Err(_) => {
                     self.closed = true;
                 }
-                _ => {}
             }
         }
         messages
     }
     pub fn is_closed(&self) -> bool {
-        self.closed | !self.socket.can_write()
+        self.closed
+            | match &self.transport {
+                Transport::WebSocket(socket) => !socket.can_write(),
+                Transport::Quic(connection) => connection.is_closed(),
+            }
     }
 }
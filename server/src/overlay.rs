@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+
+use bbscript::eval::ExecutionEnvironment;
+use bbscript::variant::{FromVariant, Variant};
+use block_byte_common::gui::{GUIComponentEdit, GUIElementEdit};
+use block_byte_common::messages::NetworkMessageS2C;
+use block_byte_common::Vec2;
+use immutable_string::ImmutableString;
+use json::JsonValue;
+use parking_lot::Mutex;
+
+use crate::inventory::GUILayout;
+use crate::mods::ScriptingObject;
+use crate::util::Identifier;
+use crate::world::PlayerData;
+use crate::Server;
+
+//text and texture are templates like "{health} / {max_health}", fill and slice name a single variable
+#[derive(Clone, Debug)]
+enum BoundProperty {
+    Text(String),
+    Texture(String),
+    Fill(String),
+    Slice(String),
+}
+#[derive(Clone, Debug)]
+pub struct ElementBinding {
+    element: String,
+    property: BoundProperty,
+}
+impl ElementBinding {
+    pub fn from_json(element: &str, json: &JsonValue) -> Result<Vec<ElementBinding>, String> {
+        json.entries()
+            .map(|(property, value)| {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| format!("binding {} of {} must be a string", property, element))?
+                    .to_string();
+                Ok(ElementBinding {
+                    element: element.to_string(),
+                    property: match property {
+                        "text" => BoundProperty::Text(value),
+                        "texture" => BoundProperty::Texture(value),
+                        "fill" => BoundProperty::Fill(value),
+                        "slice" => BoundProperty::Slice(value),
+                        _ => {
+                            return Err(format!(
+                                "unknown bound property {} of {}",
+                                property, element
+                            ))
+                        }
+                    },
+                })
+            })
+            .collect()
+    }
+    fn uses(&self, variable: &str) -> bool {
+        match &self.property {
+            BoundProperty::Text(template) | BoundProperty::Texture(template) => {
+                template.contains(format!("{{{}}}", variable).as_str())
+            }
+            BoundProperty::Fill(name) | BoundProperty::Slice(name) => name == variable,
+        }
+    }
+    fn create_edit(&self, variables: &HashMap<String, Variant>) -> GUIElementEdit {
+        GUIElementEdit {
+            component_type: match &self.property {
+                BoundProperty::Text(template) => GUIComponentEdit::TextComponent {
+                    text: Some(format_template(template, variables)),
+                    font_size: None,
+                },
+                BoundProperty::Texture(template) => GUIComponentEdit::ImageComponent {
+                    texture: Some(format_template(template, variables)),
+                    size: None,
+                    slice: None,
+                },
+                BoundProperty::Fill(name) => GUIComponentEdit::GaugeComponent {
+                    background: None,
+                    texture: None,
+                    size: None,
+                    fill: Some(get_fraction(variables, name)),
+                    tooltip: None,
+                },
+                BoundProperty::Slice(name) => GUIComponentEdit::ImageComponent {
+                    texture: None,
+                    size: None,
+                    slice: Some(Some((
+                        Vec2::ZERO,
+                        Vec2 {
+                            x: get_fraction(variables, name),
+                            y: 1.,
+                        },
+                    ))),
+                },
+            },
+            ..Default::default()
+        }
+    }
+}
+
+fn format_variable(value: &Variant) -> String {
+    if let Some(value) = i64::from_variant(value) {
+        value.to_string()
+    } else if let Some(value) = f64::from_variant(value) {
+        ((value * 100.).round() / 100.).to_string()
+    } else if let Some(value) = bool::from_variant(value) {
+        value.to_string()
+    } else if let Some(value) = ImmutableString::from_variant(value) {
+        value.to_string()
+    } else if let Some(value) = Identifier::from_variant(value) {
+        value.to_string()
+    } else {
+        String::new()
+    }
+}
+//variables that were never set show up as empty text
+fn format_template(template: &str, variables: &HashMap<String, Variant>) -> String {
+    let mut output = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        output.push_str(&rest[..start]);
+        if let Some(value) = variables.get(&rest[start + 1..end]) {
+            output.push_str(format_variable(value).as_str());
+        }
+        rest = &rest[end + 1..];
+    }
+    output.push_str(rest);
+    output
+}
+fn get_fraction(variables: &HashMap<String, Variant>, name: &str) -> f32 {
+    let value = variables.get(name).map_or(0., |value| {
+        f64::from_variant(value)
+            .cloned()
+            .or_else(|| i64::from_variant(value).map(|value| *value as f64))
+            .unwrap_or(0.)
+    });
+    value.clamp(0., 1.) as f32
+}
+
+//a layout shown to a player outside of any inventory, its bound elements follow the variables
+pub struct Overlay {
+    player: Arc<PlayerData>,
+    id: Identifier,
+    layout: Arc<GUILayout>,
+    variables: Mutex<HashMap<String, Variant>>,
+}
+impl Overlay {
+    pub fn new(player: Arc<PlayerData>, id: Identifier, layout: Arc<GUILayout>) -> Arc<Overlay> {
+        let overlay = Arc::new(Overlay {
+            player,
+            id,
+            layout,
+            variables: Mutex::new(HashMap::new()),
+        });
+        overlay
+            .layout
+            .send_to_player(&overlay.player, overlay.id.to_string().as_str());
+        for binding in overlay.layout.get_bindings() {
+            overlay.send_binding(binding, &HashMap::new());
+        }
+        overlay
+    }
+    fn send_binding(&self, binding: &ElementBinding, variables: &HashMap<String, Variant>) {
+        self.player.send_message(&NetworkMessageS2C::GuiEditElement(
+            format!("{}_{}", self.id.to_string(), binding.element),
+            binding.create_edit(variables),
+        ));
+    }
+    //only the elements that use the variable are sent again
+    pub fn set_variable(&self, name: &str, value: Variant) {
+        let mut variables = self.variables.lock();
+        variables.insert(name.to_string(), value);
+        for binding in self.layout.get_bindings() {
+            if binding.uses(name) {
+                self.send_binding(binding, &variables);
+            }
+        }
+    }
+    pub fn get_variable(&self, name: &str) -> Option<Variant> {
+        self.variables.lock().get(name).cloned()
+    }
+    pub fn remove(&self) {
+        self.player
+            .send_message(&NetworkMessageS2C::GuiRemoveElements(self.id.to_string()));
+    }
+}
+impl ScriptingObject for Overlay {
+    fn engine_register_server(env: &mut ExecutionEnvironment, _server: &Weak<Server>) {
+        env.register_custom_name::<Arc<Overlay>, _>("Overlay");
+        env.register_method(
+            "set_variable",
+            |overlay: &Arc<Overlay>, name: &ImmutableString, value: &Variant| {
+                overlay.set_variable(name.as_ref(), value.clone());
+                Ok(())
+            },
+        );
+        env.register_method(
+            "get_variable",
+            |overlay: &Arc<Overlay>, name: &ImmutableString| {
+                Ok(Variant::from_option(overlay.get_variable(name.as_ref())))
+            },
+        );
+        //an overlay that was already replaced leaves its successor open
+        env.register_method("close", |overlay: &Arc<Overlay>| {
+            if overlay
+                .player
+                .get_overlay(&overlay.id)
+                .map_or(false, |open| Arc::ptr_eq(&open, overlay))
+            {
+                overlay.player.close_overlay(&overlay.id);
+            }
+            Ok(())
+        });
+    }
+}
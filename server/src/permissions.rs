@@ -0,0 +1,282 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+
+use bbscript::eval::ExecutionEnvironment;
+use bbscript::variant::IntoVariant;
+use immutable_string::ImmutableString;
+use json::{object, JsonValue};
+use parking_lot::Mutex;
+
+use crate::auth::PlayerIdentity;
+use crate::logging::{self, LogTarget};
+use crate::mods::ScriptingObject;
+use crate::world::PlayerData;
+use crate::Server;
+
+//permissions are dotted nodes like bb.command.give, entries starting with - deny a node
+#[derive(Default)]
+struct PermissionEntry {
+    groups: Vec<String>,
+    permissions: Vec<String>,
+}
+impl PermissionEntry {
+    fn from_json(json: &JsonValue) -> Self {
+        let strings = |json: &JsonValue| {
+            json.members()
+                .filter_map(|entry| entry.as_str())
+                .map(|entry| entry.to_string())
+                .collect()
+        };
+        PermissionEntry {
+            groups: strings(&json["groups"]),
+            permissions: strings(&json["permissions"]),
+        }
+    }
+    fn to_json(&self) -> JsonValue {
+        object! {
+            groups: self.groups.clone(),
+            permissions: self.permissions.clone()
+        }
+    }
+    //Some when the list decides the node, the node itself comes before wildcards like bb.command.* and *
+    fn check(&self, node: &str) -> Option<bool> {
+        let mut candidates = vec![node.to_string()];
+        let mut prefix = node;
+        while let Some((parent, _)) = prefix.rsplit_once('.') {
+            candidates.push(format!("{}.*", parent));
+            prefix = parent;
+        }
+        candidates.push("*".to_string());
+        for candidate in candidates {
+            for permission in &self.permissions {
+                match permission.strip_prefix('-') {
+                    Some(denied) if denied == candidate => return Some(false),
+                    None if *permission == candidate => return Some(true),
+                    _ => {}
+                }
+            }
+        }
+        None
+    }
+}
+
+#[derive(Default)]
+struct PermissionData {
+    groups: HashMap<String, PermissionEntry>,
+    //keyed by uuid or username, like the operator list
+    players: HashMap<String, PermissionEntry>,
+}
+impl PermissionData {
+    fn from_json(json: &JsonValue) -> Self {
+        PermissionData {
+            groups: json["groups"]
+                .entries()
+                .map(|(name, group)| (name.to_string(), PermissionEntry::from_json(group)))
+                .collect(),
+            players: json["players"]
+                .entries()
+                .map(|(name, player)| (name.to_string(), PermissionEntry::from_json(player)))
+                .collect(),
+        }
+    }
+    fn to_json(&self) -> JsonValue {
+        let mut groups = JsonValue::new_object();
+        for (name, group) in &self.groups {
+            groups.insert(name.as_str(), group.to_json()).unwrap();
+        }
+        let mut players = JsonValue::new_object();
+        for (name, player) in &self.players {
+            players.insert(name.as_str(), player.to_json()).unwrap();
+        }
+        object! {
+            groups: groups,
+            players: players
+        }
+    }
+    fn get_player(&self, identity: &PlayerIdentity) -> Option<&PermissionEntry> {
+        self.players
+            .get(&identity.uuid.to_string())
+            .or_else(|| self.players.get(&identity.username))
+    }
+    //the player's own permissions win, then their groups in order with inherited groups after each one, default comes last
+    fn has_permission(&self, identity: &PlayerIdentity, node: &str) -> bool {
+        let player = self.get_player(identity);
+        if let Some(result) = player.and_then(|player| player.check(node)) {
+            return result;
+        }
+        let mut groups: Vec<&str> = player
+            .map(|player| player.groups.iter().map(|group| group.as_str()).collect())
+            .unwrap_or_default();
+        groups.push(PermissionManager::DEFAULT_GROUP);
+        let mut visited = HashSet::new();
+        while !groups.is_empty() {
+            let name = groups.remove(0);
+            if !visited.insert(name) {
+                continue;
+            }
+            if let Some(group) = self.groups.get(name) {
+                if let Some(result) = group.check(node) {
+                    return result;
+                }
+                for (i, inherited) in group.groups.iter().enumerate() {
+                    groups.insert(i, inherited.as_str());
+                }
+            }
+        }
+        false
+    }
+}
+
+pub struct PermissionManager {
+    path: PathBuf,
+    data: Mutex<PermissionData>,
+}
+impl PermissionManager {
+    //every player is in this group without being added to it
+    pub const DEFAULT_GROUP: &'static str = "default";
+    pub fn load(save_directory: &Path) -> Self {
+        let path = save_directory.join("permissions.json");
+        let data = Self::read(&path).unwrap_or_else(|error| {
+            logging::warn(LogTarget::Server, error);
+            PermissionData::default()
+        });
+        PermissionManager {
+            path,
+            data: Mutex::new(data),
+        }
+    }
+    fn read(path: &Path) -> Result<PermissionData, String> {
+        if !path.exists() {
+            return Ok(PermissionData::default());
+        }
+        std::fs::read_to_string(path)
+            .map_err(|error| error.to_string())
+            .and_then(|data| json::parse(data.as_str()).map_err(|error| error.to_string()))
+            .map(|json| PermissionData::from_json(&json))
+            .map_err(|error| format!("could not load {}: {}", path.display(), error))
+    }
+    pub fn reload(&self) -> Result<(), String> {
+        *self.data.lock() = Self::read(&self.path)?;
+        Ok(())
+    }
+    fn save(&self, data: &PermissionData) -> Result<(), String> {
+        std::fs::write(&self.path, data.to_json().pretty(4))
+            .map_err(|error| format!("could not save permissions: {}", error))
+    }
+    pub fn has_permission(&self, identity: &PlayerIdentity, node: &str) -> bool {
+        self.data.lock().has_permission(identity, node)
+    }
+    //returns false if nothing changed
+    fn update<F>(&self, entry: &str, update: F) -> Result<bool, String>
+    where
+        F: FnOnce(&mut PermissionEntry) -> bool,
+    {
+        let mut data = self.data.lock();
+        let changed = update(data.players.entry(entry.to_string()).or_default());
+        data.players
+            .retain(|_, player| !player.groups.is_empty() || !player.permissions.is_empty());
+        if changed {
+            self.save(&data)?;
+        }
+        Ok(changed)
+    }
+    pub fn set_permission(&self, entry: &str, node: &str, add: bool) -> Result<bool, String> {
+        self.update(entry, |player| {
+            update_list(&mut player.permissions, node, add)
+        })
+    }
+    pub fn set_group(&self, entry: &str, group: &str, add: bool) -> Result<bool, String> {
+        if add && !self.data.lock().groups.contains_key(group) {
+            return Err(format!("unknown group {}", group));
+        }
+        self.update(entry, |player| update_list(&mut player.groups, group, add))
+    }
+    //entries are usernames or uuids, like in operators.txt
+    pub fn run_command(&self, server: &Server, command: &[&str]) -> Result<String, String> {
+        let changed = |changed: bool, message: String| {
+            Ok(if changed {
+                message
+            } else {
+                "nothing changed".to_string()
+            })
+        };
+        match command {
+            ["reload"] => {
+                self.reload()?;
+                Ok("permissions reloaded".to_string())
+            }
+            ["check", username, node] => {
+                let player = server
+                    .players
+                    .lock()
+                    .iter()
+                    .find(|player| player.identity.username == *username)
+                    .cloned()
+                    .ok_or_else(|| format!("player {} is not online", username))?;
+                Ok(format!(
+                    "{} {} {}",
+                    username,
+                    if player.has_permission(node) {
+                        "has"
+                    } else {
+                        "doesn't have"
+                    },
+                    node
+                ))
+            }
+            ["grant", entry, node] => changed(
+                self.set_permission(entry, node, true)?,
+                format!("granted {} to {}", node, entry),
+            ),
+            ["revoke", entry, node] => changed(
+                self.set_permission(entry, node, false)?,
+                format!("revoked {} from {}", node, entry),
+            ),
+            ["addgroup", entry, group] => changed(
+                self.set_group(entry, group, true)?,
+                format!("added {} to group {}", entry, group),
+            ),
+            ["removegroup", entry, group] => changed(
+                self.set_group(entry, group, false)?,
+                format!("removed {} from group {}", entry, group),
+            ),
+            _ => Err("usage: /perm [reload|check <player> <node>|grant <player> <node>|revoke <player> <node>|addgroup <player> <group>|removegroup <player> <group>]".to_string()),
+        }
+    }
+}
+fn update_list(list: &mut Vec<String>, value: &str, add: bool) -> bool {
+    let present = list.iter().any(|entry| entry == value);
+    if add && !present {
+        list.push(value.to_string());
+        true
+    } else if !add && present {
+        list.retain(|entry| entry != value);
+        true
+    } else {
+        false
+    }
+}
+impl ScriptingObject for PermissionManager {
+    fn engine_register_server(env: &mut ExecutionEnvironment, server: &Weak<Server>) {
+        env.register_method(
+            "has_permission",
+            |player: &Arc<PlayerData>, node: &ImmutableString| {
+                Ok(player.has_permission(node.as_ref()))
+            },
+        );
+        {
+            let server = server.clone();
+            env.register_member("operator", move |player: &Arc<PlayerData>| {
+                Some(
+                    server
+                        .upgrade()
+                        .unwrap()
+                        .access_list
+                        .is_operator(&player.identity)
+                        .into_variant(),
+                )
+            });
+        }
+    }
+}
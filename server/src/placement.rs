@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bbscript::variant::{FromVariant, IntoVariant, Variant};
+use block_byte_common::{BlockPosition, Direction, Face, HorizontalFace, Position};
+use immutable_string::ImmutableString;
+use json::JsonValue;
+use parking_lot::Mutex;
+
+use crate::inventory::ItemStack;
+use crate::logging::{self, LogTarget};
+use crate::registry::{BlockStateProperty, BlockStateRef, InteractionResult};
+use crate::util::BlockLocation;
+use crate::world::PlayerData;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Orientation {
+    ClickedFace,
+    Player,
+}
+
+//how a block item picks the state it places, from the "placement" object of the item
+#[derive(Debug)]
+pub struct Placement {
+    pub state: BlockStateRef,
+    pub orientation: Option<Orientation>,
+    pub half: Option<String>,
+}
+impl Placement {
+    pub fn from_json(json: &JsonValue, state: BlockStateRef) -> Option<Self> {
+        if json.is_null() {
+            return None;
+        }
+        Some(Placement {
+            state,
+            orientation: match json["orientation"].as_str() {
+                Some("clicked_face") => Some(Orientation::ClickedFace),
+                Some("player") => Some(Orientation::Player),
+                _ => None,
+            },
+            half: json["half"].as_str().map(|half| half.to_string()),
+        })
+    }
+    //places the block next to the clicked face if there is room for it
+    pub fn place(
+        &self,
+        item: &ItemStack,
+        player: Arc<PlayerData>,
+        clicked: BlockLocation,
+        face: Face,
+        hit: Position,
+    ) -> InteractionResult {
+        let world = clicked.world;
+        let position = clicked.position.offset_by_face(face);
+        if !world.is_inside_border(position)
+            || !world
+                .get_block(&position)
+                .map_or(false, |block| block.is_air())
+        {
+            return InteractionResult::Ignored;
+        }
+        let direction = player.get_entity().get_direction();
+        let state = self.get_state(&player, position, face, hit, direction);
+        let mut context: HashMap<ImmutableString, Variant> = HashMap::new();
+        context.insert("player".into(), player.clone().into_variant());
+        context.insert(
+            "location".into(),
+            BlockLocation {
+                world: world.clone(),
+                position,
+            }
+            .into_variant(),
+        );
+        context.insert("face".into(), face.into_variant());
+        context.insert("hit".into(), hit.into_variant());
+        context.insert("state".into(), state.into_variant());
+        let result = match item
+            .item_type
+            .static_data
+            .get_function("on_place_state")
+            .call_function(
+                &player.server.script_environment,
+                Some(item.clone().into_variant()),
+                vec![Arc::new(Mutex::new(context)).into_variant()],
+            ) {
+            Ok(result) => result,
+            //a failing script doesn't place anything
+            Err(error) => {
+                logging::error(
+                    LogTarget::Scripts,
+                    format!(
+                        "on_place_state of {} failed: {:?}",
+                        item.item_type.id, error
+                    ),
+                );
+                return InteractionResult::Ignored;
+            }
+        };
+        //null keeps the computed state, anything else that isn't a state cancels placing
+        let state = match BlockStateRef::from_variant(&result) {
+            Some(state) => *state,
+            None if <()>::from_variant(&result).is_some() => state,
+            None => return InteractionResult::Ignored,
+        };
+        world.set_block(position, state, true, player.into_variant());
+        InteractionResult::Consumed
+    }
+    pub fn get_state(
+        &self,
+        player: &PlayerData,
+        position: BlockPosition,
+        face: Face,
+        hit: Position,
+        direction: Direction,
+    ) -> BlockStateRef {
+        let block_state = player.server.block_registry.state_by_ref(self.state);
+        let properties = &block_state.parent.properties;
+        let mut state = self.state;
+        let mut set = |name: &str, value: Variant| {
+            if let Ok(new_state) = player
+                .server
+                .block_registry
+                .state_by_ref(state)
+                .with_property(name, value)
+            {
+                state = new_state;
+            }
+        };
+        if let Some(orientation) = self.orientation {
+            let look = direction.to_vector();
+            //blocks placed by looking face back towards the player
+            let towards_player = face_from_vector(-look.x, -look.y, -look.z);
+            let horizontal_towards_player = face_from_vector(-look.x, 0., -look.z)
+                .to_horizontal_face()
+                .unwrap_or(HorizontalFace::Front);
+            for (name, id) in &properties.property_names {
+                let value = match (&properties.properties[*id as usize].0, orientation) {
+                    (BlockStateProperty::Face, Orientation::ClickedFace) => face.into_variant(),
+                    (BlockStateProperty::Face, Orientation::Player) => {
+                        towards_player.into_variant()
+                    }
+                    (BlockStateProperty::HorizontalFace, Orientation::ClickedFace) => face
+                        .to_horizontal_face()
+                        .unwrap_or(horizontal_towards_player)
+                        .into_variant(),
+                    (BlockStateProperty::HorizontalFace, Orientation::Player) => {
+                        horizontal_towards_player.into_variant()
+                    }
+                    _ => continue,
+                };
+                set(name.as_str(), value);
+            }
+        }
+        if let Some(half) = &self.half {
+            let top = match face {
+                Face::Down => true,
+                Face::Up => false,
+                _ => hit.y - position.y as f64 >= 0.5,
+            };
+            let value = match properties
+                .property_names
+                .get(half)
+                .map(|id| &properties.properties[*id as usize].0)
+            {
+                Some(BlockStateProperty::Bool) => top.into_variant(),
+                _ => Variant::from_str(if top { "top" } else { "bottom" }),
+            };
+            set(half.as_str(), value);
+        }
+        state
+    }
+}
+
+//the face pointing the most in the direction of the vector
+pub fn face_from_vector(x: f64, y: f64, z: f64) -> Face {
+    if y.abs() > x.abs() && y.abs() > z.abs() {
+        if y > 0. {
+            Face::Up
+        } else {
+            Face::Down
+        }
+    } else if x.abs() > z.abs() {
+        if x > 0. {
+            Face::Right
+        } else {
+            Face::Left
+        }
+    } else if z > 0. {
+        Face::Back
+    } else {
+        Face::Front
+    }
+}
+
+//where clients that don't send the hit position are assumed to have clicked
+pub fn get_face_center(position: BlockPosition, face: Face) -> Position {
+    let offset = face.get_offset();
+    Position {
+        x: position.x as f64 + 0.5 + offset.x as f64 * 0.5,
+        y: position.y as f64 + 0.5 + offset.y as f64 * 0.5,
+        z: position.z as f64 + 0.5 + offset.z as f64 * 0.5,
+    }
+}
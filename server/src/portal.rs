@@ -0,0 +1,94 @@
+use block_byte_common::Position;
+use json::JsonValue;
+
+use crate::logging::{self, LogTarget};
+use crate::util::{Identifier, Location};
+use crate::world::World;
+
+#[derive(Clone, Debug)]
+pub struct Portal {
+    pub world: Identifier,
+    pub world_generator: Option<Identifier>,
+    pub return_world: Option<Identifier>,
+    pub scale: f64,
+    pub offset: Position,
+}
+impl Portal {
+    pub fn from_json(json: &JsonValue) -> Result<Option<Self>, String> {
+        if json.is_null() {
+            return Ok(None);
+        }
+        let parse_world = |key: &str| -> Result<Option<Identifier>, String> {
+            json[key]
+                .as_str()
+                .map(|id| Identifier::parse(id).map_err(|_| format!("invalid {} {}", key, id)))
+                .transpose()
+        };
+        let scale = json["scale"].as_f64().unwrap_or(1.);
+        if !(scale > 0.) {
+            return Err(format!("scale must be positive, got {}", scale));
+        }
+        Ok(Some(Portal {
+            world: parse_world("world")?.ok_or_else(|| "missing world".to_string())?,
+            world_generator: parse_world("world_generator")?,
+            return_world: parse_world("return_world")?,
+            scale,
+            offset: Position {
+                x: json["offset"]["x"].as_f64().unwrap_or(0.),
+                y: json["offset"]["y"].as_f64().unwrap_or(0.),
+                z: json["offset"]["z"].as_f64().unwrap_or(0.),
+            },
+        }))
+    }
+    pub fn transform(&self, position: Position) -> Position {
+        Position {
+            x: position.x * self.scale + self.offset.x,
+            y: position.y + self.offset.y,
+            z: position.z * self.scale + self.offset.z,
+        }
+    }
+    pub fn inverse_transform(&self, position: Position) -> Position {
+        Position {
+            x: (position.x - self.offset.x) / self.scale,
+            y: position.y - self.offset.y,
+            z: (position.z - self.offset.z) / self.scale,
+        }
+    }
+    //the same portal block in its destination world leads back to the return world
+    pub fn get_destination(&self, world: &World, position: Position) -> Option<Location> {
+        let (target, target_world, position) = if world.id == self.world {
+            let return_world = self.return_world.as_ref()?;
+            (
+                return_world,
+                world.server.get_world(return_world.clone().into()),
+                self.inverse_transform(position),
+            )
+        } else {
+            (
+                &self.world,
+                match &self.world_generator {
+                    Some(world_generator) => Some(
+                        world
+                            .server
+                            .get_or_create_world(self.world.clone(), world_generator.clone()),
+                    ),
+                    None => world.server.get_world(self.world.clone().into()),
+                },
+                self.transform(position),
+            )
+        };
+        match target_world {
+            Some(target_world) => Some(Location {
+                position: target_world.get_border().clamp(position),
+                world: target_world,
+            }),
+            None => {
+                logging::warn(
+                    LogTarget::Server,
+                    format!("portal destination world {} is not loaded", target),
+                );
+                None
+            }
+        }
+    }
+}
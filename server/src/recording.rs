@@ -0,0 +1,501 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use block_byte_common::content::Transformation;
+use block_byte_common::messages::{
+    BonePose, ClientModelTarget, NetworkMessageS2C, PROTOCOL_VERSION,
+};
+use block_byte_common::recording::{
+    self, RecordingFrame, RecordingHeader, RECORDING_MAGIC, RECORDING_TICKS_PER_SECOND,
+    RECORDING_VERSION,
+};
+use block_byte_common::{BlockPosition, ChunkPosition, Direction, Position};
+
+use crate::util::Identifier;
+use crate::world::PlayerData;
+use crate::Server;
+
+#[derive(Default)]
+struct ModelState {
+    items: HashMap<u32, Option<u32>>,
+    transforms: HashMap<u32, Option<Transformation>>,
+    animation: Option<u32>,
+    poses: HashMap<Vec<u32>, BonePose>,
+}
+impl ModelState {
+    fn create_messages<F>(&self, target: F, messages: &mut Vec<NetworkMessageS2C>)
+    where
+        F: Fn() -> ClientModelTarget,
+    {
+        if !self.items.is_empty() {
+            messages.push(NetworkMessageS2C::ModelItem(
+                target(),
+                self.items
+                    .iter()
+                    .map(|(slot, item)| (*slot, *item))
+                    .collect(),
+            ));
+        }
+        if !self.transforms.is_empty() {
+            messages.push(NetworkMessageS2C::ModelItemTransform(
+                target(),
+                self.transforms
+                    .iter()
+                    .map(|(slot, transform)| (*slot, *transform))
+                    .collect(),
+            ));
+        }
+        if let Some(animation) = self.animation {
+            messages.push(NetworkMessageS2C::ModelAnimation(target(), animation));
+        }
+        for (path, pose) in &self.poses {
+            messages.push(NetworkMessageS2C::ModelBonePose(
+                target(),
+                path.clone(),
+                Some(*pose),
+            ));
+        }
+    }
+}
+struct RecordedEntity {
+    type_id: u32,
+    position: Position,
+    rotation: Direction,
+    animation: String,
+    animation_start: f32,
+    model: ModelState,
+}
+struct RecordedChunk {
+    //the LoadChunk message as it was sent
+    load: Vec<u8>,
+    //blocks changed since the chunk was sent
+    blocks: HashMap<(u8, u8, u8), u32>,
+    models: HashMap<BlockPosition, ModelState>,
+}
+
+//what the client knows about the world, folded from the recorded messages so keyframes don't have to encode chunks again
+#[derive(Default)]
+struct RecordingState {
+    dictionary: Option<Vec<u8>>,
+    chunks: HashMap<ChunkPosition, RecordedChunk>,
+    entities: HashMap<u32, RecordedEntity>,
+    text_displays: HashMap<u32, Vec<u8>>,
+}
+impl RecordingState {
+    fn apply(&mut self, message: &NetworkMessageS2C, data: &[u8]) {
+        match message {
+            NetworkMessageS2C::LoadChunk(position, ..) => {
+                self.chunks.insert(
+                    *position,
+                    RecordedChunk {
+                        load: data.to_vec(),
+                        blocks: HashMap::new(),
+                        models: HashMap::new(),
+                    },
+                );
+            }
+            NetworkMessageS2C::UnloadChunk(position) => {
+                self.chunks.remove(position);
+            }
+            NetworkMessageS2C::SetBlock(position, id) => {
+                if let Some(chunk) = self.chunks.get_mut(&position.to_chunk_pos()) {
+                    chunk.blocks.insert(position.chunk_offset(), *id);
+                    chunk.models.remove(position);
+                }
+            }
+            NetworkMessageS2C::SetBlocks(position, blocks) => {
+                if let Some(chunk) = self.chunks.get_mut(position) {
+                    for ((x, y, z), id) in blocks {
+                        chunk.blocks.insert((*x, *y, *z), *id);
+                        chunk.models.remove(&BlockPosition {
+                            x: position.x * 16 + *x as i32,
+                            y: position.y * 16 + *y as i32,
+                            z: position.z * 16 + *z as i32,
+                        });
+                    }
+                }
+            }
+            NetworkMessageS2C::AddEntity(type_id, id, position, rotation, animation, start) => {
+                self.entities.insert(
+                    *id,
+                    RecordedEntity {
+                        type_id: *type_id,
+                        position: *position,
+                        rotation: *rotation,
+                        animation: animation.clone(),
+                        animation_start: *start,
+                        model: ModelState::default(),
+                    },
+                );
+            }
+            NetworkMessageS2C::MoveEntity(id, position, rotation) => {
+                if let Some(entity) = self.entities.get_mut(id) {
+                    entity.position = *position;
+                    entity.rotation = *rotation;
+                }
+            }
+            NetworkMessageS2C::DeleteEntity(id) | NetworkMessageS2C::PickupEntity(id, _) => {
+                self.entities.remove(id);
+            }
+            NetworkMessageS2C::EntityAnimationState(id, state) => {
+                if let Some(entity) = self.entities.get_mut(id) {
+                    entity.animation = state.clone();
+                }
+            }
+            NetworkMessageS2C::ModelItem(target, items) => {
+                if let Some(model) = self.get_model(target) {
+                    for (slot, item) in items {
+                        model.items.insert(*slot, *item);
+                    }
+                }
+            }
+            NetworkMessageS2C::ModelItemTransform(target, transforms) => {
+                if let Some(model) = self.get_model(target) {
+                    for (slot, transform) in transforms {
+                        model.transforms.insert(*slot, *transform);
+                    }
+                }
+            }
+            NetworkMessageS2C::ModelAnimation(target, animation) => {
+                if let Some(model) = self.get_model(target) {
+                    model.animation = Some(*animation);
+                }
+            }
+            NetworkMessageS2C::ModelBonePose(target, path, pose) => {
+                if let Some(model) = self.get_model(target) {
+                    match pose {
+                        Some(pose) => {
+                            model.poses.insert(path.clone(), *pose);
+                        }
+                        None => {
+                            model.poses.remove(path);
+                        }
+                    }
+                }
+            }
+            NetworkMessageS2C::AddTextDisplay(id, ..) => {
+                self.text_displays.insert(*id, data.to_vec());
+            }
+            NetworkMessageS2C::RemoveTextDisplay(id) => {
+                self.text_displays.remove(id);
+            }
+            NetworkMessageS2C::ChunkDictionary(dictionary) => {
+                self.dictionary = Some(dictionary.clone());
+            }
+            _ => {}
+        }
+    }
+    fn get_model(&mut self, target: &ClientModelTarget) -> Option<&mut ModelState> {
+        match target {
+            ClientModelTarget::Block(position) => self
+                .chunks
+                .get_mut(&position.to_chunk_pos())
+                .map(|chunk| chunk.models.entry(*position).or_default()),
+            ClientModelTarget::Entity(id) => {
+                self.entities.get_mut(id).map(|entity| &mut entity.model)
+            }
+            ClientModelTarget::ViewModel => None,
+        }
+    }
+}
+
+//writes what one player sees of a world to recordings/<username>-<time>.bbrec in the save directory
+pub struct SessionRecorder {
+    pub world: Identifier,
+    pub file: String,
+    output: BufWriter<File>,
+    state: RecordingState,
+    pending: Vec<Vec<u8>>,
+    tick: u32,
+    keyframe_interval: u32,
+    max_ticks: Option<u32>,
+    max_size: Option<u64>,
+    written: u64,
+    //false until the messages the client got before the recording started are added
+    initialized: bool,
+    dictionary: Option<Arc<Vec<u8>>>,
+    //the player's own entity is never sent to them, so the recorder adds it
+    entity: Option<(u32, Position, Direction)>,
+}
+impl SessionRecorder {
+    //called with the connection locked, messages sent until initialize are recorded but no frames are written
+    pub fn start(
+        player: &PlayerData,
+        world: Identifier,
+        dictionary: Option<Arc<Vec<u8>>>,
+    ) -> Result<Self, String> {
+        let settings = &player.server.settings;
+        let max_minutes = settings.get_i64("server.recording_max_minutes", 120);
+        let max_megabytes = settings.get_i64("server.recording_max_size", 2048);
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        //usernames can contain path separators, uuids can't
+        let file = format!("{}-{}.bbrec", player.identity.uuid, started.as_millis());
+        let directory = player.server.save_directory.join("recordings");
+        let output = std::fs::create_dir_all(&directory)
+            .and_then(|_| File::create(directory.join(&file)))
+            .map_err(|error| format!("could not create recording: {}", error))?;
+        let mut recorder = SessionRecorder {
+            world: world.clone(),
+            file,
+            output: BufWriter::new(output),
+            state: RecordingState::default(),
+            pending: Vec::new(),
+            tick: 0,
+            keyframe_interval: (settings
+                .get_i64("server.recording_keyframe_interval", 30)
+                .max(1) as u32)
+                * RECORDING_TICKS_PER_SECOND,
+            max_ticks: (max_minutes > 0).then(|| {
+                (max_minutes.min(u32::MAX as i64) as u32)
+                    .saturating_mul(60 * RECORDING_TICKS_PER_SECOND)
+            }),
+            max_size: (max_megabytes > 0)
+                .then(|| (max_megabytes as u64).saturating_mul(1024 * 1024)),
+            written: 0,
+            initialized: false,
+            dictionary: None,
+            entity: None,
+        };
+        let header = RecordingHeader {
+            version: RECORDING_VERSION,
+            protocol_version: PROTOCOL_VERSION,
+            world: world.to_string(),
+            player: player.identity.username.clone(),
+            started: started.as_secs(),
+        };
+        recorder
+            .output
+            .write_all(RECORDING_MAGIC)
+            .map_err(Self::write_error)?;
+        recorder.write(&bitcode::serialize(&header).unwrap())?;
+        recorder.set_chunk_dictionary(dictionary);
+        Ok(recorder)
+    }
+    //initial are the messages the client got before the recording started
+    pub fn initialize(&mut self, initial: Vec<NetworkMessageS2C>) {
+        for message in initial {
+            self.state
+                .apply(&message, &bitcode::serialize(&message).unwrap());
+        }
+        //messages recorded while initial was collected are at least as new as it, so they are applied again on top
+        for data in &self.pending {
+            if let Ok(message) = bitcode::deserialize::<NetworkMessageS2C>(data) {
+                self.state.apply(&message, data);
+            }
+        }
+        //the first keyframe has all of it
+        self.pending.clear();
+        self.initialized = true;
+    }
+    fn write_error(error: std::io::Error) -> String {
+        format!("could not write recording: {}", error)
+    }
+    fn write(&mut self, data: &[u8]) -> Result<(), String> {
+        self.written += data.len() as u64;
+        recording::write_record(&mut self.output, data).map_err(Self::write_error)
+    }
+    //only what the player sees of the world is kept, their guis, abilities and teleports are left out
+    fn is_recorded(message: &NetworkMessageS2C) -> bool {
+        match message {
+            NetworkMessageS2C::SetBlock(..)
+            | NetworkMessageS2C::SetBlocks(..)
+            | NetworkMessageS2C::LoadChunk(..)
+            | NetworkMessageS2C::UnloadChunk(..)
+            | NetworkMessageS2C::AddEntity(..)
+            | NetworkMessageS2C::MoveEntity(..)
+            | NetworkMessageS2C::DeleteEntity(..)
+            | NetworkMessageS2C::PickupEntity(..)
+            | NetworkMessageS2C::EntityAnimationState(..)
+            | NetworkMessageS2C::SetWorldBorder(..)
+            | NetworkMessageS2C::SetSky(..)
+            | NetworkMessageS2C::SpawnParticles(..)
+            | NetworkMessageS2C::BlockBreakProgress(..)
+            | NetworkMessageS2C::AddTextDisplay(..)
+            | NetworkMessageS2C::RemoveTextDisplay(..)
+            | NetworkMessageS2C::ChunkDictionary(..) => true,
+            NetworkMessageS2C::PlaySound(_, _, _, _, relative, _) => !relative,
+            NetworkMessageS2C::ModelItem(target, _)
+            | NetworkMessageS2C::ModelItemTransform(target, _)
+            | NetworkMessageS2C::ModelAnimation(target, _)
+            | NetworkMessageS2C::ModelBonePose(target, _, _) => {
+                !matches!(target, ClientModelTarget::ViewModel)
+            }
+            _ => false,
+        }
+    }
+    pub fn record(&mut self, message: &NetworkMessageS2C) {
+        if !Self::is_recorded(message) {
+            return;
+        }
+        let data = bitcode::serialize(message).unwrap();
+        self.state.apply(message, &data);
+        self.pending.push(data);
+    }
+    //called with the connection locked, so chunks encoded with a new dictionary are recorded after it
+    pub fn set_chunk_dictionary(&mut self, dictionary: Option<Arc<Vec<u8>>>) {
+        let dictionary = match dictionary {
+            Some(dictionary) => dictionary,
+            None => return,
+        };
+        if self
+            .dictionary
+            .as_ref()
+            .map_or(false, |current| Arc::ptr_eq(current, &dictionary))
+        {
+            return;
+        }
+        self.record(&NetworkMessageS2C::ChunkDictionary(dictionary.to_vec()));
+        self.dictionary = Some(dictionary);
+    }
+    pub fn tick(&mut self, player: &PlayerData) -> Result<(), String> {
+        if !self.initialized {
+            return Ok(());
+        }
+        if self
+            .max_ticks
+            .map_or(false, |max_ticks| self.tick >= max_ticks)
+        {
+            return Err("the recording reached its maximum length".to_string());
+        }
+        if self
+            .max_size
+            .map_or(false, |max_size| self.written >= max_size)
+        {
+            return Err("the recording reached its maximum size".to_string());
+        }
+        let entity = player.get_entity();
+        let position = entity.get_location().position;
+        let rotation = entity.rotation_shifting.lock().0;
+        match self.entity {
+            Some((id, last_position, last_rotation)) if id == entity.client_id => {
+                if last_position != position
+                    || last_rotation.pitch != rotation.pitch
+                    || last_rotation.yaw != rotation.yaw
+                {
+                    self.record(&NetworkMessageS2C::MoveEntity(id, position, rotation));
+                }
+            }
+            previous => {
+                if let Some((id, _, _)) = previous {
+                    self.record(&NetworkMessageS2C::DeleteEntity(id));
+                }
+                for message in entity.create_add_messages(position) {
+                    self.record(&message);
+                }
+            }
+        }
+        self.entity = Some((entity.client_id, position, rotation));
+        let messages = std::mem::take(&mut self.pending);
+        if !messages.is_empty() {
+            self.write_frame(false, messages)?;
+        }
+        if self.tick % self.keyframe_interval == 0 {
+            let keyframe = self.create_keyframe(player, position, rotation);
+            self.write_frame(true, keyframe)?;
+        }
+        self.tick += 1;
+        Ok(())
+    }
+    fn write_frame(&mut self, keyframe: bool, messages: Vec<Vec<u8>>) -> Result<(), String> {
+        let frame = RecordingFrame {
+            tick: self.tick,
+            keyframe,
+            messages,
+        };
+        self.write(&bitcode::serialize(&frame).unwrap())
+    }
+    fn create_keyframe(
+        &self,
+        player: &PlayerData,
+        position: Position,
+        rotation: Direction,
+    ) -> Vec<Vec<u8>> {
+        let world = player.get_entity().get_location().chunk.world.clone();
+        let mut created = Vec::new();
+        if let Some(dictionary) = &self.state.dictionary {
+            created.push(NetworkMessageS2C::ChunkDictionary(dictionary.clone()));
+        }
+        created.push(NetworkMessageS2C::SetWorldBorder(world.get_border()));
+        created.push(NetworkMessageS2C::SetSky(world.get_sky()));
+        //replays start with the camera where the player was
+        created.push(NetworkMessageS2C::TeleportPlayer(position, rotation));
+        let mut messages: Vec<Vec<u8>> = created
+            .drain(..)
+            .map(|message| bitcode::serialize(&message).unwrap())
+            .collect();
+        for (position, chunk) in &self.state.chunks {
+            messages.push(chunk.load.clone());
+            if !chunk.blocks.is_empty() {
+                created.push(NetworkMessageS2C::SetBlocks(
+                    *position,
+                    chunk
+                        .blocks
+                        .iter()
+                        .map(|(offset, id)| (*offset, *id))
+                        .collect(),
+                ));
+            }
+            for (block, model) in &chunk.models {
+                model.create_messages(|| ClientModelTarget::Block(*block), &mut created);
+            }
+        }
+        for (id, entity) in &self.state.entities {
+            created.push(NetworkMessageS2C::AddEntity(
+                entity.type_id,
+                *id,
+                entity.position,
+                entity.rotation,
+                entity.animation.clone(),
+                entity.animation_start,
+            ));
+            entity
+                .model
+                .create_messages(|| ClientModelTarget::Entity(*id), &mut created);
+        }
+        messages.extend(
+            created
+                .iter()
+                .map(|message| bitcode::serialize(message).unwrap()),
+        );
+        messages.extend(self.state.text_displays.values().cloned());
+        messages
+    }
+    //writes what is left and returns the file name
+    pub fn finish(mut self) -> Result<String, String> {
+        let messages = std::mem::take(&mut self.pending);
+        if !messages.is_empty() {
+            self.write_frame(false, messages)?;
+        }
+        self.output.flush().map_err(Self::write_error)?;
+        Ok(self.file)
+    }
+}
+
+//also used by the console
+pub fn run_command(server: &Server, command: &[&str]) -> Result<String, String> {
+    let (action, username) = match command {
+        [action @ ("start" | "stop"), username] => (*action, *username),
+        _ => return Err("usage: /record [start|stop] <player>".to_string()),
+    };
+    let player = server
+        .players
+        .lock()
+        .iter()
+        .find(|player| player.identity.username == username)
+        .cloned()
+        .ok_or_else(|| format!("player {} is not online", username))?;
+    if action == "start" {
+        player
+            .start_recording()
+            .map(|file| format!("recording {} to recordings/{}", username, file))
+    } else {
+        player
+            .stop_recording()
+            .map(|file| format!("saved recording of {} as recordings/{}", username, file))
+    }
+}
@@ -11,21 +11,34 @@ use std::{
 };
 
 use bbscript::eval::ExecutionEnvironment;
-use bbscript::variant::{FromVariant, FunctionType, FunctionVariant, IntoVariant, Variant};
+use bbscript::variant::{
+    FromVariant, FunctionType, FunctionVariant, IntoVariant, SharedArray, Variant,
+};
 use block_byte_common::content::{
-    ClientBlockData, ClientBlockRenderDataType, ClientContent, ClientEntityData, ClientItemData,
+    BlockTint, ClientBiomeData, ClientBlockData, ClientBlockRenderDataType, ClientContent,
+    ClientEntityData, ClientItemData, ClientParticleData, StepParticles,
 };
-use block_byte_common::{BlockPosition, Face, HorizontalFace};
+use block_byte_common::{BlockPosition, Face, HorizontalFace, Position};
+use json::JsonValue;
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
 use strum_macros::{Display, EnumIter};
 use twox_hash::XxHash64;
 use zip::{write::FileOptions, DateTime, ZipWriter};
 
-use crate::inventory::Recipe;
+use crate::ai::Schedule;
+use crate::detector::Detector;
+use crate::energy::EnergyType;
+use crate::fire::Flammability;
+use crate::inventory::{Inventory, Recipe};
 use crate::mods::{ClientContentData, ScriptingObject};
+use crate::placement::Placement;
+use crate::portal::Portal;
+use crate::sensor::Sensor;
+use crate::signal::SignalType;
+use crate::transfer::{ItemTransfer, SidedAccess};
 use crate::util::BlockLocation;
-use crate::world::{Entity, PlayerData};
+use crate::world::{Entity, PlayerData, StructureTransform};
 use crate::{
     inventory::ItemStack,
     mods::ScriptCallback,
@@ -62,6 +75,8 @@ impl StaticData {
 pub struct BlockRegistry {
     blocks: HashMap<Identifier, Arc<Block>, BuildHasherDefault<XxHash64>>,
     states: Vec<BlockState>,
+    collision: Vec<bool>,
+    fire: Option<BlockStateRef>,
     id_generator: u32,
 }
 
@@ -70,6 +85,8 @@ impl BlockRegistry {
         let mut block_registry = BlockRegistry {
             blocks: Default::default(),
             states: Vec::new(),
+            collision: Vec::new(),
+            fire: None,
             id_generator: 0,
         };
         block_registry
@@ -80,6 +97,7 @@ impl BlockRegistry {
                         id,
                         default_state,
                         data_container: None,
+                        fluid_tanks: Vec::new(),
                         item_model_mapping: ItemModelMapping {
                             mapping: HashMap::new(),
                         },
@@ -88,6 +106,16 @@ impl BlockRegistry {
                         static_data: StaticData {
                             data: HashMap::new(),
                         },
+                        flammability: None,
+                        fire: false,
+                        blast_resistance: 0.,
+                        portal: None,
+                        signal: None,
+                        energy: None,
+                        sensor: None,
+                        sides: None,
+                        transfer: None,
+                        detector: None,
                     })
                 },
                 |_, _| ClientBlockData {
@@ -98,6 +126,13 @@ impl BlockRegistry {
                     transparent: false,
                     selectable: false,
                     no_collide: true,
+                    tint: BlockTint::None,
+                    interaction_prompt: None,
+                    step_particles: StepParticles {
+                        rate: 0.,
+                        ..StepParticles::default()
+                    },
+                    surface_velocity: None,
                 },
             )
             .expect("couldn't register air");
@@ -145,6 +180,23 @@ impl BlockRegistry {
     pub fn state_by_ref(&self, block_state_ref: BlockStateRef) -> &BlockState {
         self.states.get(block_state_ref.state_id as usize).unwrap()
     }
+    pub fn freeze(&mut self) {
+        self.collision = self.states.iter().map(|state| state.collidable).collect();
+        self.fire = self
+            .states
+            .iter()
+            .find(|state| state.parent.fire)
+            .map(|state| state.get_ref());
+    }
+    pub fn get_fire_block(&self) -> Option<BlockStateRef> {
+        self.fire
+    }
+    pub fn is_collidable(&self, block_state_ref: BlockStateRef) -> bool {
+        match self.collision.get(block_state_ref.state_id as usize) {
+            Some(collidable) => *collidable,
+            None => self.state_by_ref(block_state_ref).collidable,
+        }
+    }
     pub fn state_from_string(&self, state: &str) -> anyhow::Result<BlockStateRef> {
         let (block, props) = if state.contains('{') {
             let split = state.split_once('{').unwrap();
@@ -297,10 +349,21 @@ pub struct Block {
     pub id: Identifier,
     pub default_state: u32,
     pub data_container: Option<(u32,)>,
+    pub fluid_tanks: Vec<u32>,
     pub item_model_mapping: ItemModelMapping,
     pub properties: BlockStatePropertyStorage,
     pub networks: HashMap<Identifier, ScriptCallback>,
     pub static_data: StaticData,
+    pub flammability: Option<Flammability>,
+    pub fire: bool,
+    pub blast_resistance: f32,
+    pub portal: Option<Portal>,
+    pub signal: Option<SignalType>,
+    pub energy: Option<EnergyType>,
+    pub sensor: Option<Sensor>,
+    pub sides: Option<SidedAccess>,
+    pub transfer: Option<ItemTransfer>,
+    pub detector: Option<Detector>,
 }
 
 impl Block {
@@ -381,8 +444,8 @@ impl BlockStateProperty {
             BlockStateProperty::HorizontalFace => match id {
                 0 => "front",
                 1 => "back",
-                3 => "left",
-                4 => "right",
+                2 => "left",
+                3 => "right",
                 _ => unreachable!(),
             }
             .to_string(),
@@ -538,6 +601,30 @@ impl BlockState {
             .properties
             .get_from_state(self.state_id, BlockStatePropertyKey::Name(property))
     }
+    pub fn transformed(&self, transform: StructureTransform) -> BlockStateRef {
+        let properties = &self.parent.properties;
+        let mut state = self.state_id;
+        for (id, (property, _)) in properties.properties.iter().enumerate() {
+            let key = || BlockStatePropertyKey::Id(id as u32);
+            let value = properties.get_from_state(state, key());
+            let value = match property {
+                BlockStateProperty::Face => Face::from_variant(&value)
+                    .map(|face| transform.transform_face(*face).into_variant()),
+                BlockStateProperty::HorizontalFace => HorizontalFace::from_variant(&value)
+                    .and_then(|face| {
+                        transform
+                            .transform_face(face.to_face())
+                            .to_horizontal_face()
+                    })
+                    .map(|face| face.into_variant()),
+                _ => None,
+            };
+            if let Some(value) = value {
+                state = properties.set_state(state, key(), value).unwrap();
+            }
+        }
+        self.parent.get_state_ref(state)
+    }
 }
 impl ToString for BlockState {
     fn to_string(&self) -> String {
@@ -590,6 +677,40 @@ impl ScriptingObject for BlockState {
                 },
             );
         }
+        {
+            let server = server.clone();
+            env.register_method("get_properties", move |state: &BlockStateRef| {
+                let server = server.upgrade().unwrap();
+                let block_state = server.block_registry.state_by_ref(*state);
+                Ok(block_state
+                    .parent
+                    .properties
+                    .dump_properties(block_state.state_id))
+            });
+        }
+        {
+            let server = server.clone();
+            env.register_method(
+                "get_property_values",
+                move |state: &BlockStateRef, property: &ImmutableString| {
+                    let server = server.upgrade().unwrap();
+                    let properties = &server.block_registry.state_by_ref(*state).parent.properties;
+                    Ok(Variant::from_option(
+                        BlockStatePropertyKey::Name(property.as_ref())
+                            .to_id(&properties.property_names)
+                            .map(|id| {
+                                let property = &properties.properties[id].0;
+                                let values: SharedArray = Arc::new(Mutex::new(
+                                    (0..property.get_num_states())
+                                        .map(|id| property.from_id_to_value(id))
+                                        .collect(),
+                                ));
+                                values
+                            }),
+                    ))
+                },
+            );
+        }
         {
             let server = server.clone();
             env.register_method("to_string", move |state: &BlockStateRef| {
@@ -669,6 +790,9 @@ pub struct Item {
     pub client_id: u32,
     pub stack_size: u32,
     pub static_data: StaticData,
+    pub placement: Option<Placement>,
+    pub durability: Option<u32>,
+    pub category: Option<String>,
 }
 
 impl Item {
@@ -678,18 +802,23 @@ impl Item {
         player: Arc<PlayerData>,
         block_location: BlockLocation,
         block_face: Face,
+        hit: Position,
+        hand: Hand,
     ) -> InteractionResult {
         let new_item = item.clone().into_variant();
         let result = *InteractionResult::from_variant(
             &self
                 .static_data
-                .get_function("on_right_click_block")
+                .get_function(match hand {
+                    Hand::MainHand => "on_right_click_block",
+                    Hand::OffHand => "on_offhand_right_click_block",
+                })
                 .call_function(
                     &player.server.clone().script_environment,
                     Some(new_item),
                     vec![
-                        player.into_variant(),
-                        block_location.into_variant(),
+                        player.clone().into_variant(),
+                        block_location.clone().into_variant(),
                         block_face.into_variant(),
                     ],
                 )
@@ -697,19 +826,28 @@ impl Item {
         )
         .unwrap_or(&InteractionResult::Ignored);
         //*item = new_item.cast();
-        result
+        match &self.placement {
+            Some(placement) if result == InteractionResult::Ignored => {
+                placement.place(item, player, block_location, block_face, hit)
+            }
+            _ => result,
+        }
     }
     pub fn on_right_click(
         &self,
         item: &mut ItemStack,
         player: Arc<PlayerData>,
         entity: Option<Arc<Entity>>,
+        hand: Hand,
     ) -> InteractionResult {
         let mut new_item = item.clone().into_variant();
         let result = InteractionResult::from_variant(
             &self
                 .static_data
-                .get_function("on_right_click")
+                .get_function(match hand {
+                    Hand::MainHand => "on_right_click",
+                    Hand::OffHand => "on_offhand_right_click",
+                })
                 .call_function(
                     &player.server.clone().script_environment,
                     Some(new_item),
@@ -717,8 +855,8 @@ impl Item {
                 )
                 .unwrap(),
         )
-        .unwrap()
-        .clone();
+        .cloned()
+        .unwrap_or(InteractionResult::Ignored);
         result
     }
 }
@@ -729,6 +867,12 @@ pub enum InteractionResult {
     Ignored,
 }
 
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum Hand {
+    MainHand,
+    OffHand,
+}
+
 pub struct EntityRegistry {
     entities: HashMap<Identifier, Arc<EntityType>, BuildHasherDefault<XxHash64>>,
     id_generator: u32,
@@ -761,6 +905,36 @@ impl EntityRegistry {
 pub struct ItemModelMapping {
     pub mapping: HashMap<u32, u32>,
 }
+impl ItemModelMapping {
+    pub fn from_json(json: &JsonValue) -> Result<Self, String> {
+        let mut mapping = HashMap::new();
+        for (from, to) in json.entries() {
+            mapping.insert(
+                from.parse()
+                    .map_err(|_| format!("invalid inventory slot {}", from))?,
+                to.as_u32()
+                    .ok_or_else(|| format!("invalid model slot {}", to))?,
+            );
+        }
+        Ok(ItemModelMapping { mapping })
+    }
+    //model slot and client item id of every mapped inventory slot, for one ModelItem message
+    pub fn get_items(&self, inventory: &Inventory) -> Vec<(u32, Option<u32>)> {
+        let view = inventory.get_full_view();
+        self.mapping
+            .iter()
+            .map(|(inventory_index, model_index)| {
+                (
+                    *model_index,
+                    view.get_item(*inventory_index)
+                        .unwrap()
+                        .as_ref()
+                        .map(|item| item.item_type.client_id),
+                )
+            })
+            .collect()
+    }
+}
 
 pub struct EntityType {
     pub id: Identifier,
@@ -769,6 +943,13 @@ pub struct EntityType {
     pub item_model_mapping: ItemModelMapping,
     pub static_data: StaticData,
     pub inventory_size: u32,
+    pub offhand_slot: Option<u32>,
+    pub schedule: Option<Schedule>,
+    pub low_priority: bool,
+    pub despawn_distance: Option<f64>,
+    pub sounds: HashMap<String, String>,
+    pub projectile: bool,
+    pub pushable: bool,
 }
 
 pub struct ClientContentGenerator {}
@@ -778,6 +959,7 @@ impl ClientContentGenerator {
         block_registry: &BlockRegistry,
         item_registry: &ItemRegistry,
         entity_registry: &EntityRegistry,
+        biomes: Vec<ClientBiomeData>,
         client_content: ClientContentData,
     ) -> Vec<u8> {
         let mut zip_writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
@@ -788,8 +970,14 @@ impl ClientContentGenerator {
         zip_writer.start_file("content.json", options).unwrap();
         zip_writer
             .write_all(
-                Self::generate_content_json(block_registry, item_registry, entity_registry)
-                    .as_bytes(),
+                Self::generate_content_json(
+                    block_registry,
+                    item_registry,
+                    entity_registry,
+                    biomes,
+                    &client_content.particles,
+                )
+                .as_bytes(),
             )
             .unwrap();
         for image in client_content.images {
@@ -822,6 +1010,8 @@ impl ClientContentGenerator {
         block_registry: &BlockRegistry,
         item_registry: &ItemRegistry,
         entity_registry: &EntityRegistry,
+        biomes: Vec<ClientBiomeData>,
+        particles: &HashMap<Identifier, ClientParticleData>,
     ) -> String {
         serde_json::to_string(&ClientContent {
             blocks: block_registry
@@ -847,6 +1037,16 @@ impl ClientContentGenerator {
                 entities.sort_by(|a, b| a.0.cmp(&b.0));
                 entities.iter().map(|item| item.1.clone()).collect()
             },
+            biomes,
+            particles: particles
+                .iter()
+                .map(|(id, particle)| (id.to_string(), particle.clone()))
+                .collect(),
+            block_names: block_registry
+                .states
+                .iter()
+                .map(|state| state.to_string())
+                .collect(),
         })
         .unwrap()
     }
@@ -0,0 +1,128 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+
+use bbscript::eval::{ExecutionEnvironment, ScriptError};
+use bbscript::lex::FilePosition;
+use bbscript::variant::{FromVariant, FunctionVariant, Variant};
+use parking_lot::Mutex;
+
+use crate::logging::{self, LogTarget};
+use crate::mods::{ScriptCallback, ScriptingObject};
+use crate::Server;
+
+fn to_callback(callback: &Variant) -> Result<ScriptCallback, ScriptError> {
+    FunctionVariant::from_variant(callback)
+        .map(|function| ScriptCallback::from_function_variant(function))
+        .ok_or_else(|| ScriptError::runtime("callback must be a function", FilePosition::INVALID))
+}
+
+#[derive(Clone)]
+pub struct TaskHandle(Arc<AtomicBool>);
+impl TaskHandle {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+impl ScriptingObject for TaskHandle {
+    fn engine_register_server(env: &mut ExecutionEnvironment, server: &Weak<Server>) {
+        env.register_custom_name::<TaskHandle, _>("TaskHandle");
+        env.register_method("cancel", |task: &TaskHandle| {
+            task.cancel();
+            Ok(())
+        });
+        env.register_member("cancelled", |task: &TaskHandle| Some(task.is_cancelled()));
+        {
+            let server = server.clone();
+            env.register_function("schedule", move |delay: &i64, callback: &Variant| {
+                Ok(server.upgrade().unwrap().scheduler.schedule(
+                    (*delay).max(0) as u64,
+                    None,
+                    to_callback(callback)?,
+                ))
+            });
+        }
+        {
+            let server = server.clone();
+            env.register_function(
+                "schedule_repeating",
+                move |interval: &i64, callback: &Variant| {
+                    if *interval <= 0 {
+                        return Err(ScriptError::runtime(
+                            "repeating task interval must be positive",
+                            FilePosition::INVALID,
+                        ));
+                    }
+                    Ok(server.upgrade().unwrap().scheduler.schedule(
+                        *interval as u64,
+                        Some(*interval as u64),
+                        to_callback(callback)?,
+                    ))
+                },
+            );
+        }
+    }
+}
+
+struct ScheduledTask {
+    callback: ScriptCallback,
+    run_at: u64,
+    interval: Option<u64>,
+    handle: TaskHandle,
+}
+
+//tasks live in memory only and are dropped when the server stops
+pub struct Scheduler {
+    tasks: Mutex<Vec<ScheduledTask>>,
+    current_tick: AtomicU64,
+}
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler {
+            tasks: Mutex::new(Vec::new()),
+            current_tick: AtomicU64::new(0),
+        }
+    }
+    pub fn schedule(
+        &self,
+        delay: u64,
+        interval: Option<u64>,
+        callback: ScriptCallback,
+    ) -> TaskHandle {
+        let handle = TaskHandle(Arc::new(AtomicBool::new(false)));
+        self.tasks.lock().push(ScheduledTask {
+            callback,
+            run_at: self.current_tick.load(Ordering::Relaxed) + delay.max(1),
+            interval,
+            handle: handle.clone(),
+        });
+        handle
+    }
+    pub fn tick(&self, env: &ExecutionEnvironment) {
+        let tick = self.current_tick.fetch_add(1, Ordering::Relaxed) + 1;
+        let due: Vec<_> = self
+            .tasks
+            .lock()
+            .extract_if(|task| task.handle.is_cancelled() || task.run_at <= tick)
+            .collect();
+        for mut task in due {
+            if task.handle.is_cancelled() {
+                continue;
+            }
+            if let Err(error) = task.callback.call_function(env, None, vec![]) {
+                logging::error(
+                    LogTarget::Scripts,
+                    format!("scheduled task failed: {}", error),
+                );
+            }
+            if let Some(interval) = task.interval {
+                if !task.handle.is_cancelled() {
+                    task.run_at = tick + interval;
+                    self.tasks.lock().push(task);
+                }
+            }
+        }
+    }
+}
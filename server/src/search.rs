@@ -0,0 +1,251 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Weak};
+
+use bbscript::eval::ExecutionEnvironment;
+use bbscript::variant::{IntoVariant, SharedArray, Variant};
+use immutable_string::ImmutableString;
+use parking_lot::Mutex;
+
+use crate::mods::{IdentifierTag, ScriptingObject};
+use crate::registry::{BlockRegistry, ItemRegistry};
+use crate::util::Identifier;
+use crate::Server;
+
+struct SearchEntry {
+    id: Identifier,
+    name: String,
+    category: Option<String>,
+    item: bool,
+    block: bool,
+}
+
+pub struct SearchResults {
+    pub ids: Vec<Identifier>,
+    pub total: usize,
+    pub page: usize,
+    pub pages: usize,
+}
+
+//every entry is indexed under lowercase words of its name, id and tags, sorted so prefixes can be found with a binary search
+pub struct SearchIndex {
+    entries: Vec<SearchEntry>,
+    tokens: Vec<(String, u32)>,
+    categories: Vec<String>,
+}
+impl SearchIndex {
+    pub fn new(
+        item_registry: &ItemRegistry,
+        block_registry: &BlockRegistry,
+        tags: &HashMap<Identifier, Arc<IdentifierTag>>,
+    ) -> Self {
+        let mut entries: Vec<SearchEntry> = Vec::new();
+        let mut lookup: HashMap<Identifier, usize> = HashMap::new();
+        for id in item_registry.list() {
+            let item = item_registry.item_by_identifier(id).unwrap();
+            lookup.insert(id.clone(), entries.len());
+            entries.push(SearchEntry {
+                id: id.clone(),
+                name: item.client_data.name.clone(),
+                category: item.category.clone(),
+                item: true,
+                block: false,
+            });
+        }
+        for (id, _) in block_registry.list_blocks() {
+            match lookup.get(id) {
+                Some(index) => entries[*index].block = true,
+                None => {
+                    lookup.insert(id.clone(), entries.len());
+                    entries.push(SearchEntry {
+                        id: id.clone(),
+                        name: String::new(),
+                        category: None,
+                        item: false,
+                        block: true,
+                    });
+                }
+            }
+        }
+        entries.sort_by(|first, second| first.id.to_string().cmp(&second.id.to_string()));
+        let lookup: HashMap<Identifier, usize> = entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| (entry.id.clone(), index))
+            .collect();
+        let mut tokens = HashSet::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let index = index as u32;
+            let id = entry.id.to_string().to_lowercase();
+            tokens.insert((id, index));
+            for word in Self::split_words(&entry.id.get_key().to_lowercase()) {
+                tokens.insert((word, index));
+            }
+            for word in Self::split_words(&entry.name.to_lowercase()) {
+                tokens.insert((word, index));
+            }
+            if let Some(category) = &entry.category {
+                tokens.insert((format!("${}", category).to_lowercase(), index));
+            }
+        }
+        for (tag_id, tag) in tags {
+            for id in tag.list() {
+                if let Some(index) = lookup.get(&id) {
+                    let index = *index as u32;
+                    tokens.insert((format!("#{}", tag_id).to_lowercase(), index));
+                    tokens.insert((format!("#{}", tag_id.get_key()).to_lowercase(), index));
+                }
+            }
+        }
+        let mut tokens: Vec<_> = tokens.into_iter().collect();
+        tokens.sort();
+        let mut categories: Vec<_> = entries
+            .iter()
+            .filter_map(|entry| entry.category.clone())
+            .collect();
+        categories.sort();
+        categories.dedup();
+        SearchIndex {
+            entries,
+            tokens,
+            categories,
+        }
+    }
+    pub fn get_categories(&self) -> &Vec<String> {
+        &self.categories
+    }
+    fn split_words(text: &str) -> Vec<String> {
+        text.split(|character: char| !character.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_string())
+            .collect()
+    }
+    fn find_prefix(&self, prefix: &str) -> HashSet<u32> {
+        let start = self
+            .tokens
+            .partition_point(|(token, _)| token.as_str() < prefix);
+        self.tokens[start..]
+            .iter()
+            .take_while(|(token, _)| token.starts_with(prefix))
+            .map(|(_, index)| *index)
+            .collect()
+    }
+    //terms have to match the start of a word, "#tag" matches tags, "$category" only the whole category and "@item" or "@block" limit the kind
+    pub fn search(&self, query: &str, page: usize, page_size: usize) -> SearchResults {
+        let mut matches: Option<HashSet<u32>> = None;
+        let mut items_only = false;
+        let mut blocks_only = false;
+        for term in query.to_lowercase().split_whitespace() {
+            match term {
+                "@item" => items_only = true,
+                "@block" => blocks_only = true,
+                term => {
+                    let mut found = self.find_prefix(term);
+                    if term.starts_with('$') {
+                        found.retain(|index| {
+                            self.entries[*index as usize]
+                                .category
+                                .as_ref()
+                                .map_or(false, |category| {
+                                    format!("${}", category).to_lowercase() == term
+                                })
+                        });
+                    }
+                    matches = Some(match matches {
+                        Some(matches) => matches.intersection(&found).cloned().collect(),
+                        None => found,
+                    });
+                }
+            }
+        }
+        let mut indices: Vec<u32> = match matches {
+            Some(matches) => matches.into_iter().collect(),
+            None => (0..self.entries.len() as u32).collect(),
+        };
+        indices.retain(|index| {
+            let entry = &self.entries[*index as usize];
+            (!items_only || entry.item) && (!blocks_only || entry.block)
+        });
+        indices.sort();
+        let page_size = page_size.max(1);
+        let pages = (indices.len() + page_size - 1) / page_size;
+        SearchResults {
+            ids: indices
+                .iter()
+                .skip(page * page_size)
+                .take(page_size)
+                .map(|index| self.entries[*index as usize].id.clone())
+                .collect(),
+            total: indices.len(),
+            page,
+            pages,
+        }
+    }
+    pub fn run_command(&self, command: &[&str]) -> String {
+        const PAGE_SIZE: usize = 10;
+        let (query, page) = match command.split_last() {
+            Some((last, query)) if !query.is_empty() && last.parse::<usize>().is_ok() => {
+                (query, last.parse::<usize>().unwrap().max(1) - 1)
+            }
+            _ => (command, 0),
+        };
+        let results = self.search(query.join(" ").as_str(), page, PAGE_SIZE);
+        if results.total == 0 {
+            return "nothing found".to_string();
+        }
+        format!(
+            "{} results (page {}/{}): {}",
+            results.total,
+            results.page + 1,
+            results.pages,
+            results
+                .ids
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+impl ScriptingObject for SearchIndex {
+    fn engine_register_server(env: &mut ExecutionEnvironment, server: &Weak<Server>) {
+        {
+            let server = server.clone();
+            env.register_function("item_categories", move || {
+                let categories: SharedArray = Arc::new(Mutex::new(
+                    server
+                        .upgrade()
+                        .unwrap()
+                        .search_index
+                        .get_categories()
+                        .iter()
+                        .map(|category| Variant::from_str(category.as_str()))
+                        .collect(),
+                ));
+                Ok(categories.into_variant())
+            });
+        }
+        let server = server.clone();
+        env.register_function(
+            "search",
+            move |query: &ImmutableString, page: &i64, page_size: &i64| {
+                let results = server.upgrade().unwrap().search_index.search(
+                    query.as_ref(),
+                    (*page).max(0) as usize,
+                    (*page_size).max(1) as usize,
+                );
+                let ids: SharedArray = Arc::new(Mutex::new(
+                    results
+                        .ids
+                        .iter()
+                        .map(|id| Variant::from_str(id.to_string().as_str()))
+                        .collect(),
+                ));
+                let mut output: HashMap<ImmutableString, Variant> = HashMap::new();
+                output.insert("results".into(), ids.into_variant());
+                output.insert("total".into(), (results.total as i64).into_variant());
+                output.insert("pages".into(), (results.pages as i64).into_variant());
+                Ok(Arc::new(Mutex::new(output)))
+            },
+        );
+    }
+}
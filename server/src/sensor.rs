@@ -0,0 +1,211 @@
+use std::collections::{HashMap, HashSet};
+
+use bbscript::variant::{IntoVariant, Variant};
+use block_byte_common::{BlockPosition, Face};
+use json::JsonValue;
+
+use crate::logging::{self, LogTarget};
+use crate::registry::BlockState;
+use crate::signal::{self, SignalType};
+use crate::transfer::StateFace;
+use crate::util::BlockLocation;
+use crate::world::{BlockData, Chunk, World};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Measurement {
+    Inventory,
+    Fluid,
+    Energy,
+    Signal,
+}
+impl Measurement {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "inventory" => Some(Measurement::Inventory),
+            "fluid" => Some(Measurement::Fluid),
+            "energy" => Some(Measurement::Energy),
+            "signal" => Some(Measurement::Signal),
+            _ => None,
+        }
+    }
+}
+
+//comparator style blocks, they watch the neighbor on one face and react when its level changes
+#[derive(Clone, Debug)]
+pub struct Sensor {
+    pub face: StateFace,
+    pub measurement: Measurement,
+    pub property: Option<String>,
+}
+impl Sensor {
+    pub fn from_json(json: &JsonValue) -> Result<Option<Self>, String> {
+        if json.is_null() {
+            return Ok(None);
+        }
+        let measurement = json["measure"]
+            .as_str()
+            .ok_or_else(|| "sensor needs a measurement".to_string())?;
+        Ok(Some(Sensor {
+            face: StateFace::from_json(&json["face"])
+                .ok_or_else(|| "sensor needs a face".to_string())?,
+            measurement: Measurement::from_name(measurement)
+                .ok_or_else(|| format!("unknown sensor measurement {}", measurement))?,
+            property: json["property"]
+                .as_str()
+                .map(|property| property.to_string()),
+        }))
+    }
+}
+
+//empty is 0, anything stored at all is at least 1 and full is MAX_POWER
+fn to_level(fraction: f64) -> u32 {
+    if fraction <= 0. {
+        return 0;
+    }
+    (1 + (fraction * (SignalType::MAX_POWER - 1) as f64) as u32).min(SignalType::MAX_POWER)
+}
+pub fn measure(world: &World, position: BlockPosition, measurement: Measurement) -> u32 {
+    if measurement == Measurement::Signal {
+        return signal::get_power(world, position);
+    }
+    let block = match world.get_block(&position) {
+        Some(BlockData::Data(block)) => block,
+        _ => return 0,
+    };
+    to_level(match measurement {
+        Measurement::Inventory => block.inventory.get_fill(),
+        Measurement::Fluid => block.inventory.get_fluid_tanks().get_fill(),
+        Measurement::Energy => block.get_energy_storage().map_or(0., |storage| {
+            block.get_energy() as f64 / storage.capacity.max(1) as f64
+        }),
+        Measurement::Signal => unreachable!(),
+    })
+}
+
+fn get_state(world: &World, position: BlockPosition) -> Option<&BlockState> {
+    let block = world.get_block(&position)?;
+    Some(
+        world
+            .server
+            .block_registry
+            .state_by_ref(block.get_block_state()),
+    )
+}
+fn get_watched_face(state: &BlockState) -> Option<Face> {
+    state.parent.sensor.as_ref().and_then(|sensor| {
+        sensor
+            .face
+            .resolve(&state.parent.properties, state.state_id)
+    })
+}
+
+//kept per chunk, so changes only lock the chunk they happen in
+pub struct ChunkSensors {
+    levels: HashMap<BlockPosition, u32>,
+    changed: HashSet<BlockPosition>,
+}
+impl ChunkSensors {
+    pub fn new() -> Self {
+        ChunkSensors {
+            levels: HashMap::new(),
+            changed: HashSet::new(),
+        }
+    }
+}
+
+//only remembers the position, sensors are looked up and evaluated on the next world tick
+pub fn on_chunk_change(chunk: &Chunk, position: BlockPosition) {
+    let mut sensors = chunk.sensors.lock();
+    if sensors.changed.is_empty() {
+        chunk.world.sensor_updates.lock().push(chunk.ptr());
+    }
+    sensors.changed.insert(position);
+}
+pub fn on_measured_change(world: &World, position: BlockPosition) {
+    if let Some(chunk) = world.get_chunk(position.to_chunk_pos()) {
+        on_chunk_change(&chunk, position);
+    }
+}
+
+pub fn tick(world: &World) {
+    let chunks: Vec<_> = world.sensor_updates.lock().drain(..).collect();
+    let mut evaluated = HashSet::new();
+    for chunk in chunks {
+        let changed: Vec<_> = chunk.sensors.lock().changed.drain().collect();
+        for position in changed {
+            if get_state(world, position).map_or(true, |state| state.parent.sensor.is_none()) {
+                chunk.sensors.lock().levels.remove(&position);
+            } else if evaluated.insert(position) {
+                evaluate(world, position);
+            }
+            for face in Face::all() {
+                let neighbor = position.offset_by_face(*face);
+                let watches_position = get_state(world, neighbor)
+                    .and_then(get_watched_face)
+                    .map_or(false, |watched| watched == face.opposite());
+                if watches_position && evaluated.insert(neighbor) {
+                    evaluate(world, neighbor);
+                }
+            }
+        }
+    }
+}
+
+fn evaluate(world: &World, position: BlockPosition) {
+    let state = match get_state(world, position) {
+        Some(state) => state,
+        None => return,
+    };
+    let (sensor, face) = match (&state.parent.sensor, get_watched_face(state)) {
+        (Some(sensor), Some(face)) => (sensor, face),
+        _ => return,
+    };
+    let level = measure(world, position.offset_by_face(face), sensor.measurement);
+    let chunk = match world.get_chunk(position.to_chunk_pos()) {
+        Some(chunk) => chunk,
+        None => return,
+    };
+    let previous = chunk
+        .sensors
+        .lock()
+        .levels
+        .insert(position, level)
+        .unwrap_or(0);
+    if level == previous {
+        return;
+    }
+    let world = world.ptr();
+    if let Some(property) = &sensor.property {
+        if let Ok(new_state) = state.with_property(property.as_str(), (level as i64).into_variant())
+        {
+            world.set_block(position, new_state, true, Variant::NULL());
+        }
+    }
+    if let Err(error) = state
+        .parent
+        .static_data
+        .get_function("on_sensor_change")
+        .call_function(
+            &world.server.script_environment,
+            Some(
+                BlockLocation {
+                    position,
+                    world: world.clone(),
+                }
+                .into_variant(),
+            ),
+            vec![
+                (level as i64).into_variant(),
+                (previous as i64).into_variant(),
+            ],
+        )
+    {
+        logging::error(
+            LogTarget::Scripts,
+            format!(
+                "on_sensor_change of {} failed: {:?}",
+                state.parent.id, error
+            ),
+        );
+    }
+}
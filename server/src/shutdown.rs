@@ -0,0 +1,178 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use block_byte_common::gui::{GUIComponent, GUIElement, PositionAnchor};
+use block_byte_common::messages::NetworkMessageS2C;
+use block_byte_common::{Color, Position};
+use parking_lot::Mutex;
+
+use crate::logging::{self, LogTarget};
+use crate::Server;
+
+struct PendingShutdown {
+    remaining_ticks: u64,
+    restart: bool,
+    announced: bool,
+}
+
+pub struct ShutdownManager {
+    pending: Mutex<Option<PendingShutdown>>,
+    finished: AtomicBool,
+    restart: AtomicBool,
+    uptime: AtomicU64,
+    auto_restarted: AtomicBool,
+}
+impl ShutdownManager {
+    const TICKS_PER_SECOND: u64 = 20;
+    const ANNOUNCE_SECONDS: [u64; 12] = [600, 300, 120, 60, 30, 15, 10, 5, 4, 3, 2, 1];
+    const BLOCK_JOINS_SECONDS: u64 = 30;
+    const AUTO_RESTART_COUNTDOWN: u64 = 60;
+    const GUI_ELEMENT: &'static str = "shutdown_countdown";
+    pub fn new() -> Self {
+        ShutdownManager {
+            pending: Mutex::new(None),
+            finished: AtomicBool::new(false),
+            restart: AtomicBool::new(false),
+            uptime: AtomicU64::new(0),
+            auto_restarted: AtomicBool::new(false),
+        }
+    }
+    pub fn schedule(&self, seconds: u64, restart: bool) {
+        *self.pending.lock() = Some(PendingShutdown {
+            remaining_ticks: seconds.saturating_mul(Self::TICKS_PER_SECOND),
+            restart,
+            announced: false,
+        });
+    }
+    pub fn cancel(&self, server: &Server) -> bool {
+        if self.pending.lock().take().is_none() {
+            return false;
+        }
+        for player in &*server.players.lock() {
+            player.send_message(&NetworkMessageS2C::GuiRemoveElements(
+                Self::GUI_ELEMENT.to_string(),
+            ));
+            player.send_chat_message("scheduled shutdown was cancelled".to_string());
+        }
+        true
+    }
+    pub fn is_joining_blocked(&self) -> bool {
+        self.pending.lock().as_ref().map_or(false, |shutdown| {
+            shutdown.remaining_ticks <= Self::BLOCK_JOINS_SECONDS * Self::TICKS_PER_SECOND
+        })
+    }
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+    pub fn get_exit_code(&self, server: &Server) -> i32 {
+        if self.restart.load(Ordering::Relaxed) {
+            server.settings.get_i64("server.restart_exit_code", 2) as i32
+        } else {
+            0
+        }
+    }
+    fn announce(server: &Server, seconds: u64, restart: bool) {
+        let text = format!(
+            "server {} in {} second{}",
+            if restart { "restarting" } else { "stopping" },
+            seconds,
+            if seconds == 1 { "" } else { "s" }
+        );
+        logging::info(LogTarget::Server, text.as_str());
+        for player in &*server.players.lock() {
+            player.send_message(&NetworkMessageS2C::GuiSetElement(
+                Self::GUI_ELEMENT.to_string(),
+                GUIElement {
+                    component_type: GUIComponent::TextComponent {
+                        font_size: 40.,
+                        text: text.clone(),
+                    },
+                    position: Position {
+                        x: 0.,
+                        y: 250.,
+                        z: 0.,
+                    },
+                    anchor: PositionAnchor::Center,
+                    base_color: Color::WHITE,
+                    size_percent: None,
+                },
+            ));
+            player.send_chat_message(text.clone());
+        }
+    }
+    pub fn tick(&self, server: &Server) {
+        let uptime = self.uptime.fetch_add(1, Ordering::Relaxed) + 1;
+        let auto_restart = server
+            .settings
+            .get_i64("server.auto_restart_minutes", 0)
+            .max(0) as u64
+            * 60
+            * Self::TICKS_PER_SECOND;
+        let mut pending = self.pending.lock();
+        if auto_restart > 0
+            && pending.is_none()
+            && uptime + Self::AUTO_RESTART_COUNTDOWN * Self::TICKS_PER_SECOND >= auto_restart
+            && !self.auto_restarted.swap(true, Ordering::Relaxed)
+        {
+            *pending = Some(PendingShutdown {
+                remaining_ticks: Self::AUTO_RESTART_COUNTDOWN * Self::TICKS_PER_SECOND,
+                restart: true,
+                announced: false,
+            });
+        }
+        let shutdown = match pending.as_mut() {
+            Some(shutdown) => shutdown,
+            None => return,
+        };
+        if shutdown.remaining_ticks == 0 {
+            self.restart.store(shutdown.restart, Ordering::Relaxed);
+            self.finished.store(true, Ordering::Relaxed);
+            let reason = if shutdown.restart {
+                "server is restarting"
+            } else {
+                "server stopped"
+            };
+            for player in &*server.players.lock() {
+                server.kick(player, reason.to_string());
+            }
+            return;
+        }
+        if shutdown.remaining_ticks % Self::TICKS_PER_SECOND == 0 {
+            let seconds = shutdown.remaining_ticks / Self::TICKS_PER_SECOND;
+            if !shutdown.announced || Self::ANNOUNCE_SECONDS.contains(&seconds) {
+                shutdown.announced = true;
+                Self::announce(server, seconds, shutdown.restart);
+            }
+        }
+        shutdown.remaining_ticks -= 1;
+    }
+    pub fn run_command(&self, server: &Server, command: &[&str]) -> Result<String, String> {
+        let restart = match command.first() {
+            Some(&"stop") => false,
+            Some(&"restart") => true,
+            _ => return Err("unknown command".to_string()),
+        };
+        match command.get(1) {
+            Some(&"cancel") => {
+                if self.cancel(server) {
+                    Ok("shutdown cancelled".to_string())
+                } else {
+                    Err("no shutdown is scheduled".to_string())
+                }
+            }
+            seconds => {
+                let seconds = match seconds {
+                    Some(seconds) => seconds
+                        .parse::<u64>()
+                        .map_err(|_| format!("invalid delay {}", seconds))?,
+                    None => 0,
+                };
+                self.schedule(seconds, restart);
+                Ok(format!(
+                    "{} scheduled in {} seconds",
+                    if restart { "restart" } else { "shutdown" },
+                    seconds
+                ))
+            }
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use bbscript::variant::IntoVariant;
+use block_byte_common::{BlockPosition, Face};
+use json::JsonValue;
+use parking_lot::Mutex;
+
+use crate::cable::{CableNetworks, Connection};
+use crate::logging::{self, LogTarget};
+use crate::sensor;
+use crate::util::{BlockLocation, Identifier};
+use crate::world::{BlockNetwork, World};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignalType {
+    Emitter(u32),
+    Conductor,
+    Consumer,
+}
+impl SignalType {
+    pub const MAX_POWER: u32 = 15;
+    pub fn from_json(json: &JsonValue) -> Result<Option<Self>, String> {
+        if json.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(
+            match json["type"].as_str().ok_or("missing signal type")? {
+                "emitter" => SignalType::Emitter(
+                    json["power"]
+                        .as_u32()
+                        .unwrap_or(Self::MAX_POWER)
+                        .min(Self::MAX_POWER),
+                ),
+                "conductor" => SignalType::Conductor,
+                "consumer" => SignalType::Consumer,
+                signal_type => return Err(format!("unknown signal type {}", signal_type)),
+            },
+        ))
+    }
+}
+
+fn get_signal(world: &World, position: BlockPosition) -> Option<SignalType> {
+    let block = world.get_block(&position)?;
+    world
+        .server
+        .block_registry
+        .state_by_ref(block.get_block_state())
+        .parent
+        .signal
+}
+
+pub struct SignalNetworks {
+    conductors: CableNetworks<(BlockPosition, SignalType)>,
+    received: Mutex<HashMap<BlockPosition, u32>>,
+}
+impl SignalNetworks {
+    pub fn new() -> SignalNetworks {
+        SignalNetworks {
+            conductors: CableNetworks::new(Identifier::new("bb", "signal")),
+            received: Mutex::new(HashMap::new()),
+        }
+    }
+    pub fn on_chunk_load(&self, positions: Vec<BlockPosition>) {
+        self.conductors.on_chunk_load(positions);
+    }
+    pub fn get_network(&self, position: BlockPosition) -> Option<Arc<BlockNetwork>> {
+        self.conductors.get_network(position)
+    }
+    pub fn get_received_power(&self, position: BlockPosition) -> u32 {
+        self.received.lock().get(&position).cloned().unwrap_or(0)
+    }
+    fn rebuild(&self, world: &World, start: BlockPosition) -> Vec<BlockPosition> {
+        let (members, endpoints) = self.conductors.rebuild(
+            world,
+            start,
+            |neighbor, _| match get_signal(world, neighbor) {
+                Some(SignalType::Conductor) => Connection::Cable,
+                Some(signal) => Connection::Endpoint((neighbor, signal)),
+                None => Connection::None,
+            },
+            |network, endpoints| {
+                network.set_power(
+                    endpoints
+                        .iter()
+                        .map(|(_, signal)| match signal {
+                            SignalType::Emitter(power) => *power,
+                            _ => 0,
+                        })
+                        .max()
+                        .unwrap_or(0),
+                );
+            },
+        );
+        for member in members {
+            sensor::on_measured_change(world, member);
+        }
+        endpoints
+            .iter()
+            .filter(|(_, signal)| *signal == SignalType::Consumer)
+            .map(|(position, _)| *position)
+            .collect()
+    }
+    fn compute_received_power(&self, world: &World, position: BlockPosition) -> u32 {
+        let mut power = 0;
+        for face in Face::all() {
+            let neighbor = position.offset_by_face(*face);
+            power = power.max(match get_signal(world, neighbor) {
+                Some(SignalType::Emitter(emitter_power)) => emitter_power,
+                Some(SignalType::Conductor) => self
+                    .get_network(neighbor)
+                    .map(|network| network.get_power())
+                    .unwrap_or(0),
+                _ => 0,
+            });
+        }
+        power
+    }
+    fn update_consumer(&self, world: &World, position: BlockPosition) {
+        let power = self.compute_received_power(world, position);
+        let previous = self.received.lock().insert(position, power).unwrap_or(0);
+        if previous == power {
+            return;
+        }
+        sensor::on_measured_change(world, position);
+        let block = match world.get_block(&position) {
+            Some(block) => block,
+            None => return,
+        };
+        let block_type = &world
+            .server
+            .block_registry
+            .state_by_ref(block.get_block_state())
+            .parent;
+        if let Err(error) = block_type
+            .static_data
+            .get_function("on_power_change")
+            .call_function(
+                &world.server.script_environment,
+                Some(
+                    BlockLocation {
+                        position,
+                        world: world.ptr(),
+                    }
+                    .into_variant(),
+                ),
+                vec![
+                    (power as i64).into_variant(),
+                    (previous as i64).into_variant(),
+                ],
+            )
+        {
+            logging::error(
+                LogTarget::Scripts,
+                format!("on_power_change of {} failed: {:?}", block_type.id, error),
+            );
+        }
+    }
+}
+
+pub fn tick(world: &World) {
+    for position in world.signal_networks.conductors.take_loaded() {
+        on_block_update(world, position);
+    }
+}
+
+pub fn on_block_update(world: &World, position: BlockPosition) {
+    let signals = &world.signal_networks;
+    let signal = get_signal(world, position);
+    let mut consumers = Vec::new();
+    if signal == Some(SignalType::Conductor) {
+        if !signals.conductors.was_rebuilt(world, position) {
+            consumers.extend(signals.rebuild(world, position));
+        }
+    } else {
+        signals.conductors.remove(position);
+        if signal != Some(SignalType::Consumer) {
+            signals.received.lock().remove(&position);
+        }
+        for face in Face::all() {
+            let neighbor = position.offset_by_face(*face);
+            match get_signal(world, neighbor) {
+                Some(SignalType::Conductor) => {
+                    if !signals.conductors.was_rebuilt(world, neighbor) {
+                        consumers.extend(signals.rebuild(world, neighbor));
+                    }
+                }
+                Some(SignalType::Consumer) => consumers.push(neighbor),
+                _ => {}
+            }
+        }
+        if signal == Some(SignalType::Consumer) {
+            consumers.push(position);
+        }
+    }
+    let mut updated = HashSet::new();
+    for consumer in consumers {
+        if updated.insert(consumer) {
+            signals.update_consumer(world, consumer);
+        }
+    }
+}
+
+pub fn get_power(world: &World, position: BlockPosition) -> u32 {
+    match get_signal(world, position) {
+        Some(SignalType::Emitter(power)) => power,
+        Some(SignalType::Conductor) => world
+            .signal_networks
+            .get_network(position)
+            .map(|network| network.get_power())
+            .unwrap_or(0),
+        Some(SignalType::Consumer) => world.signal_networks.get_received_power(position),
+        None => 0,
+    }
+}
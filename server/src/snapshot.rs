@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::Weak;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bbscript::variant::{IntoVariant, Variant};
+use image::io::Reader;
+use image::ImageFormat;
+
+use crate::logging::{self, LogTarget};
+use crate::mods::ScriptCallback;
+use crate::world::PlayerData;
+
+//who is told once the snapshot arrives, the player declines or the request times out
+pub enum SnapshotRequester {
+    Player(Weak<PlayerData>),
+    Script(ScriptCallback),
+}
+impl SnapshotRequester {
+    pub fn finish(self, player: &PlayerData, result: Result<String, String>) {
+        match self {
+            SnapshotRequester::Player(requester) => {
+                if let Some(requester) = requester.upgrade() {
+                    requester.send_chat_message(match result {
+                        Ok(file) => {
+                            format!("snapshot of {} saved as {}", player.identity.username, file)
+                        }
+                        Err(error) => {
+                            format!("no snapshot of {}: {}", player.identity.username, error)
+                        }
+                    });
+                }
+            }
+            SnapshotRequester::Script(callback) => {
+                if let Err(error) = callback.call_function(
+                    &player.server.script_environment,
+                    None,
+                    vec![
+                        player.ptr().into_variant(),
+                        match result {
+                            Ok(file) => Variant::from_str(file.as_str()),
+                            Err(_) => Variant::NULL(),
+                        },
+                    ],
+                ) {
+                    logging::error(
+                        LogTarget::Scripts,
+                        format!(
+                            "snapshot callback for {} failed: {:?}",
+                            player.identity.username, error
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+pub struct SnapshotRequests {
+    next_id: u32,
+    pending: HashMap<u32, (Instant, u32, SnapshotRequester)>,
+}
+impl SnapshotRequests {
+    pub const MAX_SIZE: u32 = 512;
+    const TIMEOUT: Duration = Duration::from_secs(30);
+    pub fn new() -> Self {
+        SnapshotRequests {
+            next_id: 0,
+            pending: HashMap::new(),
+        }
+    }
+    pub fn add(&mut self, max_size: u32, requester: SnapshotRequester) -> u32 {
+        let id = self.next_id;
+        self.next_id = id.wrapping_add(1);
+        self.pending
+            .insert(id, (Instant::now(), max_size, requester));
+        id
+    }
+    pub fn take(&mut self, id: u32) -> Option<(u32, SnapshotRequester)> {
+        self.pending
+            .remove(&id)
+            .map(|(_, max_size, requester)| (max_size, requester))
+    }
+    pub fn take_expired(&mut self) -> Vec<SnapshotRequester> {
+        let expired: Vec<u32> = self
+            .pending
+            .iter()
+            .filter(|(_, (requested, _, _))| requested.elapsed() > Self::TIMEOUT)
+            .map(|(id, _)| *id)
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|id| self.pending.remove(&id))
+            .map(|(_, _, requester)| requester)
+            .collect()
+    }
+}
+
+//clients can send anything, so only pngs within the requested size are written to the snapshots folder
+pub fn save(player: &PlayerData, data: &[u8], max_size: u32) -> Result<String, String> {
+    //the size is checked before decoding, so a tiny file can't claim a huge image
+    let (width, height) = Reader::with_format(Cursor::new(data), ImageFormat::Png)
+        .into_dimensions()
+        .map_err(|_| "invalid image".to_string())?;
+    if width > max_size || height > max_size {
+        return Err("image larger than requested".to_string());
+    }
+    Reader::with_format(Cursor::new(data), ImageFormat::Png)
+        .decode()
+        .map_err(|_| "invalid image".to_string())?;
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let file = format!("{}-{}.png", player.identity.uuid, time);
+    let directory = player.server.save_directory.join("snapshots");
+    std::fs::create_dir_all(&directory)
+        .and_then(|_| std::fs::write(directory.join(&file), data))
+        .map_err(|error| format!("could not save snapshot: {}", error))?;
+    logging::info(
+        LogTarget::Server,
+        format!(
+            "saved snapshot of {} as snapshots/{}",
+            player.identity.username, file
+        ),
+    );
+    Ok(file)
+}
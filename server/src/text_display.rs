@@ -0,0 +1,148 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Weak};
+
+use bbscript::eval::ExecutionEnvironment;
+use bbscript::variant::Variant;
+use block_byte_common::messages::NetworkMessageS2C;
+use block_byte_common::{ChunkPosition, Color, Position};
+use immutable_string::ImmutableString;
+use parking_lot::Mutex;
+
+use crate::mods::ScriptingObject;
+use crate::util::Location;
+use crate::world::{PlayerData, World};
+use crate::Server;
+
+static TEXT_DISPLAY_ID_GENERATOR: AtomicU32 = AtomicU32::new(0);
+
+pub struct TextDisplay {
+    pub id: u32,
+    world: Weak<World>,
+    position: Mutex<Position>,
+    text: Mutex<String>,
+    background: Mutex<Color>,
+    scale: Mutex<f32>,
+}
+impl TextDisplay {
+    const DEFAULT_BACKGROUND: Color = Color {
+        r: 0,
+        g: 0,
+        b: 0,
+        a: 96,
+    };
+    pub fn spawn(location: &Location, text: String) -> Arc<TextDisplay> {
+        let text_display = Arc::new(TextDisplay {
+            id: TEXT_DISPLAY_ID_GENERATOR.fetch_add(1, Ordering::Relaxed),
+            world: Arc::downgrade(&location.world),
+            position: Mutex::new(location.position),
+            text: Mutex::new(text),
+            background: Mutex::new(Self::DEFAULT_BACKGROUND),
+            scale: Mutex::new(1.),
+        });
+        location.world.add_text_display(text_display.clone());
+        text_display.announce(&text_display.create_add_message());
+        text_display
+    }
+    pub fn get_position(&self) -> Position {
+        *self.position.lock()
+    }
+    pub fn get_chunk_position(&self) -> ChunkPosition {
+        self.get_position().to_chunk_pos()
+    }
+    pub fn create_add_message(&self) -> NetworkMessageS2C {
+        NetworkMessageS2C::AddTextDisplay(
+            self.id,
+            self.get_position(),
+            self.text.lock().clone(),
+            *self.background.lock(),
+            *self.scale.lock(),
+        )
+    }
+    pub fn sync_to(&self, viewer: &PlayerData) {
+        viewer.send_message(&self.create_add_message());
+    }
+    fn announce(&self, message: &NetworkMessageS2C) {
+        if let Some(world) = self.world.upgrade() {
+            if let Some(chunk) = world.get_chunk(self.get_chunk_position()) {
+                chunk.announce_to_viewers(message);
+            }
+        }
+    }
+    pub fn set_text(&self, text: String) {
+        *self.text.lock() = text;
+        self.announce(&self.create_add_message());
+    }
+    pub fn set_background(&self, background: Color) {
+        *self.background.lock() = background;
+        self.announce(&self.create_add_message());
+    }
+    pub fn set_scale(&self, scale: f32) {
+        *self.scale.lock() = scale.max(0.);
+        self.announce(&self.create_add_message());
+    }
+    pub fn teleport(&self, position: Position) {
+        self.announce(&NetworkMessageS2C::RemoveTextDisplay(self.id));
+        *self.position.lock() = position;
+        self.announce(&self.create_add_message());
+    }
+    pub fn remove(&self) {
+        if let Some(world) = self.world.upgrade() {
+            world.remove_text_display(self.id);
+        }
+        self.announce(&NetworkMessageS2C::RemoveTextDisplay(self.id));
+    }
+}
+impl ScriptingObject for TextDisplay {
+    fn engine_register_server(env: &mut ExecutionEnvironment, _server: &Weak<Server>) {
+        env.register_custom_name::<Arc<TextDisplay>, _>("TextDisplay");
+        env.register_function(
+            "TextDisplay",
+            |location: &Location, text: &ImmutableString| {
+                Ok(TextDisplay::spawn(location, text.to_string()))
+            },
+        );
+        env.register_member("text", |text_display: &Arc<TextDisplay>| {
+            Some(Variant::from_str(text_display.text.lock().as_str()))
+        });
+        env.register_member("position", |text_display: &Arc<TextDisplay>| {
+            Some(text_display.get_position())
+        });
+        env.register_method(
+            "set_text",
+            |text_display: &Arc<TextDisplay>, text: &ImmutableString| {
+                text_display.set_text(text.to_string());
+                Ok(())
+            },
+        );
+        env.register_method(
+            "set_background",
+            |text_display: &Arc<TextDisplay>, r: &i64, g: &i64, b: &i64, a: &i64| {
+                text_display.set_background(Color {
+                    r: (*r).clamp(0, 255) as u8,
+                    g: (*g).clamp(0, 255) as u8,
+                    b: (*b).clamp(0, 255) as u8,
+                    a: (*a).clamp(0, 255) as u8,
+                });
+                Ok(())
+            },
+        );
+        env.register_method(
+            "set_scale",
+            |text_display: &Arc<TextDisplay>, scale: &f64| {
+                text_display.set_scale(*scale as f32);
+                Ok(())
+            },
+        );
+        env.register_method(
+            "teleport",
+            |text_display: &Arc<TextDisplay>, position: &Position| {
+                text_display.teleport(*position);
+                Ok(())
+            },
+        );
+        env.register_method("remove", |text_display: &Arc<TextDisplay>| {
+            text_display.remove();
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use bbscript::variant::FromVariant;
+use block_byte_common::{Face, HorizontalFace, Vec3};
+use json::JsonValue;
+
+use crate::registry::{BlockStatePropertyKey, BlockStatePropertyStorage};
+
+fn parse_face(name: &str) -> Option<Face> {
+    Face::all().iter().find(|face| face.name() == name).cloned()
+}
+
+//slot ranges of a block inventory reachable from each face
+#[derive(Clone, Debug)]
+pub struct SidedAccess {
+    sides: HashMap<Face, Range<u32>>,
+}
+impl SidedAccess {
+    pub fn from_json(json: &JsonValue, inventory_size: u32) -> Result<Option<Self>, String> {
+        if json.is_null() {
+            return Ok(None);
+        }
+        let mut sides = HashMap::new();
+        for (face, range) in json.entries() {
+            let range = match range
+                .as_str()
+                .and_then(|range| range.split_once(".."))
+                .and_then(|(start, end)| {
+                    Some((start.parse::<u32>().ok()?, end.parse::<u32>().ok()?))
+                }) {
+                Some((start, end)) => start.min(inventory_size)..end.min(inventory_size),
+                None => {
+                    return Err(format!(
+                        "slot range of side {} must look like start..end",
+                        face
+                    ))
+                }
+            };
+            if face == "all" {
+                for face in Face::all() {
+                    sides.entry(*face).or_insert(range.clone());
+                }
+            } else {
+                sides.insert(
+                    parse_face(face).ok_or_else(|| format!("unknown face {}", face))?,
+                    range,
+                );
+            }
+        }
+        Ok(Some(SidedAccess { sides }))
+    }
+    //faces missing from the configuration expose no slots
+    pub fn get_slots(&self, face: Face) -> Range<u32> {
+        self.sides.get(&face).cloned().unwrap_or(0..0)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum StateFace {
+    Fixed(Face),
+    //read from a Face or HorizontalFace property of the block state
+    Property(String),
+}
+impl StateFace {
+    pub fn from_json(json: &JsonValue) -> Option<Self> {
+        json.as_str().map(|name| match parse_face(name) {
+            Some(face) => StateFace::Fixed(face),
+            None => StateFace::Property(name.to_string()),
+        })
+    }
+    pub fn resolve(&self, properties: &BlockStatePropertyStorage, state: u32) -> Option<Face> {
+        match self {
+            StateFace::Fixed(face) => Some(*face),
+            StateFace::Property(property) => {
+                let value = properties.get_from_state(state, BlockStatePropertyKey::Name(property));
+                Face::from_variant(&value)
+                    .cloned()
+                    .or_else(|| HorizontalFace::from_variant(&value).map(|face| face.to_face()))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ItemTransfer {
+    pub push: Option<StateFace>,
+    pub pull: Option<StateFace>,
+    pub interval: u32,
+    pub count: u32,
+}
+impl ItemTransfer {
+    pub fn from_json(json: &JsonValue) -> Option<Self> {
+        if json.is_null() {
+            return None;
+        }
+        Some(ItemTransfer {
+            push: StateFace::from_json(&json["push"]),
+            pull: StateFace::from_json(&json["pull"]),
+            interval: json["interval"].as_u32().unwrap_or(8).max(1),
+            count: json["count"].as_u32().unwrap_or(1).max(1),
+        })
+    }
+}
+
+//conveyor belts and boost pads, entities standing on the block are moved towards the face
+#[derive(Clone, Debug)]
+pub struct SurfaceMovement {
+    pub direction: StateFace,
+    pub speed: f32,
+}
+impl SurfaceMovement {
+    pub fn from_json(json: &JsonValue) -> Result<Option<Self>, String> {
+        if json.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(SurfaceMovement {
+            direction: StateFace::from_json(&json["direction"])
+                .ok_or_else(|| "surface needs a direction".to_string())?,
+            speed: json["speed"].as_f32().unwrap_or(1.),
+        }))
+    }
+    //in blocks per second, resolved once per state so clients get it with the block data
+    pub fn get_velocity(&self, properties: &BlockStatePropertyStorage, state: u32) -> Option<Vec3> {
+        self.direction.resolve(properties, state).map(|face| {
+            let offset = face.get_offset();
+            Vec3 {
+                x: offset.x as f32 * self.speed,
+                y: offset.y as f32 * self.speed,
+                z: offset.z as f32 * self.speed,
+            }
+        })
+    }
+}
@@ -1,8 +1,11 @@
 use crate::mods::ScriptingObject;
 use crate::registry::BlockStateRef;
+use crate::sensor::{self, Measurement};
+use crate::signal;
 use crate::Server;
 use anyhow::anyhow;
-use bbscript::eval::ExecutionEnvironment;
+use bbscript::eval::{ExecutionEnvironment, ScriptError};
+use bbscript::lex::FilePosition;
 use bbscript::variant::{FromVariant, IntoVariant, Variant};
 use block_byte_common::{BlockPosition, Face, Position};
 use immutable_string::ImmutableString;
@@ -192,6 +195,19 @@ impl ScriptingObject for BlockLocation {
                 Ok(())
             },
         );
+        env.register_method("get_signal_power", |location: &BlockLocation| {
+            Ok(signal::get_power(&location.world, location.position) as i64)
+        });
+        env.register_method(
+            "measure",
+            |location: &BlockLocation, measurement: &ImmutableString| {
+                let measurement =
+                    Measurement::from_name(measurement.as_ref()).ok_or_else(|| {
+                        ScriptError::runtime("unknown measurement", FilePosition::INVALID)
+                    })?;
+                Ok(sensor::measure(&location.world, location.position, measurement) as i64)
+            },
+        );
         env.register_method("get_block_load", |location: &BlockLocation| {
             Ok(location
                 .world
@@ -1,64 +1,181 @@
 use std::any::{Any, TypeId};
-use std::fmt::Formatter;
 use std::hash::Hasher;
-use std::ops::{Add, Range};
+use std::ops::Range;
 use std::sync::atomic::Ordering;
 use std::{
     collections::{HashMap, HashSet},
     hash::Hash,
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::{
-        atomic::{AtomicBool, AtomicU32, AtomicU8},
+        atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU64, AtomicU8},
         Arc, Weak,
     },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use array_init::array_init;
 use atomic_counter::{AtomicCounter, RelaxedCounter};
-use bbscript::eval::ExecutionEnvironment;
+use bbscript::eval::{ExecutionEnvironment, ScriptError};
 use bbscript::lex::FilePosition;
 use bbscript::variant::{
-    FromVariant, FunctionType, FunctionVariant, IntoVariant, Primitive, Variant,
+    Array, FromVariant, FunctionType, FunctionVariant, IntoVariant, Map, Primitive, SharedArray,
+    SharedMap, Variant,
 };
 use bitcode::__private::Serialize;
+use block_byte_common::content::Transformation;
 use block_byte_common::gui::{
     GUIComponent, GUIComponentEdit, GUIElement, GUIElementEdit, PositionAnchor,
 };
 use block_byte_common::messages::{
-    ClientModelTarget, MovementType, NetworkMessageC2S, NetworkMessageS2C,
+    BonePose, Capability, ChunkCompression, ClientModelTarget, MovementType, NetworkMessageC2S,
+    NetworkMessageS2C, SoundCategory, MAX_PLACE_BATCH,
 };
 use block_byte_common::{
     BlockPosition, ChunkPosition, Color, Direction, Face, KeyboardKey, KeyboardModifier, Position,
-    Vec2, AABB,
+    SkySettings, Vec2, Vec3, WorldBorder, AABB,
 };
-use flate2::Compression;
 use fxhash::{FxHashMap, FxHashSet};
 use immutable_string::ImmutableString;
 use json::{object, JsonValue};
 use parking_lot::Mutex;
 use pathfinding::prelude::astar;
 use rand::{thread_rng, Rng};
-use serde::de::Error;
 use serde::{Deserialize, Deserializer, Serializer};
 use uuid::Uuid;
 
+use crate::ai::{BlockClaims, ScheduleController};
+use crate::auth::PlayerIdentity;
+use crate::chunkgen::GenerationQueue;
+use crate::clipboard::Clipboard;
+use crate::compression::ChunkEncoder;
+use crate::crafting::CraftingGrid;
+use crate::creative::CreativeCatalog;
+use crate::debug_shapes::{self, DebugShapeView};
+use crate::detector;
+use crate::energy::{self, EnergyNetworks, EnergyStorage};
+use crate::explosion;
+use crate::fire;
+use crate::gamerules::{GameRuleValue, GameRules};
 use crate::inventory::{
     GUILayout, GuiInventoryData, GuiInventoryViewer, GuiKey, InventorySaveData, InventoryView,
-    ModGuiViewer,
+    LegacyInventorySaveData, ModGuiViewer, OwnedInventoryView,
 };
+use crate::logging::{self, LogTarget};
+use crate::metrics::{Metrics, TickPhase};
+use crate::migration::SaveManifest;
 use crate::mods::{ScriptCallback, ScriptingObject, UserDataWrapper};
-use crate::registry::{Block, BlockState};
+use crate::overlay::Overlay;
+use crate::placement;
+use crate::portal::Portal;
+use crate::recording::{self, SessionRecorder};
+use crate::registry::Block;
+use crate::sensor::{self, ChunkSensors};
+use crate::signal::{self, SignalNetworks};
+use crate::snapshot::{self, SnapshotRequester, SnapshotRequests};
+use crate::text_display::TextDisplay;
+use crate::transfer::StateFace;
 use crate::util::BlockLocation;
 use crate::{
     inventory::{Inventory, InventoryWrapper, ItemStack, WeakInventoryWrapper},
     net::PlayerConnection,
-    registry::{BlockRegistry, BlockStateRef, EntityType, InteractionResult},
+    registry::{BlockRegistry, BlockStateRef, EntityType, Hand, InteractionResult, Item},
     util::{ChunkBlockLocation, ChunkLocation, Identifier, Location},
     worldgen::WorldGenerator,
-    Server,
+    Server, ServerSettings,
 };
 
+pub struct WorldMetadata {
+    pub seed: u64,
+    pub generator: Identifier,
+    //kept as they are, for generators that take options
+    pub generator_options: JsonValue,
+    pub time: u64,
+    pub spawn_point: Position,
+    pub gamerules: Vec<(String, String)>,
+}
+impl WorldMetadata {
+    const FILE_NAME: &'static str = "world.json";
+    pub fn new(seed: u64, generator: Identifier) -> Self {
+        WorldMetadata {
+            seed,
+            generator,
+            generator_options: JsonValue::new_object(),
+            time: 0,
+            spawn_point: Position {
+                x: 0.,
+                y: 100.,
+                z: 0.,
+            },
+            gamerules: Vec::new(),
+        }
+    }
+    pub fn load(directory: &Path) -> Option<Self> {
+        let path = directory.join(Self::FILE_NAME);
+        let data = std::fs::read_to_string(&path).ok()?;
+        let metadata = json::parse(data.as_str())
+            .ok()
+            .and_then(|json| Self::from_json(&json));
+        if metadata.is_none() {
+            logging::warn(
+                LogTarget::Server,
+                format!("could not read world metadata {}", path.display()),
+            );
+        }
+        metadata
+    }
+    fn from_json(json: &JsonValue) -> Option<Self> {
+        let spawn_point = &json["spawn_point"];
+        Some(WorldMetadata {
+            seed: json["seed"].as_u64()?,
+            generator: Identifier::parse(json["generator"].as_str()?).ok()?,
+            generator_options: json["generator_options"].clone(),
+            time: json["time"].as_u64().unwrap_or(0),
+            spawn_point: Position {
+                x: spawn_point["x"].as_f64().unwrap_or(0.),
+                y: spawn_point["y"].as_f64().unwrap_or(100.),
+                z: spawn_point["z"].as_f64().unwrap_or(0.),
+            },
+            gamerules: json["gamerules"]
+                .entries()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        })
+    }
+    pub fn save(&self, directory: &Path) {
+        let mut gamerules = JsonValue::new_object();
+        for (name, value) in &self.gamerules {
+            let value = if let Ok(value) = value.parse::<bool>() {
+                JsonValue::from(value)
+            } else if let Ok(value) = value.parse::<f64>() {
+                JsonValue::from(value)
+            } else {
+                JsonValue::from(value.as_str())
+            };
+            gamerules.insert(name, value).unwrap();
+        }
+        let json = object! {
+            seed: self.seed,
+            generator: self.generator.to_string(),
+            generator_options: self.generator_options.clone(),
+            time: self.time,
+            spawn_point: object! {
+                x: self.spawn_point.x,
+                y: self.spawn_point.y,
+                z: self.spawn_point.z,
+            },
+            gamerules: gamerules,
+        };
+        let path = directory.join(Self::FILE_NAME);
+        if let Err(error) = std::fs::write(&path, json.pretty(4)) {
+            logging::error(
+                LogTarget::Server,
+                format!("could not save {}: {}", path.display(), error),
+            );
+        }
+    }
+}
+
 pub struct World {
     pub server: Arc<Server>,
     this: Weak<Self>,
@@ -66,15 +183,68 @@ pub struct World {
     unload_timer: RelaxedCounter,
     world_generator: WorldGenerator,
     unloaded_structure_placements:
-        Mutex<HashMap<ChunkPosition, Vec<(BlockPosition, Arc<Structure>)>>>,
+        Mutex<HashMap<ChunkPosition, Vec<(BlockPosition, Arc<Structure>, StructureTransform)>>>,
     pub id: Identifier,
     temporary: bool,
     pub user_data: Mutex<UserData>,
+    seed: u64,
+    generator: Identifier,
+    generator_options: JsonValue,
+    time: AtomicU64,
+    spawn_point: Mutex<Position>,
+    pub sleep_ticks: AtomicU32,
+    pub settings: ServerSettings,
+    pub gamerules: ServerSettings,
+    border: Mutex<WorldBorder>,
+    daylight_cycle: AtomicBool,
+    pub tick_budget: TickBudget,
+    pub generation_queue: GenerationQueue,
+    text_displays: Mutex<FxHashMap<u32, Arc<TextDisplay>>>,
+    block_updates: Mutex<Vec<Arc<Chunk>>>,
+    pub sensor_updates: Mutex<Vec<Arc<Chunk>>>,
+    pub signal_networks: SignalNetworks,
+    pub energy_networks: EnergyNetworks,
 }
 
 impl World {
     const UNLOAD_TIME: usize = 1000;
-    pub fn new(server: Arc<Server>, world_generator: WorldGenerator, id: Identifier) -> Arc<Self> {
+    pub const DAY_LENGTH: u64 = 24000;
+    pub const SLEEP_SKIP_DELAY: u32 = 40;
+    const SKY_SYNC_INTERVAL: u64 = 400;
+    pub fn new(
+        server: Arc<Server>,
+        world_generator: WorldGenerator,
+        id: Identifier,
+        metadata: WorldMetadata,
+    ) -> Arc<Self> {
+        let settings = ServerSettings::load_from_file(
+            &World::get_path(&server.save_directory, &id).join("settings.txt"),
+        );
+        let user_data = World::load_user_data(&server, &id);
+        let gamerules = ServerSettings::new();
+        for (name, value) in metadata.gamerules {
+            gamerules.set(format!("gamerule.{}", name).as_str(), value);
+        }
+        //older worlds kept these in settings.txt, they are moved to the metadata
+        for (name, value) in settings.remove_prefixed("gamerule.") {
+            gamerules.set(format!("gamerule.{}", name).as_str(), value);
+        }
+        let mut spawn_point = metadata.spawn_point;
+        for (axis, value) in settings.remove_prefixed("spawn.") {
+            if let Ok(value) = value.parse() {
+                match axis.as_str() {
+                    "x" => spawn_point.x = value,
+                    "y" => spawn_point.y = value,
+                    "z" => spawn_point.z = value,
+                    _ => {}
+                }
+            }
+        }
+        let border = WorldBorder {
+            center_x: settings.get_f64("border.center_x", 0.),
+            center_z: settings.get_f64("border.center_z", 0.),
+            radius: settings.get_f64("border.radius", WorldBorder::DEFAULT_RADIUS),
+        };
         let world = Arc::new_cyclic(|this| World {
             this: this.clone(),
             chunks: Mutex::new(FxHashMap::default()),
@@ -84,24 +254,68 @@ impl World {
             unloaded_structure_placements: Mutex::new(HashMap::new()),
             id,
             temporary: false,
-            user_data: Mutex::new(UserData::new()),
+            user_data: Mutex::new(user_data),
+            seed: metadata.seed,
+            generator: metadata.generator,
+            generator_options: metadata.generator_options,
+            time: AtomicU64::new(metadata.time),
+            spawn_point: Mutex::new(spawn_point),
+            sleep_ticks: AtomicU32::new(0),
+            settings,
+            gamerules,
+            border: Mutex::new(border),
+            daylight_cycle: AtomicBool::new(true),
+            text_displays: Mutex::new(FxHashMap::default()),
+            block_updates: Mutex::new(Vec::new()),
+            sensor_updates: Mutex::new(Vec::new()),
+            tick_budget: TickBudget::new(),
+            generation_queue: GenerationQueue::new(),
+            signal_networks: SignalNetworks::new(),
+            energy_networks: EnergyNetworks::new(Identifier::new("bb", "energy")),
         });
         std::fs::create_dir_all(world.get_world_path()).unwrap();
+        SaveManifest::create_if_missing(&world.get_world_path());
+        //a crash before the first save must not lose the seed of a new world
+        world.save_metadata();
         world
     }
+    pub fn get_path(save_directory: &Path, id: &Identifier) -> PathBuf {
+        save_directory.join("worlds").join(id.to_string())
+    }
     pub fn get_world_path(&self) -> PathBuf {
-        let mut path = self.server.save_directory.clone();
+        World::get_path(&self.server.save_directory, &self.id)
+    }
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+    pub fn get_generator_options(&self) -> &JsonValue {
+        &self.generator_options
+    }
+    fn load_user_data(server: &Server, id: &Identifier) -> UserData {
+        let mut path = server.save_directory.clone();
         path.push("worlds");
-        path.push(self.id.to_string());
-        path
+        path.push(id.to_string());
+        path.push("user_data.bin");
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(_) => return UserData::new(),
+        };
+        bitcode::deserialize(data.as_slice()).unwrap_or_else(|_| {
+            logging::warn(
+                LogTarget::Server,
+                format!("could not read user data of world {}", id),
+            );
+            UserData::new()
+        })
     }
     pub fn place_structure(
         &self,
         position: BlockPosition,
         structure: &Arc<Structure>,
+        transform: StructureTransform,
         load_chunks: bool,
     ) {
-        let chunks = structure.get_chunks(position);
+        let chunks = structure.get_chunks(position, transform);
         for chunk_position in chunks {
             let chunk = if load_chunks {
                 Some(self.load_chunk(chunk_position))
@@ -119,7 +333,9 @@ impl World {
                 .unwrap_or(false);
             {
                 if loaded {
-                    chunk.unwrap().place_structure(position, structure.clone());
+                    chunk
+                        .unwrap()
+                        .place_structure(position, structure.clone(), transform);
                 } else {
                     let mut unloaded_structure_placements =
                         self.unloaded_structure_placements.lock();
@@ -130,7 +346,7 @@ impl World {
                     let placement_list = unloaded_structure_placements
                         .get_mut(&chunk_position)
                         .unwrap();
-                    placement_list.push((position, structure.clone()));
+                    placement_list.push((position, structure.clone(), transform));
                 }
             }
         }
@@ -162,11 +378,12 @@ impl World {
         let chunks = self.get_chunks_with_center_radius(position.to_chunk_pos(), 1);
         for chunk in chunks {
             for entity in &*chunk.entities.lock() {
-                if entity
-                    .get_collider()
-                    .iter_blocks()
-                    .find(|block_position| block_position == &position)
-                    .is_some()
+                if !entity.is_spectator()
+                    && entity
+                        .get_collider()
+                        .iter_blocks()
+                        .find(|block_position| block_position == &position)
+                        .is_some()
                 {
                     return true;
                 }
@@ -174,6 +391,19 @@ impl World {
         }
         false
     }
+    pub fn get_entities_in_radius(&self, position: Position, radius: f64) -> Vec<Arc<Entity>> {
+        let mut entities = Vec::new();
+        for chunk in self
+            .get_chunks_with_center_radius(position.to_chunk_pos(), (radius / 16.).ceil() as u32)
+        {
+            for entity in &*chunk.entities.lock() {
+                if entity.get_location().position.distance(&position) <= radius {
+                    entities.push(entity.clone());
+                }
+            }
+        }
+        entities
+    }
     pub fn set_block(
         &self,
         position: BlockPosition,
@@ -191,6 +421,19 @@ impl World {
             data,
         );
     }
+    pub fn set_blocks(&self, blocks: Vec<(BlockPosition, BlockStateRef)>, update_neighbors: bool) {
+        let mut chunks: HashMap<ChunkPosition, Vec<_>> = HashMap::new();
+        for (position, block) in blocks {
+            chunks
+                .entry(position.to_chunk_pos())
+                .or_default()
+                .push((position.chunk_offset(), block));
+        }
+        for (position, blocks) in chunks {
+            self.load_chunk(position)
+                .set_blocks(&blocks, update_neighbors);
+        }
+    }
     pub fn get_block_load(&self, position: BlockPosition) -> BlockData {
         let chunk_offset = position.chunk_offset();
         self.load_chunk(position.to_chunk_pos()).get_block(
@@ -245,11 +488,162 @@ impl World {
         let chunks = self.chunks.lock();
         chunks.get(&position).map(|c| c.clone())
     }
+    pub fn get_terrain_height(&self, x: i32, z: i32) -> i32 {
+        self.world_generator.get_column_height(x, z)
+    }
+    pub fn get_border(&self) -> WorldBorder {
+        *self.border.lock()
+    }
+    pub fn set_border(&self, border: WorldBorder) {
+        *self.border.lock() = border;
+        self.settings
+            .set("border.center_x", border.center_x.to_string());
+        self.settings
+            .set("border.center_z", border.center_z.to_string());
+        self.settings
+            .set("border.radius", border.radius.to_string());
+        for player in self.server.players.lock().iter() {
+            if Arc::ptr_eq(&player.get_entity().get_location().chunk.world, &self.ptr()) {
+                player.send_message(&NetworkMessageS2C::SetWorldBorder(border));
+            }
+        }
+    }
+    pub fn add_text_display(&self, text_display: Arc<TextDisplay>) {
+        self.text_displays
+            .lock()
+            .insert(text_display.id, text_display);
+    }
+    pub fn remove_text_display(&self, id: u32) {
+        self.text_displays.lock().remove(&id);
+    }
+    pub fn get_text_displays_in_chunk(&self, position: ChunkPosition) -> Vec<Arc<TextDisplay>> {
+        self.text_displays
+            .lock()
+            .values()
+            .filter(|text_display| text_display.get_chunk_position() == position)
+            .cloned()
+            .collect()
+    }
+    pub fn get_spawn_point(&self) -> Position {
+        *self.spawn_point.lock()
+    }
+    pub fn set_spawn_point(&self, position: Position) {
+        *self.spawn_point.lock() = position;
+    }
+    pub fn is_inside_border(&self, position: BlockPosition) -> bool {
+        self.border
+            .lock()
+            .contains(&position.to_position().add(0.5, 0.5, 0.5))
+    }
+    pub fn get_gamerule(&self, name: &str) -> Option<GameRuleValue> {
+        self.server.game_rules.get(&self.gamerules, name)
+    }
+    pub fn get_gamerule_bool(&self, name: &str) -> bool {
+        self.server.game_rules.get_bool(&self.gamerules, name)
+    }
+    pub fn get_gamerule_number(&self, name: &str) -> f64 {
+        self.server.game_rules.get_number(&self.gamerules, name)
+    }
+    pub fn get_time(&self) -> u64 {
+        self.time.load(Ordering::Relaxed)
+    }
+    pub fn set_time(&self, time: u64) {
+        self.time.store(time, Ordering::Relaxed);
+        self.sync_sky();
+    }
+    pub fn get_sky(&self) -> SkySettings {
+        SkySettings {
+            time: self.get_time(),
+            day_length: World::DAY_LENGTH,
+            daylight_cycle: self.get_gamerule_bool(GameRules::DAYLIGHT_CYCLE),
+            sun_and_moon: self.settings.get("sky.sun_and_moon", "true") == "true",
+            clouds: self.settings.get("sky.clouds", "true") == "true",
+            cloud_height: self.settings.get_f64("sky.cloud_height", 128.) as f32,
+            cloud_speed: self.settings.get_f64("sky.cloud_speed", 1.) as f32,
+            cloud_cover: self.settings.get_f64("sky.cloud_cover", 0.4) as f32,
+        }
+    }
+    //clients run the clock themselves, this only corrects drift and jumps
+    pub fn sync_sky(&self) {
+        let sky = self.get_sky();
+        for player in self.server.players.lock().iter() {
+            if Arc::ptr_eq(&player.get_entity().get_location().chunk.world, &self.ptr()) {
+                player.send_message(&NetworkMessageS2C::SetSky(sky));
+            }
+        }
+    }
+    pub fn get_simulation_distance(&self) -> i32 {
+        self.settings
+            .get_i64(
+                "simulation_distance",
+                self.server
+                    .settings
+                    .get_i64("server.simulation_distance", 4),
+            )
+            .max(0) as i32
+    }
+    pub fn get_day_time(&self) -> u64 {
+        self.get_time() % World::DAY_LENGTH
+    }
+    pub fn is_night(&self) -> bool {
+        let day_time = self.get_day_time();
+        day_time >= 13000 && day_time < 23000
+    }
+    pub fn spawn_particles(
+        &self,
+        id: &Identifier,
+        position: Position,
+        count: u32,
+        spread: f32,
+        velocity: Vec3,
+    ) {
+        if let Some(chunk) = self.get_chunk(position.to_chunk_pos()) {
+            chunk.announce_to_viewers(&NetworkMessageS2C::SpawnParticles(
+                id.to_string(),
+                position,
+                count,
+                spread,
+                velocity,
+            ));
+        }
+    }
+    pub fn skip_to_morning(&self) {
+        let time = self.get_time();
+        self.set_time(time - (time % World::DAY_LENGTH) + World::DAY_LENGTH);
+    }
     pub fn tick(&self) {
+        self.tick_budget
+            .finish_tick((self.settings.get_f64("tick_budget_ms", 25.) * 1000.) as u64);
+        let daylight_cycle = self.get_gamerule_bool(GameRules::DAYLIGHT_CYCLE);
+        if daylight_cycle {
+            self.time.fetch_add(1, Ordering::Relaxed);
+        }
+        if self.daylight_cycle.swap(daylight_cycle, Ordering::Relaxed) != daylight_cycle
+            || (daylight_cycle && self.get_time() % Self::SKY_SYNC_INTERVAL == 0)
+        {
+            self.sync_sky();
+        }
+        let simulation_distance = self.get_simulation_distance();
+        let player_chunks: Vec<ChunkPosition> = self
+            .server
+            .players
+            .lock()
+            .iter()
+            .map(|player| player.get_entity().get_location())
+            .filter(|location| std::ptr::eq(Arc::as_ptr(&location.chunk.world), self))
+            .map(|location| location.chunk.position)
+            .collect();
         let mut chunks = self.chunks.lock();
         chunks
-            .extract_if(|_, chunk| {
-                let should_unload = chunk.tick();
+            .extract_if(|position, chunk| {
+                let simulated = player_chunks.iter().any(|player_chunk| {
+                    (player_chunk.x - position.x)
+                        .abs()
+                        .max((player_chunk.y - position.y).abs())
+                        .max((player_chunk.z - position.z).abs())
+                        <= simulation_distance
+                });
+                let should_unload = chunk.tick(simulated);
                 if should_unload {
                     chunk.destroy();
                 }
@@ -261,6 +655,19 @@ impl World {
         } else {
             self.unload_timer.inc();
         }
+        drop(chunks);
+        self.generation_queue.dispatch(self, &player_chunks);
+        detector::tick(&self.ptr());
+        signal::tick(self);
+        energy::tick(self);
+        sensor::tick(self);
+    }
+    //block changes are collected during the tick and sent once per chunk at its end
+    pub fn flush_block_updates(&self) {
+        let chunks: Vec<_> = self.block_updates.lock().drain(..).collect();
+        for chunk in chunks {
+            chunk.flush_block_updates();
+        }
     }
     pub fn raycast(
         &self,
@@ -379,6 +786,42 @@ impl World {
         for chunk in self.chunks.lock().drain() {
             chunk.1.destroy();
         }
+        self.save();
+    }
+    fn save_metadata(&self) {
+        WorldMetadata {
+            seed: self.seed,
+            generator: self.generator.clone(),
+            generator_options: self.generator_options.clone(),
+            time: self.get_time(),
+            spawn_point: self.get_spawn_point(),
+            gamerules: self.gamerules.get_prefixed("gamerule."),
+        }
+        .save(&self.get_world_path());
+    }
+    pub fn save(&self) {
+        self.save_metadata();
+        let mut settings_path = self.get_world_path();
+        settings_path.push("settings.txt");
+        if let Err(error) = std::fs::write(&settings_path, self.settings.save_to_string()) {
+            logging::error(
+                LogTarget::Server,
+                format!("could not save {}: {}", settings_path.display(), error),
+            );
+        }
+        let mut user_data_path = self.get_world_path();
+        user_data_path.push("user_data.bin");
+        let result = bitcode::serialize(&*self.user_data.lock())
+            .map_err(|error| error.to_string())
+            .and_then(|data| {
+                std::fs::write(&user_data_path, data).map_err(|error| error.to_string())
+            });
+        if let Err(error) = result {
+            logging::error(
+                LogTarget::Server,
+                format!("could not save {}: {}", user_data_path.display(), error),
+            );
+        }
     }
 }
 pub enum RaycastResult {
@@ -404,10 +847,125 @@ impl ScriptingObject for World {
         env.register_member("user_data", |world: &Arc<World>| {
             Some(UserDataWrapper::World(world.ptr()).into_variant())
         });
+        env.register_member("seed", |world: &Arc<World>| Some(world.get_seed() as i64));
+        env.register_member("time", |world: &Arc<World>| Some(world.get_time() as i64));
+        env.register_member("day_time", |world: &Arc<World>| {
+            Some(world.get_day_time() as i64)
+        });
+        env.register_method("set_time", |world: &Arc<World>, time: &i64| {
+            world.set_time((*time).max(0) as u64);
+            Ok(())
+        });
+        env.register_method("is_night", |world: &Arc<World>| Ok(world.is_night()));
+        env.register_method(
+            "spawn_particles",
+            |world: &Arc<World>,
+             id: &ImmutableString,
+             position: &Position,
+             count: &i64,
+             spread: &f64,
+             velocity: &Position| {
+                world.spawn_particles(
+                    &Identifier::parse(id.as_ref()).map_err(|_| {
+                        ScriptError::runtime("invalid particle id", FilePosition::INVALID)
+                    })?,
+                    *position,
+                    (*count).clamp(0, 1024) as u32,
+                    *spread as f32,
+                    Vec3 {
+                        x: velocity.x as f32,
+                        y: velocity.y as f32,
+                        z: velocity.z as f32,
+                    },
+                );
+                Ok(())
+            },
+        );
+        env.register_method("ignite", |world: &Arc<World>, position: &BlockPosition| {
+            Ok(fire::ignite(world, *position))
+        });
+        env.register_method(
+            "explode",
+            |world: &Arc<World>, position: &Position, power: &f64, fire: &bool| {
+                explosion::explode(world, *position, *power as f32, *fire);
+                Ok(())
+            },
+        );
+        env.register_method(
+            "extinguish",
+            |world: &Arc<World>, position: &BlockPosition| Ok(fire::extinguish(world, *position)),
+        );
+        env.register_method(
+            "get_gamerule",
+            |world: &Arc<World>, name: &ImmutableString| {
+                Ok(Variant::from_option(
+                    world
+                        .get_gamerule(name.as_ref())
+                        .map(|value| value.to_variant()),
+                ))
+            },
+        );
+        env.register_method(
+            "set_gamerule",
+            |world: &Arc<World>, name: &ImmutableString, value: &Variant| {
+                let value = GameRuleValue::from_variant(value).ok_or_else(|| {
+                    ScriptError::runtime(
+                        "gamerule value must be a bool or number",
+                        FilePosition::INVALID,
+                    )
+                })?;
+                world
+                    .server
+                    .game_rules
+                    .set(&world.gamerules, name.as_ref(), value)
+                    .map_err(|error| ScriptError::runtime(error.as_str(), FilePosition::INVALID))
+            },
+        );
+        env.register_member("entity_tick_ms", |world: &Arc<World>| {
+            Some(world.tick_budget.get_last_used().as_secs_f64() * 1000.)
+        });
+        env.register_member("tick_overage_ms", |world: &Arc<World>| {
+            Some(world.tick_budget.get_total_overage().as_secs_f64() * 1000.)
+        });
+        env.register_member("throttled_ticks", |world: &Arc<World>| {
+            Some(world.tick_budget.get_throttled_ticks() as i64)
+        });
+        env.register_method(
+            "set_border",
+            |world: &Arc<World>, center: &Position, radius: &f64| {
+                world.set_border(WorldBorder {
+                    center_x: center.x,
+                    center_z: center.z,
+                    radius: *radius,
+                });
+                Ok(())
+            },
+        );
+        env.register_member("border_center", |world: &Arc<World>| {
+            let border = world.get_border();
+            Some(Position {
+                x: border.center_x,
+                y: 0.,
+                z: border.center_z,
+            })
+        });
+        env.register_member("border_radius", |world: &Arc<World>| {
+            Some(world.get_border().radius)
+        });
+        env.register_member("spawn_point", |world: &Arc<World>| {
+            Some(world.get_spawn_point())
+        });
+        env.register_method(
+            "set_spawn_point",
+            |world: &Arc<World>, position: &Position| {
+                world.set_spawn_point(*position);
+                Ok(())
+            },
+        );
         /*engine.register_fn(
             "place_structure",
             |world: &mut Arc<World>, structure: Arc<Structure>, position: BlockPosition| {
-                world.place_structure(position, &structure, true);
+                world.place_structure(position, &structure, StructureTransform::IDENTITY, true);
             },
         );
         engine.register_fn(
@@ -428,6 +986,55 @@ impl PartialEq for World {
     }
 }
 
+pub struct TickBudget {
+    used: AtomicU64,
+    last_used: AtomicU64,
+    overloaded: AtomicBool,
+    total_overage: AtomicU64,
+    throttled_ticks: AtomicU64,
+}
+impl TickBudget {
+    const REDUCED_TICK_INTERVAL: u64 = 4;
+    pub fn new() -> Self {
+        TickBudget {
+            used: AtomicU64::new(0),
+            last_used: AtomicU64::new(0),
+            overloaded: AtomicBool::new(false),
+            total_overage: AtomicU64::new(0),
+            throttled_ticks: AtomicU64::new(0),
+        }
+    }
+    pub fn record(&self, duration: Duration) {
+        self.used
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+    pub fn finish_tick(&self, budget_micros: u64) {
+        let used = self.used.swap(0, Ordering::Relaxed);
+        self.last_used.store(used, Ordering::Relaxed);
+        let overloaded = used > budget_micros;
+        self.overloaded.store(overloaded, Ordering::Relaxed);
+        if overloaded {
+            self.total_overage
+                .fetch_add(used - budget_micros, Ordering::Relaxed);
+            self.throttled_ticks.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    pub fn should_tick(&self, entity: &Entity, time: u64) -> bool {
+        !self.overloaded.load(Ordering::Relaxed)
+            || !entity.entity_type.low_priority
+            || (time + entity.client_id as u64) % Self::REDUCED_TICK_INTERVAL == 0
+    }
+    pub fn get_last_used(&self) -> Duration {
+        Duration::from_micros(self.last_used.load(Ordering::Relaxed))
+    }
+    pub fn get_total_overage(&self) -> Duration {
+        Duration::from_micros(self.total_overage.load(Ordering::Relaxed))
+    }
+    pub fn get_throttled_ticks(&self) -> u64 {
+        self.throttled_ticks.load(Ordering::Relaxed)
+    }
+}
+
 #[derive(Clone)]
 pub enum BlockData {
     Simple(u32),
@@ -454,12 +1061,7 @@ impl BlockData {
         }
     }
     pub fn is_collidable(&self, block_registry: &BlockRegistry) -> bool {
-        let state = match self {
-            BlockData::Simple(id) => BlockStateRef::from_state_id(*id),
-            BlockData::Data(data) => data.state,
-        };
-        let state = block_registry.state_by_ref(state);
-        state.collidable
+        block_registry.is_collidable(self.get_block_state())
     }
 }
 
@@ -470,13 +1072,18 @@ pub struct Chunk {
     entities: Mutex<Vec<Arc<Entity>>>,
     viewers: Mutex<FxHashSet<ChunkViewer>>,
     loading_stage: AtomicU8,
+    quarantined: AtomicBool,
+    collision_layers: AtomicU16,
     ticking_blocks: Mutex<HashSet<(u8, u8, u8)>>,
     scheduled_updates: Mutex<HashSet<(u8, u8, u8)>>,
+    block_updates: Mutex<FxHashMap<(u8, u8, u8), u32>>,
+    pub sensors: Mutex<ChunkSensors>,
     this: Weak<Chunk>,
 }
 
 impl Chunk {
     const UNLOAD_TIME: u8 = 200;
+    const CHECKSUM_LENGTH: usize = 64;
     pub fn new(position: ChunkPosition, world: Arc<World>) -> Arc<Self> {
         let chunk = Arc::new_cyclic(|this| Chunk {
             position,
@@ -487,99 +1094,146 @@ impl Chunk {
             entities: Mutex::new(Vec::new()),
             viewers: Mutex::new(FxHashSet::default()),
             loading_stage: AtomicU8::new(0),
+            quarantined: AtomicBool::new(false),
+            collision_layers: AtomicU16::new(0),
             ticking_blocks: Mutex::new(HashSet::new()),
             scheduled_updates: Mutex::new(HashSet::new()),
+            block_updates: Mutex::new(FxHashMap::default()),
+            sensors: Mutex::new(ChunkSensors::new()),
             this: this.clone(),
         });
-        let gen_chunk = chunk.clone();
-        world.clone().server.thread_pool.execute(Box::new(move || {
-            {
-                let save_path = gen_chunk.get_chunk_path();
-                *gen_chunk.blocks.lock() = match gen_chunk.load_from_save(save_path) {
-                    Ok((blocks, entities)) => {
-                        if entities.len() > 0 {}
-                        for entity_data in entities {
-                            let entity = Entity::new(
-                                ChunkLocation {
-                                    position: entity_data.position,
-                                    chunk: gen_chunk.clone(),
-                                },
-                                gen_chunk
-                                    .world
-                                    .server
-                                    .entity_registry
-                                    .entity_by_identifier(&entity_data.entity_type)
-                                    .unwrap(),
-                            );
-                            *entity.user_data.lock() = entity_data.user_data;
-                            *entity.velocity.lock() = entity_data.velocity;
-                            entity.rotation_shifting.lock().0 = entity_data.rotation;
-                            entity.inventory.deserialize(
-                                entity_data.inventory,
-                                &gen_chunk.world.server.item_registry,
-                            );
-                        }
-                        blocks
-                    }
-                    Err(()) => {
-                        gen_chunk.ticking_blocks.lock().clear();
-                        gen_chunk.world.world_generator.generate(&gen_chunk)
+        world.generation_queue.push(chunk.clone());
+        chunk
+    }
+    pub fn load(&self) {
+        let mut signal_blocks = Vec::new();
+        let mut energy_blocks = Vec::new();
+        {
+            let save_path = self.get_chunk_path();
+            let save = self
+                .world
+                .server
+                .metrics
+                .time(TickPhase::ChunkIO, || self.load_from_save(save_path));
+            let regenerate = save.is_ok()
+                || self
+                    .world
+                    .server
+                    .settings
+                    .get("server.regenerate_corrupted_chunks", "false")
+                    == "true";
+            *self.blocks.lock() = match save {
+                Ok(Some((blocks, entities))) => {
+                    for entity_data in entities {
+                        self.spawn_saved_entity(entity_data);
                     }
-                };
-                for x in 0..16 {
-                    for y in 0..16 {
-                        for z in 0..16 {
-                            let block_state = gen_chunk.get_block(x, y, z).get_block_state();
-                            let function = world
-                                .server
-                                .block_registry
-                                .state_by_ref(block_state)
-                                .parent
-                                .static_data
-                                .get_function("on_set");
-                            if !function.is_empty() {
-                                function
-                                    .call_function(
-                                        &world.server.script_environment,
-                                        Some(
-                                            BlockLocation {
-                                                world: world.clone(),
-                                                position: BlockPosition {
-                                                    x: (gen_chunk.position.x * 16) + x as i32,
-                                                    y: (gen_chunk.position.y * 16) + y as i32,
-                                                    z: (gen_chunk.position.z * 16) + z as i32,
-                                                },
-                                            }
-                                            .into_variant(),
-                                        ),
-                                        vec![Variant::NULL()],
-                                    )
-                                    .unwrap();
-                            }
+                    blocks
+                }
+                //keeping the chunk empty and unsaved leaves the quarantined files for an operator to recover
+                Err(()) if !regenerate => {
+                    logging::error(
+                        LogTarget::ChunkIO,
+                        format!(
+                            "chunk {},{},{} could not be restored and is left empty",
+                            self.position.x, self.position.y, self.position.z
+                        ),
+                    );
+                    self.quarantined.store(true, Ordering::SeqCst);
+                    array_init(|_| array_init(|_| array_init(|_| BlockData::Simple(0))))
+                }
+                _ => {
+                    self.ticking_blocks.lock().clear();
+                    let start = Instant::now();
+                    let blocks = self.world.world_generator.generate(&self.ptr());
+                    self.world
+                        .generation_queue
+                        .record_generation(start.elapsed());
+                    blocks
+                }
+            };
+            self.update_collision_layers(0..16);
+            for x in 0..16 {
+                for y in 0..16 {
+                    for z in 0..16 {
+                        let block_state = self.get_block(x, y, z).get_block_state();
+                        let block = &self
+                            .world
+                            .server
+                            .block_registry
+                            .state_by_ref(block_state)
+                            .parent;
+                        let position = BlockPosition {
+                            x: (self.position.x * 16) + x as i32,
+                            y: (self.position.y * 16) + y as i32,
+                            z: (self.position.z * 16) + z as i32,
+                        };
+                        if block.signal.is_some() {
+                            signal_blocks.push(position);
+                        }
+                        if block.energy.is_some() {
+                            energy_blocks.push(position);
+                        }
+                        let function = block.static_data.get_function("on_set");
+                        if !function.is_empty() {
+                            function
+                                .call_function(
+                                    &self.world.server.script_environment,
+                                    Some(
+                                        BlockLocation {
+                                            world: self.world.clone(),
+                                            position,
+                                        }
+                                        .into_variant(),
+                                    ),
+                                    vec![Variant::NULL()],
+                                )
+                                .unwrap();
                         }
                     }
                 }
             }
+        }
 
-            gen_chunk
-                .loading_stage
-                .store(1, std::sync::atomic::Ordering::SeqCst);
-            if let Some(placement_list) = {
-                gen_chunk
-                    .world
-                    .unloaded_structure_placements
-                    .lock()
-                    .remove(&position)
-            } {
-                for (position, structure) in placement_list {
-                    gen_chunk.place_structure(position, structure);
-                }
+        self.loading_stage
+            .store(1, std::sync::atomic::Ordering::SeqCst);
+        if let Some(placement_list) = {
+            self.world
+                .unloaded_structure_placements
+                .lock()
+                .remove(&self.position)
+        } {
+            for (position, structure, transform) in placement_list {
+                self.place_structure(position, structure, transform);
             }
-            gen_chunk
-                .loading_stage
-                .store(2, std::sync::atomic::Ordering::SeqCst);
-        }));
-        chunk
+        }
+        self.loading_stage
+            .store(2, std::sync::atomic::Ordering::SeqCst);
+        self.world.signal_networks.on_chunk_load(signal_blocks);
+        self.world.energy_networks.on_chunk_load(energy_blocks);
+    }
+    pub fn spawn_saved_entity(&self, entity_data: EntitySaveData) -> Option<Arc<Entity>> {
+        let entity_type = self
+            .world
+            .server
+            .entity_registry
+            .entity_by_identifier(&entity_data.entity_type)?;
+        let entity = Entity::new(
+            ChunkLocation {
+                position: entity_data.position,
+                chunk: self.ptr(),
+            },
+            entity_type,
+        );
+        *entity.user_data.lock() = entity_data.user_data;
+        *entity.velocity.lock() = entity_data.velocity;
+        entity
+            .experience
+            .store(entity_data.experience, Ordering::Relaxed);
+        entity.rotation_shifting.lock().0 = entity_data.rotation;
+        entity
+            .inventory
+            .deserialize(entity_data.inventory, &self.world.server.item_registry);
+        Some(entity)
     }
     pub fn schedule_update(&self, block: (u8, u8, u8)) {
         self.scheduled_updates.lock().insert(block);
@@ -591,20 +1245,99 @@ impl Chunk {
             self.ticking_blocks.lock().remove(&block);
         }
     }
+    //unreadable files are moved to quarantine and the next candidate is tried, Err means nothing could be restored
     pub fn load_from_save(
         &self,
         save_path: PathBuf,
-    ) -> Result<([[[BlockData; 16]; 16]; 16], Vec<EntitySaveData>), ()> {
-        let mut chunk_save_data = bitcode::deserialize::<ChunkSaveData>(
-            std::fs::read(save_path).map_err(|_| ())?.as_slice(),
-        )
-        .map_err(|_| ())?;
+    ) -> Result<Option<([[[BlockData; 16]; 16]; 16], Vec<EntitySaveData>)>, ()> {
+        let mut corrupted = false;
+        for path in [
+            save_path.clone(),
+            save_path.with_extension("tmp"),
+            save_path.with_extension("bak"),
+        ] {
+            if !path.exists() {
+                continue;
+            }
+            match Chunk::read_checked(&path).and_then(|data| self.read_save_data(data)) {
+                Some(save) => {
+                    if corrupted {
+                        logging::warn(
+                            LogTarget::ChunkIO,
+                            format!(
+                                "restored chunk {},{},{} from {}",
+                                self.position.x,
+                                self.position.y,
+                                self.position.z,
+                                path.display()
+                            ),
+                        );
+                    }
+                    return Ok(Some(save));
+                }
+                None => {
+                    corrupted = true;
+                    logging::error(
+                        LogTarget::ChunkIO,
+                        format!(
+                            "chunk {},{},{} save {} is corrupted, moving it to quarantine",
+                            self.position.x,
+                            self.position.y,
+                            self.position.z,
+                            path.display()
+                        ),
+                    );
+                    self.quarantine(&path);
+                }
+            }
+        }
+        if corrupted {
+            Err(())
+        } else {
+            Ok(None)
+        }
+    }
+    fn quarantine(&self, path: &PathBuf) {
+        let mut quarantine_path = self.world.get_world_path();
+        quarantine_path.push("corrupted");
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        quarantine_path.push(format!(
+            "{}.{}",
+            path.file_name().unwrap().to_string_lossy(),
+            timestamp
+        ));
+        let result = std::fs::create_dir_all(quarantine_path.parent().unwrap())
+            .and_then(|_| std::fs::rename(path, &quarantine_path));
+        if let Err(error) = result {
+            logging::error(
+                LogTarget::ChunkIO,
+                format!("could not quarantine {}: {}", path.display(), error),
+            );
+        }
+    }
+    fn read_save_data(
+        &self,
+        data: Vec<u8>,
+    ) -> Option<([[[BlockData; 16]; 16]; 16], Vec<EntitySaveData>)> {
+        let mut chunk_save_data = bitcode::deserialize::<ChunkSaveData>(data.as_slice()).ok()?;
         let block_registry = &self.world.server.block_registry;
         let block_palette: Vec<_> = chunk_save_data
             .palette
             .iter()
             .map(|id| (block_registry.block_by_identifier(&id.0), id.1))
             .collect();
+        if chunk_save_data
+            .blocks
+            .iter()
+            .flatten()
+            .flatten()
+            .any(|block_id| *block_id as usize >= block_palette.len())
+        {
+            return None;
+        }
         let blocks = array_init(|x| {
             array_init(|y| {
                 array_init(|z| {
@@ -633,35 +1366,167 @@ impl Chunk {
                 })
             })
         });
-        Ok((blocks, chunk_save_data.entities))
+        Some((blocks, chunk_save_data.entities))
     }
     pub fn ptr(&self) -> Arc<Chunk> {
         self.this.upgrade().unwrap()
     }
-    pub fn place_structure(&self, position: BlockPosition, structure: Arc<Structure>) {
+    pub fn place_structure(
+        &self,
+        position: BlockPosition,
+        structure: Arc<Structure>,
+        transform: StructureTransform,
+    ) {
+        let mut blocks = Vec::new();
         structure.place(
             |block_position, block| {
                 if block_position.to_chunk_pos() == self.position {
-                    let offset = block_position.chunk_offset();
-                    self.set_block(offset.0, offset.1, offset.2, block, false, Variant::NULL());
+                    blocks.push((block_position.chunk_offset(), block));
                 }
             },
             position,
+            transform,
         );
-    }
-    pub fn set_block(
-        &self,
-        offset_x: u8,
-        offset_y: u8,
-        offset_z: u8,
-        block: BlockStateRef,
-        update_neighbors: bool,
-        data: Variant,
-    ) {
-        match self.blocks.lock()[offset_x as usize][offset_y as usize][offset_z as usize] {
-            BlockData::Simple(id) => {
-                if block.get_id() == id {
-                    return;
+        self.set_blocks(&blocks, false);
+        structure.place_data(&self.world, position, transform, Some(self.position));
+    }
+    //writes every block before running any scripts and sends viewers a single update, scripts see the finished batch
+    pub fn set_blocks(&self, blocks: &[((u8, u8, u8), BlockStateRef)], update_neighbors: bool) {
+        let position_of = |offset: (u8, u8, u8)| BlockPosition {
+            x: self.position.x * 16 + offset.0 as i32,
+            y: self.position.y * 16 + offset.1 as i32,
+            z: self.position.z * 16 + offset.2 as i32,
+        };
+        let new_blocks: Vec<_> = {
+            let current = self.blocks.lock();
+            blocks
+                .iter()
+                .filter(|(offset, block)| {
+                    !matches!(
+                        current[offset.0 as usize][offset.1 as usize][offset.2 as usize],
+                        BlockData::Simple(id) if id == block.get_id()
+                    )
+                })
+                .cloned()
+                .collect()
+        };
+        if new_blocks.is_empty() {
+            return;
+        }
+        let new_blocks: Vec<_> = new_blocks
+            .into_iter()
+            .map(|(offset, block)| {
+                (
+                    offset,
+                    block,
+                    block.create_block_data(self, position_of(offset)),
+                )
+            })
+            .collect();
+        let previous_blocks: Vec<_> = {
+            let mut chunk_blocks = self.blocks.lock();
+            new_blocks
+                .iter()
+                .map(|(offset, _, block_data)| {
+                    std::mem::replace(
+                        &mut chunk_blocks[offset.0 as usize][offset.1 as usize][offset.2 as usize],
+                        block_data.clone(),
+                    )
+                })
+                .collect()
+        };
+        let min_y = new_blocks
+            .iter()
+            .map(|(offset, ..)| offset.1)
+            .min()
+            .unwrap();
+        let max_y = new_blocks
+            .iter()
+            .map(|(offset, ..)| offset.1)
+            .max()
+            .unwrap();
+        self.update_collision_layers(min_y..max_y + 1);
+        {
+            let mut ticking_blocks = self.ticking_blocks.lock();
+            for (offset, ..) in &new_blocks {
+                ticking_blocks.remove(offset);
+            }
+        }
+        if self.loading_stage.load(Ordering::SeqCst) >= 2 {
+            self.block_updates.lock().extend(
+                new_blocks
+                    .iter()
+                    .map(|(offset, block, _)| (*offset, block.get_client_id())),
+            );
+            self.flush_block_updates();
+        }
+        let block_registry = &self.world.server.block_registry;
+        let script_environment = &self.world.server.script_environment;
+        for ((offset, block, block_data), previous_block) in new_blocks.iter().zip(previous_blocks)
+        {
+            let block_location = BlockLocation {
+                world: self.world.clone(),
+                position: position_of(*offset),
+            };
+            if let BlockData::Data(data) = &previous_block {
+                data.on_destroy();
+            }
+            block_registry
+                .state_by_ref(previous_block.get_block_state())
+                .parent
+                .static_data
+                .get_function("on_destroy")
+                .call_function(
+                    script_environment,
+                    Some(block_location.clone().into_variant()),
+                    vec![Variant::NULL()],
+                )
+                .unwrap();
+            block_registry
+                .state_by_ref(*block)
+                .parent
+                .static_data
+                .get_function("on_set")
+                .call_function(
+                    script_environment,
+                    Some(block_location.into_variant()),
+                    vec![Variant::NULL()],
+                )
+                .unwrap();
+            if let BlockData::Data(data) = block_data {
+                data.update_to_clients();
+            }
+        }
+        if update_neighbors {
+            for (offset, ..) in &new_blocks {
+                self.schedule_update(*offset);
+                let block_position = position_of(*offset);
+                for neighbor_face in Face::all() {
+                    let neighbor_position = block_position.offset_by_face(*neighbor_face);
+                    if neighbor_position.to_chunk_pos() == self.position {
+                        self.schedule_update(neighbor_position.chunk_offset());
+                    } else if let Some(chunk) =
+                        self.world.get_chunk(neighbor_position.to_chunk_pos())
+                    {
+                        chunk.schedule_update(neighbor_position.chunk_offset());
+                    }
+                }
+            }
+        }
+    }
+    pub fn set_block(
+        &self,
+        offset_x: u8,
+        offset_y: u8,
+        offset_z: u8,
+        block: BlockStateRef,
+        update_neighbors: bool,
+        data: Variant,
+    ) {
+        match self.blocks.lock()[offset_x as usize][offset_y as usize][offset_z as usize] {
+            BlockData::Simple(id) => {
+                if block.get_id() == id {
+                    return;
                 }
             }
             BlockData::Data(_) => {}
@@ -700,19 +1565,26 @@ impl Chunk {
             .unwrap();
         let new_block = &self.world.server.block_registry.state_by_ref(block).parent;
         let block = block.create_block_data(&self.this.upgrade().unwrap(), block_position);
+        let offset = (offset_x, offset_y, offset_z);
         if self.loading_stage.load(std::sync::atomic::Ordering::SeqCst) >= 2 {
-            self.announce_to_viewers(&NetworkMessageS2C::SetBlock(
-                block_position,
-                block.get_client_id(),
-            ));
+            let mut block_updates = self.block_updates.lock();
+            if block_updates.is_empty() {
+                self.world.block_updates.lock().push(self.ptr());
+            }
+            block_updates.insert(offset, block.get_client_id());
+            drop(block_updates);
+            //blocks with data send their models right away, so the block has to be on the client first
+            if let BlockData::Data(_) = &block {
+                self.flush_block_updates();
+            }
         }
-        let offset = (offset_x, offset_y, offset_z);
         self.ticking_blocks.lock().remove(&offset);
         let new_block_data = match &block {
             BlockData::Simple(_) => None,
             BlockData::Data(data) => Some(data.clone()),
         };
         self.blocks.lock()[offset_x as usize][offset_y as usize][offset_z as usize] = block;
+        self.update_collision_layers(offset_y..offset_y + 1);
         new_block
             .static_data
             .get_function("on_set")
@@ -735,12 +1607,112 @@ impl Chunk {
             }
         }
     }
+    pub fn flush_block_updates(&self) {
+        let block_updates: Vec<_> = self.block_updates.lock().drain().collect();
+        match block_updates.as_slice() {
+            [] => {}
+            [((x, y, z), id)] => {
+                self.announce_to_viewers(&NetworkMessageS2C::SetBlock(
+                    BlockPosition {
+                        x: self.position.x * 16 + *x as i32,
+                        y: self.position.y * 16 + *y as i32,
+                        z: self.position.z * 16 + *z as i32,
+                    },
+                    *id,
+                ));
+            }
+            _ => {
+                self.announce_to_viewers(&NetworkMessageS2C::SetBlocks(
+                    self.position,
+                    block_updates,
+                ));
+            }
+        }
+    }
     pub fn get_block(&self, offset_x: u8, offset_y: u8, offset_z: u8) -> BlockData {
         self.blocks.lock()[offset_x as usize][offset_y as usize][offset_z as usize].clone()
     }
+    fn update_collision_layers(&self, layers: Range<u8>) {
+        let block_registry = &self.world.server.block_registry;
+        let blocks = self.blocks.lock();
+        for y in layers {
+            let collidable = (0..16)
+                .any(|x| (0..16).any(|z| blocks[x][y as usize][z].is_collidable(block_registry)));
+            if collidable {
+                self.collision_layers.fetch_or(1 << y, Ordering::Relaxed);
+            } else {
+                self.collision_layers
+                    .fetch_and(!(1 << y), Ordering::Relaxed);
+            }
+        }
+    }
+    pub fn is_collidable_at(&self, offset_x: u8, offset_y: u8, offset_z: u8) -> bool {
+        if self.collision_layers.load(Ordering::Relaxed) & (1 << offset_y) == 0 {
+            return false;
+        }
+        self.blocks.lock()[offset_x as usize][offset_y as usize][offset_z as usize]
+            .is_collidable(&self.world.server.block_registry)
+    }
     fn add_entity(&self, entity: Arc<Entity>) {
         self.entities.lock().push(entity);
     }
+    //the palette of client ids and the serialized palette index of every block
+    fn serialize_blocks(&self) -> (Vec<u32>, Vec<u8>) {
+        let mut palette = Vec::new();
+        let mut block_data = [[[0; 16]; 16]; 16];
+        {
+            let blocks = self.blocks.lock();
+            for x in 0..16 {
+                for y in 0..16 {
+                    for z in 0..16 {
+                        let block_id = blocks[x][y][z].get_client_id();
+                        let palette_entry =
+                            match palette.iter().position(|block| *block == block_id) {
+                                Some(entry) => entry,
+                                None => {
+                                    palette.push(block_id);
+                                    palette.len() - 1
+                                }
+                            };
+                        block_data[x][y][z] = palette_entry as u16;
+                    }
+                }
+            }
+        }
+        (palette, bitcode::serialize(&block_data).unwrap())
+    }
+    fn create_load_message(
+        &self,
+        palette: Vec<u32>,
+        block_data: &[u8],
+        encoder: &ChunkEncoder,
+    ) -> NetworkMessageS2C {
+        NetworkMessageS2C::LoadChunk(
+            self.position,
+            palette,
+            encoder.encode(block_data),
+            self.world
+                .world_generator
+                .get_biomes(self.position.x, self.position.z),
+            self.world
+                .world_generator
+                .get_climate(self.position.x, self.position.z),
+        )
+    }
+    fn get_block_data(&self) -> Vec<Arc<WorldBlock>> {
+        let blocks = self.blocks.lock();
+        let mut block_data = Vec::new();
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    if let BlockData::Data(block) = &blocks[x][y][z] {
+                        block_data.push(block.clone());
+                    }
+                }
+            }
+        }
+        block_data
+    }
     fn add_viewer(&self, viewer: Arc<PlayerData>) {
         self.viewers.lock().insert(ChunkViewer {
             player: viewer.clone(),
@@ -752,6 +1724,9 @@ impl Chunk {
             }
             viewer.send_messages(&entity.create_add_messages(entity.get_location().position));
         }
+        for text_display in self.world.get_text_displays_in_chunk(self.position) {
+            text_display.sync_to(&viewer);
+        }
     }
     fn remove_viewer(&self, viewer: &PlayerData, unload_entities: bool) {
         viewer.chunk_loading_manager.unload(self.ptr());
@@ -762,6 +1737,9 @@ impl Chunk {
                 }
                 viewer.send_message(&NetworkMessageS2C::DeleteEntity(entity.client_id));
             }
+            for text_display in self.world.get_text_displays_in_chunk(self.position) {
+                viewer.send_message(&NetworkMessageS2C::RemoveTextDisplay(text_display.id));
+            }
         }
         self.viewers.lock().remove(&ChunkViewer {
             player: viewer.ptr(),
@@ -779,8 +1757,9 @@ impl Chunk {
             viewer.player.send_message(message);
         }
     }
-    pub fn tick(&self) -> bool {
+    pub fn tick(&self, simulated: bool) -> bool {
         let mut entities = self.entities.lock();
+        let mut removed_entities = Vec::new();
         entities
             .extract_if(|entity| {
                 let new_location = entity.get_location();
@@ -803,13 +1782,26 @@ impl Chunk {
                             .player
                             .send_message(&NetworkMessageS2C::DeleteEntity(entity.client_id));
                     }
-                    entity.post_remove();
+                    removed_entities.push(entity.clone());
                 }
                 removed || not_same_chunk
             })
             .count();
-        let entities: Vec<_> = entities.iter().map(|e| e.clone()).collect();
-        let blocks: Vec<_> = {
+        let entities: Vec<_> = {
+            let entities = entities;
+            entities
+                .iter()
+                .filter(|entity| simulated || entity.get_player().is_some())
+                .map(|e| e.clone())
+                .collect()
+        };
+        //leave callbacks can spawn entities in this chunk, so they run after it is unlocked
+        for entity in removed_entities {
+            entity.post_remove();
+        }
+        let blocks: Vec<_> = if !simulated {
+            Vec::new()
+        } else {
             let blocks = self.blocks.lock();
             self.ticking_blocks
                 .lock()
@@ -837,46 +1829,164 @@ impl Chunk {
                 .collect()
         };
         let block_updates: Vec<_> = { self.scheduled_updates.lock().drain().collect() };
-        if entities.len() > 0 || blocks.len() > 0 || block_updates.len() > 0 {
+        let random_ticks: Vec<_> = if !simulated {
+            Vec::new()
+        } else {
+            let mut rng = thread_rng();
+            let blocks = self.blocks.lock();
+            let random_tick_speed = self
+                .world
+                .get_gamerule_number(GameRules::RANDOM_TICK_SPEED)
+                .max(0.) as u32;
+            (0..random_tick_speed)
+                .filter_map(|_| {
+                    let offset: (u8, u8, u8) = (
+                        rng.gen_range(0..16),
+                        rng.gen_range(0..16),
+                        rng.gen_range(0..16),
+                    );
+                    let block = &self
+                        .world
+                        .server
+                        .block_registry
+                        .state_by_ref(
+                            blocks[offset.0 as usize][offset.1 as usize][offset.2 as usize]
+                                .get_block_state(),
+                        )
+                        .parent;
+                    if block.fire || block.static_data.get("on_random_tick").is_some() {
+                        Some((
+                            block.clone(),
+                            BlockPosition {
+                                x: self.position.x * 16 + offset.0 as i32,
+                                y: self.position.y * 16 + offset.1 as i32,
+                                z: self.position.z * 16 + offset.2 as i32,
+                            },
+                        ))
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        };
+        if entities.len() > 0
+            || blocks.len() > 0
+            || block_updates.len() > 0
+            || random_ticks.len() > 0
+        {
             let chunk = self.ptr();
             self.world.server.thread_pool.execute(Box::new(move || {
-                for entity in entities {
-                    entity.tick();
-                }
-                for block in blocks {
-                    block
-                        .0
-                        .static_data
-                        .get_function("on_tick")
-                        .call_function(
-                            &chunk.world.server.script_environment,
-                            Some(block.1.into_variant()),
-                            vec![],
-                        )
-                        .unwrap();
-                }
-                for block_update in block_updates {
-                    let state = chunk.world.server.block_registry.state_by_ref(
-                        chunk
-                            .get_block(block_update.0, block_update.1, block_update.2)
-                            .get_block_state(),
-                    );
-                    state.on_block_update(ChunkBlockLocation {
-                        chunk: chunk.clone(),
-                        position: BlockPosition {
-                            x: chunk.position.x * 16 + block_update.0 as i32,
-                            y: chunk.position.y * 16 + block_update.1 as i32,
-                            z: chunk.position.z * 16 + block_update.2 as i32,
-                        },
-                    })
-                }
+                let metrics = &chunk.world.server.metrics;
+                let position = chunk.position;
+                metrics.trace(
+                    "chunk",
+                    || format!("chunk {} {} {}", position.x, position.y, position.z),
+                    None,
+                    || {
+                        let time = chunk.world.get_time();
+                        for entity in entities {
+                            if !chunk.world.tick_budget.should_tick(&entity, time) {
+                                continue;
+                            }
+                            let start = Instant::now();
+                            metrics.trace(
+                                "entity",
+                                || entity.entity_type.id.to_string(),
+                                Some(entity.entity_type.id.get_namespace()),
+                                || entity.tick(),
+                            );
+                            chunk.world.tick_budget.record(start.elapsed());
+                        }
+                        for block in blocks {
+                            if block.0.transfer.is_some() || block.0.energy.is_some() {
+                                if let Some(BlockData::Data(world_block)) =
+                                    chunk.world.get_block(&block.1.position)
+                                {
+                                    world_block.tick_transfer();
+                                    energy::push_energy(&chunk.world, &world_block);
+                                }
+                            }
+                            metrics.trace(
+                                "script",
+                                || format!("{} on_tick", block.0.id),
+                                Some(block.0.id.get_namespace()),
+                                || {
+                                    block
+                                        .0
+                                        .static_data
+                                        .get_function("on_tick")
+                                        .call_function(
+                                            &chunk.world.server.script_environment,
+                                            Some(block.1.into_variant()),
+                                            vec![],
+                                        )
+                                        .unwrap()
+                                },
+                            );
+                        }
+                        for block_update in block_updates {
+                            let state = chunk.world.server.block_registry.state_by_ref(
+                                chunk
+                                    .get_block(block_update.0, block_update.1, block_update.2)
+                                    .get_block_state(),
+                            );
+                            let position = BlockPosition {
+                                x: chunk.position.x * 16 + block_update.0 as i32,
+                                y: chunk.position.y * 16 + block_update.1 as i32,
+                                z: chunk.position.z * 16 + block_update.2 as i32,
+                            };
+                            state.on_block_update(ChunkBlockLocation {
+                                chunk: chunk.clone(),
+                                position,
+                            });
+                            signal::on_block_update(&chunk.world, position);
+                            energy::on_block_update(&chunk.world, position);
+                            sensor::on_chunk_change(&chunk, position);
+                        }
+                        for (block, position) in random_ticks {
+                            if block.fire {
+                                fire::random_tick(&chunk.world, position);
+                            } else {
+                                metrics.trace(
+                                    "script",
+                                    || format!("{} on_random_tick", block.id),
+                                    Some(block.id.get_namespace()),
+                                    || {
+                                        block
+                                            .static_data
+                                            .get_function("on_random_tick")
+                                            .call_function(
+                                                &chunk.world.server.script_environment,
+                                                Some(
+                                                    BlockLocation {
+                                                        world: chunk.world.clone(),
+                                                        position,
+                                                    }
+                                                    .into_variant(),
+                                                ),
+                                                vec![],
+                                            )
+                                            .unwrap()
+                                    },
+                                );
+                            }
+                        }
+                    },
+                );
             }));
         }
         self.viewers.lock().len() == 0
     }
     pub fn destroy(&self) {
         let chunk = self.this.upgrade().unwrap();
-        if !self.world.temporary {
+        for entity in self.entities.lock().iter() {
+            entity.unload_detectors();
+        }
+        //chunks that did not finish loading would overwrite their save with empty blocks
+        if !self.world.temporary
+            && self.loading_stage.load(Ordering::SeqCst) >= 2
+            && !self.quarantined.load(Ordering::SeqCst)
+        {
             self.world.server.thread_pool.execute(Box::new(move || {
                 let mut blocks_save = [[[0u16; 16]; 16]; 16];
                 let mut block_map = FxHashMap::default();
@@ -912,14 +2022,7 @@ impl Chunk {
                     {
                         continue;
                     }
-                    entities.push(EntitySaveData {
-                        entity_type: entity.entity_type.id.clone(),
-                        velocity: entity.velocity.lock().clone(),
-                        rotation: entity.get_rotation(),
-                        position,
-                        inventory: entity.inventory.serialize(),
-                        user_data: entity.user_data.lock().clone(),
-                    });
+                    entities.push(entity.to_save_data(position));
                 }
                 let chunk_save_data = ChunkSaveData {
                     blocks: blocks_save,
@@ -931,16 +2034,54 @@ impl Chunk {
                     block_data,
                     entities,
                 };
-                std::fs::write(
-                    chunk.get_chunk_path(),
-                    bitcode::serialize(&chunk_save_data).unwrap(),
-                )
-                .unwrap();
+                chunk.world.server.metrics.time(TickPhase::ChunkIO, || {
+                    Chunk::write_checked(
+                        &chunk.get_chunk_path(),
+                        bitcode::serialize(&chunk_save_data).unwrap(),
+                    )
+                });
                 chunk.entities.lock().clear();
             }));
         }
         self.viewers.lock().clear();
     }
+    //chunks saved before the footer existed don't end in a hex digest and are returned whole
+    pub fn read_checked(path: &PathBuf) -> Option<Vec<u8>> {
+        let mut data = std::fs::read(path).ok()?;
+        if data.len() < Chunk::CHECKSUM_LENGTH
+            || !data[data.len() - Chunk::CHECKSUM_LENGTH..]
+                .iter()
+                .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+        {
+            return Some(data);
+        }
+        let checksum = data.split_off(data.len() - Chunk::CHECKSUM_LENGTH);
+        if sha256::digest(data.as_slice()).as_bytes() != checksum.as_slice() {
+            return None;
+        }
+        Some(data)
+    }
+    pub fn with_checksum(mut data: Vec<u8>) -> Vec<u8> {
+        let checksum = sha256::digest(data.as_slice());
+        data.extend_from_slice(checksum.as_bytes());
+        data
+    }
+    fn write_checked(path: &PathBuf, data: Vec<u8>) {
+        let data = Chunk::with_checksum(data);
+        let temp_path = path.with_extension("tmp");
+        let result = std::fs::write(&temp_path, data).and_then(|_| {
+            if path.exists() {
+                std::fs::rename(path, path.with_extension("bak"))?;
+            }
+            std::fs::rename(&temp_path, path)
+        });
+        if let Err(error) = result {
+            logging::error(
+                LogTarget::ChunkIO,
+                format!("could not save chunk {}: {}", path.display(), error),
+            );
+        }
+    }
     pub fn get_chunk_path(&self) -> PathBuf {
         let mut path = self.world.get_world_path();
         path.push(format!(
@@ -968,11 +2109,19 @@ pub struct ChunkSaveData {
     block_data: HashMap<(u8, u8, u8), BlockSaveData>,
     entities: Vec<EntitySaveData>,
 }
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct BlockSaveData {
     inventory: InventorySaveData,
+    //structure files may predate block user data
+    #[serde(default = "UserData::new")]
+    user_data: UserData,
+    //only structure files can leave these out, older chunks are migrated
+    #[serde(default)]
+    fluids: Vec<Option<(Identifier, u32)>>,
+    #[serde(default)]
+    energy: u32,
 }
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct EntitySaveData {
     position: Position,
     rotation: Direction,
@@ -980,6 +2129,108 @@ pub struct EntitySaveData {
     inventory: InventorySaveData,
     velocity: (f64, f64, f64),
     user_data: UserData,
+    experience: u32,
+}
+#[derive(Serialize, Deserialize)]
+pub struct PlayerSaveData {
+    pub world: Identifier,
+    pub position: Position,
+    pub rotation: Direction,
+    inventory: InventorySaveData,
+    user_data: UserData,
+    player_user_data: UserData,
+    experience: u32,
+    spawn_point: Option<(Identifier, Position)>,
+}
+
+//layouts from before save versioning, user data was never written and saved as unit
+#[derive(Deserialize)]
+struct LegacyChunkSaveData {
+    palette: Vec<(Identifier, u32)>,
+    blocks: [[[u16; 16]; 16]; 16],
+    block_data: HashMap<(u8, u8, u8), LegacyBlockSaveData>,
+    entities: Vec<LegacyEntitySaveData>,
+}
+#[derive(Deserialize)]
+struct LegacyBlockSaveData {
+    inventory: LegacyInventorySaveData,
+}
+#[derive(Deserialize)]
+struct LegacyEntitySaveData {
+    position: Position,
+    rotation: Direction,
+    entity_type: Identifier,
+    inventory: LegacyInventorySaveData,
+    velocity: (f64, f64, f64),
+    #[allow(dead_code)]
+    user_data: (),
+}
+#[derive(Deserialize)]
+struct LegacyPlayerSaveData {
+    world: Identifier,
+    position: Position,
+    rotation: Direction,
+    inventory: InventorySaveData,
+    #[allow(dead_code)]
+    user_data: (),
+    experience: u32,
+    spawn_point: Option<(Identifier, Position)>,
+}
+impl ChunkSaveData {
+    pub fn upgrade_legacy(data: Vec<u8>) -> Result<Vec<u8>, String> {
+        let legacy = bitcode::deserialize::<LegacyChunkSaveData>(data.as_slice())
+            .map_err(|error| error.to_string())?;
+        let chunk = ChunkSaveData {
+            palette: legacy.palette,
+            blocks: legacy.blocks,
+            block_data: legacy
+                .block_data
+                .into_iter()
+                .map(|(position, block)| {
+                    (
+                        position,
+                        BlockSaveData {
+                            inventory: block.inventory.into(),
+                            user_data: UserData::new(),
+                            fluids: Vec::new(),
+                            energy: 0,
+                        },
+                    )
+                })
+                .collect(),
+            entities: legacy
+                .entities
+                .into_iter()
+                .map(|entity| EntitySaveData {
+                    position: entity.position,
+                    rotation: entity.rotation,
+                    entity_type: entity.entity_type,
+                    inventory: entity.inventory.into(),
+                    velocity: entity.velocity,
+                    user_data: UserData::new(),
+                    experience: 0,
+                })
+                .collect(),
+        };
+        bitcode::serialize(&chunk).map_err(|error| error.to_string())
+    }
+}
+impl PlayerSaveData {
+    pub fn upgrade_legacy(data: Vec<u8>) -> Result<Vec<u8>, String> {
+        let legacy = bitcode::deserialize::<LegacyPlayerSaveData>(data.as_slice())
+            .map_err(|error| error.to_string())?;
+        bitcode::serialize(&PlayerSaveData {
+            world: legacy.world,
+            position: legacy.position,
+            rotation: legacy.rotation,
+            inventory: legacy.inventory,
+            user_data: UserData::new(),
+            player_user_data: UserData::new(),
+            experience: legacy.experience,
+            spawn_point: legacy.spawn_point,
+        })
+        .map_err(|error| error.to_string())
+    }
 }
 
 struct ChunkViewer {
@@ -1008,38 +2259,119 @@ impl UserData {
     }
 }
 
+//values that can't be saved, like functions or game objects, are left out
 impl Serialize for UserData {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_unit()
+        let saved: HashMap<&Identifier, SavedVariant> = self
+            .0
+            .iter()
+            .filter_map(|(id, value)| SavedVariant::save(value).map(|value| (id, value)))
+            .collect();
+        Some(saved).serialize(serializer)
     }
 }
+//structure files from before user data was saved contain null
 impl<'de> Deserialize<'de> for UserData {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_unit(UserDataVisitor)?;
-        Ok(UserData::new())
+        let saved = Option::<HashMap<Identifier, SavedVariant>>::deserialize(deserializer)?;
+        Ok(UserData(
+            saved
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(id, value)| (id, value.load()))
+                .collect(),
+        ))
     }
 }
-struct UserDataVisitor;
-impl<'de> serde::de::Visitor<'de> for UserDataVisitor {
-    type Value = ();
-    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
-        formatter.write_str("unit")
+#[derive(Clone, Serialize, Deserialize)]
+enum SavedVariant {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Identifier(Identifier),
+    Array(Vec<SavedVariant>),
+    SharedArray(Vec<SavedVariant>),
+    Map(HashMap<String, SavedVariant>),
+    SharedMap(HashMap<String, SavedVariant>),
+}
+impl SavedVariant {
+    fn save(variant: &Variant) -> Option<SavedVariant> {
+        //unsaveable values inside arrays become null so the other indices stay the same
+        let array = |values: &Vec<Variant>| {
+            values
+                .iter()
+                .map(|value| SavedVariant::save(value).unwrap_or(SavedVariant::Null))
+                .collect()
+        };
+        let map = |values: &HashMap<ImmutableString, Variant>| {
+            values
+                .iter()
+                .filter_map(|(key, value)| {
+                    SavedVariant::save(value).map(|value| (key.to_string(), value))
+                })
+                .collect()
+        };
+        Some(if <()>::from_variant(variant).is_some() {
+            SavedVariant::Null
+        } else if let Some(value) = bool::from_variant(variant) {
+            SavedVariant::Bool(*value)
+        } else if let Some(value) = i64::from_variant(variant) {
+            SavedVariant::Int(*value)
+        } else if let Some(value) = f64::from_variant(variant) {
+            SavedVariant::Float(*value)
+        } else if let Some(value) = ImmutableString::from_variant(variant) {
+            SavedVariant::String(value.to_string())
+        } else if let Some(value) = Identifier::from_variant(variant) {
+            SavedVariant::Identifier(value.clone())
+        } else if let Some(values) = Array::from_variant(variant) {
+            SavedVariant::Array(array(values))
+        } else if let Some(values) = SharedArray::from_variant(variant) {
+            SavedVariant::SharedArray(array(&values.lock()))
+        } else if let Some(values) = Map::from_variant(variant) {
+            SavedVariant::Map(map(values))
+        } else if let Some(values) = SharedMap::from_variant(variant) {
+            SavedVariant::SharedMap(map(&values.lock()))
+        } else {
+            return None;
+        })
     }
-    fn visit_unit<E>(self) -> Result<Self::Value, E>
-    where
-        E: Error,
-    {
-        Ok(())
+    fn load(self) -> Variant {
+        let array = |values: Vec<SavedVariant>| values.into_iter().map(SavedVariant::load);
+        let map = |values: HashMap<String, SavedVariant>| {
+            values
+                .into_iter()
+                .map(|(key, value)| (ImmutableString::from(key), value.load()))
+                .collect::<HashMap<_, _>>()
+        };
+        match self {
+            SavedVariant::Null => Variant::NULL(),
+            SavedVariant::Bool(value) => value.into_variant(),
+            SavedVariant::Int(value) => value.into_variant(),
+            SavedVariant::Float(value) => value.into_variant(),
+            SavedVariant::String(value) => Variant::from_str(&value),
+            SavedVariant::Identifier(value) => value.into_variant(),
+            SavedVariant::Array(values) => array(values).collect::<Array>().into_variant(),
+            SavedVariant::SharedArray(values) => {
+                array(values).collect::<SharedArray>().into_variant()
+            }
+            SavedVariant::Map(values) => Map::new(map(values)).into_variant(),
+            SavedVariant::SharedMap(values) => {
+                SharedMap::new(Mutex::new(map(values))).into_variant()
+            }
+        }
     }
 }
 
 pub struct PlayerData {
+    pub identity: PlayerIdentity,
     entity: Mutex<Arc<Entity>>,
     pub connection: Mutex<PlayerConnection>,
     pub chunk_loading_manager: ChunkLoadingManager,
@@ -1049,15 +2381,47 @@ pub struct PlayerData {
     pub user_data: Mutex<UserData>,
     pub server: Arc<Server>,
     pub open_guis: Mutex<HashMap<Identifier, InventoryWrapper>>,
+    overlays: Mutex<HashMap<Identifier, Arc<Overlay>>>,
+    sleeping: Mutex<Option<BlockLocation>>,
+    pub spawn_point: Mutex<Option<Location>>,
+    experience: Mutex<u32>,
+    build_assist: Mutex<Option<f32>>,
+    pub clipboard: Mutex<Clipboard>,
+    spectator: Mutex<Option<MovementType>>,
+    //lets the player take items out of the creative catalog
+    creative: AtomicBool,
+    block_breaking: Mutex<Option<BlockBreakingProgress>>,
+    ping: Mutex<PingState>,
+    snapshots: Mutex<SnapshotRequests>,
+    recording: Mutex<Option<SessionRecorder>>,
+    pub debug_shapes: Mutex<DebugShapeView>,
     this: Weak<PlayerData>,
 }
+struct PingState {
+    next_id: u32,
+    pending: Option<(u32, Instant)>,
+    last_sent: Instant,
+    round_trip: Option<Duration>,
+}
+#[derive(Clone, Copy)]
+struct BlockBreakingProgress {
+    position: BlockPosition,
+    block: u32,
+    started: Instant,
+    time: f32,
+    stage: Option<u8>,
+}
 impl PlayerData {
+    const BREAK_TIME_TOLERANCE: f32 = 0.8;
+    const BREAK_STAGES: u8 = 10;
+    const PING_INTERVAL: Duration = Duration::from_secs(2);
     pub fn new(
         connection: PlayerConnection,
         server: Arc<Server>,
         entity: Arc<Entity>,
     ) -> Arc<Self> {
         let player = Arc::new_cyclic(|this| PlayerData {
+            identity: connection.identity.clone().unwrap(),
             connection: Mutex::new(connection),
             chunk_loading_manager: ChunkLoadingManager::new(
                 this.clone(),
@@ -1070,24 +2434,112 @@ impl PlayerData {
             hand_item: Mutex::new(None),
             user_data: Mutex::new(UserData::new()),
             open_guis: Mutex::new(HashMap::new()),
+            overlays: Mutex::new(HashMap::new()),
+            sleeping: Mutex::new(None),
+            spawn_point: Mutex::new(None),
+            experience: Mutex::new(0),
+            build_assist: Mutex::new(None),
+            clipboard: Mutex::new(Clipboard::new()),
+            spectator: Mutex::new(None),
+            creative: AtomicBool::new(false),
+            block_breaking: Mutex::new(None),
+            ping: Mutex::new(PingState {
+                next_id: 0,
+                pending: None,
+                last_sent: Instant::now(),
+                round_trip: None,
+            }),
+            snapshots: Mutex::new(SnapshotRequests::new()),
+            recording: Mutex::new(None),
+            debug_shapes: Mutex::new(DebugShapeView::new()),
             server,
             this: this.clone(),
         });
         player.chunk_loading_manager.load_initial_chunks();
         Inventory::set_cursor(&player, &None);
         player.resync_abilities();
+        if let Some(layout) = Identifier::parse(
+            player
+                .server
+                .settings
+                .get("server.experience_layout", "core:layout_experience")
+                .as_str(),
+        )
+        .ok()
+        .and_then(|layout| player.server.get_gui_layout(&layout))
+        {
+            layout.send_to_player(&player, "experience");
+        }
+        player.sync_experience();
+        player.send_message(&NetworkMessageS2C::SetWorldBorder(
+            entity.get_location().chunk.world.get_border(),
+        ));
+        player.send_message(&NetworkMessageS2C::SetSky(
+            entity.get_location().chunk.world.get_sky(),
+        ));
+        player.send_message(&NetworkMessageS2C::GuiLayout(
+            player.server.settings.get_f64("server.gui_scale", 1.) as f32,
+            player.server.settings.get_f64("server.gui_safe_area", 0.) as f32,
+        ));
+        player.send_message(&NetworkMessageS2C::RegisterKeybinds(
+            player
+                .server
+                .keybinds
+                .iter()
+                .map(|(id, key)| (id.to_string(), *key))
+                .collect(),
+        ));
         entity.set_player(player.clone());
         player
     }
     pub fn destroy(&self) {
+        if self.is_recording() {
+            match self.stop_recording() {
+                Ok(file) => logging::info(
+                    LogTarget::Server,
+                    format!(
+                        "saved recording of {} as recordings/{}",
+                        self.identity.username, file
+                    ),
+                ),
+                Err(error) => logging::error(LogTarget::Server, error),
+            }
+        }
         self.chunk_loading_manager.unload_chunks();
 
         for inventory in self.open_guis.lock().drain() {
+            if let Some(crafting) = inventory.1.get_inventory().get_crafting() {
+                crafting.return_items(inventory.1.get_inventory(), self);
+            }
             inventory.1.get_inventory().remove_viewer(GuiKey {
                 player: self.ptr(),
                 id: inventory.0,
             });
         }
+        self.overlays.lock().clear();
+    }
+    //an overlay with the same id is replaced
+    pub fn open_overlay(&self, id: Identifier, layout: Arc<GUILayout>) -> Arc<Overlay> {
+        self.close_overlay(&id);
+        let overlay = Overlay::new(self.ptr(), id.clone(), layout);
+        self.overlays.lock().insert(id, overlay.clone());
+        overlay
+    }
+    pub fn get_overlay(&self, id: &Identifier) -> Option<Arc<Overlay>> {
+        self.overlays.lock().get(id).cloned()
+    }
+    pub fn close_overlay(&self, id: &Identifier) {
+        if let Some(overlay) = self.overlays.lock().remove(id) {
+            overlay.remove();
+        }
+    }
+    fn parse_overlay_id(id: &ImmutableString) -> Result<Identifier, ScriptError> {
+        Identifier::parse(id.as_ref()).map_err(|_| {
+            ScriptError::runtime(
+                format!("invalid overlay id {}", id).as_str(),
+                FilePosition::INVALID,
+            )
+        })
     }
     pub fn modify_inventory_hand<F>(&self, function: F)
     where
@@ -1124,28 +2576,399 @@ impl PlayerData {
             *self.move_type.lock(),
         ));
     }
-    pub fn tick(&self) {
-        self.chunk_loading_manager.tick();
-    }
-    pub fn get_entity(&self) -> Arc<Entity> {
-        self.entity.lock().clone()
+    pub fn set_build_assist(&self, reach: Option<f32>) {
+        *self.build_assist.lock() = reach;
+        self.send_message(&NetworkMessageS2C::SetBuildAssist(reach));
     }
-    pub fn send_message(&self, message: &NetworkMessageS2C) {
-        self.connection.lock().send(message);
+    pub fn get_build_assist(&self) -> Option<f32> {
+        *self.build_assist.lock()
     }
-    pub fn send_messages(&self, messages: &Vec<NetworkMessageS2C>) {
-        let mut connection = self.connection.lock();
-        for message in messages {
-            connection.send(message);
+    pub fn set_spectator(&self, spectator: bool) {
+        {
+            let mut previous = self.spectator.lock();
+            if previous.is_some() == spectator {
+                return;
+            }
+            let mut move_type = self.move_type.lock();
+            if spectator {
+                *previous = Some(*move_type);
+                *move_type = MovementType::Spectator;
+            } else {
+                *move_type = previous.take().unwrap();
+            }
+        }
+        self.resync_abilities();
+        let entity = self.get_entity();
+        let location = entity.get_location();
+        if spectator {
+            location.chunk.announce_to_viewers_except(
+                NetworkMessageS2C::DeleteEntity(entity.client_id),
+                &entity,
+            );
+        } else {
+            for message in entity.create_add_messages(location.position) {
+                location.chunk.announce_to_viewers_except(message, &entity);
+            }
         }
     }
-    pub fn set_open_inventory(
-        &self,
-        id: Identifier,
-        new_inventory: Option<(InventoryWrapper, GuiInventoryData)>,
-    ) {
+    pub fn is_spectator(&self) -> bool {
+        self.spectator.lock().is_some()
+    }
+    pub fn set_creative(&self, creative: bool) {
+        self.creative.store(creative, Ordering::Relaxed);
+    }
+    pub fn is_creative(&self) -> bool {
+        self.creative.load(Ordering::Relaxed)
+    }
+    fn get_save_path(server: &Server, uuid: &Uuid) -> PathBuf {
+        let mut path = server.save_directory.clone();
+        path.push("players");
+        path.push(format!("{}.bin", uuid));
+        path
+    }
+    pub fn load_save_data(server: &Server, uuid: &Uuid) -> Option<PlayerSaveData> {
+        let data = std::fs::read(PlayerData::get_save_path(server, uuid)).ok()?;
+        match bitcode::deserialize::<PlayerSaveData>(data.as_slice()) {
+            Ok(save_data) => Some(save_data),
+            Err(_) => {
+                logging::warn(
+                    LogTarget::Server,
+                    format!("could not read player data of {}", uuid),
+                );
+                None
+            }
+        }
+    }
+    pub fn apply_save_data(&self, save_data: PlayerSaveData) {
+        let entity = self.get_entity();
+        entity
+            .inventory
+            .deserialize(save_data.inventory, &self.server.item_registry);
+        *entity.user_data.lock() = save_data.user_data;
+        *self.user_data.lock() = save_data.player_user_data;
+        entity.rotation_shifting.lock().0 = save_data.rotation;
+        *self.spawn_point.lock() = save_data.spawn_point.and_then(|(world, position)| {
+            self.server.worlds.lock().get(&world).map(|world| Location {
+                position,
+                world: world.clone(),
+            })
+        });
+        *self.experience.lock() = save_data.experience;
+        self.sync_experience();
+    }
+    pub fn save(&self) {
+        let entity = self.get_entity();
+        let location = entity.get_location();
+        let save_data = PlayerSaveData {
+            world: location.chunk.world.id.clone(),
+            position: location.position,
+            rotation: entity.get_rotation(),
+            inventory: entity.inventory.serialize(),
+            user_data: entity.user_data.lock().clone(),
+            player_user_data: self.user_data.lock().clone(),
+            experience: self.get_experience(),
+            spawn_point: self
+                .spawn_point
+                .lock()
+                .as_ref()
+                .map(|spawn_point| (spawn_point.world.id.clone(), spawn_point.position)),
+        };
+        let path = PlayerData::get_save_path(&self.server, &self.identity.uuid);
+        let temp_path = path.with_extension("tmp");
+        let result = std::fs::create_dir_all(path.parent().unwrap())
+            .and_then(|_| std::fs::write(&temp_path, bitcode::serialize(&save_data).unwrap()))
+            .and_then(|_| std::fs::rename(&temp_path, &path));
+        if let Err(error) = result {
+            logging::error(
+                LogTarget::Server,
+                format!(
+                    "could not save player data of {}: {}",
+                    self.identity.username, error
+                ),
+            );
+        }
+    }
+    pub fn tick(&self) {
+        if let Some(dictionary) = self.server.chunk_dictionary.get_dictionary() {
+            let mut connection = self.connection.lock();
+            connection.set_chunk_dictionary(dictionary);
+            if let Some(recorder) = self.recording.lock().as_mut() {
+                recorder.set_chunk_dictionary(connection.get_chunk_encoder().dictionary);
+            }
+        }
+        self.tick_recording();
+        self.chunk_loading_manager.tick();
+        self.tick_block_breaking();
+        self.tick_ping();
+        self.tick_snapshots();
+        self.debug_shapes.lock().tick(self);
+    }
+    //records the world as this player sees it, until the player stops it, leaves the world or disconnects
+    pub fn start_recording(&self) -> Result<String, String> {
+        let entity = self.get_entity();
+        let world = entity.get_location().chunk.world.clone();
+        //installed before the chunks are read, so nothing sent in between is missed
+        let (encoder, file) = {
+            let connection = self.connection.lock();
+            let mut recording = self.recording.lock();
+            if recording.is_some() {
+                return Err(format!(
+                    "{} is already being recorded",
+                    self.identity.username
+                ));
+            }
+            let encoder = connection.get_chunk_encoder();
+            let recorder =
+                SessionRecorder::start(self, world.id.clone(), encoder.dictionary.clone())?;
+            let file = recorder.file.clone();
+            *recording = Some(recorder);
+            (encoder, file)
+        };
+        //chunk loading jobs lock the blocks before sending, so the connection isn't locked while reading them
+        let chunks = self.chunk_loading_manager.get_sent_chunks();
+        let mut initial = Vec::new();
+        for chunk in &chunks {
+            let (palette, block_data) = chunk.serialize_blocks();
+            initial.push(chunk.create_load_message(palette, &block_data, &encoder));
+            for other in chunk.entities.lock().iter() {
+                if other.id != entity.id {
+                    initial.extend(other.create_add_messages(other.get_location().position));
+                }
+            }
+            for text_display in world.get_text_displays_in_chunk(chunk.position) {
+                initial.push(text_display.create_add_message());
+            }
+        }
+        match self.recording.lock().as_mut() {
+            Some(recorder) if recorder.file == file => recorder.initialize(initial),
+            _ => {
+                return Err(format!(
+                    "recording of {} was stopped",
+                    self.identity.username
+                ))
+            }
+        }
+        //block models are sent again, the client already has them so only the recording changes
+        for chunk in &chunks {
+            for block in chunk.get_block_data() {
+                block.on_sent_to_client(self);
+            }
+        }
+        logging::info(
+            LogTarget::Server,
+            format!(
+                "recording {} to recordings/{}",
+                self.identity.username, file
+            ),
+        );
+        Ok(file)
+    }
+    pub fn stop_recording(&self) -> Result<String, String> {
+        let recorder = self.recording.lock().take();
+        match recorder {
+            Some(recorder) => recorder.finish(),
+            None => Err(format!("{} is not being recorded", self.identity.username)),
+        }
+    }
+    pub fn is_recording(&self) -> bool {
+        self.recording.lock().is_some()
+    }
+    fn tick_recording(&self) {
+        let mut recording = self.recording.lock();
+        let result = match recording.as_mut() {
+            Some(recorder) => {
+                if recorder.world != self.get_entity().get_location().chunk.world.id {
+                    Err("the player left the world".to_string())
+                } else {
+                    recorder.tick(self)
+                }
+            }
+            None => return,
+        };
+        if let Err(error) = result {
+            let recorder = recording.take().unwrap();
+            drop(recording);
+            logging::warn(
+                LogTarget::Server,
+                format!("recording of {} stopped: {}", self.identity.username, error),
+            );
+            if let Err(error) = recorder.finish() {
+                logging::error(LogTarget::Server, error);
+            }
+        }
+    }
+    //the round trip includes the time until the next tick reads the pong, so it is rounded up to a tick
+    fn tick_ping(&self) {
+        if !self.connection.lock().has_capability(Capability::Ping) {
+            return;
+        }
+        let mut ping = self.ping.lock();
+        if let Some((_, sent)) = ping.pending {
+            let timeout = self.server.settings.get_i64("server.ping_timeout", 30);
+            if sent.elapsed() > Duration::from_secs(timeout.max(1) as u64) {
+                ping.pending = None;
+                drop(ping);
+                self.server.kick(self, "timed out".to_string());
+            }
+            return;
+        }
+        if ping.last_sent.elapsed() < Self::PING_INTERVAL {
+            return;
+        }
+        let id = ping.next_id;
+        ping.next_id = id.wrapping_add(1);
+        ping.pending = Some((id, Instant::now()));
+        ping.last_sent = Instant::now();
+        let round_trip = ping
+            .round_trip
+            .map(|round_trip| round_trip.as_millis() as u32);
+        drop(ping);
+        self.send_message(&NetworkMessageS2C::Ping(id, round_trip));
+    }
+    pub fn on_pong(&self, id: u32) {
+        let mut ping = self.ping.lock();
+        let sent = match ping.pending {
+            Some((pending_id, sent)) if pending_id == id => sent,
+            _ => return,
+        };
+        let sample = sent.elapsed();
+        //smoothed so a single slow tick doesn't make the ping jump around
+        ping.round_trip = Some(match ping.round_trip {
+            Some(round_trip) => round_trip.mul_f32(0.75) + sample.mul_f32(0.25),
+            None => sample,
+        });
+        ping.pending = None;
+    }
+    pub fn get_ping(&self) -> Option<Duration> {
+        self.ping.lock().round_trip
+    }
+    //returns false if the client can't take snapshots, the player can still decline every request
+    pub fn request_snapshot(&self, max_size: u32, requester: SnapshotRequester) -> bool {
+        if !self.has_capability(Capability::Snapshots) {
+            return false;
+        }
+        let max_size = max_size.clamp(16, SnapshotRequests::MAX_SIZE);
+        let id = self.snapshots.lock().add(max_size, requester);
+        self.send_message(&NetworkMessageS2C::RequestSnapshot(id, max_size));
+        true
+    }
+    pub fn on_snapshot(&self, id: u32, data: Option<Vec<u8>>) {
+        let (max_size, requester) = match self.snapshots.lock().take(id) {
+            Some(request) => request,
+            None => return,
+        };
+        let result = match data {
+            Some(data) => snapshot::save(self, &data, max_size),
+            None => Err("declined".to_string()),
+        };
+        requester.finish(self, result);
+    }
+    fn tick_snapshots(&self) {
+        let expired = self.snapshots.lock().take_expired();
+        for requester in expired {
+            requester.finish(self, Err("timed out".to_string()));
+        }
+    }
+    fn start_block_breaking(&self, position: BlockPosition, block: BlockStateRef, time: f32) {
+        self.cancel_block_breaking();
+        *self.block_breaking.lock() = Some(BlockBreakingProgress {
+            position,
+            block: block.get_id(),
+            started: Instant::now(),
+            time,
+            stage: None,
+        });
+    }
+    pub fn cancel_block_breaking(&self) {
+        let breaking = self.block_breaking.lock().take();
+        if let Some(breaking) = breaking {
+            self.announce_block_break_progress(breaking.position, None);
+        }
+    }
+    fn finish_block_breaking(&self, position: BlockPosition, block: BlockStateRef) -> bool {
+        let breaking = match self.block_breaking.lock().take() {
+            Some(breaking) => breaking,
+            None => return false,
+        };
+        self.announce_block_break_progress(breaking.position, None);
+        breaking.position == position
+            && breaking.block == block.get_id()
+            && breaking.started.elapsed().as_secs_f32()
+                >= breaking.time * Self::BREAK_TIME_TOLERANCE
+    }
+    fn tick_block_breaking(&self) {
+        let (position, stage) = {
+            let mut breaking = self.block_breaking.lock();
+            let breaking = match breaking.as_mut() {
+                Some(breaking) => breaking,
+                None => return,
+            };
+            let progress = if breaking.time > 0. {
+                breaking.started.elapsed().as_secs_f32() / breaking.time
+            } else {
+                1.
+            };
+            let stage = ((progress * Self::BREAK_STAGES as f32) as u8).min(Self::BREAK_STAGES - 1);
+            if breaking.stage == Some(stage) {
+                return;
+            }
+            breaking.stage = Some(stage);
+            (breaking.position, stage)
+        };
+        self.announce_block_break_progress(position, Some(stage));
+    }
+    fn announce_block_break_progress(&self, position: BlockPosition, stage: Option<u8>) {
+        let entity = self.get_entity();
+        if let Some(chunk) = entity
+            .get_location()
+            .chunk
+            .world
+            .get_chunk(position.to_chunk_pos())
+        {
+            chunk.announce_to_viewers_except(
+                NetworkMessageS2C::BlockBreakProgress(entity.client_id, position, stage),
+                &entity,
+            );
+        }
+    }
+    pub fn get_entity(&self) -> Arc<Entity> {
+        self.entity.lock().clone()
+    }
+    pub fn send_message(&self, message: &NetworkMessageS2C) {
+        let mut connection = self.connection.lock();
+        connection.send(message);
+        if let Some(recorder) = self.recording.lock().as_mut() {
+            recorder.record(message);
+        }
+    }
+    pub fn has_capability(&self, capability: Capability) -> bool {
+        self.connection.lock().has_capability(capability)
+    }
+    //operators have every permission
+    pub fn has_permission(&self, node: &str) -> bool {
+        self.server.access_list.is_operator(&self.identity)
+            || self.server.permissions.has_permission(&self.identity, node)
+    }
+    pub fn send_messages(&self, messages: &Vec<NetworkMessageS2C>) {
+        let mut connection = self.connection.lock();
+        let mut recording = self.recording.lock();
+        for message in messages {
+            connection.send(message);
+            if let Some(recorder) = recording.as_mut() {
+                recorder.record(message);
+            }
+        }
+    }
+    pub fn set_open_inventory(
+        &self,
+        id: Identifier,
+        new_inventory: Option<(InventoryWrapper, GuiInventoryData)>,
+    ) {
         let mut guis = self.open_guis.lock();
         if let Some(current_inventory) = guis.remove(&id) {
+            if let Some(crafting) = current_inventory.get_inventory().get_crafting() {
+                crafting.return_items(current_inventory.get_inventory(), self);
+            }
             current_inventory.get_inventory().remove_viewer(GuiKey {
                 player: self.ptr(),
                 id: id.clone(),
@@ -1179,6 +3002,126 @@ impl PlayerData {
     pub fn send_chat_message(&self, text: String) {
         self.send_message(&NetworkMessageS2C::ChatMessage(text));
     }
+    //u32 experience reaches levels around 41000, whose totals only fit into u64
+    pub fn experience_for_level(level: u32) -> u64 {
+        let level = level as u64;
+        10 * level + 5 * level * level.saturating_sub(1) / 2
+    }
+    pub fn level_from_experience(experience: u32) -> u32 {
+        let mut level = 0;
+        while PlayerData::experience_for_level(level + 1) <= experience as u64 {
+            level += 1;
+        }
+        level
+    }
+    pub fn get_experience(&self) -> u32 {
+        *self.experience.lock()
+    }
+    pub fn get_level(&self) -> u32 {
+        PlayerData::level_from_experience(self.get_experience())
+    }
+    pub fn add_experience(&self, amount: i64) {
+        {
+            let mut experience = self.experience.lock();
+            *experience = (*experience as i64)
+                .saturating_add(amount)
+                .clamp(0, u32::MAX as i64) as u32;
+        }
+        self.sync_experience();
+    }
+    pub fn consume_experience(&self, amount: u32) -> bool {
+        {
+            let mut experience = self.experience.lock();
+            if *experience < amount {
+                return false;
+            }
+            *experience -= amount;
+        }
+        self.sync_experience();
+        true
+    }
+    pub fn consume_levels(&self, levels: u32) -> bool {
+        let level = self.get_level();
+        if level < levels {
+            return false;
+        }
+        let experience = self.get_experience();
+        let progress = experience as u64 - PlayerData::experience_for_level(level);
+        let target = PlayerData::experience_for_level(level - levels) + progress;
+        self.consume_experience(experience - target.min(experience as u64) as u32)
+    }
+    pub fn sync_experience(&self) {
+        let experience = self.get_experience();
+        let level = PlayerData::level_from_experience(experience);
+        let level_start = PlayerData::experience_for_level(level);
+        let progress = (experience as u64 - level_start) as f32
+            / (PlayerData::experience_for_level(level + 1) - level_start) as f32;
+        self.send_message(&NetworkMessageS2C::GuiEditElement(
+            "experience_bar".to_string(),
+            GUIElementEdit {
+                component_type: GUIComponentEdit::ImageComponent {
+                    slice: Some(Some((Vec2 { x: 0., y: 0. }, Vec2 { x: progress, y: 1. }))),
+                    size: None,
+                    texture: None,
+                },
+                ..Default::default()
+            },
+        ));
+        self.send_message(&NetworkMessageS2C::GuiEditElement(
+            "experience_level".to_string(),
+            GUIElementEdit {
+                component_type: GUIComponentEdit::TextComponent {
+                    text: Some(level.to_string()),
+                    font_size: None,
+                },
+                ..Default::default()
+            },
+        ));
+    }
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping.lock().is_some()
+    }
+    pub fn sleep(&self, location: BlockLocation) -> bool {
+        if self.is_sleeping() {
+            return false;
+        }
+        let mut event_data: HashMap<ImmutableString, Variant> = HashMap::new();
+        event_data.insert("player".into(), self.ptr().into_variant());
+        event_data.insert("location".into(), location.clone().into_variant());
+        event_data.insert("cancelled".into(), false.into_variant());
+        let event_data = Arc::new(Mutex::new(event_data));
+        self.server.call_event(
+            Identifier::new("bb", "player_sleep"),
+            event_data.clone().into_variant(),
+        );
+        let cancelled = event_data
+            .lock()
+            .get("cancelled")
+            .and_then(|cancelled| bool::from_variant(cancelled).cloned())
+            .unwrap_or(false);
+        if cancelled {
+            return false;
+        }
+        *self.spawn_point.lock() = Some(Location {
+            position: location.position.to_position().add(0.5, 1., 0.5),
+            world: location.world.clone(),
+        });
+        *self.sleeping.lock() = Some(location);
+        self.send_message(&NetworkMessageS2C::ScreenFade(1., 2.));
+        true
+    }
+    pub fn wake_up(&self) {
+        if self.sleeping.lock().take().is_none() {
+            return;
+        }
+        self.send_message(&NetworkMessageS2C::ScreenFade(0., 1.));
+        let mut event_data: HashMap<ImmutableString, Variant> = HashMap::new();
+        event_data.insert("player".into(), self.ptr().into_variant());
+        self.server.call_event(
+            Identifier::new("bb", "player_wake"),
+            Arc::new(Mutex::new(event_data)).into_variant(),
+        );
+    }
     pub fn ptr(&self) -> Arc<PlayerData> {
         self.this.upgrade().unwrap()
     }
@@ -1186,6 +3129,12 @@ impl PlayerData {
 impl ScriptingObject for PlayerData {
     fn engine_register_server(env: &mut ExecutionEnvironment, server: &Weak<Server>) {
         env.register_custom_name::<Arc<PlayerData>, _>("Player");
+        env.register_member("username", |player: &Arc<PlayerData>| {
+            Some(Variant::from_str(player.identity.username.as_str()))
+        });
+        env.register_member("uuid", |player: &Arc<PlayerData>| {
+            Some(Variant::from_str(player.identity.uuid.to_string().as_str()))
+        });
         env.register_method("get_entity", |player: &Arc<PlayerData>| {
             Ok(player.get_entity().into_variant())
         });
@@ -1201,10 +3150,68 @@ impl ScriptingObject for PlayerData {
             player.resync_abilities();
             Ok(())
         });
+        env.register_method("build_assist", |player: &Arc<PlayerData>, reach: &f64| {
+            player.set_build_assist(if *reach > 0. {
+                Some(*reach as f32)
+            } else {
+                None
+            });
+            Ok(())
+        });
+        env.register_method(
+            "select_first",
+            |player: &Arc<PlayerData>, position: &BlockPosition| {
+                player.clipboard.lock().set_first(*position);
+                Ok(())
+            },
+        );
+        env.register_method(
+            "select_second",
+            |player: &Arc<PlayerData>, position: &BlockPosition| {
+                player.clipboard.lock().set_second(*position);
+                Ok(())
+            },
+        );
+        env.register_method(
+            "set_spectator",
+            |player: &Arc<PlayerData>, spectator: &bool| {
+                player.set_spectator(*spectator);
+                Ok(())
+            },
+        );
+        env.register_member("spectator", |player: &Arc<PlayerData>| {
+            Some(player.is_spectator().into_variant())
+        });
+        env.register_method(
+            "set_creative",
+            |player: &Arc<PlayerData>, creative: &bool| {
+                player.set_creative(*creative);
+                Ok(())
+            },
+        );
+        env.register_member("creative", |player: &Arc<PlayerData>| {
+            Some(player.is_creative().into_variant())
+        });
+        //round trip time in milliseconds, -1 until the first pong arrives or for clients that don't answer pings
+        env.register_member("ping", |player: &Arc<PlayerData>| {
+            Some(
+                player
+                    .get_ping()
+                    .map_or(-1, |ping| ping.as_millis() as i64)
+                    .into_variant(),
+            )
+        });
+        env.register_method("save", |player: &Arc<PlayerData>| {
+            player.save();
+            Ok(())
+        });
         env.register_method(
             "movement_type",
             |player: &Arc<PlayerData>, movement_type: &MovementType| {
-                *player.move_type.lock() = *movement_type;
+                match &mut *player.spectator.lock() {
+                    Some(previous) => *previous = *movement_type,
+                    None => *player.move_type.lock() = *movement_type,
+                }
                 player.resync_abilities();
                 Ok(())
             },
@@ -1219,6 +3226,53 @@ impl ScriptingObject for PlayerData {
                 Ok(())
             },
         );
+        env.register_member("experience", |player: &Arc<PlayerData>| {
+            Some(player.get_experience() as i64)
+        });
+        env.register_member("level", |player: &Arc<PlayerData>| {
+            Some(player.get_level() as i64)
+        });
+        env.register_method(
+            "add_experience",
+            |player: &Arc<PlayerData>, amount: &i64| {
+                player.add_experience(*amount);
+                Ok(())
+            },
+        );
+        env.register_method(
+            "consume_experience",
+            |player: &Arc<PlayerData>, amount: &i64| {
+                Ok(player.consume_experience((*amount).max(0) as u32))
+            },
+        );
+        env.register_method(
+            "consume_levels",
+            |player: &Arc<PlayerData>, levels: &i64| {
+                Ok(player.consume_levels((*levels).max(0) as u32))
+            },
+        );
+        env.register_method(
+            "sleep",
+            |player: &Arc<PlayerData>, location: &BlockLocation| Ok(player.sleep(location.clone())),
+        );
+        env.register_method("wake_up", |player: &Arc<PlayerData>| {
+            player.wake_up();
+            Ok(())
+        });
+        env.register_method("is_sleeping", |player: &Arc<PlayerData>| {
+            Ok(player.is_sleeping())
+        });
+        env.register_member("spawn_point", |player: &Arc<PlayerData>| {
+            Some(Variant::from_option(player.spawn_point.lock().clone()))
+        });
+        env.register_method(
+            "set_spawn_point",
+            |player: &Arc<PlayerData>, location: &Variant| {
+                *player.spawn_point.lock() =
+                    Variant::into_option(location, &FilePosition::INVALID)?.cloned();
+                Ok(())
+            },
+        );
         env.register_method(
             "set_cursor_locked",
             |player: &Arc<PlayerData>, locked: &bool| {
@@ -1249,10 +3303,8 @@ impl ScriptingObject for PlayerData {
                                 layout: server
                                     .upgrade()
                                     .unwrap()
-                                    .gui_layouts
-                                    .get(&Identifier::parse(layout.as_ref()).unwrap())
-                                    .unwrap()
-                                    .clone(),
+                                    .get_gui_layout(&Identifier::parse(layout.as_ref()).unwrap())
+                                    .unwrap(),
                                 on_click: FunctionVariant::from_variant(on_click)
                                     .map(|variant| ScriptCallback::from_function_variant(variant))
                                     .unwrap_or(ScriptCallback::empty()),
@@ -1266,6 +3318,116 @@ impl ScriptingObject for PlayerData {
                 },
             );
         }
+        {
+            let server = server.clone();
+            env.register_method(
+                "open_crafting",
+                move |player: &Arc<PlayerData>, layout: &ImmutableString, grid_size: &i64| {
+                    let server = server.upgrade().unwrap();
+                    let layout = Identifier::parse(layout.as_ref())
+                        .ok()
+                        .and_then(|layout| server.get_gui_layout(&layout))
+                        .ok_or_else(|| {
+                            ScriptError::runtime("unknown gui layout", FilePosition::INVALID)
+                        })?;
+                    if !(1..=5).contains(grid_size) {
+                        return Err(ScriptError::runtime(
+                            "crafting grid size must be between 1 and 5",
+                            FilePosition::INVALID,
+                        ));
+                    }
+                    let crafting = CraftingGrid::new(*grid_size as u32, &server.get_recipes());
+                    let size = crafting.get_output_slot() + 1;
+                    let inventory = InventoryWrapper::Own(Inventory::new_crafting(crafting));
+                    player.set_open_inventory(
+                        Identifier::new("bb", "crafting"),
+                        Some((
+                            inventory.clone(),
+                            GuiInventoryData {
+                                slot_range: 0..size,
+                                layout,
+                                on_click: ScriptCallback::empty(),
+                                on_scroll: ScriptCallback::empty(),
+                            },
+                        )),
+                    );
+                    Ok(inventory)
+                },
+            );
+        }
+        {
+            let server = server.clone();
+            env.register_method(
+                "open_creative",
+                move |player: &Arc<PlayerData>, layout: &ImmutableString| {
+                    let server = server.upgrade().unwrap();
+                    let layout = Identifier::parse(layout.as_ref())
+                        .ok()
+                        .and_then(|layout| server.get_gui_layout(&layout))
+                        .ok_or_else(|| {
+                            ScriptError::runtime("unknown gui layout", FilePosition::INVALID)
+                        })?;
+                    let catalog = CreativeCatalog::new(
+                        layout.get_slot_count(),
+                        server.search_index.get_categories().clone(),
+                    );
+                    let size = catalog.get_size();
+                    let inventory = Inventory::new_catalog(catalog);
+                    let id = Identifier::new("bb", "creative");
+                    player.set_open_inventory(
+                        id.clone(),
+                        Some((
+                            InventoryWrapper::Own(inventory.clone()),
+                            GuiInventoryData {
+                                slot_range: 0..size,
+                                layout,
+                                on_click: ScriptCallback::empty(),
+                                on_scroll: ScriptCallback::empty(),
+                            },
+                        )),
+                    );
+                    inventory.get_catalog().unwrap().refresh(
+                        &inventory,
+                        &GuiKey {
+                            player: player.clone(),
+                            id,
+                        },
+                    );
+                    Ok(InventoryWrapper::Own(inventory))
+                },
+            );
+        }
+        {
+            let server = server.clone();
+            env.register_method(
+                "open_overlay",
+                move |player: &Arc<PlayerData>, id: &ImmutableString, layout: &ImmutableString| {
+                    let server = server.upgrade().unwrap();
+                    let layout = Identifier::parse(layout.as_ref())
+                        .ok()
+                        .and_then(|layout| server.get_gui_layout(&layout))
+                        .ok_or_else(|| {
+                            ScriptError::runtime("unknown gui layout", FilePosition::INVALID)
+                        })?;
+                    Ok(player.open_overlay(PlayerData::parse_overlay_id(id)?, layout))
+                },
+            );
+        }
+        env.register_method(
+            "get_overlay",
+            |player: &Arc<PlayerData>, id: &ImmutableString| {
+                Ok(Variant::from_option(
+                    player.get_overlay(&PlayerData::parse_overlay_id(id)?),
+                ))
+            },
+        );
+        env.register_method(
+            "close_overlay",
+            |player: &Arc<PlayerData>, id: &ImmutableString| {
+                player.close_overlay(&PlayerData::parse_overlay_id(id)?);
+                Ok(())
+            },
+        );
         env.register_method(
             "get_open_inventory",
             |player: &Arc<PlayerData>, id: &ImmutableString| {
@@ -1292,6 +3454,68 @@ impl ScriptingObject for PlayerData {
                 player.hand_item.lock().as_ref().cloned(),
             ))
         });
+        {
+            let server = server.clone();
+            env.register_method(
+                "teleport_world",
+                move |player: &Arc<PlayerData>, id: &ImmutableString, position: &Position| {
+                    let world = Identifier::parse(id.as_ref())
+                        .ok()
+                        .and_then(|id| server.upgrade().unwrap().get_world(Arc::new(id)))
+                        .ok_or_else(|| {
+                            ScriptError::runtime("world not loaded", FilePosition::INVALID)
+                        })?;
+                    player.get_entity().teleport_world(world, *position);
+                    Ok(())
+                },
+            );
+        }
+        env.register_method(
+            "set_waypoint",
+            |player: &Arc<PlayerData>, name: &ImmutableString, position: &Position| {
+                player.send_message(&NetworkMessageS2C::SetWaypoint(
+                    name.to_string(),
+                    Some(*position),
+                ));
+                Ok(())
+            },
+        );
+        env.register_method(
+            "remove_waypoint",
+            |player: &Arc<PlayerData>, name: &ImmutableString| {
+                player.send_message(&NetworkMessageS2C::SetWaypoint(name.to_string(), None));
+                Ok(())
+            },
+        );
+        env.register_method(
+            "request_snapshot",
+            |player: &Arc<PlayerData>, max_size: &i64, callback: &Variant| {
+                let callback = FunctionVariant::from_variant(callback)
+                    .map(|function| ScriptCallback::from_function_variant(function))
+                    .ok_or_else(|| {
+                        ScriptError::runtime("callback must be a function", FilePosition::INVALID)
+                    })?;
+                Ok(player.request_snapshot(
+                    (*max_size).max(0) as u32,
+                    SnapshotRequester::Script(callback),
+                ))
+            },
+        );
+        env.register_method("start_recording", |player: &Arc<PlayerData>| {
+            player
+                .start_recording()
+                .map(|file| Variant::from_str(file.as_str()))
+                .map_err(|error| ScriptError::runtime(error.as_str(), FilePosition::INVALID))
+        });
+        env.register_method("stop_recording", |player: &Arc<PlayerData>| {
+            Ok(match player.stop_recording() {
+                Ok(file) => Variant::from_str(file.as_str()),
+                Err(_) => Variant::NULL(),
+            })
+        });
+        env.register_member("recording", |player: &Arc<PlayerData>| {
+            Some(player.is_recording().into_variant())
+        });
     }
 }
 
@@ -1312,11 +3536,39 @@ pub struct Entity {
     pub slot: Mutex<u32>,
     pub player: Mutex<Option<Weak<PlayerData>>>,
     pathfinder: Mutex<Pathfinder>,
+    schedule_controller: Mutex<ScheduleController>,
+    pub experience: AtomicU32,
+    step_distance: Mutex<f64>,
+    portal_cooldown: AtomicU32,
+    return_portal: Mutex<Option<(Identifier, BlockPosition, BlockStateRef)>>,
+    item_cooldowns: Mutex<FxHashMap<u32, u32>>,
+    projectile_stuck: AtomicBool,
+    bone_poses: Mutex<FxHashMap<Vec<u32>, BonePose>>,
+    detected_blocks: Mutex<Option<(Arc<World>, HashSet<BlockPosition>)>>,
 }
 
 static ENTITY_CLIENT_ID_GENERATOR: AtomicU32 = AtomicU32::new(0);
 
 impl Entity {
+    const BURN_INTERVAL: u64 = 20;
+    const BURN_DAMAGE: f64 = 1.;
+    const AMBIENT_SOUND_CHANCE: f64 = 1. / 240.;
+    const STEP_DISTANCE: f64 = 1.5;
+    const PORTAL_COOLDOWN: u32 = 80;
+    const GRAVITY: f64 = 2. / 20.;
+    const TICKS_PER_SECOND: f64 = 20.;
+    const COLLISION_RADIUS: f64 = 4.;
+    pub fn to_save_data(&self, position: Position) -> EntitySaveData {
+        EntitySaveData {
+            entity_type: self.entity_type.id.clone(),
+            velocity: self.velocity.lock().clone(),
+            rotation: self.get_rotation(),
+            position,
+            inventory: self.inventory.serialize(),
+            user_data: self.user_data.lock().clone(),
+            experience: self.experience.load(Ordering::Relaxed),
+        }
+    }
     pub fn new<T: Into<ChunkLocation>>(location: T, entity_type: &Arc<EntityType>) -> Arc<Entity> {
         let location: ChunkLocation = location.into();
         let chunk = location.chunk.clone();
@@ -1330,7 +3582,10 @@ impl Entity {
             id: Uuid::new_v4(),
             teleport: Mutex::new(None),
             rotation_shifting: Mutex::new((Direction::default(), false)),
-            animation_controller: Mutex::new(AnimationController::new(weak.clone(), 1)),
+            animation_controller: Mutex::new(AnimationController::new(
+                weak.clone(),
+                "idle".to_string(),
+            )),
             inventory: Inventory::new(
                 WeakInventoryWrapper::Entity(weak.clone()),
                 entity_type.inventory_size,
@@ -1341,6 +3596,15 @@ impl Entity {
             slot: Mutex::new(0),
             player: Mutex::new(None),
             pathfinder: Mutex::new(Pathfinder::new((&location).into())),
+            schedule_controller: Mutex::new(ScheduleController::new()),
+            experience: AtomicU32::new(0),
+            step_distance: Mutex::new(0.),
+            portal_cooldown: AtomicU32::new(0),
+            return_portal: Mutex::new(None),
+            item_cooldowns: Mutex::new(FxHashMap::default()),
+            projectile_stuck: AtomicBool::new(false),
+            bone_poses: Mutex::new(FxHashMap::default()),
+            detected_blocks: Mutex::new(None),
             location: Mutex::new(location.clone()),
         });
         chunk.add_entity(entity.clone());
@@ -1360,23 +3624,10 @@ impl Entity {
         player.send_message(&NetworkMessageS2C::ControllingEntity(
             self.entity_type.client_id,
         ));
-        /*self.inventory.add_viewer(GuiInventoryViewer {
-            slot_range: 0..9,
-            layout: self
-                .server
-                .gui_layouts
-                .get(&Identifier::new("core", "layout_hotbar"))
-                .unwrap()
-                .clone(),
-            id: self.get_id().clone(),
-            viewer: player.clone(),
-            on_click: ScriptCallback::empty(),
-            on_scroll: ScriptCallback::empty(),
-        });*/
-
         *self.player.lock() = Some(Arc::downgrade(&player));
 
         self.set_hand_slot(0);
+        self.sync_offhand_viewmodel(self.get_offhand_item().as_ref());
     }
     pub fn set_hand_slot(&self, slot: u32) {
         let slot = if slot == u32::MAX {
@@ -1384,31 +3635,48 @@ impl Entity {
         } else {
             slot.rem_euclid(self.inventory.get_size())
         };
-        let old_slot = *self.slot.lock();
         *self.slot.lock() = slot;
-        //todo
-        /*if let Some(player) = self.get_player() {
-            player.send_message(&NetworkMessageS2C::GuiEditElement(
-                self.inventory.get_slot_id_entity(self, old_slot),
-                GUIElementEdit {
-                    base_color: Some(Color::WHITE),
-                    ..Default::default()
-                },
-            ));
-            player.send_message(&NetworkMessageS2C::GuiEditElement(
-                self.inventory.get_slot_id_entity(self, slot),
-                GUIElementEdit {
-                    base_color: Some(Color {
-                        r: 100,
-                        g: 100,
-                        b: 100,
-                        a: 255,
-                    }),
-                    ..Default::default()
-                },
+        if let Some(player) = self.get_player() {
+            player.send_message(&NetworkMessageS2C::HotbarSelect(slot));
+        }
+    }
+    //cooldowns are per item type, so every stack of the item is blocked
+    pub fn set_item_cooldown(&self, item: &Item, ticks: u32) {
+        {
+            let mut item_cooldowns = self.item_cooldowns.lock();
+            if ticks == 0 {
+                item_cooldowns.remove(&item.client_id);
+            } else {
+                item_cooldowns.insert(item.client_id, ticks);
+            }
+        }
+        if let Some(player) = self.get_player() {
+            player.send_message(&NetworkMessageS2C::ItemCooldown(
+                item.client_id,
+                (ticks as f64 / Self::TICKS_PER_SECOND) as f32,
             ));
-            self.sync_main_hand_viewmodel(self.get_hand_item().as_ref());
-        }*/
+        }
+    }
+    pub fn get_item_cooldown(&self, item: &Item) -> u32 {
+        self.item_cooldowns
+            .lock()
+            .get(&item.client_id)
+            .copied()
+            .unwrap_or(0)
+    }
+    fn is_slot_on_cooldown(&self, slot: u32) -> bool {
+        self.inventory
+            .get_full_view()
+            .get_item(slot)
+            .ok()
+            .flatten()
+            .map_or(false, |item| self.get_item_cooldown(&item.item_type) > 0)
+    }
+    fn tick_item_cooldowns(&self) {
+        self.item_cooldowns.lock().retain(|_, ticks| {
+            *ticks -= 1;
+            *ticks > 0
+        });
     }
     pub fn get_collider(&self) -> AABB {
         let position = self.get_location().position;
@@ -1433,10 +3701,17 @@ impl Entity {
             None => None,
         }
     }
+    pub fn is_spectator(&self) -> bool {
+        self.get_player()
+            .map_or(false, |player| player.is_spectator())
+    }
     pub fn get_id(&self) -> &Uuid {
         &self.id
     }
     pub fn create_add_messages(&self, position: Position) -> Vec<NetworkMessageS2C> {
+        if self.is_spectator() {
+            return Vec::new();
+        }
         let animation_controller = self.animation_controller.lock();
         let mut messages = Vec::new();
         messages.push(NetworkMessageS2C::AddEntity(
@@ -1444,19 +3719,24 @@ impl Entity {
             self.client_id,
             position,
             self.rotation_shifting.lock().0,
-            animation_controller.animation,
+            animation_controller.animation.clone(),
             animation_controller.animation_start_time,
         ));
-        for (inventory_index, model_index) in &self.entity_type.item_model_mapping.mapping {
+        let items = self
+            .entity_type
+            .item_model_mapping
+            .get_items(&self.inventory);
+        if !items.is_empty() {
             messages.push(NetworkMessageS2C::ModelItem(
                 ClientModelTarget::Entity(self.client_id),
-                *model_index,
-                self.inventory
-                    .get_full_view()
-                    .get_item(*inventory_index)
-                    .unwrap()
-                    .as_ref()
-                    .map(|item| item.item_type.client_id),
+                items,
+            ));
+        }
+        for (path, pose) in self.bone_poses.lock().iter() {
+            messages.push(NetworkMessageS2C::ModelBonePose(
+                ClientModelTarget::Entity(self.client_id),
+                path.clone(),
+                Some(*pose),
             ));
         }
         messages
@@ -1494,20 +3774,320 @@ impl Entity {
         let location = self.location.lock();
         location.clone()
     }
+    //blocks the entity still has to walk through to reach its target
+    pub fn get_path(&self) -> Vec<BlockPosition> {
+        self.pathfinder.lock().get_path()
+    }
     pub fn apply_knockback(&self, x: f64, y: f64, z: f64) {
         let mut velocity = self.velocity.lock();
         velocity.0 += x;
         velocity.1 += y;
         velocity.2 += z;
+        self.projectile_stuck.store(false, Ordering::Relaxed);
+    }
+    pub fn launch(&self, direction: Direction, speed: f64) {
+        let direction = direction.to_vector();
+        *self.velocity.lock() = (
+            direction.x * speed,
+            direction.y * speed,
+            direction.z * speed,
+        );
+        self.projectile_stuck.store(false, Ordering::Relaxed);
+    }
+    //projectiles sweep the whole step instead of moving per axis, so fast ones can't skip over thin walls or entities
+    fn tick_projectile(&self, teleport_location: Option<&ChunkLocation>) -> ChunkLocation {
+        let location = teleport_location
+            .cloned()
+            .unwrap_or_else(|| self.get_location());
+        if self.projectile_stuck.load(Ordering::Relaxed) {
+            return location;
+        }
+        let world = location.chunk.world.clone();
+        let velocity = {
+            let mut velocity = self.velocity.lock();
+            velocity.1 -= Self::GRAVITY;
+            *velocity
+        };
+        let length =
+            (velocity.0 * velocity.0 + velocity.1 * velocity.1 + velocity.2 * velocity.2).sqrt();
+        if length == 0. {
+            return location;
+        }
+        let direction = Position {
+            x: velocity.0 / length,
+            y: velocity.1 / length,
+            z: velocity.2 / length,
+        };
+        let hitbox = &self.entity_type.client_data;
+        let half_hitbox = (
+            hitbox.hitbox_w / 2.,
+            hitbox.hitbox_h / 2.,
+            hitbox.hitbox_d / 2.,
+        );
+        let start = location
+            .position
+            .add(half_hitbox.0, half_hitbox.1, half_hitbox.2);
+
+        //entities the projectile starts inside of are skipped, so it can be spawned inside whoever shot it
+        let mut hit_entity: Option<(f64, Arc<Entity>)> = None;
+        for entity in world.get_entities_in_radius(start, length + 4.) {
+            if entity.id == self.id || entity.is_removed() {
+                continue;
+            }
+            let aabb = entity.get_collider();
+            let (x2, y2, z2) = aabb.calc_second_point();
+            if (aabb.x..=x2).contains(&start.x)
+                && (aabb.y..=y2).contains(&start.y)
+                && (aabb.z..=z2).contains(&start.z)
+            {
+                continue;
+            }
+            if let Some(distance) = aabb.raycast(
+                start,
+                Vec3 {
+                    x: direction.x as f32,
+                    y: direction.y as f32,
+                    z: direction.z as f32,
+                },
+            ) {
+                if distance <= length
+                    && distance
+                        < hit_entity
+                            .as_ref()
+                            .map(|hit| hit.0)
+                            .unwrap_or(f64::INFINITY)
+                {
+                    hit_entity = Some((distance, entity));
+                }
+            }
+        }
+        let max_distance = hit_entity.as_ref().map(|hit| hit.0).unwrap_or(length);
+        let mut hit_block = None;
+        //projectiles stop in front of unloaded chunks instead of loading them
+        let mut unloaded = None;
+        voxel_tile_raycast::voxel_raycast(
+            voxel_tile_raycast::na::Vector3::new(start.x, start.y, start.z),
+            voxel_tile_raycast::na::Vector3::new(direction.x, direction.y, direction.z),
+            max_distance,
+            |index, hit_position, hit_normal| {
+                let position = BlockPosition {
+                    x: index.x,
+                    y: index.y,
+                    z: index.z,
+                };
+                let offset = position.chunk_offset();
+                let distance = if hit_normal == voxel_tile_raycast::na::Vector3::zeros() {
+                    0.
+                } else {
+                    (hit_position - voxel_tile_raycast::na::Vector3::new(start.x, start.y, start.z))
+                        .norm()
+                };
+                match world.get_chunk(position.to_chunk_pos()) {
+                    Some(chunk) => {
+                        if !chunk.is_collidable_at(offset.0, offset.1, offset.2) {
+                            return false;
+                        }
+                    }
+                    None => {
+                        if distance <= max_distance {
+                            unloaded = Some(distance);
+                        }
+                        return true;
+                    }
+                }
+                if distance <= max_distance {
+                    let face = Face::all()
+                        .iter()
+                        .find(|face| {
+                            let offset = face.get_offset();
+                            offset.x == hit_normal.x
+                                && offset.y == hit_normal.y
+                                && offset.z == hit_normal.z
+                        })
+                        .cloned()
+                        .unwrap_or(Face::Up);
+                    hit_block = Some((distance, position, face));
+                }
+                true
+            },
+        );
+        if unloaded.is_some() {
+            hit_entity = None;
+        }
+        let distance = hit_block
+            .as_ref()
+            .map(|hit| hit.0)
+            .or(unloaded)
+            .or(hit_entity.as_ref().map(|hit| hit.0))
+            .unwrap_or(length);
+        let position = start.add(
+            direction.x * distance - half_hitbox.0,
+            direction.y * distance - half_hitbox.1,
+            direction.z * distance - half_hitbox.2,
+        );
+        self.rotation_shifting.lock().0 = Direction {
+            pitch: direction.y.asin(),
+            yaw: direction.x.atan2(direction.z),
+        };
+        if let Some((_, block_position, face)) = hit_block {
+            *self.velocity.lock() = (0., 0., 0.);
+            self.projectile_stuck.store(true, Ordering::Relaxed);
+            if let Err(error) = self
+                .entity_type
+                .static_data
+                .get_function("on_hit_block")
+                .call_function(
+                    &self.server.script_environment,
+                    Some(self.ptr().into_variant()),
+                    vec![
+                        BlockLocation {
+                            world: world.clone(),
+                            position: block_position,
+                        }
+                        .into_variant(),
+                        face.into_variant(),
+                    ],
+                )
+            {
+                logging::error(
+                    LogTarget::Scripts,
+                    format!(
+                        "on_hit_block of {} failed: {:?}",
+                        self.entity_type.id, error
+                    ),
+                );
+            }
+        } else if let Some((_, entity)) = hit_entity {
+            *self.velocity.lock() = (0., 0., 0.);
+            if let Err(error) = self
+                .entity_type
+                .static_data
+                .get_function("on_hit_entity")
+                .call_function(
+                    &self.server.script_environment,
+                    Some(self.ptr().into_variant()),
+                    vec![entity.into_variant()],
+                )
+            {
+                logging::error(
+                    LogTarget::Scripts,
+                    format!(
+                        "on_hit_entity of {} failed: {:?}",
+                        self.entity_type.id, error
+                    ),
+                );
+            }
+        } else if unloaded.is_some() {
+            *self.velocity.lock() = (0., 0., 0.);
+        }
+        ChunkLocation::from(&Location { world, position })
+    }
+    //pushable entities move themselves out of everything they overlap along the shallowest axis,
+    //when the other one is pushable too both take half
+    fn resolve_entity_collisions(&self) -> (f64, f64, f64) {
+        let on_collide = self.entity_type.static_data.get_function("on_collide");
+        if !self.entity_type.pushable && on_collide.function.is_none() {
+            return (0., 0., 0.);
+        }
+        let aabb = self.get_collider();
+        let (x2, y2, z2) = aabb.calc_second_point();
+        let center = Position {
+            x: aabb.x + aabb.w / 2.,
+            y: aabb.y + aabb.h / 2.,
+            z: aabb.z + aabb.d / 2.,
+        };
+        let world = self.get_location().chunk.world.clone();
+        let mut push = (0., 0., 0.);
+        for entity in world.get_entities_in_radius(center, Self::COLLISION_RADIUS) {
+            if entity.id == self.id || entity.is_removed() || entity.is_spectator() {
+                continue;
+            }
+            let other = entity.get_collider();
+            if !aabb.collides(&other) {
+                continue;
+            }
+            if let Err(error) = on_collide.call_function(
+                &self.server.script_environment,
+                Some(self.ptr().into_variant()),
+                vec![entity.clone().into_variant()],
+            ) {
+                logging::error(
+                    LogTarget::Scripts,
+                    format!("on_collide of {} failed: {:?}", self.entity_type.id, error),
+                );
+            }
+            if !self.entity_type.pushable {
+                continue;
+            }
+            let (other_x2, other_y2, other_z2) = other.calc_second_point();
+            //signed distance to move out of the other entity on each axis, towards the closer side
+            let separation = |min: f64, max: f64, other_min: f64, other_max: f64| {
+                if max - other_min < other_max - min {
+                    other_min - max
+                } else {
+                    other_max - min
+                }
+            };
+            let x = separation(aabb.x, x2, other.x, other_x2);
+            let y = separation(aabb.y, y2, other.y, other_y2);
+            let z = separation(aabb.z, z2, other.z, other_z2);
+            let share = if entity.entity_type.pushable && entity.get_player().is_none() {
+                0.5
+            } else {
+                1.
+            };
+            if x.abs() <= y.abs() && x.abs() <= z.abs() {
+                push.0 += x * share;
+            } else if z.abs() <= y.abs() {
+                push.2 += z * share;
+            } else {
+                push.1 += y * share;
+            }
+        }
+        push
+    }
+    //conveyor belts and boost pads move whatever stands on the middle of them
+    fn get_surface_velocity(aabb: &AABB, world: &World) -> Option<Vec3> {
+        let below = Position {
+            x: aabb.x + aabb.w / 2.,
+            y: aabb.y - 0.1,
+            z: aabb.z + aabb.d / 2.,
+        };
+        let block = world.get_block(&below.to_block_pos())?;
+        world
+            .server
+            .block_registry
+            .state_by_ref(block.get_block_state())
+            .client_data
+            .surface_velocity
     }
     pub fn tick(&self) {
+        if self.get_player().is_none() && self.should_despawn() {
+            self.remove();
+            return;
+        }
         let mut teleport_location = { self.teleport.lock().as_ref().map(|loc| loc.clone()) };
-        if self.get_player().is_none() {
+        if self.get_player().is_none() && self.entity_type.projectile {
+            teleport_location = Some(self.tick_projectile(teleport_location.as_ref()));
+        } else if self.get_player().is_none() {
+            if let Some(schedule) = &self.entity_type.schedule {
+                let target = self.schedule_controller.lock().tick(
+                    schedule,
+                    &(&self.get_location()).into(),
+                    self.id,
+                );
+                self.pathfinder.lock().set_target(target);
+            }
+            let push = if teleport_location.is_none() {
+                self.resolve_entity_collisions()
+            } else {
+                (0., 0., 0.)
+            };
             let mut velocity = self.velocity.lock();
             velocity.0 *= 0.8;
             velocity.1 *= 0.8;
             velocity.2 *= 0.8;
-            velocity.1 -= 2. / 20.;
+            velocity.1 -= Self::GRAVITY;
 
             let mut physics_aabb = self.get_collider();
             let world = if let Some(teleport_location) = &teleport_location {
@@ -1518,8 +4098,20 @@ impl Entity {
             };
             let is_on_ground = physics_aabb
                 .move_by(0., -0.1, 0.)
-                .has_block(&world, |block| block.collidable);
-            if let Some(face) = self.pathfinder.lock().get_required_face() {
+                .has_collidable_block(&world);
+            let start_position = physics_aabb.get_position();
+            let required_face = self.pathfinder.lock().get_required_face();
+            if self.entity_type.schedule.is_some() {
+                self.animation_controller.lock().set_movement_animation(
+                    if required_face.is_some() {
+                        "walking"
+                    } else {
+                        "idle"
+                    }
+                    .to_string(),
+                );
+            }
+            if let Some(face) = required_face {
                 let offset = face.get_offset();
                 velocity.0 = offset.x as f64 * 0.2;
                 if is_on_ground && offset.y > 0 {
@@ -1527,9 +4119,12 @@ impl Entity {
                 }
                 velocity.2 = offset.z as f64 * 0.2;
             }
+            velocity.0 += push.0;
+            velocity.1 += push.1;
+            velocity.2 += push.2;
             {
                 let x_moved_physics_aabb = physics_aabb.move_by(velocity.0, 0., 0.);
-                if !x_moved_physics_aabb.has_block(&world, |state| state.collidable) {
+                if !x_moved_physics_aabb.has_collidable_block(&world) {
                     physics_aabb = x_moved_physics_aabb;
                 } else {
                     velocity.0 = 0.;
@@ -1537,7 +4132,7 @@ impl Entity {
             }
             {
                 let y_moved_physics_aabb = physics_aabb.move_by(0., velocity.1, 0.);
-                if !y_moved_physics_aabb.has_block(&world, |state| state.collidable) {
+                if !y_moved_physics_aabb.has_collidable_block(&world) {
                     physics_aabb = y_moved_physics_aabb;
                 } else {
                     velocity.1 = 0.;
@@ -1545,17 +4140,76 @@ impl Entity {
             }
             {
                 let z_moved_physics_aabb = physics_aabb.move_by(0., 0., velocity.2);
-                if !z_moved_physics_aabb.has_block(&world, |state| state.collidable) {
+                if !z_moved_physics_aabb.has_collidable_block(&world) {
                     physics_aabb = z_moved_physics_aabb;
                 } else {
                     velocity.2 = 0.;
                 }
             }
+            //the surface only carries the entity along, it doesn't add to its velocity
+            if let Some(surface) = is_on_ground
+                .then(|| Self::get_surface_velocity(&physics_aabb, &world))
+                .flatten()
+            {
+                let surface = Position::from(surface).multiply(1. / Self::TICKS_PER_SECOND);
+                for (x, y, z) in [
+                    (surface.x, 0., 0.),
+                    (0., surface.y, 0.),
+                    (0., 0., surface.z),
+                ] {
+                    let moved_physics_aabb = physics_aabb.move_by(x, y, z);
+                    if !moved_physics_aabb.has_collidable_block(&world) {
+                        physics_aabb = moved_physics_aabb;
+                    }
+                }
+            }
+            if is_on_ground {
+                let end_position = physics_aabb.get_position();
+                let mut step_distance = self.step_distance.lock();
+                *step_distance += ((end_position.x - start_position.x).powi(2)
+                    + (end_position.z - start_position.z).powi(2))
+                .sqrt();
+                if *step_distance >= Self::STEP_DISTANCE {
+                    *step_distance = 0.;
+                    self.play_sound_event("step");
+                }
+            }
+            if thread_rng().gen_bool(Self::AMBIENT_SOUND_CHANCE) {
+                self.play_sound_event("ambient");
+            }
             teleport_location = Some(ChunkLocation::from(&Location {
                 world,
                 position: physics_aabb.get_position(),
             }))
         }
+        //entities that stand still have to be pushed back in too when the border shrinks
+        if teleport_location.is_none() {
+            let location = self.get_location();
+            if !location
+                .chunk
+                .world
+                .get_border()
+                .contains(&location.position)
+            {
+                teleport_location = Some(location);
+            }
+        }
+        if let Some(teleport_location) = &mut teleport_location {
+            let border = teleport_location.chunk.world.get_border();
+            if !border.contains(&teleport_location.position) {
+                let position = border.clamp(teleport_location.position);
+                *teleport_location = ChunkLocation::from(&Location {
+                    position,
+                    world: teleport_location.chunk.world.clone(),
+                });
+                if let Some(player) = self.get_player() {
+                    player.send_message(&NetworkMessageS2C::TeleportPlayer(
+                        position,
+                        self.get_rotation(),
+                    ));
+                }
+            }
+        }
         if let Some(teleport_location) = teleport_location {
             self.pathfinder
                 .lock()
@@ -1592,6 +4246,13 @@ impl Entity {
                             new_location.chunk.world.clone(),
                             new_location.position.to_chunk_pos(),
                         );
+                        player.save();
+                        player.send_message(&NetworkMessageS2C::SetWorldBorder(
+                            new_location.chunk.world.get_border(),
+                        ));
+                        player.send_message(&NetworkMessageS2C::SetSky(
+                            new_location.chunk.world.get_sky(),
+                        ));
                     }
                 }
             }
@@ -1607,6 +4268,13 @@ impl Entity {
         {
             *self.teleport.lock() = None;
         }
+        if self.experience.load(Ordering::Relaxed) > 0 {
+            self.tick_experience_pickup();
+        }
+        self.tick_burning();
+        self.tick_portal();
+        self.tick_item_cooldowns();
+        self.tick_detectors();
         self.entity_type
             .static_data
             .get_function("on_tick")
@@ -1618,8 +4286,24 @@ impl Entity {
             .unwrap();
 
         if let Some(player) = self.get_player() {
-            let messages = player.connection.lock().receive_messages();
+            let messages = {
+                let mut connection = player.connection.lock();
+                connection.resend_movement();
+                connection.receive_messages()
+            };
             for message in messages {
+                //older clients don't send where they clicked, the center of the face is used instead
+                let message = match message {
+                    NetworkMessageC2S::RightClickBlock(block_position, face, shifting) => {
+                        NetworkMessageC2S::RightClickBlockAt(
+                            block_position,
+                            face,
+                            shifting,
+                            placement::get_face_center(block_position, face),
+                        )
+                    }
+                    message => message,
+                };
                 match message {
                     NetworkMessageC2S::Keyboard(key, key_mod, pressed, _repeat) => {
                         let mut keyboard_event: HashMap<ImmutableString, Variant> = HashMap::new();
@@ -1636,6 +4320,22 @@ impl Entity {
                             }
                         }
                     }
+                    NetworkMessageC2S::KeybindAction(action, pressed) => {
+                        //clients can only trigger actions that some mod registered
+                        if !Identifier::parse(action.as_str())
+                            .map_or(false, |id| self.server.keybinds.contains_key(&id))
+                        {
+                            continue;
+                        }
+                        let mut keybind_event: HashMap<ImmutableString, Variant> = HashMap::new();
+                        keybind_event.insert("action".into(), Variant::from_str(action.as_str()));
+                        keybind_event.insert("pressed".into(), pressed.into_variant());
+                        keybind_event.insert("player".into(), player.ptr().into_variant());
+                        self.server.call_event(
+                            Identifier::new("bb", "keybind"),
+                            Arc::new(Mutex::new(keybind_event)).into_variant(),
+                        );
+                    }
                     NetworkMessageC2S::GuiClick(element, button, shifting) => {
                         let ui = player
                             .open_guis
@@ -1675,20 +4375,63 @@ impl Entity {
                             }
                         }
                     }
+                    NetworkMessageC2S::GuiTextChanged(element, text) => {
+                        let ui = player
+                            .open_guis
+                            .lock()
+                            .iter()
+                            .find(|(id, _)| element.starts_with(id.to_string().as_str()))
+                            .map(|(id, inventory)| (id.clone(), inventory.clone()));
+                        if let Some((id, inventory)) = ui {
+                            let string_id = id.to_string();
+                            inventory.get_inventory().on_text_changed(
+                                GuiKey {
+                                    player: player.clone(),
+                                    id: id.clone(),
+                                },
+                                element[string_id.len()..].trim_start_matches('_'),
+                                text,
+                            );
+                        }
+                    }
+                    NetworkMessageC2S::GuiVisibleRange(element, first, end) => {
+                        let ui = player
+                            .open_guis
+                            .lock()
+                            .iter()
+                            .find(|(id, _)| element.starts_with(id.to_string().as_str()))
+                            .map(|(id, inventory)| (id.clone(), inventory.clone()));
+                        if let Some((id, inventory)) = ui {
+                            let string_id = id.to_string();
+                            inventory.get_inventory().on_visible_range(
+                                GuiKey {
+                                    player: player.clone(),
+                                    id: id.clone(),
+                                },
+                                element[string_id.len()..].trim_start_matches('_'),
+                                first,
+                                end,
+                            );
+                        }
+                    }
                     NetworkMessageC2S::PlayerPosition(position, shift, rotation, moved) => {
                         let world = { self.location.lock().chunk.world.clone() };
                         self.move_to(&Location { position, world }, Some((rotation, shift)));
-                        self.animation_controller
-                            .lock()
-                            .set_animation(Some(if moved { 2 } else { 1 }));
+                        self.animation_controller.lock().set_movement_animation(
+                            if moved { "walking" } else { "idle" }.to_string(),
+                        );
                     }
                     NetworkMessageC2S::RequestBlockBreakTime(id, position) => {
                         let world = { self.location.lock().chunk.world.clone() };
+                        if !world.is_inside_border(position) {
+                            continue;
+                        }
+                        let block = world.get_block_load(position).get_block_state();
                         let block_break_time = (*f64::from_variant(
                             &world
                                 .server
                                 .block_registry
-                                .state_by_ref(world.get_block_load(position).get_block_state())
+                                .state_by_ref(block)
                                 .parent
                                 .static_data
                                 .get_function("on_left_click")
@@ -1707,6 +4450,7 @@ impl Entity {
                         )
                         .unwrap_or(&-1.));
                         if block_break_time >= 0. {
+                            player.start_block_breaking(position, block, block_break_time as f32);
                             player.send_message(&NetworkMessageS2C::BlockBreakTimeResponse(
                                 id,
                                 block_break_time as f32,
@@ -1715,6 +4459,36 @@ impl Entity {
                     }
                     NetworkMessageC2S::BreakBlock(block_position) => {
                         let world = &self.get_location().chunk.world;
+                        let block_data = world.get_block_load(block_position);
+                        let block = block_data.get_block_state();
+                        if !world.is_inside_border(block_position)
+                            || !player.finish_block_breaking(block_position, block)
+                        {
+                            //the client already removed the block, so it gets the real one back
+                            player.send_message(&NetworkMessageS2C::SetBlock(
+                                block_position,
+                                block_data.get_client_id(),
+                            ));
+                            continue;
+                        }
+                        let experience = self
+                            .server
+                            .block_registry
+                            .state_by_ref(block)
+                            .parent
+                            .static_data
+                            .get("experience")
+                            .and_then(|experience| i64::from_variant(experience).cloned())
+                            .unwrap_or(0);
+                        if experience > 0 {
+                            self.server.drop_experience(
+                                &Location {
+                                    position: block_position.to_position().add(0.5, 0.5, 0.5),
+                                    world: world.clone(),
+                                },
+                                experience as u32,
+                            );
+                        }
                         world.set_block(
                             block_position,
                             BlockStateRef::AIR,
@@ -1722,7 +4496,26 @@ impl Entity {
                             self.get_player().unwrap().into_variant(),
                         );
                     }
-                    NetworkMessageC2S::RightClickBlock(block_position, face, shifting) => {
+                    NetworkMessageC2S::RightClickBlockAt(block_position, face, shifting, hit) => {
+                        let hit = Position {
+                            x: hit
+                                .x
+                                .clamp(block_position.x as f64, block_position.x as f64 + 1.),
+                            y: hit
+                                .y
+                                .clamp(block_position.y as f64, block_position.y as f64 + 1.),
+                            z: hit
+                                .z
+                                .clamp(block_position.z as f64, block_position.z as f64 + 1.),
+                        };
+                        if !self
+                            .get_location()
+                            .chunk
+                            .world
+                            .is_inside_border(block_position.offset_by_face(face))
+                        {
+                            continue;
+                        }
                         let hand_slot = *self.slot.lock();
                         let block = self
                             .get_location()
@@ -1755,45 +4548,127 @@ impl Entity {
                         if right_click_result == InteractionResult::Consumed {
                             continue;
                         }
-                        self.inventory
-                            .get_full_view()
-                            .modify_item(hand_slot, |stack| {
-                                if let Some(stack) = stack {
-                                    right_click_result =
+                        for (slot, hand) in [
+                            (Some(hand_slot), Hand::MainHand),
+                            (self.entity_type.offhand_slot, Hand::OffHand),
+                        ] {
+                            let slot = match slot {
+                                Some(slot) if right_click_result == InteractionResult::Ignored => {
+                                    slot
+                                }
+                                _ => continue,
+                            };
+                            if self.is_slot_on_cooldown(slot) {
+                                continue;
+                            }
+                            self.inventory
+                                .get_full_view()
+                                .modify_item(slot, |stack| {
+                                    if let Some(stack) = stack {
+                                        right_click_result =
+                                            stack.item_type.clone().on_right_click_block(
+                                                stack,
+                                                player.clone(),
+                                                BlockLocation {
+                                                    position: block_position,
+                                                    world: player
+                                                        .get_entity()
+                                                        .get_location()
+                                                        .chunk
+                                                        .world
+                                                        .clone(),
+                                                },
+                                                face,
+                                                hit,
+                                                hand,
+                                            );
+                                    }
+                                })
+                                .unwrap();
+                        }
+                    }
+                    NetworkMessageC2S::PlaceBlocks(positions, face) => {
+                        let reach = match player.get_build_assist() {
+                            Some(reach) => reach as f64 + 2.,
+                            None => continue,
+                        };
+                        if positions.len() > MAX_PLACE_BATCH {
+                            continue;
+                        }
+                        let location = self.get_location();
+                        let world = location.chunk.world.clone();
+                        let hand_slot = *self.slot.lock();
+                        for position in positions {
+                            if location.position.distance(&Position {
+                                x: position.x as f64 + 0.5,
+                                y: position.y as f64 + 0.5,
+                                z: position.z as f64 + 0.5,
+                            }) > reach
+                                || !world.is_inside_border(position)
+                                || !world
+                                    .get_block(&position)
+                                    .map_or(false, |block| block.is_air())
+                            {
+                                continue;
+                            }
+                            self.inventory
+                                .get_full_view()
+                                .modify_item(hand_slot, |stack| {
+                                    if let Some(stack) = stack {
                                         stack.item_type.clone().on_right_click_block(
                                             stack,
                                             player.clone(),
                                             BlockLocation {
-                                                position: block_position,
-                                                world: player
-                                                    .get_entity()
-                                                    .get_location()
-                                                    .chunk
-                                                    .world
-                                                    .clone(),
+                                                position: position.offset_by_face(face.opposite()),
+                                                world: world.clone(),
                                             },
                                             face,
+                                            placement::get_face_center(
+                                                position.offset_by_face(face.opposite()),
+                                                face,
+                                            ),
+                                            Hand::MainHand,
                                         );
-                                }
-                            })
-                            .unwrap();
+                                    }
+                                })
+                                .unwrap();
+                        }
                     }
                     NetworkMessageC2S::RightClick(_shifting) => {
                         let hand_slot = *self.slot.lock();
                         let mut right_click_result = InteractionResult::Ignored;
-                        self.inventory
-                            .get_full_view()
-                            .modify_item(hand_slot, |stack| {
-                                if let Some(stack) = stack {
-                                    //todo: send shifting state
-                                    right_click_result = stack.item_type.clone().on_right_click(
-                                        stack,
-                                        player.ptr(),
-                                        None,
-                                    );
+                        for (slot, hand) in [
+                            (Some(hand_slot), Hand::MainHand),
+                            (self.entity_type.offhand_slot, Hand::OffHand),
+                        ] {
+                            let slot = match slot {
+                                Some(slot) if right_click_result == InteractionResult::Ignored => {
+                                    slot
                                 }
-                            })
-                            .unwrap();
+                                _ => continue,
+                            };
+                            if self.is_slot_on_cooldown(slot) {
+                                continue;
+                            }
+                            self.inventory
+                                .get_full_view()
+                                .modify_item(slot, |stack| {
+                                    if let Some(stack) = stack {
+                                        //todo: send shifting state
+                                        right_click_result = stack
+                                            .item_type
+                                            .clone()
+                                            .on_right_click(stack, player.ptr(), None, hand);
+                                    }
+                                })
+                                .unwrap();
+                        }
+                    }
+                    NetworkMessageC2S::SwapHands => {
+                        self.swap_hands();
+                    }
+                    NetworkMessageC2S::AbortBlockBreak => {
+                        player.cancel_block_breaking();
                     }
                     NetworkMessageC2S::LeftClickEntity(client_id) => {
                         let location = self.get_location();
@@ -1831,24 +4706,325 @@ impl Entity {
                             }
                         }
                     }
-                    NetworkMessageC2S::MouseScroll(_scroll_x, scroll_y) => {
-                        let new_slot = (*self.slot.lock() as i32 - scroll_y).rem_euclid(9);
+                    NetworkMessageC2S::Pong(id) => {
+                        player.on_pong(id);
+                    }
+                    NetworkMessageC2S::Snapshot(id, data) => {
+                        player.on_snapshot(id, data);
+                    }
+                    NetworkMessageC2S::ChangeHandSlot(offset) => {
+                        let new_slot = (*self.slot.lock() as i32 + offset).rem_euclid(9);
                         self.set_hand_slot(new_slot as u32);
                     }
                     NetworkMessageC2S::SendMessage(message) => {
                         if message.starts_with("/") {
-                            /*let message = &message[1..].trim_end();
-                            let parts: rhai::Array = message
-                                .split(" ")
-                                .map(|str| Dynamic::from_str(str).unwrap())
-                                .collect();
-                            let mut event_data = rhai::Map::new();
-                            event_data.insert("player".into(), Dynamic::from(player.clone()));
-                            event_data.insert("command".into(), parts.into());
-                            let _ = self.server.call_event(
-                                Identifier::new("bb", "command"),
-                                Dynamic::from(event_data),
-                            );*/
+                            if player.has_permission("bb.command.clipboard") {
+                                let location = self.get_location();
+                                let command: Vec<&str> = message[1..].split_whitespace().collect();
+                                let result = player.clipboard.lock().run_command(
+                                    &location.chunk.world,
+                                    location.position.to_block_pos(),
+                                    &command,
+                                );
+                                if let Some(result) = result {
+                                    player.send_chat_message(match result {
+                                        Ok(message) => message,
+                                        Err(error) => format!("error: {}", error),
+                                    });
+                                    continue;
+                                }
+                            }
+                            let command: Vec<&str> = message[1..].split_whitespace().collect();
+                            if command.first() == Some(&"list") {
+                                let players: Vec<_> = self
+                                    .server
+                                    .players
+                                    .lock()
+                                    .iter()
+                                    .map(|player| match player.get_ping() {
+                                        Some(ping) => format!(
+                                            "{} ({}ms)",
+                                            player.identity.username,
+                                            ping.as_millis()
+                                        ),
+                                        None => player.identity.username.clone(),
+                                    })
+                                    .collect();
+                                player.send_chat_message(format!(
+                                    "{} players online: {}",
+                                    players.len(),
+                                    players.join(", ")
+                                ));
+                                continue;
+                            }
+                            if command.first() == Some(&"tps") {
+                                if !player.has_permission("bb.command.tps") {
+                                    player.send_chat_message(
+                                        "error: you don't have permission to use this command"
+                                            .to_string(),
+                                    );
+                                    continue;
+                                }
+                                let world = &self.get_location().chunk.world;
+                                player.send_chat_message(format!(
+                                    "{}, worldgen queued: {}, worldgen avg: {:.2}ms",
+                                    self.server.metrics.get_summary(),
+                                    world.generation_queue.get_queued(),
+                                    world
+                                        .generation_queue
+                                        .get_average_generation_time()
+                                        .as_micros() as f64
+                                        / 1000.
+                                ));
+                                continue;
+                            }
+                            if command.first() == Some(&"stop")
+                                || command.first() == Some(&"restart")
+                            {
+                                let result = if !player
+                                    .has_permission(&format!("bb.command.{}", command[0]))
+                                {
+                                    Err("you don't have permission to use this command".to_string())
+                                } else {
+                                    self.server.shutdown.run_command(&self.server, &command)
+                                };
+                                player.send_chat_message(match result {
+                                    Ok(message) => message,
+                                    Err(error) => format!("error: {}", error),
+                                });
+                                continue;
+                            }
+                            if command.first() == Some(&"reload") {
+                                let result = if !player.has_permission("bb.command.reload") {
+                                    Err("you don't have permission to use this command".to_string())
+                                } else {
+                                    self.server.reload()
+                                };
+                                player.send_chat_message(match result {
+                                    Ok(message) => message,
+                                    Err(error) => format!("error: {}", error),
+                                });
+                                continue;
+                            }
+                            if command.first() == Some(&"profile") {
+                                let result = if !player.has_permission("bb.command.profile") {
+                                    Err("you don't have permission to use this command".to_string())
+                                } else {
+                                    match command.get(1).map(|ticks| ticks.parse::<u32>()) {
+                                        Some(Ok(ticks)) if ticks > 0 => self
+                                            .server
+                                            .metrics
+                                            .start_trace(ticks)
+                                            .map(|file_name| {
+                                                format!(
+                                                    "profiling {} ticks into {}",
+                                                    ticks.min(Metrics::MAX_TRACE_TICKS),
+                                                    file_name
+                                                )
+                                            })
+                                            .map_err(|_| {
+                                                "a profile is already running".to_string()
+                                            }),
+                                        _ => Err("usage: /profile <ticks>".to_string()),
+                                    }
+                                };
+                                player.send_chat_message(match result {
+                                    Ok(message) => message,
+                                    Err(error) => format!("error: {}", error),
+                                });
+                                continue;
+                            }
+                            if command.first() == Some(&"search") {
+                                player.send_chat_message(
+                                    self.server.search_index.run_command(&command[1..]),
+                                );
+                                continue;
+                            }
+                            if command.first() == Some(&"give") {
+                                let result = if !player.has_permission("bb.command.give") {
+                                    Err("you don't have permission to use this command".to_string())
+                                } else {
+                                    self.give_command(&command[1..])
+                                };
+                                player.send_chat_message(match result {
+                                    Ok(message) => message,
+                                    Err(error) => format!("error: {}", error),
+                                });
+                                continue;
+                            }
+                            if command.first() == Some(&"kit") {
+                                let arguments = &command[1..];
+                                let result = if !matches!(arguments, [] | ["list"])
+                                    && !player.has_permission("bb.command.kit")
+                                {
+                                    Err("you don't have permission to use this command".to_string())
+                                } else {
+                                    self.server
+                                        .kits
+                                        .run_command(&self.server, &player, arguments)
+                                };
+                                player.send_chat_message(match result {
+                                    Ok(message) => message,
+                                    Err(error) => format!("error: {}", error),
+                                });
+                                continue;
+                            }
+                            if command.first() == Some(&"snapshot") {
+                                let result = if !player.has_permission("bb.command.snapshot") {
+                                    Err("you don't have permission to use this command".to_string())
+                                } else {
+                                    match command[1..] {
+                                        [username] | [username, _] => {
+                                            let max_size = match command.get(2) {
+                                                Some(size) => size
+                                                    .parse()
+                                                    .map_err(|_| format!("invalid size {}", size)),
+                                                None => Ok(256),
+                                            };
+                                            max_size.and_then(|max_size| {
+                                                let target = self
+                                                    .server
+                                                    .players
+                                                    .lock()
+                                                    .iter()
+                                                    .find(|player| {
+                                                        player.identity.username == username
+                                                    })
+                                                    .cloned()
+                                                    .ok_or_else(|| {
+                                                        format!("player {} is not online", username)
+                                                    })?;
+                                                if target.request_snapshot(
+                                                    max_size,
+                                                    SnapshotRequester::Player(Arc::downgrade(
+                                                        &player,
+                                                    )),
+                                                ) {
+                                                    logging::info(
+                                                        LogTarget::Server,
+                                                        format!(
+                                                            "{} requested a snapshot of {}",
+                                                            player.identity.username, username
+                                                        ),
+                                                    );
+                                                    Ok(format!(
+                                                        "requested a snapshot of {}",
+                                                        username
+                                                    ))
+                                                } else {
+                                                    Err(format!(
+                                                        "the client of {} can't take snapshots",
+                                                        username
+                                                    ))
+                                                }
+                                            })
+                                        }
+                                        _ => Err("usage: /snapshot <player> [size]".to_string()),
+                                    }
+                                };
+                                player.send_chat_message(match result {
+                                    Ok(message) => message,
+                                    Err(error) => format!("error: {}", error),
+                                });
+                                continue;
+                            }
+                            if command.first() == Some(&"record") {
+                                let result = if !player.has_permission("bb.command.record") {
+                                    Err("you don't have permission to use this command".to_string())
+                                } else {
+                                    recording::run_command(&self.server, &command[1..])
+                                };
+                                player.send_chat_message(match result {
+                                    Ok(message) => message,
+                                    Err(error) => format!("error: {}", error),
+                                });
+                                continue;
+                            }
+                            if command.first() == Some(&"scripttrace") {
+                                let result = if !player.has_permission("bb.command.scripttrace") {
+                                    Err("you don't have permission to use this command".to_string())
+                                } else {
+                                    self.server.event_tracer.run_command(&command[1..])
+                                };
+                                player.send_chat_message(match result {
+                                    Ok(message) => message,
+                                    Err(error) => format!("error: {}", error),
+                                });
+                                continue;
+                            }
+                            if command.first() == Some(&"debugshapes") {
+                                let result = if !player.has_permission("bb.command.debugshapes") {
+                                    Err("you don't have permission to use this command".to_string())
+                                } else {
+                                    debug_shapes::run_command(&player, &command[1..])
+                                };
+                                player.send_chat_message(match result {
+                                    Ok(message) => message,
+                                    Err(error) => format!("error: {}", error),
+                                });
+                                continue;
+                            }
+                            if command.first() == Some(&"setworldspawn") {
+                                let world = self.get_location().chunk.world.clone();
+                                player.send_chat_message(
+                                    if !player.has_permission("bb.command.setworldspawn") {
+                                        "error: you don't have permission to use this command"
+                                            .to_string()
+                                    } else {
+                                        let position = self.get_location().position;
+                                        world.set_spawn_point(position);
+                                        format!(
+                                            "spawn point of {} set to {:.1} {:.1} {:.1}",
+                                            world.id, position.x, position.y, position.z
+                                        )
+                                    },
+                                );
+                                continue;
+                            }
+                            if command.first() == Some(&"gamerule") {
+                                let result = if command.len() > 2
+                                    && !player.has_permission("bb.command.gamerule")
+                                {
+                                    Err("you don't have permission to use this command".to_string())
+                                } else {
+                                    self.server.game_rules.run_command(
+                                        &self.get_location().chunk.world.gamerules,
+                                        &command[1..],
+                                    )
+                                };
+                                player.send_chat_message(match result {
+                                    Ok(message) => message,
+                                    Err(error) => format!("error: {}", error),
+                                });
+                                continue;
+                            }
+                            if command.first() == Some(&"perm") {
+                                let result = if !player.has_permission("bb.command.perm") {
+                                    Err("you don't have permission to use this command".to_string())
+                                } else {
+                                    self.server
+                                        .permissions
+                                        .run_command(&self.server, &command[1..])
+                                };
+                                player.send_chat_message(match result {
+                                    Ok(message) => message,
+                                    Err(error) => format!("error: {}", error),
+                                });
+                                continue;
+                            }
+                            if let Some(result) =
+                                self.server
+                                    .get_commands()
+                                    .run(&self.server, &player, &command)
+                            {
+                                match result {
+                                    Ok(message) if message.is_empty() => {}
+                                    Ok(message) => player.send_chat_message(message),
+                                    Err(error) => {
+                                        player.send_chat_message(format!("error: {}", error))
+                                    }
+                                }
+                            }
                         }
                     }
                     _ => {}
@@ -1856,6 +5032,68 @@ impl Entity {
             }
         }
     }
+    //unknown ids are completed through the search index when there is only one match
+    fn give_command(&self, command: &[&str]) -> Result<String, String> {
+        let query = command
+            .first()
+            .ok_or_else(|| "usage: /give <item> [count]".to_string())?;
+        let count = match command.get(1) {
+            Some(count) => count
+                .parse::<u32>()
+                .map_err(|_| format!("invalid count {}", count))?,
+            None => 1,
+        };
+        let item = match Identifier::parse(*query)
+            .ok()
+            .and_then(|id| self.server.item_registry.item_by_identifier(&id))
+        {
+            Some(item) => item.clone(),
+            None => {
+                let results =
+                    self.server
+                        .search_index
+                        .search(format!("@item {}", query).as_str(), 0, 5);
+                match results.ids.as_slice() {
+                    [id] => self
+                        .server
+                        .item_registry
+                        .item_by_identifier(id)
+                        .unwrap()
+                        .clone(),
+                    [] => return Err(format!("unknown item {}", query)),
+                    ids => {
+                        return Err(format!(
+                            "{} is ambiguous, {} matches: {}{}",
+                            query,
+                            results.total,
+                            ids.iter()
+                                .map(|id| id.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                            if results.total > ids.len() {
+                                ", ..."
+                            } else {
+                                ""
+                            }
+                        ))
+                    }
+                }
+            }
+        };
+        let mut remaining = count;
+        while remaining > 0 {
+            let stack = ItemStack::new(&item, remaining);
+            if stack.get_count() == 0 {
+                break;
+            }
+            remaining -= stack.get_count();
+            if let Some(rest) = self.inventory.get_full_view().add_item(&stack) {
+                self.server
+                    .drop_item(&(&self.get_location()).into(), rest)?;
+            }
+        }
+        Ok(format!("gave {} {}", count, item.id))
+    }
     pub fn on_attack(&self, player: &Entity) {
         self.entity_type
             .static_data
@@ -1882,6 +5120,170 @@ impl Entity {
         let inventory = self.inventory.get_full_view();
         inventory.get_item(*self.slot.lock()).ok().flatten()
     }
+    fn tick_burning(&self) {
+        let location = self.get_location();
+        let world = &location.chunk.world;
+        if (world.get_time() + self.client_id as u64) % Self::BURN_INTERVAL != 0
+            || !fire::is_fire(world, location.position.to_block_pos())
+        {
+            return;
+        }
+        let mut event_data: HashMap<ImmutableString, Variant> = HashMap::new();
+        event_data.insert("entity".into(), self.ptr().into_variant());
+        event_data.insert("damage".into(), Self::BURN_DAMAGE.into_variant());
+        self.server.call_event(
+            Identifier::new("bb", "entity_burn"),
+            Arc::new(Mutex::new(event_data)).into_variant(),
+        );
+        self.play_sound_event("hurt");
+    }
+    fn get_portal_at(
+        &self,
+        world: &World,
+        position: &BlockPosition,
+    ) -> Option<(Portal, BlockStateRef)> {
+        world.get_block(position).and_then(|block| {
+            self.server
+                .block_registry
+                .state_by_ref(block.get_block_state())
+                .parent
+                .portal
+                .clone()
+                .map(|portal| (portal, block.get_block_state()))
+        })
+    }
+    fn tick_portal(&self) {
+        let location = self.get_location();
+        let world = &location.chunk.world;
+        self.place_return_portal(world);
+        let portal = self.get_portal_at(world, &location.position.to_block_pos());
+        //an entity has to step out of the portal it arrived in before portals take it again
+        if self.portal_cooldown.load(Ordering::Relaxed) > 0 {
+            if portal.is_some() {
+                self.portal_cooldown
+                    .store(Self::PORTAL_COOLDOWN, Ordering::Relaxed);
+            } else {
+                self.portal_cooldown.fetch_sub(1, Ordering::Relaxed);
+            }
+            return;
+        }
+        let (portal, block) = match portal {
+            Some(portal) => portal,
+            None => return,
+        };
+        if let Some(destination) = portal.get_destination(world, location.position) {
+            let world_id = destination.world.id.clone();
+            let position = destination.position.to_block_pos();
+            self.teleport_world(destination.world, destination.position);
+            if portal.return_world.is_some() {
+                *self.return_portal.lock() = Some((world_id, position, block));
+            }
+        }
+    }
+    //waits until the chunk at the destination is generated, so generation doesn't overwrite the portal
+    fn place_return_portal(&self, world: &World) {
+        let mut return_portal = self.return_portal.lock();
+        let (world_id, position, block) = match &*return_portal {
+            Some(return_portal) => return_portal.clone(),
+            None => return,
+        };
+        if world.id != world_id {
+            *return_portal = None;
+            return;
+        }
+        match world.get_chunk(position.to_chunk_pos()) {
+            Some(chunk) if chunk.loading_stage.load(Ordering::SeqCst) >= 2 => {}
+            _ => return,
+        }
+        *return_portal = None;
+        if self.get_portal_at(world, &position).is_none() {
+            world.set_block(position, block, true, Variant::NULL());
+        }
+    }
+    pub fn teleport_world(&self, world: Arc<World>, position: Position) {
+        if let Some(player) = self.get_player() {
+            player.cancel_block_breaking();
+        }
+        *self.return_portal.lock() = None;
+        self.portal_cooldown
+            .store(Self::PORTAL_COOLDOWN, Ordering::Relaxed);
+        let position = world.get_border().clamp(position);
+        self.teleport(&Location { position, world }, None);
+    }
+    pub fn play_sound_event(&self, state: &str) {
+        let sound = match self.entity_type.sounds.get(state) {
+            Some(sound) => sound,
+            None => return,
+        };
+        let location = self.get_location();
+        location
+            .chunk
+            .announce_to_viewers(&NetworkMessageS2C::PlaySound(
+                sound.clone(),
+                location.position,
+                1.,
+                thread_rng().gen_range(0.9..1.1),
+                false,
+                SoundCategory::Creature,
+            ));
+    }
+    pub fn pickup(&self, collector: &Entity) {
+        self.get_location()
+            .chunk
+            .announce_to_viewers(&NetworkMessageS2C::PickupEntity(
+                self.client_id,
+                collector.client_id,
+            ));
+        self.remove();
+    }
+    //ambient entities disappear when no player is near them, checked once a second
+    fn should_despawn(&self) -> bool {
+        let distance = match self.entity_type.despawn_distance {
+            Some(distance) => distance,
+            None => return false,
+        };
+        let location = self.get_location();
+        if (location.chunk.world.get_time() + self.client_id as u64) % 20 != 0 {
+            return false;
+        }
+        !self.server.players.lock().iter().any(|player| {
+            let player_location = player.get_entity().get_location();
+            Arc::ptr_eq(&player_location.chunk.world, &location.chunk.world)
+                && player_location.position.distance(&location.position) <= distance
+        })
+    }
+    pub fn kill(&self) {
+        self.play_sound_event("death");
+        let experience = self
+            .entity_type
+            .static_data
+            .get("experience")
+            .and_then(|experience| i64::from_variant(experience).cloned())
+            .unwrap_or(0);
+        if experience > 0 {
+            self.server
+                .drop_experience(&(&self.get_location()).into(), experience as u32);
+        }
+        self.remove();
+    }
+    fn tick_experience_pickup(&self) {
+        let location = self.get_location();
+        for chunk in location
+            .chunk
+            .world
+            .get_chunks_with_center_radius(location.chunk.position, 1)
+        {
+            for entity in chunk.entities.lock().iter() {
+                if let Some(player) = entity.get_player() {
+                    if entity.get_location().position.distance(&location.position) <= 1.5 {
+                        player.add_experience(self.experience.swap(0, Ordering::Relaxed) as i64);
+                        self.remove();
+                        return;
+                    }
+                }
+            }
+        }
+    }
     pub fn remove(&self) {
         self.removed
             .store(true, std::sync::atomic::Ordering::Relaxed)
@@ -1891,13 +5293,58 @@ impl Entity {
             .map(|player| player.connection.lock().is_closed())
             .unwrap_or(self.removed.load(std::sync::atomic::Ordering::Relaxed))
     }
-    pub fn post_remove(&self) {}
+    pub fn post_remove(&self) {
+        if self.entity_type.schedule.is_some() {
+            BlockClaims::of(&self.get_location().chunk.world).release_all(self.id);
+        }
+        self.update_detected_blocks(None);
+    }
+    pub fn unload_detectors(&self) {
+        if let Some((world, blocks)) = self.detected_blocks.lock().take() {
+            detector::on_unload(&world, blocks);
+        }
+    }
+    //enter and leave callbacks of detector blocks, driven by the entity so blocks never scan for entities
+    fn tick_detectors(&self) {
+        let detected = if self.is_spectator() {
+            None
+        } else {
+            let world = self.get_location().chunk.world.clone();
+            let blocks =
+                detector::detect(&world, &self.get_collider(), self.get_player().is_some());
+            Some((world, blocks))
+        };
+        self.update_detected_blocks(detected);
+    }
+    fn update_detected_blocks(&self, detected: Option<(Arc<World>, HashSet<BlockPosition>)>) {
+        let previous = self.detected_blocks.lock().take();
+        let entity = self.ptr();
+        //blocks in another world are never shared, changing worlds leaves everything
+        let blocks_in =
+            |world: &Arc<World>, other: &Option<(Arc<World>, HashSet<BlockPosition>)>| {
+                other
+                    .as_ref()
+                    .filter(|(other_world, _)| Arc::ptr_eq(world, other_world))
+                    .map(|(_, blocks)| blocks.clone())
+                    .unwrap_or_default()
+            };
+        if let Some((world, blocks)) = &previous {
+            for position in blocks.difference(&blocks_in(world, &detected)) {
+                detector::on_leave(world, *position, &entity);
+            }
+        }
+        if let Some((world, blocks)) = &detected {
+            for position in blocks.difference(&blocks_in(world, &previous)) {
+                detector::on_enter(world, *position, &entity);
+            }
+        }
+        *self.detected_blocks.lock() = detected.filter(|(_, blocks)| !blocks.is_empty());
+    }
     pub fn sync_main_hand_viewmodel(&self, item: Option<&ItemStack>) {
         if let Some(player) = self.get_player() {
             player.send_message(&NetworkMessageS2C::ModelItem(
                 ClientModelTarget::ViewModel,
-                0,
-                item.map(|item| item.item_type.client_id),
+                vec![(0, item.map(|item| item.item_type.client_id))],
             ));
             if item.is_some() {
                 player.send_message(&NetworkMessageS2C::ModelAnimation(
@@ -1910,13 +5357,83 @@ impl Entity {
                     1.,
                     1.,
                     false,
+                    SoundCategory::Player,
                 ));
             }
         }
     }
+    pub fn sync_offhand_viewmodel(&self, item: Option<&ItemStack>) {
+        let slot = self
+            .entity_type
+            .client_data
+            .viewmodel
+            .as_ref()
+            .and_then(|viewmodel| viewmodel.2.iter().position(|slot| slot == "offhand"));
+        if let (Some(player), Some(slot)) = (self.get_player(), slot) {
+            player.send_message(&NetworkMessageS2C::ModelItem(
+                ClientModelTarget::ViewModel,
+                vec![(slot as u32, item.map(|item| item.item_type.client_id))],
+            ));
+        }
+    }
+    pub fn get_offhand_item(&self) -> Option<ItemStack> {
+        self.entity_type
+            .offhand_slot
+            .and_then(|slot| self.inventory.get_full_view().get_item(slot).ok().flatten())
+    }
+    pub fn swap_hands(&self) {
+        let offhand_slot = match self.entity_type.offhand_slot {
+            Some(slot) => slot,
+            None => return,
+        };
+        let hand_slot = *self.slot.lock();
+        if hand_slot == offhand_slot {
+            return;
+        }
+        let view = self.inventory.get_full_view();
+        let main_hand = view.get_item(hand_slot).unwrap();
+        let offhand = view.get_item(offhand_slot).unwrap();
+        view.set_item(hand_slot, offhand).unwrap();
+        view.set_item(offhand_slot, main_hand).unwrap();
+    }
     pub fn get_direction(&self) -> Direction {
         self.rotation_shifting.lock().0
     }
+    //turns the entity's head, clients with a head bone show the pitch and turn the body only as much as needed
+    pub fn look_at(&self, target: Position) {
+        let collider = self.get_collider();
+        let eye = Position {
+            x: collider.x + collider.w / 2.,
+            y: collider.y + collider.h - 0.15,
+            z: collider.z + collider.d / 2.,
+        };
+        let x = target.x - eye.x;
+        let y = target.y - eye.y;
+        let z = target.z - eye.z;
+        if x == 0. && y == 0. && z == 0. {
+            return;
+        }
+        self.rotation_shifting.lock().0 = Direction {
+            pitch: y.atan2((x * x + z * z).sqrt()),
+            yaw: x.atan2(z),
+        };
+    }
+    pub fn set_bone_pose(&self, path: Vec<u32>, pose: Option<BonePose>) {
+        {
+            let mut bone_poses = self.bone_poses.lock();
+            match pose {
+                Some(pose) => bone_poses.insert(path.clone(), pose),
+                None => bone_poses.remove(&path),
+            };
+        }
+        self.get_location()
+            .chunk
+            .announce_to_viewers(&NetworkMessageS2C::ModelBonePose(
+                ClientModelTarget::Entity(self.client_id),
+                path,
+                pose,
+            ));
+    }
     pub fn ptr(&self) -> Arc<Entity> {
         self.this.upgrade().unwrap()
     }
@@ -1960,11 +5477,55 @@ impl ScriptingObject for Entity {
             entity.remove();
             Ok(())
         });
+        env.register_method("pickup", |entity: &Arc<Entity>, collector: &Arc<Entity>| {
+            entity.pickup(collector);
+            Ok(())
+        });
+        env.register_method("kill", |entity: &Arc<Entity>| {
+            entity.kill();
+            Ok(())
+        });
         env.register_member("removed", |entity: &Arc<Entity>| Some(entity.is_removed()));
+        env.register_member("experience", |entity: &Arc<Entity>| {
+            Some(entity.experience.load(Ordering::Relaxed) as i64)
+        });
         env.register_method("knockback", |entity: &Arc<Entity>, position: &Position| {
             entity.apply_knockback(position.x, position.y, position.z);
             Ok(())
         });
+        env.register_method("look_at", |entity: &Arc<Entity>, position: &Position| {
+            entity.look_at(*position);
+            Ok(())
+        });
+        env.register_method(
+            "set_bone_pose",
+            |entity: &Arc<Entity>,
+             path: &Array,
+             position: &Position,
+             rotation: &Position,
+             weight: &f64| {
+                entity.set_bone_pose(
+                    bone_path_from_array(path)?,
+                    Some(BonePose {
+                        position: (*position).into(),
+                        rotation: (*rotation).into(),
+                        weight: weight.clamp(0., 1.) as f32,
+                    }),
+                );
+                Ok(())
+            },
+        );
+        env.register_method("clear_bone_pose", |entity: &Arc<Entity>, path: &Array| {
+            entity.set_bone_pose(bone_path_from_array(path)?, None);
+            Ok(())
+        });
+        env.register_method(
+            "launch",
+            |entity: &Arc<Entity>, direction: &Direction, speed: &f64| {
+                entity.launch(*direction, *speed);
+                Ok(())
+            },
+        );
         env.register_method("teleport", |entity: &Arc<Entity>, location: &Location| {
             entity.teleport(location, None);
             Ok(())
@@ -1984,23 +5545,95 @@ impl ScriptingObject for Entity {
         env.register_member("hand_item", |entity: &Arc<Entity>| {
             Some(Variant::from_option(entity.get_hand_item()))
         });
+        env.register_member("offhand_item", |entity: &Arc<Entity>| {
+            Some(Variant::from_option(entity.get_offhand_item()))
+        });
+        env.register_method("swap_hands", |entity: &Arc<Entity>| {
+            entity.swap_hands();
+            Ok(())
+        });
+        env.register_method(
+            "set_item_cooldown",
+            |entity: &Arc<Entity>, item: &ImmutableString, ticks: &i64| {
+                let id = Identifier::parse(item.as_ref()).map_err(|_| {
+                    ScriptError::runtime(
+                        format!("invalid item id {}", item).as_str(),
+                        FilePosition::INVALID,
+                    )
+                })?;
+                let item = entity
+                    .server
+                    .item_registry
+                    .item_by_identifier(&id)
+                    .ok_or_else(|| ScriptError::runtime("unknown item", FilePosition::INVALID))?;
+                entity.set_item_cooldown(item, (*ticks).max(0) as u32);
+                Ok(())
+            },
+        );
+        env.register_method(
+            "get_item_cooldown",
+            |entity: &Arc<Entity>, item: &ImmutableString| {
+                let id = Identifier::parse(item.as_ref()).map_err(|_| {
+                    ScriptError::runtime(
+                        format!("invalid item id {}", item).as_str(),
+                        FilePosition::INVALID,
+                    )
+                })?;
+                let item = entity
+                    .server
+                    .item_registry
+                    .item_by_identifier(&id)
+                    .ok_or_else(|| ScriptError::runtime("unknown item", FilePosition::INVALID))?;
+                Ok(entity.get_item_cooldown(item) as i64)
+            },
+        );
+        env.register_method(
+            "set_animation_state",
+            |entity: &Arc<Entity>, state: &ImmutableString| {
+                entity
+                    .animation_controller
+                    .lock()
+                    .set_animation(Some(state.to_string()));
+                Ok(())
+            },
+        );
+        env.register_method("clear_animation_state", |entity: &Arc<Entity>| {
+            entity.animation_controller.lock().set_animation(None);
+            Ok(())
+        });
+        env.register_method(
+            "play_sound_event",
+            |entity: &Arc<Entity>, event: &ImmutableString| {
+                entity.play_sound_event(event.as_ref());
+                Ok(())
+            },
+        );
+        env.register_method("get_animation_state", |entity: &Arc<Entity>| {
+            Ok(Variant::from_str(
+                entity.animation_controller.lock().get_animation().as_str(),
+            ))
+        });
     }
 }
 impl Animatable for Entity {
-    fn send_animation_to_viewers(&self, animation: u32) {
+    type Animation = String;
+    fn send_animation_to_viewers(&self, state: &String) {
         self.get_location()
             .chunk
-            .announce_to_viewers(&NetworkMessageS2C::ModelAnimation(
-                ClientModelTarget::Entity(self.client_id),
-                animation,
+            .announce_to_viewers(&NetworkMessageS2C::EntityAnimationState(
+                self.client_id,
+                state.clone(),
             ));
     }
-    fn send_animation_to(&self, viewer: &PlayerData, animation: u32) {
-        viewer.send_message(&NetworkMessageS2C::ModelAnimation(
-            ClientModelTarget::Entity(self.client_id),
-            animation,
+    fn send_animation_to(&self, viewer: &PlayerData, state: &String) {
+        viewer.send_message(&NetworkMessageS2C::EntityAnimationState(
+            self.client_id,
+            state.clone(),
         ));
     }
+    fn on_animation_started(&self, state: &String) {
+        self.play_sound_event(state);
+    }
 }
 pub struct Pathfinder {
     current_location: BlockLocation,
@@ -2107,11 +5740,15 @@ impl Pathfinder {
     pub fn get_required_face(&self) -> Option<Face> {
         self.path.get(0).map(|item| item.1)
     }
+    pub fn get_path(&self) -> Vec<BlockPosition> {
+        self.path.iter().map(|(position, _)| *position).collect()
+    }
 }
 pub struct ChunkLoadingManager {
     server: Arc<Server>,
     player: Weak<PlayerData>,
     to_load: Mutex<HashSet<Arc<Chunk>>>,
+    send_budget: Mutex<usize>,
     old_position: Mutex<ChunkPosition>,
     old_world: Mutex<Arc<World>>,
 }
@@ -2119,6 +5756,7 @@ impl ChunkLoadingManager {
     pub fn new(player: Weak<PlayerData>, server: Arc<Server>, location: Location) -> Self {
         ChunkLoadingManager {
             player,
+            send_budget: Mutex::new(Self::get_max_chunks_per_tick(&server)),
             server,
             to_load: Mutex::new(HashSet::new()),
             old_position: Mutex::new(location.position.to_chunk_pos()),
@@ -2134,51 +5772,65 @@ impl ChunkLoadingManager {
             .unwrap()
             .send_message(&NetworkMessageS2C::UnloadChunk(chunk.position));
     }
+    fn get_max_chunks_per_tick(server: &Server) -> usize {
+        server
+            .settings
+            .get_i64("server.max_chunks_sent_per_tick", 200)
+            .max(1) as usize
+    }
+    //halves the budget while the connection can't keep up and grows it back once it drains
+    fn update_send_budget(&self, player: &PlayerData) -> usize {
+        let max = Self::get_max_chunks_per_tick(&self.server);
+        let mut budget = self.send_budget.lock();
+        *budget = if player.connection.lock().is_backlogged() {
+            *budget / 2
+        } else {
+            *budget * 2
+        }
+        .clamp(1, max);
+        *budget
+    }
+    //nearby chunks go first, chunks behind the player count as up to twice as far away
+    fn get_send_priority(position: ChunkPosition, center: ChunkPosition, facing: Position) -> f64 {
+        let offset = Position {
+            x: (position.x - center.x) as f64,
+            y: (position.y - center.y) as f64,
+            z: (position.z - center.z) as f64,
+        };
+        let distance = (offset.x * offset.x + offset.y * offset.y + offset.z * offset.z).sqrt();
+        if distance == 0. {
+            return 0.;
+        }
+        let alignment =
+            (offset.x * facing.x + offset.y * facing.y + offset.z * facing.z) / distance;
+        distance * (1.5 - alignment * 0.5)
+    }
     pub fn tick(&self) {
-        for chunk in self
-            .to_load
-            .lock()
-            .extract_if(|chunk| chunk.loading_stage.load(Ordering::Relaxed) >= 2)
-            .take(
-                self.server
-                    .settings
-                    .get_i64("server.max_chunks_sent_per_tick", 200) as usize,
-            )
-        {
-            let entity = self.player.upgrade().unwrap();
-            self.server.thread_pool.execute(Box::new(move || {
-                let mut palette = Vec::new();
-                let mut block_data = [[[0; 16]; 16]; 16];
-                {
-                    let blocks = chunk.blocks.lock();
-                    for x in 0..16 {
-                        for y in 0..16 {
-                            for z in 0..16 {
-                                let block_id = blocks[x][y][z].get_client_id();
-                                let palette_entry =
-                                    match palette.iter().position(|block| *block == block_id) {
-                                        Some(entry) => entry,
-                                        None => {
-                                            palette.push(block_id);
-                                            palette.len() - 1
-                                        }
-                                    };
-                                block_data[x][y][z] = palette_entry as u16;
-                            }
-                        }
-                    }
+        let player = self.player.upgrade().unwrap();
+        let budget = self.update_send_budget(&player);
+        let mut ready: Vec<_> = self
+            .to_load
+            .lock()
+            .extract_if(|chunk| chunk.loading_stage.load(Ordering::Relaxed) >= 2)
+            .collect();
+        let center = *self.old_position.lock();
+        let facing = player.get_entity().get_rotation().to_vector();
+        ready.sort_by(|first, second| {
+            Self::get_send_priority(first.position, center, facing)
+                .total_cmp(&Self::get_send_priority(second.position, center, facing))
+        });
+        if ready.len() > budget {
+            self.to_load.lock().extend(ready.drain(budget..));
+        }
+        for chunk in ready {
+            let entity = player.clone();
+            self.server.thread_pool.execute(Box::new(move || {
+                let (palette, block_data) = chunk.serialize_blocks();
+                let encoder = entity.connection.lock().get_chunk_encoder();
+                if encoder.compression == ChunkCompression::Zstd {
+                    entity.server.chunk_dictionary.add_sample(&block_data);
                 }
-                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Compression::default());
-                std::io::copy(
-                    &mut bitcode::serialize(&block_data).unwrap().as_slice(),
-                    &mut encoder,
-                )
-                .unwrap();
-                let load_message = NetworkMessageS2C::LoadChunk(
-                    chunk.position,
-                    palette,
-                    encoder.finish().unwrap(),
-                );
+                let load_message = chunk.create_load_message(palette, &block_data, &encoder);
                 entity.send_message(&load_message);
                 {
                     let blocks = chunk.blocks.lock();
@@ -2197,6 +5849,17 @@ impl ChunkLoadingManager {
             }));
         }
     }
+    //chunks in view that are sent or being sent, the rest is still generating
+    pub fn get_sent_chunks(&self) -> Vec<Arc<Chunk>> {
+        let positions = Self::get_chunks_to_load_at(self, *self.old_position.lock());
+        let world = self.old_world.lock();
+        let to_load = self.to_load.lock();
+        positions
+            .into_iter()
+            .filter_map(|position| world.get_chunk(position))
+            .filter(|chunk| !to_load.contains(chunk))
+            .collect()
+    }
     pub fn unload_chunks(&self) {
         let world = self.old_world.lock();
         for pos in Self::get_chunks_to_load_at(self, *self.old_position.lock()) {
@@ -2293,90 +5956,247 @@ impl Hash for Entity {
 }
 #[extend::ext]
 impl AABB {
-    fn has_block<F>(&self, world: &World, predicate: F) -> bool
-    where
-        F: Fn(&BlockState) -> bool,
-    {
-        self.iter_blocks()
-            .find(|position| {
-                predicate.call((world
-                    .server
-                    .block_registry
-                    .state_by_ref(world.get_block_load(*position).get_block_state()),))
-            })
-            .is_some()
+    fn has_collidable_block(&self, world: &World) -> bool {
+        let mut chunk: Option<Arc<Chunk>> = None;
+        self.iter_blocks().any(|position| {
+            let chunk_position = position.to_chunk_pos();
+            if chunk
+                .as_ref()
+                .map_or(true, |chunk| chunk.position != chunk_position)
+            {
+                chunk = Some(world.load_chunk(chunk_position));
+            }
+            let chunk = chunk.as_ref().unwrap();
+            let offset = position.chunk_offset();
+            chunk.is_collidable_at(offset.0, offset.1, offset.2)
+        })
     }
 }
-pub struct AnimationController<T> {
+pub struct AnimationController<T: Animatable> {
     viewable: Weak<T>,
-    animation: u32,
+    animation: T::Animation,
     animation_start_time: f32,
-    default_animation: u32,
+    default_animation: T::Animation,
+    explicit: bool,
 }
 
 impl<T: Animatable> AnimationController<T> {
-    pub fn new(entity: Weak<T>, default_animation: u32) -> Self {
+    pub fn new(entity: Weak<T>, default_animation: T::Animation) -> Self {
         AnimationController {
             viewable: entity,
-            animation: default_animation,
+            animation: default_animation.clone(),
             animation_start_time: 0., //todo
             default_animation,
+            explicit: false,
+        }
+    }
+    //an explicit animation is kept until it's cleared with None, movement animations don't replace it
+    pub fn set_animation(&mut self, animation: Option<T::Animation>) {
+        self.explicit = animation.is_some();
+        self.apply_animation(animation.unwrap_or(self.default_animation.clone()));
+    }
+    pub fn set_movement_animation(&mut self, animation: T::Animation) {
+        if !self.explicit {
+            self.apply_animation(animation);
         }
     }
-    pub fn set_animation(&mut self, animation: Option<u32>) {
-        let new_animation = animation.unwrap_or(self.default_animation);
+    fn apply_animation(&mut self, new_animation: T::Animation) {
         if self.animation != new_animation {
             self.animation = new_animation;
             self.animation_start_time = 0.; //todo
-            self.viewable
-                .upgrade()
-                .unwrap()
-                .send_animation_to_viewers(self.animation);
+            let viewable = self.viewable.upgrade().unwrap();
+            viewable.send_animation_to_viewers(&self.animation);
+            viewable.on_animation_started(&self.animation);
         }
     }
+    pub fn get_animation(&self) -> &T::Animation {
+        &self.animation
+    }
     pub fn sync_to(&self, viewer: &PlayerData) {
         self.viewable
             .upgrade()
             .unwrap()
-            .send_animation_to(viewer, self.animation);
+            .send_animation_to(viewer, &self.animation);
     }
     pub fn resync(&self) {
         self.viewable
             .upgrade()
             .unwrap()
-            .send_animation_to_viewers(self.animation);
+            .send_animation_to_viewers(&self.animation);
     }
 }
+//blocks are animated by index, entities by the name of a state from their content
 pub trait Animatable {
-    fn send_animation_to_viewers(&self, animation: u32);
-    fn send_animation_to(&self, viewer: &PlayerData, animation: u32);
+    type Animation: Clone + PartialEq;
+    fn send_animation_to_viewers(&self, animation: &Self::Animation);
+    fn send_animation_to(&self, viewer: &PlayerData, animation: &Self::Animation);
+    fn on_animation_started(&self, _animation: &Self::Animation) {}
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StructureTransform {
+    pub rotation: u8,
+    pub mirror: bool,
+}
+impl StructureTransform {
+    pub const IDENTITY: StructureTransform = StructureTransform {
+        rotation: 0,
+        mirror: false,
+    };
+    pub fn new(rotation: i64, mirror: bool) -> Self {
+        StructureTransform {
+            rotation: rotation.rem_euclid(4) as u8,
+            mirror,
+        }
+    }
+    pub fn random<R: Rng>(rng: &mut R) -> Self {
+        Self::new(rng.gen_range(0..4), false)
+    }
+    pub fn all() -> [StructureTransform; 8] {
+        array_init(|i| Self::new(i as i64 % 4, i >= 4))
+    }
+    fn get_index(&self) -> usize {
+        self.rotation as usize + if self.mirror { 4 } else { 0 }
+    }
+    //mirroring flips the x axis and is applied before rotating clockwise around the y axis
+    pub fn transform_position(&self, position: BlockPosition) -> BlockPosition {
+        let mut position = if self.mirror {
+            BlockPosition {
+                x: -position.x,
+                ..position
+            }
+        } else {
+            position
+        };
+        for _ in 0..self.rotation {
+            position = BlockPosition {
+                x: -position.z,
+                y: position.y,
+                z: position.x,
+            };
+        }
+        position
+    }
+    pub fn transform_face(&self, face: Face) -> Face {
+        let offset = self.transform_position(face.get_offset());
+        *Face::all()
+            .iter()
+            .find(|face| face.get_offset() == offset)
+            .unwrap()
+    }
+    fn transform_horizontal(&self, x: f64, z: f64) -> (f64, f64) {
+        let (mut x, mut z) = (if self.mirror { -x } else { x }, z);
+        for _ in 0..self.rotation {
+            (x, z) = (-z, x);
+        }
+        (x, z)
+    }
+    //entities are rotated around the center of the origin block so they stay in the same block as before
+    pub fn transform_entity_position(&self, position: Position) -> Position {
+        let (x, z) = self.transform_horizontal(position.x - 0.5, position.z - 0.5);
+        Position {
+            x: x + 0.5,
+            y: position.y,
+            z: z + 0.5,
+        }
+    }
+    pub fn transform_rotation(&self, rotation: Direction) -> Direction {
+        let yaw = if self.mirror {
+            -rotation.yaw
+        } else {
+            rotation.yaw
+        };
+        Direction {
+            pitch: rotation.pitch,
+            yaw: yaw - self.rotation as f64 * std::f64::consts::FRAC_PI_2,
+        }
+    }
 }
+
 #[derive(Clone)]
 pub struct Structure {
     blocks: Vec<(BlockPosition, (BlockStateRef, f32))>,
+    block_data: HashMap<BlockPosition, BlockSaveData>,
+    entities: Vec<EntitySaveData>,
+    transformed_states: HashMap<u32, [BlockStateRef; 8]>,
 }
 
 impl Structure {
-    pub fn from_json(json: JsonValue, block_registry: &BlockRegistry) -> Self {
+    pub fn from_json(json: JsonValue, block_registry: &BlockRegistry) -> Result<Self, String> {
         let mut blocks = Vec::new();
+        let mut block_data = HashMap::new();
         for block in json["blocks"].members() {
+            let coordinate = |name: &str| {
+                block[name]
+                    .as_i32()
+                    .ok_or_else(|| format!("block is missing {}", name))
+            };
+            let position = BlockPosition {
+                x: coordinate("x")?,
+                y: coordinate("y")?,
+                z: coordinate("z")?,
+            };
+            if !block["data"].is_null() {
+                block_data.insert(
+                    position,
+                    serde_json::from_str(&block["data"].dump())
+                        .map_err(|error| format!("invalid block data: {}", error))?,
+                );
+            }
+            let id = block["id"].as_str().ok_or("block is missing id")?;
             blocks.push((
-                BlockPosition {
-                    x: block["x"].as_i32().unwrap(),
-                    y: block["y"].as_i32().unwrap(),
-                    z: block["z"].as_i32().unwrap(),
-                },
-                {
-                    (
-                        block_registry
-                            .state_from_string(block["id"].as_str().unwrap())
-                            .unwrap(),
-                        block["chance"].as_f32().unwrap_or(1.),
-                    )
-                },
+                position,
+                (
+                    block_registry
+                        .state_from_string(id)
+                        .map_err(|error| format!("invalid block {}: {}", id, error))?,
+                    block["chance"].as_f32().unwrap_or(1.),
+                ),
             ));
         }
-        Structure { blocks }
+        let entities = json["entities"]
+            .members()
+            .map(|entity| {
+                serde_json::from_str(&entity.dump())
+                    .map_err(|error| format!("invalid entity: {}", error))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Structure::new(blocks, block_data, entities, block_registry))
+    }
+    fn new(
+        blocks: Vec<(BlockPosition, (BlockStateRef, f32))>,
+        block_data: HashMap<BlockPosition, BlockSaveData>,
+        entities: Vec<EntitySaveData>,
+        block_registry: &BlockRegistry,
+    ) -> Self {
+        let mut transformed_states = HashMap::new();
+        for (_, (state, _)) in &blocks {
+            if transformed_states.contains_key(&state.get_id()) {
+                continue;
+            }
+            let block_state = block_registry.state_by_ref(*state);
+            let states =
+                StructureTransform::all().map(|transform| block_state.transformed(transform));
+            if states
+                .iter()
+                .all(|transformed| transformed.get_id() == state.get_id())
+            {
+                continue;
+            }
+            //states reachable by transforming need their own entry so transformed structures can be transformed again
+            for transformed in states {
+                let block_state = block_registry.state_by_ref(transformed);
+                transformed_states.insert(
+                    transformed.get_id(),
+                    StructureTransform::all().map(|transform| block_state.transformed(transform)),
+                );
+            }
+        }
+        Structure {
+            blocks,
+            block_data,
+            entities,
+            transformed_states,
+        }
     }
     pub fn from_world(
         world: &World,
@@ -2395,50 +6215,297 @@ impl Structure {
             z: first.z.max(second.z),
         };
         let mut blocks = Vec::new();
+        let mut block_data = HashMap::new();
         for x in fixed_first.x..=fixed_second.x {
             for y in fixed_first.y..=fixed_second.y {
                 for z in fixed_first.z..=fixed_second.z {
                     let block_position = BlockPosition { x, y, z };
                     if let Some(block) = world.get_block(&block_position) {
                         if !block.get_block_state().is_air() {
-                            blocks
-                                .push((block_position.add(-origin), (block.get_block_state(), 1.)));
+                            blocks.push((block_position + -origin, (block.get_block_state(), 1.)));
+                        }
+                        if let BlockData::Data(block) = block {
+                            block_data.insert(block_position + -origin, block.serialize());
+                        }
+                    }
+                }
+            }
+        }
+        let mut entities = Vec::new();
+        let first_chunk = fixed_first.to_chunk_pos();
+        let second_chunk = fixed_second.to_chunk_pos();
+        for x in first_chunk.x..=second_chunk.x {
+            for y in first_chunk.y..=second_chunk.y {
+                for z in first_chunk.z..=second_chunk.z {
+                    let chunk = match world.get_chunk(ChunkPosition { x, y, z }) {
+                        Some(chunk) => chunk,
+                        None => continue,
+                    };
+                    for entity in chunk.entities.lock().iter() {
+                        if entity.is_removed() || entity.get_player().is_some() {
+                            continue;
+                        }
+                        let position = entity.get_location().position;
+                        let block_position = position.to_block_pos();
+                        if block_position.x >= fixed_first.x
+                            && block_position.y >= fixed_first.y
+                            && block_position.z >= fixed_first.z
+                            && block_position.x <= fixed_second.x
+                            && block_position.y <= fixed_second.y
+                            && block_position.z <= fixed_second.z
+                        {
+                            entities.push(entity.to_save_data(position.add(
+                                -origin.x as f64,
+                                -origin.y as f64,
+                                -origin.z as f64,
+                            )));
                         }
                     }
                 }
             }
         }
-        Structure { blocks }
+        Structure::new(blocks, block_data, entities, &world.server.block_registry)
     }
     pub fn export(&self, block_registry: &BlockRegistry) -> JsonValue {
         let mut blocks = Vec::new();
         for (position, block) in &self.blocks {
             let state = block_registry.state_by_ref(block.0);
-            blocks.push(object! {
+            let mut json = object! {
                 x:position.x,
                 y:position.y,
                 z:position.z,
                 id:state.to_string(),
-            });
+            };
+            if let Some(data) = self.block_data.get(position) {
+                json["data"] = json::parse(&serde_json::to_string(data).unwrap()).unwrap();
+            }
+            blocks.push(json);
         }
+        let entities = self
+            .entities
+            .iter()
+            .map(|entity| json::parse(&serde_json::to_string(entity).unwrap()).unwrap())
+            .collect();
         object! {
-            blocks:JsonValue::Array(blocks)
+            blocks:JsonValue::Array(blocks),
+            entities:JsonValue::Array(entities)
         }
     }
-    pub fn place<F>(&self, mut placer: F, position: BlockPosition)
+    pub fn place<F>(&self, placer: F, position: BlockPosition, transform: StructureTransform)
     where
         F: FnMut(BlockPosition, BlockStateRef),
+    {
+        self.place_with_rng(placer, position, transform, &mut rand::thread_rng());
+    }
+    pub fn place_with_rng<F, R>(
+        &self,
+        mut placer: F,
+        position: BlockPosition,
+        transform: StructureTransform,
+        rng: &mut R,
+    ) where
+        F: FnMut(BlockPosition, BlockStateRef),
+        R: Rng,
     {
         for (block_position, block) in &self.blocks {
-            if rand::thread_rng().gen_bool(block.1 as f64) {
-                placer.call_mut((block_position.clone() + position, block.0.clone()));
+            if rng.gen_bool(block.1 as f64) {
+                placer.call_mut((
+                    transform.transform_position(*block_position) + position,
+                    self.transform_state(block.0, transform),
+                ));
+            }
+        }
+    }
+    fn transform_state(
+        &self,
+        state: BlockStateRef,
+        transform: StructureTransform,
+    ) -> BlockStateRef {
+        self.transformed_states
+            .get(&state.get_id())
+            .map(|states| states[transform.get_index()])
+            .unwrap_or(state)
+    }
+    fn transform_entity(
+        &self,
+        entity: &EntitySaveData,
+        transform: StructureTransform,
+    ) -> EntitySaveData {
+        let velocity = transform.transform_horizontal(entity.velocity.0, entity.velocity.2);
+        EntitySaveData {
+            position: transform.transform_entity_position(entity.position),
+            rotation: transform.transform_rotation(entity.rotation),
+            velocity: (velocity.0, entity.velocity.1, velocity.1),
+            ..entity.clone()
+        }
+    }
+    //restores block inventories and spawns entities, optionally only those inside a single chunk
+    pub fn place_data(
+        &self,
+        world: &World,
+        position: BlockPosition,
+        transform: StructureTransform,
+        chunk: Option<ChunkPosition>,
+    ) {
+        for (block_position, data) in &self.block_data {
+            let block_position = transform.transform_position(*block_position) + position;
+            if chunk.map_or(false, |chunk| chunk != block_position.to_chunk_pos()) {
+                continue;
+            }
+            if let Some(BlockData::Data(block)) = world.get_block(&block_position) {
+                block.deserialize(data.clone());
+            }
+        }
+        for entity in &self.entities {
+            let mut entity = self.transform_entity(entity, transform);
+            entity.position = entity.position + position.to_position();
+            let chunk_position = entity.position.to_chunk_pos();
+            if chunk.map_or(false, |chunk| chunk != chunk_position)
+                || !world.is_inside_border(entity.position.to_block_pos())
+            {
+                continue;
+            }
+            world.load_chunk(chunk_position).spawn_saved_entity(entity);
+        }
+    }
+    pub fn has_data(&self) -> bool {
+        !self.block_data.is_empty() || !self.entities.is_empty()
+    }
+    //same as place_data for a chunk that is still being generated and whose blocks aren't set yet
+    pub fn place_generated_data(
+        &self,
+        chunk: &Chunk,
+        blocks: &[[[BlockData; 16]; 16]; 16],
+        position: BlockPosition,
+        transform: StructureTransform,
+    ) {
+        for (block_position, data) in &self.block_data {
+            let block_position = transform.transform_position(*block_position) + position;
+            if block_position.to_chunk_pos() != chunk.position {
+                continue;
+            }
+            let offset = block_position.chunk_offset();
+            if let BlockData::Data(block) =
+                &blocks[offset.0 as usize][offset.1 as usize][offset.2 as usize]
+            {
+                block.deserialize(data.clone());
+            }
+        }
+        for entity in &self.entities {
+            let mut entity = self.transform_entity(entity, transform);
+            entity.position = entity.position + position.to_position();
+            if entity.position.to_chunk_pos() != chunk.position
+                || !chunk.world.is_inside_border(entity.position.to_block_pos())
+            {
+                continue;
             }
+            chunk.spawn_saved_entity(entity);
+        }
+    }
+    pub fn with_transform(&self, transform: StructureTransform) -> Structure {
+        Structure {
+            blocks: self
+                .blocks
+                .iter()
+                .map(|(position, block)| {
+                    (
+                        transform.transform_position(*position),
+                        (self.transform_state(block.0, transform), block.1),
+                    )
+                })
+                .collect(),
+            block_data: self
+                .block_data
+                .iter()
+                .map(|(position, data)| (transform.transform_position(*position), data.clone()))
+                .collect(),
+            entities: self
+                .entities
+                .iter()
+                .map(|entity| self.transform_entity(entity, transform))
+                .collect(),
+            transformed_states: self.transformed_states.clone(),
+        }
+    }
+    pub fn flipped_vertically(&self) -> Structure {
+        let flip = |position: BlockPosition| BlockPosition {
+            y: -position.y,
+            ..position
+        };
+        Structure {
+            blocks: self
+                .blocks
+                .iter()
+                .map(|(position, block)| (flip(*position), *block))
+                .collect(),
+            block_data: self
+                .block_data
+                .iter()
+                .map(|(position, data)| (flip(*position), data.clone()))
+                .collect(),
+            //entities keep their height inside the block they are standing in
+            entities: self
+                .entities
+                .iter()
+                .map(|entity| {
+                    let block_y = entity.position.y.floor();
+                    EntitySaveData {
+                        position: Position {
+                            y: entity.position.y - 2. * block_y,
+                            ..entity.position
+                        },
+                        ..entity.clone()
+                    }
+                })
+                .collect(),
+            transformed_states: self.transformed_states.clone(),
         }
     }
-    pub fn get_chunks(&self, position: BlockPosition) -> HashSet<ChunkPosition> {
+    pub fn get_block_count(&self) -> usize {
+        self.blocks.len()
+    }
+    //the lowest and highest corner of the blocks once placed, None for an empty structure
+    pub fn get_bounds(
+        &self,
+        position: BlockPosition,
+        transform: StructureTransform,
+    ) -> Option<(BlockPosition, BlockPosition)> {
+        self.blocks
+            .iter()
+            .map(|(block_position, _)| transform.transform_position(*block_position) + position)
+            .fold(None, |bounds, block| {
+                Some(match bounds {
+                    Some((min, max)) => (
+                        BlockPosition {
+                            x: block.x.min(min.x),
+                            y: block.y.min(min.y),
+                            z: block.z.min(min.z),
+                        },
+                        BlockPosition {
+                            x: block.x.max(max.x),
+                            y: block.y.max(max.y),
+                            z: block.z.max(max.z),
+                        },
+                    ),
+                    None => (block, block),
+                })
+            })
+    }
+    pub fn get_chunks(
+        &self,
+        position: BlockPosition,
+        transform: StructureTransform,
+    ) -> HashSet<ChunkPosition> {
         let mut chunks = HashSet::new();
         for (block_position, _) in &self.blocks {
-            chunks.insert((block_position.clone() + position).to_chunk_pos());
+            chunks
+                .insert((transform.transform_position(*block_position) + position).to_chunk_pos());
+        }
+        for entity in &self.entities {
+            chunks.insert(
+                (transform.transform_entity_position(entity.position) + position.to_position())
+                    .to_chunk_pos(),
+            );
         }
         chunks
     }
@@ -2467,6 +6534,7 @@ pub struct BlockNetwork {
     id: Identifier,
     pub user_data: Mutex<UserData>,
     members: Mutex<HashSet<BlockLocation>>,
+    power: AtomicU32,
 }
 impl BlockNetwork {
     pub fn new(id: Identifier) -> Arc<Self> {
@@ -2475,8 +6543,18 @@ impl BlockNetwork {
             this: this.clone(),
             user_data: Mutex::new(UserData::new()),
             members: Mutex::new(HashSet::new()),
+            power: AtomicU32::new(0),
         })
     }
+    pub fn add_member(&self, location: BlockLocation) {
+        self.members.lock().insert(location);
+    }
+    pub fn get_power(&self) -> u32 {
+        self.power.load(Ordering::Relaxed)
+    }
+    pub fn set_power(&self, power: u32) {
+        self.power.store(power, Ordering::Relaxed);
+    }
     pub fn merge(&self, other: Arc<BlockNetwork>) {
         assert_eq!(self.id, other.id);
         if Arc::ptr_eq(&self.ptr(), &other) {
@@ -2527,6 +6605,10 @@ pub struct WorldBlock {
     pub user_data: Mutex<UserData>,
     animation_controller: AnimationController<WorldBlock>,
     pub network_controller: Mutex<NetworkController>,
+    bone_poses: Mutex<FxHashMap<Vec<u32>, BonePose>>,
+    transfer_cooldown: AtomicU32,
+    item_transforms: Mutex<FxHashMap<u32, Transformation>>,
+    energy: AtomicU32,
 }
 
 impl WorldBlock {
@@ -2547,11 +6629,16 @@ impl WorldBlock {
                 WeakInventoryWrapper::Block(this.clone()),
                 block.data_container.as_ref().unwrap().0,
                 None,
-            ),
+            )
+            .with_fluid_tanks(&block.fluid_tanks),
             animation_controller: AnimationController::new(this.clone(), 0),
             block,
             user_data: Mutex::new(UserData::new()),
             network_controller: Mutex::new(NetworkController::new()),
+            bone_poses: Mutex::new(FxHashMap::default()),
+            transfer_cooldown: AtomicU32::new(0),
+            item_transforms: Mutex::new(FxHashMap::default()),
+            energy: AtomicU32::new(0),
             this: this.clone(),
         })
     }
@@ -2580,47 +6667,200 @@ impl WorldBlock {
     }
     pub fn on_sent_to_client(&self, player: &PlayerData) {
         self.animation_controller.sync_to(player);
-        for (inventory_index, model_index) in &self.block.item_model_mapping.mapping {
+        let items = self.block.item_model_mapping.get_items(&self.inventory);
+        if !items.is_empty() {
             player.send_message(&NetworkMessageS2C::ModelItem(
                 ClientModelTarget::Block(self.position),
-                *model_index,
-                self.inventory
-                    .get_full_view()
-                    .get_item(*inventory_index)
-                    .unwrap()
-                    .as_ref()
-                    .map(|item| item.item_type.client_id),
+                items,
+            ));
+        }
+        let item_transforms: Vec<_> = self
+            .item_transforms
+            .lock()
+            .iter()
+            .map(|(slot, transform)| (*slot, Some(*transform)))
+            .collect();
+        if !item_transforms.is_empty() {
+            player.send_message(&NetworkMessageS2C::ModelItemTransform(
+                ClientModelTarget::Block(self.position),
+                item_transforms,
+            ));
+        }
+        for (path, pose) in self.bone_poses.lock().iter() {
+            player.send_message(&NetworkMessageS2C::ModelBonePose(
+                ClientModelTarget::Block(self.position),
+                path.clone(),
+                Some(*pose),
+            ));
+        }
+    }
+    pub fn set_bone_pose(&self, path: Vec<u32>, pose: Option<BonePose>) {
+        {
+            let mut bone_poses = self.bone_poses.lock();
+            match pose {
+                Some(pose) => bone_poses.insert(path.clone(), pose),
+                None => bone_poses.remove(&path),
+            };
+        }
+        self.chunk()
+            .announce_to_viewers(&NetworkMessageS2C::ModelBonePose(
+                ClientModelTarget::Block(self.position),
+                path,
+                pose,
+            ));
+    }
+    //moves the item shown for an inventory slot, fails for slots without a model item slot
+    pub fn set_item_transform(
+        &self,
+        slot: u32,
+        transform: Option<Transformation>,
+    ) -> Result<(), ()> {
+        let model_slot = *self.block.item_model_mapping.mapping.get(&slot).ok_or(())?;
+        {
+            let mut item_transforms = self.item_transforms.lock();
+            match transform {
+                Some(transform) => item_transforms.insert(model_slot, transform),
+                None => item_transforms.remove(&model_slot),
+            };
+        }
+        self.chunk()
+            .announce_to_viewers(&NetworkMessageS2C::ModelItemTransform(
+                ClientModelTarget::Block(self.position),
+                vec![(model_slot, transform)],
             ));
+        Ok(())
+    }
+    //blocks without sided access expose their whole inventory on every face
+    pub fn get_side_slots(&self, side: Face) -> Range<u32> {
+        match &self.block.sides {
+            Some(sides) => sides.get_slots(side),
+            None => 0..self.inventory.get_size(),
+        }
+    }
+    pub fn get_inputs_view_for_side(&self, side: Face) -> InventoryView {
+        self.inventory.get_view(self.get_side_slots(side))
+    }
+    //pushes into and pulls from the neighbors named by the block's transfer settings
+    pub fn tick_transfer(&self) {
+        let transfer = match &self.block.transfer {
+            Some(transfer) => transfer,
+            None => return,
+        };
+        if self.transfer_cooldown.load(Ordering::Relaxed) > 0 {
+            self.transfer_cooldown.fetch_sub(1, Ordering::Relaxed);
+            return;
+        }
+        let world = self.chunk().world.clone();
+        let state = world.server.block_registry.state_by_ref(self.state);
+        let neighbor = |face: Face| match world.get_block(&self.position.offset_by_face(face)) {
+            Some(BlockData::Data(block)) => Some(block),
+            _ => None,
+        };
+        let mut moved = 0;
+        let resolve = |face: &StateFace| face.resolve(&self.block.properties, state.state_id);
+        if let Some(face) = transfer.push.as_ref().and_then(resolve) {
+            if let Some(target) = neighbor(face) {
+                moved += self.get_inputs_view_for_side(face).move_items(
+                    &target.get_inputs_view_for_side(face.opposite()),
+                    transfer.count,
+                );
+            }
+        }
+        if let Some(face) = transfer.pull.as_ref().and_then(resolve) {
+            if let Some(source) = neighbor(face) {
+                moved += source
+                    .get_inputs_view_for_side(face.opposite())
+                    .move_items(&self.get_inputs_view_for_side(face), transfer.count);
+            }
+        }
+        //idle blocks check again next tick instead of waiting out the interval
+        if moved > 0 {
+            self.transfer_cooldown
+                .store(transfer.interval - 1, Ordering::Relaxed);
+        }
+    }
+    pub fn get_energy_storage(&self) -> Option<&EnergyStorage> {
+        self.block
+            .energy
+            .as_ref()
+            .and_then(|energy| energy.get_storage())
+    }
+    pub fn get_energy(&self) -> u32 {
+        self.energy.load(Ordering::Relaxed)
+    }
+    //face is the side the energy comes in from, scripts pass None and skip the side and input limits
+    pub fn insert_energy(&self, face: Option<Face>, amount: u32) -> u32 {
+        let storage = match self.get_energy_storage() {
+            Some(storage) => storage,
+            None => return 0,
+        };
+        let limit = match face {
+            Some(face) if !storage.get_side(face).can_input() => return 0,
+            Some(_) => amount.min(storage.max_input),
+            None => amount,
+        };
+        let mut inserted = 0;
+        let _ = self
+            .energy
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |energy| {
+                inserted = limit.min(storage.capacity.saturating_sub(energy));
+                Some(energy + inserted)
+            });
+        if inserted > 0 {
+            self.inventory.sync_energy();
+        }
+        inserted
+    }
+    pub fn extract_energy(&self, amount: u32) -> u32 {
+        let mut extracted = 0;
+        let _ = self
+            .energy
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |energy| {
+                extracted = amount.min(energy);
+                Some(energy - extracted)
+            });
+        if extracted > 0 {
+            self.inventory.sync_energy();
         }
+        extracted
     }
-    pub fn get_inputs_view_for_side(&self, _side: Face) -> InventoryView {
-        self.inventory.get_full_view()
+    //gives back what extract_energy took but couldn't be used, may briefly exceed capacity so nothing is lost
+    pub fn restore_energy(&self, amount: u32) {
+        if amount > 0 {
+            self.energy.fetch_add(amount, Ordering::Relaxed);
+            self.inventory.sync_energy();
+        }
     }
     pub fn serialize(&self) -> BlockSaveData {
         BlockSaveData {
             inventory: self.inventory.serialize(),
+            user_data: self.user_data.lock().clone(),
+            fluids: self.inventory.get_fluid_tanks().export_content(),
+            energy: self.get_energy(),
         }
     }
     pub fn deserialize(&self, data: BlockSaveData) {
-        self.inventory.deserialize(
-            data.inventory,
-            &self.chunk.upgrade().unwrap().world.server.item_registry,
+        let server = &self.chunk.upgrade().unwrap().world.server;
+        *self.user_data.lock() = data.user_data;
+        self.inventory
+            .deserialize(data.inventory, &server.item_registry);
+        self.inventory.load_fluids(data.fluids, &server.fluids);
+        self.energy.store(
+            self.get_energy_storage()
+                .map_or(0, |storage| data.energy.min(storage.capacity)),
+            Ordering::Relaxed,
         );
+        self.inventory.sync_energy();
     }
     pub fn update_to_clients(&self) {
         self.animation_controller.resync();
-        let chunk = self.chunk.upgrade().unwrap();
-        for (inventory_index, model_index) in &self.block.item_model_mapping.mapping {
-            chunk.announce_to_viewers(&NetworkMessageS2C::ModelItem(
-                ClientModelTarget::Block(self.position),
-                *model_index,
-                self.inventory
-                    .get_full_view()
-                    .get_item(*inventory_index)
-                    .unwrap()
-                    .as_ref()
-                    .map(|item| item.item_type.client_id),
-            ));
+        let items = self.block.item_model_mapping.get_items(&self.inventory);
+        if !items.is_empty() {
+            self.chunk()
+                .announce_to_viewers(&NetworkMessageS2C::ModelItem(
+                    ClientModelTarget::Block(self.position),
+                    items,
+                ));
         }
     }
     pub fn ptr(&self) -> Arc<WorldBlock> {
@@ -2649,22 +6889,128 @@ impl ScriptingObject for WorldBlock {
                 .map(|network| Dynamic::from(network))
                 .unwrap_or(Dynamic::UNIT)
         });*/
+        env.register_member("inventory", |block: &Arc<WorldBlock>| {
+            Some(InventoryWrapper::Block(block.clone()))
+        });
+        env.register_method(
+            "set_item_transform",
+            |block: &Arc<WorldBlock>,
+             slot: &i64,
+             position: &Position,
+             rotation: &Position,
+             scale: &f64| {
+                block
+                    .set_item_transform(
+                        *slot as u32,
+                        Some(Transformation {
+                            position: (*position).into(),
+                            rotation: (*rotation).into(),
+                            scale: Vec3 {
+                                x: *scale as f32,
+                                y: *scale as f32,
+                                z: *scale as f32,
+                            },
+                            origin: Vec3::ZERO,
+                        }),
+                    )
+                    .map_err(|_| {
+                        ScriptError::runtime(
+                            "slot has no item in the block model",
+                            FilePosition::INVALID,
+                        )
+                    })
+            },
+        );
+        env.register_method(
+            "clear_item_transform",
+            |block: &Arc<WorldBlock>, slot: &i64| {
+                block.set_item_transform(*slot as u32, None).map_err(|_| {
+                    ScriptError::runtime(
+                        "slot has no item in the block model",
+                        FilePosition::INVALID,
+                    )
+                })
+            },
+        );
+        env.register_member("energy", |block: &Arc<WorldBlock>| {
+            Some(block.get_energy() as i64)
+        });
+        env.register_member("energy_capacity", |block: &Arc<WorldBlock>| {
+            Some(
+                block
+                    .get_energy_storage()
+                    .map_or(0, |storage| storage.capacity) as i64,
+            )
+        });
+        env.register_method("insert_energy", |block: &Arc<WorldBlock>, amount: &i64| {
+            Ok(block.insert_energy(None, (*amount).clamp(0, u32::MAX as i64) as u32) as i64)
+        });
+        env.register_method("extract_energy", |block: &Arc<WorldBlock>, amount: &i64| {
+            Ok(block.extract_energy((*amount).clamp(0, u32::MAX as i64) as u32) as i64)
+        });
+        env.register_method("side_view", |block: &Arc<WorldBlock>, side: &Face| {
+            Ok(OwnedInventoryView::new(
+                block.get_side_slots(*side),
+                InventoryWrapper::Block(block.clone()),
+            ))
+        });
+        env.register_method(
+            "set_bone_pose",
+            |block: &Arc<WorldBlock>,
+             path: &Array,
+             position: &Position,
+             rotation: &Position,
+             weight: &f64| {
+                block.set_bone_pose(
+                    bone_path_from_array(path)?,
+                    Some(BonePose {
+                        position: (*position).into(),
+                        rotation: (*rotation).into(),
+                        weight: weight.clamp(0., 1.) as f32,
+                    }),
+                );
+                Ok(())
+            },
+        );
+        env.register_method(
+            "clear_bone_pose",
+            |block: &Arc<WorldBlock>, path: &Array| {
+                block.set_bone_pose(bone_path_from_array(path)?, None);
+                Ok(())
+            },
+        );
     }
 }
+//bone paths are child indices from the root bone, the same as the head bone in entity content
+fn bone_path_from_array(path: &Array) -> Result<Vec<u32>, ScriptError> {
+    path.iter()
+        .map(|index| {
+            i64::from_variant(index)
+                .and_then(|index| u32::try_from(*index).ok())
+                .ok_or_else(|| {
+                    ScriptError::runtime(
+                        "bone path must be an array of bone indices",
+                        FilePosition::INVALID,
+                    )
+                })
+        })
+        .collect()
+}
 impl Animatable for WorldBlock {
-    fn send_animation_to_viewers(&self, animation: u32) {
+    type Animation = u32;
+    fn send_animation_to_viewers(&self, animation: &u32) {
         self.chunk
             .upgrade()
             .unwrap()
             .announce_to_viewers(&NetworkMessageS2C::ModelAnimation(
                 ClientModelTarget::Block(self.position),
-                animation,
+                *animation,
             ));
     }
-    fn send_animation_to(&self, viewer: &PlayerData, animation: u32) {
+    fn send_animation_to(&self, viewer: &PlayerData, animation: &u32) {
         viewer.send_message(&NetworkMessageS2C::ModelAnimation(
             ClientModelTarget::Block(self.position),
-            animation,
+            *animation,
         ));
     }
 }
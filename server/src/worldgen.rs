@@ -1,17 +1,27 @@
+use crate::logging::{self, LogTarget};
+use crate::mods::{self, ScriptCallback, ScriptingObject};
 use crate::util::Identifier;
 use crate::{
     registry::{BlockRegistry, BlockStateRef},
-    world::{BlockData, Chunk, Structure},
+    world::{BlockData, Chunk, Structure, StructureTransform, World},
+    Server,
 };
 use array_init::array_init;
-use block_byte_common::BlockPosition;
+use bbscript::eval::{run_with_deadline, ExecutionEnvironment, ScriptError};
+use bbscript::lex::FilePosition;
+use bbscript::variant::{FromVariant, FunctionVariant, IntoVariant, Variant};
+use block_byte_common::content::{ClientBiomeData, ClimateTint};
+use block_byte_common::{BlockPosition, ChunkPosition};
+use immutable_string::ImmutableString;
 use json::JsonValue;
 use moka::sync::Cache;
 use noise::{Fbm, NoiseFn, OpenSimplex, Seedable};
 use parking_lot::Mutex;
+use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 use thread_local::ThreadLocal;
 
 pub struct WorldGeneratorType {
@@ -20,9 +30,10 @@ pub struct WorldGeneratorType {
     temperature: NoiseConfig,
     moisture: NoiseConfig,
     biomes: Vec<Biome>,
+    passes: Vec<GenerationPass>,
 }
 impl WorldGeneratorType {
-    pub fn new(biomes: Vec<Biome>) -> Arc<WorldGeneratorType> {
+    pub fn new(biomes: Vec<Biome>, passes: Vec<GenerationPass>) -> Arc<WorldGeneratorType> {
         Arc::new(Self {
             land: NoiseConfig::new(4561561, 5000., Spline::new(vec![])),
             terrain: NoiseConfig::new(
@@ -41,11 +52,46 @@ impl WorldGeneratorType {
                 Spline::new(vec![SplinePoint::new(-1., 0.), SplinePoint::new(1., 100.)]),
             ),
             biomes,
+            passes,
+        })
+    }
+    pub fn from_json(
+        json: &JsonValue,
+        biomes: Vec<Biome>,
+        environment: &ExecutionEnvironment,
+    ) -> Result<Arc<WorldGeneratorType>, String> {
+        Ok(WorldGeneratorType::new(
+            biomes,
+            json["passes"]
+                .members()
+                .map(|pass| GenerationPass::from_json(pass, environment))
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+}
+
+//script passes run after the built in step of their stage and before its event hook
+pub struct GenerationPass {
+    stage: GenerationStage,
+    function: ScriptCallback,
+    max_operations: u32,
+    max_time: Duration,
+}
+impl GenerationPass {
+    pub fn from_json(json: &JsonValue, environment: &ExecutionEnvironment) -> Result<Self, String> {
+        let stage = json["stage"].as_str().ok_or("pass is missing stage")?;
+        let function = mods::json_to_variant(json["function"].clone(), environment);
+        Ok(GenerationPass {
+            stage: GenerationStage::from_name(stage)
+                .ok_or_else(|| format!("unknown generation stage {}", stage))?,
+            function: ScriptCallback::from_function_variant(
+                FunctionVariant::from_variant(&function)
+                    .ok_or_else(|| format!("pass function {} not found", json["function"]))?,
+            ),
+            max_operations: json["max_operations"].as_u32().unwrap_or(65536),
+            max_time: Duration::from_millis(json["max_time_ms"].as_u64().unwrap_or(50)),
         })
     }
-    /*pub fn from_json(json: JsonValue) -> Arc<WorldGeneratorType> {
-        WorldGeneratorType::new(json["biomes"].members().map(|biome| biome.as_str()))
-    }*/
 }
 
 pub struct WorldGenerator {
@@ -53,21 +99,29 @@ pub struct WorldGenerator {
     generator_type: Arc<WorldGeneratorType>,
     column_cache: ThreadLocal<Cache<(i32, i32), [[(i32, usize); 16]; 16]>>,
     column_cache_common: Mutex<Cache<(i32, i32), [[(i32, usize); 16]; 16]>>,
+    climate_cache: Cache<(i32, i32), Vec<[u8; 2]>>,
     land: NoiseProvider,
     terrain: NoiseProvider,
     temperature: NoiseProvider,
     moisture: NoiseProvider,
+    caves: Fbm<OpenSimplex>,
 }
 impl WorldGenerator {
+    const FEATURE_RADIUS: i32 = 1;
+    const CAVE_SIZE: f64 = 48.;
+    const CAVE_WIDTH: f64 = 0.06;
+    const CAVE_ROOF: i32 = 6;
     pub fn new(seed: u64, generator_type: Arc<WorldGeneratorType>) -> Self {
         Self {
             seed,
             column_cache: ThreadLocal::new(),
             column_cache_common: Mutex::new(Cache::new(2048)),
+            climate_cache: Cache::new(2048),
             land: generator_type.land.instantiate(seed as u32),
             terrain: generator_type.terrain.instantiate(seed as u32),
             temperature: generator_type.temperature.instantiate(seed as u32),
             moisture: generator_type.moisture.instantiate(seed as u32),
+            caves: Fbm::new(seed as u32 ^ 6518913),
             generator_type,
         }
     }
@@ -76,6 +130,12 @@ impl WorldGenerator {
         let z = z as f64;
         self.terrain.get(x, z) as i32
     }
+    //same as the height the chunk holding the column is generated with, cached per chunk
+    pub fn get_column_height(&self, x: i32, z: i32) -> i32 {
+        self.get_columns(x.div_euclid(16), z.div_euclid(16))[x.rem_euclid(16) as usize]
+            [z.rem_euclid(16) as usize]
+            .0
+    }
     pub fn get_biome_at(&self, x: i32, z: i32, height: i32) -> usize {
         let height = height as f64;
         let x = x as f64;
@@ -93,82 +153,450 @@ impl WorldGenerator {
             .unwrap();
         biome.0
     }
-    pub fn generate(&self, chunk: &Arc<Chunk>) -> [[[BlockData; 16]; 16]; 16] {
-        let position = chunk.position;
+    fn get_columns(&self, chunk_x: i32, chunk_z: i32) -> [[(i32, usize); 16]; 16] {
         let cache = self
             .column_cache
             .get_or(|| self.column_cache_common.lock().clone());
-
-        let column_data: [[(i32, usize); 16]; 16] =
-            cache.get_with((position.x, position.z), || {
-                array_init(|x| {
-                    array_init(|z| {
-                        let total_x = (x as i32) + (position.x * 16);
-                        let total_z = (z as i32) + (position.z * 16);
-                        let terrain_height = self.get_terrain_height_at(total_x, total_z);
-                        (
-                            terrain_height,
-                            self.get_biome_at(total_x, total_z, terrain_height),
-                        )
-                    })
+        cache.get_with((chunk_x, chunk_z), || {
+            array_init(|x| {
+                array_init(|z| {
+                    let total_x = (x as i32) + (chunk_x * 16);
+                    let total_z = (z as i32) + (chunk_z * 16);
+                    let terrain_height = self.get_terrain_height_at(total_x, total_z);
+                    (
+                        terrain_height,
+                        self.get_biome_at(total_x, total_z, terrain_height),
+                    )
                 })
-            });
-        let mut structure_rng = rand::rngs::StdRng::seed_from_u64(
-            41516516 * self.seed
-                + (position.x * 41156) as u64
-                + (position.y * 261265) as u64
-                + (position.z * 156415) as u64,
+            })
+        })
+    }
+    pub fn get_biomes(&self, chunk_x: i32, chunk_z: i32) -> Vec<u16> {
+        let columns = self.get_columns(chunk_x, chunk_z);
+        let mut biomes = Vec::with_capacity(256);
+        for x in 0..16 {
+            for z in 0..16 {
+                biomes.push(columns[x][z].1 as u16);
+            }
+        }
+        biomes
+    }
+    //temperature and moisture of every column scaled to a byte, only used by clients to tint blocks
+    pub fn get_climate(&self, chunk_x: i32, chunk_z: i32) -> Vec<[u8; 2]> {
+        self.climate_cache.get_with((chunk_x, chunk_z), || {
+            let to_byte = |value: f64| (value / 100. * 255.).clamp(0., 255.) as u8;
+            let mut climate = Vec::with_capacity(256);
+            for x in 0..16 {
+                for z in 0..16 {
+                    let x = ((chunk_x * 16) + x) as f64;
+                    let z = ((chunk_z * 16) + z) as f64;
+                    climate.push([
+                        to_byte(self.temperature.get(x, z)),
+                        to_byte(self.moisture.get(x, z)),
+                    ]);
+                }
+            }
+            climate
+        })
+    }
+    fn column_rng(&self, x: i32, z: i32) -> StdRng {
+        StdRng::seed_from_u64(
+            self.seed.wrapping_mul(41516516)
+                ^ (x as u64).wrapping_mul(41156)
+                ^ (z as u64).wrapping_mul(156415),
+        )
+    }
+    pub fn generate(&self, chunk: &Arc<Chunk>) -> [[[BlockData; 16]; 16]; 16] {
+        let position = chunk.position;
+        let mut proto_chunk = ProtoChunk::new(
+            position,
+            self.get_columns(position.x, position.z),
+            &chunk.world,
         );
-        array_init(|x| {
-            array_init(|i| {
+        for stage in GenerationStage::all() {
+            match stage {
+                GenerationStage::Terrain => self.shape_terrain(&mut proto_chunk),
+                GenerationStage::Surface => self.build_surface(&mut proto_chunk),
+                GenerationStage::Carvers => self.carve_caves(&mut proto_chunk),
+                GenerationStage::Features => self.place_features(&mut proto_chunk),
+            }
+            proto_chunk = self.run_passes(stage, &chunk.world, proto_chunk);
+            proto_chunk = stage.call_hook(&chunk.world, proto_chunk);
+        }
+        let blocks = array_init(|x| {
+            array_init(|y| {
                 array_init(|z| {
+                    proto_chunk.blocks[x][y][z].create_block_data(
+                        chunk,
+                        BlockPosition {
+                            x: (position.x * 16) + x as i32,
+                            y: (position.y * 16) + y as i32,
+                            z: (position.z * 16) + z as i32,
+                        },
+                    )
+                })
+            })
+        });
+        for (structure, origin, transform) in &proto_chunk.structures {
+            structure.place_generated_data(chunk, &blocks, *origin, *transform);
+        }
+        blocks
+    }
+    fn run_passes(
+        &self,
+        stage: GenerationStage,
+        world: &Arc<World>,
+        proto_chunk: ProtoChunk,
+    ) -> ProtoChunk {
+        let mut passes = self
+            .generator_type
+            .passes
+            .iter()
+            .filter(|pass| pass.stage == stage)
+            .peekable();
+        if passes.peek().is_none() {
+            return proto_chunk;
+        }
+        let position = proto_chunk.position;
+        let proto_chunk = Arc::new(Mutex::new(proto_chunk));
+        for pass in passes {
+            proto_chunk.lock().budget = Some(pass.max_operations);
+            //blocks set before an error or running out of budget are kept
+            if let Err(error) = run_with_deadline(Instant::now() + pass.max_time, || {
+                pass.function.call_function(
+                    &world.server.script_environment,
+                    None,
+                    vec![
+                        proto_chunk.clone().into_variant(),
+                        world.clone().into_variant(),
+                        (self.seed as i64).into_variant(),
+                        mods::json_data_to_variant(world.get_generator_options()),
+                    ],
+                )
+            }) {
+                logging::warn(
+                    LogTarget::Worldgen,
+                    format!(
+                        "{:?} pass failed in chunk {:?}: {:?}",
+                        stage, position, error
+                    ),
+                );
+            }
+            proto_chunk.lock().budget = None;
+        }
+        match Arc::try_unwrap(proto_chunk) {
+            Ok(proto_chunk) => proto_chunk.into_inner(),
+            Err(proto_chunk) => proto_chunk.lock().clone(),
+        }
+    }
+    fn shape_terrain(&self, proto_chunk: &mut ProtoChunk) {
+        for x in 0..16 {
+            for z in 0..16 {
+                let (height, biome) = proto_chunk.columns[x][z];
+                let biome = self.generator_type.biomes.get(biome).unwrap();
+                for i in 0..16 {
+                    let y = i as i32 + proto_chunk.position.y * 16;
+                    proto_chunk.blocks[x][i][z] = if y <= height {
+                        biome.bottom_block
+                    } else if y <= 0 {
+                        biome.water_block
+                    } else {
+                        BlockStateRef::AIR
+                    };
+                }
+            }
+        }
+    }
+    fn build_surface(&self, proto_chunk: &mut ProtoChunk) {
+        for x in 0..16 {
+            for z in 0..16 {
+                let (height, biome) = proto_chunk.columns[x][z];
+                let biome = self.generator_type.biomes.get(biome).unwrap();
+                for i in 0..16 {
+                    let y = i as i32 + proto_chunk.position.y * 16;
+                    if y == height {
+                        proto_chunk.blocks[x][i][z] = biome.top_block;
+                    } else if y < height && y >= height - 4 {
+                        proto_chunk.blocks[x][i][z] = biome.middle_block;
+                    }
+                }
+            }
+        }
+    }
+    //tunnels follow where the noise crosses zero, so they wind through the ground instead of forming blobs
+    fn carve_caves(&self, proto_chunk: &mut ProtoChunk) {
+        let position = proto_chunk.position;
+        for x in 0..16 {
+            for z in 0..16 {
+                let (height, _) = proto_chunk.columns[x][z];
+                let total_x = ((position.x * 16) + x as i32) as f64;
+                let total_z = ((position.z * 16) + z as i32) as f64;
+                for i in 0..16 {
                     let y = i as i32 + position.y * 16;
-                    let (height, biome) = column_data[x][z];
-                    let biome = self.generator_type.biomes.get(biome).unwrap();
-                    if i == 0 {
-                        for (chance, structure) in biome.get_structures() {
-                            if height / 16 == position.y && structure_rng.gen_bool(*chance) {
-                                chunk.world.place_structure(
-                                    BlockPosition {
-                                        x: (x as i32) + (position.x * 16),
-                                        y: height + 1,
-                                        z: (z as i32) + (position.z * 16),
+                    //the surface stays closed, so caves don't open up below water or features
+                    if y > height - Self::CAVE_ROOF {
+                        continue;
+                    }
+                    let noise = self.caves.get([
+                        total_x / Self::CAVE_SIZE,
+                        y as f64 / (Self::CAVE_SIZE / 2.),
+                        total_z / Self::CAVE_SIZE,
+                    ]);
+                    if noise.abs() < Self::CAVE_WIDTH {
+                        proto_chunk.blocks[x][i][z] = BlockStateRef::AIR;
+                    }
+                }
+            }
+        }
+    }
+    fn place_features(&self, proto_chunk: &mut ProtoChunk) {
+        let position = proto_chunk.position;
+        for chunk_x in (position.x - Self::FEATURE_RADIUS)..=(position.x + Self::FEATURE_RADIUS) {
+            for chunk_z in (position.z - Self::FEATURE_RADIUS)..=(position.z + Self::FEATURE_RADIUS)
+            {
+                let columns = self.get_columns(chunk_x, chunk_z);
+                for x in 0..16 {
+                    for z in 0..16 {
+                        let (height, biome) = columns[x][z];
+                        let biome = self.generator_type.biomes.get(biome).unwrap();
+                        let origin = BlockPosition {
+                            x: (chunk_x * 16) + x as i32,
+                            y: height + 1,
+                            z: (chunk_z * 16) + z as i32,
+                        };
+                        let mut rng = self.column_rng(origin.x, origin.z);
+                        for (chance, structure, random_rotation) in biome.get_structures() {
+                            if rng.gen_bool(*chance) {
+                                let mut structure_rng = StdRng::seed_from_u64(rng.gen());
+                                let transform = if *random_rotation {
+                                    StructureTransform::random(&mut structure_rng)
+                                } else {
+                                    StructureTransform::IDENTITY
+                                };
+                                structure.place_with_rng(
+                                    |block_position, block| {
+                                        proto_chunk.set_block(block_position, block);
                                     },
-                                    structure,
-                                    false,
+                                    origin,
+                                    transform,
+                                    &mut structure_rng,
                                 );
+                                if structure.has_data() {
+                                    proto_chunk.structures.push((
+                                        structure.clone(),
+                                        origin,
+                                        transform,
+                                    ));
+                                }
                             }
                         }
                     }
-                    let block_position = BlockPosition {
-                        x: (position.x * 16) + x as i32,
-                        y,
-                        z: (position.z * 16) + z as i32,
-                    };
-                    if y > height {
-                        if y > 0 {
-                            BlockData::Simple(0)
-                        } else {
-                            biome.water_block.create_block_data(chunk, block_position)
-                        }
-                    } else if y == height {
-                        biome.top_block.create_block_data(chunk, block_position)
-                    } else if y >= height - 4 {
-                        biome.middle_block.create_block_data(chunk, block_position)
-                    } else {
-                        biome.bottom_block.create_block_data(chunk, block_position)
-                    }
+                }
+            }
+        }
+    }
+}
 
-                    /*BlockData::Simple(if y <= heights[x][z] {
-                        1
-                    } else if y <= 0 {
-                        2
-                    } else {
-                        0
-                    })*/
-                })
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GenerationStage {
+    Terrain,
+    Surface,
+    Carvers,
+    Features,
+}
+impl GenerationStage {
+    pub fn all() -> [GenerationStage; 4] {
+        [
+            GenerationStage::Terrain,
+            GenerationStage::Surface,
+            GenerationStage::Carvers,
+            GenerationStage::Features,
+        ]
+    }
+    pub fn from_name(name: &str) -> Option<GenerationStage> {
+        match name {
+            "terrain" => Some(GenerationStage::Terrain),
+            "surface" => Some(GenerationStage::Surface),
+            "carvers" => Some(GenerationStage::Carvers),
+            "features" => Some(GenerationStage::Features),
+            _ => None,
+        }
+    }
+    pub fn get_event(&self) -> Identifier {
+        Identifier::new(
+            "bb",
+            match self {
+                GenerationStage::Terrain => "worldgen_terrain",
+                GenerationStage::Surface => "worldgen_surface",
+                GenerationStage::Carvers => "worldgen_carvers",
+                GenerationStage::Features => "worldgen_features",
+            },
+        )
+    }
+    fn call_hook(&self, world: &Arc<World>, proto_chunk: ProtoChunk) -> ProtoChunk {
+        let event = self.get_event();
+        if !world.server.get_events().has_handlers(&event) {
+            return proto_chunk;
+        }
+        let proto_chunk = Arc::new(Mutex::new(proto_chunk));
+        let mut event_data: HashMap<ImmutableString, Variant> = HashMap::new();
+        event_data.insert("chunk".into(), proto_chunk.clone().into_variant());
+        event_data.insert("world".into(), world.clone().into_variant());
+        world
+            .server
+            .call_event(event, Arc::new(Mutex::new(event_data)).into_variant());
+        match Arc::try_unwrap(proto_chunk) {
+            Ok(proto_chunk) => proto_chunk.into_inner(),
+            Err(proto_chunk) => proto_chunk.lock().clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ProtoChunk {
+    pub position: ChunkPosition,
+    blocks: [[[BlockStateRef; 16]; 16]; 16],
+    columns: [[(i32, usize); 16]; 16],
+    //block accesses the running pass has left
+    budget: Option<u32>,
+    world: Weak<World>,
+    //placed structures whose block data and entities are applied once the chunk is built
+    structures: Vec<(Arc<Structure>, BlockPosition, StructureTransform)>,
+}
+impl ProtoChunk {
+    pub fn new(
+        position: ChunkPosition,
+        columns: [[(i32, usize); 16]; 16],
+        world: &Arc<World>,
+    ) -> Self {
+        ProtoChunk {
+            position,
+            blocks: [[[BlockStateRef::AIR; 16]; 16]; 16],
+            columns,
+            budget: None,
+            world: Arc::downgrade(world),
+            structures: Vec::new(),
+        }
+    }
+    fn spend(&mut self) -> Result<(), ScriptError> {
+        if let Some(budget) = &mut self.budget {
+            if *budget == 0 {
+                return Err(ScriptError::runtime(
+                    "worldgen pass ran out of budget",
+                    FilePosition::INVALID,
+                ));
+            }
+            *budget -= 1;
+        }
+        Ok(())
+    }
+    fn to_local_offset(x: i64, y: i64, z: i64) -> Option<(usize, usize, usize)> {
+        if (0..16).contains(&x) && (0..16).contains(&y) && (0..16).contains(&z) {
+            Some((x as usize, y as usize, z as usize))
+        } else {
+            None
+        }
+    }
+    fn to_offset(&self, position: BlockPosition) -> Option<(usize, usize, usize)> {
+        if position.to_chunk_pos() != self.position {
+            return None;
+        }
+        let offset = position.chunk_offset();
+        Some((offset.0 as usize, offset.1 as usize, offset.2 as usize))
+    }
+    pub fn get_block(&self, position: BlockPosition) -> Option<BlockStateRef> {
+        self.to_offset(position)
+            .map(|offset| self.blocks[offset.0][offset.1][offset.2])
+    }
+    pub fn set_block(&mut self, position: BlockPosition, block: BlockStateRef) -> bool {
+        match self.to_offset(position) {
+            Some(offset) => {
+                self.blocks[offset.0][offset.1][offset.2] = block;
+                true
+            }
+            None => false,
+        }
+    }
+    pub fn get_height(&self, x: i32, z: i32) -> Option<i32> {
+        if x.div_euclid(16) != self.position.x || z.div_euclid(16) != self.position.z {
+            return self
+                .world
+                .upgrade()
+                .map(|world| world.get_terrain_height(x, z));
+        }
+        Some(self.columns[x.rem_euclid(16) as usize][z.rem_euclid(16) as usize].0)
+    }
+}
+impl ScriptingObject for ProtoChunk {
+    fn engine_register_server(env: &mut ExecutionEnvironment, _server: &Weak<Server>) {
+        env.register_custom_name::<Arc<Mutex<ProtoChunk>>, _>("ProtoChunk");
+        env.register_member("origin", |proto_chunk: &Arc<Mutex<ProtoChunk>>| {
+            let position = proto_chunk.lock().position;
+            Some(BlockPosition {
+                x: position.x * 16,
+                y: position.y * 16,
+                z: position.z * 16,
             })
-        })
+        });
+        env.register_method(
+            "get_block",
+            |proto_chunk: &Arc<Mutex<ProtoChunk>>, position: &BlockPosition| {
+                let mut proto_chunk = proto_chunk.lock();
+                proto_chunk.spend()?;
+                Ok(Variant::from_option(proto_chunk.get_block(*position)))
+            },
+        );
+        env.register_method(
+            "set_block",
+            |proto_chunk: &Arc<Mutex<ProtoChunk>>,
+             position: &BlockPosition,
+             block: &BlockStateRef| {
+                let mut proto_chunk = proto_chunk.lock();
+                proto_chunk.spend()?;
+                Ok(proto_chunk.set_block(*position, *block))
+            },
+        );
+        //chunk relative coordinates from 0 to 15, without building a BlockPosition for every call
+        env.register_method(
+            "get_local",
+            |proto_chunk: &Arc<Mutex<ProtoChunk>>, x: &i64, y: &i64, z: &i64| {
+                let mut proto_chunk = proto_chunk.lock();
+                proto_chunk.spend()?;
+                Ok(Variant::from_option(
+                    ProtoChunk::to_local_offset(*x, *y, *z)
+                        .map(|offset| proto_chunk.blocks[offset.0][offset.1][offset.2]),
+                ))
+            },
+        );
+        env.register_method(
+            "set_local",
+            |proto_chunk: &Arc<Mutex<ProtoChunk>>,
+             x: &i64,
+             y: &i64,
+             z: &i64,
+             block: &BlockStateRef| {
+                let mut proto_chunk = proto_chunk.lock();
+                proto_chunk.spend()?;
+                Ok(match ProtoChunk::to_local_offset(*x, *y, *z) {
+                    Some(offset) => {
+                        proto_chunk.blocks[offset.0][offset.1][offset.2] = *block;
+                        true
+                    }
+                    None => false,
+                })
+            },
+        );
+        env.register_method(
+            "get_height",
+            |proto_chunk: &Arc<Mutex<ProtoChunk>>, x: &i64, z: &i64| {
+                Ok(Variant::from_option(
+                    proto_chunk
+                        .lock()
+                        .get_height(*x as i32, *z as i32)
+                        .map(|height| height as i64),
+                ))
+            },
+        );
     }
 }
 struct NoiseConfig {
@@ -213,7 +641,8 @@ pub struct Biome {
     height_spline: Spline,
     temperature_noise_spline: Spline,
     moisture_noise_spline: Spline,
-    structures: Vec<(f64, Arc<Structure>)>,
+    structures: Vec<(f64, Arc<Structure>, bool)>,
+    client_data: ClientBiomeData,
 }
 impl Biome {
     pub fn from_json(
@@ -247,14 +676,46 @@ impl Biome {
                             .get(&Identifier::parse(structure["id"].as_str().unwrap()).unwrap())
                             .unwrap()
                             .clone(),
+                        structure["random_rotation"].as_bool().unwrap_or(false),
                     )
                 })
                 .collect(),
+            client_data: {
+                let client = &json["client"];
+                let mut client_data = ClientBiomeData::default();
+                if let Some(color) = parse_color(&client["sky_color"]) {
+                    client_data.sky_color = color;
+                }
+                if let Some(color) = parse_color(&client["fog_color"]) {
+                    client_data.fog_color = color;
+                }
+                if let Some(density) = client["fog_density"].as_f32() {
+                    client_data.fog_density = density;
+                }
+                if let Some(color) = parse_color(&client["foliage_tint"]) {
+                    client_data.foliage_tint = color;
+                }
+                if let Some(color) = parse_color(&client["water_tint"]) {
+                    client_data.water_tint = color;
+                }
+                let climate_tint = &client["climate_tint"];
+                if let (Some(cold), Some(dry), Some(wet)) = (
+                    parse_color(&climate_tint["cold"]),
+                    parse_color(&climate_tint["dry"]),
+                    parse_color(&climate_tint["wet"]),
+                ) {
+                    client_data.climate_tint = Some(ClimateTint { cold, dry, wet });
+                }
+                client_data
+            },
         }
     }
-    pub fn get_structures(&self) -> &Vec<(f64, Arc<Structure>)> {
+    pub fn get_structures(&self) -> &Vec<(f64, Arc<Structure>, bool)> {
         &self.structures
     }
+    pub fn get_client_data(&self) -> &ClientBiomeData {
+        &self.client_data
+    }
     pub fn get_fitness(&self, land: f64, height: f64, temperature: f64, moisture: f64) -> f64 {
         let fitness = self.land_noise_spline.sample(land).unwrap_or(1.)
             * self.height_spline.sample(height).unwrap_or(1.)
@@ -266,6 +727,12 @@ impl Biome {
         fitness
     }
 }
+fn parse_color(json: &JsonValue) -> Option<[f32; 3]> {
+    if json.len() != 3 {
+        return None;
+    }
+    Some([json[0].as_f32()?, json[1].as_f32()?, json[2].as_f32()?])
+}
 #[derive(Copy, Clone)]
 pub struct SplinePoint {
     key: f64,